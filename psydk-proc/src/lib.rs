@@ -29,7 +29,7 @@ pub fn derive_answer_fn(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
         .map(|f| {
             // if the field is an Option, we get the type inside the Option
             let path = extract_path(f);
-            if path.segments[0].ident.to_string() == "Option" {
+            if path.segments[0].ident == "Option" {
                 let t = if let syn::PathArguments::AngleBracketed(args) = &path.segments[0].arguments {
                     let arg = args.args.iter().next().unwrap();
                     if let syn::GenericArgument::Type(t) = arg {
@@ -62,7 +62,7 @@ pub fn derive_answer_fn(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
         .map(|f| {
             // if the field is an Option, we get the type inside the Option
             let path = extract_path(f);
-            if path.segments[0].ident.to_string() == "Option" {
+            if path.segments[0].ident == "Option" {
                 let t = if let syn::PathArguments::AngleBracketed(args) = &path.segments[0].arguments {
                     let arg = args.args.iter().next().unwrap();
                     if let syn::GenericArgument::Type(t) = arg {
@@ -94,8 +94,38 @@ pub fn derive_answer_fn(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
         })
         .collect::<Vec<_>>();
 
+    // the name of the StimulusParamValue variant of each field (i.e., T for Option<T> and T)
+    let type_names = field_types
+        .iter()
+        .map(|f| {
+            let path = extract_path(f);
+            if path.segments[0].ident == "Option" {
+                match &path.segments[0].arguments {
+                    syn::PathArguments::AngleBracketed(args) => match args.args.iter().next().unwrap() {
+                        syn::GenericArgument::Type(t) => extract_path(t).segments[0].ident.to_string(),
+                        _ => panic!("Only Option<T> is supported"),
+                    },
+                    _ => panic!("Only Option<T> is supported"),
+                }
+            } else {
+                path.segments[0].ident.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+
     let expanded = quote! {
          impl StimulusParams for #input {
+            fn param_names(&self) -> &'static [&'static str] {
+                &[#(stringify!(#field_names)),*]
+            }
+
+            fn param_type(&self, name: &str) -> Option<&'static str> {
+                match name {
+                    #(stringify!(#field_names) => Some(#type_names),)*
+                    _ => None,
+                }
+            }
+
             fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
                 match name {
                     #(stringify!(#field_names) => {
@@ -172,6 +202,7 @@ pub fn derive_call_fn(input: TokenStream) -> TokenStream {
 
 // a derive macro that implements FromPyObject for a simple enum (from a snake_case string)
 // the enum must also implement strum::EnumString
+// the impl is only compiled with the `python` feature of the crate that uses the derive
 #[proc_macro_derive(FromPyStr)]
 pub fn derive_from_py_str(input: TokenStream) -> TokenStream {
     // Parse the input (the enum on which we're deriving) into a syntax tree
@@ -184,6 +215,7 @@ pub fn derive_from_py_str(input: TokenStream) -> TokenStream {
 
 
 
+        #[cfg(feature = "python")]
         impl<'py> FromPyObject<'py> for #enum_ident {
             fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
                 use std::str::FromStr;
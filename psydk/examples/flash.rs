@@ -0,0 +1,66 @@
+//! A minimal experiment written in Rust: a white disc flashes for 10 frames, once per second, until
+//! escape is pressed.
+//!
+//! Run with `cargo run --example flash --no-default-features --features skia,software` to build
+//! without Python, or with `cargo run --example flash`.
+
+use psydk::{
+    app::App,
+    errors::PsydkResult,
+    visual::{
+        color::LinRgba,
+        geometry::{Shape, Size, Transformation2D},
+        stimuli::{
+            shape::{GradientType, ShapeParams, ShapeStimulus},
+            DynamicStimulus, StrokeStyle,
+        },
+    },
+};
+
+fn main() -> PsydkResult<()> {
+    let mut app = App::new()?;
+
+    app.run_experiment(|ctx| {
+        let window = ctx.create_default_window(true, None, None, false)?;
+
+        let disc = ShapeStimulus::new(
+            ShapeParams {
+                shape: Shape::Circle {
+                    x: Size::Pixels(0.0),
+                    y: Size::Pixels(0.0),
+                    radius: Size::Pixels(100.0),
+                },
+                x: Size::Pixels(0.0),
+                y: Size::Pixels(0.0),
+                fill_color: LinRgba::new(1.0, 1.0, 1.0, 1.0),
+                gradient_angle: 0.0,
+                stroke_style: StrokeStyle::None,
+                stroke_color: LinRgba::default(),
+                stroke_width: Size::Pixels(0.0),
+                alpha: None,
+            },
+            None,
+            GradientType::Linear,
+            None,
+            None,
+            Transformation2D::Identity(),
+        )?;
+        let disc = DynamicStimulus::new(disc);
+
+        let mut receiver = window.create_event_receiver();
+        loop {
+            let mut frame = window.get_frame()?;
+            frame.add(&disc);
+            window.present(&mut frame, Some(10), None, false, None)?;
+
+            let mut blank = window.get_frame()?;
+            window.present(&mut blank, None, Some(1.0), false, Some(false))?;
+
+            if receiver.poll().key_pressed("Escape") {
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
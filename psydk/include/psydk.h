@@ -0,0 +1,59 @@
+/*
+ * C interface to the psydk presentation engine. Build psydk with the `capi` feature to export these
+ * functions from the shared library. Python is not needed: leave out the default `python` feature,
+ * e.g. `cargo build -p psydk --no-default-features --features capi,skia,software`.
+ *
+ * `psydk_run` runs the event loop on the calling thread (which must be the main thread on macOS)
+ * and calls the experiment function on a separate thread. All other functions must be called from
+ * the experiment function. Functions returning `int32_t` return 0 on success and -1 on failure;
+ * functions returning pointers return NULL on failure. `psydk_last_error` returns the message of
+ * the last failure on the calling thread. Timestamps are seconds since the UNIX epoch.
+ */
+
+#ifndef PSYDK_H
+#define PSYDK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct PsydkContext PsydkContext;
+typedef struct PsydkWindow PsydkWindow;
+typedef struct PsydkStimulus PsydkStimulus;
+
+/* The experiment function, returns 0 on success. */
+typedef int32_t (*PsydkExperimentFn)(const PsydkContext *context, void *user_data);
+
+const char *psydk_last_error(void);
+double psydk_now(void);
+
+int32_t psydk_run(PsydkExperimentFn experiment, void *user_data);
+
+/* Fullscreen window on the given monitor (0 is the first monitor). */
+PsydkWindow *psydk_create_window(const PsydkContext *context, uint32_t monitor);
+void psydk_window_free(PsydkWindow *window);
+double psydk_refresh_rate(const PsydkWindow *window);
+
+/* Positions and sizes are in pixels relative to the center of the window, colors are linear RGBA
+ * in [0, 1]. */
+PsydkStimulus *psydk_circle(float x, float y, float radius, float r, float g, float b, float a);
+PsydkStimulus *psydk_rectangle(float x, float y, float width, float height, float r, float g, float b, float a);
+void psydk_stimulus_free(PsydkStimulus *stimulus);
+
+/* Present the stimuli for `n_frames` refresh intervals, writing the onset of the first frame to
+ * `onset` (if not NULL). */
+int32_t psydk_present(PsydkWindow *window, const PsydkStimulus *const *stimuli, size_t n_stimuli, uint32_t n_frames,
+                      double *onset);
+
+/* Returns 1 and the name and timestamp of the oldest unpolled key press, or 0 if there is none. */
+int32_t psydk_poll_key(PsydkWindow *window, char *key, size_t key_len, double *timestamp);
+int32_t psydk_flush_keys(PsydkWindow *window);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PSYDK_H */
@@ -5,6 +5,9 @@ from .psydk import __all__
 # optional: include the documentation from the Rust module
 from .psydk import __doc__  # noqa: F401
 
+# type stubs for IDEs and type checkers, see `psydk._stubs`
+from ._stubs import generate_stubs  # noqa: F401
+
 # set gstreamer plugin environment variable to site-packages/psydk/.dylibs/
 import platform
 
@@ -0,0 +1,193 @@
+"""Generate type stubs (.pyi) for the Rust extension.
+
+The stubs are derived from the compiled extension at runtime: signatures come from the
+`__text_signature__` that pyo3 generates from the `#[pyo3(signature = ...)]` attributes, and
+docstrings from the Rust doc comments. pyo3 does not record types, so parameters and return values
+are left unannotated.
+
+Run `python -m psydk._stubs` (or `pixi run stubs`) after building the extension to update the stubs
+in the package directory, so that they are included in the wheel.
+"""
+
+import inspect
+import os
+import sys
+import types
+
+# attributes that are provided by `object` and don't need to be repeated in the stubs
+_OBJECT_ATTRIBUTES = (set(dir(object)) | {"__dict__", "__weakref__", "__module__"}) - {
+    "__init__",
+    "__repr__",
+    "__str__",
+    "__eq__",
+    "__hash__",
+}
+
+_ROUTINE_TYPES = (
+    types.BuiltinFunctionType,
+    types.BuiltinMethodType,
+    types.MethodDescriptorType,
+    types.ClassMethodDescriptorType,
+    types.WrapperDescriptorType,
+    types.FunctionType,
+)
+
+
+def _docstring(obj, indent):
+    doc = inspect.getdoc(obj)
+    if not doc:
+        return []
+    doc = doc.replace("\\", "\\\\").replace('"""', '\\"\\"\\"')
+    lines = doc.splitlines()
+    if len(lines) == 1:
+        return [f'{indent}"""{lines[0]}"""']
+    return [f'{indent}"""{lines[0]}'] + [f"{indent}{line}".rstrip() for line in lines[1:]] + [f'{indent}"""']
+
+
+def _signature(obj, receiver):
+    """The parameters of `obj`, with all defaults replaced by `...` as is customary in stubs.
+    `receiver` is the name of the first parameter of methods (`self` or `cls`)."""
+    try:
+        signature = inspect.signature(obj)
+    except (ValueError, TypeError):
+        return f"({receiver}, *args, **kwargs)" if receiver else "(*args, **kwargs)"
+
+    parameters = list(signature.parameters.values())
+    if receiver and (not parameters or parameters[0].name != receiver):
+        parameters.insert(0, inspect.Parameter(receiver, inspect.Parameter.POSITIONAL_ONLY))
+
+    return _format(parameters)
+
+
+def _format(parameters):
+    parameters = [p.replace(default=...) if p.default is not p.empty else p for p in parameters]
+    return str(inspect.Signature(parameters)).replace("=Ellipsis", "=...")
+
+
+def _function(name, obj, indent, receiver=None, decorator=None):
+    lines = [f"{indent}@{decorator}"] if decorator else []
+    doc = _docstring(obj, indent + "    ")
+    lines.append(f"{indent}def {name}{_signature(obj, receiver)}:" + ("" if doc else " ..."))
+    return lines + doc
+
+
+def _class(name, cls):
+    bases = [base.__name__ for base in cls.__bases__ if base is not object]
+    lines = [f"class {name}" + (f"({', '.join(bases)})" if bases else "") + ":"]
+    body = _docstring(cls, "    ")
+
+    # the constructor (`#[new]`) has its signature on the class itself
+    if getattr(cls, "__text_signature__", None):
+        try:
+            parameters = list(inspect.signature(cls).parameters.values())
+            init = _format([inspect.Parameter("self", inspect.Parameter.POSITIONAL_ONLY)] + parameters)
+            body.append(f"    def __init__{init} -> None: ...")
+        except (ValueError, TypeError):
+            pass
+
+    for attr, value in cls.__dict__.items():
+        if attr in _OBJECT_ATTRIBUTES or (attr.startswith("_") and not attr.startswith("__")):
+            continue
+        if isinstance(value, staticmethod):
+            body += _function(attr, value.__func__, "    ", decorator="staticmethod")
+        elif isinstance(value, (classmethod, types.ClassMethodDescriptorType)):
+            body += _function(attr, getattr(cls, attr), "    ", receiver="cls", decorator="classmethod")
+        elif isinstance(value, _ROUTINE_TYPES):
+            body += _function(attr, value, "    ", receiver="self")
+        elif isinstance(value, (types.GetSetDescriptorType, types.MemberDescriptorType, property)):
+            body.append("    @property")
+            doc = _docstring(value, "        ")
+            body.append(f"    def {attr}(self):" + ("" if doc else " ..."))
+            body += doc
+        elif isinstance(value, cls):
+            # variants of enums
+            body.append(f"    {attr}: {name}")
+
+    return lines + (body or ["    ..."])
+
+
+def _module_stub(module, submodules):
+    lines = _docstring(module, "")
+    lines.append("")
+    for name in submodules:
+        lines.append(f"from . import {name} as {name}")
+    if submodules:
+        lines.append("")
+
+    for name, value in sorted(vars(module).items()):
+        if name.startswith("_") or isinstance(value, types.ModuleType):
+            continue
+        if isinstance(value, type):
+            lines += _class(name, value)
+        elif callable(value):
+            lines += _function(name, value, "")
+        else:
+            lines.append(f"{name}: {type(value).__name__}")
+        lines.append("")
+
+    return "\n".join(lines).strip() + "\n"
+
+
+def _write(path, content):
+    os.makedirs(os.path.dirname(path), exist_ok=True)
+    with open(path, "w", encoding="utf-8") as f:
+        f.write(content)
+
+
+def _submodules(module, qualname):
+    return [
+        name
+        for name, value in sorted(vars(module).items())
+        if isinstance(value, types.ModuleType) and sys.modules.get(f"{qualname}.{name}") is value
+    ]
+
+
+def _generate(module, qualname, directory, filename, written):
+    submodules = _submodules(module, qualname)
+    path = os.path.join(directory, filename)
+    _write(path, _module_stub(module, submodules))
+    written.append(path)
+
+    for name in submodules:
+        submodule = getattr(module, name)
+        if _submodules(submodule, f"{qualname}.{name}"):
+            _generate(submodule, f"{qualname}.{name}", os.path.join(directory, name), "__init__.pyi", written)
+        else:
+            _generate(submodule, f"{qualname}.{name}", directory, f"{name}.pyi", written)
+
+
+def generate_stubs(directory=None):
+    """Generate `.pyi` stubs for all classes and functions of psydk.
+
+    Parameters
+    ----------
+    directory : str, optional
+        The directory to write the stubs to. Defaults to the directory of the installed package.
+
+    Returns
+    -------
+    list[str]
+        The paths of the files that were written.
+    """
+    from . import psydk as extension
+
+    directory = directory or os.path.dirname(os.path.abspath(__file__))
+    written = []
+
+    # the submodules are registered as `psydk.<name>`, i.e. as siblings of the extension module
+    _generate(extension, "psydk", directory, "psydk.pyi", written)
+
+    init = os.path.join(directory, "__init__.pyi")
+    _write(init, "from .psydk import *\nfrom ._stubs import generate_stubs as generate_stubs\n")
+    written.append(init)
+
+    marker = os.path.join(directory, "py.typed")
+    _write(marker, "")
+    written.append(marker)
+
+    return written
+
+
+if __name__ == "__main__":
+    for path in generate_stubs(sys.argv[1] if len(sys.argv) > 1 else None):
+        print(path)
@@ -1,6 +1,8 @@
 use std::{
+    cell::RefCell,
     collections::HashMap,
     sync::{
+        atomic::{AtomicU64, Ordering},
         mpsc::{Receiver, Sender},
         Arc, Mutex,
     },
@@ -8,6 +10,7 @@ use std::{
 };
 
 use derive_debug::Dbg;
+#[cfg(feature = "python")]
 use pyo3::{
     pyclass, pyfunction,
     types::{PyDict, PyTuple},
@@ -17,16 +20,17 @@ use renderer::{cosmic_text, renderer::SharedRendererState, wgpu::TextureFormat};
 use wgpu::MemoryHints;
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    error::EventLoopError,
+    event::{DeviceEvent, DeviceId, ElementState, Ime, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     monitor::MonitorHandle,
-    window::{Window as WinitWindow, WindowId},
+    window::{Window as WinitWindow, WindowId, WindowLevel},
 };
 
 use crate::{
-    config::ExperimentConfig,
+    config::{ExperimentConfig, GraphicsBackend, RendererBackend},
     context::{EventLoopAction, ExperimentContext, GammaOptions, Monitor, WindowOptions},
-    errors,
+    errors::{self, PsydkError, PsydkResult},
     input::Event,
     visual::{
         color::LinRgba,
@@ -37,12 +41,66 @@ use crate::{
 
 pub type ArcMutex<T> = Arc<Mutex<T>>;
 
+/// Functions that put hardware into a safe state (e.g., switch relays off). They run at the end of
+/// every run and before the process exits when a window is closed or Escape is pressed.
+static SHUTDOWN_HOOKS: Mutex<Vec<(u64, Arc<dyn Fn() + Send + Sync>)>> = Mutex::new(Vec::new());
+
+/// A registered shutdown hook. The hook is removed when this is dropped.
+#[derive(Debug)]
+pub struct ShutdownHook(u64);
+
+impl Drop for ShutdownHook {
+    fn drop(&mut self) {
+        let mut hooks = SHUTDOWN_HOOKS.lock().unwrap_or_else(|e| e.into_inner());
+        hooks.retain(|(id, _)| *id != self.0);
+    }
+}
+
+/// Register a function that runs at the end of every run and before the process exits, until
+/// the returned handle is dropped. It must be safe to call more than once.
+#[must_use = "the hook is removed when the handle is dropped"]
+pub fn register_shutdown_hook(hook: impl Fn() + Send + Sync + 'static) -> ShutdownHook {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    SHUTDOWN_HOOKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, Arc::new(hook)));
+    ShutdownHook(id)
+}
+
+/// Run all shutdown hooks.
+pub(crate) fn run_shutdown_hooks() {
+    // the hooks run without holding the lock, so that they can drop their own handles
+    let hooks = SHUTDOWN_HOOKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, hook)| hook.clone())
+        .collect::<Vec<_>>();
+    for hook in hooks {
+        // a hook that panics must not prevent the others from running
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(&*hook)).is_err() {
+            log::error!("A shutdown hook panicked");
+        }
+    }
+}
+
+thread_local! {
+    /// The event loop of the thread that runs experiments. It is created by the first run and
+    /// reused by later runs in the same process.
+    static EVENT_LOOP: RefCell<Option<EventLoop<()>>> = const { RefCell::new(None) };
+    static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+}
+
 #[derive(Debug)]
 pub struct GPUState {
     pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    /// Set to the reason if the device was lost (e.g., because of a driver reset).
+    pub device_lost: ArcMutex<Option<String>>,
 }
 
 #[derive(Dbg)]
@@ -55,52 +113,115 @@ pub struct App {
     #[dbg(placeholder = "[[ RendererFactory ]]")]
     pub shared_renderer_state: Arc<dyn SharedRendererState>,
     pub font_manager: ArcMutex<renderer::cosmic_text::FontSystem>,
+    pub config: ExperimentConfig,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Use the clipboard of the event loop thread. The clipboard is created on first use and kept, as on
+/// X11 copied text is only available while its owner is alive.
+fn with_clipboard<R>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<R, arboard::Error>) -> PsydkResult<R> {
+    CLIPBOARD
+        .with(|clipboard| {
+            let mut clipboard = clipboard.borrow_mut();
+            if clipboard.is_none() {
+                *clipboard = Some(arboard::Clipboard::new()?);
+            }
+            f(clipboard.as_mut().unwrap())
+        })
+        .map_err(|e| PsydkError::CustomError(format!("Failed to access the clipboard: {e}")))
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new() -> PsydkResult<Self> {
+        Self::new_with_config(ExperimentConfig::default())
+    }
+
+    /// Create a new app using the given configuration. Fails if the selected graphics adapter or
+    /// backend is not available, or if the renderer backend selected in the configuration is not
+    /// available in this build.
+    pub fn new_with_config(mut config: ExperimentConfig) -> PsydkResult<Self> {
         let (action_sender, action_receiver) = std::sync::mpsc::channel();
 
-        let backend = wgpu::Backends::METAL | wgpu::Backends::DX12;
-        let backend_options = wgpu::BackendOptions {
-            gl: wgpu::GlBackendOptions::default(),
-            dx12: wgpu::Dx12BackendOptions {
-                latency_waitable_object: wgpu::wgt::Dx12UseFrameLatencyWaitableObject::DontWait,
-                ..Default::default()
-            },
-            noop: wgpu::NoopBackendOptions::default(),
+        let backend = config.graphics_backend.backends();
+        let instance = Self::create_instance(backend);
+
+        // request an adapter, either by name or using the power preference
+        let adapter = if let Some(adapter_name) = &config.adapter {
+            let adapters = instance.enumerate_adapters(backend);
+            let available = adapters.iter().map(|a| a.get_info().name).collect::<Vec<_>>();
+
+            let adapter = adapters
+                .into_iter()
+                .find(|a| a.get_info().name.to_lowercase().contains(&adapter_name.to_lowercase()));
+
+            match adapter {
+                Some(adapter) => Ok(adapter),
+                None => {
+                    return Err(PsydkError::ParameterError(format!(
+                        "No graphics adapter matching \"{}\" found. Available adapters: {:?}",
+                        adapter_name, available
+                    )))
+                }
+            }
+        } else {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference.into(),
+                force_fallback_adapter: false,
+                compatible_surface: None, // idealy we would use the surface here, but we don't have it yet
+            }))
         };
-        let instance_desc = wgpu::InstanceDescriptor {
-            backends: backend,
-            backend_options,
-            // use defaults for the rest
-            ..Default::default()
+
+        // if there is no suitable GPU (e.g., in a VM or over remote desktop), fall back to a software adapter,
+        // unless a backend was asked for explicitly
+        let (instance, adapter) = match adapter {
+            Ok(adapter) => (instance, adapter),
+            Err(e) if config.graphics_backend != GraphicsBackend::Auto => {
+                return Err(PsydkError::WindowCreationError(format!(
+                    "No graphics adapter found for the {:?} backend: {}",
+                    config.graphics_backend, e
+                )))
+            }
+            Err(_) => {
+                log::warn!("No suitable graphics adapter found, falling back to a software adapter.");
+                let instance = Self::create_instance(wgpu::Backends::all());
+                let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                }))
+                .map_err(|e| {
+                    PsydkError::WindowCreationError(format!(
+                        "Failed to find any graphics adapter, not even a software one: {}",
+                        e
+                    ))
+                })?;
+                (instance, adapter)
+            }
         };
 
-        let instance = wgpu::Instance::new(&instance_desc);
+        log::debug!("Selected graphics adapter: {:?}", adapter.get_info());
 
-        // request an adapter
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            compatible_surface: None, // idealy we would use the surface here, but we don't have it yet
-        }))
-        .expect("Failed to find an suitable graphics adapter. This is likely a bug, please report it.");
+        let software_adapter = adapter.get_info().device_type == wgpu::DeviceType::Cpu;
 
-        log::debug!("Selected graphics adapter: {:?}", adapter.get_info());
+        // GPU renderers are not usable on a software adapter, so we rasterize on the CPU instead
+        if software_adapter && config.renderer_backend != RendererBackend::Software {
+            log::warn!(
+                "The graphics adapter is a software adapter. Falling back to the software renderer. \
+                 Rendering will be slow and frame timing will not be accurate."
+            );
+            config.renderer_backend = RendererBackend::Software;
+        }
 
         let mut limits = wgpu::Limits::downlevel_defaults();
         limits.max_storage_buffers_per_shader_stage = 16;
 
-        let features =
+        let mut features =
             wgpu::Features::TEXTURE_FORMAT_16BIT_NORM | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
 
+        // software adapters might not support all features, but the software renderer does not need them
+        if software_adapter {
+            features &= adapter.features();
+        }
+
         // Create the logical device and command queue
         let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
             label: None,
@@ -110,13 +231,25 @@ impl App {
             memory_hints: MemoryHints::Performance,
             trace: wgpu::Trace::Off,
         }))
-        .expect("Failed to create device. This is likely a bug, please report it.");
+        .map_err(|e| PsydkError::WindowCreationError(format!("Failed to create the graphics device: {}", e)))?;
+
+        // we cannot recover from a lost device, but we can at least report it properly
+        let device_lost = Arc::new(Mutex::new(None));
+        let device_lost_clone = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            log::error!("The graphics device was lost ({:?}): {}", reason, message);
+            device_lost_clone
+                .lock()
+                .unwrap()
+                .replace(format!("{:?}: {}", reason, message));
+        });
 
         let gpu_state = GPUState {
             instance,
             adapter,
             device,
             queue,
+            device_lost,
         };
 
         // create font manager
@@ -135,21 +268,87 @@ impl App {
         font_manager.db_mut().load_font_data(noto_sans_bold_italic.to_vec());
 
         // create shared renderer state
-        let renderer = renderer::skia_backend::SkiaSharedRendererState::new(
-            &gpu_state.adapter,
-            &gpu_state.device,
-            &gpu_state.queue,
-        );
+        log::debug!("Using renderer backend: {:?}", config.renderer_backend);
+        let shared_renderer_state: Arc<dyn SharedRendererState> = match config.renderer_backend {
+            #[cfg(feature = "skia")]
+            RendererBackend::Skia => match renderer::skia_backend::SkiaSharedRendererState::new(
+                &gpu_state.adapter,
+                &gpu_state.device,
+                &gpu_state.queue,
+            ) {
+                Ok(state) => Arc::new(state),
+                // Skia cannot render with every graphics backend (e.g., Vulkan on Linux)
+                #[cfg(feature = "software")]
+                Err(e) => {
+                    log::warn!(
+                        "Skia is not available ({}). Falling back to the software renderer. Rendering will be \
+                         slow and frame timing will not be accurate.",
+                        e
+                    );
+                    config.renderer_backend = RendererBackend::Software;
+                    Arc::new(renderer::tiny_skia_backend::TinySkiaSharedRendererState::new(
+                        &gpu_state.device,
+                        &gpu_state.queue,
+                    ))
+                }
+                #[cfg(not(feature = "software"))]
+                Err(e) => {
+                    return Err(PsydkError::WindowCreationError(format!(
+                        "Failed to set up the Skia renderer: {}",
+                        e
+                    )))
+                }
+            },
+            #[cfg(feature = "vello")]
+            RendererBackend::Vello => Arc::new(renderer::vello_backend::VelloSharedRendererState::new(
+                &gpu_state.device,
+            )),
+            #[cfg(feature = "software")]
+            RendererBackend::Software => Arc::new(renderer::tiny_skia_backend::TinySkiaSharedRendererState::new(
+                &gpu_state.device,
+                &gpu_state.queue,
+            )),
+            #[allow(unreachable_patterns)]
+            backend => {
+                return Err(PsydkError::ParameterError(format!(
+                    "The {:?} renderer backend is not available in this build of psydk, as the `{}` feature is \
+                     not enabled. Enabled renderer features: {}",
+                    backend,
+                    backend.feature(),
+                    RendererBackend::enabled_features().join(", ")
+                )))
+            }
+        };
 
-        Self {
+        Ok(Self {
             windows: vec![],
             gpu_state: Arc::new(Mutex::new(gpu_state)),
             action_receiver,
             action_sender,
             dummy_window: None,
-            shared_renderer_state: Arc::new(renderer),
+            shared_renderer_state,
             font_manager: Arc::new(Mutex::new(font_manager)),
-        }
+            config,
+        })
+    }
+
+    fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+        let backend_options = wgpu::BackendOptions {
+            gl: wgpu::GlBackendOptions::default(),
+            dx12: wgpu::Dx12BackendOptions {
+                latency_waitable_object: wgpu::wgt::Dx12UseFrameLatencyWaitableObject::DontWait,
+                ..Default::default()
+            },
+            noop: wgpu::NoopBackendOptions::default(),
+        };
+        let instance_desc = wgpu::InstanceDescriptor {
+            backends,
+            backend_options,
+            // use defaults for the rest
+            ..Default::default()
+        };
+
+        wgpu::Instance::new(&instance_desc)
     }
 
     /// Create a new window with the given options.
@@ -157,18 +356,58 @@ impl App {
         &self,
         window_options: &WindowOptions,
         gamma_options: GammaOptions,
+        timing_critical: bool,
         event_loop: &ActiveEventLoop,
-    ) -> Window {
-        let window_attributes = WinitWindow::default_attributes()
+    ) -> PsydkResult<Window> {
+        let mut window_attributes = WinitWindow::default_attributes()
             .with_title("Winit window")
             .with_transparent(false);
 
-        let winit_window = event_loop.create_window(window_attributes).unwrap();
+        if let WindowOptions::Windowed { resolution } = window_options {
+            let (width, height) = resolution.unwrap_or((800, 600));
+            window_attributes = window_attributes.with_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        }
+
+        // overlays cover the monitor without going fullscreen, which would hide other applications
+        // on some platforms (e.g., by moving the window to a space of its own on macOS)
+        let overlay = matches!(window_options, WindowOptions::Overlay { .. });
+        if overlay {
+            let mon_handle = window_options
+                .monitor()
+                .map(|monitor| monitor.handle().clone())
+                .or_else(|| event_loop.primary_monitor())
+                .or_else(|| event_loop.available_monitors().next())
+                .ok_or_else(|| PsydkError::MonitorError("No monitor found. Is a display connected?".into()))?;
+            window_attributes = window_attributes
+                .with_title("psydk overlay")
+                .with_transparent(true)
+                .with_decorations(false)
+                .with_resizable(false)
+                .with_active(false)
+                .with_window_level(WindowLevel::AlwaysOnTop)
+                .with_position(mon_handle.position())
+                .with_inner_size(mon_handle.size());
+        }
+
+        let winit_window = event_loop
+            .create_window(window_attributes)
+            .map_err(|e| PsydkError::WindowCreationError(e.to_string()))?;
 
         // make sure cursor is visible (for normlisation across platforms)
         winit_window.set_cursor_visible(true);
 
-        winit_window.focus_window();
+        // overlays must not take the focus from the application they are shown on top of
+        let click_through = match window_options {
+            WindowOptions::Overlay { click_through, .. } => *click_through,
+            _ => false,
+        };
+        if overlay {
+            if let Err(e) = winit_window.set_cursor_hittest(!click_through) {
+                log::warn!("Mouse input cannot pass through the overlay on this platform: {e}");
+            }
+        } else {
+            winit_window.focus_window();
+        }
 
         // log::debug!("Window created: {:?}", winit_window);
 
@@ -185,7 +424,7 @@ impl App {
 
         let surface = instance
             .create_surface(winit_window.clone())
-            .expect("Failed to create surface. This is likely a bug, please report it.");
+            .map_err(|e| PsydkError::WindowCreationError(format!("Failed to create surface: {e}")))?;
 
         // print supported swapchain formats
         let swapchain_formats = surface.get_capabilities(adapter).formats;
@@ -199,13 +438,31 @@ impl App {
         let swapchain_format = TextureFormat::Bgra8Unorm;
         let swapchain_view_format = vec![TextureFormat::Bgra8Unorm];
 
+        // the renderer outputs premultiplied colors, so an overlay is composited with the desktop
+        // behind it if the surface supports any alpha mode other than opaque
+        let alpha_mode = if overlay {
+            [
+                wgpu::CompositeAlphaMode::PreMultiplied,
+                wgpu::CompositeAlphaMode::Inherit,
+                wgpu::CompositeAlphaMode::PostMultiplied,
+            ]
+            .into_iter()
+            .find(|mode| swapchain_capabilities.alpha_modes.contains(mode))
+            .unwrap_or_else(|| {
+                log::warn!("The surface does not support transparency, the overlay will have a black background");
+                swapchain_capabilities.alpha_modes[0]
+            })
+        } else {
+            swapchain_capabilities.alpha_modes[0]
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: swapchain_format,
             width: size.width,
             height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
+            alpha_mode,
             view_formats: swapchain_view_format,
             desired_maximum_frame_latency: 1,
         };
@@ -214,13 +471,25 @@ impl App {
 
         surface.configure(device, &config);
 
-        // set fullscreen mode
-        let mon_handle = window_options.monitor().unwrap().handle();
-        let mon_name = mon_handle.name().unwrap_or("Unnamed monitor".to_string());
-
-        winit_window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(Some(mon_handle.clone()))));
+        // set fullscreen mode (windowed windows stay on the monitor the platform placed them on)
+        if !matches!(
+            window_options,
+            WindowOptions::Windowed { .. } | WindowOptions::Overlay { .. }
+        ) {
+            let mon_handle = window_options
+                .monitor()
+                .ok_or_else(|| PsydkError::MonitorError("No monitor was selected for the window".into()))?
+                .handle();
+
+            if timing_critical {
+                winit_window.set_fullscreen(Some(crate::visual::compositor::fullscreen(&mon_handle)));
+                crate::visual::compositor::apply_timing_hints(&winit_window);
+            } else {
+                winit_window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(Some(mon_handle.clone()))));
+            }
+        }
 
-        let wgpu_renderer = pollster::block_on(renderer::wgpu_renderer::WgpuRenderer::new(
+        let mut wgpu_renderer = pollster::block_on(renderer::wgpu_renderer::WgpuRenderer::new(
             winit_window.clone(),
             instance,
             device,
@@ -229,6 +498,7 @@ impl App {
             gamma_options.lut,
             gamma_options.encode_gamma,
         ));
+        wgpu_renderer.set_alpha_mode(alpha_mode);
 
         // create the renderer
         let mut renderer = self
@@ -250,14 +520,32 @@ impl App {
             wgpu_renderer,
             shared_renderer_state: self.shared_renderer_state.clone(),
             mouse_cursor_visible: true,
+            cursor: Default::default(),
             mouse_position: None,
+            gaze_position: None,
+            fixation_failures: 0,
+            gaze_events: None,
             size: size.into(),
             physical_screen: PhysicalScreen::new(size.width, width_mm, viewing_distance),
-            event_handlers: HashMap::new(), // TODO this should be a weak reference
-            bg_color: LinRgba::new(0.5, 0.5, 0.5, 1.0),
+            viewing_distance_log: None,
+            audio_trigger: None,
+            coordinate_system: Default::default(),
+            bg_color: match overlay {
+                true => LinRgba::new(0.0, 0.0, 0.0, 0.0),
+                false => LinRgba::new(0.5, 0.5, 0.5, 1.0),
+            },
+            click_through,
+            ime_allowed: false,
+            ime_active: false,
             frame_callbacks: HashMap::new(),
             frame_queue: Vec::new(),
             last_frame_id: 0,
+            watchdog: Default::default(),
+            displayed_stimuli: Vec::new(),
+            refresh_measurement: None,
+            debug_overlay: None,
+            debug_layout: false,
+            refresh: Default::default(),
         };
 
         // create channel for physical input
@@ -268,20 +556,17 @@ impl App {
 
         #[cfg(all(feature = "dx12", target_os = "windows"))]
         {
-            let swap_chain = unsafe {
-                window_state
-                    .surface
-                    .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.unwrap().swap_chain().unwrap())
-            };
-
+            // the surface is not a DX12 surface if we are running on a different backend (e.g., GL)
             let waitable_handle = unsafe {
                 window_state
                     .surface
-                    .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.unwrap().waitable_handle().unwrap())
+                    .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.and_then(|s| s.waitable_handle()))
             };
 
             // this is waiting for the frame latency waitable object to be signaled
-            unsafe { windows::Win32::System::Threading::WaitForSingleObject(waitable_handle, 10000) };
+            if let Some(waitable_handle) = waitable_handle {
+                unsafe { windows::Win32::System::Threading::WaitForSingleObject(waitable_handle, 10000) };
+            }
         }
 
         // create handle
@@ -292,6 +577,9 @@ impl App {
             event_broadcast_sender,
             event_broadcast_receiver,
             config: Arc::new(Mutex::new(ExperimentConfig::default())),
+            event_handlers: Arc::new(Mutex::new(HashMap::new())), // TODO this should be a weak reference
+            keyboard: Default::default(),
+            simulated_participant: Arc::new(Mutex::new(None)),
         };
 
         let win_clone = window.clone();
@@ -305,7 +593,7 @@ impl App {
         //     false
         // });
 
-        window
+        Ok(window)
     }
 
     // /// Run the app
@@ -323,7 +611,14 @@ impl App {
     {
         log::debug!("Main task is running on thread {:?}", std::thread::current().id());
 
-        let event_loop = EventLoop::new().unwrap();
+        // raise the system timer resolution for the duration of the experiment
+        let _timer_resolution = crate::time::TimerResolutionGuard::new();
+
+        // winit only allows one event loop per process, so it is kept and reused by later runs
+        let event_loop = match EVENT_LOOP.with(|event_loop| event_loop.borrow_mut().take()) {
+            Some(event_loop) => event_loop,
+            None => EventLoop::new().map_err(|e| PsydkError::WindowCreationError(e.to_string()))?,
+        };
         event_loop.set_control_flow(ControlFlow::Poll);
 
         let event_loop_proxy = event_loop.create_proxy();
@@ -340,15 +635,28 @@ impl App {
             self.shared_renderer_state.clone(),
             audio_host,
             self.font_manager.clone(),
+            self.config.clone(),
         );
 
         // create mutex to hold potential error
         let error_mutex = Arc::new(Mutex::new(None));
         let error_mutex_clone = error_mutex.clone();
 
+        // audio callbacks raise their priority when they first run. The setting only applies to this run.
+        // The event loop keeps its priority, as it polls continuously and would starve the rest of the
+        // system (including the audio threads) at a real-time priority
+        let realtime_priority = self.config.realtime_priority;
+        let _audio_realtime = timed_audio::realtime::AudioRealtimeGuard::new(realtime_priority);
+
         // start experiment
         thread::spawn(move || {
-            let res = experiment_fn(exp_manager);
+            // the experiment thread runs the present loop, which blocks on every frame
+            let _priority =
+                realtime_priority.then(|| timed_audio::realtime::promote_current_thread("experiment", false));
+
+            // a panic must not keep the event loop running, as it could never be stopped
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| experiment_fn(exp_manager)))
+                .unwrap_or_else(|_| Err(PsydkError::CustomError("The experiment function panicked".into())));
 
             // send Exit event to the event loop, then wake it up
             action_sender.send(EventLoopAction::Exit(None)).unwrap();
@@ -363,7 +671,28 @@ impl App {
         });
 
         // start event loop
-        let _ = event_loop.run_app(self);
+        let result = Self::run_event_loop(event_loop, self);
+
+        // tear down the windows of this run, so that their surfaces are released before the GPU
+        // state is dropped together with the app
+        for window in self.windows.drain(..) {
+            window.close();
+        }
+        self.dummy_window = None;
+
+        run_shutdown_hooks();
+
+        // list all data files of the session, with hashes, next to the data
+        let session = crate::cli::launch_options().and_then(|options| options.session.clone());
+        match crate::utils::manifest::finish_session(session) {
+            Ok(Some(path)) => log::info!("Wrote the session manifest to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to write the session manifest: {}", e),
+        }
+
+        if let Err(e) = result {
+            return Err(PsydkError::CustomError(format!("The event loop failed: {e}")));
+        }
 
         // check if there was an error
         let error = error_mutex.lock().unwrap().take();
@@ -377,6 +706,22 @@ impl App {
         }
     }
 
+    /// Run the event loop until the experiment exits, keeping it for later runs where the platform
+    /// allows it.
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    fn run_event_loop(mut event_loop: EventLoop<()>, app: &mut App) -> Result<(), EventLoopError> {
+        use winit::platform::run_on_demand::EventLoopExtRunOnDemand;
+
+        let result = event_loop.run_app_on_demand(app);
+        EVENT_LOOP.with(|stored| stored.borrow_mut().replace(event_loop));
+        result
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    fn run_event_loop(event_loop: EventLoop<()>, app: &mut App) -> Result<(), EventLoopError> {
+        event_loop.run_app(app)
+    }
+
     // Start a thread that will dispath
 }
 
@@ -386,10 +731,13 @@ impl ApplicationHandler<()> for App {
     fn user_event(&mut self, event_loop: &ActiveEventLoop, event: ()) {
         // check if we need to create a new window
         self.action_receiver.try_recv().map(|action| match action {
-            EventLoopAction::CreateNewWindow(options, gamma_options, sender) => {
-                let window = self.create_window(&options, gamma_options, event_loop);
-                self.windows.push(window.clone());
-                sender.send(window).unwrap();
+            EventLoopAction::CreateNewWindow(options, gamma_options, timing_critical, sender) => {
+                let window = self.create_window(&options, gamma_options, timing_critical, event_loop);
+                if let Ok(window) = &window {
+                    self.windows.push(window.clone());
+                }
+                // the receiver might have given up waiting, in which case there is nothing to do
+                let _ = sender.send(window);
             }
             EventLoopAction::GetAvailableMonitors(sender) => {
                 let monitors = event_loop.available_monitors();
@@ -402,6 +750,18 @@ impl ApplicationHandler<()> for App {
                     .collect();
                 sender.send(monitors).unwrap();
             }
+            EventLoopAction::ShowFileDialog(dialog, sender) => {
+                let _ = sender.send(dialog.show());
+            }
+            EventLoopAction::GetClipboardText(sender) => {
+                let _ = sender.send(with_clipboard(|clipboard| clipboard.get_text()));
+            }
+            EventLoopAction::SetClipboardText(text, sender) => {
+                let _ = sender.send(with_clipboard(|clipboard| clipboard.set_text(text)));
+            }
+            EventLoopAction::CreateCustomCursor(source, sender) => {
+                let _ = sender.send(event_loop.create_custom_cursor(source));
+            }
             EventLoopAction::Exit(..) => {
                 event_loop.exit();
             }
@@ -411,15 +771,28 @@ impl ApplicationHandler<()> for App {
     fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::CloseRequested => {
-                // for now, exit the program
-                std::process::exit(0);
                 // find the window
                 let window = self.windows.iter().find(|w| w.winit_id == window_id);
 
+                // windowed windows (e.g. previews) are closed on their own, so that an interactive
+                // session keeps running
+                let windowed = window
+                    .and_then(|w| {
+                        w.with_state(|win_state| win_state.winit_window.fullscreen().is_none())
+                            .ok()
+                    })
+                    .unwrap_or(false);
+                if !windowed {
+                    // for now, exit the program
+                    run_shutdown_hooks();
+                    std::process::exit(0);
+                }
+
                 if let Some(window) = window {
-                    // remove the window
-                    self.windows.retain(|w| w.winit_id != window_id);
+                    window.close();
                 }
+                // remove the window
+                self.windows.retain(|w| w.winit_id != window_id);
             }
             WindowEvent::Resized(size) => {
                 // find the window
@@ -427,7 +800,37 @@ impl ApplicationHandler<()> for App {
 
                 if let Some(window) = window {
                     // update the window size
-                    window.resize(size);
+                    if let Err(e) = window.resize(size) {
+                        log::warn!("Failed to resize window: {}", e);
+                    }
+                }
+            }
+            WindowEvent::Focused(false) => {
+                // key releases are not reported while the window is not focused
+                if let Some(window) = self.windows.iter().find(|w| w.winit_id == window_id) {
+                    window.keyboard.release_all(std::time::Instant::now());
+                }
+            }
+            WindowEvent::Ime(ref ime) => {
+                let Some(window) = self.windows.iter().find(|w| w.winit_id == window_id) else {
+                    return;
+                };
+                // while an input method is enabled, typed text arrives as commits
+                if matches!(ime, Ime::Enabled | Ime::Disabled) {
+                    let _ = window.with_state(|win_state| win_state.ime_active = *ime == Ime::Enabled);
+                }
+                if let Ok(input) = Event::try_from_winit(event.clone(), window) {
+                    let text = match &input {
+                        Event::ImeCommit { timestamp, text } => Some(Event::Text {
+                            timestamp: timestamp.clone(),
+                            text: text.clone(),
+                        }),
+                        _ => None,
+                    };
+                    window.inject_event(input);
+                    if let Some(text) = text {
+                        window.inject_event(text);
+                    }
                 }
             }
             WindowEvent::KeyboardInput { .. }
@@ -442,11 +845,14 @@ impl ApplicationHandler<()> for App {
                 if let WindowEvent::CursorMoved { position, .. } = event {
                     if let Some(window) = window {
                         let mut window_state = window.state.lock().unwrap();
-                        let window_state = window_state.as_mut().unwrap();
+                        let Some(window_state) = window_state.as_mut() else {
+                            return;
+                        };
                         let win_size = window_state.size;
-                        let shifted_position = (
+                        let shifted_position = window_state.coordinate_system.from_scene(
                             position.x as f32 - win_size.width as f32 / 2.0,
                             position.y as f32 - win_size.height as f32 / 2.0,
+                            win_size,
                         );
                         window_state.mouse_position = Some(shifted_position);
                     }
@@ -457,18 +863,44 @@ impl ApplicationHandler<()> for App {
                         // if escape key was pressed, close window
                         if input.key_pressed("\u{1b}") {
                             // for now, just exit the program
+                            run_shutdown_hooks();
                             std::process::exit(0);
                         }
 
-                        // broadcast the event
-                        window.event_broadcast_sender.try_broadcast(input.clone()); //.unwrap();
+                        window.inject_event(input);
+                    }
 
-                        // send the event to the window
-                        window.dispatch_event(input);
+                    // typed text (unless an input method is enabled, which commits it instead)
+                    if let WindowEvent::KeyboardInput { event: key_event, .. } = &event {
+                        let ime_active = window.with_state(|win_state| win_state.ime_active).unwrap_or(false);
+                        if let (ElementState::Pressed, Some(text), false) =
+                            (key_event.state, &key_event.text, ime_active)
+                        {
+                            if !text.chars().any(char::is_control) {
+                                window.inject_event(Event::Text {
+                                    timestamp: std::time::Instant::now().into(),
+                                    text: text.to_string(),
+                                });
+                            }
+                        }
                     }
                 }
             }
             _ => {}
         }
     }
+
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, device_id: DeviceId, event: DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            let event = Event::MouseMotion {
+                timestamp: std::time::Instant::now().into(),
+                delta,
+            };
+            // raw motion arrives at the polling rate of the mouse, so it is only passed to event
+            // handlers and not broadcast, where it would push other events out of the receivers
+            for window in &self.windows {
+                window.dispatch_event(event.clone());
+            }
+        }
+    }
 }
@@ -1,18 +1,30 @@
 use std::sync::Arc;
 
+#[cfg(feature = "python")]
 use numpy::{IntoPyArray, PyReadonlyArrayDyn};
+#[cfg(feature = "python")]
 use pyo3::ffi::c_str;
+#[cfg(feature = "python")]
 use pyo3::types::PyAnyMethods;
+#[cfg(feature = "python")]
 use pyo3::{pyclass, pyfunction, pymethods, Bound, PyAny, PyObject, PyRef, PyRefMut, PyResult, Python};
 use timed_audio::cpal::traits::{DeviceTrait, HostTrait};
 use timed_audio::cpal::{default_host, Device, Host};
-use timed_audio::{AudioObject, Stream};
+use timed_audio::{
+    AudioObject, GeneratorParams, InputStream, ModulatedParam, PlaybackHandle, RampCurve, Stream, TriggerChannel,
+};
 
-use crate::time::Timestamp;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{to_timeout, Timestamp},
+    visual::window::Window,
+};
+
+pub mod scheduler;
 
 #[derive(Clone)]
-#[pyclass]
-#[pyo3(name = "Host")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "Host"))]
 pub struct PyHost {
     pub(crate) host: Arc<Host>,
 }
@@ -26,57 +38,347 @@ impl Default for PyHost {
 }
 
 #[derive(Clone)]
-#[pyclass]
-#[pyo3(name = "Stream")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "Stream"))]
 pub struct PyStream {
     stream: Option<Stream>,
+    /// The default input device of the host, used for latency calibration.
+    default_input_device: Option<Device>,
 }
 
 #[derive(Clone)]
-#[pyclass]
-#[pyo3(name = "Device")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "Device"))]
 pub struct PyDevice {
     pub(crate) device: Device,
+    is_default_output: bool,
+    is_default_input: bool,
+}
+
+/// A stream that captures audio from an input device, e.g. a microphone.
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "InputStream"))]
+pub struct PyInputStream {
+    pub(crate) stream: InputStream,
+}
+
+/// A handle to a playing (or scheduled) sound, returned by `Stream.play` and `Stream.play_at`.
+#[derive(Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "PlaybackHandle"))]
+pub struct PyPlaybackHandle {
+    handle: PlaybackHandle,
+}
+
+/// Sample rates that are checked against the ranges reported by the device.
+const COMMON_SAMPLE_RATES: [u32; 11] = [
+    8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 176400, 192000,
+];
+
+impl PyHost {
+    /// Open the audio host with the given name (case-insensitive), e.g. "ASIO", "JACK", "WASAPI",
+    /// "ALSA", or "CoreAudio". "PipeWire" is accepted as an alias for JACK, as PipeWire exposes
+    /// its low-latency mode through the JACK API.
+    pub fn from_name(name: &str) -> PsydkResult<Self> {
+        let name = match name.to_lowercase().as_str() {
+            "pipewire" => "jack".to_string(),
+            name => name.to_string(),
+        };
+        let available = timed_audio::cpal::available_hosts();
+
+        let id = available
+            .iter()
+            .find(|id| id.name().to_lowercase() == name)
+            .ok_or_else(|| {
+                let names = available.iter().map(|id| id.name()).collect::<Vec<_>>();
+                PsydkError::AudioError(format!(
+                    "Audio host \"{name}\" is not available. Available hosts: {names:?}. ASIO and JACK require psydk to be built with the `asio` or `jack` feature."
+                ))
+            })?;
+
+        let host = timed_audio::cpal::host_from_id(*id)
+            .map_err(|e| PsydkError::AudioError(format!("Failed to open audio host \"{name}\": {e}")))?;
+        Ok(Self { host: Arc::new(host) })
+    }
+
+    fn wrap_device(&self, device: Device) -> PyDevice {
+        PyDevice::new(device, &self.host)
+    }
+
+    /// All devices of the host, optionally only those with outputs or inputs.
+    pub fn devices(&self, output: Option<bool>) -> PsydkResult<Vec<PyDevice>> {
+        let devices: Vec<Device> = match output {
+            None => self.host.devices().map(|d| d.collect()),
+            Some(true) => self.host.output_devices().map(|d| d.collect()),
+            Some(false) => self.host.input_devices().map(|d| d.collect()),
+        }
+        .map_err(|e| PsydkError::AudioError(format!("Failed to enumerate audio devices: {e}")))?;
+
+        Ok(devices.into_iter().map(|d| self.wrap_device(d)).collect())
+    }
+
+    /// Find the first output (or input) device whose name contains `name` (case-insensitive).
+    pub fn find_device(&self, name: &str, output: bool) -> PsydkResult<PyDevice> {
+        let devices = self.devices(Some(output))?;
+        let names = devices.iter().map(|d| d.name()).collect::<Vec<_>>();
+
+        devices
+            .into_iter()
+            .find(|d| d.name().to_lowercase().contains(&name.to_lowercase()))
+            .ok_or_else(|| {
+                PsydkError::AudioError(format!(
+                    "No audio device matching \"{name}\" found. Available devices: {names:?}"
+                ))
+            })
+    }
+}
+
+impl PyDevice {
+    pub fn new(device: Device, host: &Host) -> Self {
+        let name = device.name().ok();
+        let is_default = |default: Option<Device>| name.is_some() && default.and_then(|d| d.name().ok()) == name;
+
+        Self {
+            is_default_output: is_default(host.default_output_device()),
+            is_default_input: is_default(host.default_input_device()),
+            device,
+        }
+    }
+
+    /// The name of the device. Returns "Unknown device" if the name can't be determined.
+    pub fn name(&self) -> String {
+        self.device.name().unwrap_or_else(|_| "Unknown device".to_string())
+    }
+
+    /// Supported stream configurations as (channels, min sample rate, max sample rate).
+    fn config_ranges(&self, output: bool) -> Vec<(u16, u32, u32)> {
+        let ranges: Vec<_> = if output {
+            self.device
+                .supported_output_configs()
+                .map(|c| c.collect())
+                .unwrap_or_default()
+        } else {
+            self.device
+                .supported_input_configs()
+                .map(|c| c.collect())
+                .unwrap_or_default()
+        };
+
+        ranges
+            .iter()
+            .map(|r| (r.channels(), r.min_sample_rate().0, r.max_sample_rate().0))
+            .collect()
+    }
+
+    /// Sample rates supported by the device (for output, unless `output` is false).
+    pub fn supported_sample_rates(&self, output: bool) -> Vec<u32> {
+        let ranges = self.config_ranges(output);
+        let mut rates = COMMON_SAMPLE_RATES
+            .iter()
+            .copied()
+            .chain(ranges.iter().flat_map(|(_, min, max)| [*min, *max]))
+            .filter(|rate| ranges.iter().any(|(_, min, max)| (*min..=*max).contains(rate)))
+            .collect::<Vec<_>>();
+        rates.sort_unstable();
+        rates.dedup();
+        rates
+    }
+
+    /// Channel counts supported by the device (for output, unless `output` is false).
+    pub fn supported_channels(&self, output: bool) -> Vec<u16> {
+        let mut channels = self
+            .config_ranges(output)
+            .iter()
+            .map(|(channels, _, _)| *channels)
+            .collect::<Vec<_>>();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
 }
 
 #[derive(Debug, Clone)]
-#[pyclass]
-#[pyo3(name = "AudioObject")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "AudioObject"))]
 pub struct PyAudioObject {
     pub(crate) audio_object: AudioObject,
 }
 
 impl PyStream {
-    pub fn new(host: &Host, device: Option<&PyDevice>) -> Self {
+    pub fn new(host: &Host, device: Option<&PyDevice>) -> PsydkResult<Self> {
+        let default_device;
         let device = match device {
             Some(device) => &device.device,
-            None => &host.default_output_device().unwrap(),
+            None => {
+                default_device = host.default_output_device().ok_or_else(|| {
+                    PsydkError::AudioError(
+                        "No default audio output device found. Make sure an audio device is connected and enabled, or select a device explicitly.".into(),
+                    )
+                })?;
+                &default_device
+            }
         };
 
-        let config = device.default_output_config().unwrap();
+        let config = device.default_output_config().map_err(|e| {
+            PsydkError::AudioError(format!(
+                "Failed to get the default output configuration of the audio device: {e}"
+            ))
+        })?;
         let sample_format = config.sample_format();
-        Self {
+        Ok(Self {
             stream: Some(Stream::new(&device, &config.into(), sample_format)),
-        }
+            default_input_device: host.default_input_device(),
+        })
+    }
+
+    pub(crate) fn stream(&self) -> PsydkResult<&Stream> {
+        self.stream
+            .as_ref()
+            .ok_or_else(|| PsydkError::AudioError("The audio stream has already been closed.".into()))
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyStream {
-    fn play(&self, audio_object: PyAudioObject) {
-        self.stream.as_ref().unwrap().play_now(audio_object.audio_object);
+    fn play(&self, audio_object: PyAudioObject) -> PyResult<PyPlaybackHandle> {
+        let handle = self.stream()?.play_now(audio_object.audio_object);
+        Ok(PyPlaybackHandle { handle })
     }
 
-    fn play_at(&self, audio_object: PyAudioObject, timestamp: Timestamp) {
-        self.stream
-            .as_ref()
-            .unwrap()
-            .play_at(audio_object.audio_object, timestamp.timestamp);
+    fn play_at(&self, audio_object: PyAudioObject, timestamp: Timestamp) -> PyResult<PyPlaybackHandle> {
+        let handle = self.stream()?.play_at(audio_object.audio_object, timestamp.timestamp);
+        Ok(PyPlaybackHandle { handle })
     }
 
     #[getter]
-    fn sample_rate(&self) -> u32 {
-        self.stream.as_ref().unwrap().sample_rate()
+    fn sample_rate(&self) -> PyResult<u32> {
+        Ok(self.stream()?.sample_rate())
+    }
+
+    /// The measured end-to-end latency in seconds that is used to compensate `play_at`, or `None`
+    /// if the stream has not been calibrated.
+    #[getter]
+    fn measured_latency(&self) -> PyResult<Option<f64>> {
+        Ok(self.stream()?.measured_latency().map(|l| l.as_secs_f64()))
+    }
+
+    /// Measure the end-to-end output latency by playing clicks and recording them, either through a
+    /// loopback cable or a microphone placed next to the speaker. The measured latency is then
+    /// applied automatically when scheduling audio with `play_at`.
+    ///
+    /// Parameters
+    /// ----------
+    /// input_device : Device, optional
+    ///   The device used to record the clicks. Defaults to the default input device.
+    /// n_clicks : int, optional
+    ///   The number of clicks to play. The median latency is used. Defaults to 5.
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///   The measured latency in seconds.
+    #[pyo3(signature = (input_device = None, n_clicks = 5))]
+    fn calibrate_latency(&self, py: Python, input_device: Option<PyDevice>, n_clicks: usize) -> PyResult<f64> {
+        let stream = self.stream()?;
+        let input_device = match input_device {
+            Some(device) => device.device,
+            None => self.default_input_device.clone().ok_or_else(|| {
+                PsydkError::AudioError("No default audio input device found. Select an input device explicitly.".into())
+            })?,
+        };
+
+        let latency = py
+            .allow_threads(|| stream.calibrate_latency(&input_device, n_clicks))
+            .map_err(|e| PsydkError::AudioError(format!("Latency calibration failed: {e}")))?;
+        Ok(latency.as_secs_f64())
+    }
+
+    /// Set the latency in seconds that is used to compensate `play_at`, e.g. from a previous
+    /// calibration. Pass `None` to disable compensation.
+    fn set_latency_compensation(&self, latency: Option<f64>) -> PyResult<()> {
+        let latency = latency
+            .map(|latency| {
+                std::time::Duration::try_from_secs_f64(latency).map_err(|_| {
+                    PsydkError::ParameterError(format!("Invalid latency {latency}, must be finite and non-negative"))
+                })
+            })
+            .transpose()?;
+        self.stream()?.set_latency_compensation(latency);
+        Ok(())
+    }
+
+    /// Use one output channel for square-wave trigger pulses instead of audio, e.g. to connect
+    /// the trigger input of an EEG amplifier to the audio interface when no parallel port is
+    /// available. Pulses are mixed into the output sample-accurately, so a pulse emitted with a
+    /// sound starts on exactly the same sample as the sound.
+    ///
+    /// While a trigger channel is set, sounds are played on the remaining channels, so sounds
+    /// created from samples need one channel less than the device.
+    ///
+    /// Parameters
+    /// ----------
+    /// channel : int, optional
+    ///   The index of the output channel. If None, the trigger channel is removed.
+    /// amplitude : float, optional
+    ///   The level of the pulse, as a sample value between -1 and 1. Defaults to 1.0.
+    /// width : float, optional
+    ///   The length of each pulse in seconds. Defaults to 0.005.
+    /// pulse_on_play : bool, optional
+    ///   Whether a pulse is emitted at the start of every sound that is played, e.g. with
+    ///   `play_at`. Defaults to True.
+    #[pyo3(signature = (channel, amplitude = 1.0, width = 0.005, pulse_on_play = true))]
+    fn set_trigger_channel(
+        &self,
+        channel: Option<usize>,
+        amplitude: f32,
+        width: f64,
+        pulse_on_play: bool,
+    ) -> PyResult<()> {
+        let width = std::time::Duration::try_from_secs_f64(width)
+            .ok()
+            .filter(|width| !width.is_zero())
+            .ok_or_else(|| PsydkError::ParameterError(format!("Invalid pulse width {width}, must be positive")))?;
+        if !(-1.0..=1.0).contains(&amplitude) {
+            return Err(
+                PsydkError::ParameterError(format!("Invalid amplitude {amplitude}, must be between -1 and 1")).into(),
+            );
+        }
+
+        let trigger = channel.map(|channel| TriggerChannel {
+            channel,
+            amplitude,
+            width,
+            pulse_on_play,
+        });
+        self.stream()?
+            .set_trigger_channel(trigger)
+            .map_err(|e| PsydkError::AudioError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// The index of the trigger channel, or None.
+    #[getter]
+    fn trigger_channel(&self) -> PyResult<Option<usize>> {
+        Ok(self.stream()?.trigger_channel().map(|trigger| trigger.channel))
+    }
+
+    /// Emit a pulse on the trigger channel, see `set_trigger_channel`.
+    ///
+    /// Parameters
+    /// ----------
+    /// timestamp : Timestamp, optional
+    ///   When the pulse reaches the output, compensated for the measured latency like `play_at`.
+    ///   If None, the pulse is emitted as soon as possible.
+    #[pyo3(signature = (timestamp = None))]
+    fn pulse(&self, timestamp: Option<Timestamp>) -> PyResult<()> {
+        let stream = self.stream()?;
+        match timestamp {
+            Some(timestamp) => stream.pulse_at(timestamp.timestamp),
+            None => stream.pulse_now(),
+        }
+        .map_err(|e| PsydkError::AudioError(e.to_string()))?;
+        Ok(())
     }
 
     // allow stream to be used as a context manager
@@ -96,6 +398,261 @@ impl PyStream {
     }
 }
 
+impl PyInputStream {
+    pub fn new(host: &Host, device: Option<&PyDevice>) -> PsydkResult<Self> {
+        let default_device;
+        let device = match device {
+            Some(device) => &device.device,
+            None => {
+                default_device = host.default_input_device().ok_or_else(|| {
+                    PsydkError::AudioError(
+                        "No default audio input device found. Make sure a microphone is connected and enabled, or select a device explicitly.".into(),
+                    )
+                })?;
+                &default_device
+            }
+        };
+
+        let stream = InputStream::new(device)
+            .map_err(|e| PsydkError::AudioError(format!("Failed to open the audio input stream: {e}")))?;
+        Ok(Self { stream })
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyInputStream {
+    /// The RMS level (linear, 0.0 to 1.0) of the most recently captured buffer.
+    #[getter]
+    fn rms(&self) -> f32 {
+        self.stream.rms()
+    }
+
+    /// The peak level (linear, 0.0 to 1.0) of the most recently captured buffer.
+    #[getter]
+    fn peak(&self) -> f32 {
+        self.stream.peak()
+    }
+
+    #[getter]
+    fn sample_rate(&self) -> u32 {
+        self.stream.sample_rate()
+    }
+
+    #[getter]
+    fn channels(&self) -> u16 {
+        self.stream.channels()
+    }
+
+    /// Wait until the input has been silent for a given duration, e.g. until a participant has
+    /// stopped speaking.
+    ///
+    /// Parameters
+    /// ----------
+    /// threshold : float
+    ///   The RMS level (linear, 0.0 to 1.0) below which the input is considered silent.
+    /// duration : float
+    ///   How long (in seconds) the input needs to stay silent.
+    /// timeout : float, optional
+    ///   The maximum time to wait in seconds. Waits indefinitely if not given, until the input is
+    ///   silent or the wait is interrupted (e.g., with Ctrl+C).
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   True if silence was detected, False if the timeout expired.
+    #[pyo3(signature = (threshold, duration, timeout = None))]
+    fn wait_for_silence(&self, py: Python, threshold: f32, duration: f64, timeout: Option<f64>) -> PyResult<bool> {
+        let duration = std::time::Duration::try_from_secs_f64(duration).map_err(|_| {
+            PsydkError::ParameterError(format!(
+                "Invalid silence duration {duration}, must be finite and non-negative"
+            ))
+        })?;
+        let timeout = to_timeout(timeout)?;
+        // signals are only handled by Python, so they are checked while waiting
+        py.allow_threads(|| {
+            self.stream.wait_for_silence_checked(threshold, duration, timeout, || {
+                Python::with_gil(|py| py.check_signals())
+            })
+        })
+    }
+
+    /// Stop capturing audio.
+    fn close(&self) {
+        self.stream.close();
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyPlaybackHandle {
+    /// Stop the sound immediately. A sound that has been scheduled but has not started yet will
+    /// not be played.
+    fn stop(&self) {
+        self.handle.stop();
+    }
+
+    /// Fade the sound out and stop it afterwards.
+    ///
+    /// Parameters
+    /// ----------
+    /// duration : float
+    ///   The duration of the fade-out in seconds.
+    fn fade_out(&self, duration: f64) -> PyResult<()> {
+        let duration = std::time::Duration::try_from_secs_f64(duration).map_err(|_| {
+            PsydkError::ParameterError(format!(
+                "Invalid fade-out duration {duration}, must be finite and non-negative"
+            ))
+        })?;
+        self.handle.fade_out(duration);
+        Ok(())
+    }
+
+    /// The volume as a linear gain factor, where 1.0 is the original volume.
+    #[getter]
+    fn get_volume(&self) -> f32 {
+        self.handle.volume()
+    }
+
+    #[setter]
+    fn set_volume(&self, volume: f32) {
+        self.handle.set_volume(volume);
+    }
+
+    /// The stereo pan, from -1.0 (left) to 1.0 (right).
+    #[getter]
+    fn get_pan(&self) -> f32 {
+        self.handle.pan()
+    }
+
+    #[setter]
+    fn set_pan(&self, pan: f32) {
+        self.handle.set_pan(pan);
+    }
+
+    /// Whether the sound has finished playing, either because it ended or because it was stopped.
+    #[getter]
+    fn finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyHost {
+    /// The name of the host (e.g., "CoreAudio", "WASAPI", or "ALSA").
+    #[getter(name)]
+    fn py_name(&self) -> String {
+        self.host.id().name().to_string()
+    }
+
+    /// List all audio devices of the host.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind : str, optional
+    ///   Either `"output"` or `"input"` to only list devices with outputs or inputs.
+    #[pyo3(name = "devices", signature = (kind = None))]
+    fn py_devices(&self, kind: Option<&str>) -> PyResult<Vec<PyDevice>> {
+        let output = match kind {
+            None => None,
+            Some("output") => Some(true),
+            Some("input") => Some(false),
+            Some(kind) => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Unknown device kind \"{kind}\", must be either \"output\" or \"input\""
+                ))
+                .into())
+            }
+        };
+        Ok(self.devices(output)?)
+    }
+
+    /// The default output device of the host, if any.
+    #[pyo3(name = "default_output_device")]
+    fn py_default_output_device(&self) -> Option<PyDevice> {
+        self.host.default_output_device().map(|d| self.wrap_device(d))
+    }
+
+    /// The default input device of the host, if any.
+    #[pyo3(name = "default_input_device")]
+    fn py_default_input_device(&self) -> Option<PyDevice> {
+        self.host.default_input_device().map(|d| self.wrap_device(d))
+    }
+
+    /// Find the first device whose name contains `name` (case-insensitive).
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str
+    ///   (Part of) the name of the device.
+    /// output : bool, optional
+    ///   Whether to search output devices (default) or input devices.
+    #[pyo3(name = "find_device", signature = (name, output = true))]
+    fn py_find_device(&self, name: &str, output: bool) -> PyResult<PyDevice> {
+        Ok(self.find_device(name, output)?)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyDevice {
+    #[getter(name)]
+    fn py_name(&self) -> String {
+        self.name()
+    }
+
+    #[getter(is_default_output)]
+    fn py_is_default_output(&self) -> bool {
+        self.is_default_output
+    }
+
+    #[getter(is_default_input)]
+    fn py_is_default_input(&self) -> bool {
+        self.is_default_input
+    }
+
+    /// Sample rates supported for output.
+    #[getter(supported_sample_rates)]
+    fn py_supported_sample_rates(&self) -> Vec<u32> {
+        self.supported_sample_rates(true)
+    }
+
+    /// Sample rates supported for input.
+    #[getter(supported_input_sample_rates)]
+    fn py_supported_input_sample_rates(&self) -> Vec<u32> {
+        self.supported_sample_rates(false)
+    }
+
+    /// Channel counts supported for output.
+    #[getter(supported_channels)]
+    fn py_supported_channels(&self) -> Vec<u16> {
+        self.supported_channels(true)
+    }
+
+    /// Channel counts supported for input.
+    #[getter(supported_input_channels)]
+    fn py_supported_input_channels(&self) -> Vec<u16> {
+        self.supported_channels(false)
+    }
+
+    /// The default output sample rate, if the device has outputs.
+    #[getter(default_sample_rate)]
+    fn py_default_sample_rate(&self) -> Option<u32> {
+        self.device.default_output_config().ok().map(|c| c.sample_rate().0)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Device(name={:?}, is_default_output={}, is_default_input={})",
+            self.name(),
+            self.is_default_output,
+            self.is_default_input
+        )
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyAudioObject {
     #[staticmethod]
@@ -128,40 +685,208 @@ impl PyAudioObject {
             audio_object: AudioObject::from_samples(buffer, sample_rate),
         }
     }
+
+    /// The frequency of a continuous tone in Hz. Changes take effect while the tone is playing.
+    #[getter]
+    fn get_frequency(&self) -> PyResult<f32> {
+        Ok(self.modulated_param("frequency")?.get())
+    }
+
+    #[setter]
+    fn set_frequency(&self, frequency: f32) -> PyResult<()> {
+        self.modulated_param("frequency")?.set(frequency);
+        Ok(())
+    }
+
+    /// The amplitude of a continuous tone or noise. Changes take effect while it is playing.
+    #[getter]
+    fn get_amplitude(&self) -> PyResult<f32> {
+        Ok(self.modulated_param("amplitude")?.get())
+    }
+
+    #[setter]
+    fn set_amplitude(&self, amplitude: f32) -> PyResult<()> {
+        self.modulated_param("amplitude")?.set(amplitude);
+        Ok(())
+    }
+
+    /// Ramp a parameter of a continuous tone or noise to a new value, e.g. for a frequency sweep.
+    /// The ramp is computed sample by sample in the audio callback.
+    ///
+    /// Parameters
+    /// ----------
+    /// parameter : str
+    ///   The parameter to ramp, either `"frequency"` or `"amplitude"`.
+    /// to : float
+    ///   The value at the end of the ramp.
+    /// duration : float
+    ///   The duration of the ramp in seconds.
+    /// curve : str, optional
+    ///   Either `"linear"` (default) or `"exponential"`.
+    /// start : Timestamp, optional
+    ///   When the ramp should start. Defaults to now.
+    #[pyo3(signature = (parameter, to, duration, curve = "linear", start = None))]
+    fn ramp(&self, parameter: &str, to: f32, duration: f64, curve: &str, start: Option<Timestamp>) -> PyResult<()> {
+        let curve = match curve {
+            "linear" => RampCurve::Linear,
+            "exponential" => RampCurve::Exponential,
+            _ => {
+                return Err(PsydkError::AudioError(format!(
+                    "Unknown ramp curve \"{curve}\". Use \"linear\" or \"exponential\"."
+                ))
+                .into())
+            }
+        };
+        let duration = std::time::Duration::try_from_secs_f64(duration).map_err(|_| {
+            PsydkError::ParameterError(format!(
+                "Invalid ramp duration {duration}, must be finite and non-negative"
+            ))
+        })?;
+        let start = start.map(|t| t.timestamp).unwrap_or_else(std::time::Instant::now);
+
+        self.modulated_param(parameter)?.ramp_to(to, duration, start, curve);
+        Ok(())
+    }
 }
 
+impl PyAudioObject {
+    /// The modulatable parameter with the given name, if this is a continuous generator.
+    fn modulated_param(&self, name: &str) -> PsydkResult<&ModulatedParam> {
+        let params: &GeneratorParams = self.audio_object.generator_params().ok_or_else(|| {
+            PsydkError::AudioError(
+                "Only continuous tones and noise (see `create_tone` and `create_noise`) can be modulated.".into(),
+            )
+        })?;
+
+        match name {
+            "frequency" if matches!(self.audio_object, AudioObject::Tone { .. }) => Ok(&params.frequency),
+            "amplitude" => Ok(&params.amplitude),
+            _ => Err(PsydkError::AudioError(format!(
+                "\"{name}\" can't be modulated on this audio object."
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
 pub(crate) fn get_host(py: Python) -> PyResult<PyHost> {
     // first, try to get __renderer_factory from the __globals__
-    let host = py
-        .eval(c_str!("__audio_host"), None, None)
-        .expect("No audio host found in function scope. Are you calling this function from a stimulus callback?");
+    let host = py.eval(c_str!("__audio_host"), None, None).map_err(|_| {
+        PsydkError::AudioError(
+            "No audio host found in function scope. Are you calling this function from a stimulus callback?".into(),
+        )
+    })?;
 
     // covert to Rust type
     // let renderer_factory = PyRendererFactory::extract_bound(renderer_factory).unwrap();
-    let host: PyHost = host.extract().unwrap();
+    let host: PyHost = host.extract()?;
     Ok(host)
 }
 
+/// List the names of the audio hosts that are available on this system.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "available_hosts")]
+pub fn py_available_hosts() -> Vec<String> {
+    timed_audio::cpal::available_hosts()
+        .iter()
+        .map(|id| id.name().to_string())
+        .collect()
+}
+
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "create_silence")]
 pub fn py_create_silence(py: Python, duration: f32) -> PyAudioObject {
     PyAudioObject::silence(std::time::Duration::from_secs_f32(duration))
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "create_white_noise")]
 pub fn py_create_white_noise(py: Python, amplitude: f32, duration: f32) -> PyAudioObject {
     PyAudioObject::white_noise(amplitude, duration)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "create_sine_wave")]
 pub fn py_create_sine_wave(py: Python, frequency: f32, volume: f32, duration: f32) -> PyAudioObject {
     PyAudioObject::sine_wave(frequency, volume, std::time::Duration::from_secs_f32(duration))
 }
 
+/// Create a sine tone that plays until it is stopped. Its frequency and amplitude can be changed
+/// or ramped while it plays.
+///
+/// Parameters
+/// ----------
+/// frequency : float
+///   The frequency in Hz.
+/// amplitude : float, optional
+///   The amplitude as a linear factor. Defaults to 1.0.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "create_tone", signature = (frequency, amplitude = 1.0))]
+pub fn py_create_tone(frequency: f32, amplitude: f32) -> PyAudioObject {
+    PyAudioObject {
+        audio_object: AudioObject::tone(frequency, amplitude),
+    }
+}
+
+/// Create white noise that plays until it is stopped. Its amplitude can be changed or ramped
+/// while it plays.
+///
+/// Parameters
+/// ----------
+/// amplitude : float, optional
+///   The amplitude as a linear factor. Defaults to 1.0.
+/// seed : int, optional
+///   Seed for the random number generator.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "create_noise", signature = (amplitude = 1.0, seed = None))]
+pub fn py_create_noise(amplitude: f32, seed: Option<u64>) -> PyAudioObject {
+    PyAudioObject {
+        audio_object: AudioObject::noise(amplitude, seed),
+    }
+}
+
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "create_from_samples")]
 pub fn py_create_from_samples(py: Python, samples: PyReadonlyArrayDyn<'_, f32>, sample_rate: u32) -> PyAudioObject {
     PyAudioObject::from_samples(samples, sample_rate)
 }
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Window {
+    /// Emit a pulse on the trigger channel of an audio stream at the onset of every presented
+    /// frame (see `Stream.set_trigger_channel`). The pulse is started when the onset has been
+    /// timestamped, so it follows the onset by the output latency of the stream, which is constant
+    /// up to one audio buffer and can be measured with `Stream.calibrate_latency`.
+    ///
+    /// Parameters
+    /// ----------
+    /// stream : Stream, optional
+    ///   The stream. If None, no more pulses are emitted.
+    #[pyo3(name = "set_audio_trigger")]
+    #[pyo3(signature = (stream))]
+    fn py_set_audio_trigger(&self, stream: Option<PyRef<PyStream>>) -> PyResult<()> {
+        let stream = match stream {
+            Some(stream) => {
+                let stream = stream.stream()?;
+                if stream.trigger_channel().is_none() {
+                    return Err(PsydkError::ParameterError(
+                        "The stream has no trigger channel, see `Stream.set_trigger_channel`".into(),
+                    )
+                    .into());
+                }
+                Some(stream.clone())
+            }
+            None => None,
+        };
+        self.with_state(|win_state| win_state.audio_trigger = stream)?;
+        Ok(())
+    }
+}
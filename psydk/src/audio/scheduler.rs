@@ -0,0 +1,417 @@
+use std::{
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyDictMethods},
+};
+use send_wrapper::SendWrapper;
+use timed_audio::{AudioObject, InputChunk, InputStream, Stream};
+
+use super::{PyAudioObject, PyInputStream, PyStream};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    input::EventKind,
+    time::{wait_until, TimelineEvent, Timestamp},
+    visual::window::Window,
+};
+
+/// Time between starting a schedule and the first possible onset, so that all sounds can be
+/// scheduled before the first one is due.
+const SCHEDULE_LEAD_TIME: Duration = Duration::from_millis(100);
+
+/// A sound that is played at a given time, optionally followed by a response window.
+#[derive(Debug, Clone)]
+pub struct AudioScheduleItem {
+    pub audio: AudioObject,
+    /// Onset relative to the start of the schedule.
+    pub onset: Duration,
+    /// Responses are accepted from `start` to `end` after the onset.
+    pub response_window: Option<(Duration, Duration)>,
+}
+
+/// A response that was collected while an audio schedule was running.
+#[derive(Debug, Clone)]
+pub struct AudioResponse {
+    pub time: Instant,
+    /// Where the response came from, e.g. "key_press" or "voice".
+    pub source: String,
+    /// The key or button, if any.
+    pub label: Option<String>,
+    /// The index of the item whose response window contains the response.
+    pub item: Option<usize>,
+    /// Time since the onset of that item.
+    pub rt: Option<Duration>,
+}
+
+/// Detects the onset of a vocal response as the first sample above a threshold. After a
+/// detection, the input needs to drop below the threshold for a whole buffer before the next one.
+struct VoiceKey {
+    threshold: f32,
+    armed: bool,
+}
+
+impl VoiceKey {
+    fn process(&mut self, chunk: &InputChunk, sample_rate: u32, channels: usize) -> Option<Instant> {
+        if !self.armed {
+            self.armed = chunk.data.iter().all(|s| s.abs() < self.threshold);
+            return None;
+        }
+        let index = chunk.data.iter().position(|s| s.abs() >= self.threshold)?;
+        self.armed = false;
+        let frame = (index / channels.max(1)) as f64;
+        Some(chunk.time + Duration::from_secs_f64(frame / sample_rate as f64))
+    }
+}
+
+/// Timing and responses of an audio schedule run.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct AudioScheduleReport {
+    /// The time each item was scheduled to reach the output.
+    pub onsets: Vec<Instant>,
+    /// All collected responses in the order they were given.
+    pub responses: Vec<AudioResponse>,
+}
+
+/// Plays sounds at scheduled times on the audio stream's clock and collects responses, without
+/// returning to Python in between. Does not need a window.
+#[derive(Debug, Clone, Default)]
+pub struct AudioScheduler {
+    pub items: Vec<AudioScheduleItem>,
+}
+
+impl AudioScheduler {
+    /// Play all items on `stream`. Responses are collected from `window` (events of the given
+    /// kinds) and from `voice_key` (an input stream and a threshold), if given.
+    pub fn run(
+        &self,
+        stream: &Stream,
+        window: Option<&Window>,
+        response_kinds: &[EventKind],
+        voice_key: Option<(&InputStream, f32)>,
+    ) -> PsydkResult<AudioScheduleReport> {
+        if self.items.is_empty() {
+            return Err(PsydkError::ParameterError("The schedule is empty".into()));
+        }
+
+        // subscribe to responses before the first sound is played
+        let voice = voice_key.map(|(input, threshold)| {
+            let key = VoiceKey { threshold, armed: true };
+            (input.subscribe(), key, input.sample_rate(), input.channels() as usize)
+        });
+
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let mut handler_ids = Vec::new();
+        if let Some(window) = window {
+            for kind in response_kinds {
+                let collected = collected.clone();
+                handler_ids.push(window.add_event_handler(*kind, move |event| {
+                    collected.lock().unwrap().push(event);
+                    false
+                })?);
+            }
+        }
+
+        let start = Instant::now() + SCHEDULE_LEAD_TIME;
+        let mut onsets = Vec::with_capacity(self.items.len());
+        let mut end = start;
+        for item in &self.items {
+            let onset = start + item.onset;
+            stream.play_at(item.audio.clone(), onset);
+            onsets.push(onset);
+
+            let sound_end = onset + item.audio.duration();
+            let window_end = onset + item.response_window.map_or(Duration::ZERO, |(_, end)| end);
+            end = end.max(sound_end).max(window_end);
+        }
+
+        let mut responses = Vec::new();
+        match voice {
+            Some((receiver, mut key, sample_rate, channels)) => loop {
+                let now = Instant::now();
+                if now >= end {
+                    break;
+                }
+                match receiver.recv_timeout(end - now) {
+                    Ok(chunk) => {
+                        if let Some(time) = key.process(&chunk, sample_rate, channels) {
+                            responses.push((time, "voice".to_string(), None));
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        wait_until(end);
+                        break;
+                    }
+                }
+            },
+            None => wait_until(end),
+        }
+
+        if let Some(window) = window {
+            for id in handler_ids {
+                window.remove_event_handler(id);
+            }
+        }
+        for event in std::mem::take(&mut *collected.lock().unwrap()) {
+            let timeline_event = TimelineEvent::from(&event);
+            responses.push((timeline_event.time, timeline_event.kind, timeline_event.label));
+        }
+        responses.sort_by_key(|(time, _, _)| *time);
+
+        let responses = responses
+            .into_iter()
+            .map(|(time, source, label)| {
+                // the response belongs to the latest item whose response window contains it
+                let item = self
+                    .items
+                    .iter()
+                    .zip(&onsets)
+                    .enumerate()
+                    .filter(|(_, (item, onset))| match item.response_window {
+                        Some((window_start, window_end)) => {
+                            time >= **onset + window_start && time <= **onset + window_end
+                        }
+                        None => false,
+                    })
+                    .max_by_key(|(_, (_, onset))| **onset)
+                    .map(|(index, (_, onset))| (index, time - *onset));
+                AudioResponse {
+                    time,
+                    source,
+                    label,
+                    item: item.map(|(index, _)| index),
+                    rt: item.map(|(_, rt)| rt),
+                }
+            })
+            .collect();
+
+        Ok(AudioScheduleReport { onsets, responses })
+    }
+}
+
+/// Plays sounds at scheduled times and collects responses within response windows, e.g. for
+/// auditory oddball or gap detection paradigms.
+///
+/// The whole schedule runs in Rust on the clock of the audio stream, so Python garbage collection
+/// pauses do not affect the timing. No window is needed; responses can come from a voice key (an
+/// input stream) and, if a window is open, from keyboard or mouse events.
+///
+/// Parameters
+/// ----------
+/// items : list[tuple[AudioObject, float]] or list[tuple[AudioObject, float, tuple[float, float]]], optional
+///     The items of the schedule as (audio, onset) or (audio, onset, response_window), with times
+///     in seconds. Onsets are relative to the start of the schedule and response windows are
+///     relative to the onset of the item.
+#[cfg(feature = "python")]
+#[pyclass(name = "AudioScheduler")]
+#[derive(Debug, Clone, Default)]
+pub struct PyAudioScheduler(pub AudioScheduler);
+
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+enum PyAudioScheduleItem {
+    WithWindow(PyAudioObject, f64, (f64, f64)),
+    WithoutWindow(PyAudioObject, f64),
+}
+
+fn to_duration(seconds: f64, name: &str) -> PsydkResult<Duration> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| {
+        PsydkError::ParameterError(format!(
+            "Invalid {name} {seconds}, must be a non-negative number of seconds"
+        ))
+    })
+}
+
+fn audio_schedule_item(
+    audio: PyAudioObject,
+    onset: f64,
+    response_window: Option<(f64, f64)>,
+) -> PsydkResult<AudioScheduleItem> {
+    let response_window = match response_window {
+        Some((start, end)) if end < start => {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid response window ({start}, {end}), the end must not be before the start"
+            )))
+        }
+        Some((start, end)) => Some((
+            to_duration(start, "response window start")?,
+            to_duration(end, "response window end")?,
+        )),
+        None => None,
+    };
+    if audio.audio_object.generator_params().is_some() {
+        return Err(PsydkError::ParameterError(
+            "Continuous tones and noise can't be scheduled, as they never end".into(),
+        ));
+    }
+    Ok(AudioScheduleItem {
+        audio: audio.audio_object,
+        onset: to_duration(onset, "onset")?,
+        response_window,
+    })
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyAudioScheduler {
+    #[new]
+    #[pyo3(signature = (items = Vec::new()))]
+    fn __new__(items: Vec<PyAudioScheduleItem>) -> PyResult<Self> {
+        let items = items
+            .into_iter()
+            .map(|item| match item {
+                PyAudioScheduleItem::WithWindow(audio, onset, window) => {
+                    audio_schedule_item(audio, onset, Some(window))
+                }
+                PyAudioScheduleItem::WithoutWindow(audio, onset) => audio_schedule_item(audio, onset, None),
+            })
+            .collect::<PsydkResult<Vec<_>>>()?;
+        Ok(Self(AudioScheduler { items }))
+    }
+
+    /// Add a sound to the schedule.
+    ///
+    /// Parameters
+    /// ----------
+    /// audio : AudioObject
+    ///     The sound to play. Continuous tones and noise can't be scheduled, as they never end.
+    /// onset : float
+    ///     The onset in seconds, relative to the start of the schedule.
+    /// response_window : tuple[float, float], optional
+    ///     The window (start, end) in seconds after the onset in which responses are assigned to
+    ///     this sound.
+    #[pyo3(name = "add")]
+    #[pyo3(signature = (audio, onset, response_window = None))]
+    fn py_add(&mut self, audio: PyAudioObject, onset: f64, response_window: Option<(f64, f64)>) -> PyResult<()> {
+        self.0.items.push(audio_schedule_item(audio, onset, response_window)?);
+        Ok(())
+    }
+
+    /// Play the schedule. Blocks until the last sound has ended and the last response window has
+    /// closed.
+    ///
+    /// Parameters
+    /// ----------
+    /// stream : Stream
+    ///     The audio stream to play the sounds on.
+    /// input_stream : InputStream, optional
+    ///     If given, vocal responses are detected as the first sample above `threshold`.
+    /// threshold : float, optional
+    ///     The level (linear, 0.0 to 1.0) of the voice key. Defaults to 0.1.
+    /// window : Window, optional
+    ///     If given, events of the kinds in `responses` are collected from this window.
+    /// responses : list[EventKind], optional
+    ///     The kinds of window events that are collected as responses. Defaults to key presses.
+    ///
+    /// Returns
+    /// -------
+    /// AudioScheduleReport
+    ///     The onsets of all sounds and the collected responses.
+    #[pyo3(name = "run")]
+    #[pyo3(signature = (stream, input_stream = None, threshold = 0.1, window = None, responses = vec![EventKind::KeyPress]))]
+    fn py_run(
+        &self,
+        stream: PyRef<'_, PyStream>,
+        input_stream: Option<PyRef<'_, PyInputStream>>,
+        threshold: f32,
+        window: Option<Window>,
+        responses: Vec<EventKind>,
+        py: Python,
+    ) -> PyResult<AudioScheduleReport> {
+        let scheduler = self.0.clone();
+        let stream = stream.stream()?.clone();
+        let input_stream = input_stream.as_ref().map(|input| &input.stream);
+        let args = SendWrapper::new((scheduler, stream, window, input_stream));
+        let report = py
+            .allow_threads(move || {
+                let (scheduler, stream, window, input_stream) = &*args;
+                let voice_key = input_stream.map(|input| (input, threshold));
+                scheduler.run(stream, window.as_ref(), &responses, voice_key)
+            })
+            .map_err(PyErr::from)?;
+        Ok(report)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.items.len()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl AudioScheduleReport {
+    /// The time each sound was scheduled to reach the output.
+    #[getter(onsets)]
+    fn py_onsets(&self) -> Vec<Timestamp> {
+        self.onsets.iter().map(|onset| (*onset).into()).collect()
+    }
+
+    /// The collected responses as dictionaries with the timestamp, the source ("voice" or the
+    /// kind of window event), the key or button, the index of the sound whose response window
+    /// contains the response, and the response time in seconds relative to that sound.
+    #[getter(responses)]
+    fn py_responses<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.responses
+            .iter()
+            .map(|response| {
+                let dict = PyDict::new(py);
+                dict.set_item("timestamp", Timestamp::from(response.time))?;
+                dict.set_item("source", &response.source)?;
+                dict.set_item("label", &response.label)?;
+                dict.set_item("item", response.item)?;
+                dict.set_item("rt", response.rt.map(|rt| rt.as_secs_f64()))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Add the onsets of all sounds and all responses to a timeline. Onsets are labelled with the
+    /// index of the sound.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///     The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for (item, onset) in self.onsets.iter().enumerate() {
+            timeline.0.add(TimelineEvent {
+                kind: "audio_onset".to_string(),
+                time: *onset,
+                label: Some(item.to_string()),
+                data: Vec::new(),
+            });
+        }
+
+        for response in &self.responses {
+            let mut data = Vec::new();
+            if let Some(item) = response.item {
+                data.push(("item".to_string(), item.to_string()));
+            }
+            if let Some(rt) = response.rt {
+                data.push(("rt".to_string(), rt.as_secs_f64().to_string()));
+            }
+            timeline.0.add(TimelineEvent {
+                kind: response.source.clone(),
+                time: response.time,
+                label: response.label.clone(),
+                data,
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "AudioScheduleReport(items={}, responses={})",
+            self.onsets.len(),
+            self.responses.len()
+        )
+    }
+}
@@ -0,0 +1,347 @@
+//! A minimal C ABI for the presentation engine, e.g. for MATLAB (through a MEX wrapper) or Julia
+//! (through `ccall`). The declarations are in `include/psydk.h`.
+//!
+//! The API mirrors `run_experiment`: `psydk_run` runs the event loop on the calling thread and calls
+//! the experiment function on a separate thread, with a context that windows are created from. All
+//! other functions must be called from within the experiment function. Functions that can fail
+//! return 0 on success and -1 on failure, and `psydk_last_error` returns the error message.
+//! Timestamps are seconds since the UNIX epoch.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{c_char, c_void, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    time::Instant,
+};
+
+use crate::{
+    app::App,
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    input::{Event, EventReceiver},
+    time::Timestamp,
+    visual::{
+        color::LinRgba,
+        geometry::{Shape, Size, Transformation2D},
+        stimuli::{
+            shape::{GradientType, ShapeParams, ShapeStimulus},
+            DynamicStimulus, StrokeStyle,
+        },
+        window::Window,
+    },
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The experiment function passed to `psydk_run`. Returns 0 on success.
+pub type PsydkExperimentFn = extern "C" fn(context: *const PsydkContext, user_data: *mut c_void) -> i32;
+
+/// The context of a running experiment.
+pub struct PsydkContext(ExperimentContext);
+
+/// A window, together with the key presses that have not been polled yet.
+pub struct PsydkWindow {
+    window: Window,
+    receiver: EventReceiver,
+    key_presses: VecDeque<(String, Instant)>,
+}
+
+/// A stimulus that can be presented.
+pub struct PsydkStimulus(DynamicStimulus);
+
+/// A raw pointer that is handed to the experiment thread. The caller of `psydk_run` guarantees that
+/// `user_data` can be used from there.
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into `on_error` and a message for `psydk_last_error`.
+fn ffi_call<T>(on_error: T, f: impl FnOnce() -> PsydkResult<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            on_error
+        }
+        Err(_) => {
+            set_last_error("psydk panicked".into());
+            on_error
+        }
+    }
+}
+
+fn ffi_status(f: impl FnOnce() -> PsydkResult<()>) -> i32 {
+    ffi_call(-1, || f().map(|_| 0))
+}
+
+fn null_error(name: &str) -> PsydkError {
+    PsydkError::ParameterError(format!("`{name}` must not be NULL"))
+}
+
+fn shape_stimulus(shape: Shape, fill_color: LinRgba) -> PsydkResult<*mut PsydkStimulus> {
+    let params = ShapeParams {
+        shape,
+        x: Size::Pixels(0.0),
+        y: Size::Pixels(0.0),
+        fill_color,
+        gradient_angle: 0.0,
+        stroke_style: StrokeStyle::None,
+        stroke_color: LinRgba::default(),
+        stroke_width: Size::Pixels(0.0),
+        alpha: None,
+    };
+    let stimulus = ShapeStimulus::new(
+        params,
+        None,
+        GradientType::Linear,
+        None,
+        None,
+        Transformation2D::Identity(),
+    )?;
+    Ok(Box::into_raw(Box::new(PsydkStimulus(DynamicStimulus::new(stimulus)))))
+}
+
+/// The message of the last error on the calling thread, or NULL if there was none. The string is
+/// valid until the next call that fails on the same thread.
+#[no_mangle]
+pub extern "C" fn psydk_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// The current time.
+#[no_mangle]
+pub extern "C" fn psydk_now() -> f64 {
+    Timestamp::from(Instant::now()).unix()
+}
+
+/// Run an experiment. Blocks until `experiment` returns. Must be called from the main thread on
+/// macOS.
+///
+/// # Safety
+///
+/// `experiment` must be a valid function pointer and `user_data` must be usable from another thread
+/// for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_run(experiment: Option<PsydkExperimentFn>, user_data: *mut c_void) -> i32 {
+    ffi_status(|| {
+        let experiment = experiment.ok_or_else(|| null_error("experiment"))?;
+        let user_data = UserData(user_data);
+
+        App::new()?.run_experiment(move |ctx| {
+            // move the wrapper as a whole, not just the (non-Send) pointer inside it
+            let user_data = user_data;
+            let context = PsydkContext(ctx);
+            match experiment(&context, user_data.0) {
+                0 => Ok(()),
+                code => Err(PsydkError::CustomError(format!(
+                    "The experiment function returned {code}"
+                ))),
+            }
+        })
+    })
+}
+
+/// Create a fullscreen window on the given monitor (0 is the first monitor). Returns NULL on
+/// failure. The window must be released with `psydk_window_free`.
+///
+/// # Safety
+///
+/// `context` must be the context passed to the experiment function.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_create_window(context: *const PsydkContext, monitor: u32) -> *mut PsydkWindow {
+    ffi_call(ptr::null_mut(), || {
+        let context = context.as_ref().ok_or_else(|| null_error("context"))?;
+        let window = context.0.create_default_window(true, Some(monitor), None, false)?;
+        let receiver = window.create_event_receiver();
+        Ok(Box::into_raw(Box::new(PsydkWindow {
+            window,
+            receiver,
+            key_presses: VecDeque::new(),
+        })))
+    })
+}
+
+/// Close and release a window.
+///
+/// # Safety
+///
+/// `window` must be NULL or a window returned by `psydk_create_window` that has not been released.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_window_free(window: *mut PsydkWindow) {
+    if !window.is_null() {
+        let window = Box::from_raw(window);
+        window.window.close();
+    }
+}
+
+/// Create a filled circle centered at (`x`, `y`), in pixels relative to the center of the window.
+/// Colors are linear RGBA in [0, 1]. Returns NULL on failure. The stimulus must be released with
+/// `psydk_stimulus_free`.
+#[no_mangle]
+pub extern "C" fn psydk_circle(x: f32, y: f32, radius: f32, r: f32, g: f32, b: f32, a: f32) -> *mut PsydkStimulus {
+    ffi_call(ptr::null_mut(), || {
+        let shape = Shape::Circle {
+            x: Size::Pixels(x),
+            y: Size::Pixels(y),
+            radius: Size::Pixels(radius),
+        };
+        shape_stimulus(shape, LinRgba::new(r, g, b, a))
+    })
+}
+
+/// Create a filled rectangle centered at (`x`, `y`), see `psydk_circle`.
+#[no_mangle]
+pub extern "C" fn psydk_rectangle(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32,
+) -> *mut PsydkStimulus {
+    ffi_call(ptr::null_mut(), || {
+        let shape = Shape::Rectangle {
+            x: Size::Pixels(x - width / 2.0),
+            y: Size::Pixels(y - height / 2.0),
+            width: Size::Pixels(width),
+            height: Size::Pixels(height),
+        };
+        shape_stimulus(shape, LinRgba::new(r, g, b, a))
+    })
+}
+
+/// Release a stimulus.
+///
+/// # Safety
+///
+/// `stimulus` must be NULL or a stimulus that has not been released.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_stimulus_free(stimulus: *mut PsydkStimulus) {
+    if !stimulus.is_null() {
+        drop(Box::from_raw(stimulus));
+    }
+}
+
+/// Present `n_stimuli` stimuli (drawn in order) for `n_frames` refresh intervals, on the background
+/// color of the window. If `onset` is not NULL, the onset of the first frame is written to it.
+///
+/// # Safety
+///
+/// `window` must be a valid window and `stimuli` must point to `n_stimuli` valid stimuli (it may be
+/// NULL if `n_stimuli` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn psydk_present(
+    window: *mut PsydkWindow,
+    stimuli: *const *const PsydkStimulus,
+    n_stimuli: usize,
+    n_frames: u32,
+    onset: *mut f64,
+) -> i32 {
+    ffi_status(|| {
+        let window = window.as_mut().ok_or_else(|| null_error("window"))?;
+        let stimuli = match n_stimuli {
+            0 => &[][..],
+            _ if stimuli.is_null() => return Err(null_error("stimuli")),
+            _ => std::slice::from_raw_parts(stimuli, n_stimuli),
+        };
+
+        let mut frame = window.window.get_frame()?;
+        for stimulus in stimuli {
+            let stimulus = stimulus.as_ref().ok_or_else(|| null_error("stimulus"))?;
+            frame.add(&stimulus.0);
+        }
+
+        let report = window
+            .window
+            .present(&mut frame, Some(n_frames.max(1)), None, false, None)?;
+        if let Some(onset) = onset.as_mut() {
+            *onset = report.onset().map_or(f64::NAN, |t| Timestamp::from(t).unix());
+        }
+        Ok(())
+    })
+}
+
+/// Pop the oldest key press that has not been polled yet. Returns 1 and writes the name of the key
+/// (e.g. "a", "Space" or "Escape", truncated to `key_len` bytes including the terminating NUL) and
+/// its timestamp if there was a key press, 0 if there was none, and -1 on failure.
+///
+/// # Safety
+///
+/// `window` must be a valid window, `key` must be NULL or point to `key_len` writable bytes, and
+/// `timestamp` must be NULL or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_poll_key(
+    window: *mut PsydkWindow,
+    key: *mut c_char,
+    key_len: usize,
+    timestamp: *mut f64,
+) -> i32 {
+    ffi_call(-1, || {
+        let window = window.as_mut().ok_or_else(|| null_error("window"))?;
+        for event in window.receiver.poll().iter() {
+            if let Event::KeyPress { key, timestamp, .. } = event {
+                window.key_presses.push_back((key.clone(), timestamp.timestamp));
+            }
+        }
+
+        let Some((name, time)) = window.key_presses.pop_front() else {
+            return Ok(0);
+        };
+
+        if !key.is_null() && key_len > 0 {
+            let n = name.len().min(key_len - 1);
+            ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, key, n);
+            *key.add(n) = 0;
+        }
+        if let Some(timestamp) = timestamp.as_mut() {
+            *timestamp = Timestamp::from(time).unix();
+        }
+        Ok(1)
+    })
+}
+
+/// Discard all key presses that have not been polled yet.
+///
+/// # Safety
+///
+/// `window` must be a valid window.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_flush_keys(window: *mut PsydkWindow) -> i32 {
+    ffi_status(|| {
+        let window = window.as_mut().ok_or_else(|| null_error("window"))?;
+        window.receiver.flush();
+        window.key_presses.clear();
+        Ok(())
+    })
+}
+
+/// The refresh rate of the monitor the window is on, in Hz, or a negative value on failure.
+///
+/// # Safety
+///
+/// `window` must be a valid window.
+#[no_mangle]
+pub unsafe extern "C" fn psydk_refresh_rate(window: *const PsydkWindow) -> f64 {
+    ffi_call(-1.0, || {
+        let window = window.as_ref().ok_or_else(|| null_error("window"))?;
+        window
+            .window
+            .get_current_refresh_rate()
+            .ok_or_else(|| PsydkError::MonitorError("Failed to get the refresh rate of the monitor".into()))
+    })
+}
@@ -0,0 +1,195 @@
+//! The `psydk` command, which runs an experiment script with options that are set by the lab rather
+//! than by the script, e.g. the monitor to use or a session identifier.
+//!
+//! The options are stored for the whole process and picked up by `run_experiment` and
+//! `create_default_window`, so scripts don't need to parse arguments themselves.
+
+use std::sync::OnceLock;
+
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyList};
+
+use crate::{
+    app::App,
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+};
+
+const USAGE: &str = "\
+usage: psydk [options] <script.py | experiment.toml | experiment.yaml> [script arguments...]
+
+Run a psydk experiment script, or an experiment described in a TOML or YAML file.
+
+options:
+  --dry-run             Load the script and check that the graphics adapter and renderer can be
+                        set up, but don't open a window or run the experiment
+  --timing-check [N]    Instead of running the experiment, present N blank frames (default 300) and
+                        report the measured refresh rate and detected timing problems
+  --monitor M           The monitor (index) for windows created with create_default_window
+  --session ID          A session identifier, available as ExperimentContext.session
+  --seed N              The seed of the session random number generator, e.g. to reproduce the
+                        counterbalancing of an earlier session
+  -h, --help            Show this message";
+
+/// The default number of frames presented by `--timing-check`.
+const TIMING_CHECK_FRAMES: u32 = 300;
+
+/// Options given to the `psydk` command.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    /// Only check that the experiment can be set up.
+    pub dry_run: bool,
+    /// Measure the refresh rate with this many frames instead of running the experiment.
+    pub timing_check: Option<u32>,
+    /// The monitor to use for windows, overriding the monitor requested by the script.
+    pub monitor: Option<u32>,
+    /// A session identifier.
+    pub session: Option<String>,
+    /// The seed of the session random number generator.
+    pub seed: Option<u64>,
+}
+
+static LAUNCH_OPTIONS: OnceLock<LaunchOptions> = OnceLock::new();
+
+/// The options of the `psydk` command, or `None` if the script was not started by it.
+pub fn launch_options() -> Option<&'static LaunchOptions> {
+    LAUNCH_OPTIONS.get()
+}
+
+impl LaunchOptions {
+    /// Parse the arguments before the script, returning the options, the script, and the arguments
+    /// for the script. Returns `Ok(None)` if help was requested.
+    pub fn parse(args: &[String]) -> PsydkResult<Option<(Self, String, Vec<String>)>> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.iter().peekable();
+
+        let value = |flag: &str, value: Option<&String>| {
+            value
+                .cloned()
+                .ok_or_else(|| PsydkError::ParameterError(format!("{flag} requires a value")))
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--dry-run" => options.dry_run = true,
+                "--timing-check" => {
+                    // the number of frames is optional
+                    let n_frames = match args.peek().map(|next| next.parse::<u32>()) {
+                        Some(Ok(n_frames)) => {
+                            args.next();
+                            n_frames
+                        }
+                        _ => TIMING_CHECK_FRAMES,
+                    };
+                    options.timing_check = Some(n_frames);
+                }
+                "--monitor" => {
+                    let monitor = value(arg, args.next())?;
+                    options.monitor = Some(
+                        monitor
+                            .parse()
+                            .map_err(|_| PsydkError::ParameterError(format!("Invalid monitor index: {monitor}")))?,
+                    );
+                }
+                "--session" => options.session = Some(value(arg, args.next())?),
+                "--seed" => {
+                    let seed = value(arg, args.next())?;
+                    options.seed = Some(
+                        seed.parse()
+                            .map_err(|_| PsydkError::ParameterError(format!("Invalid seed: {seed}")))?,
+                    );
+                }
+                flag if flag.starts_with('-') => {
+                    return Err(PsydkError::ParameterError(format!("Unknown option: {flag}")));
+                }
+                script => {
+                    if options.dry_run && options.timing_check.is_some() {
+                        return Err(PsydkError::ParameterError(
+                            "--dry-run and --timing-check cannot be combined".into(),
+                        ));
+                    }
+                    return Ok(Some((options, script.to_string(), args.cloned().collect())));
+                }
+            }
+        }
+
+        Err(PsydkError::ParameterError("No script given".into()))
+    }
+}
+
+/// Present blank frames on a window and print the measured refresh rate, see `--timing-check`.
+pub fn timing_check(ctx: &ExperimentContext, n_frames: u32) -> PsydkResult<()> {
+    // uses the monitor given with `--monitor`
+    let window = ctx.create_default_window(true, None, None, false)?;
+    let measurement = window.measure_refresh_rate(n_frames)?;
+    window.close();
+
+    let rate = |rate: Option<f64>| rate.map_or("unknown".to_string(), |rate| format!("{rate:.2} Hz"));
+    println!("Timing check ({} frames)", n_frames);
+    println!("  reported refresh rate: {}", rate(measurement.reported_rate));
+    println!("  measured refresh rate: {}", rate(measurement.measured_rate()));
+    println!("  jitter (SD):           {:.3} ms", measurement.jitter() * 1000.0);
+
+    let warnings = measurement.warnings();
+    if warnings.is_empty() {
+        println!("No timing problems were detected.");
+    }
+    for warning in warnings {
+        println!("  warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// Entry point of the `psydk` command.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "_cli_main")]
+pub fn py_cli_main(py: Python) -> PyResult<()> {
+    let sys = py.import("sys")?;
+    let argv: Vec<String> = sys.getattr("argv")?.extract()?;
+
+    let Some((options, script, script_args)) = LaunchOptions::parse(argv.get(1..).unwrap_or_default())? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    LAUNCH_OPTIONS
+        .set(options)
+        .map_err(|_| PsydkError::CustomError("The psydk command can only be run once per process".into()))?;
+
+    // descriptions are run directly, without a script
+    let path = std::path::PathBuf::from(&script);
+    if matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("toml" | "yaml" | "yml")
+    ) {
+        let options = launch_options().cloned().unwrap_or_default();
+        let description = crate::design::ExperimentDescription::load(&path)?;
+        if options.dry_run {
+            println!(
+                "Dry run: the description defines {} stimuli and {} trials, not running it",
+                description.stimuli.len(),
+                description.trials.len()
+            );
+            return Ok(());
+        }
+
+        let mut app = App::new()?;
+        py.allow_threads(move || {
+            app.run_experiment(move |ctx| match options.timing_check {
+                Some(n_frames) => timing_check(&ctx, n_frames),
+                None => crate::design::run_file(&ctx, &path).map(|_| ()),
+            })
+        })?;
+        return Ok(());
+    }
+
+    // the script sees itself as the program, as when started with `python script.py`
+    let argv = std::iter::once(script.clone()).chain(script_args).collect::<Vec<_>>();
+    sys.setattr("argv", PyList::new(py, argv)?)?;
+
+    py.import("runpy")?
+        .call_method1("run_path", (script, py.None(), "__main__"))?;
+    Ok(())
+}
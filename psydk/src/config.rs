@@ -1,3 +1,5 @@
+use strum::EnumString;
+
 #[derive(Debug, Clone)]
 pub struct ExperimentConfig {
     /// pedantic mode
@@ -12,6 +14,16 @@ pub struct ExperimentConfig {
     pub display_color_format: DisplayColorFormat,
     /// display color encoding
     pub display_color_encoding: DisplayColorEncoding,
+    /// renderer backend
+    pub renderer_backend: RendererBackend,
+    /// graphics API used to talk to the GPU
+    pub graphics_backend: GraphicsBackend,
+    /// (part of the) name of the graphics adapter to use, case-insensitive
+    pub adapter: Option<String>,
+    /// power preference used when selecting the graphics adapter
+    pub power_preference: PowerPreference,
+    /// raise the priority of the experiment and audio threads
+    pub realtime_priority: bool,
 }
 
 impl Default for ExperimentConfig {
@@ -23,6 +35,125 @@ impl Default for ExperimentConfig {
             internal_color_encoding: InternalColorEncoding::default(),
             display_color_format: DisplayColorFormat::default(),
             display_color_encoding: DisplayColorEncoding::default(),
+            renderer_backend: RendererBackend::default(),
+            graphics_backend: GraphicsBackend::default(),
+            adapter: None,
+            power_preference: PowerPreference::default(),
+            realtime_priority: false,
+        }
+    }
+}
+
+/// Backend used to render scenes.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum RendererBackend {
+    #[default]
+    /// Skia, using the native GPU API of the platform (Metal or DirectX 12).
+    Skia,
+    /// Vello, a compute-based renderer running on top of wgpu.
+    Vello,
+    /// CPU rasterizer (tiny-skia). Selected automatically when no suitable GPU is available.
+    Software,
+}
+
+impl RendererBackend {
+    /// Whether the backend was compiled into this build of psydk.
+    pub fn is_available(&self) -> bool {
+        renderer::Backend::from(*self).is_available()
+    }
+
+    /// The cargo feature that compiles the backend into psydk.
+    pub fn feature(&self) -> &'static str {
+        match self {
+            RendererBackend::Skia => "skia",
+            RendererBackend::Vello => "vello",
+            RendererBackend::Software => "software",
+        }
+    }
+
+    /// The features of the backends that were compiled into this build of psydk.
+    pub fn enabled_features() -> Vec<&'static str> {
+        [RendererBackend::Skia, RendererBackend::Vello, RendererBackend::Software]
+            .into_iter()
+            .filter(|backend| backend.is_available())
+            .map(|backend| backend.feature())
+            .collect()
+    }
+}
+
+impl From<RendererBackend> for renderer::Backend {
+    fn from(backend: RendererBackend) -> Self {
+        match backend {
+            RendererBackend::Skia => renderer::Backend::Skia,
+            RendererBackend::Vello => renderer::Backend::Vello,
+            RendererBackend::Software => renderer::Backend::TinySkia,
+        }
+    }
+}
+
+/// Graphics API used by wgpu.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum GraphicsBackend {
+    #[default]
+    /// Use the native API of the platform (Metal on macOS, DirectX 12 on Windows, Vulkan on Linux).
+    Auto,
+    /// Vulkan.
+    Vulkan,
+    /// Metal (macOS and iOS only).
+    Metal,
+    /// DirectX 12 (Windows only).
+    Dx12,
+    /// OpenGL or OpenGL ES. Requires the `gl` feature.
+    Gl,
+}
+
+impl GraphicsBackend {
+    /// The wgpu backends that may be used for this graphics backend.
+    pub fn backends(&self) -> wgpu::Backends {
+        match self {
+            GraphicsBackend::Auto => {
+                let backends = if cfg!(any(target_os = "windows", target_os = "macos", target_os = "ios")) {
+                    wgpu::Backends::METAL | wgpu::Backends::DX12
+                } else {
+                    wgpu::Backends::VULKAN
+                };
+
+                // some machines only have stable OpenGL drivers, so we allow falling back to GL
+                if cfg!(feature = "gl") {
+                    backends | wgpu::Backends::GL
+                } else {
+                    backends
+                }
+            }
+            GraphicsBackend::Vulkan => wgpu::Backends::VULKAN,
+            GraphicsBackend::Metal => wgpu::Backends::METAL,
+            GraphicsBackend::Dx12 => wgpu::Backends::DX12,
+            GraphicsBackend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+/// Power preference used when selecting a graphics adapter.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum PowerPreference {
+    #[default]
+    /// Prefer a discrete GPU.
+    High,
+    /// Prefer an integrated GPU.
+    Low,
+    /// No preference.
+    None,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(value: PowerPreference) -> Self {
+        match value {
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::None => wgpu::PowerPreference::None,
         }
     }
 }
@@ -1,38 +1,101 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
+    str::FromStr,
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 
 use sysinfo::System;
 
 use derive_debug::Dbg;
+#[cfg(feature = "python")]
 use pyo3::{
     pyclass, pyfunction, pymethods,
-    types::{PyAnyMethods, PyDict, PyList, PyListMethods, PySequenceMethods, PyTuple, PyTupleMethods},
-    IntoPy, Py, PyAny, PyResult, Python,
+    types::{PyAnyMethods, PyDict, PyDictMethods, PyList, PyListMethods, PySequenceMethods, PyTuple, PyTupleMethods},
+    Bound, FromPyObject, IntoPy, Py, PyAny, PyResult, Python,
 };
 use renderer::{cosmic_text, renderer::SharedRendererState};
 use winit::event_loop::EventLoopProxy;
 
 use crate::{
     app::{App, ArcMutex, GPUState},
-    audio::{PyDevice, PyHost, PyStream},
+    audio::{PyDevice, PyHost, PyInputStream, PyStream},
     errors::{self, PsydkError, PsydkResult},
     git::PyRepository,
-    visual::window::Window,
+    visual::{
+        color::LinRgba,
+        geometry::{Anchor, Size, Transformation2D},
+        stimuli::{
+            text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+            DynamicStimulus,
+        },
+        window::{Frame, Window},
+    },
 };
 
+/// An audio device, given either as a `Device` or by (part of) its name.
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+pub enum AudioDeviceSelector {
+    Device(PyDevice),
+    Name(String),
+}
+
 #[derive(Dbg)]
 pub enum EventLoopAction {
-    CreateNewWindow(WindowOptions, GammaOptions, Sender<Window>),
+    CreateNewWindow(WindowOptions, GammaOptions, bool, Sender<PsydkResult<Window>>),
     GetAvailableMonitors(Sender<Vec<Monitor>>),
+    ShowFileDialog(FileDialog, Sender<Option<PathBuf>>),
+    GetClipboardText(Sender<PsydkResult<String>>),
+    SetClipboardText(String, Sender<PsydkResult<()>>),
+    CreateCustomCursor(winit::window::CustomCursorSource, Sender<winit::window::CustomCursor>),
     Exit(Option<errors::PsydkError>),
 }
 
-#[pyclass]
+/// A native dialog for choosing a file to open or save. Dialogs must be shown on the main thread,
+/// so they are shown by the event loop.
+#[derive(Debug, Clone, Default)]
+pub struct FileDialog {
+    /// Ask for a file to save instead of a file to open.
+    pub save: bool,
+    pub title: Option<String>,
+    /// The directory the dialog starts in.
+    pub directory: Option<PathBuf>,
+    /// The suggested file name (save dialogs only).
+    pub file_name: Option<String>,
+    /// Named groups of file extensions without the dot, e.g. `("Conditions", ["csv", "xlsx"])`.
+    pub filters: Vec<(String, Vec<String>)>,
+}
+
+impl FileDialog {
+    /// Show the dialog and block until it is closed. Returns `None` if it was cancelled. Must be
+    /// called on the main thread.
+    pub fn show(&self) -> Option<PathBuf> {
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(title) = &self.title {
+            dialog = dialog.set_title(title);
+        }
+        if let Some(directory) = &self.directory {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(file_name) = &self.file_name {
+            dialog = dialog.set_file_name(file_name);
+        }
+        for (name, extensions) in &self.filters {
+            dialog = dialog.add_filter(name, extensions);
+        }
+
+        match self.save {
+            true => dialog.save_file(),
+            false => dialog.pick_file(),
+        }
+    }
+}
+
+#[cfg_attr(feature = "python", pyclass)]
 pub struct PyRendererFactory(pub Box<dyn SharedRendererState>);
 
 // impl Clone for PyRendererFactory
@@ -58,9 +121,9 @@ impl PyRendererFactory {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct Monitor {
-    #[pyo3(get)]
+    #[cfg_attr(feature = "python", pyo3(get))]
     pub name: String,
     pub resolution: (u32, u32),
     pub handle: winit::monitor::MonitorHandle,
@@ -88,6 +151,7 @@ impl Monitor {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Monitor {
     #[getter]
@@ -110,7 +174,7 @@ pub struct GammaOptions {
 /// video mode that satisfies the provided constraints. See documentation of the
 /// variants for more information.
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum WindowOptions {
     Windowed {
         /// The width and height of the window in pixels. Defaults to 800x600
@@ -141,6 +205,16 @@ pub enum WindowOptions {
         monitor: Option<Monitor>,
         refresh_rate: Option<f64>,
     },
+    /// A transparent, borderless window that covers the monitor and stays on
+    /// top of other applications. Everything that is not drawn (including the
+    /// background, which is fully transparent) shows the desktop behind it.
+    Overlay {
+        /// The monitor to cover. Defaults to the primary monitor.
+        monitor: Option<Monitor>,
+        /// Whether mouse input passes through the window to the applications
+        /// below it.
+        click_through: bool,
+    },
 }
 
 impl WindowOptions {
@@ -150,13 +224,14 @@ impl WindowOptions {
             WindowOptions::FullscreenExact { monitor, .. } => monitor.as_ref(),
             WindowOptions::FullscreenHighestRefreshRate { monitor, .. } => monitor.as_ref(),
             WindowOptions::FullscreenHighestResolution { monitor, .. } => monitor.as_ref(),
+            WindowOptions::Overlay { monitor, .. } => monitor.as_ref(),
         }
     }
 }
 
 /// The ExperimentManager is available to the user in the experiment function.
 #[derive(Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct ExperimentContext {
     pub gpu_state: ArcMutex<GPUState>,
     event_loop_proxy: EventLoopProxy<()>,
@@ -175,6 +250,7 @@ impl ExperimentContext {
         renderer_factory: Arc<dyn SharedRendererState>,
         audio_host: Arc<timed_audio::cpal::Host>,
         font_manager: Arc<Mutex<cosmic_text::FontSystem>>,
+        config: crate::config::ExperimentConfig,
     ) -> Self {
         Self {
             gpu_state,
@@ -183,7 +259,7 @@ impl ExperimentContext {
             renderer_factory,
             audio_host,
             font_manager,
-            config: Arc::new(Mutex::new(crate::config::ExperimentConfig::default())),
+            config: Arc::new(Mutex::new(config)),
         }
     }
 
@@ -216,6 +292,12 @@ impl ExperimentContext {
         Ok(())
     }
 
+    pub fn audio_host(&self) -> PyHost {
+        PyHost {
+            host: self.audio_host.clone(),
+        }
+    }
+
     pub fn renderer_factory(&self) -> &Arc<dyn SharedRendererState> {
         &self.renderer_factory
     }
@@ -224,36 +306,57 @@ impl ExperimentContext {
     /// a new UserEvent to the event loop and wait until the winit window
     /// has been created. Then it will setup the wgpu device and surface and
     /// return a new Window object.
-    pub fn create_window(&self, window_options: &WindowOptions, gamma_options: GammaOptions) -> Window {
+    pub fn create_window(
+        &self,
+        window_options: &WindowOptions,
+        gamma_options: GammaOptions,
+        timing_critical: bool,
+    ) -> PsydkResult<Window> {
         // set up window by dispatching a new CreateNewWindow action
         let (sender, receiver) = channel();
-        let action = EventLoopAction::CreateNewWindow(window_options.clone(), gamma_options, sender);
+        let action = EventLoopAction::CreateNewWindow(window_options.clone(), gamma_options, timing_critical, sender);
 
         // send action
-        self.action_sender.send(action).unwrap();
+        self.action_sender
+            .send(action)
+            .map_err(|_| PsydkError::WindowCreationError("The event loop is no longer running".into()))?;
         self.event_loop_proxy.send_event(());
 
         // wait for response
-        let mut window = receiver.recv().expect("Failed to create window");
+        let mut window = receiver
+            .recv()
+            .map_err(|_| PsydkError::WindowCreationError("The event loop is no longer running".into()))??;
 
         // set the config (this could be done in the event loop, should we need it there)
         window.config = self.config.clone();
         log::debug!("New window successfully created");
 
-        window
+        Ok(window)
     }
 
     /// Create a new window. This is a convenience function that creates a
     /// window with the default options.
-    pub fn create_default_window(&self, fullscreen: bool, monitor: Option<u32>, gamma: Option<GammaOptions>) -> Window {
+    pub fn create_default_window(
+        &self,
+        fullscreen: bool,
+        monitor: Option<u32>,
+        gamma: Option<GammaOptions>,
+        timing_critical: bool,
+    ) -> PsydkResult<Window> {
         // select monitor 1 if available
         // find all monitors available
 
+        // the monitor given to the `psydk` command takes precedence over the one requested by the script
+        let monitor = crate::cli::launch_options()
+            .and_then(|options| options.monitor)
+            .or(monitor);
+
         let monitors = self.get_available_monitors();
-        // get the second monitor if available, otherwise use the first one
-        let monitor = monitors
-            .get(monitor.unwrap_or(0) as usize)
-            .unwrap_or(monitors.first().expect("No monitor found - this should not happen"));
+        let first_monitor = monitors
+            .first()
+            .ok_or_else(|| PsydkError::MonitorError("No monitor found. Is a display connected?".into()))?;
+        // get the selected monitor if available, otherwise use the first one
+        let monitor = monitors.get(monitor.unwrap_or(0) as usize).unwrap_or(first_monitor);
 
         let gamma_options = gamma.unwrap_or_else(|| GammaOptions {
             encode_gamma: true,
@@ -266,9 +369,74 @@ impl ExperimentContext {
                 refresh_rate: None,
             },
             gamma_options,
+            timing_critical,
         )
     }
 
+    /// Create a transparent overlay window that covers a monitor and stays on top of other
+    /// applications. The monitor given to the `psydk` command takes precedence, as for
+    /// `create_default_window`.
+    pub fn create_overlay_window(
+        &self,
+        monitor: Option<u32>,
+        click_through: bool,
+        gamma: Option<GammaOptions>,
+    ) -> PsydkResult<Window> {
+        let monitor = crate::cli::launch_options()
+            .and_then(|options| options.monitor)
+            .or(monitor);
+
+        let monitors = self.get_available_monitors();
+        let monitor = match monitor {
+            Some(index) => Some(monitors.get(index as usize).cloned().ok_or_else(|| {
+                PsydkError::MonitorError(format!(
+                    "There is no monitor {index} ({} monitors found)",
+                    monitors.len()
+                ))
+            })?),
+            None => monitors.first().cloned(),
+        };
+
+        let gamma_options = gamma.unwrap_or(GammaOptions {
+            encode_gamma: true,
+            lut: None,
+        });
+
+        self.create_window(&WindowOptions::Overlay { monitor, click_through }, gamma_options, false)
+    }
+
+    /// Send an action to the event loop and wait for its response.
+    fn dispatch<R>(&self, action: impl FnOnce(Sender<R>) -> EventLoopAction) -> PsydkResult<R> {
+        let (sender, receiver) = channel();
+        let no_event_loop = || PsydkError::CustomError("The event loop is no longer running".into());
+        self.action_sender.send(action(sender)).map_err(|_| no_event_loop())?;
+        self.event_loop_proxy.send_event(());
+        receiver.recv().map_err(|_| no_event_loop())
+    }
+
+    /// Show a file dialog and wait until it is closed. Returns `None` if it was cancelled.
+    pub fn ask_file(&self, dialog: FileDialog) -> PsydkResult<Option<PathBuf>> {
+        self.dispatch(|sender| EventLoopAction::ShowFileDialog(dialog, sender))
+    }
+
+    /// The text on the clipboard.
+    pub fn clipboard_text(&self) -> PsydkResult<String> {
+        self.dispatch(EventLoopAction::GetClipboardText)?
+    }
+
+    /// Put text on the clipboard.
+    pub fn set_clipboard_text(&self, text: String) -> PsydkResult<()> {
+        self.dispatch(|sender| EventLoopAction::SetClipboardText(text, sender))?
+    }
+
+    /// Turn an image into a cursor of the platform.
+    pub fn create_custom_cursor(
+        &self,
+        source: winit::window::CustomCursorSource,
+    ) -> PsydkResult<winit::window::CustomCursor> {
+        self.dispatch(|sender| EventLoopAction::CreateCustomCursor(source, sender))
+    }
+
     /// Retrive available monitors.
     pub fn get_available_monitors(&self) -> Vec<Monitor> {
         let (sender, receiver) = channel();
@@ -282,6 +450,101 @@ impl ExperimentContext {
         receiver.recv().unwrap()
     }
 
+    /// Show the index, name, resolution, and refresh rate of every monitor full-screen on that
+    /// monitor for `duration` (or until escape is pressed). Returns the monitors in the order of
+    /// their indices.
+    pub fn identify_monitors(&self, duration: Duration) -> PsydkResult<Vec<Monitor>> {
+        let monitors = self.get_available_monitors();
+
+        let mut windows = Vec::with_capacity(monitors.len());
+        for (index, monitor) in monitors.iter().enumerate() {
+            let window = self.create_window(
+                &WindowOptions::FullscreenHighestResolution {
+                    monitor: Some(monitor.clone()),
+                    refresh_rate: None,
+                },
+                GammaOptions {
+                    encode_gamma: true,
+                    lut: None,
+                },
+                false,
+            )?;
+            let frame = self.monitor_identification_frame(&window, index, monitor)?;
+            windows.push((window, frame));
+        }
+
+        let mut receivers = windows
+            .iter()
+            .map(|(window, _)| window.create_event_receiver())
+            .collect::<Vec<_>>();
+        let start = Instant::now();
+        'present: while start.elapsed() < duration {
+            for (window, frame) in windows.iter_mut() {
+                // monitors whose window was closed are skipped
+                if window.with_state(|_| ()).is_ok() {
+                    window.present(frame, None, None, false, None)?;
+                }
+            }
+            for receiver in receivers.iter_mut() {
+                if receiver.poll().key_pressed("Escape") {
+                    break 'present;
+                }
+            }
+        }
+
+        for (window, _) in windows {
+            window.close();
+        }
+        Ok(monitors)
+    }
+
+    /// A frame that shows the index and properties of `monitor` in large text.
+    fn monitor_identification_frame(&self, window: &Window, index: usize, monitor: &Monitor) -> PsydkResult<Frame> {
+        let (size, coordinate_system) = window.with_state(|state| {
+            state.bg_color = LinRgba::new(0.0, 0.0, 0.0, 1.0);
+            (state.size, state.coordinate_system)
+        })?;
+        let height = size.height as f32;
+
+        let refresh_rate = monitor
+            .refresh_rate()
+            .map_or("unknown refresh rate".to_string(), |rate| format!("{rate:.2} Hz"));
+        let details = format!(
+            "{}\n{} x {} px, {}",
+            monitor.name(),
+            monitor.resolution.0,
+            monitor.resolution.1,
+            refresh_rate
+        );
+
+        let mut frame = window.get_frame()?;
+        for (text, y, font_size) in [
+            (index.to_string(), -height / 8.0, height / 3.0),
+            (details, height / 5.0, height / 24.0),
+        ] {
+            let (x, y) = coordinate_system.from_scene(0.0, y, size);
+            let text = TextStimulus::new(
+                Size::Pixels(x),
+                Size::Pixels(y),
+                &text,
+                TextAlignment::Center,
+                TextDirection::Ltr,
+                TextOrientation::Horizontal,
+                Anchor::Center,
+                Size::Pixels(font_size),
+                &[],
+                FontWeight::Bold,
+                LinRgba::new(1.0, 1.0, 1.0, 1.0),
+                1.0,
+                0.0,
+                Transformation2D::Identity(),
+                self,
+            );
+            frame.add(&DynamicStimulus::new(text));
+        }
+        Ok(frame)
+    }
+
     pub fn get_repository(&self) -> PsydkResult<Option<gix::Repository>> {
         // get the current directory
         let mut current_dir = std::env::current_dir().map_err(|e| errors::PsydkError::IOError(e))?;
@@ -313,12 +576,33 @@ impl ExperimentContext {
         );
         info
     }
+
+    /// Information about the graphics adapter, driver, and backends in use.
+    pub fn gpu_info(&self) -> HashMap<String, String> {
+        let adapter_info = self.gpu_state.lock().unwrap().adapter.get_info();
+        let config = self.config.lock().unwrap();
+
+        let mut info = HashMap::new();
+        info.insert("adapter_name".to_string(), adapter_info.name);
+        info.insert("adapter_vendor".to_string(), format!("0x{:04x}", adapter_info.vendor));
+        info.insert("adapter_device".to_string(), format!("0x{:04x}", adapter_info.device));
+        info.insert("device_type".to_string(), format!("{:?}", adapter_info.device_type));
+        info.insert("driver".to_string(), adapter_info.driver);
+        info.insert("driver_info".to_string(), adapter_info.driver_info);
+        info.insert("graphics_backend".to_string(), adapter_info.backend.to_string());
+        info.insert(
+            "renderer_backend".to_string(),
+            format!("{:?}", config.renderer_backend).to_lowercase(),
+        );
+        info
+    }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl ExperimentContext {
     #[pyo3(name = "create_default_window")]
-    #[pyo3(signature = (fullscreen = false, monitor = None, encode_gamma=true, lut_img_path = None))]
+    #[pyo3(signature = (fullscreen = false, monitor = None, encode_gamma=true, lut_img_path = None, timing_critical = false))]
     /// Create a new window. This is a convenience function that creates a
     /// window with the default options.
     ///
@@ -334,6 +618,11 @@ impl ExperimentContext {
     ///   Whether to create a fullscreen window. Defaults to `false`.
     /// monitor : int, optional
     ///   The index of the monitor to use. Defaults to 0.
+    /// timing_critical : bool, optional
+    ///   Apply platform hints that reduce interference of the desktop compositor with frame
+    ///   timing: exclusive fullscreen and MMCSS scheduling of the compositor on Windows, display
+    ///   sync without Core Animation transactions on macOS, and compositor bypass on X11. Defaults
+    ///   to `false`.
     ///
     /// Returns
     /// -------
@@ -345,12 +634,13 @@ impl ExperimentContext {
         monitor: Option<u32>,
         encode_gamma: bool,
         lut_img_path: Option<String>,
-    ) -> Window {
+        timing_critical: bool,
+    ) -> PyResult<Window> {
         let gamma_options = if let Some(path) = lut_img_path {
             let img = renderer::image::io::Reader::open(path)
-                .unwrap()
+                .map_err(PsydkError::IOError)?
                 .decode()
-                .unwrap()
+                .map_err(PsydkError::ImageError)?
                 .into_rgb8();
             GammaOptions {
                 encode_gamma,
@@ -363,14 +653,111 @@ impl ExperimentContext {
             }
         };
 
-        self.create_default_window(fullscreen, monitor, Some(gamma_options))
+        Ok(self.create_default_window(fullscreen, monitor, Some(gamma_options), timing_critical)?)
+    }
+
+    /// Create a transparent window that covers a monitor and stays on top of other applications,
+    /// e.g. to show calibration markers, a photodiode patch, or areas of interest on top of a
+    /// video call or another program.
+    ///
+    /// The background of the window is fully transparent (set `bg_color` to make it opaque), so
+    /// only what is drawn is visible. Partially transparent stimuli are blended with the desktop by
+    /// the compositor, without gamma correction. Because the compositor is always involved, the
+    /// timing of an overlay is less precise than that of a fullscreen window.
+    ///
+    /// Transparency depends on the platform: it is supported on Windows and macOS and on Linux with
+    /// a compositing window manager (X11) or on Wayland. Where it is not supported, the background
+    /// is black. Click-through is not supported on Wayland by all compositors, nor on X11 without
+    /// the XFixes extension.
+    ///
+    /// Parameters
+    /// ----------
+    /// monitor : int, optional
+    ///   The index of the monitor to cover. Defaults to 0.
+    /// click_through : bool, optional
+    ///   Whether mouse input passes through the window to the applications below it. Can be
+    ///   changed later with `Window.click_through`. Defaults to `True`.
+    /// encode_gamma : bool, optional
+    ///   Whether to encode the colors with the sRGB transfer function. Defaults to `True`.
+    ///
+    /// Returns
+    /// -------
+    /// Window
+    ///  The new window.
+    #[pyo3(name = "create_overlay_window")]
+    #[pyo3(signature = (monitor = None, click_through = true, encode_gamma = true))]
+    fn py_create_overlay_window(
+        &self,
+        py: Python,
+        monitor: Option<u32>,
+        click_through: bool,
+        encode_gamma: bool,
+    ) -> PyResult<Window> {
+        let gamma_options = GammaOptions {
+            encode_gamma,
+            lut: None,
+        };
+        Ok(py.allow_threads(|| self.create_overlay_window(monitor, click_through, Some(gamma_options)))?)
     }
 
-    // Create a new audio stream
+    /// Create a new audio stream.
+    ///
+    /// Parameters
+    /// ----------
+    /// device : Device or str, optional
+    ///   The output device to use, either a `Device` or (part of) its name. Defaults to the default
+    ///   output device of the host.
+    /// host : str, optional
+    ///   The audio host to use, e.g. `"asio"` on Windows or `"jack"` (or `"pipewire"`) on Linux.
+    ///   Low-latency hosts avoid the additional, jittered latency of shared-mode system mixers.
+    ///   Defaults to the host of the experiment (see `get_audio_host`).
     #[pyo3(name = "create_audio_stream")]
-    #[pyo3(signature = (device = None))]
-    fn py_create_audio_stream(&self, device: Option<&PyDevice>) -> PyStream {
-        PyStream::new(&self.audio_host, device)
+    #[pyo3(signature = (device = None, host = None))]
+    fn py_create_audio_stream(&self, device: Option<AudioDeviceSelector>, host: Option<&str>) -> PyResult<PyStream> {
+        let host = match host {
+            Some(name) => PyHost::from_name(name)?,
+            None => self.audio_host(),
+        };
+        let device = match device {
+            Some(AudioDeviceSelector::Device(device)) => Some(device),
+            Some(AudioDeviceSelector::Name(name)) => Some(host.find_device(&name, true)?),
+            None => None,
+        };
+        Ok(PyStream::new(&host.host, device.as_ref())?)
+    }
+
+    /// Open an audio input stream, e.g. to monitor a microphone.
+    ///
+    /// Parameters
+    /// ----------
+    /// device : Device or str, optional
+    ///   The input device to use, either a `Device` or (part of) its name. Defaults to the default
+    ///   input device of the host.
+    /// host : str, optional
+    ///   The audio host to use. Defaults to the host of the experiment (see `get_audio_host`).
+    #[pyo3(name = "create_audio_input_stream")]
+    #[pyo3(signature = (device = None, host = None))]
+    fn py_create_audio_input_stream(
+        &self,
+        device: Option<AudioDeviceSelector>,
+        host: Option<&str>,
+    ) -> PyResult<PyInputStream> {
+        let host = match host {
+            Some(name) => PyHost::from_name(name)?,
+            None => self.audio_host(),
+        };
+        let device = match device {
+            Some(AudioDeviceSelector::Device(device)) => Some(device),
+            Some(AudioDeviceSelector::Name(name)) => Some(host.find_device(&name, false)?),
+            None => None,
+        };
+        Ok(PyInputStream::new(&host.host, device.as_ref())?)
+    }
+
+    /// The audio host used by the experiment. Use it to list and inspect audio devices.
+    #[pyo3(name = "get_audio_host")]
+    fn py_get_audio_host(&self) -> PyHost {
+        self.audio_host()
     }
 
     #[pyo3(name = "get_available_monitors")]
@@ -378,6 +765,132 @@ impl ExperimentContext {
         self.get_available_monitors()
     }
 
+    /// Show the index, name, resolution, and refresh rate of every monitor full-screen on that
+    /// monitor, to find out which monitor is which on a multi-monitor setup. The index is the one
+    /// to pass as `monitor` to `create_default_window`.
+    ///
+    /// Parameters
+    /// ----------
+    /// duration : float, optional
+    ///   How long to show the monitor information in seconds. Escape closes it earlier. Defaults to
+    ///   3 seconds.
+    ///
+    /// Returns
+    /// -------
+    /// list[Monitor]
+    ///   The monitors, in the order of their indices.
+    #[pyo3(name = "identify_monitors")]
+    #[pyo3(signature = (duration = 3.0))]
+    fn py_identify_monitors(&self, py: Python, duration: f64) -> PyResult<Vec<Monitor>> {
+        let context = send_wrapper::SendWrapper::new(self.clone());
+        let duration = Duration::try_from_secs_f64(duration).map_err(|_| {
+            PsydkError::ParameterError(format!("Invalid duration {duration}, must be finite and non-negative"))
+        })?;
+        Ok(py.allow_threads(move || context.identify_monitors(duration))?)
+    }
+
+    /// The session identifier given to the `psydk` command with `--session`, or None.
+    #[getter(session)]
+    fn py_session(&self) -> Option<String> {
+        crate::cli::launch_options().and_then(|options| options.session.clone())
+    }
+
+    /// The seed of the session random number generator, which randomizes generated designs. It is
+    /// given to the `psydk` command with `--seed`, or chosen randomly, and listed in the manifest.
+    #[getter(seed)]
+    fn py_seed(&self) -> u64 {
+        crate::utils::random::session_seed()
+    }
+
+    /// Ask the operator for a file to open, e.g. a condition file at the start of a session.
+    ///
+    /// Parameters
+    /// ----------
+    /// filters : dict[str, list[str]], optional
+    ///   Named groups of file extensions (without the dot) to choose from, e.g.
+    ///   `{"Conditions": ["csv", "xlsx"]}`.
+    /// title : str, optional
+    ///   The title of the dialog.
+    /// directory : str, optional
+    ///   The directory the dialog starts in.
+    ///
+    /// Returns
+    /// -------
+    /// str or None
+    ///   The chosen file, or None if the dialog was cancelled.
+    #[pyo3(name = "ask_open_file")]
+    #[pyo3(signature = (filters = None, title = None, directory = None))]
+    fn py_ask_open_file(
+        &self,
+        py: Python,
+        filters: Option<Bound<'_, PyDict>>,
+        title: Option<String>,
+        directory: Option<PathBuf>,
+    ) -> PyResult<Option<PathBuf>> {
+        let dialog = FileDialog {
+            save: false,
+            title,
+            directory,
+            file_name: None,
+            filters: file_filters(filters)?,
+        };
+        Ok(py.allow_threads(|| self.ask_file(dialog))?)
+    }
+
+    /// Ask the operator for a file to save to.
+    ///
+    /// Parameters
+    /// ----------
+    /// filters : dict[str, list[str]], optional
+    ///   Named groups of file extensions (without the dot), see `ask_open_file`.
+    /// title : str, optional
+    ///   The title of the dialog.
+    /// directory : str, optional
+    ///   The directory the dialog starts in.
+    /// file_name : str, optional
+    ///   The suggested file name.
+    ///
+    /// Returns
+    /// -------
+    /// str or None
+    ///   The chosen file, or None if the dialog was cancelled.
+    #[pyo3(name = "ask_save_file")]
+    #[pyo3(signature = (filters = None, title = None, directory = None, file_name = None))]
+    fn py_ask_save_file(
+        &self,
+        py: Python,
+        filters: Option<Bound<'_, PyDict>>,
+        title: Option<String>,
+        directory: Option<PathBuf>,
+        file_name: Option<String>,
+    ) -> PyResult<Option<PathBuf>> {
+        let dialog = FileDialog {
+            save: true,
+            title,
+            directory,
+            file_name,
+            filters: file_filters(filters)?,
+        };
+        Ok(py.allow_threads(|| self.ask_file(dialog))?)
+    }
+
+    /// The text on the clipboard.
+    #[pyo3(name = "get_clipboard")]
+    fn py_get_clipboard(&self, py: Python) -> PyResult<String> {
+        Ok(py.allow_threads(|| self.clipboard_text())?)
+    }
+
+    /// Put text on the clipboard.
+    ///
+    /// Parameters
+    /// ----------
+    /// text : str
+    ///   The text.
+    #[pyo3(name = "set_clipboard")]
+    fn py_set_clipboard(&self, py: Python, text: String) -> PyResult<()> {
+        Ok(py.allow_threads(|| self.set_clipboard_text(text))?)
+    }
+
     #[pyo3(name = "get_repository")]
     fn py_get_repository(&self) -> PsydkResult<Option<PyRepository>> {
         self.get_repository().map(|r| r.map(|r| r.into()))
@@ -388,6 +901,29 @@ impl ExperimentContext {
         Ok(self.system_info())
     }
 
+    #[pyo3(name = "gpu_info")]
+    fn py_gpu_info(&self) -> PyResult<HashMap<String, String>> {
+        Ok(self.gpu_info())
+    }
+
+    /// Report which threads had their priority raised (see the `realtime` argument of
+    /// `run_experiment`). Each entry contains the thread, the method used, whether it succeeded,
+    /// and details such as the error.
+    #[pyo3(name = "priority_report")]
+    fn py_priority_report(&self) -> Vec<HashMap<String, String>> {
+        timed_audio::realtime::reports()
+            .into_iter()
+            .map(|report| {
+                HashMap::from([
+                    ("thread".to_string(), report.thread),
+                    ("method".to_string(), report.method.to_string()),
+                    ("achieved".to_string(), report.achieved.to_string()),
+                    ("detail".to_string(), report.detail),
+                ])
+            })
+            .collect()
+    }
+
     #[pyo3(name = "load_system_fonts")]
     fn py_load_system_fonts(&self) -> PyResult<()> {
         self.load_system_fonts();
@@ -407,23 +943,162 @@ impl ExperimentContext {
     }
 }
 
+/// Convert the `filters` argument of the file dialogs, preserving the order of the groups.
+#[cfg(feature = "python")]
+fn file_filters(filters: Option<Bound<'_, PyDict>>) -> PyResult<Vec<(String, Vec<String>)>> {
+    let Some(filters) = filters else {
+        return Ok(Vec::new());
+    };
+    filters
+        .iter()
+        .map(|(name, extensions)| {
+            let extensions: Vec<String> = extensions.extract()?;
+            // accept ".csv" as well as "csv"
+            let extensions = extensions
+                .into_iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect();
+            Ok((name.extract()?, extensions))
+        })
+        .collect()
+}
+
+#[cfg(feature = "remote")]
+#[cfg(feature = "python")]
+#[pymethods]
+impl ExperimentContext {
+    /// Start a server that lets other machines control the experiment, e.g. from a scanner control
+    /// room. Clients connect with a WebSocket or a plain TCP socket and send JSON commands to
+    /// start, pause, resume, or abort the experiment, set parameters, and query its status.
+    ///
+    /// Parameters
+    /// ----------
+    /// host : str, optional
+    ///   The address to listen on. Defaults to "127.0.0.1", which only accepts connections from
+    ///   the same machine. Use "0.0.0.0" to accept connections from all interfaces, which
+    ///   requires a `token`.
+    /// port : int, optional
+    ///   The port to listen on. Defaults to 8765. Use 0 to pick a free port.
+    /// token : str, optional
+    ///   A shared secret that clients have to send as the `token` field of every request.
+    /// allowed_origins : list[str], optional
+    ///   The origins of web pages that may connect with a WebSocket, e.g.
+    ///   `["http://localhost:3000"]`. WebSocket connections from other web pages are refused, so
+    ///   that a page open in a browser cannot control the experiment.
+    ///
+    /// Returns
+    /// -------
+    /// ControlServer
+    ///   The server, which is used to wait for commands and to report the status.
+    #[pyo3(name = "start_control_server")]
+    #[pyo3(signature = (host = "127.0.0.1", port = 8765, token = None, allowed_origins = Vec::new()))]
+    fn py_start_control_server(
+        &self,
+        host: &str,
+        port: u16,
+        token: Option<String>,
+        allowed_origins: Vec<String>,
+    ) -> PyResult<crate::remote::PyControlServer> {
+        let server = crate::remote::ControlServer::start(&format!("{host}:{port}"), token, allowed_origins)?;
+        Ok(crate::remote::PyControlServer(server))
+    }
+}
+
 /// Runs your experiment function. This function will block the current thread
 /// until the experiment function returns!
 ///
+/// `run_experiment` can be called several times in the same interpreter session, e.g. to run
+/// sequential experiments. Windows and the graphics device of a run are released when it ends, and
+/// the event loop is reused by the next run. It must always be called from the same thread (on
+/// macOS, the main thread).
+///
 /// Parameters
 /// ----------
 /// experiment_fn : callable
 ///    The function that runs your experiment. This function should take a single argument, an instance of `ExperimentManager`, and should not return nothing.
+/// renderer : str, optional
+///    The renderer backend to use, either `"skia"` (default), `"vello"`, or `"software"`. The backend
+///    must have been enabled when psydk was built. If no suitable GPU is found, psydk falls back to
+///    the software renderer.
+/// backend : str, optional
+///    The graphics API to use, one of `"auto"` (default), `"vulkan"`, `"metal"`, `"dx12"`, or `"gl"`.
+/// adapter : str, optional
+///    Select the graphics adapter whose name contains this string (case-insensitive), e.g. `"Intel"`.
+///    By default, the adapter is selected based on `power_preference`.
+/// power_preference : str, optional
+///    Either `"high"` (default) to prefer a discrete GPU, `"low"` to prefer an integrated GPU, or `"none"`.
+/// realtime : bool, optional
+///    Raise the priority of the experiment thread (which presents frames) and the audio callbacks
+///    for the duration of the experiment (MMCSS on Windows, real-time scheduling on macOS and Linux).
+///    This reduces frame drops on busy machines, but may require elevated privileges. See
+///    `ExperimentContext.priority_report` for what was achieved. Defaults to False.
+#[cfg(feature = "python")]
 #[pyfunction]
-#[pyo3(name = "run_experiment", signature = (py_experiment_fn, *args, **kwargs))]
+#[pyo3(
+    name = "run_experiment",
+    signature = (py_experiment_fn, *args, renderer = None, backend = None, adapter = None, power_preference = None, realtime = false, **kwargs)
+)]
 pub fn py_run_experiment(
     py: Python,
     py_experiment_fn: Py<PyAny>,
     args: Py<PyTuple>,
+    renderer: Option<String>,
+    backend: Option<String>,
+    adapter: Option<String>,
+    power_preference: Option<String>,
+    realtime: bool,
     kwargs: Option<Py<PyDict>>,
 ) -> PyResult<()> {
+    let mut config = crate::config::ExperimentConfig::default();
+
+    if let Some(renderer) = renderer {
+        let backend = crate::config::RendererBackend::from_str(&renderer)
+            .map_err(|_| PsydkError::ParameterError(format!("Unknown renderer backend: {}", renderer)))?;
+
+        if !backend.is_available() {
+            return Err(PsydkError::ParameterError(format!(
+                "The {} renderer backend is not available in this build of psydk, as the `{}` feature is not \
+                 enabled. Enabled renderer features: {}",
+                renderer,
+                backend.feature(),
+                crate::config::RendererBackend::enabled_features().join(", ")
+            ))
+            .into());
+        }
+
+        config.renderer_backend = backend;
+    }
+
+    if let Some(backend) = backend {
+        config.graphics_backend = crate::config::GraphicsBackend::from_str(&backend)
+            .map_err(|_| PsydkError::ParameterError(format!("Unknown graphics backend: {}", backend)))?;
+    }
+
+    if let Some(power_preference) = power_preference {
+        config.power_preference = crate::config::PowerPreference::from_str(&power_preference)
+            .map_err(|_| PsydkError::ParameterError(format!("Unknown power preference: {}", power_preference)))?;
+    }
+
+    config.adapter = adapter;
+    config.realtime_priority = realtime;
+
     // create app
-    let mut app = App::new();
+    let mut app = App::new_with_config(config)?;
+
+    if let Some(options) = crate::cli::launch_options() {
+        if options.dry_run {
+            let adapter = app.gpu_state.lock().unwrap().adapter.get_info();
+            println!(
+                "Dry run: the experiment can be set up ({} on {}), not running it",
+                adapter.name, adapter.backend
+            );
+            return Ok(());
+        }
+        if let Some(n_frames) = options.timing_check {
+            py.allow_threads(move || app.run_experiment(move |ctx| crate::cli::timing_check(&ctx, n_frames)))?;
+            return Ok(());
+        }
+    }
 
     // set the __globals__ to make "_renderer_factory" available
     // this will allow functions to create renderer-specific objects
@@ -431,6 +1106,7 @@ pub fn py_run_experiment(
 
     let globals = PyDict::new(py);
     let renderer_factory = PyRendererFactory(app.shared_renderer_state.cloned());
+    let experiment_fn = py_experiment_fn.clone_ref(py);
 
     let rust_experiment_fn = move |em: ExperimentContext| -> Result<(), errors::PsydkError> {
         Python::with_gil(|py| -> _ {
@@ -455,14 +1131,136 @@ pub fn py_run_experiment(
             let em_as_seq = PyList::new(py, vec![em])?;
             let em_as_seq = em_as_seq.as_sequence();
 
-            let args = em_as_seq.concat(args_as_seq).unwrap();
-            let args = args.to_tuple().unwrap();
+            let args = em_as_seq.concat(args_as_seq)?;
+            let args = args.to_tuple()?;
 
             py_experiment_fn.call_bound(py, args, Some(&kwargs))
         })?;
         Ok(())
     };
 
-    py.allow_threads(move || app.run_experiment(rust_experiment_fn))?; // run the experiment
-    Ok(())
+    let result = py.allow_threads(move || app.run_experiment(rust_experiment_fn)); // run the experiment
+
+    // the context of this run must not be picked up by a later run
+    if let Ok(globals) = experiment_fn.getattr(py, "__globals__") {
+        let _ = globals.bind(py).del_item("_experiment_context");
+    }
+
+    Ok(result?)
+}
+
+/// Show a frame in a window, e.g. to design displays iteratively from Jupyter or an interactive
+/// Python session.
+///
+/// `draw` is called with a new window and must return the frame to show. Stimuli can be created
+/// inside `draw` just like in an experiment function. The frame is shown (and re-rendered, so
+/// animations run) until the window is closed, escape is pressed, or `duration` has elapsed. Each
+/// call opens a new window, so a display can be changed and previewed again from the next cell.
+///
+/// The preview window is a regular window, not a fullscreen window, so its timing is not
+/// representative of an experiment.
+///
+/// Parameters
+/// ----------
+/// draw : callable
+///   Called as `draw(window, *args, **kwargs)`, returns the `Frame` to show.
+/// *args : tuple
+///   Additional positional arguments for `draw`.
+/// width : int, optional
+///   The width of the window in pixels. Defaults to 800.
+/// height : int, optional
+///   The height of the window in pixels. Defaults to 600.
+/// duration : float, optional
+///   Close the preview after this many seconds. By default, the preview is shown until it is
+///   closed.
+/// **kwargs : dict
+///   Additional keyword arguments for `draw`.
+///
+/// Examples
+/// --------
+/// >>> def draw(window):
+/// ...     frame = window.get_frame()
+/// ...     frame.add(psydk.visual.stimuli.GaborStimulus(0, 0, "4deg", "1deg", "1deg"))
+/// ...     return frame
+/// >>> psydk.preview(draw)
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "preview", signature = (draw, *args, width = 800, height = 600, duration = None, **kwargs))]
+pub fn py_preview(
+    py: Python,
+    draw: Py<PyAny>,
+    args: Py<PyTuple>,
+    width: u32,
+    height: u32,
+    duration: Option<f64>,
+    kwargs: Option<Py<PyDict>>,
+) -> PyResult<()> {
+    let mut app = App::new_with_config(crate::config::ExperimentConfig::default())?;
+    let draw_fn = draw.clone_ref(py);
+
+    let rust_experiment_fn = move |em: ExperimentContext| -> Result<(), errors::PsydkError> {
+        let window = em.create_window(
+            &WindowOptions::Windowed {
+                resolution: Some((width, height)),
+            },
+            GammaOptions {
+                encode_gamma: true,
+                lut: None,
+            },
+            false,
+        )?;
+
+        let frame = Python::with_gil(|py| -> PyResult<Py<Frame>> {
+            let kwargs = if let Some(kwargs) = kwargs {
+                kwargs.into_bound(py)
+            } else {
+                PyDict::new(py)
+            };
+
+            // stimuli created in `draw` pick up the context from the globals
+            draw.getattr(py, "__globals__")?
+                .bind(py)
+                .downcast::<PyDict>()?
+                .set_item("_experiment_context", em.clone())?;
+
+            let mut all_args = vec![window.clone().into_py(py)];
+            all_args.extend(args.bind(py).iter().map(|arg| arg.unbind()));
+            let args = PyTuple::new(py, all_args)?;
+
+            draw.call_bound(py, args, Some(&kwargs))?
+                .extract(py)
+                .map_err(|_| PsydkError::ParameterError("The draw function must return a Frame".into()).into())
+        })?;
+
+        let mut receiver = window.create_event_receiver();
+        let start = std::time::Instant::now();
+        loop {
+            let result = Python::with_gil(|py| {
+                let mut frame = frame.borrow_mut(py);
+                window.present(&mut frame, None, None, true, None)
+            });
+
+            // the window was closed by the user
+            if window.with_state(|_| ()).is_err() {
+                break;
+            }
+            result?;
+
+            let done = duration.is_some_and(|duration| start.elapsed().as_secs_f64() >= duration);
+            if done || receiver.poll().key_pressed("Escape") {
+                break;
+            }
+        }
+
+        window.close();
+        Ok(())
+    };
+
+    let result = py.allow_threads(move || app.run_experiment(rust_experiment_fn));
+
+    if let Ok(globals) = draw_fn.getattr(py, "__globals__") {
+        let _ = globals.bind(py).del_item("_experiment_context");
+    }
+
+    Ok(result?)
 }
@@ -0,0 +1,502 @@
+//! Counterbalanced condition orders.
+//!
+//! A design crosses factors into conditions and orders them as a Latin square (so that the order
+//! of blocks is balanced across participants), in permuted blocks (every condition once per block,
+//! in random order), or randomly with a limit on how often a level may repeat in a row. Random
+//! designs are seeded from the session generator (see `utils::random`) unless a seed is given, and
+//! keep the seed, so they can be generated again.
+
+use std::{collections::BTreeMap, path::Path};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyInt},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    design::ParamValue,
+    errors::{PsydkError, PsydkResult},
+    utils::{manifest, random},
+};
+
+/// How often a random order is restarted before giving up on the constraints.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// A factor and its levels.
+#[derive(Debug, Clone)]
+pub struct Factor {
+    pub name: String,
+    pub levels: Vec<ParamValue>,
+}
+
+/// A trial of a design.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignTrial {
+    pub block: usize,
+    /// The index of the level of every factor.
+    pub levels: Vec<usize>,
+}
+
+/// All combinations of the levels of `factors`, as level indices. The last factor varies fastest.
+pub fn conditions(factors: &[Factor]) -> Vec<Vec<usize>> {
+    factors.iter().fold(vec![vec![]], |conditions, factor| {
+        conditions
+            .iter()
+            .flat_map(|condition| {
+                (0..factor.levels.len()).map(move |level| {
+                    let mut condition = condition.clone();
+                    condition.push(level);
+                    condition
+                })
+            })
+            .collect()
+    })
+}
+
+/// A Latin square of size `n`: every row is an order of `0..n`, and every number appears once in
+/// every column. If `balanced`, the square is a Williams design, in which every number is also
+/// followed by every other number equally often. For odd `n`, this takes `2n` rows.
+pub fn latin_square(n: usize, balanced: bool) -> Vec<Vec<usize>> {
+    if !balanced {
+        return (0..n)
+            .map(|row| (0..n).map(|column| (row + column) % n).collect())
+            .collect();
+    }
+
+    // the first row is 0, 1, n - 1, 2, n - 2, ..., and the others are shifted by the row
+    let first = (0..n)
+        .map(|column| match column % 2 {
+            1 => (column + 1) / 2,
+            _ => (n - column / 2) % n,
+        })
+        .collect::<Vec<_>>();
+    let mut rows = (0..n)
+        .map(|row| first.iter().map(|number| (number + row) % n).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    if n % 2 == 1 {
+        let reversed = rows
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect::<Vec<_>>();
+        rows.extend(reversed);
+    }
+    rows
+}
+
+/// Whether appending `condition` to `order` makes a run of the same level longer than allowed.
+fn exceeds_max_run(order: &[Vec<usize>], condition: &[usize], max_run: &[Option<usize>]) -> bool {
+    max_run.iter().enumerate().any(|(factor, max_run)| {
+        max_run.is_some_and(|max_run| {
+            let run = order
+                .iter()
+                .rev()
+                .take_while(|previous| previous[factor] == condition[factor])
+                .count();
+            run >= max_run
+        })
+    })
+}
+
+/// A generated design.
+#[derive(Debug, Clone)]
+pub struct Design {
+    pub factors: Vec<Factor>,
+    pub trials: Vec<DesignTrial>,
+    /// The seed the design was generated with, or `None` if it is not random.
+    pub seed: Option<u64>,
+}
+
+impl Design {
+    fn check_factors(factors: &[Factor]) -> PsydkResult<()> {
+        if factors.is_empty() {
+            return Err(PsydkError::ParameterError("A design needs at least one factor".into()));
+        }
+        for (index, factor) in factors.iter().enumerate() {
+            if factor.levels.is_empty() {
+                return Err(PsydkError::ParameterError(format!(
+                    "The factor \"{}\" has no levels",
+                    factor.name
+                )));
+            }
+            if factors[..index].iter().any(|other| other.name == factor.name) {
+                return Err(PsydkError::ParameterError(format!(
+                    "The factor \"{}\" is given twice",
+                    factor.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn rng(seed: Option<u64>) -> (StdRng, u64) {
+        let seed = seed.unwrap_or_else(random::next_seed);
+        (StdRng::seed_from_u64(seed), seed)
+    }
+
+    /// One block per condition, in the order of the row of a Latin square that belongs to
+    /// `participant`, with `repetitions` trials per block. Participants beyond the number of rows
+    /// start over with the first row.
+    pub fn latin_square(
+        factors: Vec<Factor>,
+        participant: usize,
+        balanced: bool,
+        repetitions: usize,
+    ) -> PsydkResult<Self> {
+        Self::check_factors(&factors)?;
+        let conditions = conditions(&factors);
+        let square = latin_square(conditions.len(), balanced);
+        let row = &square[participant % square.len()];
+
+        let trials = row
+            .iter()
+            .enumerate()
+            .flat_map(|(block, &condition)| {
+                let levels = &conditions[condition];
+                (0..repetitions).map(move |_| DesignTrial {
+                    block,
+                    levels: levels.clone(),
+                })
+            })
+            .collect();
+        Ok(Self {
+            factors,
+            trials,
+            seed: None,
+        })
+    }
+
+    /// `blocks` blocks that each contain every condition once, in random order.
+    pub fn permuted_blocks(factors: Vec<Factor>, blocks: usize, seed: Option<u64>) -> PsydkResult<Self> {
+        Self::check_factors(&factors)?;
+        let (mut rng, seed) = Self::rng(seed);
+        let conditions = conditions(&factors);
+
+        let mut trials = Vec::with_capacity(blocks * conditions.len());
+        for block in 0..blocks {
+            let mut order = conditions.clone();
+            order.shuffle(&mut rng);
+            trials.extend(order.into_iter().map(|levels| DesignTrial { block, levels }));
+        }
+        Ok(Self {
+            factors,
+            trials,
+            seed: Some(seed),
+        })
+    }
+
+    /// Every condition `repetitions` times in random order, such that no level of a factor occurs
+    /// more than `max_run` times in a row (given for every factor, `None` for no limit).
+    pub fn constrained(
+        factors: Vec<Factor>,
+        repetitions: usize,
+        max_run: &[Option<usize>],
+        seed: Option<u64>,
+    ) -> PsydkResult<Self> {
+        Self::check_factors(&factors)?;
+        if max_run.len() != factors.len() {
+            return Err(PsydkError::ParameterError(format!(
+                "Expected a maximum run length for each of the {} factors, got {}",
+                factors.len(),
+                max_run.len()
+            )));
+        }
+        if max_run.contains(&Some(0)) {
+            return Err(PsydkError::ParameterError(
+                "The maximum run length must be at least 1".into(),
+            ));
+        }
+
+        let (mut rng, seed) = Self::rng(seed);
+        let pool = conditions(&factors)
+            .into_iter()
+            .flat_map(|condition| std::iter::repeat(condition).take(repetitions))
+            .collect::<Vec<_>>();
+
+        // conditions are drawn one by one from those that don't exceed a run length, starting over
+        // if none is left
+        for _ in 0..MAX_ATTEMPTS {
+            let mut remaining = pool.clone();
+            let mut order = Vec::with_capacity(pool.len());
+            while !remaining.is_empty() {
+                let allowed = (0..remaining.len())
+                    .filter(|&i| !exceeds_max_run(&order, &remaining[i], max_run))
+                    .collect::<Vec<_>>();
+                let Some(&i) = allowed.choose(&mut rng) else {
+                    break;
+                };
+                order.push(remaining.swap_remove(i));
+            }
+
+            if remaining.is_empty() {
+                let trials = order
+                    .into_iter()
+                    .map(|levels| DesignTrial { block: 0, levels })
+                    .collect();
+                return Ok(Self {
+                    factors,
+                    trials,
+                    seed: Some(seed),
+                });
+            }
+        }
+
+        Err(PsydkError::ParameterError(format!(
+            "No order satisfies the maximum run lengths after {MAX_ATTEMPTS} attempts"
+        )))
+    }
+
+    /// The levels of a trial by factor.
+    pub fn levels(&self, trial: &DesignTrial) -> impl Iterator<Item = (&str, &ParamValue)> {
+        self.factors
+            .iter()
+            .zip(&trial.levels)
+            .map(|(factor, &level)| (factor.name.as_str(), &factor.levels[level]))
+    }
+
+    /// Write the design to a CSV file with the "trial", the "block", and a column per factor.
+    pub fn to_csv(&self, path: &Path) -> PsydkResult<()> {
+        let partial = manifest::partial_path(path);
+        manifest::record_open(path);
+        let mut writer = csv::Writer::from_path(&partial).map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        let columns = ["trial".to_string(), "block".to_string()];
+        let columns = columns
+            .into_iter()
+            .chain(self.factors.iter().map(|factor| factor.name.clone()));
+        writer
+            .write_record(columns)
+            .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+        for (index, trial) in self.trials.iter().enumerate() {
+            let record = [index.to_string(), trial.block.to_string()]
+                .into_iter()
+                .chain(self.levels(trial).map(|(_, level)| level.to_string()));
+            writer
+                .write_record(record)
+                .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        manifest::commit(&partial, path)?;
+        manifest::record_complete(path, Some(self.trials.len() as u64));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "python")]
+fn extract_factors(factors: &Bound<'_, PyDict>) -> PyResult<Vec<Factor>> {
+    factors
+        .iter()
+        .map(|(name, levels)| {
+            Ok(Factor {
+                name: name.extract()?,
+                levels: levels.extract()?,
+            })
+        })
+        .collect()
+}
+
+/// A counterbalanced order of the conditions of an experiment, i.e. of all combinations of the
+/// levels of its factors.
+///
+/// Designs are created with `Design.latin_square`, `Design.permuted_blocks`, or
+/// `Design.constrained`. Random designs are seeded from the session random number generator (see
+/// `ExperimentContext.seed`) unless a seed is given, and keep their seed, so that they can be
+/// generated again.
+///
+/// Factors are given as a dictionary from names to lists of levels, e.g.
+/// `{"congruent": [True, False], "side": ["left", "right"]}`.
+#[cfg(feature = "python")]
+#[pyclass(name = "Design")]
+pub struct PyDesign(pub Design);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyDesign {
+    /// One block per condition, ordered by a row of a Latin square, so that every condition occurs
+    /// at every position equally often across participants.
+    ///
+    /// Parameters
+    /// ----------
+    /// factors : dict[str, list]
+    ///   The factors and their levels.
+    /// participant : int
+    ///   The participant (counting from 0), which selects the row of the square. Participants
+    ///   beyond the number of rows start over with the first row.
+    /// balanced : bool, optional
+    ///   Whether to use a balanced Latin square (a Williams design), in which every condition also
+    ///   follows every other condition equally often. For an odd number of conditions, a balanced
+    ///   square has twice as many rows as conditions. Defaults to True.
+    /// repetitions : int, optional
+    ///   The number of trials per block. Defaults to 1.
+    ///
+    /// Returns
+    /// -------
+    /// Design
+    ///   The design.
+    #[staticmethod]
+    #[pyo3(name = "latin_square")]
+    #[pyo3(signature = (factors, participant, balanced = true, repetitions = 1))]
+    fn py_latin_square(
+        factors: &Bound<'_, PyDict>,
+        participant: usize,
+        balanced: bool,
+        repetitions: usize,
+    ) -> PyResult<Self> {
+        let factors = extract_factors(factors)?;
+        Ok(Self(Design::latin_square(factors, participant, balanced, repetitions)?))
+    }
+
+    /// Blocks that each contain every condition once, in random order.
+    ///
+    /// Parameters
+    /// ----------
+    /// factors : dict[str, list]
+    ///   The factors and their levels.
+    /// blocks : int, optional
+    ///   The number of blocks. Defaults to 1.
+    /// seed : int, optional
+    ///   The seed. Defaults to a seed drawn from the session random number generator.
+    ///
+    /// Returns
+    /// -------
+    /// Design
+    ///   The design.
+    #[staticmethod]
+    #[pyo3(name = "permuted_blocks")]
+    #[pyo3(signature = (factors, blocks = 1, seed = None))]
+    fn py_permuted_blocks(factors: &Bound<'_, PyDict>, blocks: usize, seed: Option<u64>) -> PyResult<Self> {
+        let factors = extract_factors(factors)?;
+        Ok(Self(Design::permuted_blocks(factors, blocks, seed)?))
+    }
+
+    /// Every condition a number of times in random order, with a limit on how often the same level
+    /// of a factor may occur in a row.
+    ///
+    /// Parameters
+    /// ----------
+    /// factors : dict[str, list]
+    ///   The factors and their levels.
+    /// repetitions : int, optional
+    ///   How often every condition occurs. Defaults to 1.
+    /// max_run : int or dict[str, int], optional
+    ///   The maximum number of trials in a row with the same level, for all factors or by factor.
+    ///   Defaults to no limit.
+    /// seed : int, optional
+    ///   The seed. Defaults to a seed drawn from the session random number generator.
+    ///
+    /// Returns
+    /// -------
+    /// Design
+    ///   The design.
+    #[staticmethod]
+    #[pyo3(name = "constrained")]
+    #[pyo3(signature = (factors, repetitions = 1, max_run = None, seed = None))]
+    fn py_constrained(
+        factors: &Bound<'_, PyDict>,
+        repetitions: usize,
+        max_run: Option<&Bound<'_, PyAny>>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let factors = extract_factors(factors)?;
+        let max_run = match max_run {
+            None => vec![None; factors.len()],
+            Some(max_run) if max_run.is_instance_of::<PyInt>() => vec![Some(max_run.extract()?); factors.len()],
+            Some(max_run) => {
+                let max_run: BTreeMap<String, usize> = max_run.extract()?;
+                if let Some(name) = max_run
+                    .keys()
+                    .find(|name| !factors.iter().any(|factor| &factor.name == *name))
+                {
+                    return Err(PsydkError::ParameterError(format!("Unknown factor \"{name}\"")).into());
+                }
+                factors
+                    .iter()
+                    .map(|factor| max_run.get(&factor.name).copied())
+                    .collect()
+            }
+        };
+        Ok(Self(Design::constrained(factors, repetitions, &max_run, seed)?))
+    }
+
+    /// The trials, as dictionaries with the "trial" index, the "block", and the level of every
+    /// factor.
+    #[getter(trials)]
+    fn py_trials<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .trials
+            .iter()
+            .enumerate()
+            .map(|(index, trial)| {
+                let dict = PyDict::new(py);
+                dict.set_item("trial", index)?;
+                dict.set_item("block", trial.block)?;
+                for (name, level) in self.0.levels(trial) {
+                    dict.set_item(name, level.to_py(py)?)?;
+                }
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// The names of the factors.
+    #[getter(factors)]
+    fn py_factors(&self) -> Vec<String> {
+        self.0.factors.iter().map(|factor| factor.name.clone()).collect()
+    }
+
+    /// The seed the design was generated with, or None if the design is not random.
+    #[getter(seed)]
+    fn py_seed(&self) -> Option<u64> {
+        self.0.seed
+    }
+
+    /// Write the design to a CSV file, with the "trial", the "block", and a column per factor.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///   The file to write to.
+    #[pyo3(name = "to_csv")]
+    fn py_to_csv(&self, path: std::path::PathBuf) -> PyResult<()> {
+        Ok(self.0.to_csv(&path)?)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.trials.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Design(factors={:?}, trials={}, seed={:?})",
+            self.py_factors(),
+            self.0.trials.len(),
+            self.0.seed
+        )
+    }
+}
+
+/// A Latin square, in which every number from 0 to n - 1 appears once in every row and once in
+/// every column, e.g. to counterbalance the order of n conditions across participants.
+///
+/// Parameters
+/// ----------
+/// n : int
+///   The size of the square.
+/// balanced : bool, optional
+///   Whether to return a balanced Latin square (a Williams design), in which every number also
+///   follows every other number equally often. For odd n, it has 2n rows. Defaults to True.
+///
+/// Returns
+/// -------
+/// list[list[int]]
+///   The rows of the square.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "latin_square")]
+#[pyo3(signature = (n, balanced = true))]
+pub fn py_latin_square(n: usize, balanced: bool) -> Vec<Vec<usize>> {
+    latin_square(n, balanced)
+}
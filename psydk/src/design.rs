@@ -0,0 +1,1035 @@
+//! Experiments described in a TOML or YAML file instead of code.
+//!
+//! A description defines named stimuli, a list of trials made up of timed items, and a mapping from
+//! keys to responses:
+//!
+//! ```toml
+//! randomize = true
+//! repetitions = 10
+//! iti = 1.0
+//! output = "results.csv"
+//! responses = { f = "left", j = "right" }
+//!
+//! [stimuli.fixation]
+//! type = "shape"
+//! shape = { type = "circle", radius = "0.2deg" }
+//! fill_color = [1.0, 1.0, 1.0]
+//!
+//! [stimuli.left]
+//! type = "text"
+//! text = "<"
+//! font_size = "2deg"
+//!
+//! [[trials]]
+//! name = "left"
+//! correct = "left"
+//! data = { condition = "congruent" }
+//! items = [
+//!     { stimuli = ["fixation"], duration = 0.5 },
+//!     { stimuli = ["left"], duration = 0.2, respond = true },
+//!     { duration = 1.5 },
+//! ]
+//! ```
+//!
+//! A `[breaks]` table adds a break screen (see `BreakScreen`) between repetitions, or every
+//! `every` trials:
+//!
+//! ```toml
+//! [breaks]
+//! min_rest = 30.0
+//! message = "Block {block} of {blocks} is done. Take a short break."
+//! ```
+//!
+//! A `[practice]` table adds practice trials (by default, the trials of the experiment) that are
+//! presented before the experiment, with feedback after every trial that has a correct response.
+//! The practice is repeated in blocks until the accuracy of a block reaches the `criterion`, for at
+//! most `max_blocks` blocks:
+//!
+//! ```toml
+//! [practice]
+//! criterion = 0.8
+//! max_blocks = 3
+//! feedback = { correct = ["well_done"], incorrect = ["wrong"], missed = ["too_slow"], duration = 0.5 }
+//!
+//! [[practice.trials]]
+//! correct = "left"
+//! items = [{ stimuli = ["left"], duration = 1.0, respond = true }]
+//! ```
+//!
+//! Stimuli are created by calling the stimulus class named by `type` (e.g. "text" or
+//! "TextStimulus") with the remaining keys as keyword arguments, and `shape` tables are passed to
+//! the shape function of the same name. Stimuli registered by plugins (see `plugins`) are used by
+//! the name they were registered under. Each trial is presented by the `Scheduler`, so the whole
+//! trial runs without returning to Python. Trials are randomized with the session random number
+//! generator, so the order can be reproduced with the seed given to the `psydk` command.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple},
+};
+use rand::seq::SliceRandom;
+use send_wrapper::SendWrapper;
+use serde::Deserialize;
+
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    input::{Event, EventKind},
+    time::Timestamp,
+    utils::{manifest, random},
+    visual::{
+        breaks::BreakScreen,
+        scheduler::{ScheduleItem, Scheduler},
+        stimuli::DynamicStimulus,
+        window::Window,
+    },
+};
+#[cfg(feature = "python")]
+use crate::{plugins, visual::stimuli::PyStimulus};
+
+/// A parameter value, as written in the description.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<ParamValue>),
+    Map(BTreeMap<String, ParamValue>),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Bool(value) => write!(f, "{value}"),
+            ParamValue::Int(value) => write!(f, "{value}"),
+            ParamValue::Float(value) => write!(f, "{value}"),
+            ParamValue::String(value) => write!(f, "{value}"),
+            ParamValue::List(values) => {
+                let values = values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+                write!(f, "[{}]", values.join(", "))
+            }
+            ParamValue::Map(values) => {
+                let values = values
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", values.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl ParamValue {
+    /// Convert to a Python object. Lists become tuples, as colors and sizes are expected as tuples.
+    pub(crate) fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self {
+            ParamValue::Bool(value) => value.into_pyobject(py)?.to_owned().into_any(),
+            ParamValue::Int(value) => value.into_pyobject(py)?.into_any(),
+            ParamValue::Float(value) => value.into_pyobject(py)?.into_any(),
+            ParamValue::String(value) => value.into_pyobject(py)?.into_any(),
+            ParamValue::List(values) => PyTuple::new(
+                py,
+                values
+                    .iter()
+                    .map(|value| value.to_py(py))
+                    .collect::<PyResult<Vec<_>>>()?,
+            )?
+            .into_any(),
+            ParamValue::Map(values) => params_to_kwargs(py, values)?.into_any(),
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for ParamValue {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        // bools are ints in Python, so they need to be checked first
+        if ob.is_instance_of::<PyBool>() {
+            return Ok(ParamValue::Bool(ob.extract()?));
+        }
+        if ob.is_instance_of::<PyInt>() {
+            return Ok(ParamValue::Int(ob.extract()?));
+        }
+        if ob.is_instance_of::<PyFloat>() {
+            return Ok(ParamValue::Float(ob.extract()?));
+        }
+        if ob.is_instance_of::<PyString>() {
+            return Ok(ParamValue::String(ob.extract()?));
+        }
+        if let Ok(values) = ob.downcast::<PyDict>() {
+            let mut map = BTreeMap::new();
+            for (key, value) in values.iter() {
+                map.insert(key.extract()?, value.extract()?);
+            }
+            return Ok(ParamValue::Map(map));
+        }
+        if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+            return Ok(ParamValue::List(
+                ob.try_iter()?.map(|value| value?.extract()).collect::<PyResult<_>>()?,
+            ));
+        }
+        Err(PsydkError::ParameterError(format!(
+            "Unsupported parameter value {ob}, expected a bool, number, string, list, or dict"
+        ))
+        .into())
+    }
+}
+
+#[cfg(feature = "python")]
+fn params_to_kwargs<'py>(py: Python<'py>, params: &BTreeMap<String, ParamValue>) -> PyResult<Bound<'py, PyDict>> {
+    let kwargs = PyDict::new(py);
+    for (key, value) in params {
+        kwargs.set_item(key, value.to_py(py)?)?;
+    }
+    Ok(kwargs)
+}
+
+/// A stimulus, given by its type and the arguments of its constructor.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StimulusDescription {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+/// A set of stimuli that is shown for a given duration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemDescription {
+    /// The names of the stimuli, drawn in order. A blank screen if empty.
+    #[serde(default)]
+    pub stimuli: Vec<String>,
+    /// The duration in seconds.
+    pub duration: f64,
+    /// Whether response times are measured from the onset of this item. Responses before it are
+    /// ignored. Defaults to the first item of the trial.
+    #[serde(default)]
+    pub respond: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrialDescription {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub items: Vec<ItemDescription>,
+    /// The mapping from keys to responses, overriding the mapping of the experiment.
+    #[serde(default)]
+    pub responses: Option<BTreeMap<String, String>>,
+    /// The correct response.
+    #[serde(default)]
+    pub correct: Option<String>,
+    /// How often the trial is repeated in each repetition of the experiment.
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// Additional columns of the results, e.g. the condition.
+    #[serde(default)]
+    pub data: BTreeMap<String, ParamValue>,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+/// Breaks between blocks of trials.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BreakDescription {
+    /// A break every this many trials. Defaults to a break after every repetition.
+    #[serde(default)]
+    pub every: Option<u32>,
+    /// The minimum rest in seconds, during which the break can't be ended.
+    #[serde(default = "default_min_rest")]
+    pub min_rest: f64,
+    /// The maximum rest in seconds, after which the experiment continues automatically.
+    #[serde(default)]
+    pub max_rest: Option<f64>,
+    /// The message, see `BreakScreen`.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// The keys that end the break.
+    #[serde(default)]
+    pub keys: Option<Vec<String>>,
+}
+
+fn default_min_rest() -> f64 {
+    30.0
+}
+
+impl BreakDescription {
+    fn screen(&self) -> PsydkResult<BreakScreen> {
+        let duration = |seconds: f64| {
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|_| PsydkError::ParameterError(format!("Invalid rest {seconds} of the breaks")))
+        };
+        let defaults = BreakScreen::default();
+        Ok(BreakScreen {
+            message: self.message.clone().unwrap_or(defaults.message),
+            min_rest: duration(self.min_rest)?,
+            max_rest: self.max_rest.map(duration).transpose()?,
+            keys: self.keys.clone().unwrap_or(defaults.keys),
+            ..defaults
+        })
+    }
+}
+
+/// An experiment, loaded from a TOML or YAML file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExperimentDescription {
+    #[serde(default)]
+    pub stimuli: BTreeMap<String, StimulusDescription>,
+    pub trials: Vec<TrialDescription>,
+    /// The mapping from keys to responses.
+    #[serde(default)]
+    pub responses: BTreeMap<String, String>,
+    /// Whether the order of the trials is shuffled (separately for each repetition).
+    #[serde(default)]
+    pub randomize: bool,
+    /// How often all trials are repeated.
+    #[serde(default = "default_repeat")]
+    pub repetitions: u32,
+    /// A blank interval after each trial in seconds.
+    #[serde(default)]
+    pub iti: f64,
+    /// A CSV file the results are written to, one row per trial.
+    #[serde(default)]
+    pub output: Option<String>,
+    /// Breaks between blocks of trials.
+    #[serde(default)]
+    pub breaks: Option<BreakDescription>,
+    /// Practice trials with feedback that are presented before the experiment.
+    #[serde(default)]
+    pub practice: Option<PracticeDescription>,
+}
+
+/// Practice trials that are repeated in blocks until the accuracy reaches a criterion.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PracticeDescription {
+    /// The practice trials. Defaults to the trials of the experiment.
+    #[serde(default)]
+    pub trials: Option<Vec<TrialDescription>>,
+    /// The proportion of correct responses (from 0 to 1) in a block that ends the practice.
+    /// Only trials with a correct response count.
+    #[serde(default)]
+    pub criterion: f64,
+    /// The maximum number of practice blocks.
+    #[serde(default = "default_max_blocks")]
+    pub max_blocks: u32,
+    /// Whether the experiment is aborted if the criterion is not reached in `max_blocks` blocks.
+    /// Otherwise, the experiment continues with a warning.
+    #[serde(default)]
+    pub abort_if_failed: bool,
+    #[serde(default)]
+    pub feedback: Option<FeedbackDescription>,
+}
+
+fn default_max_blocks() -> u32 {
+    5
+}
+
+/// The stimuli that are shown after each practice trial with a correct response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedbackDescription {
+    #[serde(default)]
+    pub correct: Vec<String>,
+    #[serde(default)]
+    pub incorrect: Vec<String>,
+    /// Shown instead of `incorrect` if there was no response.
+    #[serde(default)]
+    pub missed: Option<Vec<String>>,
+    /// How long the feedback is shown, in seconds.
+    #[serde(default = "default_feedback_duration")]
+    pub duration: f64,
+}
+
+fn default_feedback_duration() -> f64 {
+    0.5
+}
+
+/// The outcome of one trial.
+#[derive(Debug, Clone)]
+pub struct TrialResult {
+    /// The index of the trial in the description.
+    pub trial: usize,
+    pub name: Option<String>,
+    /// The practice block (counting from 0) for practice trials.
+    pub practice: Option<u32>,
+    /// The onset of the first item.
+    pub onset: Option<Timestamp>,
+    /// The first key that was mapped to a response, the response and the response time.
+    pub key: Option<String>,
+    pub response: Option<String>,
+    pub rt: Option<Duration>,
+    pub correct: Option<bool>,
+    /// The duration of the break before this trial, if there was one.
+    pub rest: Option<Duration>,
+    pub data: BTreeMap<String, ParamValue>,
+}
+
+impl ExperimentDescription {
+    /// Load a description from a `.toml`, `.yaml` or `.yml` file.
+    pub fn load(path: impl AsRef<Path>) -> PsydkResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let description: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content).map_err(|e| {
+                PsydkError::ParameterError(format!("Invalid experiment description {}: {e}", path.display()))
+            })?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(|e| {
+                PsydkError::ParameterError(format!("Invalid experiment description {}: {e}", path.display()))
+            })?,
+            _ => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Experiment descriptions must be .toml, .yaml or .yml files, got {}",
+                    path.display()
+                )))
+            }
+        };
+        description.validate()?;
+        Ok(description)
+    }
+
+    fn validate(&self) -> PsydkResult<()> {
+        if self.trials.is_empty() {
+            return Err(PsydkError::ParameterError("The experiment has no trials".into()));
+        }
+        if let Some(breaks) = &self.breaks {
+            if breaks.every == Some(0) {
+                return Err(PsydkError::ParameterError("Breaks can't be every 0 trials".into()));
+            }
+            breaks.screen()?;
+        }
+        self.validate_trials("Trial", &self.trials)?;
+        if let Some(practice) = &self.practice {
+            self.validate_practice(practice)?;
+        }
+        Ok(())
+    }
+
+    fn validate_trials(&self, what: &str, trials: &[TrialDescription]) -> PsydkResult<()> {
+        for (index, trial) in trials.iter().enumerate() {
+            if trial.items.is_empty() {
+                return Err(PsydkError::ParameterError(format!("{what} {index} has no items")));
+            }
+            self.validate_stimuli(
+                &format!("{what} {index}"),
+                trial.items.iter().flat_map(|item| &item.stimuli),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn validate_stimuli<'a>(&self, what: &str, names: impl IntoIterator<Item = &'a String>) -> PsydkResult<()> {
+        for name in names {
+            if !self.stimuli.contains_key(name) {
+                return Err(PsydkError::ParameterError(format!(
+                    "{what} uses the stimulus \"{name}\", which is not defined"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_practice(&self, practice: &PracticeDescription) -> PsydkResult<()> {
+        if !(0.0..=1.0).contains(&practice.criterion) {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid practice criterion {}, must be between 0 and 1",
+                practice.criterion
+            )));
+        }
+        if practice.max_blocks == 0 {
+            return Err(PsydkError::ParameterError(
+                "There must be at least one practice block".into(),
+            ));
+        }
+        let trials = practice.trials.as_ref().unwrap_or(&self.trials);
+        if trials.is_empty() {
+            return Err(PsydkError::ParameterError("The practice has no trials".into()));
+        }
+        self.validate_trials("Practice trial", trials)?;
+        if practice.criterion > 0.0 && trials.iter().all(|trial| trial.correct.is_none()) {
+            return Err(PsydkError::ParameterError(
+                "A practice criterion needs practice trials with a correct response".into(),
+            ));
+        }
+        if let Some(feedback) = &practice.feedback {
+            if feedback.duration < 0.0 {
+                return Err(PsydkError::ParameterError(format!(
+                    "Invalid feedback duration {}, must be non-negative",
+                    feedback.duration
+                )));
+            }
+            self.validate_stimuli(
+                "The feedback",
+                feedback
+                    .correct
+                    .iter()
+                    .chain(&feedback.incorrect)
+                    .chain(feedback.missed.iter().flatten()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Create all stimuli by calling the stimulus classes of the Python module.
+    #[cfg(feature = "python")]
+    pub fn create_stimuli(
+        &self,
+        py: Python,
+        context: &ExperimentContext,
+    ) -> PyResult<HashMap<String, DynamicStimulus>> {
+        plugins::load_plugins(py)?;
+        let stimuli_module = py.import("psydk.visual.stimuli")?;
+        let geometry_module = py.import("psydk.visual.geometry")?;
+
+        // stimuli that need the context (e.g. for fonts) look it up in the globals of `__main__`
+        let main = py.import("__main__")?;
+        main.setattr("_experiment_context", context.clone())?;
+
+        let result: PyResult<HashMap<_, _>> = self
+            .stimuli
+            .iter()
+            .map(|(name, description)| {
+                // stimuli registered by plugins take precedence over the built-in ones
+                if plugins::has_stimulus(&description.kind) {
+                    let stimulus = plugins::create_stimulus(&description.kind, context, &description.params)?;
+                    return Ok((name.clone(), stimulus));
+                }
+
+                let class = stimulus_class_name(&description.kind);
+                let class = stimuli_module.getattr(class.as_str()).map_err(|_| {
+                    PsydkError::ParameterError(format!("Unknown type \"{}\" of stimulus \"{name}\"", description.kind))
+                })?;
+
+                let kwargs = PyDict::new(py);
+                for (key, value) in &description.params {
+                    let value = match (key.as_str(), value) {
+                        ("shape", ParamValue::Map(shape)) => {
+                            let mut shape = shape.clone();
+                            let Some(ParamValue::String(kind)) = shape.remove("type") else {
+                                return Err(PsydkError::ParameterError(format!(
+                                    "The shape of stimulus \"{name}\" needs a type"
+                                ))
+                                .into());
+                            };
+                            geometry_module
+                                .getattr(kind.as_str())?
+                                .call((), Some(&params_to_kwargs(py, &shape)?))?
+                        }
+                        _ => value.to_py(py)?,
+                    };
+                    kwargs.set_item(key, value)?;
+                }
+
+                let stimulus: PyStimulus = class.call((), Some(&kwargs))?.extract()?;
+                Ok((name.clone(), stimulus.as_super().clone()))
+            })
+            .collect();
+
+        let _ = main.delattr("_experiment_context");
+        result
+    }
+
+    /// The number of trials that are presented (without practice trials). Unlike `blocks`, this
+    /// does not draw from the session random number generator, so it does not change the order.
+    fn trial_count(&self) -> usize {
+        let per_block: usize = self.trials.iter().map(|trial| trial.repeat as usize).sum();
+        self.repetitions as usize * per_block
+    }
+
+    /// The trials of each repetition, in the order in which they are presented. Randomized blocks
+    /// draw from the session random number generator, so this is only called when the experiment
+    /// runs.
+    fn blocks(&self) -> Vec<Vec<usize>> {
+        (0..self.repetitions)
+            .map(|_| {
+                let mut block = self
+                    .trials
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, trial)| std::iter::repeat(index).take(trial.repeat as usize))
+                    .collect::<Vec<_>>();
+                if self.randomize {
+                    random::with_session_rng(|rng| block.shuffle(rng));
+                }
+                block
+            })
+            .collect()
+    }
+
+    /// Present all trials on `window`, after the practice trials if there are any. Results are
+    /// written to the `.partial` file of `output` (or the output of the description) after every
+    /// trial, so that they are kept if the experiment is aborted with escape, and the file is
+    /// renamed to `output` once all trials have been run. An existing file is never overwritten.
+    pub fn run(
+        &self,
+        context: &ExperimentContext,
+        window: &Window,
+        stimuli: &HashMap<String, DynamicStimulus>,
+        output: Option<&Path>,
+    ) -> PsydkResult<Vec<TrialResult>> {
+        let data_columns = self
+            .trials
+            .iter()
+            .chain(
+                self.practice
+                    .iter()
+                    .flat_map(|practice| practice.trials.iter().flatten()),
+            )
+            .flat_map(|trial| trial.data.keys().cloned())
+            .collect::<BTreeSet<_>>();
+
+        let output = output
+            .map(Path::to_path_buf)
+            .or_else(|| self.output.as_ref().map(Into::into));
+        let mut results = ResultWriter::new(output, data_columns)?;
+
+        if let Some(practice) = &self.practice {
+            self.run_practice(practice, window, stimuli, &mut results)?;
+        }
+
+        let blocks = self.blocks();
+        let n_trials = blocks.iter().map(Vec::len).sum::<usize>();
+        let mut break_screen = self.breaks.as_ref().map(BreakDescription::screen).transpose()?;
+
+        let order = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(block, trials)| trials.iter().enumerate().map(move |(i, index)| (block, i, *index)));
+        let mut rest = None;
+        for (position, (block, index_in_block, index)) in order.enumerate() {
+            // breaks are taken at block boundaries (or every `every` trials), but not before the
+            // first trial
+            if let (Some(screen), Some(breaks)) = (&mut break_screen, &self.breaks) {
+                let boundary = match breaks.every {
+                    Some(every) => position % every as usize == 0,
+                    None => index_in_block == 0,
+                };
+                if position > 0 && boundary {
+                    let (finished, n_blocks) = match breaks.every {
+                        Some(every) => (
+                            position / every as usize - 1,
+                            (n_trials + every as usize - 1) / every as usize,
+                        ),
+                        None => (block - 1, blocks.len()),
+                    };
+                    rest = Some(screen.show(context, window, Some(finished), Some(n_blocks))?.duration());
+                }
+            }
+
+            let trial = &self.trials[index];
+            let (mut result, aborted) = self.run_trial(index, trial, window, stimuli)?;
+            result.rest = rest.take();
+            results.add(result)?;
+
+            if aborted {
+                return Err(PsydkError::CustomError("The experiment was aborted".into()));
+            }
+
+            if self.iti > 0.0 {
+                window.present(&mut window.get_frame()?, None, Some(self.iti), false, Some(false))?;
+            }
+        }
+
+        results.finish()
+    }
+
+    /// Present blocks of practice trials with feedback until the accuracy of a block reaches the
+    /// criterion or the maximum number of blocks has been presented.
+    fn run_practice(
+        &self,
+        practice: &PracticeDescription,
+        window: &Window,
+        stimuli: &HashMap<String, DynamicStimulus>,
+        results: &mut ResultWriter,
+    ) -> PsydkResult<()> {
+        let trials = practice.trials.as_ref().unwrap_or(&self.trials);
+
+        for block in 0..practice.max_blocks {
+            let mut order = (0..trials.len())
+                .flat_map(|index| std::iter::repeat(index).take(trials[index].repeat as usize))
+                .collect::<Vec<_>>();
+            if self.randomize {
+                random::with_session_rng(|rng| order.shuffle(rng));
+            }
+
+            let (mut n_correct, mut n_scored) = (0, 0);
+            for index in order {
+                let (mut result, aborted) = self.run_trial(index, &trials[index], window, stimuli)?;
+                result.practice = Some(block);
+                let correct = result.correct;
+                let responded = result.response.is_some();
+                results.add(result)?;
+
+                if aborted {
+                    return Err(PsydkError::CustomError("The experiment was aborted".into()));
+                }
+
+                if let Some(correct) = correct {
+                    n_scored += 1;
+                    n_correct += correct as u32;
+                    if let Some(feedback) = &practice.feedback {
+                        let names = match (correct, &feedback.missed) {
+                            (true, _) => &feedback.correct,
+                            (false, Some(missed)) if !responded => missed,
+                            (false, _) => &feedback.incorrect,
+                        };
+                        let mut frame = window.get_frame()?;
+                        for name in names {
+                            frame.add(&stimuli[name]);
+                        }
+                        window.present(&mut frame, None, Some(feedback.duration), false, Some(false))?;
+                    }
+                }
+
+                if self.iti > 0.0 {
+                    window.present(&mut window.get_frame()?, None, Some(self.iti), false, Some(false))?;
+                }
+            }
+
+            let accuracy = if n_scored > 0 {
+                n_correct as f64 / n_scored as f64
+            } else {
+                1.0
+            };
+            if accuracy >= practice.criterion {
+                return Ok(());
+            }
+            log::info!(
+                "Practice block {} reached an accuracy of {:.2}, below the criterion of {:.2}",
+                block + 1,
+                accuracy,
+                practice.criterion
+            );
+        }
+
+        if practice.abort_if_failed {
+            return Err(PsydkError::CustomError(format!(
+                "The practice criterion was not reached in {} blocks",
+                practice.max_blocks
+            )));
+        }
+        log::warn!(
+            "The practice criterion was not reached in {} blocks, continuing with the experiment",
+            practice.max_blocks
+        );
+        Ok(())
+    }
+
+    /// Present one trial. Returns the result and whether escape was pressed.
+    fn run_trial(
+        &self,
+        index: usize,
+        trial: &TrialDescription,
+        window: &Window,
+        stimuli: &HashMap<String, DynamicStimulus>,
+    ) -> PsydkResult<(TrialResult, bool)> {
+        let mut scheduler = Scheduler::default();
+        let mut onset = Duration::ZERO;
+        for item in &trial.items {
+            let duration = Duration::try_from_secs_f64(item.duration).map_err(|_| {
+                PsydkError::ParameterError(format!("Invalid duration {} in trial {index}", item.duration))
+            })?;
+            scheduler.items.push(ScheduleItem {
+                stimuli: item.stimuli.iter().map(|name| stimuli[name].clone()).collect(),
+                onset,
+                duration,
+                deadline: None,
+            });
+            onset += duration;
+        }
+
+        let report = scheduler.run(window, &[EventKind::KeyPress], true)?;
+
+        let respond_item = trial.items.iter().position(|item| item.respond).unwrap_or(0);
+        let mapping = trial.responses.as_ref().unwrap_or(&self.responses);
+
+        let mut aborted = false;
+        let mut response = None;
+        for scheduled in &report.responses {
+            let Event::KeyPress { key, .. } = &scheduled.event else {
+                continue;
+            };
+            if key == "Escape" {
+                aborted = true;
+                break;
+            }
+            let Some(item_onset) = report.item_onset(respond_item) else {
+                continue;
+            };
+            let time = scheduled.event.timestamp().timestamp;
+            if time < item_onset {
+                continue;
+            }
+            let mapped = mapping.iter().find(|(k, _)| k.eq_ignore_ascii_case(key));
+            if let Some((_, label)) = mapped {
+                response = Some((key.clone(), label.clone(), time - item_onset));
+                break;
+            }
+        }
+
+        let result = TrialResult {
+            trial: index,
+            name: trial.name.clone(),
+            practice: None,
+            onset: report.item_onset(0).map(Into::into),
+            key: response.as_ref().map(|(key, _, _)| key.clone()),
+            correct: trial
+                .correct
+                .as_ref()
+                .map(|correct| response.as_ref().is_some_and(|(_, label, _)| label == correct)),
+            response: response.as_ref().map(|(_, label, _)| label.clone()),
+            rt: response.map(|(_, _, rt)| rt),
+            rest: None,
+            data: trial.data.clone(),
+        };
+        Ok((result, aborted))
+    }
+}
+
+/// Writes the results to a CSV file after every trial, so that they are kept if the experiment is
+/// aborted.
+struct ResultWriter {
+    writer: Option<(csv::Writer<File>, PathBuf)>,
+    data_columns: BTreeSet<String>,
+    results: Vec<TrialResult>,
+}
+
+impl ResultWriter {
+    fn new(output: Option<PathBuf>, data_columns: BTreeSet<String>) -> PsydkResult<Self> {
+        let writer = match output {
+            Some(path) => {
+                // never overwrite the results of an earlier session, complete or not
+                let partial = manifest::partial_path(&path);
+                if path.exists() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("File {} already exists", path.display()),
+                    )
+                    .into());
+                }
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&partial)
+                    .map_err(|e| match e.kind() {
+                        std::io::ErrorKind::AlreadyExists => std::io::Error::new(
+                            e.kind(),
+                            format!(
+                                "File {} already exists, it holds the results of an incomplete session",
+                                partial.display()
+                            ),
+                        ),
+                        _ => e,
+                    })?;
+                manifest::record_open(&path);
+                let mut writer = csv::Writer::from_writer(file);
+                let columns = [
+                    "trial", "name", "practice", "onset", "key", "response", "rt", "correct", "rest",
+                ];
+                writer
+                    .write_record(
+                        columns
+                            .iter()
+                            .map(|c| c.to_string())
+                            .chain(data_columns.iter().cloned()),
+                    )
+                    .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+                Some((writer, path))
+            }
+            None => None,
+        };
+        Ok(Self {
+            writer,
+            data_columns,
+            results: Vec::new(),
+        })
+    }
+
+    fn add(&mut self, result: TrialResult) -> PsydkResult<()> {
+        if let Some((writer, _)) = &mut self.writer {
+            let optional = |value: Option<String>| value.unwrap_or_default();
+            let mut record = vec![
+                result.trial.to_string(),
+                optional(result.name.clone()),
+                optional(result.practice.map(|block| block.to_string())),
+                optional(result.onset.as_ref().map(|onset| onset.unix().to_string())),
+                optional(result.key.clone()),
+                optional(result.response.clone()),
+                optional(result.rt.map(|rt| rt.as_secs_f64().to_string())),
+                optional(result.correct.map(|correct| correct.to_string())),
+                optional(result.rest.map(|rest| rest.as_secs_f64().to_string())),
+            ];
+            record.extend(
+                self.data_columns
+                    .iter()
+                    .map(|column| optional(result.data.get(column).map(|value| value.to_string()))),
+            );
+            writer
+                .write_record(&record)
+                .and_then(|_| writer.flush().map_err(Into::into))
+                .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+        }
+        self.results.push(result);
+        Ok(())
+    }
+
+    /// Move the results file to its final path once all trials have been run. If the experiment
+    /// stops earlier, the results stay in the `.partial` file and are listed as incomplete in the
+    /// manifest.
+    fn finish(self) -> PsydkResult<Vec<TrialResult>> {
+        if let Some((mut writer, path)) = self.writer {
+            writer.flush()?;
+            drop(writer);
+            manifest::commit(&manifest::partial_path(&path), &path)?;
+            manifest::record_complete(&path, Some(self.results.len() as u64));
+        }
+        Ok(self.results)
+    }
+}
+
+/// The name of the stimulus class for a type, e.g. "TextStimulus" for "text" and "RichTextStimulus"
+/// for "rich_text". Class names are used as they are.
+#[cfg(feature = "python")]
+fn stimulus_class_name(kind: &str) -> String {
+    if kind.ends_with("Stimulus") {
+        return kind.to_string();
+    }
+    let mut name = kind
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    name.push_str("Stimulus");
+    name
+}
+
+/// Load the description in `path`, create a window, and run the experiment. Used by the `psydk`
+/// command for description files.
+#[cfg(feature = "python")]
+pub fn run_file(context: &ExperimentContext, path: &Path) -> PsydkResult<Vec<TrialResult>> {
+    let description = ExperimentDescription::load(path)?;
+    let window = context.create_default_window(true, None, None, false)?;
+    let stimuli = Python::with_gil(|py| description.create_stimuli(py, context))?;
+    description.run(context, &window, &stimuli, None)
+}
+
+/// An experiment described in a TOML or YAML file: named stimuli, trials made up of timed items,
+/// and a mapping from keys to responses. See the documentation for the format.
+///
+/// Each trial is presented by a `Scheduler`, so trials run without returning to Python. Escape
+/// aborts the experiment. Descriptions can also be run without any code with the `psydk` command,
+/// e.g. `psydk experiment.toml`.
+#[cfg(feature = "python")]
+#[pyclass(name = "ExperimentDescription")]
+#[derive(Debug, Clone)]
+pub struct PyExperimentDescription(pub ExperimentDescription);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyExperimentDescription {
+    /// Load a description from a `.toml`, `.yaml` or `.yml` file.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///     The path of the file.
+    #[staticmethod]
+    #[pyo3(name = "load")]
+    fn py_load(path: &str) -> PyResult<Self> {
+        Ok(Self(ExperimentDescription::load(path)?))
+    }
+
+    /// Run the experiment on a window.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///     The window to present the trials on.
+    /// output : str, optional
+    ///     A CSV file for the results, overriding the `output` of the description. It must not
+    ///     exist yet.
+    /// context : ExperimentContext, optional
+    ///     The experiment context. Found automatically inside experiment functions.
+    ///
+    /// Returns
+    /// -------
+    /// list[dict]
+    ///     One dictionary per trial with the "trial" index, "name", "practice" (the practice block
+    ///     for practice trials, otherwise None), "onset", "key", "response", "rt" (in seconds),
+    ///     "correct", "rest" (the duration of the break before the trial in seconds, if there was
+    ///     one), and the "data" of the trial. Practice trials come first; their "trial" is the
+    ///     index of the practice trial.
+    #[pyo3(name = "run")]
+    #[pyo3(signature = (window, output = None, context = None))]
+    fn py_run<'py>(
+        &self,
+        window: Window,
+        output: Option<String>,
+        context: Option<ExperimentContext>,
+        py: Python<'py>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let context = crate::visual::stimuli::helpers::get_experiment_context(context, py)?;
+        let stimuli = self.0.create_stimuli(py, &context)?;
+
+        let description = SendWrapper::new(self.0.clone());
+        let context = SendWrapper::new(context);
+        let window_wrapper = SendWrapper::new(window.clone());
+        let stimuli = SendWrapper::new(stimuli);
+        let results = py.allow_threads(move || {
+            description.run(&context, &window_wrapper, &stimuli, output.as_deref().map(Path::new))
+        })?;
+
+        window.call_watchdog_callback(py)?;
+
+        results
+            .into_iter()
+            .map(|result| {
+                let dict = PyDict::new(py);
+                dict.set_item("trial", result.trial)?;
+                dict.set_item("name", result.name)?;
+                dict.set_item("practice", result.practice)?;
+                dict.set_item("onset", result.onset)?;
+                dict.set_item("key", result.key)?;
+                dict.set_item("response", result.response)?;
+                dict.set_item("rt", result.rt.map(|rt| rt.as_secs_f64()))?;
+                dict.set_item("correct", result.correct)?;
+                dict.set_item("rest", result.rest.map(|rest| rest.as_secs_f64()))?;
+                let data = PyDict::new(py);
+                for (key, value) in &result.data {
+                    data.set_item(key, value.to_py(py)?)?;
+                }
+                dict.set_item("data", data)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.trial_count()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ExperimentDescription(stimuli={}, trials={}, repetitions={})",
+            self.0.stimuli.len(),
+            self.0.trials.len(),
+            self.0.repetitions
+        )
+    }
+}
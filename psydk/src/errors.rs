@@ -9,6 +9,7 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum PsydkError {
     // Pyo3 errors
+    #[cfg(feature = "python")]
     #[error("{0}")]
     Pyo3Error(#[from] pyo3::PyErr),
 
@@ -76,6 +77,20 @@ pub enum PsydkError {
     #[error("Presentation error: {0}")]
     PresentationError(String),
 
+    // window errors
+    #[error("Failed to create window: {0}")]
+    WindowCreationError(String),
+    #[error("The window has already been closed.")]
+    WindowClosed,
+
+    // audio errors
+    #[error("Audio error: {0}")]
+    AudioError(String),
+
+    // the surface or the graphics device was lost and could not be recovered
+    #[error("The display was lost and could not be recovered: {0}")]
+    DisplayLost(String),
+
     #[cfg(feature = "gst")]
     // a GStreamer error
     #[error("Glib error: {0}")]
@@ -102,9 +117,41 @@ macro_rules! error {
     };
 }
 
+#[cfg(feature = "python")]
+pyo3::create_exception!(
+    psydk,
+    DisplayLost,
+    pyo3::exceptions::PyException,
+    "Raised when the display or the graphics device was lost (e.g., because the display was unplugged or the driver was reset) and could not be recovered."
+);
+
 // allow PsydkError to be converted to a PyErr
+#[cfg(feature = "python")]
 impl From<PsydkError> for pyo3::PyErr {
     fn from(err: PsydkError) -> pyo3::PyErr {
-        pyo3::exceptions::PyException::new_err(err.to_string())
+        use pyo3::exceptions::{PyException, PyKeyError, PyOSError, PyRuntimeError, PyValueError};
+
+        match err {
+            // pass through errors that originated in Python
+            PsydkError::Pyo3Error(err) => err,
+            PsydkError::DisplayLost(_) => DisplayLost::new_err(err.to_string()),
+            PsydkError::IOError(_) | PsydkError::FileExistsAndNotEmptyError(_) => PyOSError::new_err(err.to_string()),
+            PsydkError::ColumnNameDoesNotExistError(_) => PyKeyError::new_err(err.to_string()),
+            PsydkError::ParameterError(_)
+            | PsydkError::BrushError(_)
+            | PsydkError::ColumnNamesNotUniqueError
+            | PsydkError::DataLengthMismatchError(..)
+            | PsydkError::InvalidBIDSPathError(..)
+            | PsydkError::WrongDimensionsError(..)
+            | PsydkError::NonIdenticalDimensionsError(..)
+            | PsydkError::EmptyVectorError
+            | PsydkError::SingleImageError => PyValueError::new_err(err.to_string()),
+            PsydkError::MonitorError(_)
+            | PsydkError::PresentationError(_)
+            | PsydkError::WindowCreationError(_)
+            | PsydkError::WindowClosed
+            | PsydkError::AudioError(_) => PyRuntimeError::new_err(err.to_string()),
+            _ => PyException::new_err(err.to_string()),
+        }
     }
 }
@@ -1,11 +1,12 @@
 use std::sync::{Arc, Mutex};
 
 use gix::{open, Repository};
+#[cfg(feature = "python")]
 use pyo3::prelude::{pyclass, pymethods};
 
 #[derive(Debug)]
-#[pyclass]
-#[pyo3(name = "Repository")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "Repository"))]
 /// A repository.
 pub struct PyRepository {
     pub repo: Arc<Mutex<Repository>>,
@@ -20,6 +21,7 @@ impl PyRepository {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyRepository {
     #[new]
@@ -0,0 +1,249 @@
+//! Response deadlines. If no response arrives before the deadline, a `Timeout` event is emitted
+//! at the deadline by a timer thread, so that timeouts are as precise as responses and don't
+//! depend on how often the experiment checks for them.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use super::{Event, EventHandlerId, EventKind};
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{wait_until, Timestamp, TimestampOrOffset},
+    visual::window::Window,
+};
+
+/// The timer thread sleeps until this long before the deadline and waits precisely for the rest.
+const PRECISE_WAIT: Duration = Duration::from_millis(2);
+
+/// What happened to a response deadline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeadlineState {
+    Pending,
+    /// A response arrived at the given time.
+    Responded(Instant),
+    /// The deadline passed without a response.
+    TimedOut,
+    Cancelled,
+}
+
+/// Waits for a response until a deadline and emits a `Timeout` event on the window if none
+/// arrives. Dropping the deadline cancels it.
+#[derive(Debug)]
+pub struct ResponseDeadline {
+    pub deadline: Instant,
+    window: Window,
+    state: Arc<(Mutex<DeadlineState>, Condvar)>,
+    handlers: Vec<EventHandlerId>,
+}
+
+impl ResponseDeadline {
+    pub fn state(&self) -> DeadlineState {
+        *self.state.0.lock().unwrap()
+    }
+
+    /// Cancel the deadline if it is still pending.
+    pub fn cancel(&mut self) {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if *state == DeadlineState::Pending {
+            *state = DeadlineState::Cancelled;
+            condvar.notify_all();
+        }
+        drop(state);
+
+        for id in self.handlers.drain(..) {
+            self.window.remove_event_handler(id);
+        }
+    }
+
+    /// Block until the deadline is resolved, i.e. until a response arrives, the deadline passes,
+    /// or it is cancelled.
+    pub fn wait(&self) -> DeadlineState {
+        let (lock, condvar) = &*self.state;
+        let state = condvar
+            .wait_while(lock.lock().unwrap(), |state| *state == DeadlineState::Pending)
+            .unwrap();
+        *state
+    }
+}
+
+impl Drop for ResponseDeadline {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Window {
+    /// Wait for an event of one of the given kinds until `deadline`. If none arrives, a
+    /// `Timeout` event is emitted at the deadline.
+    pub fn set_response_deadline(&self, kinds: &[EventKind], deadline: Instant) -> PsydkResult<ResponseDeadline> {
+        if kinds.is_empty() {
+            return Err(PsydkError::ParameterError(
+                "At least one kind of event must count as a response".into(),
+            ));
+        }
+
+        let state = Arc::new((Mutex::new(DeadlineState::Pending), Condvar::new()));
+        let mut handlers = Vec::new();
+        for kind in kinds {
+            let state = state.clone();
+            let id = self.add_event_handler(*kind, move |event| {
+                let (lock, condvar) = &*state;
+                let mut state = lock.lock().unwrap();
+                if *state == DeadlineState::Pending {
+                    *state = DeadlineState::Responded(event.timestamp().timestamp);
+                    condvar.notify_all();
+                }
+                false
+            });
+            match id {
+                Ok(id) => handlers.push(id),
+                Err(e) => {
+                    for id in handlers {
+                        self.remove_event_handler(id);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let timer_state = state.clone();
+        let window = self.clone();
+        std::thread::spawn(move || {
+            let (lock, condvar) = &*timer_state;
+            // sleep until shortly before the deadline unless the deadline is resolved earlier
+            let coarse = deadline.checked_sub(PRECISE_WAIT).unwrap_or(deadline);
+            let mut state = lock.lock().unwrap();
+            while *state == DeadlineState::Pending {
+                let now = Instant::now();
+                if now >= coarse {
+                    break;
+                }
+                state = condvar.wait_timeout(state, coarse - now).unwrap().0;
+            }
+            if *state != DeadlineState::Pending {
+                return;
+            }
+            drop(state);
+
+            wait_until(deadline);
+            let mut state = lock.lock().unwrap();
+            if *state != DeadlineState::Pending {
+                return;
+            }
+            *state = DeadlineState::TimedOut;
+            condvar.notify_all();
+            drop(state);
+
+            window.inject_event(Event::Timeout {
+                timestamp: Instant::now().into(),
+                scheduled_for: deadline.into(),
+            });
+        });
+
+        Ok(ResponseDeadline {
+            deadline,
+            window: self.clone(),
+            state,
+            handlers,
+        })
+    }
+}
+
+/// A response deadline, see `Window.set_response_deadline`.
+#[cfg(feature = "python")]
+#[pyclass(name = "ResponseDeadline")]
+pub struct PyResponseDeadline(pub ResponseDeadline);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyResponseDeadline {
+    /// Cancel the deadline, so that no `Timeout` event is emitted.
+    #[pyo3(name = "cancel")]
+    fn py_cancel(&mut self) {
+        self.0.cancel();
+    }
+
+    /// Block until a response arrives, the deadline passes, or the deadline is cancelled.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   True if a response arrived before the deadline.
+    #[pyo3(name = "wait")]
+    fn py_wait(&self, py: Python) -> bool {
+        matches!(py.allow_threads(|| self.0.wait()), DeadlineState::Responded(_))
+    }
+
+    /// The deadline.
+    #[getter(deadline)]
+    fn py_deadline(&self) -> Timestamp {
+        self.0.deadline.into()
+    }
+
+    /// The state of the deadline: "pending", "responded", "timed_out", or "cancelled".
+    #[getter(state)]
+    fn py_state(&self) -> &'static str {
+        match self.0.state() {
+            DeadlineState::Pending => "pending",
+            DeadlineState::Responded(_) => "responded",
+            DeadlineState::TimedOut => "timed_out",
+            DeadlineState::Cancelled => "cancelled",
+        }
+    }
+
+    /// The time of the response, or None if no response arrived before the deadline.
+    #[getter(response_time)]
+    fn py_response_time(&self) -> Option<Timestamp> {
+        match self.0.state() {
+            DeadlineState::Responded(time) => Some(time.into()),
+            _ => None,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ResponseDeadline(state={:?})", self.py_state())
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Window {
+    /// Set a deadline for a response. If no event of the given kinds arrives before the deadline,
+    /// a "timeout" event (with the deadline as `scheduled_for`) is emitted exactly at the
+    /// deadline and delivered like other input events, e.g. to handlers added with
+    /// `add_event_handler("timeout", ...)`. The deadline is timed in a background thread, so
+    /// the experiment does not need to check for it.
+    ///
+    /// Parameters
+    /// ----------
+    /// deadline : Timestamp, float, or timedelta
+    ///   The deadline, as a timestamp (e.g. the stimulus onset plus the response window) or as an
+    ///   offset from now.
+    /// responses : list[EventKind], optional
+    ///   The kinds of events that count as a response. Defaults to key presses.
+    ///
+    /// Returns
+    /// -------
+    /// ResponseDeadline
+    ///   The deadline, which can be cancelled or waited for. It is cancelled when it is garbage
+    ///   collected.
+    #[pyo3(name = "set_response_deadline")]
+    #[pyo3(signature = (deadline, responses = vec![EventKind::KeyPress]))]
+    fn py_set_response_deadline(
+        &self,
+        deadline: TimestampOrOffset,
+        responses: Vec<EventKind>,
+    ) -> PyResult<PyResponseDeadline> {
+        let deadline = match deadline {
+            TimestampOrOffset::Timestamp(timestamp) => timestamp.timestamp,
+            TimestampOrOffset::Offset(offset) => Timestamp::from(Instant::now()).offset_by(offset.seconds())?.timestamp,
+        };
+        Ok(PyResponseDeadline(self.set_response_deadline(&responses, deadline)?))
+    }
+}
@@ -0,0 +1,96 @@
+//! Online detection of saccades and blinks in the gaze samples of a window.
+//!
+//! Saccades are detected with a velocity threshold (I-VT): a saccade starts at the first sample
+//! whose angular velocity relative to the previous sample exceeds the threshold, and ends when the
+//! velocity falls below it again. Blinks are gaps in valid samples (the tracker lost the pupil)
+//! that last longer than a minimum and shorter than a maximum duration; longer gaps are treated as
+//! the participant looking away.
+
+use std::time::{Duration, Instant};
+
+use super::Event;
+use crate::visual::window::WindowState;
+
+/// Samples further apart than this are not used to compute a velocity, e.g. after the tracker
+/// paused.
+const MAX_SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone)]
+pub struct GazeEventDetector {
+    /// The angular velocity above which a saccade is detected, in degrees per second.
+    pub velocity_threshold: f32,
+    /// Gaps in valid samples shorter than this are not blinks.
+    pub min_blink: Duration,
+    /// Gaps in valid samples longer than this are not blinks.
+    pub max_blink: Duration,
+    /// The previous valid sample, in the coordinates of the scene.
+    last_sample: Option<(Instant, (f32, f32))>,
+    in_saccade: bool,
+    /// The first invalid sample of the current gap in valid samples.
+    lost_since: Option<Instant>,
+}
+
+impl GazeEventDetector {
+    pub fn new(velocity_threshold: f32, min_blink: Duration, max_blink: Duration) -> Self {
+        Self {
+            velocity_threshold,
+            min_blink,
+            max_blink,
+            last_sample: None,
+            in_saccade: false,
+            lost_since: None,
+        }
+    }
+
+    /// Process a gaze sample (in the coordinate system of the window, `None` if the sample is
+    /// invalid) and return the detected events.
+    pub fn add_sample(&mut self, time: Instant, position: Option<(f32, f32)>, win_state: &WindowState) -> Vec<Event> {
+        // samples that are passed more than once (e.g. to several areas of interest) or out of
+        // order are ignored
+        if self.last_sample.is_some_and(|(last_time, _)| time <= last_time)
+            || self.lost_since.is_some_and(|lost_since| time <= lost_since)
+        {
+            return Vec::new();
+        }
+
+        let Some((x, y)) = position else {
+            self.lost_since.get_or_insert(time);
+            self.in_saccade = false;
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        if let Some(lost_since) = self.lost_since.take() {
+            let duration = time - lost_since;
+            if duration >= self.min_blink && duration <= self.max_blink {
+                events.push(Event::Blink {
+                    timestamp: lost_since.into(),
+                    duration: duration.as_secs_f64(),
+                });
+            }
+            // there is no meaningful velocity across a gap
+            self.last_sample = None;
+        }
+
+        let scene_position = win_state.coordinate_system.to_scene(x, y, win_state.size);
+        if let Some((last_time, last_position)) = self.last_sample {
+            let interval = time - last_time;
+            if interval <= MAX_SAMPLE_INTERVAL {
+                let angle = win_state.physical_screen.visual_angle(last_position, scene_position);
+                let velocity = (angle / interval.as_secs_f64()) as f32;
+                let above = velocity > self.velocity_threshold;
+                if above && !self.in_saccade {
+                    events.push(Event::Saccade {
+                        timestamp: time.into(),
+                        position: (x, y),
+                        velocity,
+                    });
+                }
+                self.in_saccade = above;
+            }
+        }
+        self.last_sample = Some((time, scene_position));
+
+        events
+    }
+}
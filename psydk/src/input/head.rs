@@ -0,0 +1,500 @@
+//! Head tracking that keeps the viewing distance of a window up to date, so that sizes in degrees
+//! of visual angle stay correct when the participant moves towards or away from the screen.
+//!
+//! Head trackers are usually separate programs (e.g. webcam-based face trackers like FaceOSC)
+//! that send their measurements as OSC messages over UDP. The distance can also be set from Python
+//! with `update`, e.g. from a face tracker that runs in the experiment script.
+
+use std::{
+    net::UdpSocket,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use numpy::IntoPyArray;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+    visual::window::Window,
+};
+
+/// How long the receiving thread waits for a message before checking whether it should stop.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// How values of the tracker are converted to viewing distances.
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceMapping {
+    /// The distance in mm is the value times `scale`, or `scale` divided by the value if
+    /// `inverse` is set (for trackers that report the apparent size of the face).
+    pub scale: f32,
+    pub inverse: bool,
+    /// The weight of the previous distance in the exponential smoothing of the distance, between 0
+    /// (no smoothing) and 1.
+    pub smoothing: f32,
+    /// Distances outside this range (in mm) are clamped, e.g. when the tracker briefly loses the
+    /// face.
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl DistanceMapping {
+    fn to_distance(&self, value: f32) -> Option<f32> {
+        let distance = if self.inverse {
+            self.scale / value
+        } else {
+            self.scale * value
+        };
+        distance
+            .is_finite()
+            .then(|| distance.clamp(self.min_distance, self.max_distance))
+    }
+}
+
+#[derive(Debug)]
+struct HeadState {
+    mapping: DistanceMapping,
+    /// The most recent value of the tracker, before it was converted to a distance.
+    value: Option<f32>,
+    /// The smoothed viewing distance in mm.
+    distance: Option<f32>,
+    last_update: Option<Instant>,
+}
+
+/// Convert a value of the tracker to a viewing distance and set it as the viewing distance of
+/// `window`. Returns the new distance, or `None` if the value was invalid.
+fn apply_value(state: &Mutex<HeadState>, window: &Window, value: f32, time: Instant) -> PsydkResult<Option<f32>> {
+    let distance = {
+        let mut state = state.lock().unwrap();
+        let Some(distance) = state.mapping.to_distance(value) else {
+            return Ok(None);
+        };
+        let smoothing = state.mapping.smoothing.clamp(0.0, 1.0);
+        let distance = match state.distance {
+            Some(previous) => previous * smoothing + distance * (1.0 - smoothing),
+            None => distance,
+        };
+        state.value = Some(value);
+        state.distance = Some(distance);
+        state.last_update = Some(time);
+        distance
+    };
+    window.with_state(|win_state| win_state.physical_screen.viewing_distance = distance)?;
+    Ok(Some(distance))
+}
+
+/// Call `f` with the address and the numeric arguments of every message in an OSC packet.
+/// Arguments after the first one that is not a number are ignored.
+fn parse_osc(packet: &[u8], f: &mut impl FnMut(&str, &[f64])) {
+    if let Some(bundle) = packet.strip_prefix(b"#bundle\0") {
+        // skip the time tag
+        let mut elements = bundle.get(8..).unwrap_or_default();
+        while let Some(size) = take::<4>(&mut elements) {
+            let size = i32::from_be_bytes(size).max(0) as usize;
+            let Some(element) = elements.get(..size) else {
+                return;
+            };
+            parse_osc(element, f);
+            elements = &elements[size..];
+        }
+        return;
+    }
+
+    let Some((address, rest)) = osc_string(packet) else {
+        return;
+    };
+    let Some((tags, mut rest)) = osc_string(rest) else {
+        return;
+    };
+    let Some(tags) = tags.strip_prefix(',') else {
+        return;
+    };
+
+    let mut args = Vec::new();
+    for tag in tags.bytes() {
+        let value = match tag {
+            b'f' => take::<4>(&mut rest).map(|b| f32::from_be_bytes(b) as f64),
+            b'i' => take::<4>(&mut rest).map(|b| i32::from_be_bytes(b) as f64),
+            b'd' => take::<8>(&mut rest).map(f64::from_be_bytes),
+            b'h' => take::<8>(&mut rest).map(|b| i64::from_be_bytes(b) as f64),
+            // arguments without data
+            b'T' | b'F' | b'N' | b'I' => continue,
+            _ => break,
+        };
+        match value {
+            Some(value) => args.push(value),
+            None => return,
+        }
+    }
+    f(address, &args);
+}
+
+/// Split off an OSC string (null-terminated and padded to a multiple of 4 bytes).
+fn osc_string(data: &[u8]) -> Option<(&str, &[u8])> {
+    let end = data.iter().position(|b| *b == 0)?;
+    let string = std::str::from_utf8(&data[..end]).ok()?;
+    Some((string, data.get((end + 4) & !3..)?))
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = data.get(..N)?.try_into().ok()?;
+    *data = &data[N..];
+    Some(bytes)
+}
+
+/// Keeps the viewing distance of a window up to date with the measurements of a head tracker.
+///
+/// While the tracker exists, the viewing distance of every presented frame is logged by the
+/// window, see `frame_log`.
+#[derive(Debug)]
+pub struct HeadTracker {
+    pub window: Window,
+    /// The UDP port to receive OSC messages on, if any.
+    pub port: Option<u16>,
+    /// The OSC address of the messages with the measurements.
+    pub address: String,
+    /// The index of the argument of these messages that holds the measurement.
+    pub argument: usize,
+    state: Arc<Mutex<HeadState>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeadTracker {
+    pub fn new(
+        window: Window,
+        port: Option<u16>,
+        address: String,
+        argument: usize,
+        mapping: DistanceMapping,
+    ) -> PsydkResult<Self> {
+        if !(mapping.min_distance > 0.0 && mapping.min_distance <= mapping.max_distance) {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid distance range {} to {} mm",
+                mapping.min_distance, mapping.max_distance
+            )));
+        }
+        window.with_state(|win_state| {
+            win_state.viewing_distance_log.get_or_insert_with(Vec::new);
+        })?;
+
+        Ok(Self {
+            window,
+            port,
+            address,
+            argument,
+            state: Arc::new(Mutex::new(HeadState {
+                mapping,
+                value: None,
+                distance: None,
+                last_update: None,
+            })),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    /// Start receiving OSC messages. Does nothing if the tracker is already receiving or has no
+    /// port.
+    pub fn start(&mut self) -> PsydkResult<()> {
+        let Some(port) = self.port else {
+            return Ok(());
+        };
+        if self.is_running() {
+            return Ok(());
+        }
+        self.stop.store(false, Ordering::Relaxed);
+
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+
+        let state = self.state.clone();
+        let stop = self.stop.clone();
+        let window = self.window.clone();
+        let (address, argument) = (self.address.clone(), self.argument);
+        self.thread = Some(std::thread::spawn(move || {
+            let mut packet = [0u8; 4096];
+            while !stop.load(Ordering::Relaxed) {
+                let size = match socket.recv(&mut packet) {
+                    Ok(size) => size,
+                    Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                        continue
+                    }
+                    Err(e) => {
+                        log::error!("Failed to receive head tracking data: {}", e);
+                        break;
+                    }
+                };
+                let time = Instant::now();
+
+                let mut values = Vec::new();
+                parse_osc(&packet[..size], &mut |message_address, args| {
+                    if message_address == address {
+                        values.extend(args.get(argument).map(|value| *value as f32));
+                    }
+                });
+                for value in values {
+                    if apply_value(&state, &window, value, time).is_err() {
+                        // the window was closed
+                        return;
+                    }
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// Stop receiving OSC messages. The viewing distance keeps its last value.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Set the viewing distance from a value of the tracker, as if it was received in an OSC
+    /// message. Returns the new distance, or `None` if the value was invalid.
+    pub fn update(&self, value: f32, time: Instant) -> PsydkResult<Option<f32>> {
+        apply_value(&self.state, &self.window, value, time)
+    }
+
+    /// Set the scale of the mapping so that the most recent value corresponds to `distance` (in
+    /// mm), e.g. while the participant sits at a measured distance.
+    pub fn calibrate(&self, distance: f32) -> PsydkResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let value = state
+            .value
+            .filter(|value| *value != 0.0)
+            .ok_or_else(|| PsydkError::CustomError("The head tracker has not sent a valid value yet".into()))?;
+        state.mapping.scale = if state.mapping.inverse {
+            distance * value
+        } else {
+            distance / value
+        };
+        // start over without the smoothing of the old scale
+        state.distance = state.mapping.to_distance(value);
+        let distance = state.distance;
+        drop(state);
+
+        if let Some(distance) = distance {
+            self.window
+                .with_state(|win_state| win_state.physical_screen.viewing_distance = distance)?;
+        }
+        Ok(())
+    }
+
+    pub fn mapping(&self) -> DistanceMapping {
+        self.state.lock().unwrap().mapping
+    }
+
+    /// The current viewing distance in mm and the time it was last updated, if the tracker sent
+    /// a valid value.
+    pub fn distance(&self) -> Option<(f32, Instant)> {
+        let state = self.state.lock().unwrap();
+        state.distance.zip(state.last_update)
+    }
+}
+
+impl Drop for HeadTracker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Updates the viewing distance of a window with the measurements of a head tracker, so that
+/// sizes in degrees of visual angle stay correct for participants without a chin rest.
+///
+/// Measurements are received as OSC messages over UDP, e.g. from a webcam-based face tracker, or
+/// passed with `update`. The value of the tracker is converted to a distance in mm by multiplying
+/// it with `scale`, or, with `inverse`, by dividing `scale` by it (for trackers that report the
+/// apparent size of the face, like the "/pose/scale" messages of FaceOSC). Use `calibrate` while
+/// the participant sits at a measured distance to set the scale.
+///
+/// The viewing distance at the onset of every presented frame is logged, see `frame_log`.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to update the viewing distance of.
+/// port : int or None, optional
+///   The UDP port to receive OSC messages on. Pass None to only set the distance with `update`.
+///   Defaults to 8338 (the port of FaceOSC).
+/// address : str, optional
+///   The OSC address of the messages with the measurements. Defaults to "/pose/scale".
+/// argument : int, optional
+///   The index of the argument of these messages that holds the measurement. Defaults to 0.
+/// scale : float, optional
+///   The factor that converts values of the tracker to mm. Defaults to 1.
+/// inverse : bool, optional
+///   Whether the distance is inversely proportional to the value of the tracker. Defaults to True.
+/// smoothing : float, optional
+///   The weight of the previous distance when a new value arrives, between 0 (no smoothing) and
+///   1. Defaults to 0.5.
+/// min_distance : float, optional
+///   The smallest plausible viewing distance in mm. Defaults to 200.
+/// max_distance : float, optional
+///   The largest plausible viewing distance in mm. Defaults to 2000.
+#[cfg(feature = "python")]
+#[pyclass(name = "HeadTracker")]
+pub struct PyHeadTracker(pub HeadTracker);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyHeadTracker {
+    #[new]
+    #[pyo3(signature = (
+        window,
+        port = Some(8338),
+        address = "/pose/scale".to_string(),
+        argument = 0,
+        scale = 1.0,
+        inverse = true,
+        smoothing = 0.5,
+        min_distance = 200.0,
+        max_distance = 2000.0,
+    ))]
+    fn __new__(
+        window: Window,
+        port: Option<u16>,
+        address: String,
+        argument: usize,
+        scale: f32,
+        inverse: bool,
+        smoothing: f32,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> PyResult<Self> {
+        let mapping = DistanceMapping {
+            scale,
+            inverse,
+            smoothing,
+            min_distance,
+            max_distance,
+        };
+        let mut tracker = HeadTracker::new(window, port, address, argument, mapping)?;
+        tracker.start()?;
+        Ok(Self(tracker))
+    }
+
+    /// Start receiving OSC messages again after `stop`.
+    #[pyo3(name = "start")]
+    fn py_start(&mut self) -> PyResult<()> {
+        Ok(self.0.start()?)
+    }
+
+    /// Stop receiving OSC messages. The viewing distance keeps its last value.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+
+    /// Set the viewing distance from a measurement, e.g. of a face tracker that runs in Python.
+    ///
+    /// Parameters
+    /// ----------
+    /// value : float
+    ///   The value of the tracker, converted to a distance like the values in OSC messages.
+    /// timestamp : Timestamp, optional
+    ///   The time of the measurement. Defaults to now.
+    ///
+    /// Returns
+    /// -------
+    /// float or None
+    ///   The new viewing distance in mm, or None if the value was invalid.
+    #[pyo3(name = "update")]
+    #[pyo3(signature = (value, timestamp = None))]
+    fn py_update(&self, value: f32, timestamp: Option<Timestamp>) -> PyResult<Option<f32>> {
+        let time = timestamp.map_or_else(Instant::now, |timestamp| timestamp.timestamp);
+        Ok(self.0.update(value, time)?)
+    }
+
+    /// Set the scale so that the most recent value of the tracker corresponds to the given
+    /// distance. Call this while the participant sits at a measured distance from the screen.
+    ///
+    /// Parameters
+    /// ----------
+    /// distance : float
+    ///   The current viewing distance in mm.
+    #[pyo3(name = "calibrate")]
+    fn py_calibrate(&self, distance: f32) -> PyResult<()> {
+        Ok(self.0.calibrate(distance)?)
+    }
+
+    /// The viewing distances at the onsets of all frames that were presented since the tracker
+    /// was created or the log was cleared.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary with "onset" (the onset of each frame) and "viewing_distance" (the viewing
+    ///   distance in mm at that frame, as a 1D array).
+    #[pyo3(name = "frame_log")]
+    fn py_frame_log<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let log = self
+            .0
+            .window
+            .with_state(|win_state| win_state.viewing_distance_log.clone().unwrap_or_default())?;
+        let (onsets, distances): (Vec<_>, Vec<_>) = log
+            .into_iter()
+            .map(|(onset, distance)| (Timestamp::from(onset), distance))
+            .unzip();
+
+        let dict = PyDict::new(py);
+        dict.set_item("onset", onsets)?;
+        dict.set_item("viewing_distance", distances.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// Discard the logged viewing distances, e.g. at the start of a block.
+    #[pyo3(name = "clear_frame_log")]
+    fn py_clear_frame_log(&self) -> PyResult<()> {
+        Ok(self.0.window.with_state(|win_state| {
+            win_state.viewing_distance_log = Some(Vec::new());
+        })?)
+    }
+
+    /// The current viewing distance in mm, or None if the tracker has not sent a valid value yet.
+    #[getter(viewing_distance)]
+    fn py_viewing_distance(&self) -> Option<f32> {
+        self.0.distance().map(|(distance, _)| distance)
+    }
+
+    /// The time the viewing distance was last updated, or None.
+    #[getter(last_update)]
+    fn py_last_update(&self) -> Option<Timestamp> {
+        self.0.distance().map(|(_, time)| time.into())
+    }
+
+    /// The factor that converts values of the tracker to mm.
+    #[getter(scale)]
+    fn py_scale(&self) -> f32 {
+        self.0.mapping().scale
+    }
+
+    /// Whether the tracker is receiving OSC messages.
+    #[getter(running)]
+    fn py_running(&self) -> bool {
+        self.0.is_running()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "HeadTracker(port={:?}, address={:?}, viewing_distance={:?})",
+            self.0.port,
+            self.0.address,
+            self.0.distance().map(|(distance, _)| distance)
+        )
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods};
+
+use super::Event;
+use crate::time::Timestamp;
+
+/// The state of a single key.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyState {
+    pub pressed: bool,
+    /// The most recent time the key was pressed.
+    pub last_press: Option<Instant>,
+    /// The most recent time the key was released.
+    pub last_release: Option<Instant>,
+}
+
+/// Keys are matched case-insensitively, so that e.g. "a" and "A" (with shift) are the same key
+/// and named keys can be given as "space" or "Space".
+fn normalize(key: &str) -> String {
+    key.to_lowercase()
+}
+
+/// Identifies a physical key. Events without a scancode (e.g. simulated key presses) are identified
+/// by their key name instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PhysicalKey {
+    Code(u32),
+    Name(String),
+}
+
+impl PhysicalKey {
+    fn new(key: &str, code: u32) -> Self {
+        match code {
+            0 => PhysicalKey::Name(normalize(key)),
+            code => PhysicalKey::Code(code),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Keys {
+    /// The state of every physical key.
+    states: HashMap<PhysicalKey, KeyState>,
+    /// The physical key that most recently produced each key name.
+    physical: HashMap<String, PhysicalKey>,
+    /// The name each physical key produced when it was last pressed.
+    names: HashMap<PhysicalKey, String>,
+}
+
+/// The current state of all keys of a window, updated as keyboard events arrive.
+///
+/// The state is kept per physical key, as the name of a key can change while it is held (e.g. "1"
+/// is released as "!" when shift is pressed in between). Keys are looked up by the name they
+/// produced most recently.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct KeyboardState {
+    keys: Arc<Mutex<Keys>>,
+}
+
+impl KeyboardState {
+    /// Update the state with an event. Events other than key presses and releases are ignored.
+    pub fn update(&self, event: &Event) {
+        let (key, code, timestamp, pressed) = match event {
+            Event::KeyPress { key, code, timestamp } => (key, *code, timestamp, true),
+            Event::KeyRelease { key, code, timestamp } => (key, *code, timestamp, false),
+            _ => return,
+        };
+
+        let physical = PhysicalKey::new(key, code);
+        let mut keys = self.keys.lock().unwrap();
+        keys.physical.insert(normalize(key), physical.clone());
+        if pressed {
+            keys.names.insert(physical.clone(), normalize(key));
+        }
+
+        let state = keys.states.entry(physical).or_default();
+        // key repeat sends further presses while the key is held, which are not new transitions
+        if pressed && !state.pressed {
+            state.last_press = Some(timestamp.timestamp);
+        } else if !pressed {
+            state.last_release = Some(timestamp.timestamp);
+        }
+        state.pressed = pressed;
+    }
+
+    /// Mark all keys as released, e.g. when the window loses focus and release events would be
+    /// missed.
+    pub fn release_all(&self, time: Instant) {
+        for state in self.keys.lock().unwrap().states.values_mut() {
+            if state.pressed {
+                state.pressed = false;
+                state.last_release = Some(time);
+            }
+        }
+    }
+
+    /// The state of the physical key that most recently produced the given key name.
+    pub fn key(&self, key: &str) -> KeyState {
+        let keys = self.keys.lock().unwrap();
+        keys.physical
+            .get(&normalize(key))
+            .and_then(|physical| keys.states.get(physical))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// All keys that are currently pressed, in the order they were pressed, by the name they
+    /// produced when they were pressed.
+    pub fn pressed(&self) -> Vec<String> {
+        let keys = self.keys.lock().unwrap();
+        let mut pressed = keys
+            .states
+            .iter()
+            .filter(|(_, state)| state.pressed)
+            .filter_map(|(physical, state)| Some((keys.names.get(physical)?.clone(), state.last_press)))
+            .collect::<Vec<_>>();
+        pressed.sort_by_key(|(_, last_press)| *last_press);
+        pressed.into_iter().map(|(key, _)| key).collect()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl KeyboardState {
+    /// Returns True if the key is currently pressed.
+    ///
+    /// Parameters
+    /// ----------
+    /// key : str
+    ///   The key, e.g. "a" or "space". Case is ignored.
+    #[pyo3(name = "is_pressed")]
+    fn py_is_pressed(&self, key: &str) -> bool {
+        self.key(key).pressed
+    }
+
+    /// Returns all keys that are currently pressed, in the order they were pressed.
+    #[pyo3(name = "get_pressed")]
+    fn py_get_pressed(&self) -> Vec<String> {
+        self.pressed()
+    }
+
+    /// The most recent time the key was pressed, or None if it has not been pressed yet.
+    #[pyo3(name = "last_press")]
+    fn py_last_press(&self, key: &str) -> Option<Timestamp> {
+        self.key(key).last_press.map(Into::into)
+    }
+
+    /// The most recent time the key was released, or None if it has not been released yet.
+    #[pyo3(name = "last_release")]
+    fn py_last_release(&self, key: &str) -> Option<Timestamp> {
+        self.key(key).last_release.map(Into::into)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("KeyboardState(pressed={:?})", self.pressed())
+    }
+}
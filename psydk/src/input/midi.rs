@@ -0,0 +1,564 @@
+//! MIDI input and output.
+//!
+//! Note-on and note-off messages from a MIDI input (e.g. a drum pad, which many labs use as a
+//! silent response device) are timestamped when they arrive and can be delivered to a window as
+//! `MidiNoteOn` and `MidiNoteOff` events. MIDI outputs send notes and other messages, e.g. to
+//! trigger an external sampler.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use midir::{Ignore, MidiInputConnection, MidiOutputConnection};
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+
+use super::Event;
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{wait_until, TimelineEvent, Timestamp},
+    visual::window::Window,
+};
+
+/// The name psydk uses for its MIDI clients.
+const CLIENT_NAME: &str = "psydk";
+
+/// Selects a MIDI port by index or by (part of) its name.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+pub enum MidiPortSelector {
+    Index(usize),
+    Name(String),
+}
+
+/// Find a port among `ports`, as (port, name). Without a selector, the first port is used.
+fn select_port<P: Clone>(
+    ports: Vec<P>,
+    name: impl Fn(&P) -> Option<String>,
+    selector: Option<&MidiPortSelector>,
+    direction: &str,
+) -> PsydkResult<(P, String)> {
+    let mut named = ports.into_iter().map(|port| {
+        let port_name = name(&port).unwrap_or_default();
+        (port, port_name)
+    });
+    let found = match selector {
+        None => named.next(),
+        Some(MidiPortSelector::Index(index)) => named.nth(*index),
+        Some(MidiPortSelector::Name(wanted)) => {
+            named.find(|(_, port_name)| port_name.to_lowercase().contains(&wanted.to_lowercase()))
+        }
+    };
+    found.ok_or_else(|| match selector {
+        None => PsydkError::CustomError(format!("No MIDI {direction} ports are available")),
+        Some(selector) => PsydkError::ParameterError(format!("No MIDI {direction} port {selector:?}")),
+    })
+}
+
+/// The names of the available MIDI input and output ports.
+pub fn port_names() -> PsydkResult<(Vec<String>, Vec<String>)> {
+    let to_error = |e: midir::InitError| PsydkError::CustomError(format!("MIDI: {e}"));
+    let input = midir::MidiInput::new(CLIENT_NAME).map_err(to_error)?;
+    let output = midir::MidiOutput::new(CLIENT_NAME).map_err(to_error)?;
+    let inputs = input
+        .ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect();
+    let outputs = output
+        .ports()
+        .iter()
+        .filter_map(|port| output.port_name(port).ok())
+        .collect();
+    Ok((inputs, outputs))
+}
+
+/// A note-on or note-off message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiNote {
+    pub timestamp: Instant,
+    pub note: u8,
+    /// The velocity, 0 for note-off messages.
+    pub velocity: u8,
+    pub channel: u8,
+}
+
+impl MidiNote {
+    /// Parse a raw MIDI message, which is a note if its status is note-on (0x9n) or note-off
+    /// (0x8n).
+    fn parse(timestamp: Instant, message: &[u8]) -> Option<Self> {
+        let [status, note, velocity, ..] = *message else {
+            return None;
+        };
+        let velocity = match status & 0xF0 {
+            0x90 => velocity,
+            0x80 => 0,
+            _ => return None,
+        };
+        Some(Self {
+            timestamp,
+            note,
+            velocity,
+            channel: status & 0x0F,
+        })
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.velocity > 0
+    }
+
+    fn to_event(self) -> Event {
+        match self.is_on() {
+            true => Event::MidiNoteOn {
+                timestamp: self.timestamp.into(),
+                note: self.note,
+                velocity: self.velocity as f32,
+                channel: self.channel,
+            },
+            false => Event::MidiNoteOff {
+                timestamp: self.timestamp.into(),
+                note: self.note,
+                channel: self.channel,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MidiInputState {
+    /// Notes that have not been polled yet.
+    pending: Vec<MidiNote>,
+    log: Vec<MidiNote>,
+    /// The window that notes are delivered to as events.
+    window: Option<Window>,
+}
+
+/// Receives notes from a MIDI input port. Messages are handled by the MIDI driver's thread as soon
+/// as they arrive.
+pub struct MidiInput {
+    pub port_name: String,
+    state: Arc<Mutex<MidiInputState>>,
+    connection: Option<MidiInputConnection<()>>,
+}
+
+impl MidiInput {
+    pub fn open(selector: Option<&MidiPortSelector>) -> PsydkResult<Self> {
+        let mut input =
+            midir::MidiInput::new(CLIENT_NAME).map_err(|e| PsydkError::CustomError(format!("MIDI: {e}")))?;
+        input.ignore(Ignore::All);
+        let (port, port_name) = select_port(input.ports(), |port| input.port_name(port).ok(), selector, "input")?;
+
+        let state = Arc::new(Mutex::new(MidiInputState::default()));
+        let callback_state = state.clone();
+        let connection = input
+            .connect(
+                &port,
+                "psydk-input",
+                move |_, message, _| {
+                    // the driver's timestamps have an arbitrary origin, so notes are timestamped
+                    // on the psydk clock when they arrive
+                    let Some(note) = MidiNote::parse(Instant::now(), message) else {
+                        return;
+                    };
+                    let mut state = callback_state.lock().unwrap();
+                    state.pending.push(note);
+                    state.log.push(note);
+                    if let Some(window) = &state.window {
+                        window.inject_event(note.to_event());
+                    }
+                },
+                (),
+            )
+            .map_err(|e| PsydkError::CustomError(format!("Failed to open the MIDI input {port_name}: {e}")))?;
+
+        Ok(Self {
+            port_name,
+            state,
+            connection: Some(connection),
+        })
+    }
+
+    /// The notes that arrived since the last call.
+    pub fn poll(&self) -> Vec<MidiNote> {
+        std::mem::take(&mut self.state.lock().unwrap().pending)
+    }
+
+    /// All notes that arrived.
+    pub fn log(&self) -> Vec<MidiNote> {
+        self.state.lock().unwrap().log.clone()
+    }
+
+    /// Deliver all following notes to `window` as events, or stop delivering them with `None`.
+    pub fn forward_to(&self, window: Option<Window>) {
+        self.state.lock().unwrap().window = window;
+    }
+
+    /// Close the port. Notes that already arrived are kept.
+    pub fn close(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+    }
+}
+
+/// A message that was sent to a MIDI output.
+#[derive(Debug, Clone)]
+pub struct SentMidiMessage {
+    pub timestamp: Instant,
+    pub message: Vec<u8>,
+}
+
+/// Sends messages to a MIDI output port.
+pub struct MidiOutput {
+    pub port_name: String,
+    connection: Arc<Mutex<MidiOutputConnection>>,
+    log: Arc<Mutex<Vec<SentMidiMessage>>>,
+}
+
+fn send_message(
+    connection: &Mutex<MidiOutputConnection>,
+    log: &Mutex<Vec<SentMidiMessage>>,
+    message: &[u8],
+) -> PsydkResult<Instant> {
+    connection
+        .lock()
+        .unwrap()
+        .send(message)
+        .map_err(|e| PsydkError::CustomError(format!("Failed to send a MIDI message: {e}")))?;
+    let timestamp = Instant::now();
+    log.lock().unwrap().push(SentMidiMessage {
+        timestamp,
+        message: message.to_vec(),
+    });
+    Ok(timestamp)
+}
+
+fn check_data(name: &str, value: u8) -> PsydkResult<u8> {
+    match value {
+        0..=127 => Ok(value),
+        _ => Err(PsydkError::ParameterError(format!(
+            "Invalid MIDI {name} {value}, must be between 0 and 127"
+        ))),
+    }
+}
+
+fn check_channel(channel: u8) -> PsydkResult<u8> {
+    match channel {
+        0..=15 => Ok(channel),
+        _ => Err(PsydkError::ParameterError(format!(
+            "Invalid MIDI channel {channel}, must be between 0 and 15"
+        ))),
+    }
+}
+
+impl MidiOutput {
+    pub fn open(selector: Option<&MidiPortSelector>) -> PsydkResult<Self> {
+        let output = midir::MidiOutput::new(CLIENT_NAME).map_err(|e| PsydkError::CustomError(format!("MIDI: {e}")))?;
+        let (port, port_name) = select_port(output.ports(), |port| output.port_name(port).ok(), selector, "output")?;
+        let connection = output
+            .connect(&port, "psydk-output")
+            .map_err(|e| PsydkError::CustomError(format!("Failed to open the MIDI output {port_name}: {e}")))?;
+        Ok(Self {
+            port_name,
+            connection: Arc::new(Mutex::new(connection)),
+            log: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Send a raw MIDI message.
+    pub fn send(&self, message: &[u8]) -> PsydkResult<Instant> {
+        send_message(&self.connection, &self.log, message)
+    }
+
+    /// Send a note-on message. If `duration` is given, the note-off message is sent after it by a
+    /// background thread.
+    pub fn note_on(&self, note: u8, velocity: u8, channel: u8, duration: Option<Duration>) -> PsydkResult<Instant> {
+        let (note, velocity, channel) = (
+            check_data("note", note)?,
+            check_data("velocity", velocity)?,
+            check_channel(channel)?,
+        );
+        let onset = self.send(&[0x90 | channel, note, velocity])?;
+
+        if let Some(duration) = duration {
+            let (connection, sent) = (self.connection.clone(), self.log.clone());
+            std::thread::spawn(move || {
+                wait_until(onset + duration);
+                if let Err(e) = send_message(&connection, &sent, &[0x80 | channel, note, 0]) {
+                    log::error!("{}", e);
+                }
+            });
+        }
+        Ok(onset)
+    }
+
+    pub fn note_off(&self, note: u8, channel: u8) -> PsydkResult<Instant> {
+        self.send(&[0x80 | check_channel(channel)?, check_data("note", note)?, 0])
+    }
+
+    pub fn control_change(&self, controller: u8, value: u8, channel: u8) -> PsydkResult<Instant> {
+        self.send(&[
+            0xB0 | check_channel(channel)?,
+            check_data("controller", controller)?,
+            check_data("value", value)?,
+        ])
+    }
+
+    pub fn program_change(&self, program: u8, channel: u8) -> PsydkResult<Instant> {
+        self.send(&[0xC0 | check_channel(channel)?, check_data("program", program)?])
+    }
+
+    /// All messages that were sent.
+    pub fn log(&self) -> Vec<SentMidiMessage> {
+        self.log.lock().unwrap().clone()
+    }
+}
+
+/// Receives notes from a MIDI input, e.g. a drum pad used as a silent response device.
+///
+/// Notes are timestamped when they arrive. If a window is given, they are also delivered to it as
+/// "midi_note_on" and "midi_note_off" events with the `note`, `velocity`, and `channel`, so they
+/// can be handled like key presses, e.g. with `add_event_handler` or by the `Scheduler`.
+///
+/// Parameters
+/// ----------
+/// port : int or str, optional
+///   The index of the port or (part of) its name. Defaults to the first port, see `ports`.
+/// window : Window, optional
+///   A window to deliver the notes to as events. See `forward_to`.
+#[cfg(feature = "python")]
+#[pyclass(name = "MidiInput")]
+pub struct PyMidiInput(pub MidiInput);
+
+#[cfg(feature = "python")]
+fn note_to_py<'py>(py: Python<'py>, note: &MidiNote) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("timestamp", Timestamp::from(note.timestamp))?;
+    dict.set_item("note", note.note)?;
+    dict.set_item("velocity", note.velocity)?;
+    dict.set_item("channel", note.channel)?;
+    dict.set_item("on", note.is_on())?;
+    Ok(dict)
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMidiInput {
+    #[new]
+    #[pyo3(signature = (port = None, window = None))]
+    fn __new__(port: Option<MidiPortSelector>, window: Option<Window>) -> PyResult<Self> {
+        let input = MidiInput::open(port.as_ref())?;
+        input.forward_to(window);
+        Ok(Self(input))
+    }
+
+    /// The names of the available MIDI input ports.
+    #[staticmethod]
+    #[pyo3(name = "ports")]
+    fn py_ports() -> PyResult<Vec<String>> {
+        Ok(port_names()?.0)
+    }
+
+    /// The notes that arrived since the last call, as dictionaries with the "timestamp", the
+    /// "note", the "velocity" (0 for releases), the "channel", and whether the note was played
+    /// ("on") or released.
+    #[pyo3(name = "poll")]
+    fn py_poll<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0.poll().iter().map(|note| note_to_py(py, note)).collect()
+    }
+
+    /// Deliver all following notes to a window as "midi_note_on" and "midi_note_off" events, or
+    /// stop delivering them with None.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window or None
+    ///   The window to deliver the notes to.
+    #[pyo3(name = "forward_to")]
+    fn py_forward_to(&self, window: Option<Window>) {
+        self.0.forward_to(window);
+    }
+
+    /// All notes that arrived, in the format of `poll`.
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0.log().iter().map(|note| note_to_py(py, note)).collect()
+    }
+
+    /// Add all played notes to a timeline as "midi_note" events, labelled with the note number.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for note in self.0.log().iter().filter(|note| note.is_on()) {
+            timeline.0.add(TimelineEvent {
+                kind: "midi_note".to_string(),
+                time: note.timestamp,
+                label: Some(note.note.to_string()),
+                data: vec![
+                    ("velocity".to_string(), note.velocity.to_string()),
+                    ("channel".to_string(), note.channel.to_string()),
+                ],
+            });
+        }
+    }
+
+    /// The name of the port.
+    #[getter(port)]
+    fn py_port(&self) -> String {
+        self.0.port_name.clone()
+    }
+
+    /// Close the port. Notes that already arrived are kept.
+    #[pyo3(name = "close")]
+    fn py_close(&mut self) {
+        self.0.close();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MidiInput(port={:?})", self.0.port_name)
+    }
+}
+
+/// Sends messages to a MIDI output, e.g. to trigger an external sampler or sound module.
+///
+/// Parameters
+/// ----------
+/// port : int or str, optional
+///   The index of the port or (part of) its name. Defaults to the first port, see `ports`.
+#[cfg(feature = "python")]
+#[pyclass(name = "MidiOutput")]
+pub struct PyMidiOutput(pub MidiOutput);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMidiOutput {
+    #[new]
+    #[pyo3(signature = (port = None))]
+    fn __new__(port: Option<MidiPortSelector>) -> PyResult<Self> {
+        Ok(Self(MidiOutput::open(port.as_ref())?))
+    }
+
+    /// The names of the available MIDI output ports.
+    #[staticmethod]
+    #[pyo3(name = "ports")]
+    fn py_ports() -> PyResult<Vec<String>> {
+        Ok(port_names()?.1)
+    }
+
+    /// Play a note.
+    ///
+    /// Parameters
+    /// ----------
+    /// note : int
+    ///   The MIDI note number (0-127), e.g. 60 for middle C.
+    /// velocity : int, optional
+    ///   The velocity (1-127). Defaults to 100.
+    /// channel : int, optional
+    ///   The MIDI channel (0-15). Defaults to 0.
+    /// duration : float, optional
+    ///   Release the note after this many seconds. Otherwise, the note is held until `note_off`.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   When the note-on message was sent.
+    #[pyo3(name = "note_on")]
+    #[pyo3(signature = (note, velocity = 100, channel = 0, duration = None))]
+    fn py_note_on(&self, note: u8, velocity: u8, channel: u8, duration: Option<f64>) -> PyResult<Timestamp> {
+        let duration = duration
+            .map(|duration| {
+                Duration::try_from_secs_f64(duration).map_err(|_| {
+                    PsydkError::ParameterError(format!("Invalid duration {duration}, must be non-negative"))
+                })
+            })
+            .transpose()?;
+        Ok(self.0.note_on(note, velocity, channel, duration)?.into())
+    }
+
+    /// Release a note.
+    ///
+    /// Parameters
+    /// ----------
+    /// note : int
+    ///   The MIDI note number (0-127).
+    /// channel : int, optional
+    ///   The MIDI channel (0-15). Defaults to 0.
+    #[pyo3(name = "note_off")]
+    #[pyo3(signature = (note, channel = 0))]
+    fn py_note_off(&self, note: u8, channel: u8) -> PyResult<Timestamp> {
+        Ok(self.0.note_off(note, channel)?.into())
+    }
+
+    /// Send a control change message.
+    ///
+    /// Parameters
+    /// ----------
+    /// controller : int
+    ///   The controller number (0-127).
+    /// value : int
+    ///   The value (0-127).
+    /// channel : int, optional
+    ///   The MIDI channel (0-15). Defaults to 0.
+    #[pyo3(name = "control_change")]
+    #[pyo3(signature = (controller, value, channel = 0))]
+    fn py_control_change(&self, controller: u8, value: u8, channel: u8) -> PyResult<Timestamp> {
+        Ok(self.0.control_change(controller, value, channel)?.into())
+    }
+
+    /// Send a program change message, e.g. to select a sound.
+    ///
+    /// Parameters
+    /// ----------
+    /// program : int
+    ///   The program number (0-127).
+    /// channel : int, optional
+    ///   The MIDI channel (0-15). Defaults to 0.
+    #[pyo3(name = "program_change")]
+    #[pyo3(signature = (program, channel = 0))]
+    fn py_program_change(&self, program: u8, channel: u8) -> PyResult<Timestamp> {
+        Ok(self.0.program_change(program, channel)?.into())
+    }
+
+    /// Send a raw MIDI message.
+    ///
+    /// Parameters
+    /// ----------
+    /// message : bytes or list[int]
+    ///   The bytes of the message, including the status byte.
+    #[pyo3(name = "send")]
+    fn py_send(&self, message: Vec<u8>) -> PyResult<Timestamp> {
+        Ok(self.0.send(&message)?.into())
+    }
+
+    /// All messages that were sent, as dictionaries with the "timestamp" and the "message" bytes.
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log()
+            .into_iter()
+            .map(|sent| {
+                let dict = PyDict::new(py);
+                dict.set_item("timestamp", Timestamp::from(sent.timestamp))?;
+                dict.set_item("message", sent.message)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// The name of the port.
+    #[getter(port)]
+    fn py_port(&self) -> String {
+        self.0.port_name.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MidiOutput(port={:?})", self.0.port_name)
+    }
+}
@@ -1,5 +1,6 @@
 use std::{convert::Infallible, ops::Deref, str::FromStr, sync::Arc};
 
+#[cfg(feature = "python")]
 use pyo3::{
     pyclass, pymethods,
     types::{PyAnyMethods, PyString},
@@ -24,11 +25,29 @@ use crate::{
     visual::{geometry::Size, window::Window},
 };
 
+pub mod deadline;
+pub mod gaze;
+pub mod head;
+pub mod keyboard;
+#[cfg(feature = "midi")]
+pub mod midi;
+#[cfg(feature = "pupil")]
+pub mod pupil;
+#[cfg(feature = "gamepad")]
+pub mod rumble;
+pub mod sampler;
+pub mod scanner;
+pub mod simulation;
+pub mod text;
+pub mod trajectory;
 // pub mod video;
 
+/// Touchpads report scrolling in pixels, which is converted to lines at this rate.
+const PIXELS_PER_LINE: f64 = 20.0;
+
 /// A mouse button.
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum MouseButton {
     /// The left mouse button.
     Left(),
@@ -45,7 +64,7 @@ pub enum MouseButton {
 }
 
 #[derive(Debug, Clone, enum_fields::EnumFields, strum::EnumDiscriminants)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[strum_discriminants(
     name(EventKind),
     strum(serialize_all = "snake_case"),
@@ -70,6 +89,35 @@ pub enum Event {
         /// KeyCode of the key that was released.
         code: u32,
     },
+    /// Text was entered, either typed on the keyboard or committed by an input method. Unlike key
+    /// presses, this takes the keyboard layout, dead keys, and input methods into account, so use
+    /// it to collect typed text.
+    Text {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The text that was entered.
+        text: String,
+    },
+    /// The text an input method is composing (e.g., the reading of Chinese or Japanese characters
+    /// before a candidate is chosen) has changed. The text is empty when the composition ends.
+    /// Only delivered while input methods are allowed for the window (see `Window.ime_allowed`).
+    ImePreedit {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The text that is being composed.
+        text: String,
+        /// The byte range of the composed text that is selected (or the position of the cursor
+        /// in it, if the range is empty), if any.
+        selection: Option<(usize, usize)>,
+    },
+    /// An input method has committed text. Every commit is followed by a `Text` event with the
+    /// same text.
+    ImeCommit {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The committed text.
+        text: String,
+    },
 
     /// A mouse button press event. This is triggered when a mouse button is
     /// pressed.
@@ -194,11 +242,69 @@ pub enum Event {
     MouseWheel {
         /// Timestamp of the event.
         timestamp: Timestamp,
-        /// The amount of horizontal scrolling.
+        /// The amount of horizontal scrolling in lines. Positive values move the content to the
+        /// right.
         horizontal: f32,
-        /// The amount of vertical scrolling.
+        /// The amount of vertical scrolling in lines. Positive values move the content down (i.e.,
+        /// scroll towards the top).
         vertical: f32,
     },
+    /// Raw motion of the mouse, reported by the device at its polling rate. Unlike `CursorMoved`,
+    /// this is not affected by pointer acceleration and is reported even when the cursor is at the
+    /// edge of the screen. Only delivered to event handlers, not to event receivers.
+    MouseMotion {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The movement since the last event in device units.
+        delta: (f64, f64),
+    },
+    /// The onset of a saccade, detected in the gaze samples of the window when the angular
+    /// velocity of the gaze exceeds the threshold set with `detect_gaze_events`.
+    Saccade {
+        /// Timestamp of the sample at which the velocity exceeded the threshold.
+        timestamp: Timestamp,
+        /// The gaze position at the onset, in the coordinate system of the window.
+        position: (f32, f32),
+        /// The angular velocity of the gaze in degrees per second.
+        velocity: f32,
+    },
+    /// A blink, detected in the gaze samples of the window as a gap in valid samples. Reported when
+    /// valid samples resume.
+    Blink {
+        /// Timestamp of the first missing sample.
+        timestamp: Timestamp,
+        /// The duration of the blink in seconds.
+        duration: f64,
+    },
+    /// A note was played on a MIDI device, e.g. a pad of a drum controller (see `MidiInput`).
+    MidiNoteOn {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The MIDI note number (0-127).
+        note: u8,
+        /// The MIDI velocity (1-127).
+        velocity: f32,
+        /// The MIDI channel (0-15).
+        channel: u8,
+    },
+    /// A note was released on a MIDI device. Note-on messages with a velocity of 0 count as
+    /// releases.
+    MidiNoteOff {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The MIDI note number (0-127).
+        note: u8,
+        /// The MIDI channel (0-15).
+        channel: u8,
+    },
+    /// No response arrived before a response deadline (see `Window.set_response_deadline`).
+    /// Emitted at the deadline.
+    Timeout {
+        /// Timestamp of the event.
+        timestamp: Timestamp,
+        /// The deadline that passed.
+        scheduled_for: Timestamp,
+    },
     /// Onset event.
     Onset {
         /// Timestamp of the event.
@@ -248,6 +354,7 @@ impl Event {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Event {
     #[getter]
@@ -256,6 +363,12 @@ impl Event {
         self.position().cloned()
     }
 
+    #[getter]
+    #[pyo3(name = "delta")]
+    fn py_delta(&self) -> Option<(f64, f64)> {
+        self.delta().cloned()
+    }
+
     #[getter]
     #[pyo3(name = "window")]
     fn py_window(&self) -> Option<Window> {
@@ -268,6 +381,18 @@ impl Event {
         self.key().cloned()
     }
 
+    #[getter]
+    #[pyo3(name = "text")]
+    fn py_text(&self) -> Option<String> {
+        self.text().cloned()
+    }
+
+    #[getter]
+    #[pyo3(name = "selection")]
+    fn py_selection(&self) -> Option<(usize, usize)> {
+        self.selection().cloned().flatten()
+    }
+
     #[getter]
     #[pyo3(name = "id")]
     fn py_id(&self) -> Option<u64> {
@@ -292,6 +417,36 @@ impl Event {
         self.name().cloned()
     }
 
+    #[getter]
+    #[pyo3(name = "velocity")]
+    fn py_velocity(&self) -> Option<f32> {
+        self.velocity().cloned()
+    }
+
+    #[getter]
+    #[pyo3(name = "duration")]
+    fn py_duration(&self) -> Option<f64> {
+        self.duration().cloned()
+    }
+
+    #[getter]
+    #[pyo3(name = "note")]
+    fn py_note(&self) -> Option<u8> {
+        self.note().cloned()
+    }
+
+    #[getter]
+    #[pyo3(name = "channel")]
+    fn py_channel(&self) -> Option<u8> {
+        self.channel().cloned()
+    }
+
+    #[getter]
+    #[pyo3(name = "scheduled_for")]
+    fn py_scheduled_for(&self) -> Option<Timestamp> {
+        self.scheduled_for().cloned()
+    }
+
     #[getter]
     #[pyo3(name = "kind")]
     fn py_kind(&self) -> EventKind {
@@ -397,9 +552,10 @@ impl EventTryFrom<winit_event::WindowEvent> for Event {
                 let window_state = window.state.lock().unwrap();
                 let window_state = window_state.as_ref().unwrap();
                 let window_size = window_state.size;
-                let position = (
+                let position = window_state.coordinate_system.from_scene(
                     position.0 - (window_size.width as f32 / 2.0),
                     position.1 - (window_size.height as f32 / 2.0),
+                    window_size,
                 );
 
                 // dispatch on TouchPhase
@@ -484,6 +640,32 @@ impl EventTryFrom<winit_event::WindowEvent> for Event {
                 stage,
                 window: window.clone(),
             },
+            // match input method events (enabling and disabling is tracked by the event loop)
+            // match mouse wheel events
+            winit_event::WindowEvent::MouseWheel { delta, .. } => {
+                let (horizontal, vertical) = match delta {
+                    winit_event::MouseScrollDelta::LineDelta(x, y) => (x, y),
+                    winit_event::MouseScrollDelta::PixelDelta(position) => (
+                        (position.x / PIXELS_PER_LINE) as f32,
+                        (position.y / PIXELS_PER_LINE) as f32,
+                    ),
+                };
+                Event::MouseWheel {
+                    timestamp: timestamp.into(),
+                    horizontal,
+                    vertical,
+                }
+            }
+            winit_event::WindowEvent::Ime(winit_event::Ime::Preedit(text, selection)) => Event::ImePreedit {
+                timestamp: timestamp.into(),
+                text,
+                selection,
+            },
+            winit_event::WindowEvent::Ime(winit_event::Ime::Commit(text)) => Event::ImeCommit {
+                timestamp: timestamp.into(),
+                text,
+            },
+            winit_event::WindowEvent::Ime(_) => return Err("Input method state changes are not events"),
             // match any other event
             _ => Event::Other {
                 timestamp: timestamp.into(),
@@ -497,14 +679,14 @@ impl EventTryFrom<winit_event::WindowEvent> for Event {
 
 /// Receives physical input events.
 #[derive(Debug)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct EventReceiver {
     pub(crate) receiver: async_broadcast::Receiver<Event>,
 }
 
 /// Contains a vector of events.
 #[derive(Debug, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct EventVec(Vec<Event>);
 
 // convenience methods for KeyEventVec
@@ -522,6 +704,7 @@ impl EventVec {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl EventVec {
     /// Check if the given KeyEventVec contains the provided key in the
@@ -591,6 +774,7 @@ impl EventReceiver {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl EventReceiver {
     /// Polls the receiver for new events.
@@ -626,6 +810,7 @@ pub trait EventHandlingExt {
     fn dispatch_event(&self, event: Event) -> bool;
 }
 
+#[cfg(feature = "python")]
 impl FromPyObject<'_> for EventKind {
     fn extract_bound(ob: &pyo3::Bound<'_, pyo3::PyAny>) -> pyo3::PyResult<Self> {
         let kind = ob.extract::<String>()?;
@@ -646,6 +831,7 @@ impl FromPyObject<'_> for EventKind {
     }
 }
 
+#[cfg(feature = "python")]
 impl<'py> IntoPyObject<'py> for EventKind {
     type Target = PyString;
     type Output = Bound<'py, Self::Target>;
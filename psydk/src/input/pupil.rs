@@ -0,0 +1,652 @@
+//! Gaze and pupil data from Pupil Labs eye trackers, received over the network API of Pupil
+//! Capture or Pupil Service (Pupil Remote). This covers Pupil Core and Neon when it is connected to
+//! a computer running Pupil Capture.
+//!
+//! Pupil Remote is a ZMQ request socket (port 50020 by default) that tells us the ports of the
+//! data sockets and the time of the Pupil clock. Data is published on the subscription socket as
+//! multipart messages of a topic and a msgpack payload, and annotations are sent to Pupil Capture
+//! on the publishing socket in the same format. Pupil timestamps are converted to `Timestamp`s with
+//! a clock offset that is estimated from the round trip of time requests.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use derive_debug::Dbg;
+#[cfg(feature = "python")]
+use numpy::{ndarray::Array2, IntoPyArray};
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use serde::Deserialize;
+
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+    visual::{calibration::EyeTracker, window::Window},
+};
+
+/// How long to wait for a reply of Pupil Remote.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the receiving thread waits for data before checking whether it should stop.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(10);
+
+fn to_error(e: zmq::Error) -> PsydkError {
+    PsydkError::IOError(std::io::Error::other(e))
+}
+
+/// The offset between the Pupil clock and the local clock.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSync {
+    /// The local time that the offset refers to.
+    anchor: Instant,
+    /// The Pupil time at `anchor` in seconds.
+    pub offset: f64,
+    /// The round trip time of the request the offset was estimated from. The offset is accurate
+    /// to about half of it.
+    pub round_trip: Duration,
+}
+
+impl ClockSync {
+    /// The local time of a Pupil timestamp.
+    pub fn to_instant(&self, pupil_time: f64) -> Option<Instant> {
+        let seconds = pupil_time - self.offset;
+        let duration = Duration::try_from_secs_f64(seconds.abs()).ok()?;
+        if seconds >= 0.0 {
+            self.anchor.checked_add(duration)
+        } else {
+            self.anchor.checked_sub(duration)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GazeDatum {
+    norm_pos: (f64, f64),
+    confidence: f64,
+    timestamp: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SurfaceGaze {
+    norm_pos: (f64, f64),
+    confidence: f64,
+    on_surf: bool,
+    timestamp: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SurfaceDatum {
+    gaze_on_surfaces: Vec<SurfaceGaze>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PupilDatum {
+    id: u8,
+    #[serde(default)]
+    diameter: f64,
+    #[serde(default)]
+    diameter_3d: Option<f64>,
+    confidence: f64,
+    timestamp: f64,
+}
+
+#[derive(Debug)]
+struct DataBuffer {
+    gaze_times: Vec<Instant>,
+    /// x, y, and confidence of every gaze sample.
+    gaze: Vec<f32>,
+    pupil_times: Vec<Instant>,
+    /// Eye, diameter in pixels, diameter in mm (NaN without the 3D model), and confidence of every
+    /// pupil sample.
+    pupil: Vec<f32>,
+    latest_gaze: Option<(f32, f32)>,
+    trial_onset: Instant,
+}
+
+/// Receives gaze and pupil data from Pupil Capture on a background thread.
+///
+/// Without a surface, gaze positions are normalized coordinates of the world camera image (origin
+/// at the bottom left). With a surface (defined in the Surface Tracker plugin to cover the screen),
+/// gaze positions on the surface are converted to the coordinate system of the window, and the
+/// window's `gaze_position` follows the gaze.
+#[derive(Dbg)]
+pub struct PupilTracker {
+    #[dbg(placeholder = "...")]
+    remote: Mutex<zmq::Socket>,
+    #[dbg(placeholder = "...")]
+    publisher: Mutex<zmq::Socket>,
+    #[dbg(placeholder = "...")]
+    context: zmq::Context,
+    sub_address: String,
+    pub window: Option<Window>,
+    pub surface: Option<String>,
+    /// Gaze samples below this confidence don't update the window's gaze position.
+    pub min_confidence: f64,
+    clock: Arc<Mutex<ClockSync>>,
+    buffer: Arc<Mutex<DataBuffer>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PupilTracker {
+    /// Connect to Pupil Remote at `address:port` and estimate the clock offset.
+    pub fn connect(
+        address: &str,
+        port: u16,
+        window: Option<Window>,
+        surface: Option<String>,
+        min_confidence: f64,
+    ) -> PsydkResult<Self> {
+        let context = zmq::Context::new();
+        let remote = context.socket(zmq::REQ).map_err(to_error)?;
+        let timeout = REQUEST_TIMEOUT.as_millis() as i32;
+        remote.set_rcvtimeo(timeout).map_err(to_error)?;
+        remote.set_sndtimeo(timeout).map_err(to_error)?;
+        remote.set_linger(0).map_err(to_error)?;
+        remote.connect(&format!("tcp://{address}:{port}")).map_err(to_error)?;
+
+        let sub_port = Self::request(&remote, "SUB_PORT")?;
+        let pub_port = Self::request(&remote, "PUB_PORT")?;
+        let clock = Self::estimate_clock(&remote, 20)?;
+
+        let publisher = context.socket(zmq::PUB).map_err(to_error)?;
+        publisher.set_linger(0).map_err(to_error)?;
+        publisher
+            .connect(&format!("tcp://{address}:{pub_port}"))
+            .map_err(to_error)?;
+
+        Ok(Self {
+            remote: Mutex::new(remote),
+            publisher: Mutex::new(publisher),
+            context,
+            sub_address: format!("tcp://{address}:{sub_port}"),
+            window,
+            surface,
+            min_confidence,
+            clock: Arc::new(Mutex::new(clock)),
+            buffer: Arc::new(Mutex::new(DataBuffer {
+                gaze_times: Vec::new(),
+                gaze: Vec::new(),
+                pupil_times: Vec::new(),
+                pupil: Vec::new(),
+                latest_gaze: None,
+                trial_onset: Instant::now(),
+            })),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    fn request(remote: &zmq::Socket, request: &str) -> PsydkResult<String> {
+        remote.send(request, 0).map_err(to_error)?;
+        let reply = remote.recv_string(0).map_err(|e| match e {
+            zmq::Error::EAGAIN => PsydkError::CustomError(format!(
+                "Pupil Remote did not reply within {} ms, is Pupil Capture running?",
+                REQUEST_TIMEOUT.as_millis()
+            )),
+            e => to_error(e),
+        })?;
+        reply.map_err(|_| PsydkError::CustomError("Pupil Remote sent an invalid reply".into()))
+    }
+
+    /// Request the Pupil time `samples` times and keep the estimate with the shortest round trip.
+    fn estimate_clock(remote: &zmq::Socket, samples: u32) -> PsydkResult<ClockSync> {
+        let mut best: Option<ClockSync> = None;
+        for _ in 0..samples.max(1) {
+            let sent = Instant::now();
+            let reply = Self::request(remote, "t")?;
+            let received = Instant::now();
+
+            let pupil_time: f64 = reply
+                .trim()
+                .parse()
+                .map_err(|_| PsydkError::CustomError(format!("Invalid Pupil time {reply:?}")))?;
+            let round_trip = received - sent;
+            // the reply was most likely sent halfway through the round trip
+            let sync = ClockSync {
+                anchor: sent + round_trip / 2,
+                offset: pupil_time,
+                round_trip,
+            };
+            if best.map_or(true, |best| round_trip < best.round_trip) {
+                best = Some(sync);
+            }
+        }
+        Ok(best.unwrap())
+    }
+
+    /// Estimate the clock offset again, e.g. before every block of a long session. Samples that
+    /// were already received keep their timestamps.
+    pub fn sync_clock(&self, samples: u32) -> PsydkResult<ClockSync> {
+        let clock = Self::estimate_clock(&self.remote.lock().unwrap(), samples)?;
+        *self.clock.lock().unwrap() = clock;
+        Ok(clock)
+    }
+
+    pub fn clock(&self) -> ClockSync {
+        *self.clock.lock().unwrap()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    /// Start receiving data. Does nothing if the tracker is already receiving.
+    pub fn start(&mut self) -> PsydkResult<()> {
+        if self.is_running() {
+            return Ok(());
+        }
+        self.stop.store(false, Ordering::Relaxed);
+
+        let subscriber = self.context.socket(zmq::SUB).map_err(to_error)?;
+        subscriber
+            .set_rcvtimeo(RECEIVE_TIMEOUT.as_millis() as i32)
+            .map_err(to_error)?;
+        subscriber.connect(&self.sub_address).map_err(to_error)?;
+        subscriber.set_subscribe(b"pupil.0.3d").map_err(to_error)?;
+        subscriber.set_subscribe(b"pupil.1.3d").map_err(to_error)?;
+        let surface_topic = self.surface.as_ref().map(|surface| format!("surfaces.{surface}"));
+        match &surface_topic {
+            Some(topic) => subscriber.set_subscribe(topic.as_bytes()).map_err(to_error)?,
+            None => subscriber.set_subscribe(b"gaze.").map_err(to_error)?,
+        }
+
+        let buffer = self.buffer.clone();
+        let clock = self.clock.clone();
+        let stop = self.stop.clone();
+        let window = self.window.clone();
+        let min_confidence = self.min_confidence;
+        self.thread = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let parts = match subscriber.recv_multipart(0) {
+                    Ok(parts) => parts,
+                    Err(zmq::Error::EAGAIN) => continue,
+                    Err(e) => {
+                        log::error!("Failed to receive data from Pupil Capture: {}", e);
+                        break;
+                    }
+                };
+                let [topic, payload, ..] = &parts[..] else {
+                    continue;
+                };
+                let topic = String::from_utf8_lossy(topic);
+                let clock = *clock.lock().unwrap();
+
+                if topic.starts_with("pupil.") {
+                    let Ok(datum) = rmp_serde::from_slice::<PupilDatum>(payload) else {
+                        continue;
+                    };
+                    let Some(time) = clock.to_instant(datum.timestamp) else {
+                        continue;
+                    };
+                    let mut buffer = buffer.lock().unwrap();
+                    buffer.pupil_times.push(time);
+                    buffer.pupil.extend([
+                        datum.id as f32,
+                        datum.diameter as f32,
+                        datum.diameter_3d.map_or(f32::NAN, |diameter| diameter as f32),
+                        datum.confidence as f32,
+                    ]);
+                    continue;
+                }
+
+                // gaze samples, either on the surface (converted to window coordinates) or in the
+                // world camera image
+                let samples: Vec<(f64, (f32, f32), f64)> = match &surface_topic {
+                    Some(surface_topic) if topic == *surface_topic => {
+                        let Ok(datum) = rmp_serde::from_slice::<SurfaceDatum>(payload) else {
+                            continue;
+                        };
+                        let Some(window) = &window else {
+                            continue;
+                        };
+                        let Ok((size, coordinate_system)) =
+                            window.with_state(|state| (state.size, state.coordinate_system))
+                        else {
+                            continue;
+                        };
+                        datum
+                            .gaze_on_surfaces
+                            .into_iter()
+                            .filter(|gaze| gaze.on_surf)
+                            .map(|gaze| {
+                                // surface coordinates have their origin at the bottom left
+                                let (x, y) = (
+                                    (gaze.norm_pos.0 as f32 - 0.5) * size.width as f32,
+                                    (0.5 - gaze.norm_pos.1 as f32) * size.height as f32,
+                                );
+                                let position = coordinate_system.from_scene(x, y, size);
+                                (gaze.timestamp, position, gaze.confidence)
+                            })
+                            .collect()
+                    }
+                    _ => {
+                        let Ok(datum) = rmp_serde::from_slice::<GazeDatum>(payload) else {
+                            continue;
+                        };
+                        let position = (datum.norm_pos.0 as f32, datum.norm_pos.1 as f32);
+                        vec![(datum.timestamp, position, datum.confidence)]
+                    }
+                };
+
+                let mut window_samples = Vec::new();
+                {
+                    let mut buffer = buffer.lock().unwrap();
+                    for (timestamp, (x, y), confidence) in samples {
+                        let Some(time) = clock.to_instant(timestamp) else {
+                            continue;
+                        };
+                        buffer.gaze_times.push(time);
+                        buffer.gaze.extend([x, y, confidence as f32]);
+                        let valid = confidence >= min_confidence;
+                        if valid {
+                            buffer.latest_gaze = Some((x, y));
+                        }
+                        window_samples.push((time, valid.then_some((x, y))));
+                    }
+                }
+
+                // surface gaze is in window coordinates, so it drives the window's gaze position
+                // and the detection of saccades and blinks
+                if let (Some(window), true) = (&window, surface_topic.is_some()) {
+                    for (time, position) in window_samples {
+                        let _ = window.add_gaze_sample(time, position);
+                    }
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// Stop receiving data. Data that was already received is kept.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Send an annotation (e.g. the onset of a trial) to Pupil Capture, where it is stored with
+    /// the recording. `time` is converted to Pupil time.
+    pub fn annotate(&self, label: &str, time: Instant, duration: f64) -> PsydkResult<()> {
+        let clock = self.clock();
+        let timestamp = clock.offset + Timestamp::from(time).seconds_since_instant(clock.anchor);
+        let topic = "annotation";
+
+        #[derive(serde::Serialize)]
+        struct Annotation<'a> {
+            topic: &'a str,
+            label: &'a str,
+            timestamp: f64,
+            duration: f64,
+        }
+        let payload = rmp_serde::to_vec_named(&Annotation {
+            topic,
+            label,
+            timestamp,
+            duration,
+        })
+        .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        let publisher = self.publisher.lock().unwrap();
+        publisher.send(topic, zmq::SNDMORE).map_err(to_error)?;
+        publisher.send(payload, 0).map_err(to_error)?;
+        Ok(())
+    }
+
+    /// Discard all data and start a new trial.
+    pub fn start_trial(&self) -> Instant {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.gaze_times.clear();
+        buffer.gaze.clear();
+        buffer.pupil_times.clear();
+        buffer.pupil.clear();
+        buffer.trial_onset = Instant::now();
+        buffer.trial_onset
+    }
+
+    /// The most recent gaze position with at least `min_confidence`.
+    pub fn latest_gaze(&self) -> Option<(f32, f32)> {
+        self.buffer.lock().unwrap().latest_gaze
+    }
+
+    /// The positions of the gaze samples of the current trial between `start` and `end` with at
+    /// least `min_confidence`.
+    pub fn gaze_between(&self, start: Instant, end: Instant) -> Vec<(f32, f32)> {
+        let buffer = self.buffer.lock().unwrap();
+        buffer
+            .gaze_times
+            .iter()
+            .zip(buffer.gaze.chunks_exact(3))
+            .filter(|(time, sample)| (start..=end).contains(*time) && sample[2] as f64 >= self.min_confidence)
+            .map(|(_, sample)| (sample[0], sample[1]))
+            .collect()
+    }
+}
+
+impl EyeTracker for PupilTracker {
+    fn gaze_samples(&mut self, start: Instant, end: Instant) -> PsydkResult<Vec<(f32, f32)>> {
+        Ok(self.gaze_between(start, end))
+    }
+}
+
+impl Drop for PupilTracker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Receives gaze and pupil data from a Pupil Labs eye tracker (Pupil Core, or Neon connected to
+/// Pupil Capture) over the network API of Pupil Capture. Pupil Remote must be enabled in Pupil
+/// Capture.
+///
+/// Data is received on a background thread and timestamped on the psydk clock, using a clock offset
+/// that is estimated when connecting (and again with `sync_clock`). Use `start_trial` at the
+/// beginning of each trial and `get_trial` at the end to retrieve the data as numpy arrays.
+///
+/// To get gaze positions on the screen, define a surface that covers the screen in the Surface
+/// Tracker plugin of Pupil Capture (e.g. with AprilTag markers in the corners) and pass its name as
+/// `surface`. Gaze positions are then converted to the coordinate system of `window`, and the
+/// window's `gaze_position` (shown by the debug overlay) follows the gaze.
+///
+/// Parameters
+/// ----------
+/// address : str, optional
+///   The address of the computer running Pupil Capture. Defaults to "127.0.0.1".
+/// port : int, optional
+///   The port of Pupil Remote. Defaults to 50020.
+/// window : Window, optional
+///   The window to convert surface gaze positions to. Required with `surface`.
+/// surface : str, optional
+///   The name of a surface in the Surface Tracker plugin. Without a surface, gaze positions are
+///   normalized coordinates of the world camera image.
+/// min_confidence : float, optional
+///   Gaze samples below this confidence are recorded, but don't update `latest_gaze` and the
+///   window's gaze position. Defaults to 0.6.
+#[cfg(feature = "python")]
+#[pyclass(name = "PupilLabsTracker")]
+pub struct PyPupilTracker(pub PupilTracker);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyPupilTracker {
+    #[new]
+    #[pyo3(signature = (address = "127.0.0.1", port = 50020, window = None, surface = None, min_confidence = 0.6))]
+    fn __new__(
+        py: Python,
+        address: &str,
+        port: u16,
+        window: Option<Window>,
+        surface: Option<String>,
+        min_confidence: f64,
+    ) -> PyResult<Self> {
+        if surface.is_some() && window.is_none() {
+            return Err(PsydkError::ParameterError("A window is required to map gaze on a surface".into()).into());
+        }
+        let mut tracker =
+            py.allow_threads(move || PupilTracker::connect(address, port, window, surface, min_confidence))?;
+        tracker.start()?;
+        Ok(Self(tracker))
+    }
+
+    /// Start receiving data again after `stop`.
+    #[pyo3(name = "start")]
+    fn py_start(&mut self) -> PyResult<()> {
+        Ok(self.0.start()?)
+    }
+
+    /// Stop receiving data.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+
+    /// Estimate the offset between the Pupil clock and the psydk clock again, e.g. before every
+    /// block of a long session.
+    ///
+    /// Parameters
+    /// ----------
+    /// samples : int, optional
+    ///   The number of time requests. The one with the shortest round trip is used.
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///   The round trip time of that request in seconds. The offset is accurate to about half of
+    ///   it.
+    #[pyo3(name = "sync_clock")]
+    #[pyo3(signature = (samples = 20))]
+    fn py_sync_clock(&self, py: Python, samples: u32) -> PyResult<f64> {
+        let clock = py.allow_threads(|| self.0.sync_clock(samples))?;
+        Ok(clock.round_trip.as_secs_f64())
+    }
+
+    /// The round trip time in seconds of the request the current clock offset is based on.
+    #[getter(clock_round_trip)]
+    fn py_clock_round_trip(&self) -> f64 {
+        self.0.clock().round_trip.as_secs_f64()
+    }
+
+    /// Convert a Pupil timestamp (e.g. from a recording) to a Timestamp.
+    ///
+    /// Parameters
+    /// ----------
+    /// pupil_time : float
+    ///   The Pupil timestamp in seconds.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp or None
+    ///   The timestamp, or None if it can't be represented.
+    #[pyo3(name = "to_timestamp")]
+    fn py_to_timestamp(&self, pupil_time: f64) -> Option<Timestamp> {
+        self.0.clock().to_instant(pupil_time).map(Timestamp::from)
+    }
+
+    /// Send an annotation to Pupil Capture, where it is stored with the recording.
+    ///
+    /// Parameters
+    /// ----------
+    /// label : str
+    ///   The label of the annotation, e.g. "trial_onset".
+    /// timestamp : Timestamp, optional
+    ///   The time of the annotation. Defaults to now.
+    /// duration : float, optional
+    ///   The duration of the annotated event in seconds.
+    #[pyo3(name = "annotate")]
+    #[pyo3(signature = (label, timestamp = None, duration = 0.0))]
+    fn py_annotate(&self, py: Python, label: &str, timestamp: Option<Timestamp>, duration: f64) -> PyResult<()> {
+        let time = timestamp.map_or_else(Instant::now, |timestamp| timestamp.timestamp);
+        Ok(py.allow_threads(|| self.0.annotate(label, time, duration))?)
+    }
+
+    /// Discard all data and start a new trial.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The onset of the trial.
+    #[pyo3(name = "start_trial")]
+    fn py_start_trial(&self) -> Timestamp {
+        self.0.start_trial().into()
+    }
+
+    /// The data of the current trial.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary with "gaze_time" (the time of each gaze sample in seconds relative to the
+    ///   trial onset), "gaze" (one row per sample with x, y, and confidence), "pupil_time", "pupil"
+    ///   (one row per sample with the eye, the diameter in pixels, the diameter in mm, and
+    ///   confidence), and "onset" (the trial onset).
+    #[pyo3(name = "get_trial")]
+    fn py_get_trial<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let buffer = self.0.buffer.lock().unwrap();
+        let onset = buffer.trial_onset;
+        let relative = |times: &[Instant]| {
+            times
+                .iter()
+                .map(|time| Timestamp::from(*time).seconds_since_instant(onset))
+                .collect::<Vec<_>>()
+        };
+        let to_array = |values: &[f32], columns: usize| {
+            Array2::from_shape_vec((values.len() / columns, columns), values.to_vec())
+                .map_err(|e| PsydkError::CustomError(e.to_string()))
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item("gaze_time", relative(&buffer.gaze_times).into_pyarray(py))?;
+        dict.set_item("gaze", to_array(&buffer.gaze, 3)?.into_pyarray(py))?;
+        dict.set_item("pupil_time", relative(&buffer.pupil_times).into_pyarray(py))?;
+        dict.set_item("pupil", to_array(&buffer.pupil, 4)?.into_pyarray(py))?;
+        dict.set_item("onset", Timestamp::from(onset))?;
+        Ok(dict)
+    }
+
+    /// The gaze positions between two times with at least `min_confidence`. Only samples of the
+    /// current trial are kept, see `start_trial`. This is used by `calibrate_eye_tracker`.
+    ///
+    /// Parameters
+    /// ----------
+    /// start : Timestamp
+    ///   The start of the interval.
+    /// end : Timestamp
+    ///   The end of the interval.
+    ///
+    /// Returns
+    /// -------
+    /// list[tuple[float, float]]
+    ///   The gaze positions.
+    #[pyo3(name = "gaze_samples")]
+    fn py_gaze_samples(&self, start: Timestamp, end: Timestamp) -> Vec<(f32, f32)> {
+        self.0.gaze_between(start.timestamp, end.timestamp)
+    }
+
+    /// The most recent gaze position with at least `min_confidence`, or None.
+    #[getter(latest_gaze)]
+    fn py_latest_gaze(&self) -> Option<(f32, f32)> {
+        self.0.latest_gaze()
+    }
+
+    /// Whether the tracker is receiving data.
+    #[getter(running)]
+    fn py_running(&self) -> bool {
+        self.0.is_running()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PupilLabsTracker(address={:?}, surface={:?}, running={})",
+            self.0.sub_address,
+            self.0.surface,
+            self.0.is_running()
+        )
+    }
+}
@@ -0,0 +1,440 @@
+//! Vibration (rumble) output on gamepads, e.g. as a tactile stimulus or as feedback.
+//!
+//! Effects are played and stopped by a background thread at the requested times. The force
+//! feedback scheduling of gilrs works in ticks of 50 ms, so effects are played until they are
+//! stopped and the thread stops them itself, which makes durations as precise as onsets.
+
+use std::{
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder},
+    GamepadId, Gilrs,
+};
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{wait_until, TimelineEvent, Timestamp, TimestampOrOffset},
+};
+
+/// The thread sleeps until this long before an onset or offset and waits precisely for the rest.
+const PRECISE_WAIT: Duration = Duration::from_millis(2);
+
+/// A rumble that has been played.
+#[derive(Debug, Clone)]
+pub struct RumbleLogEntry {
+    /// The time the rumble was scheduled for.
+    pub scheduled: Instant,
+    /// The time the effect was started.
+    pub onset: Instant,
+    /// The time the effect was stopped, if it has been stopped.
+    pub offset: Option<Instant>,
+    pub strong: f32,
+    pub weak: f32,
+}
+
+enum RumbleCommand {
+    Start {
+        strong: f32,
+        weak: f32,
+        start: Instant,
+        duration: Duration,
+        reply: Sender<PsydkResult<()>>,
+    },
+    Stop,
+}
+
+struct PendingRumble {
+    start: Instant,
+    duration: Duration,
+    strong: f32,
+    weak: f32,
+    effect: Effect,
+}
+
+struct ActiveRumble {
+    end: Instant,
+    effect: Effect,
+    log_index: usize,
+}
+
+/// Plays rumble effects on a gamepad.
+///
+/// A rumble that starts while another one is playing replaces it, like sounds on an audio stream.
+#[derive(Debug)]
+pub struct GamepadRumble {
+    pub index: usize,
+    sender: Option<Sender<RumbleCommand>>,
+    thread: Option<JoinHandle<()>>,
+    log: Arc<Mutex<Vec<RumbleLogEntry>>>,
+}
+
+impl GamepadRumble {
+    /// Open the gamepad with the given index among the connected gamepads.
+    pub fn open(index: usize) -> PsydkResult<Self> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (ready_sender, ready_receiver) = std::sync::mpsc::channel();
+
+        // gilrs is created on the rumble thread, since gamepad handles can't be sent between
+        // threads on all platforms
+        let thread_log = log.clone();
+        let thread = std::thread::spawn(move || {
+            let (gilrs, id) = match open_gamepad(index) {
+                Ok(gamepad) => {
+                    let _ = ready_sender.send(Ok(()));
+                    gamepad
+                }
+                Err(e) => {
+                    let _ = ready_sender.send(Err(e));
+                    return;
+                }
+            };
+            run(gilrs, id, receiver, thread_log);
+        });
+
+        ready_receiver
+            .recv()
+            .unwrap_or_else(|_| Err(PsydkError::CustomError("The rumble thread stopped unexpectedly".into())))?;
+
+        Ok(Self {
+            index,
+            sender: Some(sender),
+            thread: Some(thread),
+            log,
+        })
+    }
+
+    /// Rumble with the given intensities (between 0 and 1) of the strong (low frequency) and the
+    /// weak (high frequency) motor from `start` for `duration`.
+    pub fn rumble(&self, strong: f32, weak: f32, start: Instant, duration: Duration) -> PsydkResult<()> {
+        for intensity in [strong, weak] {
+            if !(0.0..=1.0).contains(&intensity) {
+                return Err(PsydkError::ParameterError(format!(
+                    "Invalid intensity {intensity}, must be between 0 and 1"
+                )));
+            }
+        }
+
+        let (reply, reply_receiver) = std::sync::mpsc::channel();
+        self.send(RumbleCommand::Start {
+            strong,
+            weak,
+            start,
+            duration,
+            reply,
+        })?;
+        reply_receiver
+            .recv()
+            .unwrap_or_else(|_| Err(PsydkError::CustomError("The rumble thread has stopped".into())))
+    }
+
+    /// Stop the current rumble and discard all scheduled ones.
+    pub fn stop(&self) -> PsydkResult<()> {
+        self.send(RumbleCommand::Stop)
+    }
+
+    /// All rumbles that have been played, in order.
+    pub fn log(&self) -> Vec<RumbleLogEntry> {
+        self.log.lock().unwrap().clone()
+    }
+
+    fn send(&self, command: RumbleCommand) -> PsydkResult<()> {
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(command).ok())
+            .ok_or_else(|| PsydkError::CustomError("The rumble thread has stopped".into()))
+    }
+}
+
+impl Drop for GamepadRumble {
+    fn drop(&mut self) {
+        // the thread stops when the channel is closed
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn open_gamepad(index: usize) -> PsydkResult<(Gilrs, GamepadId)> {
+    let gilrs =
+        Gilrs::new().map_err(|e| PsydkError::CustomError(format!("Failed to initialize gamepad support: {e}")))?;
+    let (id, gamepad) = gilrs
+        .gamepads()
+        .nth(index)
+        .ok_or_else(|| PsydkError::ParameterError(format!("No gamepad with index {index} is connected")))?;
+    if !gamepad.is_ff_supported() {
+        return Err(PsydkError::ParameterError(format!(
+            "Gamepad {index} ({}) does not support rumble",
+            gamepad.name()
+        )));
+    }
+    Ok((gilrs, id))
+}
+
+/// Upload an effect that plays both motors with the given intensities until it is stopped.
+fn build_effect(gilrs: &mut Gilrs, id: GamepadId, strong: f32, weak: f32) -> PsydkResult<Effect> {
+    let magnitude = |intensity: f32| (intensity * u16::MAX as f32).round() as u16;
+    EffectBuilder::new()
+        .add_effect(BaseEffect {
+            kind: BaseEffectType::Strong {
+                magnitude: magnitude(strong),
+            },
+            ..Default::default()
+        })
+        .add_effect(BaseEffect {
+            kind: BaseEffectType::Weak {
+                magnitude: magnitude(weak),
+            },
+            ..Default::default()
+        })
+        .gamepads(&[id])
+        .finish(gilrs)
+        .map_err(|e| PsydkError::CustomError(format!("Failed to create the rumble effect: {e}")))
+}
+
+/// The loop of the rumble thread. Effects are uploaded when they are requested, so that only
+/// starting them is left at the onset.
+fn run(mut gilrs: Gilrs, id: GamepadId, receiver: Receiver<RumbleCommand>, log: Arc<Mutex<Vec<RumbleLogEntry>>>) {
+    let mut pending: Vec<PendingRumble> = Vec::new();
+    let mut active: Option<ActiveRumble> = None;
+
+    let stop = |active: ActiveRumble| {
+        let _ = active.effect.stop();
+        log.lock().unwrap()[active.log_index].offset = Some(Instant::now());
+    };
+
+    loop {
+        let next = pending
+            .iter()
+            .map(|rumble| rumble.start)
+            .chain(active.as_ref().map(|active| active.end))
+            .min();
+
+        // wait for commands until shortly before the next onset or offset
+        let command = match next {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(next) => match next
+                .checked_duration_since(Instant::now())
+                .and_then(|remaining| remaining.checked_sub(PRECISE_WAIT))
+            {
+                Some(timeout) if !timeout.is_zero() => receiver.recv_timeout(timeout),
+                _ => Err(RecvTimeoutError::Timeout),
+            },
+        };
+
+        match command {
+            Ok(RumbleCommand::Start {
+                strong,
+                weak,
+                start,
+                duration,
+                reply,
+            }) => {
+                match build_effect(&mut gilrs, id, strong, weak) {
+                    Ok(effect) => {
+                        pending.push(PendingRumble {
+                            start,
+                            duration,
+                            strong,
+                            weak,
+                            effect,
+                        });
+                        let _ = reply.send(Ok(()));
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(e));
+                    }
+                }
+                continue;
+            }
+            Ok(RumbleCommand::Stop) => {
+                pending.clear();
+                if let Some(active) = active.take() {
+                    stop(active);
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        let Some(next) = next else {
+            continue;
+        };
+        wait_until(next);
+
+        let now = Instant::now();
+        if active.as_ref().is_some_and(|active| active.end <= now) {
+            stop(active.take().unwrap());
+        }
+
+        let due = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, rumble)| rumble.start <= now)
+            .min_by_key(|(_, rumble)| rumble.start)
+            .map(|(index, _)| index);
+        if let Some(index) = due {
+            let rumble = pending.remove(index);
+            // the new rumble replaces the one that is playing
+            if let Some(active) = active.take() {
+                stop(active);
+            }
+            if let Err(e) = rumble.effect.play() {
+                log::error!("Failed to play the rumble effect: {e}");
+                continue;
+            }
+            let onset = Instant::now();
+            let mut log = log.lock().unwrap();
+            log.push(RumbleLogEntry {
+                scheduled: rumble.start,
+                onset,
+                offset: None,
+                strong: rumble.strong,
+                weak: rumble.weak,
+            });
+            active = Some(ActiveRumble {
+                end: onset + rumble.duration,
+                effect: rumble.effect,
+                log_index: log.len() - 1,
+            });
+        }
+    }
+
+    if let Some(active) = active.take() {
+        stop(active);
+    }
+}
+
+/// Vibrates a gamepad, e.g. as a tactile stimulus or as feedback. Requires the `gamepad`
+/// feature.
+///
+/// Rumbles are started and stopped by a background thread at the requested times, and the time
+/// each rumble actually started is logged, like the onsets of sounds. A rumble that starts while
+/// another one is playing replaces it.
+///
+/// Parameters
+/// ----------
+/// gamepad : int, optional
+///   The index of the gamepad among the connected gamepads. Defaults to 0.
+#[cfg(feature = "python")]
+#[pyclass(name = "GamepadRumble")]
+pub struct PyGamepadRumble(pub GamepadRumble);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyGamepadRumble {
+    #[new]
+    #[pyo3(signature = (gamepad = 0))]
+    fn __new__(gamepad: usize) -> PyResult<Self> {
+        Ok(Self(GamepadRumble::open(gamepad)?))
+    }
+
+    /// Rumble the gamepad.
+    ///
+    /// Parameters
+    /// ----------
+    /// intensity : float, optional
+    ///   The intensity of the strong (low frequency) motor, between 0 and 1. Defaults to 1.
+    /// duration : float, optional
+    ///   The duration in seconds. Defaults to 0.2.
+    /// start : Timestamp, float, or timedelta, optional
+    ///   When the rumble starts, as a timestamp (e.g. the onset of a frame plus a delay) or as an
+    ///   offset from now. Defaults to now.
+    /// weak : float, optional
+    ///   The intensity of the weak (high frequency) motor, between 0 and 1. Defaults to
+    ///   `intensity`.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The time the rumble is scheduled for. The actual onset is recorded in `log`.
+    #[pyo3(name = "rumble")]
+    #[pyo3(signature = (intensity = 1.0, duration = 0.2, start = None, weak = None))]
+    fn py_rumble(
+        &self,
+        intensity: f32,
+        duration: f64,
+        start: Option<TimestampOrOffset>,
+        weak: Option<f32>,
+    ) -> PyResult<Timestamp> {
+        let duration = Duration::try_from_secs_f64(duration)
+            .map_err(|_| PsydkError::ParameterError(format!("Invalid duration {duration}, must be non-negative")))?;
+        let start = match start {
+            None => Instant::now(),
+            Some(TimestampOrOffset::Timestamp(timestamp)) => timestamp.timestamp,
+            Some(TimestampOrOffset::Offset(offset)) => {
+                Timestamp::from(Instant::now()).offset_by(offset.seconds())?.timestamp
+            }
+        };
+        self.0.rumble(intensity, weak.unwrap_or(intensity), start, duration)?;
+        Ok(start.into())
+    }
+
+    /// Stop the current rumble and discard all scheduled ones.
+    #[pyo3(name = "stop")]
+    fn py_stop(&self) -> PyResult<()> {
+        Ok(self.0.stop()?)
+    }
+
+    /// All rumbles that have been played, as dictionaries with "scheduled", "onset", "offset"
+    /// (None while the rumble is playing), "intensity", and "weak".
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log()
+            .iter()
+            .map(|entry| {
+                let dict = PyDict::new(py);
+                dict.set_item("scheduled", Timestamp::from(entry.scheduled))?;
+                dict.set_item("onset", Timestamp::from(entry.onset))?;
+                dict.set_item("offset", entry.offset.map(Timestamp::from))?;
+                dict.set_item("intensity", entry.strong)?;
+                dict.set_item("weak", entry.weak)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Add the onsets of all rumbles to a timeline as "rumble_onset" events, labelled with the
+    /// index of the rumble.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for (index, entry) in self.0.log().into_iter().enumerate() {
+            let mut data = vec![
+                ("intensity".to_string(), entry.strong.to_string()),
+                ("weak".to_string(), entry.weak.to_string()),
+            ];
+            if let Some(offset) = entry.offset {
+                data.push(("duration".to_string(), (offset - entry.onset).as_secs_f64().to_string()));
+            }
+            timeline.0.add(TimelineEvent {
+                kind: "rumble_onset".to_string(),
+                time: entry.onset,
+                label: Some(index.to_string()),
+                data,
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("GamepadRumble(gamepad={}, played={})", self.0.index, self.0.log().len())
+    }
+}
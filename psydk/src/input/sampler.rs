@@ -0,0 +1,409 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use numpy::{ndarray::Array2, IntoPyArray};
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use strum::EnumString;
+
+use super::{Event, EventHandlerId, EventKind};
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+    visual::window::Window,
+};
+
+/// An analog gamepad or joystick axis.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// The left trigger or throttle.
+    LeftZ,
+    /// The right trigger or throttle.
+    RightZ,
+}
+
+#[cfg(feature = "gamepad")]
+impl From<GamepadAxis> for gilrs::Axis {
+    fn from(axis: GamepadAxis) -> Self {
+        match axis {
+            GamepadAxis::LeftStickX => gilrs::Axis::LeftStickX,
+            GamepadAxis::LeftStickY => gilrs::Axis::LeftStickY,
+            GamepadAxis::RightStickX => gilrs::Axis::RightStickX,
+            GamepadAxis::RightStickY => gilrs::Axis::RightStickY,
+            GamepadAxis::LeftZ => gilrs::Axis::LeftZ,
+            GamepadAxis::RightZ => gilrs::Axis::RightZ,
+        }
+    }
+}
+
+/// What a `ContinuousSampler` samples.
+#[derive(Debug, Clone)]
+pub enum SamplerSource {
+    /// The cursor position in a window, in pixels relative to the center.
+    Mouse(Window),
+    /// Axes of a gamepad or joystick, between -1 and 1.
+    Gamepad { index: usize, axes: Vec<GamepadAxis> },
+}
+
+impl SamplerSource {
+    pub fn channels(&self) -> Vec<String> {
+        match self {
+            SamplerSource::Mouse(_) => vec!["x".to_string(), "y".to_string()],
+            SamplerSource::Gamepad { axes, .. } => axes.iter().map(|axis| format!("{axis:?}")).collect(),
+        }
+    }
+}
+
+/// Reads one sample of all channels.
+type Reader = Box<dyn FnMut() -> Vec<f32>>;
+
+#[derive(Debug)]
+struct SampleBuffer {
+    times: Vec<Instant>,
+    /// Samples of all channels, one row per sample.
+    values: Vec<f32>,
+    trial_onset: Instant,
+}
+
+/// Records analog input at a fixed rate on a background thread, e.g. for tracking or effort
+/// paradigms.
+///
+/// The buffer is allocated up front for `capacity` samples so that recording does not allocate
+/// during a trial. Every sample is timestamped when it is taken.
+#[derive(Debug)]
+pub struct ContinuousSampler {
+    pub source: SamplerSource,
+    pub rate: f64,
+    buffer: Arc<Mutex<SampleBuffer>>,
+    capacity: usize,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    handler: Option<EventHandlerId>,
+}
+
+impl ContinuousSampler {
+    pub fn new(source: SamplerSource, rate: f64, capacity: usize) -> PsydkResult<Self> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid sampling rate {rate}, must be a positive number"
+            )));
+        }
+        if let SamplerSource::Gamepad { axes, .. } = &source {
+            if axes.is_empty() {
+                return Err(PsydkError::ParameterError("At least one axis must be sampled".into()));
+            }
+        }
+
+        let channels = source.channels().len();
+        Ok(Self {
+            source,
+            rate,
+            buffer: Arc::new(Mutex::new(SampleBuffer {
+                times: Vec::with_capacity(capacity),
+                values: Vec::with_capacity(capacity * channels),
+                trial_onset: Instant::now(),
+            })),
+            capacity,
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+            handler: None,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    /// Start sampling. Does nothing if the sampler is already running.
+    pub fn start(&mut self) -> PsydkResult<()> {
+        if self.is_running() {
+            return Ok(());
+        }
+        self.stop.store(false, Ordering::Relaxed);
+
+        // the reader is created on the sampling thread, since gamepad handles can't be sent
+        // between threads on all platforms
+        let make_reader: Box<dyn FnOnce() -> PsydkResult<Reader> + Send> = match &self.source {
+            SamplerSource::Mouse(window) => {
+                // the latest cursor position is held until the next sample
+                let position = Arc::new(Mutex::new((0.0f32, 0.0f32)));
+                let handler_position = position.clone();
+                self.handler = Some(window.add_event_handler(EventKind::CursorMoved, move |event| {
+                    if let Event::CursorMoved { position, .. } = event {
+                        *handler_position.lock().unwrap() = position;
+                    }
+                    false
+                })?);
+                Box::new(move || {
+                    Ok(Box::new(move || {
+                        let (x, y) = *position.lock().unwrap();
+                        vec![x, y]
+                    }) as Reader)
+                })
+            }
+            #[cfg(feature = "gamepad")]
+            SamplerSource::Gamepad { index, axes } => {
+                let (index, axes) = (*index, axes.clone());
+                Box::new(move || {
+                    let mut gilrs = gilrs::Gilrs::new()
+                        .map_err(|e| PsydkError::CustomError(format!("Failed to initialize gamepad support: {e}")))?;
+                    let id = gilrs.gamepads().nth(index).map(|(id, _)| id).ok_or_else(|| {
+                        PsydkError::ParameterError(format!("No gamepad with index {index} is connected"))
+                    })?;
+                    Ok(Box::new(move || {
+                        // process pending events so that the axis state is up to date
+                        while gilrs.next_event().is_some() {}
+                        let gamepad = gilrs.gamepad(id);
+                        axes.iter().map(|axis| gamepad.value((*axis).into())).collect()
+                    }) as Reader)
+                })
+            }
+            #[cfg(not(feature = "gamepad"))]
+            SamplerSource::Gamepad { .. } => {
+                return Err(PsydkError::ParameterError(
+                    "Can't sample a gamepad, psydk was built without the `gamepad` feature".into(),
+                ))
+            }
+        };
+
+        let buffer = self.buffer.clone();
+        let stop = self.stop.clone();
+        let interval = Duration::from_secs_f64(1.0 / self.rate);
+        let (ready_sender, ready_receiver) = std::sync::mpsc::channel();
+        self.thread = Some(std::thread::spawn(move || {
+            let mut read = match make_reader() {
+                Ok(read) => {
+                    let _ = ready_sender.send(Ok(()));
+                    read
+                }
+                Err(e) => {
+                    let _ = ready_sender.send(Err(e));
+                    return;
+                }
+            };
+
+            let mut next = Instant::now();
+            while !stop.load(Ordering::Relaxed) {
+                let values = read();
+                let time = Instant::now();
+                {
+                    let mut buffer = buffer.lock().unwrap();
+                    buffer.times.push(time);
+                    buffer.values.extend(values);
+                }
+
+                next += interval;
+                let now = Instant::now();
+                if next > now {
+                    std::thread::sleep(next - now);
+                } else {
+                    // we fell behind, don't try to catch up with a burst of samples
+                    next = now;
+                }
+            }
+        }));
+
+        let ready = ready_receiver.recv().unwrap_or_else(|_| {
+            Err(PsydkError::CustomError(
+                "The sampling thread stopped unexpectedly".into(),
+            ))
+        });
+        if ready.is_err() {
+            self.stop();
+        }
+        ready
+    }
+
+    /// Stop sampling. Samples that were already taken are kept.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if let (Some(id), SamplerSource::Mouse(window)) = (self.handler.take(), &self.source) {
+            window.remove_event_handler(id);
+        }
+    }
+
+    /// Discard all samples and start a new trial.
+    pub fn start_trial(&self) -> Instant {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.times.clear();
+        buffer.values.clear();
+        // keep the full capacity for the next trial
+        let channels = self.source.channels().len();
+        buffer.times.reserve(self.capacity);
+        buffer.values.reserve(self.capacity * channels);
+        buffer.trial_onset = Instant::now();
+        buffer.trial_onset
+    }
+
+    /// The onset of the current trial and the times and values of all samples since then.
+    pub fn samples(&self) -> (Instant, Vec<Instant>, Vec<f32>) {
+        let buffer = self.buffer.lock().unwrap();
+        (buffer.trial_onset, buffer.times.clone(), buffer.values.clone())
+    }
+}
+
+impl Drop for ContinuousSampler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Records a continuous response, e.g. the position of a joystick or the mouse, at a fixed rate.
+///
+/// Samples are taken on a background thread independently of the frame rate and are stored with
+/// their timestamps. Use `start_trial` at the beginning of each trial and `get_trial` at the end
+/// to retrieve the trace as numpy arrays.
+///
+/// Parameters
+/// ----------
+/// source : str, optional
+///   "mouse" (default) samples the cursor position in `window`; "gamepad" samples the given `axes`
+///   of a gamepad or joystick (requires the `gamepad` feature).
+/// window : Window, optional
+///   The window to sample the cursor position in. Required for the "mouse" source.
+/// rate : float, optional
+///   The sampling rate in Hz. Defaults to 1000.
+/// axes : list[str], optional
+///   The gamepad axes to sample: "left_stick_x", "left_stick_y", "right_stick_x", "right_stick_y",
+///   "left_z", or "right_z". Defaults to the left stick.
+/// gamepad : int, optional
+///   The index of the gamepad among the connected gamepads. Defaults to 0.
+/// max_trial_duration : float, optional
+///   The buffer is preallocated for trials of up to this length in seconds. Longer trials are
+///   still recorded completely. Defaults to 60.
+#[cfg(feature = "python")]
+#[pyclass(name = "ContinuousSampler")]
+pub struct PyContinuousSampler(pub ContinuousSampler);
+
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+enum SourceKind {
+    Mouse,
+    Gamepad,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyContinuousSampler {
+    #[new]
+    #[pyo3(signature = (
+        source = SourceKind::Mouse,
+        window = None,
+        rate = 1000.0,
+        axes = vec![GamepadAxis::LeftStickX, GamepadAxis::LeftStickY],
+        gamepad = 0,
+        max_trial_duration = 60.0,
+    ))]
+    fn __new__(
+        source: SourceKind,
+        window: Option<Window>,
+        rate: f64,
+        axes: Vec<GamepadAxis>,
+        gamepad: usize,
+        max_trial_duration: f64,
+    ) -> PyResult<Self> {
+        let source = match source {
+            SourceKind::Mouse => SamplerSource::Mouse(window.ok_or_else(|| {
+                PsydkError::ParameterError("A window is required to sample the mouse position".into())
+            })?),
+            SourceKind::Gamepad => SamplerSource::Gamepad { index: gamepad, axes },
+        };
+        let capacity = (rate * max_trial_duration.max(0.0)).ceil() as usize;
+        let mut sampler = ContinuousSampler::new(source, rate, capacity)?;
+        sampler.start()?;
+        Ok(Self(sampler))
+    }
+
+    /// Start sampling again after `stop`.
+    #[pyo3(name = "start")]
+    fn py_start(&mut self) -> PyResult<()> {
+        Ok(self.0.start()?)
+    }
+
+    /// Stop sampling.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+
+    /// Discard all samples and start a new trial.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The onset of the trial.
+    #[pyo3(name = "start_trial")]
+    fn py_start_trial(&self) -> Timestamp {
+        self.0.start_trial().into()
+    }
+
+    /// The samples of the current trial.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary with "time" (the time of each sample in seconds relative to the trial
+    ///   onset, as a 1D array), "values" (one row per sample and one column per channel, as a 2D
+    ///   array), "channels" (the names of the columns), and "onset" (the trial onset).
+    #[pyo3(name = "get_trial")]
+    fn py_get_trial<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let channels = self.0.source.channels();
+        let (onset, times, values) = self.0.samples();
+
+        let times = times
+            .iter()
+            .map(|time| time.saturating_duration_since(onset).as_secs_f64())
+            .collect::<Vec<_>>();
+        let values = Array2::from_shape_vec((times.len(), channels.len()), values)
+            .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("time", times.into_pyarray(py))?;
+        dict.set_item("values", values.into_pyarray(py))?;
+        dict.set_item("channels", channels)?;
+        dict.set_item("onset", Timestamp::from(onset))?;
+        Ok(dict)
+    }
+
+    /// The names of the sampled channels.
+    #[getter(channels)]
+    fn py_channels(&self) -> Vec<String> {
+        self.0.source.channels()
+    }
+
+    /// The sampling rate in Hz.
+    #[getter(rate)]
+    fn py_rate(&self) -> f64 {
+        self.0.rate
+    }
+
+    /// Whether the sampler is running.
+    #[getter(running)]
+    fn py_running(&self) -> bool {
+        self.0.is_running()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ContinuousSampler(channels={:?}, rate={})",
+            self.0.source.channels(),
+            self.0.rate
+        )
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+
+use super::{Event, EventHandlerId, EventKind};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{deadline, to_timeout, TimelineEvent, Timestamp},
+    visual::window::Window,
+};
+
+#[derive(Debug, Default)]
+struct PulseState {
+    pulses: Vec<Instant>,
+}
+
+/// Counts and timestamps the TR pulses (triggers) of an MRI scanner.
+///
+/// Pulses can come from key presses (most trigger boxes emulate a keyboard), from a serial port,
+/// or from any other source that calls `pulse`.
+#[derive(Debug, Clone)]
+pub struct ScannerSync {
+    state: Arc<(Mutex<PulseState>, Condvar)>,
+    /// Pulses closer than this to the previous pulse are ignored, e.g. when a trigger box sends
+    /// more than one key press per pulse.
+    pub min_interval: Duration,
+    handler: Option<(Window, EventHandlerId)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ScannerSync {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            state: Arc::new((Mutex::new(PulseState::default()), Condvar::new())),
+            min_interval,
+            handler: None,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Record a pulse at `time`. Returns false if the pulse was ignored because it was too close
+    /// to the previous one.
+    pub fn pulse(&self, time: Instant) -> bool {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if let Some(last) = state.pulses.last() {
+            if time.saturating_duration_since(*last) < self.min_interval {
+                return false;
+            }
+        }
+        state.pulses.push(time);
+        condvar.notify_all();
+        true
+    }
+
+    /// Treat presses of `key` on `window` as pulses. Keys are matched case-insensitively.
+    pub fn listen_to_key(&mut self, window: &Window, key: &str) -> PsydkResult<()> {
+        let key = key.to_lowercase();
+        let sync = self.clone();
+        let id = window.add_event_handler(EventKind::KeyPress, move |event| {
+            if let Event::KeyPress {
+                key: pressed,
+                timestamp,
+                ..
+            } = &event
+            {
+                if pressed.to_lowercase() == key {
+                    sync.pulse(timestamp.timestamp);
+                }
+            }
+            false
+        })?;
+        self.handler = Some((window.clone(), id));
+        Ok(())
+    }
+
+    /// Treat bytes received on a serial port as pulses. If `trigger` is given, only that byte
+    /// counts as a pulse. The port is read on a background thread.
+    #[cfg(feature = "serial")]
+    pub fn listen_to_serial(&self, port: &str, baud_rate: u32, trigger: Option<u8>) -> PsydkResult<()> {
+        use std::io::Read;
+
+        let mut port = serialport::new(port, baud_rate)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map_err(|e| PsydkError::IOError(e.into()))?;
+
+        let sync = self.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            while !sync.stop.load(Ordering::Relaxed) {
+                match port.read(&mut buffer) {
+                    Ok(n) => {
+                        // the bytes of a single read arrive at (almost) the same time
+                        let time = Instant::now();
+                        for byte in &buffer[..n] {
+                            if trigger.map_or(true, |trigger| *byte == trigger) {
+                                sync.pulse(time);
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        log::error!("Failed to read scanner pulses from the serial port: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop listening for pulses. Pulses that were already recorded are kept.
+    pub fn close(&mut self) {
+        if let Some((window, id)) = self.handler.take() {
+            window.remove_event_handler(id);
+        }
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Block until the number of pulses is at least `count` (or the timeout expires) and return
+    /// the time of that pulse.
+    pub fn wait_for_count(&self, count: usize, timeout: Option<Duration>) -> Option<Instant> {
+        let (lock, condvar) = &*self.state;
+        let deadline = deadline(timeout);
+        let mut state = lock.lock().unwrap();
+        while state.pulses.len() < count {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    condvar.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => condvar.wait(state).unwrap(),
+            };
+        }
+        state.pulses.get(count.checked_sub(1)?).copied()
+    }
+
+    /// Block until the next pulse (or the timeout expires) and return its time.
+    pub fn wait_for_trigger(&self, timeout: Option<Duration>) -> Option<Instant> {
+        self.wait_for_count(self.count() + 1, timeout)
+    }
+
+    /// The number of pulses (volumes) so far.
+    pub fn count(&self) -> usize {
+        self.state.0.lock().unwrap().pulses.len()
+    }
+
+    /// The times of all pulses so far.
+    pub fn pulses(&self) -> Vec<Instant> {
+        self.state.0.lock().unwrap().pulses.clone()
+    }
+
+    pub fn last_pulse(&self) -> Option<Instant> {
+        self.state.0.lock().unwrap().pulses.last().copied()
+    }
+
+    /// The repetition time estimated as the median interval between pulses.
+    pub fn estimated_tr(&self) -> Option<Duration> {
+        let pulses = self.pulses();
+        let mut intervals = pulses.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        intervals.sort();
+        intervals.get(intervals.len() / 2).copied()
+    }
+
+    /// Forget all pulses, e.g. between runs.
+    pub fn reset(&self) {
+        self.state.0.lock().unwrap().pulses.clear();
+    }
+}
+
+/// Keeps an experiment in sync with an MRI scanner by counting and timestamping its TR pulses.
+///
+/// Most trigger boxes send a key press (often "5" or "t") for every volume. Serial port trigger
+/// boxes are supported when psydk is built with the `serial` feature. Pulses from other sources,
+/// e.g. a parallel port, can be recorded with `pulse`.
+///
+/// All queries except the `wait_*` methods return immediately, so the paradigm can check the scanner
+/// clock between frames.
+///
+/// Parameters
+/// ----------
+/// window : Window, optional
+///   The window that receives the trigger key presses.
+/// key : str, optional
+///   The key that the trigger box sends. Defaults to "5". Case is ignored.
+/// serial_port : str, optional
+///   Read pulses from this serial port (e.g. "/dev/ttyUSB0" or "COM3") instead of the keyboard.
+/// baud_rate : int, optional
+///   The baud rate of the serial port. Defaults to 115200.
+/// serial_trigger : int, optional
+///   The byte that signals a pulse on the serial port. By default, every byte is a pulse.
+/// min_interval : float, optional
+///   Pulses closer than this (in seconds) to the previous pulse are ignored. Defaults to 0.0.
+#[cfg(feature = "python")]
+#[pyclass(name = "ScannerSync")]
+#[derive(Debug, Clone)]
+pub struct PyScannerSync(pub ScannerSync);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyScannerSync {
+    #[new]
+    #[pyo3(signature = (window = None, key = "5", serial_port = None, baud_rate = 115200, serial_trigger = None, min_interval = 0.0))]
+    fn __new__(
+        window: Option<Window>,
+        key: &str,
+        serial_port: Option<&str>,
+        baud_rate: u32,
+        serial_trigger: Option<u8>,
+        min_interval: f64,
+    ) -> PyResult<Self> {
+        let min_interval = Duration::try_from_secs_f64(min_interval).map_err(|_| {
+            PsydkError::ParameterError(format!("Invalid min_interval {min_interval}, must be non-negative"))
+        })?;
+        let mut sync = ScannerSync::new(min_interval);
+
+        if let Some(port) = serial_port {
+            #[cfg(feature = "serial")]
+            sync.listen_to_serial(port, baud_rate, serial_trigger)?;
+            #[cfg(not(feature = "serial"))]
+            {
+                let _ = (baud_rate, serial_trigger);
+                return Err(PsydkError::ParameterError(format!(
+                    "Can't read from serial port {port}, psydk was built without the `serial` feature"
+                ))
+                .into());
+            }
+        }
+        if let Some(window) = window {
+            sync.listen_to_key(&window, key)?;
+        }
+
+        Ok(Self(sync))
+    }
+
+    /// Block until the next pulse.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeout : float, optional
+    ///   The maximum time to wait in seconds. Waits indefinitely if not given.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp or None
+    ///   The time of the pulse, or None if the timeout expired.
+    #[pyo3(name = "wait_for_trigger")]
+    #[pyo3(signature = (timeout = None))]
+    fn py_wait_for_trigger(&self, py: Python, timeout: Option<f64>) -> PyResult<Option<Timestamp>> {
+        let timeout = to_timeout(timeout)?;
+        Ok(py.allow_threads(|| self.0.wait_for_trigger(timeout)).map(Into::into))
+    }
+
+    /// Block until the given volume has started, i.e. until at least `volume` pulses have been
+    /// received. Returns immediately if that has already happened.
+    ///
+    /// Parameters
+    /// ----------
+    /// volume : int
+    ///   The volume to wait for, counting from 1.
+    /// timeout : float, optional
+    ///   The maximum time to wait in seconds. Waits indefinitely if not given.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp or None
+    ///   The time of the pulse that started the volume, or None if the timeout expired.
+    #[pyo3(name = "wait_for_volume")]
+    #[pyo3(signature = (volume, timeout = None))]
+    fn py_wait_for_volume(&self, py: Python, volume: usize, timeout: Option<f64>) -> PyResult<Option<Timestamp>> {
+        let timeout = to_timeout(timeout)?;
+        Ok(py
+            .allow_threads(|| self.0.wait_for_count(volume, timeout))
+            .map(Into::into))
+    }
+
+    /// Record a pulse from another source, e.g. a parallel port.
+    ///
+    /// Parameters
+    /// ----------
+    /// timestamp : Timestamp, optional
+    ///   The time of the pulse. Defaults to now.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   False if the pulse was ignored because it was closer than `min_interval` to the previous
+    ///   pulse.
+    #[pyo3(name = "pulse")]
+    #[pyo3(signature = (timestamp = None))]
+    fn py_pulse(&self, timestamp: Option<Timestamp>) -> bool {
+        self.0.pulse(timestamp.map_or_else(Instant::now, |t| t.timestamp))
+    }
+
+    /// The number of pulses (volumes) so far.
+    #[getter(count)]
+    fn py_count(&self) -> usize {
+        self.0.count()
+    }
+
+    /// The time of the most recent pulse, or None if there has not been one yet.
+    #[getter(last_pulse)]
+    fn py_last_pulse(&self) -> Option<Timestamp> {
+        self.0.last_pulse().map(Into::into)
+    }
+
+    /// The times of all pulses so far.
+    #[getter(pulses)]
+    fn py_pulses(&self) -> Vec<Timestamp> {
+        self.0.pulses().into_iter().map(Into::into).collect()
+    }
+
+    /// Seconds since the most recent pulse, or None if there has not been one yet.
+    #[getter(time_since_last_pulse)]
+    fn py_time_since_last_pulse(&self) -> Option<f64> {
+        self.0.last_pulse().map(|last| last.elapsed().as_secs_f64())
+    }
+
+    /// The repetition time in seconds, estimated as the median interval between pulses. None
+    /// until two pulses have been received.
+    #[getter(estimated_tr)]
+    fn py_estimated_tr(&self) -> Option<f64> {
+        self.0.estimated_tr().map(|tr| tr.as_secs_f64())
+    }
+
+    /// The current state as a dictionary with the number of pulses, the time of the last pulse,
+    /// and the estimated TR.
+    #[pyo3(name = "status")]
+    fn py_status<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("count", self.0.count())?;
+        dict.set_item("last_pulse", self.0.last_pulse().map(Timestamp::from))?;
+        dict.set_item("estimated_tr", self.py_estimated_tr())?;
+        Ok(dict)
+    }
+
+    /// Forget all pulses, e.g. between runs.
+    #[pyo3(name = "reset")]
+    fn py_reset(&self) {
+        self.0.reset();
+    }
+
+    /// Stop listening for pulses. Pulses that were already recorded are kept.
+    #[pyo3(name = "close")]
+    fn py_close(&mut self) {
+        self.0.close();
+    }
+
+    /// Add all pulses to a timeline as "pulse" events, labelled with the volume number (counting
+    /// from 1).
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for (index, time) in self.0.pulses().into_iter().enumerate() {
+            timeline.0.add(TimelineEvent {
+                kind: "pulse".to_string(),
+                time,
+                label: Some((index + 1).to_string()),
+                data: Vec::new(),
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ScannerSync(count={})", self.0.count())
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, PyResult};
+use rand::Rng;
+use strum::EnumString;
+
+use super::Event;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::wait_until,
+    visual::window::Window,
+};
+
+/// How a simulated participant chooses its responses.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ResponsePolicy {
+    /// Press a random key from the list of keys.
+    Random,
+    /// Press the correct key, if the frame specifies one, and a random key otherwise.
+    Correct,
+    /// Replay the responses of a previous session in order.
+    Replay,
+}
+
+/// An ex-Gaussian response time distribution, i.e. the sum of a normal and an exponential
+/// distribution, which is a common model of human response times. Times are in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct RtDistribution {
+    pub mu: f64,
+    pub sigma: f64,
+    pub tau: f64,
+    /// Response times are never shorter than this.
+    pub min: f64,
+}
+
+impl RtDistribution {
+    pub fn sample(&self, rng: &mut impl Rng) -> f64 {
+        // Box-Muller transform
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        let exponential = -self.tau * (1.0 - rng.gen::<f64>()).ln();
+        (self.mu + self.sigma * normal + exponential).max(self.min)
+    }
+}
+
+/// A simulated participant that responds to frames that expect a response, so that whole
+/// experiments can be run automatically for debugging and timing validation.
+#[derive(Debug, Clone)]
+pub struct SimulatedParticipant {
+    pub policy: ResponsePolicy,
+    pub keys: Vec<String>,
+    pub rt: RtDistribution,
+    /// Probability of not responding at all.
+    pub miss_rate: f64,
+    /// Responses (key and response time in seconds) of a previous session. A key of None means
+    /// the participant did not respond.
+    replay: Arc<Mutex<VecDeque<(Option<String>, f64)>>>,
+}
+
+impl SimulatedParticipant {
+    pub fn new(
+        policy: ResponsePolicy,
+        keys: Vec<String>,
+        rt: RtDistribution,
+        miss_rate: f64,
+        replay: Vec<(Option<String>, f64)>,
+    ) -> PsydkResult<Self> {
+        if policy != ResponsePolicy::Replay && keys.is_empty() {
+            return Err(PsydkError::ParameterError(
+                "A simulated participant needs at least one key to respond with".into(),
+            ));
+        }
+        if policy == ResponsePolicy::Replay && replay.is_empty() {
+            return Err(PsydkError::ParameterError(
+                "The replay policy needs the responses of a previous session".into(),
+            ));
+        }
+
+        Ok(Self {
+            policy,
+            keys,
+            rt,
+            miss_rate,
+            replay: Arc::new(Mutex::new(replay.into())),
+        })
+    }
+
+    /// Choose the key and response time for a frame. Returns None if the participant does not
+    /// respond.
+    pub fn choose(&self, correct: Option<&str>) -> Option<(String, Duration)> {
+        let mut rng = rand::thread_rng();
+
+        let (key, rt) = match self.policy {
+            ResponsePolicy::Replay => {
+                let (key, rt) = self.replay.lock().unwrap().pop_front()?;
+                (key?, rt)
+            }
+            policy => {
+                if rng.gen::<f64>() < self.miss_rate {
+                    return None;
+                }
+                let key = match correct {
+                    Some(correct) if policy == ResponsePolicy::Correct => correct.to_string(),
+                    _ => self.keys[rng.gen_range(0..self.keys.len())].clone(),
+                };
+                (key, self.rt.sample(&mut rng))
+            }
+        };
+
+        Some((key, Duration::try_from_secs_f64(rt.max(0.0)).ok()?))
+    }
+
+    /// Respond to a frame that was shown at `onset`. The key press (and a release shortly after)
+    /// is injected into the window from a background thread at the chosen response time.
+    pub fn respond(&self, window: &Window, onset: Instant, correct: Option<&str>) {
+        let Some((key, rt)) = self.choose(correct) else {
+            return;
+        };
+
+        let window = window.clone();
+        std::thread::spawn(move || {
+            let press = onset + rt;
+            wait_until(press);
+            window.inject_event(Event::KeyPress {
+                timestamp: press.into(),
+                key: key.clone(),
+                code: 0,
+            });
+
+            let release = press + Duration::from_millis(80);
+            wait_until(release);
+            window.inject_event(Event::KeyRelease {
+                timestamp: release.into(),
+                key,
+                code: 0,
+            });
+        });
+    }
+}
+
+/// A simulated participant that presses keys in response to frames, for piloting and testing
+/// experiments without a human.
+///
+/// Attach it to a window with `Window.simulate`. Frames that expect a response (see
+/// `Frame.expect_response`) then trigger a simulated key press, which is delivered like a real
+/// one to event handlers, event receivers, and the keyboard state.
+///
+/// Parameters
+/// ----------
+/// policy : str, optional
+///   "random" (default) presses a random key from `keys`, "correct" presses the correct key given
+///   to `Frame.expect_response`, and "replay" replays the responses in `replay` in order.
+/// keys : list[str], optional
+///   The keys to respond with.
+/// rt_mean : float, optional
+///   The mean of the normal part of the response time distribution in seconds. Defaults to 0.5.
+/// rt_sd : float, optional
+///   The standard deviation of the normal part in seconds. Defaults to 0.1.
+/// rt_tau : float, optional
+///   The mean of the exponential part in seconds, for a right-skewed (ex-Gaussian) distribution.
+///   Defaults to 0.0.
+/// rt_min : float, optional
+///   The shortest possible response time in seconds. Defaults to 0.15.
+/// miss_rate : float, optional
+///   The probability of not responding. Defaults to 0.0.
+/// replay : list[tuple[str | None, float]], optional
+///   The responses of a previous session as (key, response time in seconds). A key of None is a
+///   missed response.
+#[cfg(feature = "python")]
+#[pyclass(name = "SimulatedParticipant")]
+#[derive(Debug, Clone)]
+pub struct PySimulatedParticipant(pub SimulatedParticipant);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PySimulatedParticipant {
+    #[new]
+    #[pyo3(signature = (
+        policy = ResponsePolicy::Random,
+        keys = Vec::new(),
+        rt_mean = 0.5,
+        rt_sd = 0.1,
+        rt_tau = 0.0,
+        rt_min = 0.15,
+        miss_rate = 0.0,
+        replay = Vec::new(),
+    ))]
+    fn __new__(
+        policy: ResponsePolicy,
+        keys: Vec<String>,
+        rt_mean: f64,
+        rt_sd: f64,
+        rt_tau: f64,
+        rt_min: f64,
+        miss_rate: f64,
+        replay: Vec<(Option<String>, f64)>,
+    ) -> PyResult<Self> {
+        let rt = RtDistribution {
+            mu: rt_mean,
+            sigma: rt_sd,
+            tau: rt_tau,
+            min: rt_min,
+        };
+        Ok(Self(SimulatedParticipant::new(policy, keys, rt, miss_rate, replay)?))
+    }
+
+    /// The number of replayed responses that have not been used yet.
+    #[getter(remaining_replay)]
+    fn py_remaining_replay(&self) -> usize {
+        self.0.replay.lock().unwrap().len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SimulatedParticipant(policy={:?}, keys={:?})",
+            self.0.policy, self.0.keys
+        )
+    }
+}
@@ -0,0 +1,348 @@
+//! Text entry.
+//!
+//! A text input collects the `Text` events of a window (which cover typed characters as well as
+//! text committed by input methods) into a string that can be edited with the usual keys, and shows
+//! it, together with the text an input method is composing, in a text stimulus.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use super::{Event, EventHandlerId, EventKind};
+#[cfg(feature = "python")]
+use crate::visual::stimuli::PyStimulus;
+use crate::{
+    errors::PsydkResult,
+    time::Timestamp,
+    visual::{
+        stimuli::{DynamicStimulus, StimulusParamValue},
+        window::Window,
+    },
+};
+
+/// Text with a caret that is edited with the usual keys.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EditableText {
+    pub text: String,
+    /// The position of the caret, as a byte offset into `text`.
+    pub caret: usize,
+}
+
+impl EditableText {
+    /// Insert `text` at the caret, as far as it fits into `max_length` characters.
+    pub fn insert(&mut self, text: &str, max_length: Option<usize>) {
+        let text = match max_length {
+            Some(max_length) => {
+                let available = max_length.saturating_sub(self.text.chars().count());
+                text.chars().take(available).collect::<String>()
+            }
+            None => text.to_string(),
+        };
+        self.text.insert_str(self.caret, &text);
+        self.caret += text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.caret = 0;
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.caret].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.caret..]
+            .chars()
+            .next()
+            .map_or(self.caret, |c| self.caret + c.len_utf8())
+    }
+
+    /// Handle Backspace, Delete, the left and right arrow keys, Home, or End. Returns true if the
+    /// key was one of them.
+    pub fn key(&mut self, key: &str) -> bool {
+        match key {
+            "Backspace" => {
+                let start = self.previous_boundary();
+                self.text.replace_range(start..self.caret, "");
+                self.caret = start;
+            }
+            "Delete" => {
+                let end = self.next_boundary();
+                self.text.replace_range(self.caret..end, "");
+            }
+            "ArrowLeft" => self.caret = self.previous_boundary(),
+            "ArrowRight" => self.caret = self.next_boundary(),
+            "Home" => self.caret = 0,
+            "End" => self.caret = self.text.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The text with `inserted` (e.g., the text an input method is composing and a caret marker)
+    /// at the caret.
+    pub fn with_caret(&self, inserted: &str) -> String {
+        format!("{}{}{}", &self.text[..self.caret], inserted, &self.text[self.caret..])
+    }
+}
+
+#[derive(Debug, Default)]
+struct TextInputState {
+    text: EditableText,
+    /// The text an input method is composing, shown at the caret.
+    preedit: String,
+    max_length: Option<usize>,
+    multiline: bool,
+    /// Shown at the caret, if set.
+    caret_marker: Option<String>,
+    /// The stimulus whose "text" shows the input.
+    stimulus: Option<DynamicStimulus>,
+    first_input: Option<Instant>,
+    submitted: Option<Instant>,
+}
+
+impl TextInputState {
+    fn insert(&mut self, text: &str) {
+        self.text.insert(text, self.max_length);
+    }
+
+    /// Handle an editing key. Returns true if the key was used.
+    fn key(&mut self, key: &str, time: Instant) -> bool {
+        // while an input method is composing, it handles the keys itself
+        if !self.preedit.is_empty() {
+            return false;
+        }
+        match key {
+            "Enter" if self.multiline => self.insert("\n"),
+            "Enter" => self.submitted = Some(time),
+            key => return self.text.key(key),
+        }
+        true
+    }
+
+    /// The text with the composed text and the caret marker at the caret.
+    fn display(&self) -> String {
+        let caret_marker = self.caret_marker.as_deref().unwrap_or_default();
+        self.text.with_caret(&format!("{}{}", self.preedit, caret_marker))
+    }
+
+    fn update_stimulus(&self) {
+        if let Some(stimulus) = &self.stimulus {
+            stimulus.stage_params([("text".to_string(), StimulusParamValue::String(self.display()))]);
+        }
+    }
+}
+
+/// Collects the text typed into a window.
+#[derive(Debug)]
+pub struct TextInput {
+    window: Window,
+    state: Arc<Mutex<TextInputState>>,
+    handlers: Vec<EventHandlerId>,
+}
+
+impl TextInput {
+    /// Start collecting the text typed into `window`, shown in `stimulus` (if any). Input methods
+    /// are allowed for the window.
+    pub fn new(
+        window: &Window,
+        stimulus: Option<DynamicStimulus>,
+        max_length: Option<usize>,
+        multiline: bool,
+        caret_marker: Option<String>,
+    ) -> PsydkResult<Self> {
+        window.set_ime_allowed(true)?;
+
+        let state = Arc::new(Mutex::new(TextInputState {
+            max_length,
+            multiline,
+            caret_marker,
+            stimulus,
+            ..Default::default()
+        }));
+        state.lock().unwrap().update_stimulus();
+
+        let text_state = state.clone();
+        let text = window.add_event_handler(EventKind::Text, move |event| {
+            if let Event::Text { timestamp, text } = event {
+                let mut state = text_state.lock().unwrap();
+                state.insert(&text);
+                state.first_input.get_or_insert(timestamp.timestamp);
+                state.update_stimulus();
+            }
+            false
+        })?;
+
+        let preedit_state = state.clone();
+        let preedit = window.add_event_handler(EventKind::ImePreedit, move |event| {
+            if let Event::ImePreedit { timestamp, text, .. } = event {
+                let mut state = preedit_state.lock().unwrap();
+                if !text.is_empty() {
+                    state.first_input.get_or_insert(timestamp.timestamp);
+                }
+                state.preedit = text;
+                state.update_stimulus();
+            }
+            false
+        })?;
+
+        let key_state = state.clone();
+        let key = window.add_event_handler(EventKind::KeyPress, move |event| {
+            if let Event::KeyPress { timestamp, key, .. } = event {
+                let mut state = key_state.lock().unwrap();
+                if state.key(&key, timestamp.timestamp) {
+                    state.update_stimulus();
+                }
+            }
+            false
+        })?;
+
+        Ok(Self {
+            window: window.clone(),
+            state,
+            handlers: vec![text, preedit, key],
+        })
+    }
+
+    /// The text that has been entered (without the text an input method is still composing).
+    pub fn text(&self) -> String {
+        self.state.lock().unwrap().text.text.clone()
+    }
+
+    /// Replace the text and move the caret to its end.
+    pub fn set_text(&self, text: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.text.clear();
+        state.insert(text);
+        state.update_stimulus();
+    }
+
+    /// The text an input method is composing.
+    pub fn preedit(&self) -> String {
+        self.state.lock().unwrap().preedit.clone()
+    }
+
+    /// When the first character was entered, if any.
+    pub fn first_input(&self) -> Option<Instant> {
+        self.state.lock().unwrap().first_input
+    }
+
+    /// When Enter was pressed, if it was (and the input is not multiline).
+    pub fn submitted(&self) -> Option<Instant> {
+        self.state.lock().unwrap().submitted
+    }
+
+    /// Discard the text and start over.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.text.clear();
+        state.preedit.clear();
+        state.first_input = None;
+        state.submitted = None;
+        state.update_stimulus();
+    }
+
+    /// Stop collecting text. The text that was entered is kept.
+    pub fn stop(&mut self) {
+        for id in self.handlers.drain(..) {
+            self.window.remove_event_handler(id);
+        }
+    }
+}
+
+impl Drop for TextInput {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Collects the text typed into a window, e.g. for open questions.
+///
+/// Text is taken from `text` events, so the keyboard layout, dead keys, and input methods (e.g.,
+/// for Chinese, Japanese, or Korean) are taken into account. Input methods are allowed for the
+/// window when the input is created. The text can be edited with Backspace, Delete, the left and
+/// right arrow keys, Home, and End. Pressing Enter submits the text (or starts a new line, if the
+/// input is multiline).
+///
+/// If a stimulus is given, its `text` is updated with the entered text, with the text an input
+/// method is composing and the caret inserted at the caret position. The change is shown when the
+/// stimulus is next presented.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to collect the text from.
+/// stimulus : TextStimulus, optional
+///   A stimulus with a `text` parameter that shows the input.
+/// max_length : int, optional
+///   The maximum number of characters. Defaults to no limit.
+/// multiline : bool, optional
+///   Whether Enter starts a new line instead of submitting the text. Defaults to False.
+/// caret : str, optional
+///   The text that marks the caret in the stimulus, or None to show no caret. Defaults to "|".
+#[cfg(feature = "python")]
+#[pyclass(name = "TextInput")]
+pub struct PyTextInput(pub TextInput);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyTextInput {
+    #[new]
+    #[pyo3(signature = (window, stimulus = None, max_length = None, multiline = false, caret = Some("|".to_string())))]
+    fn __new__(
+        window: Window,
+        stimulus: Option<PyStimulus>,
+        max_length: Option<usize>,
+        multiline: bool,
+        caret: Option<String>,
+    ) -> PyResult<Self> {
+        let stimulus = stimulus.map(|stimulus| stimulus.as_super().clone());
+        Ok(Self(TextInput::new(&window, stimulus, max_length, multiline, caret)?))
+    }
+
+    /// The text that has been entered, without the text an input method is still composing.
+    #[getter(text)]
+    fn py_text(&self) -> String {
+        self.0.text()
+    }
+
+    #[setter(text)]
+    fn py_set_text(&self, text: &str) {
+        self.0.set_text(text);
+    }
+
+    /// The text an input method is currently composing, if any.
+    #[getter(preedit)]
+    fn py_preedit(&self) -> String {
+        self.0.preedit()
+    }
+
+    /// When the first character was entered (or an input method started composing), or None.
+    #[getter(first_input)]
+    fn py_first_input(&self) -> Option<Timestamp> {
+        self.0.first_input().map(Timestamp::from)
+    }
+
+    /// When the text was submitted with Enter, or None if it has not been submitted.
+    #[getter(submitted)]
+    fn py_submitted(&self) -> Option<Timestamp> {
+        self.0.submitted().map(Timestamp::from)
+    }
+
+    /// Discard the text and start over.
+    #[pyo3(name = "clear")]
+    fn py_clear(&self) {
+        self.0.clear();
+    }
+
+    /// Stop collecting text. The text that was entered is kept.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[cfg(feature = "python")]
+use numpy::IntoPyArray;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+
+use super::{Event, EventHandlerId, EventKind};
+use crate::{errors::PsydkResult, time::Timestamp, visual::window::Window};
+
+#[derive(Debug)]
+struct TrajectoryState {
+    trial_onset: Instant,
+    /// Raw motion events as (time, dx, dy).
+    motion: Vec<(Instant, f64, f64)>,
+    /// Cursor positions as (time, x, y), in pixels in the coordinate system of the window.
+    cursor: Vec<(Instant, f32, f32)>,
+}
+
+/// Records mouse movements at the polling rate of the mouse, for mouse-tracking paradigms.
+///
+/// Both raw device motion (which is not affected by pointer acceleration) and the cursor position
+/// are recorded with the time each event arrived.
+#[derive(Debug)]
+pub struct MouseTrajectoryRecorder {
+    window: Window,
+    state: Arc<Mutex<TrajectoryState>>,
+    handlers: Vec<EventHandlerId>,
+}
+
+impl MouseTrajectoryRecorder {
+    /// Start recording the mouse movements over `window`.
+    pub fn new(window: &Window) -> PsydkResult<Self> {
+        let state = Arc::new(Mutex::new(TrajectoryState {
+            trial_onset: Instant::now(),
+            motion: Vec::new(),
+            cursor: Vec::new(),
+        }));
+
+        let motion_state = state.clone();
+        let motion = window.add_event_handler(EventKind::MouseMotion, move |event| {
+            if let Event::MouseMotion { timestamp, delta } = event {
+                motion_state
+                    .lock()
+                    .unwrap()
+                    .motion
+                    .push((timestamp.timestamp, delta.0, delta.1));
+            }
+            false
+        })?;
+
+        let cursor_state = state.clone();
+        let cursor = window.add_event_handler(EventKind::CursorMoved, move |event| {
+            if let Event::CursorMoved {
+                timestamp, position, ..
+            } = event
+            {
+                cursor_state
+                    .lock()
+                    .unwrap()
+                    .cursor
+                    .push((timestamp.timestamp, position.0, position.1));
+            }
+            false
+        })?;
+
+        Ok(Self {
+            window: window.clone(),
+            state,
+            handlers: vec![motion, cursor],
+        })
+    }
+
+    /// Discard all recorded movements and start a new trial.
+    pub fn start_trial(&self) -> Instant {
+        let mut state = self.state.lock().unwrap();
+        state.motion.clear();
+        state.cursor.clear();
+        state.trial_onset = Instant::now();
+        state.trial_onset
+    }
+
+    /// Stop recording. Movements that were already recorded are kept.
+    pub fn stop(&mut self) {
+        for id in self.handlers.drain(..) {
+            self.window.remove_event_handler(id);
+        }
+    }
+
+    /// The polling rate of the mouse in Hz, estimated from the median interval between raw motion
+    /// events of the current trial.
+    pub fn polling_rate(&self) -> Option<f64> {
+        let state = self.state.lock().unwrap();
+        let mut intervals = state
+            .motion
+            .windows(2)
+            .map(|w| w[1].0 - w[0].0)
+            .filter(|interval| !interval.is_zero())
+            .collect::<Vec<_>>();
+        intervals.sort();
+        intervals
+            .get(intervals.len() / 2)
+            .map(|interval| 1.0 / interval.as_secs_f64())
+    }
+}
+
+impl Drop for MouseTrajectoryRecorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Records mouse trajectories for mouse-tracking paradigms, e.g. to analyze the curvature of
+/// movements towards response options.
+///
+/// Raw motion events are captured at the polling rate of the mouse (often 125-1000 Hz), not just
+/// once per frame, and are timestamped as they arrive. The cursor position is recorded as well.
+/// Recording starts when the recorder is created. Use `start_trial` at the beginning of each trial
+/// and `get_trial` at the end.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to record the mouse movements in.
+#[cfg(feature = "python")]
+#[pyclass(name = "MouseTrajectoryRecorder")]
+pub struct PyMouseTrajectoryRecorder(pub MouseTrajectoryRecorder);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMouseTrajectoryRecorder {
+    #[new]
+    fn __new__(window: Window) -> PyResult<Self> {
+        Ok(Self(MouseTrajectoryRecorder::new(&window)?))
+    }
+
+    /// Discard all recorded movements and start a new trial.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The onset of the trial.
+    #[pyo3(name = "start_trial")]
+    fn py_start_trial(&self) -> Timestamp {
+        self.0.start_trial().into()
+    }
+
+    /// The movements of the current trial. All times are in seconds relative to the trial onset.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary of numpy arrays: "time", "dx", and "dy" for the raw motion events (in device
+    ///   units), "x" and "y" for the raw motion summed up from the trial onset, and "cursor_time",
+    ///   "cursor_x", and "cursor_y" for the cursor position in pixels in the coordinate system of
+    ///   the window. Also contains the trial "onset".
+    #[pyo3(name = "get_trial")]
+    fn py_get_trial<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let state = self.0.state.lock().unwrap();
+        let onset = state.trial_onset;
+        let relative = |time: Instant| time.saturating_duration_since(onset).as_secs_f64();
+
+        let time = state.motion.iter().map(|m| relative(m.0)).collect::<Vec<_>>();
+        let dx = state.motion.iter().map(|m| m.1).collect::<Vec<_>>();
+        let dy = state.motion.iter().map(|m| m.2).collect::<Vec<_>>();
+        let x = dx
+            .iter()
+            .scan(0.0, |sum, d| {
+                *sum += d;
+                Some(*sum)
+            })
+            .collect::<Vec<_>>();
+        let y = dy
+            .iter()
+            .scan(0.0, |sum, d| {
+                *sum += d;
+                Some(*sum)
+            })
+            .collect::<Vec<_>>();
+
+        let dict = PyDict::new(py);
+        dict.set_item("onset", Timestamp::from(onset))?;
+        dict.set_item("time", time.into_pyarray(py))?;
+        dict.set_item("dx", dx.into_pyarray(py))?;
+        dict.set_item("dy", dy.into_pyarray(py))?;
+        dict.set_item("x", x.into_pyarray(py))?;
+        dict.set_item("y", y.into_pyarray(py))?;
+        dict.set_item(
+            "cursor_time",
+            state
+                .cursor
+                .iter()
+                .map(|c| relative(c.0))
+                .collect::<Vec<_>>()
+                .into_pyarray(py),
+        )?;
+        dict.set_item(
+            "cursor_x",
+            state.cursor.iter().map(|c| c.1).collect::<Vec<_>>().into_pyarray(py),
+        )?;
+        dict.set_item(
+            "cursor_y",
+            state.cursor.iter().map(|c| c.2).collect::<Vec<_>>().into_pyarray(py),
+        )?;
+        Ok(dict)
+    }
+
+    /// The polling rate of the mouse in Hz, estimated from the current trial. None if fewer than
+    /// two raw motion events have been recorded.
+    #[getter(polling_rate)]
+    fn py_polling_rate(&self) -> Option<f64> {
+        self.0.polling_rate()
+    }
+
+    /// Stop recording.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+}
@@ -0,0 +1,493 @@
+//! Controls an Arduino (or any other board) running the StandardFirmata sketch over a serial port.
+//!
+//! Only the parts of the Firmata protocol needed to switch digital lines, write PWM values, and
+//! read digital and analog inputs are implemented. Incoming messages are parsed on a background
+//! thread and timestamped as soon as they arrive.
+
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use serialport::SerialPort;
+use strum::EnumString;
+
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{deadline, to_timeout, wait_until, Timestamp},
+};
+
+// Firmata messages
+const DIGITAL_MESSAGE: u8 = 0x90;
+const ANALOG_MESSAGE: u8 = 0xE0;
+const REPORT_ANALOG: u8 = 0xC0;
+const REPORT_DIGITAL: u8 = 0xD0;
+const SET_PIN_MODE: u8 = 0xF4;
+const SET_DIGITAL_PIN_VALUE: u8 = 0xF5;
+const REPORT_VERSION: u8 = 0xF9;
+const START_SYSEX: u8 = 0xF0;
+const END_SYSEX: u8 = 0xF7;
+const SAMPLING_INTERVAL: u8 = 0x7A;
+
+/// The mode of a pin.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum PinMode {
+    Input,
+    Output,
+    Analog,
+    Pwm,
+    InputPullup,
+}
+
+impl PinMode {
+    fn firmata_mode(self) -> u8 {
+        match self {
+            PinMode::Input => 0x00,
+            PinMode::Output => 0x01,
+            PinMode::Analog => 0x02,
+            PinMode::Pwm => 0x03,
+            PinMode::InputPullup => 0x0B,
+        }
+    }
+}
+
+/// A change of a digital input.
+#[derive(Debug, Clone, Copy)]
+pub struct PinChange {
+    pub pin: u8,
+    pub value: bool,
+    /// When the message reporting the change was received.
+    pub time: Instant,
+}
+
+#[derive(Debug, Default)]
+struct BoardState {
+    /// The state of the 16 digital ports (8 pins each), as last reported by the board.
+    ports: [u8; 16],
+    /// The last value of each analog channel.
+    analog: [Option<u16>; 16],
+    /// Changes of digital inputs that have not been collected yet.
+    changes: Vec<PinChange>,
+    /// Firmware protocol version, once the board has reported it.
+    version: Option<(u8, u8)>,
+}
+
+/// A board that speaks the Firmata protocol.
+#[derive(Clone)]
+pub struct Arduino {
+    port: Arc<Mutex<Box<dyn SerialPort>>>,
+    state: Arc<(Mutex<BoardState>, Condvar)>,
+}
+
+impl std::fmt::Debug for Arduino {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Arduino").finish_non_exhaustive()
+    }
+}
+
+impl Arduino {
+    /// Open the serial port and start reading messages from the board. StandardFirmata uses a
+    /// baud rate of 57600.
+    pub fn open(path: &str, baud_rate: u32) -> PsydkResult<Self> {
+        let port = serialport::new(path, baud_rate)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map_err(|e| PsydkError::IOError(e.into()))?;
+        let reader = port.try_clone().map_err(|e| PsydkError::IOError(e.into()))?;
+
+        let arduino = Self {
+            port: Arc::new(Mutex::new(port)),
+            state: Arc::new((Mutex::new(BoardState::default()), Condvar::new())),
+        };
+
+        let state = Arc::downgrade(&arduino.state);
+        std::thread::spawn(move || {
+            let mut reader = reader;
+            let mut parser = Parser::default();
+            let mut buffer = [0u8; 256];
+            loop {
+                let n = match reader.read(&mut buffer) {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::TimedOut => 0,
+                    Err(e) => {
+                        log::error!("Failed to read from the Arduino: {}", e);
+                        return;
+                    }
+                };
+                // stop once the board has been dropped
+                let Some(state) = state.upgrade() else {
+                    return;
+                };
+                let time = Instant::now();
+                for byte in &buffer[..n] {
+                    if let Some(message) = parser.push(*byte) {
+                        let (lock, condvar) = &*state;
+                        lock.lock().unwrap().apply(message, time);
+                        condvar.notify_all();
+                    }
+                }
+            }
+        });
+
+        Ok(arduino)
+    }
+
+    fn write(&self, bytes: &[u8]) -> PsydkResult<Instant> {
+        let mut port = self.port.lock().unwrap();
+        port.write_all(bytes)?;
+        port.flush()?;
+        Ok(Instant::now())
+    }
+
+    pub fn set_pin_mode(&self, pin: u8, mode: PinMode) -> PsydkResult<()> {
+        self.write(&[SET_PIN_MODE, pin & 0x7F, mode.firmata_mode()])?;
+        if matches!(mode, PinMode::Input | PinMode::InputPullup) {
+            // ask the board to report changes of the port the pin belongs to
+            self.write(&[REPORT_DIGITAL | (pin / 8), 1])?;
+        }
+        Ok(())
+    }
+
+    /// Set a digital output. Returns the time the command was written to the serial port.
+    pub fn digital_write(&self, pin: u8, value: bool) -> PsydkResult<Instant> {
+        self.write(&[SET_DIGITAL_PIN_VALUE, pin & 0x7F, value as u8])
+    }
+
+    /// Set a PWM output (0-255 on most boards).
+    pub fn analog_write(&self, pin: u8, value: u16) -> PsydkResult<Instant> {
+        self.write(&[
+            ANALOG_MESSAGE | (pin & 0x0F),
+            (value & 0x7F) as u8,
+            ((value >> 7) & 0x7F) as u8,
+        ])
+    }
+
+    /// Enable or disable reporting of an analog input channel (A0 is channel 0).
+    pub fn report_analog(&self, channel: u8, enable: bool) -> PsydkResult<()> {
+        self.write(&[REPORT_ANALOG | (channel & 0x0F), enable as u8])?;
+        Ok(())
+    }
+
+    /// Set how often the board reports analog inputs.
+    pub fn set_sampling_interval(&self, interval: Duration) -> PsydkResult<()> {
+        let ms = interval.as_millis().min(0x3FFF) as u16;
+        self.write(&[
+            START_SYSEX,
+            SAMPLING_INTERVAL,
+            (ms & 0x7F) as u8,
+            (ms >> 7) as u8,
+            END_SYSEX,
+        ])?;
+        Ok(())
+    }
+
+    /// The last reported value of a digital input.
+    pub fn digital_read(&self, pin: u8) -> bool {
+        let state = self.state.0.lock().unwrap();
+        state.ports[(pin / 8) as usize % 16] & (1 << (pin % 8)) != 0
+    }
+
+    /// The last reported value of an analog input, or None if it has not been reported yet.
+    pub fn analog_read(&self, channel: u8) -> Option<u16> {
+        self.state.0.lock().unwrap().analog[(channel & 0x0F) as usize]
+    }
+
+    /// Take all digital input changes since the last call.
+    pub fn take_changes(&self) -> Vec<PinChange> {
+        std::mem::take(&mut self.state.0.lock().unwrap().changes)
+    }
+
+    /// Block until a digital input matching `pin` and `value` (if given) changes, or the timeout
+    /// expires. Changes up to and including the matching one are consumed.
+    pub fn wait_for_change(
+        &self,
+        pin: Option<u8>,
+        value: Option<bool>,
+        timeout: Option<Duration>,
+    ) -> Option<PinChange> {
+        let (lock, condvar) = &*self.state;
+        let deadline = deadline(timeout);
+        let mut state = lock.lock().unwrap();
+        loop {
+            let position = state.changes.iter().position(|change| {
+                pin.map_or(true, |pin| change.pin == pin) && value.map_or(true, |value| change.value == value)
+            });
+            if let Some(position) = position {
+                return state.changes.drain(..=position).last();
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    condvar.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => condvar.wait(state).unwrap(),
+            };
+        }
+    }
+
+    pub fn version(&self) -> Option<(u8, u8)> {
+        self.state.0.lock().unwrap().version
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Message {
+    Digital { port: u8, value: u8 },
+    Analog { channel: u8, value: u16 },
+    Version { major: u8, minor: u8 },
+}
+
+impl BoardState {
+    fn apply(&mut self, message: Message, time: Instant) {
+        match message {
+            Message::Digital { port, value } => {
+                let port = port as usize % 16;
+                let changed = self.ports[port] ^ value;
+                for bit in 0..8 {
+                    if changed & (1 << bit) != 0 {
+                        self.changes.push(PinChange {
+                            pin: port as u8 * 8 + bit,
+                            value: value & (1 << bit) != 0,
+                            time,
+                        });
+                    }
+                }
+                self.ports[port] = value;
+            }
+            Message::Analog { channel, value } => self.analog[channel as usize % 16] = Some(value),
+            Message::Version { major, minor } => self.version = Some((major, minor)),
+        }
+    }
+}
+
+/// Splits the incoming byte stream into Firmata messages.
+#[derive(Debug, Default)]
+struct Parser {
+    command: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl Parser {
+    fn push(&mut self, byte: u8) -> Option<Message> {
+        if byte & 0x80 != 0 && byte != END_SYSEX {
+            // a new command starts
+            self.command = Some(byte);
+            self.data.clear();
+            return None;
+        }
+
+        let command = self.command?;
+        if command == START_SYSEX {
+            // sysex messages (e.g. the firmware name) are not used
+            if byte == END_SYSEX {
+                self.command = None;
+            }
+            return None;
+        }
+
+        self.data.push(byte);
+        if self.data.len() < 2 {
+            return None;
+        }
+        let (lsb, msb) = (self.data[0], self.data[1]);
+        self.data.clear();
+
+        let value = lsb as u16 | ((msb as u16) << 7);
+        match command & 0xF0 {
+            DIGITAL_MESSAGE => Some(Message::Digital {
+                port: command & 0x0F,
+                value: value as u8,
+            }),
+            ANALOG_MESSAGE => Some(Message::Analog {
+                channel: command & 0x0F,
+                value,
+            }),
+            _ if command == REPORT_VERSION => Some(Message::Version { major: lsb, minor: msb }),
+            _ => None,
+        }
+    }
+}
+
+/// An Arduino (or compatible board) running the StandardFirmata sketch, for driving LEDs, reward
+/// pumps, and custom buttons.
+///
+/// Digital inputs are reported by the board when they change and are timestamped as soon as the
+/// message arrives, so the timing is limited by the serial connection (usually about 1 ms) rather
+/// than by how often the experiment checks them.
+///
+/// Parameters
+/// ----------
+/// port : str
+///   The serial port the board is connected to, e.g. "/dev/ttyACM0" or "COM3".
+/// baud_rate : int, optional
+///   The baud rate. Defaults to 57600, which is what StandardFirmata uses.
+#[cfg(feature = "python")]
+#[pyclass(name = "Arduino")]
+#[derive(Debug, Clone)]
+pub struct PyArduino(pub Arduino);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyArduino {
+    #[new]
+    #[pyo3(signature = (port, baud_rate = 57600))]
+    fn __new__(port: &str, baud_rate: u32) -> PyResult<Self> {
+        Ok(Self(Arduino::open(port, baud_rate)?))
+    }
+
+    /// Set the mode of a pin.
+    ///
+    /// Parameters
+    /// ----------
+    /// pin : int
+    ///   The pin number.
+    /// mode : str
+    ///   One of "input", "input_pullup", "output", "pwm", or "analog". Changes of input pins are
+    ///   reported by the board.
+    #[pyo3(name = "pin_mode")]
+    fn py_pin_mode(&self, pin: u8, mode: PinMode) -> PyResult<()> {
+        Ok(self.0.set_pin_mode(pin, mode)?)
+    }
+
+    /// Set a digital output.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The time the command was sent.
+    #[pyo3(name = "digital_write")]
+    fn py_digital_write(&self, pin: u8, value: bool) -> PyResult<Timestamp> {
+        Ok(self.0.digital_write(pin, value)?.into())
+    }
+
+    /// Set a digital output high for the given duration, e.g. to open a reward valve. Returns
+    /// immediately; the output is set low again on a background thread.
+    ///
+    /// Parameters
+    /// ----------
+    /// pin : int
+    ///   The pin number.
+    /// duration : float
+    ///   How long the output stays high in seconds.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The time the output was set high.
+    #[pyo3(name = "pulse")]
+    fn py_pulse(&self, pin: u8, duration: f64) -> PyResult<Timestamp> {
+        let duration = Duration::try_from_secs_f64(duration)
+            .map_err(|_| PsydkError::ParameterError(format!("Invalid duration {duration}, must be non-negative")))?;
+        let onset = self.0.digital_write(pin, true)?;
+
+        let arduino = self.0.clone();
+        std::thread::spawn(move || {
+            wait_until(onset + duration);
+            if let Err(e) = arduino.digital_write(pin, false) {
+                log::error!("Failed to end the pulse on pin {}: {}", pin, e);
+            }
+        });
+        Ok(onset.into())
+    }
+
+    /// Set a PWM output.
+    #[pyo3(name = "analog_write")]
+    fn py_analog_write(&self, pin: u8, value: u16) -> PyResult<Timestamp> {
+        Ok(self.0.analog_write(pin, value)?.into())
+    }
+
+    /// The last reported value of a digital input.
+    #[pyo3(name = "digital_read")]
+    fn py_digital_read(&self, pin: u8) -> bool {
+        self.0.digital_read(pin)
+    }
+
+    /// The last reported value of an analog input (A0 is channel 0), or None if reporting has not
+    /// been enabled with `report_analog`.
+    #[pyo3(name = "analog_read")]
+    fn py_analog_read(&self, channel: u8) -> Option<u16> {
+        self.0.analog_read(channel)
+    }
+
+    /// Enable or disable reporting of an analog input channel.
+    #[pyo3(name = "report_analog")]
+    #[pyo3(signature = (channel, enable = true))]
+    fn py_report_analog(&self, channel: u8, enable: bool) -> PyResult<()> {
+        Ok(self.0.report_analog(channel, enable)?)
+    }
+
+    /// Set how often the board reports analog inputs, in seconds.
+    #[pyo3(name = "set_sampling_interval")]
+    fn py_set_sampling_interval(&self, interval: f64) -> PyResult<()> {
+        let interval = Duration::try_from_secs_f64(interval)
+            .ok()
+            .filter(|interval| !interval.is_zero())
+            .ok_or_else(|| {
+                PsydkError::ParameterError(format!("Invalid sampling interval {interval}, must be positive"))
+            })?;
+        Ok(self.0.set_sampling_interval(interval)?)
+    }
+
+    /// All changes of digital inputs since the last call, as dictionaries with the pin, the new
+    /// value, and the timestamp.
+    #[pyo3(name = "get_changes")]
+    fn py_get_changes<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .take_changes()
+            .into_iter()
+            .map(|change| change_to_dict(py, change))
+            .collect()
+    }
+
+    /// Block until a digital input changes.
+    ///
+    /// Parameters
+    /// ----------
+    /// pin : int, optional
+    ///   Only wait for changes of this pin.
+    /// value : bool, optional
+    ///   Only wait for changes to this value, e.g. True for a button press.
+    /// timeout : float, optional
+    ///   The maximum time to wait in seconds. Waits indefinitely if not given.
+    ///
+    /// Returns
+    /// -------
+    /// dict or None
+    ///   The change, or None if the timeout expired.
+    #[pyo3(name = "wait_for_change")]
+    #[pyo3(signature = (pin = None, value = None, timeout = None))]
+    fn py_wait_for_change<'py>(
+        &self,
+        py: Python<'py>,
+        pin: Option<u8>,
+        value: Option<bool>,
+        timeout: Option<f64>,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let timeout = to_timeout(timeout)?;
+        let change = py.allow_threads(|| self.0.wait_for_change(pin, value, timeout));
+        change.map(|change| change_to_dict(py, change)).transpose()
+    }
+
+    /// The Firmata protocol version reported by the board, or None.
+    #[getter(version)]
+    fn py_version(&self) -> Option<(u8, u8)> {
+        self.0.version()
+    }
+}
+
+#[cfg(feature = "python")]
+fn change_to_dict(py: Python<'_>, change: PinChange) -> PyResult<Bound<'_, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("pin", change.pin)?;
+    dict.set_item("value", change.value)?;
+    dict.set_item("timestamp", Timestamp::from(change.time))?;
+    Ok(dict)
+}
@@ -0,0 +1,634 @@
+//! Inlets for physiological signals (EEG, EDA, ECG, ...) that are recorded alongside the
+//! experiment.
+//!
+//! Samples are read on a background thread from a BrainFlow board or from a serial port that
+//! sends one line of ASCII numbers per sample. Every sample is stored with a timestamp on the
+//! psydk clock and can be written to a `CSVWriter`, together with the event markers that were
+//! sent in the meantime.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use numpy::{ndarray::Array2, IntoPyArray};
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use strum::EnumString;
+
+#[cfg(feature = "python")]
+use crate::utils::PyCSVWriter;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+    utils::{markers::MarkerSink, CSVWriter},
+};
+
+/// How often a BrainFlow board is polled for new data.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long the serial reader waits for data before checking whether it should stop.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A type of channel of a BrainFlow board.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum BioSignal {
+    Eeg,
+    /// All ExG channels (EEG, EMG, ECG, and EOG) of boards that don't distinguish them.
+    Exg,
+    Ecg,
+    Eda,
+    Ppg,
+}
+
+/// Where the samples come from.
+#[derive(Debug, Clone)]
+pub enum BioSource {
+    /// A board supported by BrainFlow, see the BrainFlow documentation for the board ids and the
+    /// parameters each board needs.
+    BrainFlow {
+        board_id: i32,
+        serial_port: Option<String>,
+        ip_address: Option<String>,
+        ip_port: Option<u16>,
+        signals: Vec<BioSignal>,
+    },
+    /// A serial port that sends one sample per line as numbers separated by commas, semicolons,
+    /// tabs, or spaces, with one number per channel.
+    Serial {
+        port: String,
+        baud_rate: u32,
+        channels: Vec<String>,
+    },
+}
+
+/// Reads the samples that arrived since the last call.
+type Reader = Box<dyn FnMut() -> PsydkResult<Vec<(Instant, Vec<f64>)>>>;
+
+#[derive(Debug)]
+struct BioBuffer {
+    times: Vec<Instant>,
+    /// Samples of all channels, one row per sample.
+    values: Vec<f64>,
+    trial_onset: Instant,
+}
+
+/// Markers that have not been written yet, as (time, label).
+type PendingMarkers = Arc<Mutex<Vec<(Instant, String)>>>;
+
+/// Receives samples of a physiological signal on a background thread.
+#[derive(Debug)]
+pub struct BioInlet {
+    pub source: BioSource,
+    /// The names of the channels. For BrainFlow boards, they are known once the inlet started.
+    channels: Arc<Mutex<Vec<String>>>,
+    buffer: Arc<Mutex<BioBuffer>>,
+    writer: Arc<Mutex<Option<CSVWriter>>>,
+    markers: PendingMarkers,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BioInlet {
+    pub fn new(source: BioSource) -> PsydkResult<Self> {
+        let channels = match &source {
+            BioSource::BrainFlow { signals, .. } if signals.is_empty() => {
+                return Err(PsydkError::ParameterError(
+                    "At least one signal must be recorded".into(),
+                ))
+            }
+            BioSource::Serial { channels, .. } if channels.is_empty() => {
+                return Err(PsydkError::ParameterError(
+                    "At least one channel must be recorded".into(),
+                ))
+            }
+            BioSource::BrainFlow { .. } => Vec::new(),
+            BioSource::Serial { channels, .. } => channels.clone(),
+        };
+
+        Ok(Self {
+            source,
+            channels: Arc::new(Mutex::new(channels)),
+            buffer: Arc::new(Mutex::new(BioBuffer {
+                times: Vec::new(),
+                values: Vec::new(),
+                trial_onset: Instant::now(),
+            })),
+            writer: Arc::new(Mutex::new(None)),
+            markers: Arc::new(Mutex::new(Vec::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.lock().unwrap().clone()
+    }
+
+    /// Start receiving samples. Does nothing if the inlet is already running.
+    pub fn start(&mut self) -> PsydkResult<()> {
+        if self.is_running() {
+            return Ok(());
+        }
+        self.stop.store(false, Ordering::Relaxed);
+
+        let source = self.source.clone();
+        let channels = self.channels.clone();
+        let buffer = self.buffer.clone();
+        let writer = self.writer.clone();
+        let markers = self.markers.clone();
+        let stop = self.stop.clone();
+        let (ready_sender, ready_receiver) = std::sync::mpsc::channel();
+        self.thread = Some(std::thread::spawn(move || {
+            // the device is opened on the receiving thread, since BrainFlow boards can't be sent
+            // between threads
+            let mut read = match open_reader(&source, &channels) {
+                Ok(read) => {
+                    let _ = ready_sender.send(Ok(()));
+                    read
+                }
+                Err(e) => {
+                    let _ = ready_sender.send(Err(e));
+                    return;
+                }
+            };
+
+            while !stop.load(Ordering::Relaxed) {
+                let samples = match read() {
+                    Ok(samples) => samples,
+                    Err(e) => {
+                        log::error!("Failed to read physiological data: {}", e);
+                        break;
+                    }
+                };
+                if samples.is_empty() {
+                    continue;
+                }
+
+                if let Some(writer) = &*writer.lock().unwrap() {
+                    let mut markers = markers.lock().unwrap();
+                    for (time, values) in &samples {
+                        // markers are written with the first sample at or after them
+                        let (due, pending): (Vec<_>, Vec<_>) =
+                            markers.drain(..).partition(|(marker_time, _)| marker_time <= time);
+                        *markers = pending;
+                        let marker = due.into_iter().map(|(_, label)| label).collect::<Vec<_>>().join(";");
+
+                        let mut record = Vec::with_capacity(values.len() + 2);
+                        record.push(format!("{:.6}", Timestamp::from(*time).unix()));
+                        record.extend(values.iter().map(|value| value.to_string()));
+                        record.push(marker);
+                        if let Err(e) = writer.write_record(record) {
+                            log::error!("Failed to write physiological data: {}", e);
+                        }
+                    }
+                }
+
+                let mut buffer = buffer.lock().unwrap();
+                for (time, values) in samples {
+                    buffer.times.push(time);
+                    buffer.values.extend(values);
+                }
+            }
+        }));
+
+        let ready = ready_receiver.recv().unwrap_or_else(|_| {
+            Err(PsydkError::CustomError(
+                "The receiving thread stopped unexpectedly".into(),
+            ))
+        });
+        if ready.is_err() {
+            self.stop();
+        }
+        ready
+    }
+
+    /// Stop receiving samples. Samples that were already received are kept.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Write all following samples to `writer`, with the columns "time" (Unix time in seconds),
+    /// one column per channel, and "marker". Pass `None` to stop writing.
+    pub fn set_writer(&self, writer: Option<CSVWriter>) {
+        self.markers.lock().unwrap().clear();
+        *self.writer.lock().unwrap() = writer;
+    }
+
+    /// Add a marker to the written data, in the row of the first sample at or after `time`.
+    pub fn mark(&self, label: String, time: Instant) {
+        if self.writer.lock().unwrap().is_some() {
+            self.markers.lock().unwrap().push((time, label));
+        }
+    }
+
+    /// A sink for `Markers` that adds every marker code to the written data.
+    pub fn marker_sink(&self) -> BioMarkerSink {
+        BioMarkerSink {
+            writer: self.writer.clone(),
+            markers: self.markers.clone(),
+        }
+    }
+
+    /// Discard all buffered samples and start a new trial.
+    pub fn start_trial(&self) -> Instant {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.times.clear();
+        buffer.values.clear();
+        buffer.trial_onset = Instant::now();
+        buffer.trial_onset
+    }
+
+    /// The onset of the current trial and the times and values of all samples since then.
+    pub fn samples(&self) -> (Instant, Vec<Instant>, Vec<f64>) {
+        let buffer = self.buffer.lock().unwrap();
+        (buffer.trial_onset, buffer.times.clone(), buffer.values.clone())
+    }
+}
+
+impl Drop for BioInlet {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Adds the codes of sent markers to the data that a `BioInlet` writes.
+pub struct BioMarkerSink {
+    writer: Arc<Mutex<Option<CSVWriter>>>,
+    markers: PendingMarkers,
+}
+
+impl MarkerSink for BioMarkerSink {
+    fn send(&mut self, code: u8) -> PsydkResult<()> {
+        if self.writer.lock().unwrap().is_some() {
+            self.markers.lock().unwrap().push((Instant::now(), code.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Open the device of `source` and set the channel names.
+fn open_reader(source: &BioSource, channels: &Mutex<Vec<String>>) -> PsydkResult<Reader> {
+    match source {
+        #[cfg(feature = "brainflow")]
+        BioSource::BrainFlow {
+            board_id,
+            serial_port,
+            ip_address,
+            ip_port,
+            signals,
+        } => {
+            use brainflow::{
+                board_shim::{self, BoardShim},
+                brainflow_input_params::BrainFlowInputParamsBuilder,
+                BoardIds, BrainFlowPresets,
+            };
+            use num_traits::FromPrimitive;
+
+            let to_error = |e: brainflow::error::Error| PsydkError::CustomError(format!("BrainFlow: {e}"));
+            let board_id = BoardIds::from_i32(*board_id)
+                .ok_or_else(|| PsydkError::ParameterError(format!("Unknown BrainFlow board id {board_id}")))?;
+            let preset = BrainFlowPresets::DefaultPreset;
+
+            let mut indices = Vec::new();
+            let mut names = Vec::new();
+            for signal in signals {
+                let signal_channels = match signal {
+                    BioSignal::Eeg => board_shim::get_eeg_channels(board_id, preset),
+                    BioSignal::Exg => board_shim::get_exg_channels(board_id, preset),
+                    BioSignal::Ecg => board_shim::get_ecg_channels(board_id, preset),
+                    BioSignal::Eda => board_shim::get_eda_channels(board_id, preset),
+                    BioSignal::Ppg => board_shim::get_ppg_channels(board_id, preset),
+                }
+                .map_err(to_error)?;
+                let prefix = format!("{signal:?}").to_lowercase();
+                for (i, index) in signal_channels.into_iter().enumerate() {
+                    indices.push(index);
+                    names.push(format!("{prefix}_{}", i + 1));
+                }
+            }
+            let timestamp_channel = board_shim::get_timestamp_channel(board_id, preset).map_err(to_error)?;
+
+            let mut params = BrainFlowInputParamsBuilder::default();
+            if let Some(serial_port) = serial_port {
+                params = params.serial_port(serial_port);
+            }
+            if let Some(ip_address) = ip_address {
+                params = params.ip_address(ip_address);
+            }
+            if let Some(ip_port) = ip_port {
+                params = params.ip_port(*ip_port as usize);
+            }
+            /// Releases the board when the reader is dropped, i.e. when the inlet stops.
+            struct Session(BoardShim);
+            impl Drop for Session {
+                fn drop(&mut self) {
+                    let _ = self.0.stop_stream();
+                    let _ = self.0.release_session();
+                }
+            }
+
+            let board = BoardShim::new(board_id, params.build()).map_err(to_error)?;
+            board.prepare_session().map_err(to_error)?;
+            let session = Session(board);
+            session.0.start_stream(45000, "").map_err(to_error)?;
+            *channels.lock().unwrap() = names;
+
+            Ok(Box::new(move || {
+                std::thread::sleep(POLL_INTERVAL);
+                let data = session.0.get_board_data(None, preset).map_err(to_error)?;
+                // BrainFlow timestamps are Unix times, taken when the data arrived at the computer
+                data.columns()
+                    .into_iter()
+                    .map(|column| {
+                        let time = Timestamp::from_unix(column[timestamp_channel])?.timestamp;
+                        Ok((time, indices.iter().map(|index| column[*index]).collect()))
+                    })
+                    .collect()
+            }))
+        }
+        #[cfg(not(feature = "brainflow"))]
+        BioSource::BrainFlow { .. } => {
+            let _ = channels;
+            Err(PsydkError::ParameterError(
+                "Can't open a BrainFlow board, psydk was built without the `brainflow` feature".into(),
+            ))
+        }
+        #[cfg(feature = "serial")]
+        BioSource::Serial {
+            port,
+            baud_rate,
+            channels,
+        } => {
+            use std::io::BufRead;
+
+            let port = serialport::new(port, *baud_rate)
+                .timeout(RECEIVE_TIMEOUT)
+                .open()
+                .map_err(|e| PsydkError::IOError(e.into()))?;
+            let mut reader = std::io::BufReader::new(port);
+            let n_channels = channels.len();
+            let mut line = String::new();
+            let mut warned = false;
+
+            Ok(Box::new(move || {
+                // a line that is cut off by the timeout is completed by the next call
+                match reader.read_line(&mut line) {
+                    Ok(_) if line.ends_with('\n') => {}
+                    Ok(_) => return Ok(Vec::new()),
+                    Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Ok(Vec::new()),
+                    Err(e) => return Err(e.into()),
+                }
+                let time = Instant::now();
+                let values = line
+                    .split(|c: char| matches!(c, ',' | ';' | '\t' | ' '))
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(str::parse::<f64>)
+                    .collect::<Result<Vec<_>, _>>();
+                line.clear();
+
+                match values {
+                    Ok(values) if values.len() == n_channels => Ok(vec![(time, values)]),
+                    _ => {
+                        if !warned {
+                            log::warn!("Skipping lines that don't have {} numbers", n_channels);
+                            warned = true;
+                        }
+                        Ok(Vec::new())
+                    }
+                }
+            }))
+        }
+        #[cfg(not(feature = "serial"))]
+        BioSource::Serial { port, .. } => {
+            let _ = channels;
+            Err(PsydkError::ParameterError(format!(
+                "Can't open serial port {port}, psydk was built without the `serial` feature"
+            )))
+        }
+    }
+}
+
+/// Records a physiological signal (e.g. EEG, EDA, or ECG) alongside the experiment.
+///
+/// Samples are received on a background thread from a board supported by BrainFlow (requires the
+/// `brainflow` feature) or from a serial port that sends one line of numbers per sample (requires
+/// the `serial` feature), and are timestamped on the psydk clock. Use `start_trial` at the
+/// beginning of each trial and `get_trial` at the end to retrieve the samples as numpy arrays, and
+/// `record` to write all samples to a CSV file together with event markers.
+///
+/// Parameters
+/// ----------
+/// source : str
+///   "brainflow" or "serial".
+/// board_id : int, optional
+///   The BrainFlow board id, e.g. -1 for the synthetic board. Required for "brainflow".
+/// port : str, optional
+///   The serial port, e.g. "/dev/ttyUSB0" or "COM3". Required for "serial", and passed to
+///   BrainFlow boards that are connected to a serial port.
+/// baud_rate : int, optional
+///   The baud rate of the serial port. Defaults to 115200.
+/// ip_address : str, optional
+///   The IP address of BrainFlow boards that are connected over the network.
+/// ip_port : int, optional
+///   The port of BrainFlow boards that are connected over the network.
+/// signals : list[str], optional
+///   The channels of a BrainFlow board to record: "eeg", "exg", "ecg", "eda", or "ppg". Defaults
+///   to EEG.
+/// channels : list[str], optional
+///   The names of the channels of a serial source, one per number in each line. Required for
+///   "serial".
+#[cfg(feature = "python")]
+#[pyclass(name = "BioInlet")]
+pub struct PyBioInlet(pub BioInlet);
+
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+enum SourceKind {
+    #[strum(serialize = "brainflow")]
+    BrainFlow,
+    Serial,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyBioInlet {
+    #[new]
+    #[pyo3(signature = (
+        source,
+        board_id = None,
+        port = None,
+        baud_rate = 115200,
+        ip_address = None,
+        ip_port = None,
+        signals = vec![BioSignal::Eeg],
+        channels = None,
+    ))]
+    fn __new__(
+        py: Python,
+        source: SourceKind,
+        board_id: Option<i32>,
+        port: Option<String>,
+        baud_rate: u32,
+        ip_address: Option<String>,
+        ip_port: Option<u16>,
+        signals: Vec<BioSignal>,
+        channels: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let source = match source {
+            SourceKind::BrainFlow => BioSource::BrainFlow {
+                board_id: board_id
+                    .ok_or_else(|| PsydkError::ParameterError("A board id is required for BrainFlow".into()))?,
+                serial_port: port,
+                ip_address,
+                ip_port,
+                signals,
+            },
+            SourceKind::Serial => BioSource::Serial {
+                port: port.ok_or_else(|| PsydkError::ParameterError("A serial port is required".into()))?,
+                baud_rate,
+                channels: channels.ok_or_else(|| {
+                    PsydkError::ParameterError("The channel names are required for a serial source".into())
+                })?,
+            },
+        };
+        let mut inlet = BioInlet::new(source)?;
+        py.allow_threads(|| inlet.start())?;
+        Ok(Self(inlet))
+    }
+
+    /// Start receiving samples again after `stop`.
+    #[pyo3(name = "start")]
+    fn py_start(&mut self, py: Python) -> PyResult<()> {
+        let inlet = &mut self.0;
+        Ok(py.allow_threads(|| inlet.start())?)
+    }
+
+    /// Stop receiving samples.
+    #[pyo3(name = "stop")]
+    fn py_stop(&mut self) {
+        self.0.stop();
+    }
+
+    /// Write all following samples to a CSV file, with the columns "time" (Unix time in seconds),
+    /// one column per channel, and "marker". Markers added with `mark`, or sent by a `Markers`
+    /// object that the inlet was added to, are written in the row of the first sample at or after
+    /// them.
+    ///
+    /// Parameters
+    /// ----------
+    /// writer : CSVWriter or None
+    ///   The writer, created with the headers from `record_headers`. Pass None to stop writing.
+    #[pyo3(name = "record")]
+    fn py_record(&self, writer: Option<PyCSVWriter>) -> PyResult<()> {
+        if let Some(writer) = &writer {
+            let headers = self.py_record_headers();
+            if writer.0.headers != headers {
+                return Err(PsydkError::ParameterError(format!(
+                    "The headers of the writer must be {headers:?}, got {:?}",
+                    writer.0.headers
+                ))
+                .into());
+            }
+        }
+        self.0.set_writer(writer.map(|writer| writer.0));
+        Ok(())
+    }
+
+    /// The headers of the CSV file that `record` writes.
+    #[getter(record_headers)]
+    fn py_record_headers(&self) -> Vec<String> {
+        let mut headers = vec!["time".to_string()];
+        headers.extend(self.0.channels());
+        headers.push("marker".to_string());
+        headers
+    }
+
+    /// Add a marker to the recorded data.
+    ///
+    /// Parameters
+    /// ----------
+    /// label : str
+    ///   The label of the marker.
+    /// timestamp : Timestamp, optional
+    ///   The time of the marker. Defaults to now.
+    #[pyo3(name = "mark")]
+    #[pyo3(signature = (label, timestamp = None))]
+    fn py_mark(&self, label: String, timestamp: Option<Timestamp>) {
+        let time = timestamp.map_or_else(Instant::now, |timestamp| timestamp.timestamp);
+        self.0.mark(label, time);
+    }
+
+    /// Discard all buffered samples and start a new trial.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The onset of the trial.
+    #[pyo3(name = "start_trial")]
+    fn py_start_trial(&self) -> Timestamp {
+        self.0.start_trial().into()
+    }
+
+    /// The samples of the current trial.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary with "time" (the time of each sample in seconds relative to the trial
+    ///   onset, as a 1D array), "values" (one row per sample and one column per channel, as a 2D
+    ///   array), "channels" (the names of the columns), and "onset" (the trial onset).
+    #[pyo3(name = "get_trial")]
+    fn py_get_trial<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let channels = self.0.channels();
+        let (onset, times, values) = self.0.samples();
+
+        let times = times
+            .iter()
+            .map(|time| Timestamp::from(*time).seconds_since_instant(onset))
+            .collect::<Vec<_>>();
+        let values = Array2::from_shape_vec((times.len(), channels.len()), values)
+            .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("time", times.into_pyarray(py))?;
+        dict.set_item("values", values.into_pyarray(py))?;
+        dict.set_item("channels", channels)?;
+        dict.set_item("onset", Timestamp::from(onset))?;
+        Ok(dict)
+    }
+
+    /// The names of the channels.
+    #[getter(channels)]
+    fn py_channels(&self) -> Vec<String> {
+        self.0.channels()
+    }
+
+    /// Whether the inlet is receiving samples.
+    #[getter(running)]
+    fn py_running(&self) -> bool {
+        self.0.is_running()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BioInlet(channels={:?}, running={})",
+            self.0.channels(),
+            self.0.is_running()
+        )
+    }
+}
@@ -0,0 +1,9 @@
+//! Helpers for custom lab hardware and physiological recordings.
+
+#[cfg(feature = "serial")]
+pub mod arduino;
+pub mod bio;
+#[cfg(feature = "osc")]
+pub mod osc;
+#[cfg(feature = "serial")]
+pub mod relay;
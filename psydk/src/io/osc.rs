@@ -0,0 +1,549 @@
+//! Open Sound Control (OSC) over UDP, e.g. to talk to Max/MSP, SuperCollider, motion capture, or VR
+//! middleware.
+//!
+//! Messages are sent immediately or as bundles with a time tag, so that the receiver can schedule
+//! them. Received messages are read on a background thread and stored with the time they arrived on
+//! the psydk clock. They can also be forwarded to a window as `Other` events named after their
+//! address, so that they are handled like other input events.
+
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyString},
+};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    input::Event,
+    time::{TimelineEvent, Timestamp},
+    visual::window::Window,
+};
+
+/// How long the receiving thread waits for a packet before checking whether it should stop.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Seconds from the NTP epoch (1900), which OSC time tags count from, to the UNIX epoch.
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+
+/// Convert a timestamp to an OSC time tag.
+fn to_time_tag(time: Instant) -> OscTime {
+    let seconds = Timestamp::from(time).unix() + NTP_UNIX_OFFSET;
+    OscTime {
+        seconds: seconds.trunc() as u32,
+        fractional: (seconds.fract() * (1u64 << 32) as f64) as u32,
+    }
+}
+
+/// Convert an OSC time tag to a timestamp. The special tag "immediately" (1) has no time.
+fn from_time_tag(tag: OscTime) -> Option<Instant> {
+    if tag.seconds == 0 && tag.fractional <= 1 {
+        return None;
+    }
+    let seconds = tag.seconds as f64 + tag.fractional as f64 / (1u64 << 32) as f64 - NTP_UNIX_OFFSET;
+    Timestamp::from_unix(seconds).ok().map(|timestamp| timestamp.timestamp)
+}
+
+/// A received OSC message.
+#[derive(Debug, Clone)]
+pub struct ReceivedMessage {
+    pub address: String,
+    pub args: Vec<OscType>,
+    pub sender: SocketAddr,
+    /// When the message arrived.
+    pub timestamp: Instant,
+    /// The time tag of the bundle the message arrived in, if any.
+    pub time_tag: Option<Instant>,
+}
+
+/// A message that has been sent.
+#[derive(Debug, Clone)]
+pub struct SentMessage {
+    pub address: String,
+    pub args: Vec<OscType>,
+    pub timestamp: Instant,
+    /// The time tag the message was sent with, if any.
+    pub time_tag: Option<Instant>,
+}
+
+/// Sends OSC messages to one address.
+#[derive(Debug)]
+pub struct OscSender {
+    socket: UdpSocket,
+    pub target: SocketAddr,
+    pub log: Vec<SentMessage>,
+}
+
+impl OscSender {
+    /// Send to `target` from `local_port`, or from any free port.
+    pub fn new(target: impl ToSocketAddrs, local_port: Option<u16>) -> PsydkResult<Self> {
+        let target = target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| PsydkError::ParameterError("The OSC target could not be resolved".into()))?;
+        let local: SocketAddr = match target {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], local_port.unwrap_or(0)).into(),
+            SocketAddr::V6(_) => ([0u16; 8], local_port.unwrap_or(0)).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        // allows sending to broadcast addresses, which is common for OSC on a local network
+        socket.set_broadcast(true)?;
+        Ok(Self {
+            socket,
+            target,
+            log: Vec::new(),
+        })
+    }
+
+    /// Send a message, in a bundle with a time tag if `at` is given. Returns when it was sent.
+    pub fn send(&mut self, address: &str, args: Vec<OscType>, at: Option<Instant>) -> PsydkResult<Instant> {
+        if !address.starts_with('/') {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid OSC address \"{address}\", must start with /"
+            )));
+        }
+
+        let message = OscMessage {
+            addr: address.to_string(),
+            args,
+        };
+        let packet = match at {
+            Some(at) => OscPacket::Bundle(OscBundle {
+                timetag: to_time_tag(at),
+                content: vec![OscPacket::Message(message.clone())],
+            }),
+            None => OscPacket::Message(message.clone()),
+        };
+        let bytes = rosc::encoder::encode(&packet).map_err(|e| PsydkError::CustomError(format!("OSC: {e}")))?;
+
+        self.socket.send_to(&bytes, self.target)?;
+        let timestamp = Instant::now();
+        self.log.push(SentMessage {
+            address: message.addr,
+            args: message.args,
+            timestamp,
+            time_tag: at,
+        });
+        Ok(timestamp)
+    }
+}
+
+/// Add the messages in `packet` to `messages`, with the time tag of the innermost bundle.
+fn unpack(
+    packet: OscPacket,
+    time_tag: Option<Instant>,
+    sender: SocketAddr,
+    timestamp: Instant,
+    messages: &mut Vec<ReceivedMessage>,
+) {
+    match packet {
+        OscPacket::Message(message) => messages.push(ReceivedMessage {
+            address: message.addr,
+            args: message.args,
+            sender,
+            timestamp,
+            time_tag,
+        }),
+        OscPacket::Bundle(bundle) => {
+            let time_tag = from_time_tag(bundle.timetag).or(time_tag);
+            for packet in bundle.content {
+                unpack(packet, time_tag, sender, timestamp, messages);
+            }
+        }
+    }
+}
+
+/// Receives OSC messages on a background thread.
+#[derive(Debug)]
+pub struct OscReceiver {
+    pub address: SocketAddr,
+    /// Messages that have not been polled yet.
+    pending: Arc<Mutex<Vec<ReceivedMessage>>>,
+    log: Arc<Mutex<Vec<ReceivedMessage>>>,
+    /// The window that messages are forwarded to as events.
+    forward: Arc<Mutex<Option<Window>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscReceiver {
+    /// Listen on `address`, e.g. `0.0.0.0:9000`.
+    pub fn bind(address: impl ToSocketAddrs) -> PsydkResult<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let address = socket.local_addr()?;
+
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let forward: Arc<Mutex<Option<Window>>> = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (pending, received, forward, stop) = (pending.clone(), log.clone(), forward.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let mut buffer = vec![0u8; rosc::decoder::MTU];
+                while !stop.load(Ordering::Relaxed) {
+                    let (size, sender) = match socket.recv_from(&mut buffer) {
+                        Ok(received) => received,
+                        Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                            continue
+                        }
+                        Err(e) => {
+                            log::error!("Failed to receive OSC messages: {}", e);
+                            break;
+                        }
+                    };
+                    let timestamp = Instant::now();
+                    let packet = match rosc::decoder::decode_udp(&buffer[..size]) {
+                        Ok((_, packet)) => packet,
+                        Err(e) => {
+                            log::warn!("Ignoring an invalid OSC packet from {}: {}", sender, e);
+                            continue;
+                        }
+                    };
+
+                    let mut messages = Vec::new();
+                    unpack(packet, None, sender, timestamp, &mut messages);
+                    if let Some(window) = &*forward.lock().unwrap() {
+                        for message in &messages {
+                            window.inject_event(Event::Other {
+                                timestamp: message.timestamp.into(),
+                                name: message.address.clone(),
+                            });
+                        }
+                    }
+                    received.lock().unwrap().extend(messages.iter().cloned());
+                    pending.lock().unwrap().extend(messages);
+                }
+            })
+        };
+
+        Ok(Self {
+            address,
+            pending,
+            log,
+            forward,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The messages that arrived since the last call.
+    pub fn poll(&self) -> Vec<ReceivedMessage> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+
+    /// All messages that arrived.
+    pub fn log(&self) -> Vec<ReceivedMessage> {
+        self.log.lock().unwrap().clone()
+    }
+
+    /// Forward all following messages to `window` as `Other` events named after their address, or
+    /// stop forwarding with `None`.
+    pub fn forward_to(&self, window: Option<Window>) {
+        *self.forward.lock().unwrap() = window;
+    }
+
+    /// Stop receiving messages. Messages that already arrived are kept.
+    pub fn close(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for OscReceiver {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// An OSC argument, converted from and to Python values.
+#[cfg(feature = "python")]
+pub struct PyOscArg(pub OscType);
+
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for PyOscArg {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        // bool must be checked before int, as it is a subclass
+        let arg = if ob.is_instance_of::<PyBool>() {
+            OscType::Bool(ob.extract()?)
+        } else if ob.is_instance_of::<PyInt>() {
+            let value: i64 = ob.extract()?;
+            match i32::try_from(value) {
+                Ok(value) => OscType::Int(value),
+                Err(_) => OscType::Long(value),
+            }
+        } else if ob.is_instance_of::<PyFloat>() {
+            OscType::Float(ob.extract::<f64>()? as f32)
+        } else if ob.is_instance_of::<PyString>() {
+            OscType::String(ob.extract()?)
+        } else if ob.is_instance_of::<PyBytes>() {
+            OscType::Blob(ob.extract()?)
+        } else if ob.is_none() {
+            OscType::Nil
+        } else if let Ok(timestamp) = ob.extract::<Timestamp>() {
+            OscType::Time(to_time_tag(timestamp.timestamp))
+        } else {
+            return Err(
+                PsydkError::ParameterError(format!("Can't send {} as an OSC argument", ob.get_type().name()?)).into(),
+            );
+        };
+        Ok(Self(arg))
+    }
+}
+
+#[cfg(feature = "python")]
+fn arg_to_py<'py>(py: Python<'py>, arg: &OscType) -> PyResult<PyObject> {
+    Ok(match arg {
+        OscType::Int(value) => value.into_pyobject(py)?.into_any().unbind(),
+        OscType::Long(value) => value.into_pyobject(py)?.into_any().unbind(),
+        OscType::Float(value) => value.into_pyobject(py)?.into_any().unbind(),
+        OscType::Double(value) => value.into_pyobject(py)?.into_any().unbind(),
+        OscType::String(value) => value.into_pyobject(py)?.into_any().unbind(),
+        OscType::Char(value) => value.to_string().into_pyobject(py)?.into_any().unbind(),
+        OscType::Bool(value) => value.into_pyobject(py)?.to_owned().into_any().unbind(),
+        OscType::Blob(value) => PyBytes::new(py, value).into_any().unbind(),
+        OscType::Time(tag) => from_time_tag(*tag)
+            .map(Timestamp::from)
+            .into_pyobject(py)?
+            .into_any()
+            .unbind(),
+        OscType::Array(array) => array
+            .content
+            .iter()
+            .map(|arg| arg_to_py(py, arg))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_pyobject(py)?
+            .into_any()
+            .unbind(),
+        OscType::Inf => f64::INFINITY.into_pyobject(py)?.into_any().unbind(),
+        // colors and MIDI messages are passed as their components
+        OscType::Color(color) => (color.red, color.green, color.blue, color.alpha)
+            .into_pyobject(py)?
+            .into_any()
+            .unbind(),
+        OscType::Midi(midi) => (midi.port, midi.status, midi.data1, midi.data2)
+            .into_pyobject(py)?
+            .into_any()
+            .unbind(),
+        OscType::Nil => py.None(),
+    })
+}
+
+#[cfg(feature = "python")]
+fn message_to_py<'py>(
+    py: Python<'py>,
+    address: &str,
+    args: &[OscType],
+    timestamp: Instant,
+    time_tag: Option<Instant>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("address", address)?;
+    dict.set_item(
+        "args",
+        args.iter()
+            .map(|arg| arg_to_py(py, arg))
+            .collect::<PyResult<Vec<_>>>()?,
+    )?;
+    dict.set_item("timestamp", Timestamp::from(timestamp))?;
+    dict.set_item("time_tag", time_tag.map(Timestamp::from))?;
+    Ok(dict)
+}
+
+/// Sends Open Sound Control (OSC) messages over UDP.
+///
+/// Parameters
+/// ----------
+/// host : str
+///   The host to send to, e.g. "127.0.0.1", or a broadcast address.
+/// port : int
+///   The port to send to.
+/// local_port : int, optional
+///   The port to send from. Defaults to any free port.
+#[cfg(feature = "python")]
+#[pyclass(name = "OscSender")]
+pub struct PyOscSender(pub OscSender);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyOscSender {
+    #[new]
+    #[pyo3(signature = (host, port, local_port = None))]
+    fn __new__(host: &str, port: u16, local_port: Option<u16>) -> PyResult<Self> {
+        Ok(Self(OscSender::new((host, port), local_port)?))
+    }
+
+    /// Send a message. Arguments are sent as int32 (or int64 if they don't fit), float32, string,
+    /// blob (bytes), boolean, nil (None), or time tag (Timestamp).
+    ///
+    /// Parameters
+    /// ----------
+    /// address : str
+    ///   The OSC address, e.g. "/stimulus/onset".
+    /// *args : int, float, str, bytes, bool, None, or Timestamp
+    ///   The arguments of the message.
+    /// at : Timestamp, optional
+    ///   Send the message in a bundle with this time tag, so that the receiver can schedule it.
+    ///   Otherwise, the message is sent on its own and handled when it arrives.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   When the message was sent.
+    #[pyo3(name = "send")]
+    #[pyo3(signature = (address, *args, at = None))]
+    fn py_send(&mut self, address: &str, args: Vec<PyOscArg>, at: Option<Timestamp>) -> PyResult<Timestamp> {
+        let args = args.into_iter().map(|arg| arg.0).collect();
+        Ok(self.0.send(address, args, at.map(|at| at.timestamp))?.into())
+    }
+
+    /// All messages that were sent, as dictionaries with the "address", the "args", when the
+    /// message was sent ("timestamp"), and its "time_tag" (or None).
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log
+            .iter()
+            .map(|message| message_to_py(py, &message.address, &message.args, message.timestamp, message.time_tag))
+            .collect()
+    }
+
+    /// Add all sent messages to a timeline as "osc_out" events, labelled with the address.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for message in &self.0.log {
+            timeline.0.add(TimelineEvent {
+                kind: "osc_out".to_string(),
+                time: message.timestamp,
+                label: Some(message.address.clone()),
+                data: message
+                    .args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| (format!("arg{i}"), format!("{arg:?}")))
+                    .collect(),
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("OscSender(target={})", self.0.target)
+    }
+}
+
+/// Receives Open Sound Control (OSC) messages over UDP on a background thread.
+///
+/// Every message is stored with the time it arrived. Messages in bundles also keep the time tag of
+/// the bundle.
+///
+/// Parameters
+/// ----------
+/// port : int
+///   The port to listen on.
+/// host : str, optional
+///   The address to listen on. Defaults to all interfaces ("0.0.0.0").
+/// window : Window, optional
+///   A window to forward all messages to as "other" events, whose `name` is the address of the
+///   message. See `forward_to`.
+#[cfg(feature = "python")]
+#[pyclass(name = "OscReceiver")]
+pub struct PyOscReceiver(pub OscReceiver);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyOscReceiver {
+    #[new]
+    #[pyo3(signature = (port, host = "0.0.0.0", window = None))]
+    fn __new__(port: u16, host: &str, window: Option<Window>) -> PyResult<Self> {
+        let receiver = OscReceiver::bind((host, port))?;
+        receiver.forward_to(window);
+        Ok(Self(receiver))
+    }
+
+    /// The messages that arrived since the last call, as dictionaries with the "address", the
+    /// "args", when the message arrived ("timestamp"), and the "time_tag" of its bundle (or None).
+    #[pyo3(name = "poll")]
+    fn py_poll<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .poll()
+            .iter()
+            .map(|message| message_to_py(py, &message.address, &message.args, message.timestamp, message.time_tag))
+            .collect()
+    }
+
+    /// Forward all following messages to a window as "other" events, whose `name` is the address
+    /// of the message, so that they can be handled like other input events. Pass None to stop
+    /// forwarding.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window or None
+    ///   The window to forward messages to.
+    #[pyo3(name = "forward_to")]
+    fn py_forward_to(&self, window: Option<Window>) {
+        self.0.forward_to(window);
+    }
+
+    /// All messages that arrived, in the format of `poll`.
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log()
+            .iter()
+            .map(|message| message_to_py(py, &message.address, &message.args, message.timestamp, message.time_tag))
+            .collect()
+    }
+
+    /// Add all received messages to a timeline as "osc_in" events, labelled with the address.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for message in self.0.log() {
+            timeline.0.add(TimelineEvent {
+                kind: "osc_in".to_string(),
+                time: message.timestamp,
+                label: Some(message.address.clone()),
+                data: vec![("sender".to_string(), message.sender.to_string())],
+            });
+        }
+    }
+
+    /// The port the receiver listens on.
+    #[getter(port)]
+    fn py_port(&self) -> u16 {
+        self.0.address.port()
+    }
+
+    /// Stop receiving messages. Messages that already arrived are kept.
+    #[pyo3(name = "close")]
+    fn py_close(&mut self, py: Python) {
+        py.allow_threads(|| self.0.close());
+    }
+
+    fn __repr__(&self) -> String {
+        format!("OscReceiver(address={})", self.0.address)
+    }
+}
@@ -0,0 +1,499 @@
+//! Relay boards for reward delivery (juice pumps, feeders) and other devices that are switched on
+//! for a limited time.
+//!
+//! Relays are only switched on through `pulse`, which switches them off again on a background
+//! thread. Safety limits are checked before a relay is switched on: the duration of each pulse
+//! is capped, pulses on the same channel can be required to be a minimum interval apart, and the
+//! total time all relays are on can be capped for the whole session (e.g. the maximum amount of
+//! juice). A pulse that would exceed a limit is refused with an error rather than shortened.
+//!
+//! Relays are switched off when the last handle to a board is dropped, at the end of each run,
+//! and before the process exits because the window was closed or Escape was pressed. If a relay
+//! cannot be switched off, further pulses are refused until `all_off` succeeds.
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use serialport::SerialPort;
+
+#[cfg(feature = "python")]
+use super::arduino::PyArduino;
+use super::arduino::{Arduino, PinMode};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    app::{register_shutdown_hook, ShutdownHook},
+    errors::{PsydkError, PsydkResult},
+    time::{wait_until, TimelineEvent, Timestamp},
+};
+
+/// How often switching a relay off is attempted before giving up.
+const OFF_ATTEMPTS: u32 = 3;
+
+/// The time between attempts to switch a relay off.
+const OFF_RETRY_DELAY: Duration = Duration::from_millis(5);
+
+/// Switches the relays of a board.
+pub trait RelayDriver: Send {
+    /// Switch the relay with the given index (starting at 0) on or off.
+    fn set(&mut self, channel: usize, on: bool) -> PsydkResult<()>;
+}
+
+/// The USB relay boards based on a CH340 serial chip (often sold as "LCUS"), which are switched
+/// with 4-byte commands: 0xA0, the channel (starting at 1), the state, and a checksum.
+pub struct LcusDriver(Box<dyn SerialPort>);
+
+impl LcusDriver {
+    pub fn open(port: &str, baud_rate: u32) -> PsydkResult<Self> {
+        let port = serialport::new(port, baud_rate)
+            .open()
+            .map_err(|e| PsydkError::IOError(e.into()))?;
+        Ok(Self(port))
+    }
+}
+
+impl RelayDriver for LcusDriver {
+    fn set(&mut self, channel: usize, on: bool) -> PsydkResult<()> {
+        let channel = u8::try_from(channel + 1)
+            .map_err(|_| PsydkError::ParameterError(format!("Invalid relay channel {channel}")))?;
+        let command = [0xA0, channel, on as u8];
+        let checksum = command.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        self.0.write_all(&[command[0], command[1], command[2], checksum])?;
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+/// Relays (or solid state switches) connected to the digital pins of an Arduino running
+/// StandardFirmata.
+pub struct ArduinoDriver {
+    arduino: Arduino,
+    pins: Vec<u8>,
+}
+
+impl ArduinoDriver {
+    pub fn new(arduino: Arduino, pins: Vec<u8>) -> PsydkResult<Self> {
+        for pin in &pins {
+            arduino.set_pin_mode(*pin, PinMode::Output)?;
+            arduino.digital_write(*pin, false)?;
+        }
+        Ok(Self { arduino, pins })
+    }
+}
+
+impl RelayDriver for ArduinoDriver {
+    fn set(&mut self, channel: usize, on: bool) -> PsydkResult<()> {
+        self.arduino.digital_write(self.pins[channel], on)?;
+        Ok(())
+    }
+}
+
+/// Safety limits of a relay board.
+#[derive(Debug, Clone, Copy)]
+pub struct RelayLimits {
+    /// The maximum duration of a single pulse.
+    pub max_duration: Duration,
+    /// The minimum time between the end of a pulse and the start of the next pulse on the same
+    /// channel.
+    pub min_interval: Duration,
+    /// The maximum total time all channels may be on during the session.
+    pub max_total: Option<Duration>,
+}
+
+/// A pulse that has been delivered (or is being delivered).
+#[derive(Debug, Clone)]
+pub struct RelayPulse {
+    pub channel: usize,
+    pub duration: Duration,
+    /// The time the relay was switched on.
+    pub onset: Instant,
+    /// The time the relay was switched off, once it has been.
+    pub offset: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct RelayState {
+    /// The end of the current or last pulse of each channel.
+    busy_until: Vec<Option<Instant>>,
+    /// The index in the log of the pulse that is running on each channel.
+    active: Vec<Option<usize>>,
+    /// The total requested on-time of all pulses.
+    total: Duration,
+    log: Vec<RelayPulse>,
+    /// Why a relay could not be switched off, if it could not. Pulses are refused until all
+    /// relays have been switched off successfully.
+    failure: Option<String>,
+}
+
+/// The driver of a board, which switches all relays off when it is dropped.
+struct Relays {
+    driver: Box<dyn RelayDriver>,
+    channels: usize,
+    /// Switches the relays off before the process exits, as long as the board exists.
+    shutdown_hook: Option<ShutdownHook>,
+}
+
+impl Relays {
+    /// Switch every relay off, trying every channel even if one fails, and report the first error.
+    fn all_off(&mut self) -> PsydkResult<()> {
+        let mut result = Ok(());
+        for channel in 0..self.channels {
+            result = result.and(self.driver.set(channel, false));
+        }
+        result
+    }
+
+    /// Switch a relay off, retrying a few times if it fails.
+    fn switch_off(&mut self, channel: usize) -> PsydkResult<()> {
+        let mut attempt = 1;
+        loop {
+            match self.driver.set(channel, false) {
+                Err(e) if attempt < OFF_ATTEMPTS => {
+                    log::warn!("Failed to switch off relay channel {channel} (attempt {attempt}): {e}");
+                    std::thread::sleep(OFF_RETRY_DELAY);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for Relays {
+    fn drop(&mut self) {
+        if let Err(e) = self.all_off() {
+            log::error!("Failed to switch off the relays: {}", e);
+        }
+    }
+}
+
+/// A relay board with safety limits and a log of all pulses.
+#[derive(Clone)]
+pub struct RelayBoard {
+    relays: Arc<Mutex<Relays>>,
+    channels: usize,
+    pub limits: RelayLimits,
+    state: Arc<Mutex<RelayState>>,
+}
+
+impl std::fmt::Debug for RelayBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayBoard")
+            .field("channels", &self.channels)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RelayBoard {
+    /// Create a board with `channels` relays. All relays are switched off.
+    pub fn new(driver: impl RelayDriver + 'static, channels: usize, limits: RelayLimits) -> PsydkResult<Self> {
+        let mut relays = Relays {
+            driver: Box::new(driver),
+            channels,
+            shutdown_hook: None,
+        };
+        relays.all_off()?;
+        let board = Self {
+            relays: Arc::new(Mutex::new(relays)),
+            channels,
+            limits,
+            state: Arc::new(Mutex::new(RelayState {
+                busy_until: vec![None; channels],
+                active: vec![None; channels],
+                total: Duration::ZERO,
+                log: Vec::new(),
+                failure: None,
+            })),
+        };
+
+        // switch the relays off before the process exits, which would stop pulses midway
+        let weak = (Arc::downgrade(&board.relays), Arc::downgrade(&board.state));
+        let shutdown_hook = register_shutdown_hook(move || {
+            if let (Some(relays), Some(state)) = (weak.0.upgrade(), weak.1.upgrade()) {
+                let board = RelayBoard {
+                    relays,
+                    channels,
+                    limits,
+                    state,
+                };
+                if let Err(e) = board.all_off() {
+                    log::error!("Failed to switch off the relays: {}", e);
+                }
+            }
+        });
+        board.relays.lock().unwrap().shutdown_hook = Some(shutdown_hook);
+
+        Ok(board)
+    }
+
+    /// Switch a relay on for `duration`. Returns the time it was switched on; it is switched off
+    /// again on a background thread.
+    pub fn pulse(&self, channel: usize, duration: Duration) -> PsydkResult<Instant> {
+        if channel >= self.channels {
+            return Err(PsydkError::ParameterError(format!(
+                "Invalid relay channel {channel}, the board has {} channels",
+                self.channels
+            )));
+        }
+        if duration > self.limits.max_duration {
+            return Err(PsydkError::ParameterError(format!(
+                "Pulse of {:.3} s exceeds the maximum duration of {:.3} s",
+                duration.as_secs_f64(),
+                self.limits.max_duration.as_secs_f64()
+            )));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(failure) = &state.failure {
+            return Err(PsydkError::CustomError(format!(
+                "Refusing to switch on a relay, as a relay could not be switched off ({failure}). Call \
+                 all_off to try again."
+            )));
+        }
+        let now = Instant::now();
+        if let Some(busy_until) = state.busy_until[channel] {
+            if now < busy_until + self.limits.min_interval {
+                return Err(PsydkError::CustomError(format!(
+                    "Relay channel {channel} is still on or was switched off less than {:.3} s ago",
+                    self.limits.min_interval.as_secs_f64()
+                )));
+            }
+        }
+        if let Some(max_total) = self.limits.max_total {
+            if state.total + duration > max_total {
+                return Err(PsydkError::CustomError(format!(
+                    "Pulse of {:.3} s exceeds the remaining total of {:.3} s",
+                    duration.as_secs_f64(),
+                    max_total.saturating_sub(state.total).as_secs_f64()
+                )));
+            }
+        }
+
+        self.relays.lock().unwrap().driver.set(channel, true)?;
+        let onset = Instant::now();
+        state.busy_until[channel] = Some(onset + duration);
+        state.total += duration;
+        state.log.push(RelayPulse {
+            channel,
+            duration,
+            onset,
+            offset: None,
+        });
+        let index = state.log.len() - 1;
+        state.active[channel] = Some(index);
+        drop(state);
+
+        let board = self.clone();
+        std::thread::spawn(move || {
+            wait_until(onset + duration);
+            let mut state = board.state.lock().unwrap();
+            // the pulse may have been ended early by `all_off`
+            if state.active[channel] != Some(index) {
+                return;
+            }
+            state.active[channel] = None;
+            match board.relays.lock().unwrap().switch_off(channel) {
+                Ok(()) => state.log[index].offset = Some(Instant::now()),
+                Err(e) => {
+                    log::error!("Failed to switch off relay channel {}: {}", channel, e);
+                    state.failure = Some(format!("channel {channel}: {e}"));
+                }
+            }
+        });
+
+        Ok(onset)
+    }
+
+    /// Switch all relays off immediately, e.g. in an emergency. Pulses that are running end now.
+    pub fn all_off(&self) -> PsydkResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let result = self.relays.lock().unwrap().all_off();
+        if result.is_ok() {
+            state.failure = None;
+        }
+
+        let now = Instant::now();
+        for busy_until in state.busy_until.iter_mut().flatten() {
+            *busy_until = (*busy_until).min(now);
+        }
+        for index in std::mem::replace(&mut state.active, vec![None; self.channels])
+            .into_iter()
+            .flatten()
+        {
+            state.log[index].offset = Some(now);
+        }
+        result
+    }
+
+    /// The total requested on-time of all pulses.
+    pub fn total(&self) -> Duration {
+        self.state.lock().unwrap().total
+    }
+
+    pub fn log(&self) -> Vec<RelayPulse> {
+        self.state.lock().unwrap().log.clone()
+    }
+}
+
+/// A relay board for reward delivery (juice pumps, feeders) or other devices that are switched
+/// on for a limited time. Requires the `serial` feature.
+///
+/// Relays can only be switched on with `pulse`, and safety limits are enforced: pulses longer
+/// than `max_duration`, pulses on a channel less than `min_interval` after the previous pulse
+/// ended, and pulses that would exceed `max_total` for the session are refused with an error.
+/// All relays are switched off when the board is opened, when `all_off` is called, when the board
+/// is no longer used, at the end of the experiment, and before psydk exits when the window is
+/// closed or Escape is pressed. If a relay cannot be switched off, `pulse` raises an error until
+/// `all_off` succeeds. Every pulse is logged.
+///
+/// Parameters
+/// ----------
+/// port : str, optional
+///   The serial port of a USB relay board with a CH340 chip ("LCUS" boards), e.g.
+///   "/dev/ttyUSB0" or "COM3".
+/// channels : int, optional
+///   The number of relays of the USB relay board. Defaults to 1.
+/// baud_rate : int, optional
+///   The baud rate of the USB relay board. Defaults to 9600.
+/// arduino : Arduino, optional
+///   Instead of a USB relay board, use relays connected to the digital pins of an Arduino.
+/// pins : list[int], optional
+///   The pins of the Arduino, one per channel.
+/// max_duration : float, optional
+///   The maximum duration of a pulse in seconds. Defaults to 1.
+/// min_interval : float, optional
+///   The minimum time in seconds between pulses on the same channel. Defaults to 0.
+/// max_total : float, optional
+///   The maximum total time in seconds that relays may be on during the session.
+#[cfg(feature = "python")]
+#[pyclass(name = "RelayBoard")]
+#[derive(Debug, Clone)]
+pub struct PyRelayBoard(pub RelayBoard);
+
+fn to_duration(name: &str, seconds: f64) -> PsydkResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| PsydkError::ParameterError(format!("Invalid {name} {seconds}, must be non-negative")))
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyRelayBoard {
+    #[new]
+    #[pyo3(signature = (port = None, channels = 1, baud_rate = 9600, arduino = None, pins = None, max_duration = 1.0, min_interval = 0.0, max_total = None))]
+    fn __new__(
+        port: Option<&str>,
+        channels: usize,
+        baud_rate: u32,
+        arduino: Option<PyRef<PyArduino>>,
+        pins: Option<Vec<u8>>,
+        max_duration: f64,
+        min_interval: f64,
+        max_total: Option<f64>,
+    ) -> PyResult<Self> {
+        let limits = RelayLimits {
+            max_duration: to_duration("maximum duration", max_duration)?,
+            min_interval: to_duration("minimum interval", min_interval)?,
+            max_total: max_total.map(|t| to_duration("maximum total", t)).transpose()?,
+        };
+
+        let board = match (port, arduino) {
+            (Some(port), None) => RelayBoard::new(LcusDriver::open(port, baud_rate)?, channels, limits)?,
+            (None, Some(arduino)) => {
+                let pins = pins
+                    .filter(|pins| !pins.is_empty())
+                    .ok_or_else(|| PsydkError::ParameterError("The pins of the Arduino must be given".into()))?;
+                let channels = pins.len();
+                RelayBoard::new(ArduinoDriver::new(arduino.0.clone(), pins)?, channels, limits)?
+            }
+            _ => {
+                return Err(
+                    PsydkError::ParameterError("Either a serial port or an Arduino must be given".into()).into(),
+                )
+            }
+        };
+        Ok(Self(board))
+    }
+
+    /// Switch a relay on for the given duration, e.g. to deliver a reward. Returns immediately;
+    /// the relay is switched off on a background thread.
+    ///
+    /// Parameters
+    /// ----------
+    /// channel : int
+    ///   The relay, starting at 0.
+    /// duration : float
+    ///   How long the relay stays on in seconds.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The time the relay was switched on.
+    #[pyo3(name = "pulse")]
+    fn py_pulse(&self, channel: usize, duration: f64) -> PyResult<Timestamp> {
+        let duration = to_duration("duration", duration)?;
+        Ok(self.0.pulse(channel, duration)?.into())
+    }
+
+    /// Switch all relays off immediately.
+    #[pyo3(name = "all_off")]
+    fn py_all_off(&self) -> PyResult<()> {
+        Ok(self.0.all_off()?)
+    }
+
+    /// The total requested duration of all pulses in seconds, which counts towards `max_total`.
+    #[getter(total)]
+    fn py_total(&self) -> f64 {
+        self.0.total().as_secs_f64()
+    }
+
+    /// All pulses, as dictionaries with the channel, the requested duration, the onset, and the
+    /// offset (None while the relay is on).
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log()
+            .iter()
+            .map(|pulse| {
+                let dict = PyDict::new(py);
+                dict.set_item("channel", pulse.channel)?;
+                dict.set_item("duration", pulse.duration.as_secs_f64())?;
+                dict.set_item("onset", Timestamp::from(pulse.onset))?;
+                dict.set_item("offset", pulse.offset.map(Timestamp::from))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Add all pulses to a timeline as "relay_pulse" events, labelled with the channel.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for pulse in self.0.log() {
+            timeline.0.add(TimelineEvent {
+                kind: "relay_pulse".to_string(),
+                time: pulse.onset,
+                label: Some(pulse.channel.to_string()),
+                data: vec![("duration".to_string(), pulse.duration.as_secs_f64().to_string())],
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RelayBoard(channels={}, pulses={}, total={:.3})",
+            self.0.channels,
+            self.0.log().len(),
+            self.0.total().as_secs_f64()
+        )
+    }
+}
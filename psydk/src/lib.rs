@@ -7,9 +7,12 @@ use std::{
 };
 
 use async_channel::{bounded, Receiver, Sender};
-use context::{py_run_experiment, ExperimentContext};
+use context::ExperimentContext;
+#[cfg(feature = "python")]
+use context::{py_preview, py_run_experiment};
 use derive_debug::Dbg;
 use futures_lite::{future::block_on, Future};
+#[cfg(feature = "python")]
 use pyo3::{prelude::*, py_run};
 use renderer::wgpu_renderer;
 use visual::geometry::Size;
@@ -24,10 +27,20 @@ use crate::input::{Event, EventHandlingExt, EventKind, EventTryFrom};
 
 pub mod app;
 pub mod audio;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cli;
 pub mod config;
+pub mod counterbalance;
+pub mod design;
 pub mod errors;
 pub mod git;
 pub mod input;
+pub mod io;
+#[cfg(feature = "python")]
+pub mod plugins;
+#[cfg(feature = "remote")]
+pub mod remote;
 pub mod time;
 pub mod utils;
 pub mod visual;
@@ -42,6 +55,7 @@ pub(crate) type RenderThreadChannelPayload = Box<dyn FnOnce() -> Pin<Box<dyn Fut
 
 use std::thread;
 
+#[cfg(feature = "python")]
 use pyo3::types::{PyDict, PyList, PyTuple, PyType};
 
 use crate::visual::window::Frame;
@@ -49,6 +63,7 @@ use crate::visual::window::Frame;
 // macro that adds a sub-module to the current module
 // example usage:
 //
+#[cfg(feature = "python")]
 macro_rules! new_submodule {
     ($supermodule:ident, $supermodule_name:literal, $name:literal) => {{
         let m = PyModule::new($supermodule.py(), $name)?;
@@ -63,10 +78,24 @@ macro_rules! new_submodule {
 }
 
 /// This module is implemented in Rust.
+#[cfg(feature = "python")]
 #[pymodule]
 fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(py_run_experiment, m)?);
+    m.add_function(wrap_pyfunction!(py_preview, m)?)?;
+    m.add_function(wrap_pyfunction!(cli::py_cli_main, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins::py_register_stimulus, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins::py_register_device, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins::py_create_stimulus, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins::py_load_plugins, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins::py_registered_plugins, m)?)?;
     m.add_class::<ExperimentContext>()?;
+    m.add_class::<design::PyExperimentDescription>()?;
+    m.add_class::<counterbalance::PyDesign>()?;
+    m.add_function(wrap_pyfunction!(counterbalance::py_latin_square, m)?)?;
+    #[cfg(feature = "remote")]
+    m.add_class::<remote::PyControlServer>()?;
+    m.add("DisplayLost", m.py().get_type::<errors::DisplayLost>())?;
 
     let m_visual = {
         let m = new_submodule!(m, "psydk", "visual");
@@ -74,9 +103,19 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
         let m_stimuli = {
             let m = new_submodule!(m, "psydk.visual", "stimuli");
             m.add_class::<visual::stimuli::PyStimulus>()?;
+            m.add_class::<visual::stimuli::clock::PyClockStimulus>()?;
+            m.add_class::<visual::stimuli::form::PyFormStimulus>()?;
             m.add_class::<visual::stimuli::gabor::PyGaborStimulus>()?;
+            m.add_class::<visual::stimuli::group::PyStimulusGroup>()?;
             m.add_class::<visual::stimuli::image::PyImageStimulus>()?;
+            m.add_class::<visual::stimuli::keypad::PyKeypadStimulus>()?;
+            m.add_class::<visual::stimuli::likert::PyLikertStimulus>()?;
+            m.add_class::<visual::stimuli::noise::PyNoiseStimulus>()?;
             m.add_class::<visual::stimuli::pattern::PyPatternStimulus>()?;
+            m.add_class::<visual::stimuli::rich_text::PyRichTextStimulus>()?;
+            m.add_class::<visual::stimuli::scroll_view::PyScrollViewStimulus>()?;
+            m.add_class::<visual::stimuli::shape::PyShapeStimulus>()?;
+            m.add_class::<visual::stimuli::tagged::PyTaggedStimulus>()?;
             m.add_class::<visual::stimuli::text::PyTextStimulus>()?;
             m.add_class::<visual::stimuli::video::PyVideoStimulus>()?;
             m
@@ -92,6 +131,9 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
             m.add_function(wrap_pyfunction!(visual::geometry::px, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::vw, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::vh, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::height, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::nw, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::nh, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::deg, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::mm, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::cm, &m)?)?;
@@ -103,6 +145,9 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
             m.add_function(wrap_pyfunction!(visual::geometry::line, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::polygon, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::geometry::path, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::grid, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::hex_grid, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::geometry::hex_rings, &m)?)?;
 
             m
         };
@@ -113,11 +158,45 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
             let m = new_submodule!(m, "psydk.visual", "color");
             m.add_function(wrap_pyfunction!(visual::color::py_rgb, &m)?)?;
             m.add_function(wrap_pyfunction!(visual::color::py_linrgb, &m)?)?;
+            m.add_function(wrap_pyfunction!(visual::isoluminance::py_flicker_photometry, &m)?)?;
+            m.add_class::<visual::isoluminance::IsoluminancePoint>()?;
+            m.add_function(wrap_pyfunction!(visual::contrast::py_contrast_staircase, &m)?)?;
+            m.add_class::<visual::contrast::ContrastThreshold>()?;
             m
         };
 
         m.add_submodule(&m_color)?;
         m.add_class::<visual::window::Window>()?;
+        m.add_class::<visual::cursor::PyCursor>()?;
+        m.add_class::<input::keyboard::KeyboardState>()?;
+        m.add_class::<input::scanner::PyScannerSync>()?;
+        m.add_class::<input::sampler::PyContinuousSampler>()?;
+        m.add_class::<input::trajectory::PyMouseTrajectoryRecorder>()?;
+        m.add_class::<input::text::PyTextInput>()?;
+        m.add_class::<input::head::PyHeadTracker>()?;
+        m.add_class::<input::deadline::PyResponseDeadline>()?;
+        #[cfg(feature = "pupil")]
+        m.add_class::<input::pupil::PyPupilTracker>()?;
+        #[cfg(feature = "gamepad")]
+        m.add_class::<input::rumble::PyGamepadRumble>()?;
+        m.add_class::<input::simulation::PySimulatedParticipant>()?;
+        #[cfg(feature = "midi")]
+        m.add_class::<input::midi::PyMidiInput>()?;
+        #[cfg(feature = "midi")]
+        m.add_class::<input::midi::PyMidiOutput>()?;
+        m.add_class::<plugins::PyAttachedDevice>()?;
+        m.add_class::<visual::report::PresentationReport>()?;
+        m.add_class::<visual::report::SequenceReport>()?;
+        m.add_class::<visual::report::FrameMeasurement>()?;
+        m.add_class::<visual::report::RefreshMeasurement>()?;
+        m.add_class::<visual::sequence::Sequence>()?;
+        m.add_class::<visual::scheduler::PyScheduler>()?;
+        m.add_class::<visual::breaks::PyBreakScreen>()?;
+        m.add_class::<visual::scheduler::ScheduleReport>()?;
+        m.add_class::<visual::aoi::PyAreaOfInterest>()?;
+        m.add_function(wrap_pyfunction!(visual::calibration::py_calibrate_eye_tracker, &m)?)?;
+        m.add_class::<visual::calibration::CalibrationReport>()?;
+        m.add_class::<visual::calibration::ValidationPoint>()?;
 
         m
     };
@@ -127,13 +206,20 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
     let m_audio = {
         let m = new_submodule!(m, "psydk", "audio");
         m.add_class::<audio::PyStream>()?;
+        m.add_class::<audio::PyPlaybackHandle>()?;
+        m.add_class::<audio::PyInputStream>()?;
         m.add_class::<audio::PyDevice>()?;
         m.add_class::<audio::PyHost>()?;
         m.add_class::<audio::PyAudioObject>()?;
+        m.add_class::<audio::scheduler::PyAudioScheduler>()?;
+        m.add_class::<audio::scheduler::AudioScheduleReport>()?;
+        m.add_function(wrap_pyfunction!(audio::py_available_hosts, &m)?)?;
         m.add_function(wrap_pyfunction!(audio::py_create_silence, &m)?)?;
         m.add_function(wrap_pyfunction!(audio::py_create_sine_wave, &m)?)?;
         m.add_function(wrap_pyfunction!(audio::py_create_white_noise, &m)?)?;
         m.add_function(wrap_pyfunction!(audio::py_create_from_samples, &m)?)?;
+        m.add_function(wrap_pyfunction!(audio::py_create_tone, &m)?)?;
+        m.add_function(wrap_pyfunction!(audio::py_create_noise, &m)?)?;
         m
     };
 
@@ -142,7 +228,10 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
     let m_time = {
         let m = new_submodule!(m, "psydk", "time");
         m.add_class::<time::Timestamp>()?;
+        m.add_class::<time::PyTimeline>()?;
         m.add_function(wrap_pyfunction!(time::py_now, &m)?)?;
+        m.add_function(wrap_pyfunction!(time::py_wait, &m)?)?;
+        m.add_function(wrap_pyfunction!(time::py_wait_until, &m)?)?;
         m
     };
 
@@ -151,11 +240,34 @@ fn psydk(m: &Bound<'_, PyModule>) -> PyResult<()> {
     let m_utils = {
         let m = new_submodule!(m, "psydk", "utils");
         m.add_class::<utils::PyCSVWriter>()?;
+        m.add_class::<utils::PyAudioRecorder>()?;
+        m.add_class::<utils::markers::PyMarkers>()?;
+        m.add_function(wrap_pyfunction!(utils::manifest::py_write_manifest, &m)?)?;
+        #[cfg(feature = "sqlite")]
+        m.add_class::<utils::sqlite::PySqliteWriter>()?;
+        #[cfg(feature = "upload")]
+        m.add_class::<utils::upload::PyUploader>()?;
         m.add_function(wrap_pyfunction!(time::py_now, &m)?)?;
         m
     };
 
     m.add_submodule(&m_utils)?;
 
+    let m_io = {
+        let m = new_submodule!(m, "psydk", "io");
+        #[cfg(feature = "serial")]
+        m.add_class::<io::arduino::PyArduino>()?;
+        m.add_class::<io::bio::PyBioInlet>()?;
+        #[cfg(feature = "osc")]
+        m.add_class::<io::osc::PyOscSender>()?;
+        #[cfg(feature = "osc")]
+        m.add_class::<io::osc::PyOscReceiver>()?;
+        #[cfg(feature = "serial")]
+        m.add_class::<io::relay::PyRelayBoard>()?;
+        m
+    };
+
+    m.add_submodule(&m_io)?;
+
     Ok(())
 }
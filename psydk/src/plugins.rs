@@ -0,0 +1,497 @@
+//! A registry of stimuli and input devices that are provided by other crates or Python packages.
+//!
+//! Stimuli are registered as factories that create a `DynamicStimulus` from named parameters, so
+//! they can be used from Python (`create_stimulus`), in experiment descriptions (as the `type` of a
+//! stimulus), and added to frames like the built-in stimuli. Input devices are polled on a
+//! background thread once they are attached to a window, and their events are delivered to the
+//! window as if they had come from the event loop.
+//!
+//! Rust crates register their factories with `register_stimulus` and `register_device` before the
+//! experiment is run. Python packages declare an entry point in the `psydk.plugins` group, which
+//! points to a function that is called without arguments and registers the package's factories
+//! with `psydk.register_stimulus` and `psydk.register_device`:
+//!
+//! ```toml
+//! [project.entry-points."psydk.plugins"]
+//! my_plugin = "my_plugin:register"
+//! ```
+//!
+//! Entry points are loaded the first time a stimulus or device is created from Python or from an
+//! experiment description, or explicitly with `load_plugins`.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyString, PyTuple},
+};
+
+use crate::{
+    context::ExperimentContext,
+    design::ParamValue,
+    errors::{PsydkError, PsydkResult},
+    input::Event,
+    time::Timestamp,
+    visual::{
+        stimuli::{DynamicStimulus, PyStimulus},
+        window::Window,
+    },
+};
+
+/// The entry point group that Python plugins are discovered in.
+pub const ENTRY_POINT_GROUP: &str = "psydk.plugins";
+
+/// How long a device waits for events before checking whether it should stop.
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Creates a stimulus from its parameters.
+pub type StimulusFactory =
+    dyn Fn(&ExperimentContext, &BTreeMap<String, ParamValue>) -> PsydkResult<DynamicStimulus> + Send + Sync;
+
+/// Creates an input device from its parameters.
+pub type DeviceFactory = dyn Fn(&BTreeMap<String, ParamValue>) -> PsydkResult<Box<dyn InputDevice>> + Send + Sync;
+
+/// A source of input events, e.g. a response box or an eye tracker.
+pub trait InputDevice: Send {
+    /// Wait at most `timeout` for new events and return them. Devices that report buttons should
+    /// report them as key presses and releases, so that they can be used as responses like keys.
+    /// Other events can be reported as `Event::Other`.
+    fn poll(&mut self, timeout: Duration) -> PsydkResult<Vec<Event>>;
+
+    /// Called when the device is detached from the window.
+    fn close(&mut self) {}
+}
+
+/// A factory, implemented either in Rust or in Python.
+enum Factory<F: ?Sized> {
+    Native(Arc<F>),
+    Python(Arc<Py<PyAny>>),
+}
+
+impl<F: ?Sized> Clone for Factory<F> {
+    fn clone(&self) -> Self {
+        match self {
+            Factory::Native(factory) => Factory::Native(factory.clone()),
+            Factory::Python(factory) => Factory::Python(factory.clone()),
+        }
+    }
+}
+
+static STIMULI: Mutex<BTreeMap<String, Factory<StimulusFactory>>> = Mutex::new(BTreeMap::new());
+static DEVICES: Mutex<BTreeMap<String, Factory<DeviceFactory>>> = Mutex::new(BTreeMap::new());
+static PLUGINS_LOADED: AtomicBool = AtomicBool::new(false);
+
+/// Register a stimulus factory under `name`, replacing any factory of the same name.
+pub fn register_stimulus(
+    name: &str,
+    factory: impl Fn(&ExperimentContext, &BTreeMap<String, ParamValue>) -> PsydkResult<DynamicStimulus>
+        + Send
+        + Sync
+        + 'static,
+) {
+    STIMULI
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Factory::Native(Arc::new(factory)));
+}
+
+/// Register an input device factory under `name`, replacing any factory of the same name.
+pub fn register_device(
+    name: &str,
+    factory: impl Fn(&BTreeMap<String, ParamValue>) -> PsydkResult<Box<dyn InputDevice>> + Send + Sync + 'static,
+) {
+    DEVICES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Factory::Native(Arc::new(factory)));
+}
+
+/// The names of all registered stimuli.
+pub fn registered_stimuli() -> Vec<String> {
+    STIMULI.lock().unwrap().keys().cloned().collect()
+}
+
+/// The names of all registered devices.
+pub fn registered_devices() -> Vec<String> {
+    DEVICES.lock().unwrap().keys().cloned().collect()
+}
+
+/// Call the registration functions of all Python plugins, returning the names of the plugins.
+/// Plugins are only loaded once per process; later calls return an empty list. A plugin that fails
+/// to load is logged and skipped, so that a broken package doesn't prevent experiments from running.
+pub fn load_plugins(py: Python) -> PyResult<Vec<String>> {
+    if PLUGINS_LOADED.swap(true, Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("group", ENTRY_POINT_GROUP)?;
+    let entry_points = py
+        .import("importlib.metadata")?
+        .call_method("entry_points", (), Some(&kwargs))?;
+
+    let mut loaded = Vec::new();
+    for entry_point in entry_points.try_iter()? {
+        let entry_point = entry_point?;
+        let name: String = entry_point.getattr("name")?.extract()?;
+        match entry_point.call_method0("load").and_then(|register| register.call0()) {
+            Ok(_) => loaded.push(name),
+            Err(e) => log::error!("Failed to load the psydk plugin \"{}\": {}", name, e),
+        }
+    }
+    Ok(loaded)
+}
+
+fn lookup<F: ?Sized>(registry: &Mutex<BTreeMap<String, Factory<F>>>, name: &str) -> Option<Factory<F>> {
+    registry.lock().unwrap().get(name).cloned()
+}
+
+/// Whether a stimulus factory is registered under `name`.
+pub fn has_stimulus(name: &str) -> bool {
+    lookup(&STIMULI, name).is_some()
+}
+
+/// Create a stimulus with the factory registered under `name`.
+pub fn create_stimulus(
+    name: &str,
+    context: &ExperimentContext,
+    params: &BTreeMap<String, ParamValue>,
+) -> PsydkResult<DynamicStimulus> {
+    match lookup(&STIMULI, name) {
+        Some(Factory::Native(factory)) => factory(context, params),
+        Some(Factory::Python(factory)) => Python::with_gil(|py| {
+            let kwargs = PyDict::new(py);
+            for (key, value) in params {
+                kwargs.set_item(key, value.to_py(py)?)?;
+            }
+            let stimulus = with_context(py, context, || factory.bind(py).call((), Some(&kwargs)))?;
+            let stimulus: PyStimulus = stimulus.extract().map_err(|_| {
+                PsydkError::ParameterError(format!("The factory of stimulus \"{name}\" did not return a stimulus"))
+            })?;
+            Ok(stimulus.as_super().clone())
+        }),
+        None => Err(PsydkError::ParameterError(format!(
+            "No stimulus is registered as \"{name}\""
+        ))),
+    }
+}
+
+/// Create an input device with the factory registered under `name`.
+pub fn create_device(name: &str, params: &BTreeMap<String, ParamValue>) -> PsydkResult<Box<dyn InputDevice>> {
+    match lookup(&DEVICES, name) {
+        Some(Factory::Native(factory)) => factory(params),
+        Some(Factory::Python(factory)) => Python::with_gil(|py| {
+            let kwargs = PyDict::new(py);
+            for (key, value) in params {
+                kwargs.set_item(key, value.to_py(py)?)?;
+            }
+            let device = factory.bind(py).call((), Some(&kwargs))?;
+            Ok(Box::new(PythonDevice(device.unbind())) as Box<dyn InputDevice>)
+        }),
+        None => Err(PsydkError::ParameterError(format!(
+            "No device is registered as \"{name}\""
+        ))),
+    }
+}
+
+/// Run `f` with `_experiment_context` set in `__main__`, as stimuli look up the context there.
+fn with_context<'py, R>(py: Python<'py>, context: &ExperimentContext, f: impl FnOnce() -> PyResult<R>) -> PyResult<R> {
+    let main = py.import("__main__")?;
+    if main.hasattr("_experiment_context")? {
+        return f();
+    }
+    main.setattr("_experiment_context", context.clone())?;
+    let result = f();
+    let _ = main.delattr("_experiment_context");
+    result
+}
+
+/// A device implemented in Python. Its `poll()` method is called without arguments and returns the
+/// new events, each either a key name (a key press at the time of polling) or a tuple of the key
+/// name, whether the key was pressed (True) or released (False), and optionally the `Timestamp` of
+/// the event.
+struct PythonDevice(Py<PyAny>);
+
+impl InputDevice for PythonDevice {
+    fn poll(&mut self, timeout: Duration) -> PsydkResult<Vec<Event>> {
+        let events = Python::with_gil(|py| -> PyResult<Vec<Event>> {
+            let now = Instant::now();
+            let mut events = Vec::new();
+            for item in self.0.bind(py).call_method0("poll")?.try_iter()? {
+                let item = item?;
+                let (key, pressed, timestamp) = if item.is_instance_of::<PyString>() {
+                    (item.extract::<String>()?, true, None)
+                } else if item.is_instance_of::<PyTuple>() {
+                    let tuple = item.downcast::<PyTuple>()?;
+                    let timestamp = match tuple.len() {
+                        2 => None,
+                        _ => Some(tuple.get_item(2)?.extract::<Timestamp>()?),
+                    };
+                    (tuple.get_item(0)?.extract()?, tuple.get_item(1)?.extract()?, timestamp)
+                } else {
+                    return Err(PsydkError::ParameterError(format!("Invalid device event: {item}")).into());
+                };
+
+                let timestamp = timestamp.unwrap_or_else(|| now.into());
+                events.push(match pressed {
+                    true => Event::KeyPress {
+                        timestamp,
+                        key,
+                        code: 0,
+                    },
+                    false => Event::KeyRelease {
+                        timestamp,
+                        key,
+                        code: 0,
+                    },
+                });
+            }
+            Ok(events)
+        })?;
+
+        // Python devices return immediately, so wait a little when there were no events
+        if events.is_empty() {
+            std::thread::sleep(timeout.min(Duration::from_millis(1)));
+        }
+        Ok(events)
+    }
+
+    fn close(&mut self) {
+        Python::with_gil(|py| {
+            let device = self.0.bind(py);
+            if device.hasattr("close").unwrap_or(false) {
+                if let Err(e) = device.call_method0("close") {
+                    log::warn!("Failed to close a device: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// An input device that is attached to a window. The device is polled until it is detached or the
+/// window is closed.
+#[derive(Debug, Clone)]
+pub struct AttachedDevice {
+    name: String,
+    stop: Arc<AtomicBool>,
+}
+
+impl AttachedDevice {
+    /// Poll `device` on a background thread and deliver its events to `window`.
+    pub fn attach(window: &Window, name: &str, mut device: Box<dyn InputDevice>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let window = window.clone();
+        let thread_stop = stop.clone();
+        let thread_name = name.to_string();
+        std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) && window.with_state(|_| ()).is_ok() {
+                match device.poll(POLL_TIMEOUT) {
+                    Ok(events) => events.into_iter().for_each(|event| window.inject_event(event)),
+                    Err(e) => {
+                        log::error!("Failed to poll the device \"{}\": {}", thread_name, e);
+                        break;
+                    }
+                }
+            }
+            device.close();
+        });
+
+        Self {
+            name: name.to_string(),
+            stop,
+        }
+    }
+
+    /// The name the device was registered under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stop polling the device.
+    pub fn detach(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn kwargs_to_params(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<BTreeMap<String, ParamValue>> {
+    let mut params = BTreeMap::new();
+    if let Some(kwargs) = kwargs {
+        for (key, value) in kwargs.iter() {
+            params.insert(key.extract()?, value.extract()?);
+        }
+    }
+    Ok(params)
+}
+
+/// An input device that is attached to a window, see `Window.attach_device`.
+#[pyclass(name = "AttachedDevice")]
+#[derive(Debug, Clone)]
+pub struct PyAttachedDevice(pub AttachedDevice);
+
+#[pymethods]
+impl PyAttachedDevice {
+    /// The name the device was registered under.
+    #[getter]
+    #[pyo3(name = "name")]
+    fn py_name(&self) -> String {
+        self.0.name().to_string()
+    }
+
+    /// Stop polling the device. Events that were already delivered are kept.
+    #[pyo3(name = "detach")]
+    fn py_detach(&self) {
+        self.0.detach();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("AttachedDevice(name={:?})", self.0.name())
+    }
+}
+
+#[pymethods]
+impl Window {
+    /// Attach an input device that was registered with `register_device` (or by a plugin). The
+    /// device is polled on a background thread and its events are delivered to the window like
+    /// keyboard events, so they can be used with event receivers, event handlers, and the scheduler.
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str
+    ///   The name the device was registered under.
+    /// **kwargs
+    ///   Passed to the factory of the device.
+    ///
+    /// Returns
+    /// -------
+    /// AttachedDevice
+    ///   The attached device, which can be detached with `detach()`.
+    #[pyo3(name = "attach_device")]
+    #[pyo3(signature = (name, **kwargs))]
+    fn py_attach_device(
+        &self,
+        py: Python,
+        name: &str,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyAttachedDevice> {
+        load_plugins(py)?;
+        let params = kwargs_to_params(kwargs)?;
+        let device = py.allow_threads(|| create_device(name, &params))?;
+        Ok(PyAttachedDevice(AttachedDevice::attach(self, name, device)))
+    }
+}
+
+/// Register a stimulus, so that it can be created with `create_stimulus` and used in experiment
+/// descriptions.
+///
+/// Parameters
+/// ----------
+/// name : str
+///   The name of the stimulus, e.g. "my_plugin.checkerboard". Replaces any stimulus of the same name.
+/// factory : callable
+///   Called with the parameters of the stimulus as keyword arguments. Must return a stimulus.
+#[pyfunction]
+#[pyo3(name = "register_stimulus")]
+pub fn py_register_stimulus(name: &str, factory: Bound<'_, PyAny>) -> PyResult<()> {
+    if !factory.is_callable() {
+        return Err(PsydkError::ParameterError("The factory of a stimulus must be callable".into()).into());
+    }
+    STIMULI
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Factory::Python(Arc::new(factory.unbind())));
+    Ok(())
+}
+
+/// Register an input device, so that it can be attached to windows with `Window.attach_device`.
+///
+/// Parameters
+/// ----------
+/// name : str
+///   The name of the device. Replaces any device of the same name.
+/// factory : callable
+///   Called with the parameters of the device as keyword arguments. Must return an object with a
+///   `poll()` method that returns the new events, each either a key name (a key press at the time
+///   of polling) or a tuple `(key, pressed)` or `(key, pressed, timestamp)`. `poll()` is called
+///   repeatedly on a background thread and should not block. If the object has a `close()` method,
+///   it is called when the device is detached.
+#[pyfunction]
+#[pyo3(name = "register_device")]
+pub fn py_register_device(name: &str, factory: Bound<'_, PyAny>) -> PyResult<()> {
+    if !factory.is_callable() {
+        return Err(PsydkError::ParameterError("The factory of a device must be callable".into()).into());
+    }
+    DEVICES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Factory::Python(Arc::new(factory.unbind())));
+    Ok(())
+}
+
+/// Create a stimulus that was registered with `register_stimulus` (or by a plugin).
+///
+/// Parameters
+/// ----------
+/// name : str
+///   The name the stimulus was registered under.
+/// context : ExperimentContext, optional
+///   The experiment context. Defaults to the context of the running experiment.
+/// **kwargs
+///   Passed to the factory of the stimulus.
+///
+/// Returns
+/// -------
+/// Stimulus
+///   The new stimulus.
+#[pyfunction]
+#[pyo3(name = "create_stimulus")]
+#[pyo3(signature = (name, context = None, **kwargs))]
+pub fn py_create_stimulus(
+    py: Python,
+    name: &str,
+    context: Option<ExperimentContext>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<PyStimulus> {
+    load_plugins(py)?;
+    let context = crate::visual::stimuli::helpers::get_experiment_context(context, py)?;
+    let params = kwargs_to_params(kwargs)?;
+    let stimulus = py.allow_threads(|| create_stimulus(name, &context, &params))?;
+    Ok(PyStimulus::from(stimulus))
+}
+
+/// Load all plugins that are installed as Python packages, i.e. all entry points in the
+/// `psydk.plugins` group. This happens automatically the first time a stimulus or device is
+/// created, so it only needs to be called to load plugins earlier, e.g. to list what they provide.
+///
+/// Returns
+/// -------
+/// list[str]
+///   The names of the plugins that were loaded by this call.
+#[pyfunction]
+#[pyo3(name = "load_plugins")]
+pub fn py_load_plugins(py: Python) -> PyResult<Vec<String>> {
+    load_plugins(py)
+}
+
+/// The names of all registered stimuli and devices.
+///
+/// Returns
+/// -------
+/// dict[str, list[str]]
+///   The names of the stimuli (key "stimuli") and devices (key "devices").
+#[pyfunction]
+#[pyo3(name = "registered_plugins")]
+pub fn py_registered_plugins(py: Python) -> PyResult<BTreeMap<&'static str, Vec<String>>> {
+    load_plugins(py)?;
+    Ok(BTreeMap::from([
+        ("stimuli", registered_stimuli()),
+        ("devices", registered_devices()),
+    ]))
+}
@@ -0,0 +1,480 @@
+//! A control server that lets other machines (e.g., a scanner control room) start, pause, and
+//! abort an experiment, set parameters, and query its status.
+//!
+//! The protocol is JSON. Requests are objects with a `command` field, e.g.
+//! `{"command": "start"}`, `{"command": "set", "params": {"contrast": 0.5}}`, or
+//! `{"command": "status"}`. Every request is answered with the current status. Clients can connect
+//! either with a WebSocket (one message per request) or with a plain TCP socket (one request per
+//! line).
+//!
+//! If the server was started with a token, every request needs a matching `token` field, e.g.
+//! `{"command": "start", "token": "..."}`. A token is required when the server listens on an
+//! address other than the loopback interface.
+//!
+//! Web pages open in a browser on the same machine can reach the loopback interface as well. To
+//! keep them from controlling the experiment, WebSocket upgrades with an `Origin` header (which
+//! browsers always send) are refused unless the origin has been allowed, and plain TCP
+//! connections that start with an HTTP request are closed.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString},
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use strum::Display;
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse},
+    http::StatusCode,
+    Message,
+};
+
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::{deadline, to_timeout},
+    visual::window::Window,
+};
+
+/// The state of the experiment as seen by the control server.
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum RunState {
+    /// Waiting for a start command.
+    Waiting,
+    Running,
+    Paused,
+    Aborted,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    Start,
+    Pause,
+    Resume,
+    Abort,
+    Set { params: Map<String, Value> },
+    Status,
+}
+
+#[derive(Debug)]
+struct ControlState {
+    run_state: RunState,
+    /// Parameters set by clients.
+    params: Map<String, Value>,
+    /// Status fields set by the experiment, e.g. the current trial.
+    status: Map<String, Value>,
+    /// Windows whose frame statistics are reported, with the last known number of dropped frames.
+    windows: Vec<(Window, usize)>,
+}
+
+/// The control server. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct ControlServer {
+    state: Arc<(Mutex<ControlState>, Condvar)>,
+    address: String,
+    /// The token that clients need to send with every request, if any.
+    token: Option<String>,
+    /// The origins of web pages that may connect with a WebSocket, e.g. "http://localhost:3000".
+    allowed_origins: Vec<String>,
+}
+
+impl ControlServer {
+    /// Start listening on `address` (e.g. "127.0.0.1:8765"). Connections are handled on background
+    /// threads. Unless the server only listens on the loopback interface, clients can reach it
+    /// from the network, so a `token` that they have to send with every request is required.
+    /// WebSocket connections from web pages are only accepted from `allowed_origins`.
+    pub fn start(address: &str, token: Option<String>, allowed_origins: Vec<String>) -> PsydkResult<Self> {
+        if token.as_deref() == Some("") {
+            return Err(PsydkError::ParameterError("The token must not be empty".into()));
+        }
+        let listener = TcpListener::bind(address)?;
+        if token.is_none() && !listener.local_addr()?.ip().is_loopback() {
+            return Err(PsydkError::ParameterError(format!(
+                "The control server would be reachable from the network on {address}, which requires a token"
+            )));
+        }
+        let server = Self {
+            state: Arc::new((
+                Mutex::new(ControlState {
+                    run_state: RunState::Waiting,
+                    params: Map::new(),
+                    status: Map::new(),
+                    windows: Vec::new(),
+                }),
+                Condvar::new(),
+            )),
+            address: listener.local_addr()?.to_string(),
+            token,
+            allowed_origins,
+        };
+
+        let accept_server = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let server = accept_server.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = server.handle_connection(stream) {
+                        log::warn!("Control server connection closed with an error: {}", e);
+                    }
+                });
+            }
+        });
+
+        log::info!("Control server listening on {}", server.address);
+        Ok(server)
+    }
+
+    /// The address the server is listening on.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+        // WebSocket connections start with an HTTP upgrade request
+        let mut prefix = [0u8; 4];
+        let n = stream.peek(&mut prefix)?;
+        if &prefix[..n] == b"GET " {
+            let mut websocket =
+                tungstenite::accept_hdr(stream, |request: &HandshakeRequest, response: HandshakeResponse| {
+                    self.check_origin(request)?;
+                    Ok(response)
+                })?;
+            loop {
+                match websocket.read()? {
+                    Message::Text(text) => websocket.send(Message::Text(self.handle_request(&text)))?,
+                    Message::Close(_) => return Ok(()),
+                    _ => {}
+                }
+            }
+        } else if n == prefix.len() && HTTP_METHODS.contains(&&prefix) {
+            // e.g. a form or `fetch` request from a web page, whose body could hold commands
+            Err(format!("Refused an HTTP request from {}", stream.peer_addr()?).into())
+        } else {
+            let mut writer = stream.try_clone()?;
+            for line in BufReader::new(stream).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                writeln!(writer, "{}", self.handle_request(&line))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Refuse WebSocket upgrades from web pages whose origin has not been allowed. Clients other
+    /// than browsers do not send an `Origin` header.
+    fn check_origin(&self, request: &HandshakeRequest) -> Result<(), ErrorResponse> {
+        let Some(origin) = request.headers().get("origin") else {
+            return Ok(());
+        };
+        let origin = origin.to_str().unwrap_or_default();
+        if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return Ok(());
+        }
+        log::warn!("Refused a WebSocket connection to the control server from {origin}");
+        let mut response = ErrorResponse::new(Some(format!("Origin {origin} is not allowed")));
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        Err(response)
+    }
+
+    /// Handle a single JSON request and return the JSON response.
+    fn handle_request(&self, request: &str) -> String {
+        let request = serde_json::from_str::<Map<String, Value>>(request).and_then(|mut request| {
+            let token = request.remove("token");
+            Ok((token, serde_json::from_value::<Request>(Value::Object(request))?))
+        });
+        let response = match request {
+            Ok((token, _)) if !self.authorized(token.as_ref()) => {
+                json!({ "ok": false, "error": "Invalid or missing token" })
+            }
+            Ok((_, request)) => match self.apply(request) {
+                Ok(()) => self.status_json(),
+                Err(error) => json!({ "ok": false, "error": error }),
+            },
+            Err(error) => json!({ "ok": false, "error": format!("Invalid request: {error}") }),
+        };
+        response.to_string()
+    }
+
+    /// Whether a request with `token` is allowed.
+    fn authorized(&self, token: Option<&Value>) -> bool {
+        match (&self.token, token.and_then(Value::as_str)) {
+            (None, _) => true,
+            (Some(expected), Some(token)) => constant_time_eq(expected.as_bytes(), token.as_bytes()),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn apply(&self, request: Request) -> Result<(), String> {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        let next = match (request, state.run_state) {
+            (Request::Status, _) => return Ok(()),
+            (Request::Set { params }, _) => {
+                state.params.extend(params);
+                return Ok(());
+            }
+            (_, RunState::Aborted) => return Err("The experiment has been aborted".into()),
+            (Request::Start, RunState::Waiting) => RunState::Running,
+            (Request::Pause, RunState::Running) => RunState::Paused,
+            (Request::Resume, RunState::Paused) => RunState::Running,
+            (Request::Abort, _) => RunState::Aborted,
+            (request, run_state) => return Err(format!("Can't {request:?} while {run_state}").to_lowercase()),
+        };
+        state.run_state = next;
+        condvar.notify_all();
+        Ok(())
+    }
+
+    fn status_json(&self) -> Value {
+        let mut state = self.state.0.lock().unwrap();
+        // the window state is locked while a frame is presented, in which case the last known
+        // statistics are reported instead of waiting
+        let windows = state
+            .windows
+            .iter_mut()
+            .map(|(window, dropped_frames)| {
+                if let Ok(window_state) = window.state.try_lock() {
+                    if let Some(window_state) = window_state.as_ref() {
+                        *dropped_frames = window_state.watchdog.dropped_frames.len();
+                    }
+                }
+                json!({ "dropped_frames": *dropped_frames })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "ok": true,
+            "state": state.run_state.to_string(),
+            "params": state.params,
+            "status": state.status,
+            "windows": windows,
+        })
+    }
+
+    pub fn run_state(&self) -> RunState {
+        self.state.0.lock().unwrap().run_state
+    }
+
+    /// Block while the state is one of `states` (or until the timeout expires) and return the
+    /// state.
+    pub fn wait_while(&self, states: &[RunState], timeout: Option<Duration>) -> RunState {
+        let (lock, condvar) = &*self.state;
+        let deadline = deadline(timeout);
+        let mut state = lock.lock().unwrap();
+        while states.contains(&state.run_state) {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    condvar.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => condvar.wait(state).unwrap(),
+            };
+        }
+        state.run_state
+    }
+
+    pub fn param(&self, name: &str) -> Option<Value> {
+        self.state.0.lock().unwrap().params.get(name).cloned()
+    }
+
+    pub fn params(&self) -> Map<String, Value> {
+        self.state.0.lock().unwrap().params.clone()
+    }
+
+    pub fn update_status(&self, status: impl IntoIterator<Item = (String, Value)>) {
+        self.state.0.lock().unwrap().status.extend(status);
+    }
+
+    pub fn watch(&self, window: Window) {
+        self.state.0.lock().unwrap().windows.push((window, 0));
+    }
+}
+
+/// The first four bytes of HTTP requests other than GET, which browsers can send to any port.
+const HTTP_METHODS: [&[u8; 4]; 6] = [b"POST", b"PUT ", b"HEAD", b"OPTI", b"DELE", b"PATC"];
+
+/// Compare two byte strings in a time that does not depend on where they differ, so that a token
+/// cannot be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(feature = "python")]
+fn json_to_py(py: Python, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any().unbind(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => PyInt::new(py, i).into_any().unbind(),
+            None => PyFloat::new(py, n.as_f64().unwrap_or(f64::NAN)).into_any().unbind(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any().unbind(),
+        Value::Array(values) => {
+            let values = values.iter().map(|v| json_to_py(py, v)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, values)?.into_any().unbind()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+#[cfg(feature = "python")]
+fn py_to_json(value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    Ok(if value.is_none() {
+        Value::Null
+    } else if let Ok(b) = value.downcast::<PyBool>() {
+        Value::Bool(b.is_true())
+    } else if let Ok(i) = value.extract::<i64>() {
+        json!(i)
+    } else if let Ok(f) = value.extract::<f64>() {
+        json!(f)
+    } else if let Ok(list) = value.downcast::<PyList>() {
+        Value::Array(list.iter().map(|v| py_to_json(&v)).collect::<PyResult<_>>()?)
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            map.insert(key.str()?.to_string(), py_to_json(&value)?);
+        }
+        Value::Object(map)
+    } else {
+        Value::String(value.str()?.to_string())
+    })
+}
+
+/// A server that lets other machines control the experiment over the network. Create it with
+/// `ExperimentContext.start_control_server`.
+///
+/// Clients send JSON requests with a `command` field: `"start"`, `"pause"`, `"resume"`,
+/// `"abort"`, `"set"` (with a `params` object), or `"status"`. Every request is answered with the
+/// state of the experiment, the parameters, the status set with `update_status`, and the frame
+/// statistics of watched windows. Clients can use a WebSocket or a plain TCP connection with one
+/// request per line. If the server was started with a token, requests also need a matching
+/// `token` field. WebSocket connections from web pages are refused unless their origin has been
+/// allowed.
+#[cfg(feature = "python")]
+#[pyclass(name = "ControlServer")]
+#[derive(Debug, Clone)]
+pub struct PyControlServer(pub ControlServer);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyControlServer {
+    /// The address the server is listening on.
+    #[getter(address)]
+    fn py_address(&self) -> String {
+        self.0.address().to_string()
+    }
+
+    /// The state of the experiment: "waiting", "running", "paused", or "aborted".
+    #[getter(state)]
+    fn py_state(&self) -> String {
+        self.0.run_state().to_string()
+    }
+
+    /// Whether an abort command has been received.
+    #[getter(aborted)]
+    fn py_aborted(&self) -> bool {
+        self.0.run_state() == RunState::Aborted
+    }
+
+    /// Block until a client sends the start command.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeout : float, optional
+    ///   The maximum time to wait in seconds. Waits indefinitely if not given.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   True if the experiment was started, False if it was aborted or the timeout expired.
+    #[pyo3(name = "wait_for_start")]
+    #[pyo3(signature = (timeout = None))]
+    fn py_wait_for_start(&self, py: Python, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = to_timeout(timeout)?;
+        let state = py.allow_threads(|| self.0.wait_while(&[RunState::Waiting], timeout));
+        Ok(state == RunState::Running || state == RunState::Paused)
+    }
+
+    /// Block while the experiment is paused. Call this between trials.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   False if the experiment has been aborted, True otherwise.
+    #[pyo3(name = "wait_while_paused")]
+    fn py_wait_while_paused(&self, py: Python) -> bool {
+        py.allow_threads(|| self.0.wait_while(&[RunState::Paused], None)) != RunState::Aborted
+    }
+
+    /// Get a parameter that was set by a client.
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str
+    ///   The name of the parameter.
+    /// default : Any, optional
+    ///   Returned if the parameter has not been set.
+    #[pyo3(name = "get")]
+    #[pyo3(signature = (name, default = None))]
+    fn py_get(&self, py: Python, name: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        match self.0.param(name) {
+            Some(value) => json_to_py(py, &value),
+            None => Ok(default.unwrap_or_else(|| py.None())),
+        }
+    }
+
+    /// All parameters that were set by clients.
+    #[getter(params)]
+    fn py_params(&self, py: Python) -> PyResult<PyObject> {
+        json_to_py(py, &Value::Object(self.0.params()))
+    }
+
+    /// Update the status that is reported to clients, e.g. `update_status(trial=12, block=2)`.
+    #[pyo3(name = "update_status")]
+    #[pyo3(signature = (**status))]
+    fn py_update_status(&self, status: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        if let Some(status) = status {
+            let status = status
+                .iter()
+                .map(|(key, value)| Ok((key.str()?.to_string(), py_to_json(&value)?)))
+                .collect::<PyResult<Vec<_>>>()?;
+            self.0.update_status(status);
+        }
+        Ok(())
+    }
+
+    /// Report the frame statistics (e.g. the number of dropped frames) of a window to clients.
+    /// The window's frame watchdog needs to be enabled, see `Window.set_frame_watchdog`.
+    #[pyo3(name = "watch")]
+    fn py_watch(&self, window: Window) {
+        self.0.watch(window);
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ControlServer(address={:?}, state={:?})",
+            self.0.address(),
+            self.0.run_state().to_string()
+        )
+    }
+}
@@ -1,22 +1,115 @@
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "python")]
 use pyo3::ffi::c_str;
+#[cfg(feature = "python")]
+use pyo3::pyclass::CompareOp;
+#[cfg(feature = "python")]
 use pyo3::types::PyAnyMethods;
-use pyo3::{pyclass, pyfunction, pymethods, PyResult, Python};
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pyfunction, pymethods, Bound, FromPyObject, IntoPyObject, PyAny, PyObject, PyResult, Python};
+
+use crate::errors::{PsydkError, PsydkResult};
+
+mod timeline;
+mod timer;
+
+#[cfg(feature = "python")]
+pub use timeline::PyTimeline;
+pub use timeline::{Timeline, TimelineEvent};
+pub use timer::{wait_until, TimerResolutionGuard};
 
 #[derive(Debug, Clone)]
-#[pyclass]
-#[pyo3(name = "Timestamp")]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "python", pyo3(name = "Timestamp"))]
 /// A timestamp represents a point in time.
 ///
-/// Timestamps are opaque and can only be compared to other timestamps or floats. This
-/// means that they can be compared to each other, added to or subtracted from, and used to
-/// calculate the time since another timestamp. However, they are not meaningful on their own.
+/// Timestamps are based on a monotonic clock. They can be compared to each other, added to or
+/// subtracted from, and used to calculate the time since another timestamp. Subtracting two
+/// timestamps returns the difference in seconds. Timestamps can be converted to and from UNIX
+/// time, `time.monotonic()`, and ISO 8601 strings.
 pub struct Timestamp {
     pub(crate) timestamp: Instant,
 }
 
+/// A time offset, given either in seconds or as a `datetime.timedelta`.
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+pub enum TimeOffset {
+    Seconds(f64),
+    Duration(Duration),
+}
+
+impl TimeOffset {
+    /// The offset in seconds.
+    pub(crate) fn seconds(&self) -> f64 {
+        match self {
+            TimeOffset::Seconds(seconds) => *seconds,
+            TimeOffset::Duration(duration) => duration.as_secs_f64(),
+        }
+    }
+}
+
+/// The right-hand side of a subtraction or comparison.
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+pub enum TimestampOrOffset {
+    Timestamp(Timestamp),
+    Offset(TimeOffset),
+}
+
+/// A pair of readings of the monotonic and the system clock, taken once, that is used to
+/// convert between timestamps and UNIX time.
+fn system_time_anchor() -> &'static (Instant, SystemTime) {
+    static ANCHOR: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+    ANCHOR.get_or_init(|| (Instant::now(), SystemTime::now()))
+}
+
+impl Timestamp {
+    /// Shift the timestamp by `seconds`, which may be negative.
+    pub fn offset_by(&self, seconds: f64) -> PsydkResult<Self> {
+        let duration = Duration::try_from_secs_f64(seconds.abs())
+            .map_err(|e| PsydkError::ParameterError(format!("Invalid time offset {seconds}: {e}")))?;
+        let timestamp = if seconds >= 0.0 {
+            self.timestamp.checked_add(duration)
+        } else {
+            self.timestamp.checked_sub(duration)
+        };
+        timestamp
+            .map(Timestamp::from)
+            .ok_or_else(|| PsydkError::ParameterError(format!("Timestamp offset by {seconds} s is out of range")))
+    }
+
+    /// Seconds from `other` to this timestamp, negative if `other` is later.
+    pub fn seconds_since_instant(&self, other: Instant) -> f64 {
+        match self.timestamp.checked_duration_since(other) {
+            Some(d) => d.as_secs_f64(),
+            None => -other.duration_since(self.timestamp).as_secs_f64(),
+        }
+    }
+
+    /// Seconds since the UNIX epoch.
+    pub fn unix(&self) -> f64 {
+        let (anchor_instant, anchor_system) = system_time_anchor();
+        let anchor_unix = anchor_system
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        anchor_unix + self.seconds_since_instant(*anchor_instant)
+    }
+
+    /// Create a timestamp from seconds since the UNIX epoch.
+    pub fn from_unix(seconds: f64) -> PsydkResult<Self> {
+        let (anchor_instant, anchor_system) = system_time_anchor();
+        let anchor_unix = anchor_system
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        Timestamp::from(*anchor_instant).offset_by(seconds - anchor_unix)
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl Timestamp {
     #[new]
@@ -30,42 +123,119 @@ impl Timestamp {
         Ok(self.timestamp.elapsed().as_secs_f64())
     }
 
-    // allow subtracting a float from the timestamp
-    fn __sub__(&self, other: f64) -> PyResult<Timestamp> {
-        Ok(Timestamp {
-            timestamp: self.timestamp - Duration::from_secs_f64(other),
-        })
+    // subtracting a timestamp returns the difference in seconds, subtracting seconds or a
+    // timedelta returns a new timestamp
+    fn __sub__(&self, py: Python, other: TimestampOrOffset) -> PyResult<PyObject> {
+        match other {
+            TimestampOrOffset::Timestamp(other) => Ok(self
+                .seconds_since_instant(other.timestamp)
+                .into_pyobject(py)?
+                .into_any()
+                .unbind()),
+            TimestampOrOffset::Offset(offset) => Ok(self
+                .offset_by(-offset.seconds())?
+                .into_pyobject(py)?
+                .into_any()
+                .unbind()),
+        }
     }
 
-    // allow adding a float to the timestamp
-    fn __add__(&self, other: f64) -> PyResult<Timestamp> {
-        Ok(Timestamp {
-            timestamp: self.timestamp + Duration::from_secs_f64(other),
-        })
+    // allow adding seconds or a timedelta to the timestamp
+    fn __add__(&self, other: TimeOffset) -> PyResult<Timestamp> {
+        Ok(self.offset_by(other.seconds())?)
     }
 
-    // allow comparing the timestamp to a float
-    fn __lt__(&self, other: f64) -> PyResult<bool> {
-        Ok(self.timestamp.elapsed().as_secs_f64() < other)
+    fn __radd__(&self, other: TimeOffset) -> PyResult<Timestamp> {
+        self.__add__(other)
     }
 
-    // allow comparing the timestamp to a float
-    fn __gt__(&self, other: f64) -> PyResult<bool> {
-        Ok(self.timestamp.elapsed().as_secs_f64() > other)
+    // timestamps are compared to each other; comparing to a float compares the time elapsed
+    // since the timestamp
+    fn __richcmp__(&self, other: TimestampOrOffset, op: CompareOp) -> bool {
+        match other {
+            TimestampOrOffset::Timestamp(other) => op.matches(self.timestamp.cmp(&other.timestamp)),
+            TimestampOrOffset::Offset(offset) => {
+                let elapsed = self.timestamp.elapsed().as_secs_f64();
+                elapsed
+                    .partial_cmp(&offset.seconds())
+                    .is_some_and(|ordering| op.matches(ordering))
+            }
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.timestamp.hash(&mut hasher);
+        hasher.finish()
     }
 
     /// Get the time since another timestamp in seconds.
     /// May be negative if the other timestamp is in the future.
     fn seconds_since(&self, other: Timestamp) -> PyResult<f64> {
-        Ok(self.timestamp.duration_since(other.timestamp).as_secs_f64())
+        Ok(self.seconds_since_instant(other.timestamp))
     }
 
-    // implement the __str__ method
-    fn __str__(&self) -> PyResult<String> {
-        Ok("Opaque timestamp (use the `seconds_since` method to get the time since another timestamp)".to_string())
+    /// Convert the timestamp to seconds since the UNIX epoch (like `time.time()`).
+    fn to_unix(&self) -> f64 {
+        self.unix()
     }
+
+    /// Create a timestamp from seconds since the UNIX epoch (like `time.time()`).
+    #[staticmethod]
+    #[pyo3(name = "from_unix")]
+    fn py_from_unix(seconds: f64) -> PyResult<Timestamp> {
+        Ok(Timestamp::from_unix(seconds)?)
+    }
+
+    /// Convert the timestamp to the clock used by Python's `time.monotonic()`.
+    fn to_monotonic(&self, py: Python) -> PyResult<f64> {
+        let (now, monotonic) = monotonic_reference(py)?;
+        Ok(monotonic + self.seconds_since_instant(now))
+    }
+
+    /// Create a timestamp from a value returned by Python's `time.monotonic()`.
+    #[staticmethod]
+    fn from_monotonic(py: Python, seconds: f64) -> PyResult<Timestamp> {
+        let (now, monotonic) = monotonic_reference(py)?;
+        Ok(Timestamp::from(now).offset_by(seconds - monotonic)?)
+    }
+
+    /// Format the timestamp as an ISO 8601 string in UTC.
+    fn isoformat(&self, py: Python) -> PyResult<String> {
+        self.to_datetime(py)?.call_method0("isoformat")?.extract()
+    }
+
+    /// Convert the timestamp to a timezone-aware `datetime.datetime` in UTC.
+    fn to_datetime<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let datetime = py.import("datetime")?;
+        let utc = datetime.getattr("timezone")?.getattr("utc")?;
+        datetime
+            .getattr("datetime")?
+            .call_method1("fromtimestamp", (self.unix(), utc))
+    }
+
+    // timestamps are shown as ISO 8601 strings in UTC, like `datetime.datetime`
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        self.isoformat(py)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("Timestamp({})", self.isoformat(py)?))
+    }
+}
+
+/// The current time as a monotonic `Instant` and as a value of Python's `time.monotonic()`.
+#[cfg(feature = "python")]
+fn monotonic_reference(py: Python) -> PyResult<(Instant, f64)> {
+    let monotonic = py.import("time")?.getattr("monotonic")?;
+    // take the reading of the Rust clock halfway between two readings of the Python clock
+    let before: f64 = monotonic.call0()?.extract()?;
+    let now = Instant::now();
+    let after: f64 = monotonic.call0()?.extract()?;
+    Ok((now, (before + after) / 2.0))
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "now")]
 pub fn py_now() -> Timestamp {
@@ -74,6 +244,57 @@ pub fn py_now() -> Timestamp {
     }
 }
 
+/// Wait until the given timestamp. Sleeps for most of the time and busy-waits for the last part,
+/// which gives sub-millisecond precision (also on Windows, where sleeping is otherwise quantized
+/// to 15.6 ms).
+///
+/// Parameters
+/// ----------
+/// timestamp : Timestamp
+///   The time to wait for. Returns immediately if it is in the past.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "wait_until")]
+pub fn py_wait_until(py: Python, timestamp: Timestamp) {
+    py.allow_threads(|| wait_until(timestamp.timestamp));
+}
+
+/// Wait for the given number of seconds with sub-millisecond precision (see `wait_until`).
+///
+/// Parameters
+/// ----------
+/// seconds : float
+///   The time to wait in seconds.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "wait")]
+pub fn py_wait(py: Python, seconds: f64) -> PyResult<()> {
+    // `max` would turn NaN into 0
+    let deadline = Some(seconds)
+        .filter(|seconds| !seconds.is_nan())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds.max(0.0)).ok())
+        .and_then(|duration| Instant::now().checked_add(duration))
+        .ok_or_else(|| PsydkError::ParameterError(format!("Cannot wait for {} seconds", seconds)))?;
+    py.allow_threads(|| wait_until(deadline));
+    Ok(())
+}
+
+/// Convert a timeout in seconds. Negative timeouts expire immediately, and infinite timeouts (or
+/// ones too long to be represented) never expire.
+pub(crate) fn to_timeout(timeout: Option<f64>) -> PsydkResult<Option<Duration>> {
+    match timeout {
+        Some(t) if t.is_nan() => Err(PsydkError::ParameterError("The timeout must not be NaN".into())),
+        Some(t) => Ok(Duration::try_from_secs_f64(t.max(0.0)).ok()),
+        None => Ok(None),
+    }
+}
+
+/// The instant at which a timeout expires. A timeout too long to be represented is the same as
+/// none.
+pub(crate) fn deadline(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.and_then(|timeout| Instant::now().checked_add(timeout))
+}
+
 // alow into() from Instant to Timestamp
 impl From<Instant> for Timestamp {
     fn from(timestamp: Instant) -> Self {
@@ -0,0 +1,296 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Instant;
+
+#[cfg(feature = "python")]
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, PyResult, Python};
+
+use super::Timestamp;
+use crate::errors::{PsydkError, PsydkResult};
+use crate::input::{Event, EventKind};
+
+/// A single event on a `Timeline`.
+#[derive(Debug, Clone)]
+pub struct TimelineEvent {
+    /// The kind of event (e.g., "frame", "key_press", "audio_onset", "trigger").
+    pub kind: String,
+    /// When the event happened.
+    pub time: Instant,
+    /// An optional label, e.g. the key that was pressed or the name of the stimulus.
+    pub label: Option<String>,
+    /// Additional columns.
+    pub data: Vec<(String, String)>,
+}
+
+/// Collects timestamped events from different sources on a common clock.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    reference: Instant,
+    events: Vec<TimelineEvent>,
+}
+
+impl Timeline {
+    /// Create an empty timeline. Exported times are in seconds relative to `reference`.
+    pub fn new(reference: Instant) -> Self {
+        Self {
+            reference,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, event: TimelineEvent) {
+        self.events.push(event);
+    }
+
+    /// All events, sorted by time.
+    pub fn events(&self) -> Vec<&TimelineEvent> {
+        let mut events = self.events.iter().collect::<Vec<_>>();
+        events.sort_by_key(|e| e.time);
+        events
+    }
+
+    /// The time of an event in seconds relative to the reference of the timeline.
+    pub fn relative_time(&self, time: Instant) -> f64 {
+        match time.checked_duration_since(self.reference) {
+            Some(d) => d.as_secs_f64(),
+            None => -self.reference.duration_since(time).as_secs_f64(),
+        }
+    }
+
+    /// For every event of kind `from` (optionally with label `from_label`), the time in seconds
+    /// until the next event of kind `to` (optionally with label `to_label`) that happens before
+    /// the next `from` event, or `None` if there is no such event.
+    pub fn latencies(
+        &self,
+        from: &str,
+        to: &str,
+        from_label: Option<&str>,
+        to_label: Option<&str>,
+    ) -> Vec<Option<f64>> {
+        let matches = |e: &TimelineEvent, kind: &str, label: Option<&str>| {
+            e.kind == kind && label.map_or(true, |label| e.label.as_deref() == Some(label))
+        };
+
+        let events = self.events();
+        let onsets = events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| matches(e, from, from_label))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        onsets
+            .iter()
+            .enumerate()
+            .map(|(n, &i)| {
+                let end = onsets.get(n + 1).copied().unwrap_or(events.len());
+                events[i + 1..end]
+                    .iter()
+                    .find(|e| matches(e, to, to_label))
+                    .map(|e| e.time.duration_since(events[i].time).as_secs_f64())
+            })
+            .collect()
+    }
+
+    /// Names of all additional data columns, in alphabetical order.
+    pub fn data_columns(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .flat_map(|e| e.data.iter().map(|(key, _)| key.clone()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Write the merged, time-sorted table to a CSV file with the columns `time`, `kind`,
+    /// `label`, followed by all data columns.
+    pub fn write_csv(&self, path: &Path) -> PsydkResult<()> {
+        let columns = self.data_columns();
+        let mut writer = csv::Writer::from_path(path).map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        let header = ["time", "kind", "label"]
+            .into_iter()
+            .map(String::from)
+            .chain(columns.iter().cloned());
+        writer
+            .write_record(header)
+            .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+
+        for event in self.events() {
+            let data = columns.iter().map(|column| {
+                event
+                    .data
+                    .iter()
+                    .find(|(key, _)| key == column)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            });
+            let record = [
+                self.relative_time(event.time).to_string(),
+                event.kind.clone(),
+                event.label.clone().unwrap_or_default(),
+            ]
+            .into_iter()
+            .chain(data);
+            writer
+                .write_record(record)
+                .map_err(|e| PsydkError::CustomError(e.to_string()))?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl From<&Event> for TimelineEvent {
+    fn from(event: &Event) -> Self {
+        let label = match event {
+            Event::KeyPress { key, .. } | Event::KeyRelease { key, .. } => Some(key.clone()),
+            Event::MouseButtonPress { button, .. } | Event::MouseButtonRelease { button, .. } => {
+                Some(format!("{:?}", button))
+            }
+            Event::Other { name, .. } => Some(name.clone()),
+            _ => None,
+        };
+
+        Self {
+            kind: EventKind::from(event).to_string(),
+            time: event.timestamp().timestamp,
+            label,
+            data: Vec::new(),
+        }
+    }
+}
+
+/// Collects timestamped events from different sources (frames, key presses, audio onsets,
+/// triggers, gaze samples, ...) on a common clock, so they can be exported as a single table and
+/// latencies between them can be computed.
+///
+/// Parameters
+/// ----------
+/// reference : Timestamp, optional
+///   The time that exported times are relative to. Defaults to the time the timeline was created.
+#[cfg(feature = "python")]
+#[pyclass]
+#[cfg_attr(feature = "python", pyo3(name = "Timeline"))]
+pub struct PyTimeline(pub Timeline);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyTimeline {
+    #[new]
+    #[pyo3(signature = (reference = None))]
+    fn new(reference: Option<Timestamp>) -> Self {
+        let reference = reference.map(|t| t.timestamp).unwrap_or_else(Instant::now);
+        Self(Timeline::new(reference))
+    }
+
+    /// Add an event to the timeline.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind : str
+    ///   The kind of event, e.g. `"frame"`, `"audio_onset"`, or `"trigger"`.
+    /// timestamp : Timestamp
+    ///   When the event happened.
+    /// label : str, optional
+    ///   A label for the event, e.g. the name of the stimulus.
+    /// **data
+    ///   Additional columns for the exported table.
+    #[pyo3(signature = (kind, timestamp, label = None, **data))]
+    fn add(
+        &mut self,
+        kind: String,
+        timestamp: Timestamp,
+        label: Option<String>,
+        data: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<()> {
+        let data = match data {
+            Some(data) => data
+                .iter()
+                .map(|(key, value)| Ok((key.extract::<String>()?, value.str()?.to_string())))
+                .collect::<PyResult<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        self.0.add(TimelineEvent {
+            kind,
+            time: timestamp.timestamp,
+            label,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Add an input event (e.g., a key press) to the timeline. The kind is the event type (e.g.,
+    /// `"key_press"`) and the label is the key, button, or name of the event.
+    fn add_event(&mut self, event: Event) {
+        self.0.add(TimelineEvent::from(&event));
+    }
+
+    /// Compute latencies between two kinds of events, e.g. response times from stimulus onsets to
+    /// key presses.
+    ///
+    /// Parameters
+    /// ----------
+    /// from_kind : str
+    ///   The kind of the events to measure from.
+    /// to_kind : str
+    ///   The kind of the events to measure to.
+    /// from_label : str, optional
+    ///   Only consider `from_kind` events with this label.
+    /// to_label : str, optional
+    ///   Only consider `to_kind` events with this label.
+    ///
+    /// Returns
+    /// -------
+    /// list of float or None
+    ///   For every `from_kind` event, the time in seconds until the next `to_kind` event that
+    ///   happens before the next `from_kind` event, or `None` if there is none.
+    #[pyo3(signature = (from_kind, to_kind, from_label = None, to_label = None))]
+    fn latencies(
+        &self,
+        from_kind: &str,
+        to_kind: &str,
+        from_label: Option<&str>,
+        to_label: Option<&str>,
+    ) -> Vec<Option<f64>> {
+        self.0.latencies(from_kind, to_kind, from_label, to_label)
+    }
+
+    /// Export the merged, time-sorted table to a CSV file. Times are in seconds relative to the
+    /// reference of the timeline.
+    fn to_csv(&self, path: std::path::PathBuf) -> PyResult<()> {
+        Ok(self.0.write_csv(&path)?)
+    }
+
+    /// Export the merged, time-sorted table as a dictionary of columns, e.g. to create a pandas
+    /// `DataFrame`. Missing data values are `None`.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let events = self.0.events();
+        let dict = PyDict::new(py);
+
+        dict.set_item(
+            "time",
+            events.iter().map(|e| self.0.relative_time(e.time)).collect::<Vec<_>>(),
+        )?;
+        dict.set_item("kind", events.iter().map(|e| e.kind.clone()).collect::<Vec<_>>())?;
+        dict.set_item("label", events.iter().map(|e| e.label.clone()).collect::<Vec<_>>())?;
+
+        for column in self.0.data_columns() {
+            let values = events
+                .iter()
+                .map(|e| e.data.iter().find(|(key, _)| *key == column).map(|(_, v)| v.clone()))
+                .collect::<Vec<_>>();
+            dict.set_item(column, values)?;
+        }
+
+        Ok(dict)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.events.len()
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+/// The last part of a wait is spent busy-waiting, as the OS scheduler may wake the thread up late.
+#[cfg(target_os = "windows")]
+const SPIN_TAIL: Duration = Duration::from_micros(1500);
+#[cfg(not(target_os = "windows"))]
+const SPIN_TAIL: Duration = Duration::from_micros(500);
+
+/// Raises the resolution of the system timer while it is alive.
+///
+/// On Windows, the default timer resolution is 15.6 ms, which also quantizes `sleep`. This calls
+/// `timeBeginPeriod(1)` and restores the previous resolution with `timeEndPeriod(1)` when dropped.
+/// On other platforms, this does nothing.
+pub struct TimerResolutionGuard {
+    #[cfg(target_os = "windows")]
+    active: bool,
+}
+
+impl TimerResolutionGuard {
+    pub fn new() -> Self {
+        #[cfg(target_os = "windows")]
+        {
+            let result = unsafe { windows::Win32::Media::timeBeginPeriod(1) };
+            let active = result == windows::Win32::Media::TIMERR_NOERROR;
+            if !active {
+                log::warn!("Failed to raise the timer resolution (error {})", result);
+            }
+            Self { active }
+        }
+        #[cfg(not(target_os = "windows"))]
+        Self {}
+    }
+}
+
+impl Drop for TimerResolutionGuard {
+    fn drop(&mut self) {
+        #[cfg(target_os = "windows")]
+        if self.active {
+            unsafe { windows::Win32::Media::timeEndPeriod(1) };
+        }
+    }
+}
+
+/// Block the current thread until `deadline`.
+///
+/// Sleeps for most of the wait (using a high-resolution waitable timer on Windows) and busy-waits
+/// for the final part, which gives sub-millisecond precision on all platforms.
+pub fn wait_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+
+    if let Some(coarse) = (deadline - now).checked_sub(SPIN_TAIL) {
+        sleep(coarse);
+    }
+
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn sleep(duration: Duration) {
+    std::thread::sleep(duration);
+}
+
+#[cfg(target_os = "windows")]
+fn sleep(duration: Duration) {
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Threading::{
+        CreateWaitableTimerExW, SetWaitableTimer, WaitForSingleObject, CREATE_WAITABLE_TIMER_HIGH_RESOLUTION, INFINITE,
+        TIMER_ALL_ACCESS,
+    };
+
+    thread_local! {
+        // high-resolution waitable timers are available from Windows 10 1803
+        static TIMER: Option<HANDLE> = unsafe {
+            CreateWaitableTimerExW(
+                None,
+                windows::core::PCWSTR::null(),
+                CREATE_WAITABLE_TIMER_HIGH_RESOLUTION,
+                TIMER_ALL_ACCESS.0,
+            )
+            .ok()
+        };
+    }
+
+    let waited = TIMER.with(|timer| {
+        let Some(timer) = timer else {
+            return false;
+        };
+        // negative due times are relative, in units of 100 ns
+        let due_time = -((duration.as_nanos() / 100) as i64);
+        unsafe {
+            SetWaitableTimer(*timer, &due_time, 0, None, None, false).is_ok()
+                && WaitForSingleObject(*timer, INFINITE) == windows::Win32::Foundation::WAIT_OBJECT_0
+        }
+    });
+
+    if !waited {
+        std::thread::sleep(duration);
+    }
+}
@@ -0,0 +1,225 @@
+//! Integrity of data files.
+//!
+//! Files that are only useful once complete (e.g. recordings) are written to a `<name>.partial` file
+//! next to the final path and only renamed once they are complete and synced to disk, so a crash
+//! leaves a `.partial` file instead of a truncated file that looks complete. Tables that are written
+//! row by row (e.g. by a `CSVWriter`) are written to the final path, so that the rows written before
+//! a crash are kept. Every file written during an experiment is recorded, and a manifest with the
+//! row counts, SHA-256 hashes, and completion of all files is written at the end of the session, so
+//! that archived data can be verified.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::errors::PsydkResult;
+
+/// A data file written during the session.
+#[derive(Debug, Clone)]
+struct SessionFile {
+    path: PathBuf,
+    rows: Option<u64>,
+    complete: bool,
+}
+
+static SESSION_FILES: Mutex<Vec<SessionFile>> = Mutex::new(Vec::new());
+
+/// The path that a file is written to until it is complete.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// Sync `partial` to disk and rename it to `path`. On Unix, the directory is synced as well, so
+/// that the rename survives a power loss.
+pub fn commit(partial: &Path, path: &Path) -> io::Result<()> {
+    File::open(partial)?.sync_all()?;
+    std::fs::rename(partial, path)?;
+
+    #[cfg(unix)]
+    if let Some(directory) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Record that a file is being written. It is listed as incomplete in the manifest until
+/// `record_complete` is called.
+pub fn record_open(path: &Path) {
+    let mut files = SESSION_FILES.lock().unwrap();
+    files.retain(|file| file.path != path);
+    files.push(SessionFile {
+        path: path.to_path_buf(),
+        rows: None,
+        complete: false,
+    });
+}
+
+/// Record that a file has been written completely, with the number of data rows if it is a table.
+pub fn record_complete(path: &Path, rows: Option<u64>) {
+    let mut files = SESSION_FILES.lock().unwrap();
+    files.retain(|file| file.path != path);
+    files.push(SessionFile {
+        path: path.to_path_buf(),
+        rows,
+        complete: true,
+    });
+}
+
+/// An entry of the manifest.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    /// Whether the file was completely written. Incomplete files are listed with the path of
+    /// their `.partial` file, if they were written to one.
+    pub complete: bool,
+    pub rows: Option<u64>,
+    /// The size and hash of the file, or None if the file could not be read.
+    pub bytes: Option<u64>,
+    pub sha256: Option<String>,
+    /// Why the file could not be read, if it could not.
+    pub error: Option<String>,
+}
+
+/// The files written during a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub session: Option<String>,
+    /// The seed of the session random number generator.
+    pub seed: u64,
+    /// UNIX time at which the manifest was created.
+    pub created: f64,
+    pub files: Vec<ManifestEntry>,
+}
+
+fn sha256(path: &Path) -> io::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut bytes = 0;
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        bytes += n as u64;
+        hasher.update(&buffer[..n]);
+    }
+    let hash = hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok((bytes, hash))
+}
+
+impl Manifest {
+    /// Create a manifest of all files recorded so far and forget them, so that the next manifest
+    /// only lists files written after this one.
+    pub fn take(session: Option<String>) -> PsydkResult<Self> {
+        let files = std::mem::take(&mut *SESSION_FILES.lock().unwrap());
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let partial = partial_path(&file.path);
+                let path = match file.complete || !partial.exists() {
+                    true => file.path,
+                    false => partial,
+                };
+                // a file that was never created (or already moved away) has no hash
+                let (bytes, sha256, error) = match sha256(&path) {
+                    Ok((bytes, sha256)) => (Some(bytes), Some(sha256), None),
+                    Err(e) => {
+                        log::warn!("Failed to hash {} for the manifest: {}", path.display(), e);
+                        (None, None, Some(e.to_string()))
+                    }
+                };
+                ManifestEntry {
+                    path,
+                    complete: file.complete,
+                    rows: file.rows,
+                    bytes,
+                    sha256,
+                    error,
+                }
+            })
+            .collect();
+
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Ok(Self {
+            session,
+            seed: super::random::session_seed(),
+            created,
+            files,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Write the manifest as JSON, atomically like the data files.
+    pub fn write(&self, path: &Path) -> PsydkResult<()> {
+        let partial = partial_path(path);
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(&partial, json)?;
+        commit(&partial, path)?;
+        Ok(())
+    }
+}
+
+/// Write the manifest of the session next to the first file that was written, if any files were
+/// written, and upload the files if an uploader is enabled. Called at the end of every experiment.
+pub fn finish_session(session: Option<String>) -> PsydkResult<Option<PathBuf>> {
+    let manifest = Manifest::take(session)?;
+    let Some(first) = manifest.files.first() else {
+        return Ok(None);
+    };
+
+    let name = match &manifest.session {
+        Some(session) => format!("manifest_{session}.json"),
+        None => format!("manifest_{}.json", manifest.created as u64),
+    };
+    let path = first.path.with_file_name(name);
+    manifest.write(&path)?;
+
+    #[cfg(feature = "upload")]
+    super::upload::upload_session(&path, &manifest);
+
+    for file in manifest.files.iter().filter(|file| !file.complete) {
+        log::warn!(
+            "{} was not closed before the end of the experiment",
+            file.path.display()
+        );
+    }
+    Ok(Some(path))
+}
+
+/// Write a manifest of all data files written since the last manifest, with their row counts and
+/// SHA-256 hashes. Files that could not be read are listed without a hash and with the error. A
+/// manifest is also written automatically at the end of every experiment (next to the first data
+/// file) if any files were written since.
+///
+/// Parameters
+/// ----------
+/// path : str
+///   The file to write the manifest (JSON) to.
+/// session : str, optional
+///   A session identifier to include in the manifest.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "write_manifest")]
+#[pyo3(signature = (path, session = None))]
+pub fn py_write_manifest(path: PathBuf, session: Option<String>) -> PyResult<()> {
+    Manifest::take(session)?.write(&path)?;
+    Ok(())
+}
@@ -0,0 +1,398 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyDictMethods};
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, Py, PyObject, PyRef, PyResult, Python};
+
+use crate::errors::{PsydkError, PsydkResult};
+#[cfg(feature = "python")]
+use crate::io::bio::PyBioInlet;
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::time::{TimelineEvent, Timestamp};
+
+/// A device that event markers (triggers) are sent to, e.g. an EEG amplifier connected to a
+/// parallel or serial port.
+pub trait MarkerSink: Send + Sync {
+    fn send(&mut self, code: u8) -> PsydkResult<()>;
+}
+
+/// Writes each marker code as a single byte to a serial port.
+#[cfg(feature = "serial")]
+pub struct SerialSink(std::sync::Mutex<Box<dyn serialport::SerialPort>>);
+
+#[cfg(feature = "serial")]
+impl SerialSink {
+    pub fn open(port: &str, baud_rate: u32) -> PsydkResult<Self> {
+        let port = serialport::new(port, baud_rate)
+            .open()
+            .map_err(|e| PsydkError::IOError(e.into()))?;
+        Ok(Self(std::sync::Mutex::new(port)))
+    }
+}
+
+#[cfg(feature = "serial")]
+impl MarkerSink for SerialSink {
+    fn send(&mut self, code: u8) -> PsydkResult<()> {
+        use std::io::Write;
+        self.0.lock().unwrap().write_all(&[code])?;
+        Ok(())
+    }
+}
+
+/// Sets the data pins of a parallel (LPT) port through the Linux `ppdev` driver. The pins are
+/// reset to 0 after the pulse width on a background thread, so sending does not block. Sending
+/// while a pulse is still active is an error, as the marker would be delayed until the pulse ends.
+#[cfg(target_os = "linux")]
+pub struct ParallelPortSink {
+    sender: std::sync::mpsc::Sender<u8>,
+    /// Set while the pins are set, until they are reset to 0.
+    active: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(target_os = "linux")]
+impl ParallelPortSink {
+    // ioctl requests from linux/ppdev.h
+    const PPCLAIM: libc::c_ulong = 0x708b;
+    const PPWDATA: libc::c_ulong = 0x4001_7086;
+
+    pub fn open(path: &str, pulse_width: Duration) -> PsydkResult<Self> {
+        use std::os::fd::AsRawFd;
+
+        let file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let fd = file.as_raw_fd();
+        if unsafe { libc::ioctl(fd, Self::PPCLAIM as _) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let write = move |value: u8| {
+            if unsafe { libc::ioctl(fd, Self::PPWDATA as _, &value as *const u8) } != 0 {
+                log::error!(
+                    "Failed to write to the parallel port: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        };
+        write(0);
+
+        let active = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (sender, receiver) = std::sync::mpsc::channel::<u8>();
+        let thread_active = active.clone();
+        std::thread::spawn(move || {
+            // keep the file (and the claim on the port) open as long as the sink exists
+            let _file = file;
+            for code in receiver {
+                write(code);
+                if !pulse_width.is_zero() {
+                    std::thread::sleep(pulse_width);
+                    write(0);
+                }
+                thread_active.store(false, std::sync::atomic::Ordering::Release);
+            }
+        });
+
+        Ok(Self { sender, active })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl MarkerSink for ParallelPortSink {
+    fn send(&mut self, code: u8) -> PsydkResult<()> {
+        if self.active.swap(true, std::sync::atomic::Ordering::AcqRel) {
+            return Err(PsydkError::CustomError(format!(
+                "Code {code} was not sent to the parallel port, as the previous pulse is still active. Send \
+                 markers at least the pulse width apart."
+            )));
+        }
+        self.sender.send(code).map_err(|_| {
+            self.active.store(false, std::sync::atomic::Ordering::Release);
+            PsydkError::CustomError("The parallel port thread has stopped".into())
+        })
+    }
+}
+
+/// A marker that has been sent.
+#[derive(Debug, Clone)]
+pub struct SentMarker {
+    pub name: String,
+    pub code: u8,
+    pub time: Instant,
+}
+
+/// A registry of named event codes that sends markers to all configured sinks at once.
+///
+/// Codes must be unique and between 1 and 255 (0 is the resting state of a trigger port), so that
+/// every code in the recording maps back to exactly one event.
+#[derive(Default)]
+pub struct Markers {
+    codes: BTreeMap<String, u8>,
+    sinks: Vec<Box<dyn MarkerSink>>,
+    /// All markers that have been sent, in order.
+    pub log: Vec<SentMarker>,
+}
+
+impl Markers {
+    pub fn register(&mut self, name: &str, code: i64) -> PsydkResult<()> {
+        let code = u8::try_from(code).ok().filter(|code| *code != 0).ok_or_else(|| {
+            PsydkError::ParameterError(format!(
+                "Invalid code {code} for marker \"{name}\", must be between 1 and 255"
+            ))
+        })?;
+        if let Some(existing) = self.codes.get(name) {
+            return Err(PsydkError::ParameterError(format!(
+                "Marker \"{name}\" is already registered with code {existing}"
+            )));
+        }
+        if let Some(other) = self.name(code) {
+            return Err(PsydkError::ParameterError(format!(
+                "Code {code} is already used by marker \"{other}\""
+            )));
+        }
+        self.codes.insert(name.to_string(), code);
+        Ok(())
+    }
+
+    pub fn add_sink(&mut self, sink: impl MarkerSink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn code(&self, name: &str) -> Option<u8> {
+        self.codes.get(name).copied()
+    }
+
+    pub fn name(&self, code: u8) -> Option<&str> {
+        self.codes
+            .iter()
+            .find(|(_, c)| **c == code)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Send the marker with the given name to all sinks. Unknown names are an error, so that a
+    /// typo can't produce an unregistered code. The marker is logged even if a sink failed, and is
+    /// returned together with the first error of the sinks.
+    pub fn send(&mut self, name: &str) -> PsydkResult<(SentMarker, PsydkResult<()>)> {
+        let code = self
+            .code(name)
+            .ok_or_else(|| PsydkError::ParameterError(format!("Marker \"{name}\" has not been registered")))?;
+
+        let time = Instant::now();
+        // send to every sink even if one fails, and report the first error
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.send(code) {
+                result = result.and(Err(e));
+            }
+        }
+
+        let marker = SentMarker {
+            name: name.to_string(),
+            code,
+            time,
+        };
+        self.log.push(marker.clone());
+        Ok((marker, result))
+    }
+}
+
+/// Sends event markers (triggers) to EEG/MEG amplifiers, eye trackers, and log files.
+///
+/// Event codes are registered once by name, and markers are then sent by name. Sending an
+/// unregistered name, or registering the same code twice, is an error, which prevents trigger
+/// tables in the experiment and the analysis from drifting apart. Every marker goes to all
+/// configured sinks at once.
+///
+/// Other outputs, e.g. Lab Streaming Layer through `pylsl`, can be added with `add_callback`.
+///
+/// Parameters
+/// ----------
+/// codes : dict[str, int], optional
+///   The event codes to register, e.g. `{"stim_onset": 1, "response": 2}`.
+/// parallel_port : str, optional
+///   A parallel port device, e.g. "/dev/parport0" (Linux only).
+/// pulse_width : float, optional
+///   How long the parallel port pins stay set, in seconds. Defaults to 0.005. If 0, the pins are
+///   not reset. Sending a marker before the previous pulse has ended raises an error instead of
+///   delaying the marker.
+/// serial_port : str, optional
+///   A serial port, e.g. "/dev/ttyUSB0" or "COM3". Requires the `serial` feature.
+/// baud_rate : int, optional
+///   The baud rate of the serial port. Defaults to 115200.
+/// timeline : Timeline, optional
+///   A timeline that every marker is added to as a "marker" event.
+#[cfg(feature = "python")]
+#[pyclass(name = "Markers")]
+pub struct PyMarkers {
+    markers: Markers,
+    timelines: Vec<Py<PyTimeline>>,
+    callbacks: Vec<PyObject>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMarkers {
+    #[new]
+    #[pyo3(signature = (codes = None, parallel_port = None, pulse_width = 0.005, serial_port = None, baud_rate = 115200, timeline = None))]
+    fn __new__(
+        codes: Option<BTreeMap<String, i64>>,
+        parallel_port: Option<&str>,
+        pulse_width: f64,
+        serial_port: Option<&str>,
+        baud_rate: u32,
+        timeline: Option<Py<PyTimeline>>,
+    ) -> PyResult<Self> {
+        let mut markers = Markers::default();
+        for (name, code) in codes.unwrap_or_default() {
+            markers.register(&name, code)?;
+        }
+
+        if let Some(path) = parallel_port {
+            let pulse_width = Duration::try_from_secs_f64(pulse_width).map_err(|_| {
+                PsydkError::ParameterError(format!("Invalid pulse width {pulse_width}, must be non-negative"))
+            })?;
+            #[cfg(target_os = "linux")]
+            markers.add_sink(ParallelPortSink::open(path, pulse_width)?);
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = pulse_width;
+                return Err(PsydkError::ParameterError(format!(
+                    "Can't open parallel port {path}, parallel ports are only supported on Linux"
+                ))
+                .into());
+            }
+        }
+
+        if let Some(port) = serial_port {
+            #[cfg(feature = "serial")]
+            markers.add_sink(SerialSink::open(port, baud_rate)?);
+            #[cfg(not(feature = "serial"))]
+            {
+                let _ = baud_rate;
+                return Err(PsydkError::ParameterError(format!(
+                    "Can't open serial port {port}, psydk was built without the `serial` feature"
+                ))
+                .into());
+            }
+        }
+
+        Ok(Self {
+            markers,
+            timelines: timeline.into_iter().collect(),
+            callbacks: Vec::new(),
+        })
+    }
+
+    /// Register an event code.
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str
+    ///   The name of the event.
+    /// code : int
+    ///   The code, between 1 and 255. Must not be used by another event.
+    #[pyo3(name = "register")]
+    fn py_register(&mut self, name: &str, code: i64) -> PyResult<()> {
+        Ok(self.markers.register(name, code)?)
+    }
+
+    /// Send a marker to all sinks.
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str
+    ///   The name of a registered event.
+    ///
+    /// Returns
+    /// -------
+    /// Timestamp
+    ///   The time the marker was sent.
+    #[pyo3(name = "send")]
+    fn py_send(&mut self, py: Python, name: &str) -> PyResult<Timestamp> {
+        // the marker is logged (and passed on below) even if a sink failed
+        let (sent, result) = self.markers.send(name)?;
+
+        for timeline in &self.timelines {
+            timeline.borrow_mut(py).0.add(TimelineEvent {
+                kind: "marker".to_string(),
+                time: sent.time,
+                label: Some(sent.name.clone()),
+                data: vec![("code".to_string(), sent.code.to_string())],
+            });
+        }
+        for callback in &self.callbacks {
+            callback.call1(py, (sent.name.clone(), sent.code, Timestamp::from(sent.time)))?;
+        }
+
+        result?;
+        Ok(sent.time.into())
+    }
+
+    /// Send the marker registered with the given code. See `send`.
+    #[pyo3(name = "send_code")]
+    fn py_send_code(&mut self, py: Python, code: u8) -> PyResult<Timestamp> {
+        let name = self
+            .markers
+            .name(code)
+            .ok_or_else(|| PsydkError::ParameterError(format!("Code {code} has not been registered")))?
+            .to_string();
+        self.py_send(py, &name)
+    }
+
+    /// Add every marker to a timeline as a "marker" event.
+    #[pyo3(name = "add_timeline")]
+    fn py_add_timeline(&mut self, timeline: Py<PyTimeline>) {
+        self.timelines.push(timeline);
+    }
+
+    /// Call a function for every marker, e.g. to push it to a Lab Streaming Layer outlet. The
+    /// function is called with the name, the code, and the timestamp of the marker.
+    #[pyo3(name = "add_callback")]
+    fn py_add_callback(&mut self, callback: PyObject) {
+        self.callbacks.push(callback);
+    }
+
+    /// Add the code of every marker to the data that a physiological inlet records, see
+    /// `BioInlet.record`.
+    #[pyo3(name = "add_bio_inlet")]
+    fn py_add_bio_inlet(&mut self, inlet: PyRef<PyBioInlet>) {
+        self.markers.add_sink(inlet.0.marker_sink());
+    }
+
+    /// The code of a registered event, or None.
+    #[pyo3(name = "code")]
+    fn py_code(&self, name: &str) -> Option<u8> {
+        self.markers.code(name)
+    }
+
+    /// All registered codes by name.
+    #[getter(codes)]
+    fn py_codes(&self) -> BTreeMap<String, u8> {
+        self.markers.codes.clone()
+    }
+
+    /// All markers that have been sent, as dictionaries with name, code, and timestamp.
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.markers
+            .log
+            .iter()
+            .map(|marker| {
+                let dict = PyDict::new(py);
+                dict.set_item("name", &marker.name)?;
+                dict.set_item("code", marker.code)?;
+                dict.set_item("timestamp", Timestamp::from(marker.time))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Markers(codes={:?}, sinks={}, sent={})",
+            self.markers.codes,
+            self.markers.sinks.len(),
+            self.markers.log.len()
+        )
+    }
+}
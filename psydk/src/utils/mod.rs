@@ -1,28 +1,189 @@
 use fs4::FileExt;
-use std::fs::OpenOptions;
-use std::io::{BufWriter, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Sender};
-use std::thread;
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 
+#[cfg(feature = "python")]
 use pyo3::types::{PyDict, PyDictMethods};
-use pyo3::{pyclass, pymethods, Bound, Py, PyObject, PyRef, PyRefMut, PyResult};
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, Py, PyObject, PyRef, PyRefMut, PyResult, Python};
+
+pub mod manifest;
+pub mod markers;
+pub mod random;
+mod recorder;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "upload")]
+pub mod upload;
+
+pub use recorder::AudioRecorder;
+#[cfg(feature = "python")]
+pub use recorder::PyAudioRecorder;
+
+/// The number of records that can be queued for a `CSVWriter` before `write_record` blocks, so that
+/// a slow disk slows down the experiment instead of filling up memory.
+const CSV_QUEUE_CAPACITY: usize = 1024;
+
+/// A command for the writing thread of a `CSVWriter`.
+#[derive(Debug)]
+pub enum WriterCommand {
+    Record(Vec<String>),
+    /// Sync all records written so far to disk and report back.
+    Flush(Sender<std::io::Result<()>>),
+    /// Complete the current file and continue in a new one.
+    Rotate,
+}
 
 #[derive(Debug, Clone)]
 pub struct CSVWriter {
     pub path: PathBuf,
     pub delimiter: char,
     pub headers: Vec<String>,
-    pub record_sender: Option<Sender<Vec<String>>>,
+    /// Shared by all clones, so that closing one of them closes the writer.
+    record_sender: Arc<Mutex<Option<SyncSender<WriterCommand>>>>,
+    /// The file that is currently written to, which changes when the file is rotated.
+    current_path: Arc<Mutex<PathBuf>>,
+    /// The error that stopped the writing thread, reported by the next call.
+    error: Arc<Mutex<Option<(std::io::ErrorKind, String)>>>,
+    /// The writing thread, which records the file as complete once all records have been written.
+    thread: Arc<Mutex<Option<JoinHandle<std::io::Result<()>>>>>,
+}
+
+/// A file that a `CSVWriter` is writing to.
+struct CSVFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    rows: u64,
+    bytes: u64,
+}
+
+impl CSVFile {
+    /// Open `path` for writing. Every record is flushed as it is written, so that the records written
+    /// so far survive if the writer is never closed, and the file is recorded as complete in the
+    /// session manifest once it is finished. When appending to an existing file, its header must
+    /// match `headers`.
+    fn open(
+        path: &Path,
+        delimiter: char,
+        headers: &[String],
+        write_headers: bool,
+        append: bool,
+    ) -> std::io::Result<Self> {
+        // check if the file path exists and is writable
+        if !append && path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("File {} already exists", path.display()),
+            ));
+        }
+
+        let header_line = headers.join(&delimiter.to_string());
+        let has_header = write_headers && !headers.is_empty();
+
+        let mut rows = 0;
+        if append && path.exists() {
+            let mut lines = BufReader::new(File::open(path)?).lines();
+            match lines.next().transpose()? {
+                Some(first_line) if has_header && first_line.trim_end_matches('\r') != header_line => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Can't append to {}, its header \"{}\" does not match the headers \"{}\"",
+                            path.display(),
+                            first_line.trim_end_matches('\r'),
+                            header_line
+                        ),
+                    ));
+                }
+                Some(_) => rows = lines.count() as u64 + !has_header as u64,
+                None => {}
+            }
+        }
+
+        let file = OpenOptions::new().write(true).create(true).append(true).open(path)?;
+
+        // Lock the file for writing
+        file.try_lock_exclusive()?;
+        manifest::record_open(path);
+
+        let bytes = file.metadata()?.len();
+        let mut file = Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            rows,
+            bytes,
+        };
+
+        // Write headers if they are provided (and the file doesn't have them already)
+        if has_header && bytes == 0 {
+            file.write_line(&header_line)?;
+        }
+        Ok(file)
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        // Flush the writer to ensure data is written to the file
+        self.writer.flush()?;
+        self.bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+
+    /// Sync and unlock the file, and record it as complete.
+    fn finish(self) -> std::io::Result<()> {
+        let file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        file.unlock()?;
+
+        manifest::record_complete(&self.path, Some(self.rows));
+        Ok(())
+    }
+}
+
+/// The path of the `index`th file of a rotated writer, e.g. `data_2.csv`. The first file is `path`.
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{stem}_{index}.{}", extension.to_string_lossy())),
+        None => path.with_file_name(format!("{stem}_{index}")),
+    }
+}
+
+/// The first rotated file after the `index`th one that does not exist yet, e.g. because files
+/// of an earlier session are in the same directory. Returns its index and path.
+fn next_rotated_path(path: &Path, mut index: u32) -> (u32, PathBuf) {
+    loop {
+        index += 1;
+        let next = rotated_path(path, index);
+        if !next.exists() {
+            return (index, next);
+        }
+    }
 }
 
 impl CSVWriter {
+    /// Create a writer for `path`. If `rotate_size` is given, the writer continues in a new file
+    /// (`<name>_1.csv`, `<name>_2.csv`, ..., skipping names that are taken) whenever the current file is larger than `rotate_size`
+    /// bytes. Files can also be rotated explicitly with `rotate`, e.g. after every block.
     pub fn new(
         path: String,
         delimiter: char,
         headers: Vec<String>,
         write_headers: bool,
         append: bool,
+        rotate_size: Option<u64>,
     ) -> Result<Self, std::io::Error> {
         // check if directory exists
         let path = std::path::Path::new(&path).to_path_buf();
@@ -41,108 +202,176 @@ impl CSVWriter {
             ));
         }
 
-        // check if the file path exists and is writable
-        if !append && std::path::Path::new(&path).exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("File {} already exists", path.display()),
-            ));
-        }
+        let mut file = CSVFile::open(&path, delimiter, &headers, write_headers, append)?;
+        let current_path = Arc::new(Mutex::new(path.clone()));
 
         // Create the thread that will write to the CSV file
-        let (tx, rx) = channel::<Vec<String>>();
+        let (tx, rx) = sync_channel::<WriterCommand>(CSV_QUEUE_CAPACITY);
+        let error = Arc::new(Mutex::new(None));
         let path_clone = path.clone();
-        let delimiter_clone = delimiter;
+        let current_path_clone = current_path.clone();
+        let error_clone = error.clone();
         let headers_clone = headers.clone();
 
-        thread::spawn(move || {
-            let file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(append)
-                .open(path_clone)
-                .expect("Unable to open file");
-
-            // Lock the file for writing
-            file.try_lock_exclusive().expect("Unable to lock file for writing");
-
-            let mut writer = BufWriter::new(file);
-
-            // Write headers if they are provided
-            if !headers_clone.is_empty() && write_headers {
-                let header_line = headers_clone.join(&delimiter_clone.to_string());
-                writeln!(writer, "{}", header_line).expect("Unable to write headers");
-                // Flush the writer to ensure headers are written to the file
-                writer.flush().expect("Unable to flush writer");
-            }
+        let thread = thread::spawn(move || -> std::io::Result<()> {
+            let mut index = 0;
 
-            // Write records received from the channel
-            loop {
-                match rx.recv() {
-                    Ok(record) => {
-                        let record_line = record.join(&delimiter_clone.to_string());
-                        writeln!(writer, "{}", record_line).expect("Unable to write record");
-                        // Flush the writer to ensure data is written to the file
-                        writer.flush().expect("Unable to flush writer");
-                    }
-                    Err(_) => {
-                        // Channel closed, exit the loop
-                        break;
+            // Handle commands until the channel is closed
+            let result = (|| {
+                while let Ok(command) = rx.recv() {
+                    let rotate = match command {
+                        WriterCommand::Record(record) => {
+                            file.write_line(&record.join(&delimiter.to_string()))?;
+                            file.rows += 1;
+                            rotate_size.map_or(false, |size| file.bytes >= size)
+                        }
+                        WriterCommand::Flush(reply) => {
+                            let _ = reply.send(file.sync());
+                            false
+                        }
+                        WriterCommand::Rotate => true,
+                    };
+
+                    if rotate {
+                        let (next_index, next) = next_rotated_path(&path_clone, index);
+                        index = next_index;
+                        let previous = std::mem::replace(
+                            &mut file,
+                            CSVFile::open(&next, delimiter, &headers_clone, write_headers, false)?,
+                        );
+                        previous.finish()?;
+                        *current_path_clone.lock().unwrap() = next;
                     }
                 }
+                file.finish()
+            })();
+
+            // the file stays incomplete in the manifest, so that the failure is visible in the data
+            if let Err(e) = &result {
+                log::error!(
+                    "Failed to write to {}: {}",
+                    current_path_clone.lock().unwrap().display(),
+                    e
+                );
+                *error_clone.lock().unwrap() = Some((e.kind(), e.to_string()));
             }
-            // Unlock the file after writing
-            writer.get_ref().unlock().expect("Unable to unlock file");
+            result
         });
 
         Ok(Self {
             path,
             delimiter,
             headers,
-            record_sender: Some(tx),
+            record_sender: Arc::new(Mutex::new(Some(tx))),
+            current_path,
+            error,
+            thread: Arc::new(Mutex::new(Some(thread))),
         })
     }
-    pub fn write_record(&self, record: Vec<String>) -> Result<(), std::io::Error> {
-        if let Some(sender) = &self.record_sender {
-            sender.send(record).expect("Unable to send record");
-            Ok(())
+
+    /// The error that stopped the writing thread, if any.
+    fn error(&self) -> Option<std::io::Error> {
+        let error = self.error.lock().unwrap();
+        error
+            .as_ref()
+            .map(|(kind, message)| std::io::Error::new(*kind, message.clone()))
+    }
+
+    /// Queue a command for the writing thread. Blocks while the queue is full.
+    fn send(&self, command: WriterCommand) -> Result<(), std::io::Error> {
+        if let Some(error) = self.error() {
+            return Err(error);
+        }
+        // the sender is cloned, so that the lock is not held while the queue is full
+        let sender = self.record_sender.lock().unwrap().clone();
+        if let Some(sender) = sender {
+            sender.send(command).map_err(|_| {
+                self.error()
+                    .unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "CSV writer has stopped"))
+            })
         } else {
             Err(std::io::Error::new(std::io::ErrorKind::Other, "CSV writer is closed"))
         }
     }
 
-    pub fn close(&mut self) {
+    /// Queue a record for writing. Errors of the writing thread are reported by the next call, as
+    /// records are written in the background.
+    pub fn write_record(&self, record: Vec<String>) -> Result<(), std::io::Error> {
+        self.send(WriterCommand::Record(record))
+    }
+
+    /// Whether the writer is open and has not failed.
+    pub fn healthy(&self) -> bool {
+        let running = match self.thread.lock().unwrap().as_ref() {
+            Some(thread) => !thread.is_finished(),
+            None => false,
+        };
+        self.record_sender.lock().unwrap().is_some() && running && self.error.lock().unwrap().is_none()
+    }
+
+    /// Wait until all records have been written and synced to disk.
+    pub fn flush(&self) -> Result<(), std::io::Error> {
+        let (reply_sender, reply_receiver) = channel();
+        self.send(WriterCommand::Flush(reply_sender))?;
+        reply_receiver.recv().map_err(|_| {
+            self.error()
+                .unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "CSV writer has stopped"))
+        })?
+    }
+
+    /// Complete the current file and continue in a new one.
+    pub fn rotate(&self) -> Result<(), std::io::Error> {
+        self.send(WriterCommand::Rotate)
+    }
+
+    /// The file that records are currently written to. This is `path` unless the file was rotated.
+    pub fn current_path(&self) -> PathBuf {
+        self.current_path.lock().unwrap().clone()
+    }
+
+    /// Close the writer and wait until the file has been synced and recorded as complete. Clones of
+    /// the writer share the file, so closing one of them closes all of them.
+    pub fn close(&mut self) -> Result<(), std::io::Error> {
         // Close the channel to signal the writing thread to exit
-        if let Some(sender) = self.record_sender.take() {
-            drop(sender);
+        drop(self.record_sender.lock().unwrap().take());
+
+        // the first call waits for the thread, later calls (e.g. from clones) have nothing to do
+        match self.thread.lock().unwrap().take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "CSV writer thread panicked"))?,
+            None => Ok(()),
         }
     }
 }
 
+#[cfg(feature = "python")]
 #[pyclass]
 #[derive(Clone)]
-#[pyo3(name = "CSVWriter")]
+#[cfg_attr(feature = "python", pyo3(name = "CSVWriter"))]
 pub struct PyCSVWriter(pub CSVWriter);
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyCSVWriter {
     #[new]
+    #[pyo3(signature = (path, delimiter, headers, write_headers, append, rotate_size = None))]
     pub fn new(
         path: String,
         delimiter: char,
         headers: Vec<String>,
         write_headers: bool,
         append: bool,
+        rotate_size: Option<u64>,
     ) -> PyResult<Self> {
         Ok(PyCSVWriter(
-            CSVWriter::new(path, delimiter, headers, write_headers, append)
+            CSVWriter::new(path, delimiter, headers, write_headers, append, rotate_size)
                 .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to create CSV writer: {}", e)))?,
         ))
     }
 
-    pub fn write_record(&self, record: Vec<String>) -> PyResult<()> {
-        self.0
-            .write_record(record)
+    pub fn write_record(&self, py: Python, record: Vec<String>) -> PyResult<()> {
+        py.allow_threads(|| self.0.write_record(record))
             .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to write record to CSV: {}", e)))
     }
 
@@ -169,11 +398,41 @@ impl PyCSVWriter {
             }
         }
 
-        self.write_record(record_vec)
+        self.write_record(record.py(), record_vec)
     }
 
-    pub fn close(&mut self) {
-        self.0.close();
+    /// Whether the writer is open and all records so far could be written. Errors are also raised
+    /// by the next call to `write_record` or `write_dict`.
+    pub fn healthy(&self) -> bool {
+        self.0.healthy()
+    }
+
+    /// Wait until all records that were written so far are on disk.
+    pub fn flush(&self, py: Python) -> PyResult<()> {
+        py.allow_threads(|| self.0.flush())
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to flush CSV writer: {}", e)))
+    }
+
+    /// Complete the current file and continue in a new one, e.g. at the end of a block. The files
+    /// are named `<name>_1.csv`, `<name>_2.csv`, and so on, and each starts with the headers.
+    /// Names that are already taken (e.g. by files of an earlier session) are skipped.
+    pub fn rotate(&self, py: Python) -> PyResult<()> {
+        py.allow_threads(|| self.0.rotate())
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to rotate CSV file: {}", e)))
+    }
+
+    /// The file that records are currently written to.
+    #[getter]
+    pub fn path(&self) -> PathBuf {
+        self.0.current_path()
+    }
+
+    /// Close the writer. The file is synced to disk and recorded as complete in the session
+    /// manifest. A file that was not closed is listed as incomplete.
+    pub fn close(&mut self) -> PyResult<()> {
+        self.0
+            .close()
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to close CSV writer: {}", e)))
     }
 
     // allows Window to be used as a context manager
@@ -189,7 +448,6 @@ impl PyCSVWriter {
         traceback: Bound<'_, crate::PyAny>,
     ) -> PyResult<()> {
         // close the CSV writer
-        slf.0.close();
-        Ok(())
+        slf.close()
     }
 }
@@ -0,0 +1,33 @@
+//! The random number generator of the session.
+//!
+//! Everything that randomizes the design of an experiment draws from one generator, which is
+//! seeded once per process, either with the seed given to the `psydk` command with `--seed` or
+//! with a random seed. The seed is listed in the manifest, so a session can be reproduced.
+
+use std::sync::{Mutex, OnceLock};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+static SESSION_SEED: OnceLock<u64> = OnceLock::new();
+static SESSION_RNG: Mutex<Option<StdRng>> = Mutex::new(None);
+
+/// The seed of the session generator.
+pub fn session_seed() -> u64 {
+    *SESSION_SEED.get_or_init(|| {
+        crate::cli::launch_options()
+            .and_then(|options| options.seed)
+            .unwrap_or_else(|| rand::thread_rng().gen())
+    })
+}
+
+/// Call `f` with the session generator.
+pub fn with_session_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    let mut rng = SESSION_RNG.lock().unwrap();
+    f(rng.get_or_insert_with(|| StdRng::seed_from_u64(session_seed())))
+}
+
+/// Draw a seed for a generator of its own from the session generator, so that it can be reported
+/// and reused.
+pub fn next_seed() -> u64 {
+    with_session_rng(|rng| rng.gen())
+}
@@ -0,0 +1,314 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, PyAny, PyRef, PyRefMut, PyResult};
+use timed_audio::InputChunk;
+
+use super::manifest;
+use crate::audio::PyInputStream;
+use crate::errors::{PsydkError, PsydkResult};
+use crate::time::Timestamp;
+
+/// Bit depth of FLAC recordings.
+const FLAC_BITS_PER_SAMPLE: u16 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecordingFormat {
+    Wav,
+    Flac,
+}
+
+/// Records audio from an input stream to a WAV or FLAC file on a background thread.
+///
+/// For every captured buffer, the index of its first sample and the time it was captured are
+/// written to a `<name>_timestamps.csv` file next to the recording, so that the audio can be
+/// aligned offline with frame onsets, triggers, and responses.
+pub struct AudioRecorder {
+    path: PathBuf,
+    sample_rate: u32,
+    start_time: Instant,
+    /// (sample index, capture time) of the first sample of every recorded buffer.
+    anchors: Arc<Mutex<Vec<(u64, Instant)>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<PsydkResult<()>>>,
+}
+
+impl AudioRecorder {
+    pub fn new(receiver: Receiver<InputChunk>, path: PathBuf, sample_rate: u32, channels: u16) -> PsydkResult<Self> {
+        let format = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) {
+            Some(ext) if ext == "wav" => RecordingFormat::Wav,
+            Some(ext) if ext == "flac" => RecordingFormat::Flac,
+            _ => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Unsupported recording format for {}. Use a .wav or .flac file.",
+                    path.display()
+                )))
+            }
+        };
+
+        if path.exists() {
+            return Err(PsydkError::FileExistsAndNotEmptyError(path.display().to_string()));
+        }
+
+        // the recording is written to a partial file that is moved into place once it is complete,
+        // and FLAC is encoded from a temporary WAV file once the recording has stopped
+        let wav_path = match format {
+            RecordingFormat::Wav => manifest::partial_path(&path),
+            RecordingFormat::Flac => path.with_extension("flac.part.wav"),
+        };
+        let spec = match format {
+            RecordingFormat::Wav => hound::WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+            RecordingFormat::Flac => hound::WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: FLAC_BITS_PER_SAMPLE,
+                sample_format: hound::SampleFormat::Int,
+            },
+        };
+        let mut wav_writer = hound::WavWriter::create(&wav_path, spec).map_err(to_io_error)?;
+
+        let timestamps_path = timestamps_path(&path);
+        let mut timestamps = BufWriter::new(File::create(manifest::partial_path(&timestamps_path))?);
+        writeln!(timestamps, "sample_index,time")?;
+        manifest::record_open(&path);
+        manifest::record_open(&timestamps_path);
+
+        let start_time = Instant::now();
+        let anchors = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let _anchors = anchors.clone();
+        let _stop = stop.clone();
+        let _path = path.clone();
+        let _timestamps_path = timestamps_path.clone();
+
+        let thread = thread::spawn(move || -> PsydkResult<()> {
+            let mut first_frame = None;
+            let mut n_buffers = 0;
+
+            let mut write_chunk = |chunk: InputChunk| -> PsydkResult<()> {
+                let sample_index = chunk.first_frame - *first_frame.get_or_insert(chunk.first_frame);
+                _anchors.lock().unwrap().push((sample_index, chunk.time));
+                n_buffers += 1;
+                writeln!(
+                    timestamps,
+                    "{},{}",
+                    sample_index,
+                    seconds_between(start_time, chunk.time)
+                )?;
+
+                for sample in chunk.data {
+                    match format {
+                        RecordingFormat::Wav => wav_writer.write_sample(sample),
+                        RecordingFormat::Flac => wav_writer.write_sample(to_i24(sample)),
+                    }
+                    .map_err(to_io_error)?;
+                }
+                Ok(())
+            };
+
+            while !_stop.load(Ordering::Relaxed) {
+                match receiver.recv_timeout(Duration::from_millis(50)) {
+                    Ok(chunk) => write_chunk(chunk)?,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            // the buffers that were captured before the recording was stopped are still queued
+            while let Ok(chunk) = receiver.try_recv() {
+                write_chunk(chunk)?;
+            }
+
+            wav_writer.finalize().map_err(to_io_error)?;
+            timestamps.flush()?;
+            drop(timestamps);
+
+            if format == RecordingFormat::Flac {
+                encode_flac(&wav_path, &manifest::partial_path(&_path))?;
+                std::fs::remove_file(&wav_path)?;
+            }
+
+            manifest::commit(&manifest::partial_path(&_path), &_path)?;
+            manifest::record_complete(&_path, None);
+            manifest::commit(&manifest::partial_path(&_timestamps_path), &_timestamps_path)?;
+            manifest::record_complete(&_timestamps_path, Some(n_buffers));
+            Ok(())
+        });
+
+        Ok(Self {
+            path,
+            sample_rate,
+            start_time,
+            anchors,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stop recording and wait until the file has been written.
+    pub fn stop(&mut self) -> PsydkResult<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| PsydkError::CustomError("The audio recorder thread panicked".into()))?,
+            None => Ok(()),
+        }
+    }
+
+    /// The time at which the recording was started. Times in the timestamps file are relative to it.
+    pub fn start_time(&self) -> Instant {
+        self.start_time
+    }
+
+    /// The time at which the sample with the given index was captured.
+    pub fn timestamp_of(&self, sample_index: u64) -> Option<Instant> {
+        let anchors = self.anchors.lock().unwrap();
+        let (index, time) = anchors.iter().rev().find(|(index, _)| *index <= sample_index)?;
+        Some(*time + Duration::from_secs_f64((sample_index - index) as f64 / self.sample_rate as f64))
+    }
+
+    /// The index of the sample that was captured at the given time.
+    pub fn sample_index_at(&self, time: Instant) -> Option<u64> {
+        let anchors = self.anchors.lock().unwrap();
+        let (index, anchor_time) = anchors.iter().rev().find(|(_, t)| *t <= time)?;
+        let offset = time.duration_since(*anchor_time).as_secs_f64() * self.sample_rate as f64;
+        Some(index + offset.round() as u64)
+    }
+}
+
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            log::error!("Failed to finish the recording {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn timestamps_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("{stem}_timestamps.csv"))
+}
+
+/// Seconds from `start` to `time`, negative if `time` is before `start`.
+fn seconds_between(start: Instant, time: Instant) -> f64 {
+    match time.checked_duration_since(start) {
+        Some(d) => d.as_secs_f64(),
+        None => -start.duration_since(time).as_secs_f64(),
+    }
+}
+
+fn to_i24(sample: f32) -> i32 {
+    let max = (1 << (FLAC_BITS_PER_SAMPLE - 1)) - 1;
+    (sample.clamp(-1.0, 1.0) * max as f32).round() as i32
+}
+
+fn to_io_error(e: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+fn encode_flac(wav_path: &Path, flac_path: &Path) -> PsydkResult<()> {
+    let reader = hound::WavReader::new(BufReader::new(File::open(wav_path)?)).map_err(to_io_error)?;
+    let spec = reader.spec();
+    let samples = reader
+        .into_samples::<i32>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(to_io_error)?;
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| PsydkError::CustomError(format!("Invalid FLAC encoder configuration: {e:?}")))?;
+    let source = flacenc::source::MemSource::from_samples(
+        &samples,
+        spec.channels as usize,
+        spec.bits_per_sample as usize,
+        spec.sample_rate as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| PsydkError::CustomError(format!("Failed to encode FLAC: {e:?}")))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| PsydkError::CustomError(format!("Failed to encode FLAC: {e:?}")))?;
+    std::fs::write(flac_path, sink.as_slice())?;
+    Ok(())
+}
+
+/// Records audio from an input stream to a WAV or FLAC file.
+///
+/// Alongside the recording, a `<name>_timestamps.csv` file maps sample indices to capture times
+/// (in seconds since `start_time`), so the audio can be aligned offline with other events.
+///
+/// Parameters
+/// ----------
+/// stream : InputStream
+///   The input stream to record from.
+/// path : str
+///   The file to write to. The format is determined by the extension (`.wav` or `.flac`).
+#[cfg(feature = "python")]
+#[pyclass]
+#[cfg_attr(feature = "python", pyo3(name = "AudioRecorder"))]
+pub struct PyAudioRecorder(AudioRecorder);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyAudioRecorder {
+    #[new]
+    fn new(stream: PyRef<PyInputStream>, path: PathBuf) -> PyResult<Self> {
+        let stream = &stream.stream;
+        let recorder = AudioRecorder::new(stream.subscribe(), path, stream.sample_rate(), stream.channels())?;
+        Ok(Self(recorder))
+    }
+
+    /// Stop recording and wait until the file has been written.
+    fn stop(&mut self) -> PyResult<()> {
+        Ok(self.0.stop()?)
+    }
+
+    /// The time at which the recording was started.
+    #[getter]
+    fn start_time(&self) -> Timestamp {
+        self.0.start_time().into()
+    }
+
+    /// The time at which the sample with the given index was captured, or `None` if it has not
+    /// been recorded yet.
+    fn timestamp_of(&self, sample_index: u64) -> Option<Timestamp> {
+        self.0.timestamp_of(sample_index).map(Into::into)
+    }
+
+    /// The index of the sample that was captured at the given time, or `None` if the time is
+    /// before the start of the recording.
+    fn sample_index_at(&self, timestamp: Timestamp) -> Option<u64> {
+        self.0.sample_index_at(timestamp.timestamp)
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        mut slf: PyRefMut<Self>,
+        _exc_type: Bound<'_, PyAny>,
+        _exc_value: Bound<'_, PyAny>,
+        _traceback: Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        slf.stop()
+    }
+}
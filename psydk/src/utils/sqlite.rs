@@ -0,0 +1,371 @@
+//! Results stored in a single SQLite database, as an alternative to one CSV file per session.
+//!
+//! The database has the following schema. Every `SqliteWriter` adds a row to `sessions`, so one
+//! file can hold all sessions of a study. Trial data and event data are JSON objects, which can be
+//! queried with SQLite's JSON functions, e.g. `SELECT data ->> 'rt' FROM trials`. Times are UNIX
+//! times in seconds.
+//!
+//! ```sql
+//! CREATE TABLE sessions (
+//!     id INTEGER PRIMARY KEY,
+//!     session TEXT,              -- the session identifier, if any
+//!     started REAL NOT NULL,
+//!     ended REAL                 -- NULL if the writer was not closed
+//! );
+//! CREATE TABLE metadata (
+//!     session_id INTEGER NOT NULL REFERENCES sessions(id),
+//!     key TEXT NOT NULL,
+//!     value TEXT,                -- JSON
+//!     PRIMARY KEY (session_id, key)
+//! );
+//! CREATE TABLE trials (
+//!     id INTEGER PRIMARY KEY,
+//!     session_id INTEGER NOT NULL REFERENCES sessions(id),
+//!     trial INTEGER,
+//!     time REAL NOT NULL,
+//!     data TEXT NOT NULL         -- JSON object
+//! );
+//! CREATE TABLE events (
+//!     id INTEGER PRIMARY KEY,
+//!     session_id INTEGER NOT NULL REFERENCES sessions(id),
+//!     time REAL NOT NULL,
+//!     kind TEXT NOT NULL,
+//!     data TEXT                  -- JSON object
+//! );
+//! ```
+//!
+//! The database is opened in WAL mode, so every write is durable once it returns and a crash never
+//! corrupts rows that were written before.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyString},
+};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::manifest;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    session TEXT,
+    started REAL NOT NULL,
+    ended REAL
+);
+CREATE TABLE IF NOT EXISTS metadata (
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    key TEXT NOT NULL,
+    value TEXT,
+    PRIMARY KEY (session_id, key)
+);
+CREATE TABLE IF NOT EXISTS trials (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    trial INTEGER,
+    time REAL NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    time REAL NOT NULL,
+    kind TEXT NOT NULL,
+    data TEXT
+);
+CREATE INDEX IF NOT EXISTS trials_session ON trials(session_id);
+CREATE INDEX IF NOT EXISTS events_session ON events(session_id, time);
+";
+
+fn to_error(e: rusqlite::Error) -> PsydkError {
+    PsydkError::IOError(std::io::Error::other(e))
+}
+
+fn now() -> f64 {
+    Timestamp::from(Instant::now()).unix()
+}
+
+/// Writes the trials, events, and metadata of one session to a SQLite database.
+#[derive(Debug)]
+pub struct SqliteWriter {
+    path: PathBuf,
+    connection: Mutex<Option<Connection>>,
+    session_id: i64,
+}
+
+impl SqliteWriter {
+    /// Open (or create) the database at `path` and start a new session in it.
+    pub fn open(path: &Path, session: Option<&str>) -> PsydkResult<Self> {
+        let connection = Connection::open(path).map_err(to_error)?;
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(to_error)?;
+        connection.pragma_update(None, "foreign_keys", true).map_err(to_error)?;
+        connection.execute_batch(SCHEMA).map_err(to_error)?;
+
+        connection
+            .execute(
+                "INSERT INTO sessions (session, started) VALUES (?1, ?2)",
+                params![session, now()],
+            )
+            .map_err(to_error)?;
+        let session_id = connection.last_insert_rowid();
+        manifest::record_open(path);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection: Mutex::new(Some(connection)),
+            session_id,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The id of the session in the `sessions` table.
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    fn with_connection<R>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<R>) -> PsydkResult<R> {
+        let connection = self.connection.lock().unwrap();
+        let connection = connection
+            .as_ref()
+            .ok_or_else(|| PsydkError::CustomError("The SQLite writer is closed".into()))?;
+        f(connection).map_err(to_error)
+    }
+
+    /// Set a metadata value (JSON) of the session, replacing an earlier value of the same key.
+    pub fn set_metadata(&self, key: &str, value: &str) -> PsydkResult<()> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO metadata (session_id, key, value) VALUES (?1, ?2, ?3)",
+                params![self.session_id, key, value],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Write the data (a JSON object) of a trial.
+    pub fn write_trial(&self, trial: Option<i64>, time: f64, data: &str) -> PsydkResult<()> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "INSERT INTO trials (session_id, trial, time, data) VALUES (?1, ?2, ?3, ?4)",
+                params![self.session_id, trial, time, data],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Write an event, with optional data (a JSON object).
+    pub fn write_event(&self, kind: &str, time: f64, data: Option<&str>) -> PsydkResult<()> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "INSERT INTO events (session_id, time, kind, data) VALUES (?1, ?2, ?3, ?4)",
+                params![self.session_id, time, kind, data],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// The number of trials written in this session.
+    pub fn trial_count(&self) -> PsydkResult<u64> {
+        self.with_connection(|connection| {
+            connection
+                .query_row(
+                    "SELECT COUNT(*) FROM trials WHERE session_id = ?1",
+                    params![self.session_id],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+        })
+        .map(|count| count.unwrap_or(0) as u64)
+    }
+
+    /// Mark the session as ended and close the database. The WAL is checkpointed into the main file,
+    /// so the database can be copied as a single file.
+    pub fn close(&self) -> PsydkResult<()> {
+        let Some(connection) = self.connection.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let rows = connection
+            .query_row(
+                "SELECT COUNT(*) FROM trials WHERE session_id = ?1",
+                params![self.session_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(to_error)?;
+        connection
+            .execute(
+                "UPDATE sessions SET ended = ?1 WHERE id = ?2",
+                params![now(), self.session_id],
+            )
+            .map_err(to_error)?;
+        connection
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(to_error)?;
+        connection.close().map_err(|(_, e)| to_error(e))?;
+
+        manifest::record_complete(&self.path, Some(rows as u64));
+        Ok(())
+    }
+}
+
+impl Drop for SqliteWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::error!("Failed to close {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Serialize a Python object to JSON. Objects that JSON doesn't support (e.g. timestamps) are
+/// stored as their string representation.
+#[cfg(feature = "python")]
+fn to_json(py: Python, value: &Bound<'_, PyAny>) -> PyResult<String> {
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("default", py.get_type::<PyString>())?;
+    py.import("json")?
+        .call_method("dumps", (value,), Some(&kwargs))?
+        .extract()
+}
+
+fn to_time(timestamp: Option<Timestamp>) -> f64 {
+    timestamp.map_or_else(now, |timestamp| timestamp.unix())
+}
+
+/// Stores trials, events, and session metadata in a SQLite database.
+///
+/// Every writer starts a new session in the database, so all sessions of a study can be kept in one
+/// file. Trial and event data are stored as JSON and can be queried with SQLite's JSON functions.
+/// See the documentation of `psydk::utils::sqlite` for the schema.
+///
+/// Parameters
+/// ----------
+/// path : str
+///   The database file. It is created if it doesn't exist.
+/// session : str, optional
+///   An identifier of the session, e.g. the participant.
+/// metadata : dict, optional
+///   Metadata of the session, e.g. the age of the participant or the version of the experiment.
+#[cfg(feature = "python")]
+#[pyclass]
+#[cfg_attr(feature = "python", pyo3(name = "SqliteWriter"))]
+pub struct PySqliteWriter(SqliteWriter);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PySqliteWriter {
+    #[new]
+    #[pyo3(signature = (path, session = None, metadata = None))]
+    fn new(py: Python, path: PathBuf, session: Option<String>, metadata: Option<Bound<'_, PyDict>>) -> PyResult<Self> {
+        let writer = SqliteWriter::open(&path, session.as_deref())?;
+        if let Some(metadata) = metadata {
+            for (key, value) in metadata.iter() {
+                writer.set_metadata(&key.str()?.to_string(), &to_json(py, &value)?)?;
+            }
+        }
+        Ok(Self(writer))
+    }
+
+    /// The database file.
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.0.path().to_path_buf()
+    }
+
+    /// The id of this session in the `sessions` table.
+    #[getter]
+    fn session_id(&self) -> i64 {
+        self.0.session_id()
+    }
+
+    /// Set a metadata value of the session.
+    ///
+    /// Parameters
+    /// ----------
+    /// key : str
+    ///   The name of the value.
+    /// value : object
+    ///   The value. Must be serializable as JSON.
+    fn set_metadata(&self, py: Python, key: &str, value: Bound<'_, PyAny>) -> PyResult<()> {
+        Ok(self.0.set_metadata(key, &to_json(py, &value)?)?)
+    }
+
+    /// Write the data of a trial.
+    ///
+    /// Parameters
+    /// ----------
+    /// data : dict
+    ///   The data of the trial, e.g. the condition, response, and response time.
+    /// trial : int, optional
+    ///   The number of the trial.
+    /// timestamp : Timestamp, optional
+    ///   The time of the trial. Defaults to now.
+    #[pyo3(signature = (data, trial = None, timestamp = None))]
+    fn write_trial(
+        &self,
+        py: Python,
+        data: Bound<'_, PyDict>,
+        trial: Option<i64>,
+        timestamp: Option<Timestamp>,
+    ) -> PyResult<()> {
+        let data = to_json(py, &data)?;
+        let time = to_time(timestamp);
+        py.allow_threads(|| self.0.write_trial(trial, time, &data))?;
+        Ok(())
+    }
+
+    /// Write an event, e.g. a stimulus onset or a response.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind : str
+    ///   The kind of event, e.g. "onset" or "key_press".
+    /// data : dict, optional
+    ///   Additional data of the event.
+    /// timestamp : Timestamp, optional
+    ///   The time of the event. Defaults to now.
+    #[pyo3(signature = (kind, data = None, timestamp = None))]
+    fn write_event(
+        &self,
+        py: Python,
+        kind: &str,
+        data: Option<Bound<'_, PyDict>>,
+        timestamp: Option<Timestamp>,
+    ) -> PyResult<()> {
+        let data = data.map(|data| to_json(py, &data)).transpose()?;
+        let time = to_time(timestamp);
+        py.allow_threads(|| self.0.write_event(kind, time, data.as_deref()))?;
+        Ok(())
+    }
+
+    /// Mark the session as ended and close the database.
+    fn close(&self) -> PyResult<()> {
+        Ok(self.0.close()?)
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        slf: PyRef<Self>,
+        _exc_type: Bound<'_, PyAny>,
+        _exc_value: Bound<'_, PyAny>,
+        _traceback: Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        slf.close()
+    }
+}
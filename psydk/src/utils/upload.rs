@@ -0,0 +1,641 @@
+//! Upload of data files at the end of a session.
+//!
+//! An uploader pushes the data files of a session and its manifest to an HTTPS endpoint (with a
+//! `PUT` per file) or to an S3 bucket. Files are first added to a local queue, a JSON file that
+//! survives the process, and only removed from it once they have been uploaded, so files that
+//! could not be uploaded (e.g. because the lab computer was offline) are retried at the end of the
+//! next session or with `Uploader.flush`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use hmac::{Hmac, Mac};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::manifest::{self, Manifest};
+use crate::errors::{PsydkError, PsydkResult};
+
+/// The delay before the first retry, which doubles with every further attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How many times (e.g. sessions) the upload of a queued file is attempted before it is dropped
+/// from the queue.
+const MAX_QUEUE_ATTEMPTS: u32 = 10;
+
+/// The uploader that is run at the end of every session, see `Uploader.enable`.
+static SESSION_UPLOADER: Mutex<Option<Uploader>> = Mutex::new(None);
+/// Held while the queue is uploaded, so that uploads in the background and from Python do not
+/// upload the same files or overwrite each other's queue.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where files are uploaded to.
+#[derive(Clone)]
+pub enum UploadTarget {
+    /// Every file is uploaded with `PUT <url>/<key>`.
+    Http {
+        url: String,
+        /// Sent as a bearer token.
+        token: Option<String>,
+        headers: Vec<(String, String)>,
+    },
+    /// Every file is uploaded as `<prefix><key>` to an S3 bucket, or a bucket of an S3-compatible
+    /// service at `endpoint` (addressed path-style).
+    S3 {
+        bucket: String,
+        region: String,
+        prefix: String,
+        endpoint: Option<String>,
+        access_key: String,
+        secret_key: String,
+        session_token: Option<String>,
+    },
+}
+
+/// Shown instead of credentials, which must not end up in logs.
+const REDACTED: &str = "<redacted>";
+
+impl std::fmt::Debug for UploadTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadTarget::Http { url, token, headers } => f
+                .debug_struct("Http")
+                .field("url", url)
+                .field("token", &token.as_ref().map(|_| REDACTED))
+                // headers may carry credentials as well, e.g. an API key
+                .field(
+                    "headers",
+                    &headers.iter().map(|(name, _)| (name, REDACTED)).collect::<Vec<_>>(),
+                )
+                .finish(),
+            UploadTarget::S3 {
+                bucket,
+                region,
+                prefix,
+                endpoint,
+                access_key,
+                secret_key: _,
+                session_token,
+            } => f
+                .debug_struct("S3")
+                .field("bucket", bucket)
+                .field("region", region)
+                .field("prefix", prefix)
+                .field("endpoint", endpoint)
+                .field("access_key", access_key)
+                .field("secret_key", &REDACTED)
+                .field("session_token", &session_token.as_ref().map(|_| REDACTED))
+                .finish(),
+        }
+    }
+}
+
+/// A file waiting to be uploaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedFile {
+    pub path: PathBuf,
+    /// The name of the file at the target, e.g. `<session>/<file name>`.
+    pub key: String,
+    /// How many times the upload has failed so far.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl QueuedFile {
+    pub fn new(path: PathBuf, key: String) -> Self {
+        Self { path, key, attempts: 0 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Uploader {
+    pub target: UploadTarget,
+    /// The JSON file that lists the files waiting to be uploaded.
+    pub queue: PathBuf,
+    /// How often an upload is retried before the file is left in the queue.
+    pub retries: u32,
+    pub timeout: Duration,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The UTC date (`YYYYMMDD`) and time (`YYYYMMDDTHHMMSSZ`) of `time`, as used by AWS signatures.
+fn amz_date(time: SystemTime) -> (String, String) {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let time = format!(
+        "{date}T{:02}{:02}{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+    (date, time)
+}
+
+/// Percent-encode a key for a URL, keeping the slashes.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn load_queue(path: &Path) -> PsydkResult<Vec<QueuedFile>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| PsydkError::CustomError(format!("Invalid upload queue {}: {e}", path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_queue(path: &Path, queue: &[QueuedFile]) -> PsydkResult<()> {
+    let partial = manifest::partial_path(path);
+    let json = serde_json::to_string_pretty(queue).map_err(std::io::Error::other)?;
+    std::fs::write(&partial, json)?;
+    manifest::commit(&partial, path)?;
+    Ok(())
+}
+
+impl Uploader {
+    /// Upload one file, without retrying.
+    fn put(&self, path: &Path, key: &str) -> PsydkResult<()> {
+        let body = std::fs::read(path)?;
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+
+        let request = match &self.target {
+            UploadTarget::Http { url, token, headers } => {
+                let mut request = agent
+                    .put(&format!("{}/{}", url.trim_end_matches('/'), encode_key(key)))
+                    .set("Content-Type", "application/octet-stream")
+                    .set("X-Content-SHA256", &hex(&Sha256::digest(&body)));
+                if let Some(token) = token {
+                    request = request.set("Authorization", &format!("Bearer {token}"));
+                }
+                for (name, value) in headers {
+                    request = request.set(name, value);
+                }
+                request
+            }
+            UploadTarget::S3 {
+                bucket,
+                region,
+                prefix,
+                endpoint,
+                access_key,
+                secret_key,
+                session_token,
+            } => {
+                let key = format!("{prefix}{key}");
+                let (host, uri, url) = match endpoint {
+                    Some(endpoint) => {
+                        let endpoint = endpoint.trim_end_matches('/');
+                        let host = endpoint.split_once("://").map_or(endpoint, |(_, host)| host);
+                        let uri = format!("/{bucket}/{}", encode_key(&key));
+                        (host.to_string(), uri.clone(), format!("{endpoint}{uri}"))
+                    }
+                    None => {
+                        let host = format!("{bucket}.s3.{region}.amazonaws.com");
+                        let uri = format!("/{}", encode_key(&key));
+                        (host.clone(), uri.clone(), format!("https://{host}{uri}"))
+                    }
+                };
+
+                // AWS signature version 4
+                let (date, time) = amz_date(SystemTime::now());
+                let payload_hash = hex(&Sha256::digest(&body));
+                let mut headers = vec![
+                    ("host", host),
+                    ("x-amz-content-sha256", payload_hash.clone()),
+                    ("x-amz-date", time.clone()),
+                ];
+                if let Some(session_token) = session_token {
+                    headers.push(("x-amz-security-token", session_token.clone()));
+                }
+                let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+                let canonical_headers = headers
+                    .iter()
+                    .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+                    .collect::<String>();
+                let canonical_request = format!("PUT\n{uri}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}");
+                let scope = format!("{date}/{region}/s3/aws4_request");
+                let string_to_sign = format!(
+                    "AWS4-HMAC-SHA256\n{time}\n{scope}\n{}",
+                    hex(&Sha256::digest(canonical_request.as_bytes()))
+                );
+                let key = [date.as_str(), region, "s3", "aws4_request"]
+                    .iter()
+                    .fold(format!("AWS4{secret_key}").into_bytes(), |key, part| {
+                        hmac_sha256(&key, part)
+                    });
+                let signature = hex(&hmac_sha256(&key, &string_to_sign));
+
+                let mut request = agent.put(&url).set(
+                    "Authorization",
+                    &format!(
+                        "AWS4-HMAC-SHA256 Credential={access_key}/{scope}, SignedHeaders={signed_headers}, Signature={signature}"
+                    ),
+                );
+                // the host header is set by the client
+                for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+                    request = request.set(name, value);
+                }
+                request
+            }
+        };
+
+        request
+            .send_bytes(&body)
+            .map_err(|e| PsydkError::CustomError(format!("Failed to upload {}: {e}", path.display())))?;
+        Ok(())
+    }
+
+    /// Upload one file, retrying with increasing delays.
+    fn upload(&self, file: &QueuedFile) -> PsydkResult<()> {
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 0;
+        loop {
+            match self.put(&file.path, &file.key) {
+                Ok(()) => return Ok(()),
+                // a file that is gone can't be uploaded by retrying
+                Err(PsydkError::IOError(e)) => return Err(e.into()),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(e) => {
+                    log::warn!("{e}, retrying in {} s", delay.as_secs_f64());
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Add files to the queue and upload all queued files. Files that could not be uploaded stay in
+    /// the queue, until their upload has failed `MAX_QUEUE_ATTEMPTS` times. Files that no longer
+    /// exist are dropped from the queue. Returns the files that were uploaded.
+    pub fn upload_files(&self, files: Vec<QueuedFile>) -> PsydkResult<Vec<QueuedFile>> {
+        let _lock = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = self.add_to_queue(files)?;
+
+        let mut uploaded = Vec::new();
+        let mut remaining = Vec::new();
+        for mut file in queue {
+            if !file.path.exists() {
+                log::warn!(
+                    "{} no longer exists, it is removed from the upload queue {}",
+                    file.path.display(),
+                    self.queue.display()
+                );
+                continue;
+            }
+            match self.upload(&file) {
+                Ok(()) => uploaded.push(file),
+                Err(e) => {
+                    file.attempts += 1;
+                    if file.attempts >= MAX_QUEUE_ATTEMPTS {
+                        log::error!(
+                            "{e}, giving up after {} attempts and removing the file from the upload queue {}",
+                            file.attempts,
+                            self.queue.display()
+                        );
+                    } else {
+                        log::error!("{e}, the file stays in the upload queue {}", self.queue.display());
+                        remaining.push(file);
+                    }
+                }
+            }
+        }
+        save_queue(&self.queue, &remaining)?;
+        Ok(uploaded)
+    }
+
+    /// Add files to the queue without uploading them.
+    fn enqueue(&self, files: Vec<QueuedFile>) -> PsydkResult<()> {
+        let _lock = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        self.add_to_queue(files)?;
+        Ok(())
+    }
+
+    /// Add the files that are not queued yet to the queue and return the queue. The caller holds
+    /// `QUEUE_LOCK`.
+    fn add_to_queue(&self, files: Vec<QueuedFile>) -> PsydkResult<Vec<QueuedFile>> {
+        let mut queue = load_queue(&self.queue)?;
+        for file in files {
+            let queued = queue
+                .iter()
+                .any(|queued| queued.path == file.path && queued.key == file.key);
+            if !queued {
+                queue.push(file);
+            }
+        }
+        save_queue(&self.queue, &queue)?;
+        Ok(queue)
+    }
+
+    /// Upload all files that are still in the queue.
+    pub fn flush(&self) -> PsydkResult<Vec<QueuedFile>> {
+        self.upload_files(Vec::new())
+    }
+
+    /// The files that are waiting to be uploaded.
+    pub fn pending(&self) -> PsydkResult<Vec<QueuedFile>> {
+        load_queue(&self.queue)
+    }
+}
+
+/// Upload the files of a session and its manifest with the uploader enabled with `Uploader.enable`,
+/// if there is one. Called after the manifest has been written at the end of every experiment.
+///
+/// The files are added to the queue right away and uploaded on a background thread, so that the
+/// retries do not hold up the end of the experiment. Files that have not been uploaded when the
+/// process exits stay in the queue.
+pub fn upload_session(manifest_path: &Path, manifest: &Manifest) {
+    let Some(uploader) = SESSION_UPLOADER.lock().unwrap().clone() else {
+        return;
+    };
+
+    // files of the session are grouped by the session identifier, or the time of the manifest
+    let directory = match &manifest.session {
+        Some(session) => session.clone(),
+        None => (manifest.created as u64).to_string(),
+    };
+    let key = |path: &Path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        format!("{directory}/{name}")
+    };
+    let files = manifest
+        .files
+        .iter()
+        .map(|file| file.path.as_path())
+        .chain(std::iter::once(manifest_path))
+        .map(|path| QueuedFile::new(path.to_path_buf(), key(path)))
+        .collect::<Vec<_>>();
+
+    // queue the files before returning, so that they are uploaded later if the process exits
+    if let Err(e) = uploader.enqueue(files) {
+        log::error!("Failed to queue the files of the session for upload: {e}");
+        return;
+    }
+    std::thread::spawn(move || match uploader.flush() {
+        Ok(uploaded) => log::info!("Uploaded {} queued files", uploaded.len()),
+        Err(e) => log::error!("Failed to upload the files of the session: {e}"),
+    });
+}
+
+/// Uploads data files and the manifest at the end of a session, so that data from several sites
+/// don't have to be collected by hand.
+///
+/// Files are added to a local queue (a JSON file) and only removed from it once they have been
+/// uploaded. At the end of a session, files are uploaded in the background. Uploads are retried
+/// with increasing delays; files that still fail stay in the queue and are uploaded at the end of
+/// the next session or with `flush`, until their upload has failed 10 times.
+///
+/// Create an uploader with `Uploader.http` or `Uploader.s3` and call `enable` to upload the files
+/// of every session, grouped by the session identifier.
+#[cfg(feature = "python")]
+#[pyclass(name = "Uploader")]
+pub struct PyUploader(pub Uploader);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyUploader {
+    /// An uploader that uploads every file with `PUT <url>/<session>/<file name>`.
+    ///
+    /// Parameters
+    /// ----------
+    /// url : str
+    ///   The base URL, e.g. "https://data.example.org/studies/stroop". Only HTTPS is supported, so
+    ///   that the token and the data are not sent in cleartext.
+    /// token : str, optional
+    ///   A token sent as "Authorization: Bearer <token>".
+    /// headers : dict[str, str], optional
+    ///   Further headers to send with every request.
+    /// queue : str, optional
+    ///   The file that lists the files waiting to be uploaded. Defaults to "upload_queue.json".
+    /// retries : int, optional
+    ///   How often a failed upload is retried before the file is left in the queue. Defaults to 3.
+    /// timeout : float, optional
+    ///   The timeout of every request in seconds. Defaults to 60.
+    #[staticmethod]
+    #[pyo3(name = "http")]
+    #[pyo3(signature = (url, token = None, headers = None, queue = PathBuf::from("upload_queue.json"), retries = 3, timeout = 60.0))]
+    fn py_http(
+        url: String,
+        token: Option<String>,
+        headers: Option<Vec<(String, String)>>,
+        queue: PathBuf,
+        retries: u32,
+        timeout: f64,
+    ) -> PyResult<Self> {
+        check_url(&url)?;
+        Ok(Self(Uploader {
+            target: UploadTarget::Http {
+                url,
+                token,
+                headers: headers.unwrap_or_default(),
+            },
+            queue,
+            retries,
+            timeout: to_timeout(timeout)?,
+        }))
+    }
+
+    /// An uploader that uploads every file as `<prefix><session>/<file name>` to an S3 bucket.
+    ///
+    /// Parameters
+    /// ----------
+    /// bucket : str
+    ///   The name of the bucket.
+    /// region : str
+    ///   The region of the bucket, e.g. "eu-central-1".
+    /// prefix : str, optional
+    ///   A prefix for all keys, e.g. "stroop/".
+    /// endpoint : str, optional
+    ///   The URL of an S3-compatible service (e.g. MinIO) instead of AWS, e.g.
+    ///   "https://minio.example.org". Only HTTPS is supported.
+    /// access_key : str, optional
+    ///   The access key. Defaults to the AWS_ACCESS_KEY_ID environment variable.
+    /// secret_key : str, optional
+    ///   The secret key. Defaults to the AWS_SECRET_ACCESS_KEY environment variable.
+    /// session_token : str, optional
+    ///   A session token for temporary credentials. Defaults to the AWS_SESSION_TOKEN environment
+    ///   variable, if set.
+    /// queue : str, optional
+    ///   The file that lists the files waiting to be uploaded. Defaults to "upload_queue.json".
+    /// retries : int, optional
+    ///   How often a failed upload is retried before the file is left in the queue. Defaults to 3.
+    /// timeout : float, optional
+    ///   The timeout of every request in seconds. Defaults to 60.
+    #[staticmethod]
+    #[pyo3(name = "s3")]
+    #[pyo3(signature = (
+        bucket,
+        region,
+        prefix = String::new(),
+        endpoint = None,
+        access_key = None,
+        secret_key = None,
+        session_token = None,
+        queue = PathBuf::from("upload_queue.json"),
+        retries = 3,
+        timeout = 60.0,
+    ))]
+    fn py_s3(
+        bucket: String,
+        region: String,
+        prefix: String,
+        endpoint: Option<String>,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+        session_token: Option<String>,
+        queue: PathBuf,
+        retries: u32,
+        timeout: f64,
+    ) -> PyResult<Self> {
+        let credential = |value: Option<String>, variable: &str| {
+            value.or_else(|| std::env::var(variable).ok()).ok_or_else(|| {
+                PsydkError::ParameterError(format!("No S3 credentials given, and {variable} is not set"))
+            })
+        };
+        if let Some(endpoint) = &endpoint {
+            check_url(endpoint)?;
+        }
+        Ok(Self(Uploader {
+            target: UploadTarget::S3 {
+                bucket,
+                region,
+                prefix,
+                endpoint,
+                access_key: credential(access_key, "AWS_ACCESS_KEY_ID")?,
+                secret_key: credential(secret_key, "AWS_SECRET_ACCESS_KEY")?,
+                session_token: session_token.or_else(|| std::env::var("AWS_SESSION_TOKEN").ok()),
+            },
+            queue,
+            retries,
+            timeout: to_timeout(timeout)?,
+        }))
+    }
+
+    /// Upload the data files and the manifest at the end of every session. Replaces an uploader
+    /// that was enabled before.
+    #[pyo3(name = "enable")]
+    fn py_enable(&self) {
+        *SESSION_UPLOADER.lock().unwrap() = Some(self.0.clone());
+    }
+
+    /// Stop uploading files at the end of the session.
+    #[staticmethod]
+    #[pyo3(name = "disable")]
+    fn py_disable() {
+        *SESSION_UPLOADER.lock().unwrap() = None;
+    }
+
+    /// Upload files now, e.g. files that are not listed in the manifest.
+    ///
+    /// Parameters
+    /// ----------
+    /// paths : list[str]
+    ///   The files to upload.
+    /// directory : str, optional
+    ///   The directory (or prefix) for the files at the target. Defaults to the session
+    ///   identifier, if there is one.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///   The number of files that were uploaded, including files that were waiting in the queue.
+    #[pyo3(name = "upload")]
+    #[pyo3(signature = (paths, directory = None))]
+    fn py_upload(&self, py: Python, paths: Vec<PathBuf>, directory: Option<String>) -> PyResult<usize> {
+        let directory = directory.or_else(|| crate::cli::launch_options().and_then(|options| options.session.clone()));
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                let key = match &directory {
+                    Some(directory) => format!("{directory}/{name}"),
+                    None => name,
+                };
+                QueuedFile::new(path, key)
+            })
+            .collect();
+        Ok(py.allow_threads(|| self.0.upload_files(files))?.len())
+    }
+
+    /// Upload the files that are waiting in the queue.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///   The number of files that were uploaded.
+    #[pyo3(name = "flush")]
+    fn py_flush(&self, py: Python) -> PyResult<usize> {
+        Ok(py.allow_threads(|| self.0.flush())?.len())
+    }
+
+    /// The files that are waiting to be uploaded.
+    #[getter(pending)]
+    fn py_pending(&self) -> PyResult<Vec<PathBuf>> {
+        Ok(self.0.pending()?.into_iter().map(|file| file.path).collect())
+    }
+
+    fn __repr__(&self) -> String {
+        let target = match &self.0.target {
+            UploadTarget::Http { url, .. } => url.clone(),
+            UploadTarget::S3 { bucket, prefix, .. } => format!("s3://{bucket}/{prefix}"),
+        };
+        format!("Uploader({target:?})")
+    }
+}
+
+/// Check that files are uploaded to `url` with encryption, so that the data and the credentials
+/// are not sent in cleartext.
+fn check_url(url: &str) -> PsydkResult<()> {
+    match url.split_once("://") {
+        Some(("https", host)) if !host.is_empty() => Ok(()),
+        Some(("http", _)) => Err(PsydkError::ParameterError(format!(
+            "Refusing to upload to {url} without encryption, use an https:// URL"
+        ))),
+        _ => Err(PsydkError::ParameterError(format!(
+            "Invalid upload URL: {url}, expected an https:// URL"
+        ))),
+    }
+}
+
+fn to_timeout(seconds: f64) -> PsydkResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| PsydkError::ParameterError(format!("Invalid timeout {seconds}, must be positive")))
+}
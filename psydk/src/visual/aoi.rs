@@ -0,0 +1,555 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyDictMethods},
+};
+use send_wrapper::SendWrapper;
+use strum::EnumString;
+
+use super::{
+    geometry::Shape,
+    window::{Frame, PhysicalScreen, PixelSize, Window},
+};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    input::{Event, EventHandlerId, EventKind},
+    time::{to_timeout, TimelineEvent, Timestamp},
+};
+
+/// Where the samples of an area of interest come from.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum AoiSource {
+    /// The mouse cursor of the window the area is attached to.
+    Mouse,
+    /// Gaze samples that are passed to `add_sample`, e.g. from an eye tracker.
+    Gaze,
+}
+
+/// Dwell statistics of an area of interest for the current trial.
+#[derive(Debug, Clone)]
+pub struct AoiStats {
+    /// The start of the trial.
+    pub trial_start: Instant,
+    /// Total time spent inside the area.
+    pub dwell_time: Duration,
+    /// Number of times the area was entered.
+    pub entries: u32,
+    /// Time from the start of the trial to the first entry.
+    pub first_entry_latency: Option<Duration>,
+    /// The most recent sample and whether it was inside the area.
+    last_sample: Option<(Instant, bool)>,
+}
+
+impl AoiStats {
+    fn new(trial_start: Instant) -> Self {
+        Self {
+            trial_start,
+            dwell_time: Duration::ZERO,
+            entries: 0,
+            first_entry_latency: None,
+            last_sample: None,
+        }
+    }
+
+    /// Record a sample. Time between two consecutive samples counts as dwell time if the earlier
+    /// sample was inside the area.
+    fn add_sample(&mut self, time: Instant, inside: bool) {
+        // samples from before the start of the trial are ignored
+        if time < self.trial_start {
+            return;
+        }
+
+        let was_inside = match self.last_sample {
+            Some((last_time, was_inside)) => {
+                if was_inside {
+                    self.dwell_time += time.saturating_duration_since(last_time);
+                }
+                was_inside
+            }
+            None => false,
+        };
+
+        if inside && !was_inside {
+            self.entries += 1;
+            if self.first_entry_latency.is_none() {
+                self.first_entry_latency = Some(time - self.trial_start);
+            }
+        }
+
+        self.last_sample = Some((time, inside));
+    }
+
+    /// The statistics as they would be if the trial ended at `time`.
+    fn until(&self, time: Instant) -> Self {
+        let mut stats = self.clone();
+        if let Some((last_time, true)) = self.last_sample {
+            stats.dwell_time += time.saturating_duration_since(last_time);
+            stats.last_sample = Some((time, true));
+        }
+        stats
+    }
+
+    fn inside(&self) -> bool {
+        matches!(self.last_sample, Some((_, true)))
+    }
+}
+
+#[derive(Debug)]
+struct AoiState {
+    stats: AoiStats,
+    window: Option<Window>,
+    handler_ids: Vec<EventHandlerId>,
+}
+
+/// An area of interest made up of one or more shapes. A point is inside the area if it is inside
+/// any of the shapes.
+#[derive(Debug, Clone)]
+pub struct AreaOfInterest {
+    pub name: String,
+    pub shapes: Vec<Shape>,
+    pub source: AoiSource,
+    state: Arc<Mutex<AoiState>>,
+}
+
+impl AreaOfInterest {
+    pub fn new(name: impl Into<String>, shapes: Vec<Shape>, source: AoiSource) -> Self {
+        Self {
+            name: name.into(),
+            shapes,
+            source,
+            state: Arc::new(Mutex::new(AoiState {
+                stats: AoiStats::new(Instant::now()),
+                window: None,
+                handler_ids: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns true if the point (in pixels, in the coordinate system of the window) is inside the
+    /// area.
+    pub fn contains_point(&self, x: f32, y: f32, window_size: PixelSize, screen: PhysicalScreen) -> bool {
+        self.shapes
+            .iter()
+            .any(|shape| shape.contains_point(x, y, window_size, screen))
+    }
+
+    /// Attach the area to a window. Units of the shapes are evaluated for this window and, for
+    /// the mouse source, cursor movements are recorded as samples.
+    pub fn attach(&self, window: &Window) -> PsydkResult<()> {
+        self.detach();
+
+        let mut handler_ids = Vec::new();
+        if self.source == AoiSource::Mouse {
+            let aoi = self.clone();
+            handler_ids.push(window.add_event_handler(EventKind::CursorMoved, move |event| {
+                if let Event::CursorMoved {
+                    timestamp, position, ..
+                } = event
+                {
+                    // the area may have been detached since the event was created
+                    let _ = aoi.add_sample(position.0, position.1, timestamp.timestamp);
+                }
+                false
+            })?);
+
+            let aoi = self.clone();
+            handler_ids.push(window.add_event_handler(EventKind::CursorExited, move |event| {
+                if let Event::CursorExited { timestamp, .. } = event {
+                    aoi.state.lock().unwrap().stats.add_sample(timestamp.timestamp, false);
+                }
+                false
+            })?);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.window = Some(window.clone());
+        state.handler_ids = handler_ids;
+        Ok(())
+    }
+
+    /// Detach the area from its window. Does nothing if the area is not attached.
+    pub fn detach(&self) {
+        let (window, handler_ids) = {
+            let mut state = self.state.lock().unwrap();
+            (state.window.take(), std::mem::take(&mut state.handler_ids))
+        };
+        if let Some(window) = window {
+            for id in handler_ids {
+                window.remove_event_handler(id);
+            }
+        }
+    }
+
+    /// Record a sample at the given position (in pixels, in the coordinate system of the window).
+    pub fn add_sample(&self, x: f32, y: f32, time: Instant) -> PsydkResult<()> {
+        let window = self
+            .state
+            .lock()
+            .unwrap()
+            .window
+            .clone()
+            .ok_or_else(|| PsydkError::ParameterError("the area of interest is not attached to a window".into()))?;
+        if self.source == AoiSource::Gaze {
+            window.add_gaze_sample(time, Some((x, y)))?;
+        }
+        let (window_size, screen) = window.with_state(|state| (state.size, state.physical_screen))?;
+        let inside = self.contains_point(x, y, window_size, screen);
+
+        self.state.lock().unwrap().stats.add_sample(time, inside);
+        Ok(())
+    }
+
+    /// Reset the statistics and start a new trial.
+    pub fn start_trial(&self, time: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let inside = state.stats.inside();
+        state.stats = AoiStats::new(time);
+        // if the sample source is already inside the area, the trial starts with an entry
+        if inside {
+            state.stats.add_sample(time, true);
+        }
+    }
+
+    /// The statistics of the current trial up to `time`.
+    pub fn stats(&self, time: Instant) -> AoiStats {
+        self.state.lock().unwrap().stats.until(time)
+    }
+}
+
+/// An area of interest (AOI) made up of one or more shapes.
+///
+/// When attached to a window, the area accumulates the dwell time, the number of entries, and
+/// the latency of the first entry of the mouse cursor or of gaze samples for the current trial.
+///
+/// Parameters
+/// ----------
+/// name : str
+///     The name of the area, used as the label in the timeline.
+/// shapes : Shape or list[Shape]
+///     The shapes that make up the area. Coordinates are in the coordinate system of the window.
+/// source : str, optional
+///     Either "mouse" (the default) or "gaze". Gaze samples need to be passed to `add_sample`.
+#[cfg(feature = "python")]
+#[pyclass(name = "AreaOfInterest")]
+pub struct PyAreaOfInterest(pub AreaOfInterest);
+
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+enum ShapeOrShapes {
+    Shape(Shape),
+    Shapes(Vec<Shape>),
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyAreaOfInterest {
+    #[new]
+    #[pyo3(signature = (name, shapes, source = AoiSource::Mouse))]
+    fn __new__(name: String, shapes: ShapeOrShapes, source: AoiSource) -> Self {
+        let shapes = match shapes {
+            ShapeOrShapes::Shape(shape) => vec![shape],
+            ShapeOrShapes::Shapes(shapes) => shapes,
+        };
+        Self(AreaOfInterest::new(name, shapes, source))
+    }
+
+    /// Attach the area to a window. An area can only be attached to one window at a time.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///     The window whose mouse cursor (or gaze samples) the area tracks.
+    #[pyo3(name = "attach")]
+    fn py_attach(&self, window: Window, py: Python) -> PyResult<()> {
+        let aoi = SendWrapper::new(self.0.clone());
+        py.allow_threads(move || aoi.attach(&window))?;
+        Ok(())
+    }
+
+    /// Detach the area from its window.
+    #[pyo3(name = "detach")]
+    fn py_detach(&self, py: Python) {
+        let aoi = SendWrapper::new(self.0.clone());
+        py.allow_threads(move || aoi.detach());
+    }
+
+    /// Start a new trial, resetting all statistics.
+    ///
+    /// Parameters
+    /// ----------
+    /// timestamp : Timestamp, optional
+    ///     The start of the trial, e.g. the onset of the stimulus. Defaults to now.
+    #[pyo3(name = "start_trial")]
+    #[pyo3(signature = (timestamp = None))]
+    fn py_start_trial(&self, timestamp: Option<Timestamp>) {
+        self.0.start_trial(timestamp.map_or_else(Instant::now, |t| t.timestamp));
+    }
+
+    /// Record a gaze sample.
+    ///
+    /// Parameters
+    /// ----------
+    /// x : float
+    ///     The horizontal position in pixels, in the coordinate system of the window.
+    /// y : float
+    ///     The vertical position in pixels, in the coordinate system of the window.
+    /// timestamp : Timestamp, optional
+    ///     The time of the sample. Defaults to now.
+    #[pyo3(name = "add_sample")]
+    #[pyo3(signature = (x, y, timestamp = None))]
+    fn py_add_sample(&self, x: f32, y: f32, timestamp: Option<Timestamp>, py: Python) -> PyResult<()> {
+        let aoi = SendWrapper::new(self.0.clone());
+        let time = timestamp.map_or_else(Instant::now, |t| t.timestamp);
+        py.allow_threads(move || aoi.add_sample(x, y, time))?;
+        Ok(())
+    }
+
+    /// The name of the area.
+    #[getter(name)]
+    fn py_name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    /// Total time in seconds spent inside the area in the current trial.
+    #[getter(dwell_time)]
+    fn py_dwell_time(&self) -> f64 {
+        self.0.stats(Instant::now()).dwell_time.as_secs_f64()
+    }
+
+    /// Number of times the area was entered in the current trial.
+    #[getter(entries)]
+    fn py_entries(&self) -> u32 {
+        self.0.stats(Instant::now()).entries
+    }
+
+    /// Time in seconds from the start of the trial to the first entry, or None if the area has not
+    /// been entered.
+    #[getter(first_entry_latency)]
+    fn py_first_entry_latency(&self) -> Option<f64> {
+        self.0
+            .stats(Instant::now())
+            .first_entry_latency
+            .map(|latency| latency.as_secs_f64())
+    }
+
+    /// Whether the most recent sample was inside the area.
+    #[getter(inside)]
+    fn py_inside(&self) -> bool {
+        self.0.stats(Instant::now()).inside()
+    }
+
+    /// End the current trial and return its statistics.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline, optional
+    ///     If given, the statistics are added to the timeline as an "aoi" event labelled with the
+    ///     name of the area.
+    /// timestamp : Timestamp, optional
+    ///     The end of the trial. Defaults to now.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     The name, dwell time, number of entries, and first entry latency of the trial.
+    #[pyo3(name = "end_trial")]
+    #[pyo3(signature = (timeline = None, timestamp = None))]
+    fn py_end_trial<'py>(
+        &self,
+        py: Python<'py>,
+        timeline: Option<Bound<'py, PyTimeline>>,
+        timestamp: Option<Timestamp>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let time = timestamp.map_or_else(Instant::now, |t| t.timestamp);
+        let stats = self.0.stats(time);
+        let dwell_time = stats.dwell_time.as_secs_f64();
+        let first_entry_latency = stats.first_entry_latency.map(|latency| latency.as_secs_f64());
+
+        if let Some(timeline) = timeline {
+            timeline.borrow_mut().0.add(TimelineEvent {
+                kind: "aoi".to_string(),
+                time,
+                label: Some(self.0.name.clone()),
+                data: vec![
+                    ("dwell_time".to_string(), dwell_time.to_string()),
+                    ("entries".to_string(), stats.entries.to_string()),
+                    (
+                        "first_entry_latency".to_string(),
+                        first_entry_latency.map(|l| l.to_string()).unwrap_or_default(),
+                    ),
+                ],
+            });
+        }
+
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.0.name)?;
+        dict.set_item("dwell_time", dwell_time)?;
+        dict.set_item("entries", stats.entries)?;
+        dict.set_item("first_entry_latency", first_entry_latency)?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "AreaOfInterest(name={:?}, shapes={}, source={:?})",
+            self.0.name,
+            self.0.shapes.len(),
+            self.0.source
+        )
+    }
+}
+
+impl Window {
+    /// Wait until the gaze (or the mouse cursor) stays inside `shapes` for `duration`, presenting
+    /// `frame` (if any) while waiting. Returns false if this did not happen within `timeout`.
+    /// Consecutive timeouts are counted in the window state, so that the caller can recalibrate
+    /// after repeated failures.
+    pub fn wait_for_fixation(
+        &self,
+        shapes: &[Shape],
+        source: AoiSource,
+        duration: Duration,
+        timeout: Option<Duration>,
+        mut frame: Option<&mut Frame>,
+    ) -> PsydkResult<bool> {
+        let start = Instant::now();
+        let mut inside_since = None;
+        loop {
+            match frame.as_deref_mut() {
+                Some(frame) => {
+                    self.present(frame, None, None, false, None)?;
+                }
+                None => std::thread::sleep(Duration::from_millis(1)),
+            }
+
+            let now = Instant::now();
+            let inside = self.with_state(|state| {
+                let position = match source {
+                    AoiSource::Mouse => state.mouse_position,
+                    AoiSource::Gaze => state.gaze_position,
+                };
+                position.is_some_and(|(x, y)| {
+                    shapes
+                        .iter()
+                        .any(|shape| shape.contains_point(x, y, state.size, state.physical_screen))
+                })
+            })?;
+
+            inside_since = match (inside, inside_since) {
+                (true, None) => Some(now),
+                (true, since) => since,
+                (false, _) => None,
+            };
+            if inside_since.is_some_and(|since| now - since >= duration) {
+                self.with_state(|state| state.fixation_failures = 0)?;
+                return Ok(true);
+            }
+            if timeout.is_some_and(|timeout| now - start >= timeout) {
+                self.with_state(|state| state.fixation_failures += 1)?;
+                return Ok(false);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+enum FixationRegion<'py> {
+    Area(PyRef<'py, PyAreaOfInterest>),
+    Shapes(ShapeOrShapes),
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Window {
+    /// Wait until the participant fixates a region, e.g. a fixation cross, before starting a
+    /// trial.
+    ///
+    /// Fixation is detected when the gaze position of the window (set by an eye tracker or by
+    /// areas of interest with the "gaze" source) or the mouse cursor (as a stand-in during
+    /// development) stays inside the region for `duration`. If `timeout` passes first, the
+    /// function returns False and the failure is counted. After `max_failures` failures in a row,
+    /// `recalibrate` is called (e.g. a function that runs `calibrate_eye_tracker`) and the count
+    /// starts again. A typical trial starts with
+    /// `while not window.wait_for_fixation(cross, timeout=3, frame=frame, recalibrate=calibrate): pass`.
+    ///
+    /// Parameters
+    /// ----------
+    /// region : Shape, list[Shape], or AreaOfInterest
+    ///     The region that has to be fixated, in the coordinate system of the window.
+    /// duration : float, optional
+    ///     How long the region has to be fixated without interruption, in seconds.
+    /// timeout : float, optional
+    ///     How long to wait for fixation in seconds. Waits indefinitely if not given.
+    /// source : str, optional
+    ///     Either "gaze" (the default) or "mouse".
+    /// frame : Frame, optional
+    ///     The frame that is presented while waiting, e.g. one with a fixation cross.
+    /// max_failures : int, optional
+    ///     The number of timeouts in a row after which `recalibrate` is called.
+    /// recalibrate : callable, optional
+    ///     Called without arguments after `max_failures` timeouts in a row.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///     True if the region was fixated, False if the timeout passed first.
+    #[pyo3(name = "wait_for_fixation")]
+    #[pyo3(signature = (
+        region,
+        duration = 0.3,
+        timeout = None,
+        source = AoiSource::Gaze,
+        frame = None,
+        max_failures = 3,
+        recalibrate = None,
+    ))]
+    fn py_wait_for_fixation(
+        &self,
+        py: Python,
+        region: FixationRegion,
+        duration: f64,
+        timeout: Option<f64>,
+        source: AoiSource,
+        frame: Option<&mut Frame>,
+        max_failures: u32,
+        recalibrate: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        let shapes = match region {
+            FixationRegion::Area(area) => area.0.shapes.clone(),
+            FixationRegion::Shapes(ShapeOrShapes::Shape(shape)) => vec![shape],
+            FixationRegion::Shapes(ShapeOrShapes::Shapes(shapes)) => shapes,
+        };
+        // `max` would turn NaN into 0
+        let duration = Some(duration)
+            .filter(|duration| !duration.is_nan())
+            .and_then(|duration| Duration::try_from_secs_f64(duration.max(0.0)).ok())
+            .ok_or_else(|| PsydkError::ParameterError(format!("Invalid fixation duration {duration}")))?;
+        let timeout = to_timeout(timeout)?;
+
+        let window = SendWrapper::new(self.clone());
+        let frame = SendWrapper::new(frame);
+        let fixated = py.allow_threads(move || {
+            let mut frame = frame.take();
+            window.wait_for_fixation(&shapes, source, duration, timeout, frame.as_deref_mut())
+        })?;
+
+        if !fixated {
+            let failures = self.with_state(|state| state.fixation_failures)?;
+            if let (true, Some(recalibrate)) = (failures >= max_failures, recalibrate) {
+                log::info!("No fixation in {failures} attempts in a row, recalibrating");
+                self.with_state(|state| state.fixation_failures = 0)?;
+                recalibrate.call0()?;
+            }
+        }
+        Ok(fixated)
+    }
+}
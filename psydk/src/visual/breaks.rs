@@ -0,0 +1,312 @@
+//! Break screens between blocks of trials.
+//!
+//! The break screen shows a message and counts down the minimum rest. Key presses are ignored
+//! until the minimum rest has elapsed, so that participants can't skip the break, and the break
+//! ends automatically after the maximum rest, if there is one. Every break is logged with its
+//! duration.
+
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyDict};
+use send_wrapper::SendWrapper;
+
+use super::{
+    color::{IntoLinRgba, LinRgba},
+    geometry::{Anchor, Size, Transformation2D},
+    stimuli::{
+        text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+        DynamicStimulus,
+    },
+    window::{Frame, Window},
+};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    time::{TimelineEvent, Timestamp},
+};
+
+/// A break that has been taken.
+#[derive(Debug, Clone)]
+pub struct BreakRecord {
+    /// The block that the break followed, if known.
+    pub block: Option<usize>,
+    pub onset: Instant,
+    pub end: Instant,
+    /// Whether the break ended because the maximum rest elapsed.
+    pub timed_out: bool,
+}
+
+impl BreakRecord {
+    pub fn duration(&self) -> Duration {
+        self.end - self.onset
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakScreen {
+    /// The message. "{block}" and "{blocks}" are replaced with the number of the block that
+    /// just ended (counting from 1) and the number of blocks, if they are known.
+    pub message: String,
+    /// Shown below the message during the minimum rest. "{remaining}" is replaced with the
+    /// remaining seconds.
+    pub countdown: String,
+    /// Shown below the message once the participant can continue.
+    pub prompt: String,
+    pub min_rest: Duration,
+    pub max_rest: Option<Duration>,
+    /// The keys that end the break.
+    pub keys: Vec<String>,
+    pub color: LinRgba,
+    pub log: Vec<BreakRecord>,
+}
+
+impl Default for BreakScreen {
+    fn default() -> Self {
+        Self {
+            message: "Take a short break.".into(),
+            countdown: "You can continue in {remaining} s.".into(),
+            prompt: "Press space to continue.".into(),
+            min_rest: Duration::from_secs(30),
+            max_rest: None,
+            keys: vec!["Space".into()],
+            color: LinRgba::new(1.0, 1.0, 1.0, 1.0),
+            log: Vec::new(),
+        }
+    }
+}
+
+impl BreakScreen {
+    /// Show the break screen until the participant continues or the maximum rest elapses.
+    /// Escape aborts with an error.
+    pub fn show(
+        &mut self,
+        context: &ExperimentContext,
+        window: &Window,
+        block: Option<usize>,
+        blocks: Option<usize>,
+    ) -> PsydkResult<BreakRecord> {
+        if self.max_rest.is_some_and(|max_rest| max_rest < self.min_rest) {
+            return Err(PsydkError::ParameterError(
+                "The maximum rest must not be shorter than the minimum rest".into(),
+            ));
+        }
+
+        let mut message = self.message.clone();
+        if let Some(block) = block {
+            message = message.replace("{block}", &(block + 1).to_string());
+        }
+        if let Some(blocks) = blocks {
+            message = message.replace("{blocks}", &blocks.to_string());
+        }
+
+        let mut receiver = window.create_event_receiver();
+        let onset = Instant::now();
+        // the frame is rebuilt whenever the countdown changes
+        let mut shown: Option<u64> = None;
+        let mut frame = window.get_frame()?;
+        let timed_out = loop {
+            let elapsed = onset.elapsed();
+            let remaining = self.min_rest.saturating_sub(elapsed).as_secs_f64().ceil() as u64;
+            if shown != Some(remaining) {
+                let footer = match remaining {
+                    0 => self.prompt.clone(),
+                    _ => self.countdown.replace("{remaining}", &remaining.to_string()),
+                };
+                frame = self.frame(context, window, &format!("{message}\n\n{footer}"))?;
+                shown = Some(remaining);
+            }
+
+            window.present(&mut frame, None, None, false, None)?;
+            // key presses during the minimum rest are discarded
+            let events = receiver.poll();
+            if events.key_pressed("Escape") {
+                return Err(PsydkError::CustomError("The experiment was aborted".into()));
+            }
+            if elapsed >= self.min_rest && self.keys.iter().any(|key| events.key_pressed(key)) {
+                break false;
+            }
+            if self.max_rest.is_some_and(|max_rest| elapsed >= max_rest) {
+                break true;
+            }
+        };
+
+        let record = BreakRecord {
+            block,
+            onset,
+            end: Instant::now(),
+            timed_out,
+        };
+        self.log.push(record.clone());
+        Ok(record)
+    }
+
+    fn frame(&self, context: &ExperimentContext, window: &Window, text: &str) -> PsydkResult<Frame> {
+        let (size, coordinate_system) = window.with_state(|state| (state.size, state.coordinate_system))?;
+        let (x, y) = coordinate_system.from_scene(0.0, 0.0, size);
+        let text = TextStimulus::new(
+            Size::Pixels(x),
+            Size::Pixels(y),
+            text,
+            TextAlignment::Center,
+            TextDirection::Ltr,
+            TextOrientation::Horizontal,
+            Anchor::Center,
+            Size::Pixels(size.height as f32 / 30.0),
+            &[],
+            FontWeight::Regular,
+            self.color,
+            1.0,
+            0.0,
+            Transformation2D::Identity(),
+            context,
+        );
+        let mut frame = window.get_frame()?;
+        frame.add(&DynamicStimulus::new(text));
+        Ok(frame)
+    }
+}
+
+/// A break screen with an enforced minimum rest, e.g. between blocks of trials.
+///
+/// The screen shows a message and counts down the minimum rest. Key presses are ignored until
+/// the minimum rest has elapsed; after that, the participant continues with one of `keys`. If
+/// `max_rest` is given, the break ends automatically after it. Escape aborts the experiment.
+/// Every break is logged with its duration.
+///
+/// Parameters
+/// ----------
+/// message : str, optional
+///   The message. "{block}" and "{blocks}" are replaced with the number of the block that just
+///   ended and the number of blocks, if they are passed to `show`.
+/// min_rest : float, optional
+///   The minimum rest in seconds. Defaults to 30.
+/// max_rest : float, optional
+///   The maximum rest in seconds, after which the break ends automatically.
+/// keys : list[str], optional
+///   The keys that end the break. Defaults to space.
+/// countdown : str, optional
+///   Shown during the minimum rest; "{remaining}" is replaced with the remaining seconds.
+/// prompt : str, optional
+///   Shown once the participant can continue.
+/// color : Color, optional
+///   The color of the text. Defaults to white.
+#[cfg(feature = "python")]
+#[pyclass(name = "BreakScreen")]
+pub struct PyBreakScreen(pub BreakScreen);
+
+fn to_duration(name: &str, seconds: f64) -> PsydkResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| PsydkError::ParameterError(format!("Invalid {name} {seconds}, must be non-negative")))
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyBreakScreen {
+    #[new]
+    #[pyo3(signature = (
+        message = None,
+        min_rest = 30.0,
+        max_rest = None,
+        keys = None,
+        countdown = None,
+        prompt = None,
+        color = IntoLinRgba::new(1.0, 1.0, 1.0, 1.0),
+    ))]
+    fn __new__(
+        message: Option<String>,
+        min_rest: f64,
+        max_rest: Option<f64>,
+        keys: Option<Vec<String>>,
+        countdown: Option<String>,
+        prompt: Option<String>,
+        color: IntoLinRgba,
+    ) -> PyResult<Self> {
+        let defaults = BreakScreen::default();
+        Ok(Self(BreakScreen {
+            message: message.unwrap_or(defaults.message),
+            countdown: countdown.unwrap_or(defaults.countdown),
+            prompt: prompt.unwrap_or(defaults.prompt),
+            min_rest: to_duration("minimum rest", min_rest)?,
+            max_rest: max_rest
+                .map(|max_rest| to_duration("maximum rest", max_rest))
+                .transpose()?,
+            keys: keys.unwrap_or(defaults.keys),
+            color: color.into(),
+            log: Vec::new(),
+        }))
+    }
+
+    /// Show the break screen until the participant continues or the maximum rest elapses.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window to show the break screen on.
+    /// block : int, optional
+    ///   The index of the block that just ended, for the message and the log.
+    /// blocks : int, optional
+    ///   The number of blocks, for the message.
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///   The duration of the break in seconds.
+    #[pyo3(name = "show")]
+    #[pyo3(signature = (window, block = None, blocks = None))]
+    fn py_show(&mut self, py: Python, window: Window, block: Option<usize>, blocks: Option<usize>) -> PyResult<f64> {
+        let context = SendWrapper::new(super::stimuli::helpers::get_experiment_context(None, py)?);
+        let window_wrapper = SendWrapper::new(window.clone());
+        let screen = SendWrapper::new(&mut self.0);
+        let record = py.allow_threads(move || screen.take().show(&context, &window_wrapper, block, blocks))?;
+        window.call_watchdog_callback(py)?;
+        Ok(record.duration().as_secs_f64())
+    }
+
+    /// All breaks, as dictionaries with the "block", "onset", "end", "duration" (in seconds),
+    /// and whether the break ended because the maximum rest elapsed ("timed_out").
+    #[getter(log)]
+    fn py_log<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.0
+            .log
+            .iter()
+            .map(|record| {
+                let dict = PyDict::new(py);
+                dict.set_item("block", record.block)?;
+                dict.set_item("onset", Timestamp::from(record.onset))?;
+                dict.set_item("end", Timestamp::from(record.end))?;
+                dict.set_item("duration", record.duration().as_secs_f64())?;
+                dict.set_item("timed_out", record.timed_out)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Add all breaks to a timeline as "break" events, labelled with the block.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///   The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for record in &self.0.log {
+            timeline.0.add(TimelineEvent {
+                kind: "break".to_string(),
+                time: record.onset,
+                label: record.block.map(|block| block.to_string()),
+                data: vec![("duration".to_string(), record.duration().as_secs_f64().to_string())],
+            });
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BreakScreen(min_rest={}, breaks={})",
+            self.0.min_rest.as_secs_f64(),
+            self.0.log.len()
+        )
+    }
+}
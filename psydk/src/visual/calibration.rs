@@ -0,0 +1,752 @@
+//! Calibration and validation of eye trackers with built-in targets.
+//!
+//! The procedure shows a target at each calibration point (shrinking to draw the gaze to its
+//! center) and tells the tracker when the participant fixated which point. It then shows the
+//! targets again in a different order and compares the gaze samples of the tracker with the
+//! targets to compute the accuracy and precision at each point in degrees of visual angle.
+
+use std::{
+    fmt,
+    fs::OpenOptions,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, types::PyTuple};
+use rand::seq::SliceRandom;
+use send_wrapper::SendWrapper;
+
+use super::{
+    color::{IntoLinRgba, LinRgba},
+    geometry::{Anchor, IntoSize, Shape, Size, Transformation2D},
+    stimuli::{
+        shape::{GradientType, ShapeParams, ShapeStimulus},
+        text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+        DynamicStimulus, StimulusParamValue, StrokeStyle,
+    },
+    window::{PixelSize, Window},
+};
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    time::Timestamp,
+};
+
+/// The size of the outer ring of a target relative to its final size, at the start of the
+/// animation.
+const TARGET_START_SCALE: f32 = 3.0;
+
+/// An eye tracker that can be calibrated and validated with `Calibration`. Positions are in the
+/// coordinate system of the window the procedure runs on.
+pub trait EyeTracker {
+    /// Called before the first calibration point, e.g. to put the tracker into calibration mode.
+    fn start_calibration(&mut self) -> PsydkResult<()> {
+        Ok(())
+    }
+
+    /// The participant fixated the target at `(x, y)` between `start` and `end`.
+    fn calibration_point(&mut self, _x: f32, _y: f32, _start: Instant, _end: Instant) -> PsydkResult<()> {
+        Ok(())
+    }
+
+    /// Called after the last calibration point, e.g. to compute the calibration.
+    fn finish_calibration(&mut self) -> PsydkResult<()> {
+        Ok(())
+    }
+
+    /// The gaze samples recorded between `start` and `end`.
+    fn gaze_samples(&mut self, start: Instant, end: Instant) -> PsydkResult<Vec<(f32, f32)>>;
+}
+
+/// An eye tracker implemented in Python. It must have a `gaze_samples(start, end)` method that
+/// returns the gaze positions between two `Timestamp`s as a list of `(x, y)` tuples, and can have
+/// `start_calibration()`, `calibration_point(x, y, start, end)`, and `finish_calibration()` methods.
+#[cfg(feature = "python")]
+pub struct PythonEyeTracker(pub Py<PyAny>);
+
+#[cfg(feature = "python")]
+impl PythonEyeTracker {
+    fn call_optional(&self, name: &str, args: impl FnOnce(Python) -> PyResult<Bound<PyTuple>>) -> PsydkResult<()> {
+        Python::with_gil(|py| -> PyResult<()> {
+            let tracker = self.0.bind(py);
+            if tracker.hasattr(name)? {
+                tracker.call_method1(name, args(py)?)?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "python")]
+impl EyeTracker for PythonEyeTracker {
+    fn start_calibration(&mut self) -> PsydkResult<()> {
+        self.call_optional("start_calibration", |py| Ok(PyTuple::empty(py)))
+    }
+
+    fn calibration_point(&mut self, x: f32, y: f32, start: Instant, end: Instant) -> PsydkResult<()> {
+        self.call_optional("calibration_point", |py| {
+            PyTuple::new(
+                py,
+                [
+                    x.into_pyobject(py)?.into_any(),
+                    y.into_pyobject(py)?.into_any(),
+                    Timestamp::from(start).into_pyobject(py)?.into_any(),
+                    Timestamp::from(end).into_pyobject(py)?.into_any(),
+                ],
+            )
+        })
+    }
+
+    fn finish_calibration(&mut self) -> PsydkResult<()> {
+        self.call_optional("finish_calibration", |py| Ok(PyTuple::empty(py)))
+    }
+
+    fn gaze_samples(&mut self, start: Instant, end: Instant) -> PsydkResult<Vec<(f32, f32)>> {
+        Ok(Python::with_gil(|py| {
+            self.0
+                .bind(py)
+                .call_method1("gaze_samples", (Timestamp::from(start), Timestamp::from(end)))?
+                .extract()
+        })?)
+    }
+}
+
+/// The result of the validation at one point.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct ValidationPoint {
+    /// The position of the target.
+    #[cfg_attr(feature = "python", pyo3(get))]
+    pub target: (f32, f32),
+    /// The mean gaze position, if there were samples.
+    #[cfg_attr(feature = "python", pyo3(get))]
+    pub gaze: Option<(f32, f32)>,
+    /// The angle between the target and the mean gaze position in degrees.
+    #[cfg_attr(feature = "python", pyo3(get))]
+    pub accuracy: Option<f64>,
+    /// The RMS of the angles between successive samples in degrees.
+    #[cfg_attr(feature = "python", pyo3(get))]
+    pub precision: Option<f64>,
+    /// The number of gaze samples.
+    #[cfg_attr(feature = "python", pyo3(get))]
+    pub samples: usize,
+}
+
+impl fmt::Display for ValidationPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = |value: Option<f64>| value.map_or("None".to_string(), |value| format!("{value:.2}"));
+        write!(
+            f,
+            "ValidationPoint(target=({:.0}, {:.0}), accuracy={}, precision={}, samples={})",
+            self.target.0,
+            self.target.1,
+            format(self.accuracy),
+            format(self.precision),
+            self.samples
+        )
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ValidationPoint {
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// The result of a calibration and validation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct CalibrationReport {
+    pub points: Vec<ValidationPoint>,
+    /// The largest acceptable mean accuracy in degrees.
+    pub max_mean_error: f64,
+    /// The largest acceptable accuracy at any point in degrees.
+    pub max_point_error: f64,
+    /// The number of times the calibration was run.
+    pub attempts: u32,
+}
+
+impl CalibrationReport {
+    fn mean(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+        let values = values.collect::<Option<Vec<_>>>()?;
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    }
+
+    /// The mean accuracy over all points in degrees, or `None` if any point has no samples.
+    pub fn mean_accuracy(&self) -> Option<f64> {
+        Self::mean(self.points.iter().map(|point| point.accuracy))
+    }
+
+    /// The worst accuracy of all points in degrees, or `None` if any point has no samples.
+    pub fn max_accuracy(&self) -> Option<f64> {
+        self.points
+            .iter()
+            .map(|point| point.accuracy)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(f64::max)
+    }
+
+    /// The mean precision over all points in degrees, or `None` if any point has too few samples.
+    pub fn mean_precision(&self) -> Option<f64> {
+        Self::mean(self.points.iter().map(|point| point.precision))
+    }
+
+    /// Whether every point has samples and the accuracy meets the acceptance criteria.
+    pub fn accepted(&self) -> bool {
+        match (self.mean_accuracy(), self.max_accuracy()) {
+            (Some(mean), Some(max)) => mean <= self.max_mean_error && max <= self.max_point_error,
+            _ => false,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let format = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{value:.2}°"));
+        format!(
+            "Accuracy: {} mean, {} max. Precision: {}. {}",
+            format(self.mean_accuracy()),
+            format(self.max_accuracy()),
+            format(self.mean_precision()),
+            if self.accepted() { "Accepted." } else { "Not accepted." }
+        )
+    }
+
+    fn log(&self) {
+        log::info!("Eye tracker validation (attempt {}): {}", self.attempts, self.summary());
+        for point in &self.points {
+            log::info!("  {point}");
+        }
+    }
+
+    /// Append one row per point to a CSV file, creating the file if it does not exist.
+    pub fn save(&self, path: &Path, participant: &str) -> PsydkResult<()> {
+        let is_empty = path.metadata().map(|metadata| metadata.len() == 0).unwrap_or(true);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = csv::Writer::from_writer(file);
+        let csv_error = |e: csv::Error| PsydkError::CustomError(e.to_string());
+
+        if is_empty {
+            writer
+                .write_record([
+                    "participant",
+                    "time",
+                    "attempt",
+                    "target_x",
+                    "target_y",
+                    "gaze_x",
+                    "gaze_y",
+                    "accuracy",
+                    "precision",
+                    "samples",
+                    "accepted",
+                ])
+                .map_err(csv_error)?;
+        }
+
+        let time = Timestamp::from(Instant::now()).unix().to_string();
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        for point in &self.points {
+            writer
+                .write_record([
+                    participant.to_string(),
+                    time.clone(),
+                    self.attempts.to_string(),
+                    point.target.0.to_string(),
+                    point.target.1.to_string(),
+                    optional(point.gaze.map(|gaze| gaze.0.to_string())),
+                    optional(point.gaze.map(|gaze| gaze.1.to_string())),
+                    optional(point.accuracy.map(|accuracy| accuracy.to_string())),
+                    optional(point.precision.map(|precision| precision.to_string())),
+                    point.samples.to_string(),
+                    self.accepted().to_string(),
+                ])
+                .map_err(csv_error)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl CalibrationReport {
+    /// The validation result of every point.
+    #[getter(points)]
+    fn py_points(&self) -> Vec<ValidationPoint> {
+        self.points.clone()
+    }
+
+    /// The mean accuracy over all points in degrees, or None if a point has no samples.
+    #[getter(mean_accuracy)]
+    fn py_mean_accuracy(&self) -> Option<f64> {
+        self.mean_accuracy()
+    }
+
+    /// The worst accuracy of all points in degrees, or None if a point has no samples.
+    #[getter(max_accuracy)]
+    fn py_max_accuracy(&self) -> Option<f64> {
+        self.max_accuracy()
+    }
+
+    /// The mean precision (RMS of sample-to-sample angles) over all points in degrees.
+    #[getter(mean_precision)]
+    fn py_mean_precision(&self) -> Option<f64> {
+        self.mean_precision()
+    }
+
+    /// Whether the accuracy meets the acceptance criteria.
+    #[getter(accepted)]
+    fn py_accepted(&self) -> bool {
+        self.accepted()
+    }
+
+    /// The number of times the calibration was run.
+    #[getter(attempts)]
+    fn py_attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Append the result of every point to a CSV file.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///   The CSV file. It is created if it does not exist.
+    /// participant : str
+    ///   The participant the calibration belongs to.
+    #[pyo3(name = "save")]
+    fn py_save(&self, path: &str, participant: &str) -> PyResult<()> {
+        Ok(self.save(Path::new(path), participant)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CalibrationReport({} points. {})", self.points.len(), self.summary())
+    }
+}
+
+/// Calibrates an eye tracker with 9 or 13 animated targets and validates the calibration.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    /// The number of points, 9 (a 3 x 3 grid) or 13 (the grid and 4 points between).
+    pub points: u32,
+    /// The fraction of the window (from the center to the edges) that the points cover.
+    pub area: f32,
+    /// The radius of a target.
+    pub target_radius: Size,
+    pub target_color: LinRgba,
+    /// How long a target shrinks before it is fixated, in seconds.
+    pub settle: f64,
+    /// How long gaze is recorded at each target, in seconds.
+    pub duration: f64,
+    /// Whether to validate the calibration.
+    pub validate: bool,
+    /// The largest acceptable mean accuracy in degrees.
+    pub max_mean_error: f64,
+    /// The largest acceptable accuracy at any point in degrees.
+    pub max_point_error: f64,
+}
+
+impl Calibration {
+    /// The positions of the points in the coordinates of the scene.
+    fn positions(&self, size: PixelSize) -> PsydkResult<Vec<(f32, f32)>> {
+        let mut positions = Vec::new();
+        for y in [-1.0, 0.0, 1.0] {
+            for x in [-1.0, 0.0, 1.0] {
+                positions.push((x, y));
+            }
+        }
+        match self.points {
+            9 => {}
+            13 => positions.extend([(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)]),
+            n => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Calibrations can use 9 or 13 points, not {n}"
+                )))
+            }
+        }
+
+        let (half_width, half_height) = (size.width as f32 / 2.0, size.height as f32 / 2.0);
+        Ok(positions
+            .into_iter()
+            .map(|(x, y)| (x * self.area * half_width, y * self.area * half_height))
+            .collect())
+    }
+
+    fn circle(radius: f32, color: LinRgba) -> PsydkResult<DynamicStimulus> {
+        let params = ShapeParams {
+            shape: Shape::Circle {
+                x: Size::Pixels(0.0),
+                y: Size::Pixels(0.0),
+                radius: Size::Pixels(radius),
+            },
+            x: Size::Pixels(0.0),
+            y: Size::Pixels(0.0),
+            fill_color: color,
+            gradient_angle: 0.0,
+            stroke_style: StrokeStyle::None,
+            stroke_color: LinRgba::default(),
+            stroke_width: Size::Pixels(0.0),
+            alpha: None,
+        };
+        let circle = ShapeStimulus::new(
+            params,
+            None,
+            GradientType::Linear,
+            None,
+            None,
+            Transformation2D::Identity(),
+        )?;
+        Ok(DynamicStimulus::new(circle))
+    }
+
+    fn line(color: LinRgba) -> PsydkResult<DynamicStimulus> {
+        let params = ShapeParams {
+            shape: Shape::Line {
+                x1: Size::Pixels(0.0),
+                y1: Size::Pixels(0.0),
+                x2: Size::Pixels(0.0),
+                y2: Size::Pixels(0.0),
+            },
+            x: Size::Pixels(0.0),
+            y: Size::Pixels(0.0),
+            fill_color: LinRgba::default(),
+            gradient_angle: 0.0,
+            stroke_style: StrokeStyle::Solid,
+            stroke_color: color,
+            stroke_width: Size::Pixels(2.0),
+            alpha: None,
+        };
+        let line = ShapeStimulus::new(
+            params,
+            None,
+            GradientType::Linear,
+            None,
+            None,
+            Transformation2D::Identity(),
+        )?;
+        Ok(DynamicStimulus::new(line))
+    }
+
+    fn set_position(stimulus: &DynamicStimulus, (x, y): (f32, f32)) {
+        let mut stimulus = stimulus.lock();
+        stimulus.set_param("x", StimulusParamValue::Size(Size::Pixels(x)));
+        stimulus.set_param("y", StimulusParamValue::Size(Size::Pixels(y)));
+    }
+
+    fn set_radius(stimulus: &DynamicStimulus, radius: f32) {
+        stimulus.lock().set_param(
+            "shape",
+            StimulusParamValue::Shape(Shape::Circle {
+                x: Size::Pixels(0.0),
+                y: Size::Pixels(0.0),
+                radius: Size::Pixels(radius),
+            }),
+        );
+    }
+
+    /// Show the target at every position (in window coordinates) in turn and return the interval
+    /// in which each one was fixated.
+    fn show_targets(&self, window: &Window, positions: &[(f32, f32)]) -> PsydkResult<Vec<(Instant, Instant)>> {
+        let (size, screen) = window.with_state(|state| (state.size, state.physical_screen))?;
+        let radius = self.target_radius.eval(size, screen);
+        let ring = Self::circle(radius, self.target_color)?;
+        let center = Self::circle(radius / 3.0, LinRgba::new(0.0, 0.0, 0.0, 1.0))?;
+        let mut frame = window.get_frame()?;
+        frame.add(&ring);
+        frame.add(&center);
+
+        let mut receiver = window.create_event_receiver();
+        let mut intervals = Vec::with_capacity(positions.len());
+        for &position in positions {
+            Self::set_position(&ring, position);
+            Self::set_position(&center, position);
+
+            // the ring shrinks to draw the gaze to the center of the target
+            let onset = Instant::now();
+            loop {
+                let t = (onset.elapsed().as_secs_f64() / self.settle).min(1.0) as f32;
+                Self::set_radius(&ring, radius * (TARGET_START_SCALE + (1.0 - TARGET_START_SCALE) * t));
+                window.present(&mut frame, None, None, false, None)?;
+                if receiver.poll().key_pressed("Escape") {
+                    return Err(PsydkError::CustomError("The calibration was aborted".into()));
+                }
+                if t >= 1.0 {
+                    break;
+                }
+            }
+
+            let start = Instant::now();
+            window.present(&mut frame, None, Some(self.duration), false, None)?;
+            intervals.push((start, Instant::now()));
+        }
+        Ok(intervals)
+    }
+
+    fn validate_point(
+        tracker: &mut dyn EyeTracker,
+        window: &Window,
+        target: (f32, f32),
+        (start, end): (Instant, Instant),
+    ) -> PsydkResult<ValidationPoint> {
+        let samples = tracker.gaze_samples(start, end)?;
+        let (size, coordinate_system, screen) =
+            window.with_state(|state| (state.size, state.coordinate_system, state.physical_screen))?;
+        let to_scene = |(x, y): (f32, f32)| coordinate_system.to_scene(x, y, size);
+
+        let gaze = (!samples.is_empty()).then(|| {
+            let n = samples.len() as f32;
+            (
+                samples.iter().map(|sample| sample.0).sum::<f32>() / n,
+                samples.iter().map(|sample| sample.1).sum::<f32>() / n,
+            )
+        });
+        let accuracy = gaze.map(|gaze| screen.visual_angle(to_scene(target), to_scene(gaze)));
+        let precision = (samples.len() > 1).then(|| {
+            let sum_of_squares = samples
+                .windows(2)
+                .map(|pair| screen.visual_angle(to_scene(pair[0]), to_scene(pair[1])).powi(2))
+                .sum::<f64>();
+            (sum_of_squares / (samples.len() - 1) as f64).sqrt()
+        });
+
+        Ok(ValidationPoint {
+            target,
+            gaze,
+            accuracy,
+            precision,
+            samples: samples.len(),
+        })
+    }
+
+    /// Show the targets and the mean gaze positions of the validation. Returns true if the
+    /// participant (or experimenter) wants to repeat the calibration.
+    fn show_report(
+        &self,
+        context: &ExperimentContext,
+        window: &Window,
+        report: &CalibrationReport,
+    ) -> PsydkResult<bool> {
+        let (size, coordinate_system, screen) =
+            window.with_state(|state| (state.size, state.coordinate_system, state.physical_screen))?;
+        let radius = self.target_radius.eval(size, screen);
+        let good = LinRgba::new(0.0, 0.8, 0.0, 1.0);
+        let bad = LinRgba::new(0.9, 0.0, 0.0, 1.0);
+
+        let mut frame = window.get_frame()?;
+        for point in &report.points {
+            let target = Self::circle(radius / 2.0, self.target_color)?;
+            Self::set_position(&target, point.target);
+            frame.add(&target);
+
+            if let (Some(gaze), Some(accuracy)) = (point.gaze, point.accuracy) {
+                let color = if accuracy <= report.max_point_error { good } else { bad };
+                let line = Self::line(color)?;
+                line.lock().set_param(
+                    "shape",
+                    StimulusParamValue::Shape(Shape::Line {
+                        x1: Size::Pixels(point.target.0),
+                        y1: Size::Pixels(point.target.1),
+                        x2: Size::Pixels(gaze.0),
+                        y2: Size::Pixels(gaze.1),
+                    }),
+                );
+                let marker = Self::circle(radius / 3.0, color)?;
+                Self::set_position(&marker, gaze);
+                frame.add(&line);
+                frame.add(&marker);
+            }
+        }
+
+        let (x, y) = coordinate_system.from_scene(0.0, size.height as f32 * 0.45, size);
+        let text = TextStimulus::new(
+            Size::Pixels(x),
+            Size::Pixels(y),
+            &format!("{}\nEnter: continue, R: repeat the calibration", report.summary()),
+            TextAlignment::Center,
+            TextDirection::Ltr,
+            TextOrientation::Horizontal,
+            Anchor::BottomCenter,
+            Size::Pixels(size.height as f32 / 40.0),
+            &[],
+            FontWeight::Regular,
+            self.target_color,
+            1.0,
+            0.0,
+            Transformation2D::Identity(),
+            context,
+        );
+        frame.add(&DynamicStimulus::new(text));
+
+        let mut receiver = window.create_event_receiver();
+        loop {
+            window.present(&mut frame, None, None, false, None)?;
+            let events = receiver.poll();
+            if events.key_pressed("Escape") {
+                return Err(PsydkError::CustomError("The calibration was aborted".into()));
+            }
+            if events.key_pressed("Enter") || events.key_pressed("Space") {
+                return Ok(false);
+            }
+            if events.key_pressed("r") || events.key_pressed("R") {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Calibrate `tracker` on `window` and validate the calibration. The calibration is repeated
+    /// until it is accepted on the report screen.
+    pub fn run(
+        &self,
+        context: &ExperimentContext,
+        window: &Window,
+        tracker: &mut dyn EyeTracker,
+    ) -> PsydkResult<CalibrationReport> {
+        if self.settle <= 0.0 || self.duration <= 0.0 || !(self.area > 0.0 && self.area <= 1.0) {
+            return Err(PsydkError::ParameterError(
+                "The settle time and duration must be positive and the area between 0 and 1".into(),
+            ));
+        }
+        let (size, coordinate_system) = window.with_state(|state| (state.size, state.coordinate_system))?;
+        let positions = self
+            .positions(size)?
+            .into_iter()
+            .map(|(x, y)| coordinate_system.from_scene(x, y, size))
+            .collect::<Vec<_>>();
+
+        let mut rng = rand::thread_rng();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            let mut order = positions.clone();
+            order.shuffle(&mut rng);
+            tracker.start_calibration()?;
+            for (&(x, y), (start, end)) in order.iter().zip(self.show_targets(window, &order)?) {
+                tracker.calibration_point(x, y, start, end)?;
+            }
+            tracker.finish_calibration()?;
+
+            let mut report = CalibrationReport {
+                points: Vec::new(),
+                max_mean_error: self.max_mean_error,
+                max_point_error: self.max_point_error,
+                attempts,
+            };
+            if !self.validate {
+                return Ok(report);
+            }
+
+            // validate in a different order, so that the participant can't anticipate the targets
+            order.shuffle(&mut rng);
+            // leave some time for the tracker to deliver the last samples
+            let intervals = self.show_targets(window, &order)?;
+            std::thread::sleep(Duration::from_millis(100));
+            for (&target, interval) in order.iter().zip(intervals) {
+                report
+                    .points
+                    .push(Self::validate_point(tracker, window, target, interval)?);
+            }
+            report.log();
+
+            if !self.show_report(context, window, &report)? {
+                return Ok(report);
+            }
+        }
+    }
+}
+
+/// Calibrate an eye tracker and validate the calibration with built-in targets.
+///
+/// A target is shown at each of 9 or 13 points in random order. The outer ring of the target
+/// shrinks to draw the gaze to its center, and the tracker is told when the target was fixated.
+/// The targets are then shown again in a different order, and the gaze samples of the tracker are
+/// compared with the targets: accuracy is the angle between the target and the mean gaze position,
+/// precision the RMS of the angles between successive samples (both in degrees of visual angle,
+/// which requires the physical size of the screen and the viewing distance to be set). The results
+/// are logged and shown on the screen; the calibration is repeated with R and accepted with enter.
+/// Escape aborts.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to show the targets on.
+/// tracker : object
+///   The eye tracker, e.g. a `PupilLabsTracker`. Any object with a `gaze_samples(start, end)`
+///   method that returns the gaze positions (in the coordinate system of the window) between two
+///   `Timestamp`s as a list of `(x, y)` tuples can be used. Trackers that need calibration data can
+///   also have `start_calibration()`, `calibration_point(x, y, start, end)`, and
+///   `finish_calibration()` methods.
+/// points : int, optional
+///   The number of points, 9 (a 3 x 3 grid) or 13 (the grid and 4 points between).
+/// area : float, optional
+///   The fraction of the window (from the center to the edges) that the points cover.
+/// target_radius : Size, num, or str, optional
+///   The radius of a target. Defaults to 0.5 degrees of visual angle.
+/// target_color : LinRgba, optional
+///   The color of the targets. Defaults to white.
+/// settle : float, optional
+///   How long a target shrinks before it is fixated, in seconds.
+/// duration : float, optional
+///   How long gaze is recorded at each target, in seconds.
+/// validate : bool, optional
+///   Whether to validate the calibration.
+/// max_mean_error : float, optional
+///   The largest acceptable mean accuracy in degrees.
+/// max_point_error : float, optional
+///   The largest acceptable accuracy at any point in degrees.
+///
+/// Returns
+/// -------
+/// CalibrationReport
+///   The validation of the last calibration. Use `save` to store it with the data.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "calibrate_eye_tracker")]
+#[pyo3(signature = (
+    window,
+    tracker,
+    points = 9,
+    area = 0.8,
+    target_radius = IntoSize(Size::Degrees(0.5)),
+    target_color = IntoLinRgba::new(1.0, 1.0, 1.0, 1.0),
+    settle = 0.8,
+    duration = 1.0,
+    validate = true,
+    max_mean_error = 1.0,
+    max_point_error = 1.5,
+))]
+pub fn py_calibrate_eye_tracker(
+    py: Python,
+    window: Window,
+    tracker: Py<PyAny>,
+    points: u32,
+    area: f32,
+    target_radius: IntoSize,
+    target_color: IntoLinRgba,
+    settle: f64,
+    duration: f64,
+    validate: bool,
+    max_mean_error: f64,
+    max_point_error: f64,
+) -> PyResult<CalibrationReport> {
+    let calibration = Calibration {
+        points,
+        area,
+        target_radius: target_radius.into(),
+        target_color: target_color.into(),
+        settle,
+        duration,
+        validate,
+        max_mean_error,
+        max_point_error,
+    };
+    let context = SendWrapper::new(super::stimuli::helpers::get_experiment_context(None, py)?);
+    let window = SendWrapper::new(window);
+    let mut tracker = PythonEyeTracker(tracker);
+    Ok(py.allow_threads(move || calibration.run(&context, &window, &mut tracker))?)
+}
@@ -1,4 +1,5 @@
 use csscolorparser;
+#[cfg(feature = "python")]
 use pyo3::{prelude::*, types::PyTuple};
 
 use crate::visual::geometry::IntoSize;
@@ -128,6 +129,7 @@ impl From<IntoLinRgba> for LinRgba {
     }
 }
 
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for IntoLinRgba {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         // try to extract a LinRgba from the object
@@ -156,6 +158,7 @@ impl<'py> FromPyObject<'py> for IntoLinRgba {
 }
 
 // expose functons to python to create a LinRgba
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "rgb")]
 #[pyo3(signature = (r, g, b, a = 1.0))]
@@ -182,6 +185,7 @@ pub fn py_rgb(r: f32, g: f32, b: f32, a: f32) -> LinRgba {
     LinRgba::from_srgba(r, g, b, a)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "linrgb")]
 #[pyo3(signature = (r, g, b, a = 1.0))]
@@ -207,6 +211,7 @@ pub fn py_linrgb(r: f32, g: f32, b: f32, a: f32) -> LinRgba {
 }
 
 // implement IntoPyObject for LinRgba
+#[cfg(feature = "python")]
 impl<'py> IntoPyObject<'py> for LinRgba {
     type Target = PyTuple;
     type Output = Bound<'py, Self::Target>;
@@ -219,6 +224,7 @@ impl<'py> IntoPyObject<'py> for LinRgba {
 }
 
 // allow Python tuples to be converted to LinRgba
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for LinRgba {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         // try to extract a tuple of 3 (alpha implicitly set to 1.0)
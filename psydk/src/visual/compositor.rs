@@ -0,0 +1,131 @@
+//! Platform hints that reduce interference of the desktop compositor with frame timing. They are
+//! applied to windows that are created with `timing_critical` set.
+
+use winit::{monitor::MonitorHandle, window::Fullscreen};
+
+/// The fullscreen mode of a timing-critical window.
+///
+/// On Windows, this is exclusive fullscreen in the video mode of the monitor with the highest
+/// refresh rate, so that frames are flipped directly to the display instead of being composed by
+/// DWM. On other platforms, exclusive fullscreen changes the video mode without any benefit for
+/// timing, so borderless fullscreen is used.
+pub(crate) fn fullscreen(monitor: &MonitorHandle) -> Fullscreen {
+    #[cfg(target_os = "windows")]
+    {
+        let size = monitor.size();
+        let mode = monitor
+            .video_modes()
+            .filter(|mode| mode.size() == size)
+            .max_by_key(|mode| (mode.refresh_rate_millihertz(), mode.bit_depth()));
+        if let Some(mode) = mode {
+            return Fullscreen::Exclusive(mode);
+        }
+        log::warn!("No video mode found for exclusive fullscreen, using borderless fullscreen");
+    }
+
+    Fullscreen::Borderless(Some(monitor.clone()))
+}
+
+/// Apply the platform hints to a window. Must be called after the surface has been created, as the
+/// hints on macOS are set on the layer that backs the surface. Failures are logged, not returned, as
+/// the window is still usable without the hints.
+pub(crate) fn apply_timing_hints(window: &winit::window::Window) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = window;
+        windows_hints();
+    }
+
+    #[cfg(target_os = "macos")]
+    macos_hints(window);
+
+    #[cfg(target_os = "linux")]
+    linux_hints(window);
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = window;
+        log::debug!("No timing hints are available on this platform");
+    }
+}
+
+/// Let DWM schedule its composition thread with the multimedia class scheduler, which reduces
+/// glitches for windows that are still composed (e.g. when exclusive fullscreen was not granted).
+#[cfg(target_os = "windows")]
+fn windows_hints() {
+    use windows::Win32::{Foundation::BOOL, Graphics::Dwm::DwmEnableMMCSS};
+
+    if let Err(e) = unsafe { DwmEnableMMCSS(BOOL::from(true)) } {
+        log::warn!("Failed to enable MMCSS for the desktop window manager: {}", e);
+    }
+}
+
+/// Present in sync with the display and without waiting for Core Animation transactions, so that
+/// frames are not delayed by the window server.
+#[cfg(target_os = "macos")]
+fn macos_hints(window: &winit::window::Window) {
+    use objc2::{
+        class, msg_send,
+        runtime::{AnyObject, Bool},
+    };
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    let Ok(RawWindowHandle::AppKit(handle)) = window.window_handle().map(|handle| handle.as_raw()) else {
+        log::warn!("Failed to get the view of the window, timing hints are not applied");
+        return;
+    };
+
+    unsafe {
+        let view = handle.ns_view.as_ptr() as *mut AnyObject;
+        let layer: *mut AnyObject = msg_send![view, layer];
+        if layer.is_null() {
+            log::warn!("The window has no layer, timing hints are not applied");
+            return;
+        }
+
+        let is_metal_layer: Bool = msg_send![layer, isKindOfClass: class!(CAMetalLayer)];
+        if !is_metal_layer.as_bool() {
+            log::warn!("The window is not backed by a CAMetalLayer, timing hints are not applied");
+            return;
+        }
+
+        let _: () = msg_send![layer, setDisplaySyncEnabled: Bool::YES];
+        let _: () = msg_send![layer, setPresentsWithTransaction: Bool::NO];
+    }
+}
+
+/// Ask the compositor to unredirect the fullscreen window (`_NET_WM_BYPASS_COMPOSITOR`), which is
+/// honored by most X11 compositors. There is no equivalent on Wayland.
+#[cfg(target_os = "linux")]
+fn linux_hints(window: &winit::window::Window) {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use x11rb::{
+        connection::Connection,
+        protocol::xproto::{AtomEnum, ConnectionExt, PropMode},
+        wrapper::ConnectionExt as _,
+    };
+
+    let window_id = match window.window_handle().map(|handle| handle.as_raw()) {
+        Ok(RawWindowHandle::Xlib(handle)) => handle.window as u32,
+        Ok(RawWindowHandle::Xcb(handle)) => handle.window.get(),
+        _ => {
+            log::debug!("Compositor bypass is only available on X11");
+            return;
+        }
+    };
+
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let (connection, _) = x11rb::connect(None)?;
+        let atom = connection
+            .intern_atom(false, b"_NET_WM_BYPASS_COMPOSITOR")?
+            .reply()?
+            .atom;
+        connection.change_property32(PropMode::REPLACE, window_id, atom, AtomEnum::CARDINAL, &[1])?;
+        connection.flush()?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        log::warn!("Failed to ask the compositor to unredirect the window: {}", e);
+    }
+}
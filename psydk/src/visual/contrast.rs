@@ -0,0 +1,326 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use rand::Rng;
+use send_wrapper::SendWrapper;
+
+use super::{
+    color::LinRgba,
+    geometry::{Anchor, IntoSize, Size},
+    stimuli::{
+        gabor::{ColorInterpolation, GaborStimulus, Pattern},
+        DynamicStimulus, StimulusParamValue,
+    },
+    window::Window,
+};
+use crate::errors::{PsydkError, PsydkResult};
+
+/// The background of the procedure. Gratings are drawn on it with a Michelson contrast equal to
+/// their opacity.
+const MID_GRAY: LinRgba = LinRgba {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+    a: 1.0,
+};
+/// The orientation of the gratings relative to vertical in degrees.
+const TILT: f64 = 45.0;
+/// The number of reversals at the start of the staircase that are not used for the threshold.
+const DISCARDED_REVERSALS: usize = 2;
+
+fn to_db(contrast: f64) -> f64 {
+    20.0 * contrast.log10()
+}
+
+fn from_db(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// The result of a contrast detection staircase.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct ContrastThreshold {
+    /// The Michelson contrast of every trial.
+    pub contrasts: Vec<f64>,
+    /// Whether the response of every trial was correct.
+    pub correct: Vec<bool>,
+    /// The contrast in dB at every reversal of the staircase.
+    pub reversals: Vec<f64>,
+}
+
+impl ContrastThreshold {
+    /// The threshold in dB (20 log10 of the Michelson contrast), i.e. the mean of all reversals
+    /// except the first ones. `None` if there are not enough reversals.
+    pub fn threshold_db(&self) -> Option<f64> {
+        let reversals = self.reversals.get(DISCARDED_REVERSALS..).filter(|r| !r.is_empty())?;
+        Some(reversals.iter().sum::<f64>() / reversals.len() as f64)
+    }
+
+    /// The threshold as a Michelson contrast.
+    pub fn threshold(&self) -> Option<f64> {
+        self.threshold_db().map(from_db)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ContrastThreshold {
+    /// The Michelson contrast of every trial.
+    #[getter(contrasts)]
+    fn py_contrasts(&self) -> Vec<f64> {
+        self.contrasts.clone()
+    }
+
+    /// Whether the response of every trial was correct.
+    #[getter(correct)]
+    fn py_correct(&self) -> Vec<bool> {
+        self.correct.clone()
+    }
+
+    /// The contrast in dB at every reversal of the staircase.
+    #[getter(reversals)]
+    fn py_reversals(&self) -> Vec<f64> {
+        self.reversals.clone()
+    }
+
+    /// The threshold in dB (20 log10 of the Michelson contrast), or None if the staircase ended
+    /// before enough reversals.
+    #[getter(threshold_db)]
+    fn py_threshold_db(&self) -> Option<f64> {
+        self.threshold_db()
+    }
+
+    /// The threshold as a Michelson contrast, or None if the staircase ended before enough
+    /// reversals.
+    #[getter(threshold)]
+    fn py_threshold(&self) -> Option<f64> {
+        self.threshold()
+    }
+
+    fn __repr__(&self) -> String {
+        match self.threshold_db() {
+            Some(db) => format!(
+                "ContrastThreshold(threshold_db={:.2}, threshold={:.5}, trials={})",
+                db,
+                from_db(db),
+                self.contrasts.len()
+            ),
+            None => format!("ContrastThreshold(threshold_db=None, trials={})", self.contrasts.len()),
+        }
+    }
+}
+
+/// A 3-down-1-up staircase on the contrast of a Gabor patch (converging to 79.4% correct). The
+/// patch is tilted to the left or to the right, and the participant reports the tilt with the left
+/// and right arrow keys. Escape aborts the procedure.
+///
+/// The contrast is changed in steps of constant size in dB, so on a correctly linearized display,
+/// each step changes the physical contrast by the same factor.
+#[derive(Debug, Clone)]
+pub struct ContrastStaircase {
+    /// The Michelson contrast of the first trial.
+    pub start_contrast: f64,
+    /// The change of the contrast per step in dB.
+    pub step_db: f64,
+    /// The number of reversals after which the staircase ends.
+    pub reversals: u32,
+    /// The maximum number of trials.
+    pub max_trials: u32,
+    /// The radius of the patch.
+    pub radius: Size,
+    /// The length of one cycle of the grating.
+    pub cycle_length: Size,
+    /// How long the patch is shown in seconds.
+    pub duration: f64,
+}
+
+impl ContrastStaircase {
+    /// Run the staircase on `window` and return the threshold.
+    pub fn run(&self, window: &Window) -> PsydkResult<ContrastThreshold> {
+        if !(self.start_contrast > 0.0 && self.start_contrast <= 1.0) {
+            return Err(PsydkError::ParameterError(
+                "The start contrast must be between 0 (exclusive) and 1".into(),
+            ));
+        }
+        if self.step_db <= 0.0 || self.duration <= 0.0 || self.reversals as usize <= DISCARDED_REVERSALS {
+            return Err(PsydkError::ParameterError(format!(
+                "The step and the duration must be positive and there must be more than {DISCARDED_REVERSALS} reversals"
+            )));
+        }
+
+        let gabor = DynamicStimulus::new(GaborStimulus::new(
+            Size::Pixels(0.0),
+            Size::Pixels(0.0),
+            self.radius.clone(),
+            Pattern::Sine,
+            self.cycle_length.clone(),
+            0.0,
+            self.radius.clone(),
+            0.0,
+            Anchor::Center,
+            ColorInterpolation::Linear,
+            None,
+            None,
+            None,
+            Some(self.start_contrast),
+        ));
+
+        let bg_color = window.with_state(|state| std::mem::replace(&mut state.bg_color, MID_GRAY))?;
+        let result = self.run_trials(window, &gabor);
+        // the window might have been closed in the meantime
+        let _ = window.with_state(|state| state.bg_color = bg_color);
+
+        result
+    }
+
+    fn run_trials(&self, window: &Window, gabor: &DynamicStimulus) -> PsydkResult<ContrastThreshold> {
+        let mut stimulus_frame = window.get_frame()?;
+        stimulus_frame.add(gabor);
+        let mut blank_frame = window.get_frame()?;
+
+        let mut receiver = window.create_event_receiver();
+        let mut rng = rand::thread_rng();
+        let mut result = ContrastThreshold {
+            contrasts: Vec::new(),
+            correct: Vec::new(),
+            reversals: Vec::new(),
+        };
+
+        let mut db = to_db(self.start_contrast);
+        let mut correct_in_a_row = 0;
+        // the last direction of the staircase, true if the contrast was lowered
+        let mut last_direction = None;
+        while result.reversals.len() < self.reversals as usize && result.contrasts.len() < self.max_trials as usize {
+            let contrast = from_db(db);
+            let tilted_left = rng.gen_bool(0.5);
+            {
+                let mut gabor = gabor.lock();
+                gabor.set_param("alpha", StimulusParamValue::f64(contrast));
+                gabor.set_param(
+                    "orientation",
+                    StimulusParamValue::f64(if tilted_left { -TILT } else { TILT }),
+                );
+            }
+
+            window.present(&mut blank_frame, None, Some(0.5), false, None)?;
+            receiver.flush();
+            window.present(&mut stimulus_frame, None, Some(self.duration), false, None)?;
+
+            let responded_left = loop {
+                let events = receiver.poll();
+                if events.key_pressed("Escape") {
+                    return Err(PsydkError::CustomError("The contrast staircase was aborted".into()));
+                }
+                if events.key_pressed("ArrowLeft") {
+                    break true;
+                }
+                if events.key_pressed("ArrowRight") {
+                    break false;
+                }
+                window.present(&mut blank_frame, None, None, false, None)?;
+            };
+
+            let correct = responded_left == tilted_left;
+            result.contrasts.push(contrast);
+            result.correct.push(correct);
+
+            let direction = if correct {
+                correct_in_a_row += 1;
+                if correct_in_a_row < 3 {
+                    continue;
+                }
+                correct_in_a_row = 0;
+                true
+            } else {
+                correct_in_a_row = 0;
+                false
+            };
+
+            if last_direction.is_some_and(|last| last != direction) {
+                result.reversals.push(db);
+            }
+            last_direction = Some(direction);
+            db = if direction {
+                db - self.step_db
+            } else {
+                (db + self.step_db).min(0.0)
+            };
+        }
+
+        Ok(result)
+    }
+}
+
+/// Measure the contrast detection threshold with a staircase. This is meant as a quick check of the
+/// gamma correction (or LUT) of a display: the staircase changes the contrast in steps of constant
+/// size in dB, which only result in constant physical steps if the display is linearized.
+///
+/// A Gabor patch tilted 45 degrees to the left or to the right is shown on a mid-gray background,
+/// and the participant reports the tilt with the left and right arrow keys. The contrast is lowered
+/// after three correct responses in a row and raised after every wrong response. Escape aborts.
+/// The threshold is the mean contrast at the reversals of the staircase, except the first two.
+///
+/// For a grating of a few cycles per degree, thresholds of normal observers are typically around
+/// -40 dB (1% contrast). A much higher threshold, or reversals that stay at the same contrast,
+/// suggest that small contrast steps are lost, e.g. because of a wrong LUT or too few bits per
+/// channel.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to run the procedure on.
+/// start_contrast : float, optional
+///   The Michelson contrast of the first trial, between 0 and 1.
+/// step_db : float, optional
+///   The change of the contrast per step in dB.
+/// reversals : int, optional
+///   The number of reversals after which the staircase ends.
+/// max_trials : int, optional
+///   The maximum number of trials.
+/// radius : Size, num, or str, optional
+///   The radius of the patch. Defaults to 2 degrees of visual angle.
+/// cycle_length : Size, num, or str, optional
+///   The length of one cycle of the grating. Defaults to 0.5 degrees of visual angle (2 cycles per
+///   degree).
+/// duration : float, optional
+///   How long the patch is shown in seconds.
+///
+/// Returns
+/// -------
+/// ContrastThreshold
+///   The threshold and the contrast and response of every trial.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "contrast_staircase")]
+#[pyo3(signature = (
+    window,
+    start_contrast = 0.1,
+    step_db = 2.0,
+    reversals = 10,
+    max_trials = 120,
+    radius = IntoSize(Size::Degrees(2.0)),
+    cycle_length = IntoSize(Size::Degrees(0.5)),
+    duration = 0.3,
+))]
+pub fn py_contrast_staircase(
+    py: Python,
+    window: Window,
+    start_contrast: f64,
+    step_db: f64,
+    reversals: u32,
+    max_trials: u32,
+    radius: IntoSize,
+    cycle_length: IntoSize,
+    duration: f64,
+) -> PyResult<ContrastThreshold> {
+    let staircase = ContrastStaircase {
+        start_contrast,
+        step_db,
+        reversals,
+        max_trials,
+        radius: radius.into(),
+        cycle_length: cycle_length.into(),
+        duration,
+    };
+    let window = SendWrapper::new(window);
+    Ok(py.allow_threads(move || staircase.run(&window))?)
+}
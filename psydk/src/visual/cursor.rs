@@ -0,0 +1,169 @@
+//! The appearance of the mouse cursor.
+//!
+//! A cursor is hidden, one of the shapes of the platform (e.g., an arrow, a crosshair, or a hand),
+//! or an image with a hotspot. Images are turned into platform cursors by the event loop once, so
+//! switching between cursors (e.g., from one phase of a trial to the next) is cheap.
+
+use std::{path::PathBuf, str::FromStr};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use winit::window::{CursorIcon, CustomCursor};
+
+use super::stimuli::helpers::get_experiment_context;
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+};
+
+/// The appearance of the mouse cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cursor {
+    Hidden,
+    System(CursorIcon),
+    Image(CustomCursor),
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::System(CursorIcon::Default)
+    }
+}
+
+impl Cursor {
+    /// A shape of the platform by its CSS name, e.g. "default", "crosshair", "pointer", or "wait".
+    pub fn system(name: &str) -> PsydkResult<Self> {
+        CursorIcon::from_str(name)
+            .map(Cursor::System)
+            .map_err(|_| PsydkError::ParameterError(format!("Unknown cursor shape \"{name}\"")))
+    }
+
+    /// An image (as RGBA pixels) whose pixel at `hotspot` is the position of the cursor.
+    pub fn image(
+        context: &ExperimentContext,
+        image: &renderer::image::RgbaImage,
+        hotspot: (u16, u16),
+    ) -> PsydkResult<Self> {
+        let (width, height) = image.dimensions();
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(PsydkError::ParameterError(format!(
+                "The cursor image is too large ({width}x{height} px)"
+            )));
+        };
+        if hotspot.0 >= width || hotspot.1 >= height {
+            return Err(PsydkError::ParameterError(format!(
+                "The hotspot {hotspot:?} is outside of the cursor image ({width}x{height} px)"
+            )));
+        }
+
+        let source = CustomCursor::from_rgba(image.as_raw().clone(), width, height, hotspot.0, hotspot.1)
+            .map_err(|e| PsydkError::ParameterError(format!("Invalid cursor image: {e}")))?;
+        Ok(Cursor::Image(context.create_custom_cursor(source)?))
+    }
+
+    /// Whether the cursor is visible.
+    pub fn is_visible(&self) -> bool {
+        !matches!(self, Cursor::Hidden)
+    }
+
+    /// Show the cursor on `window`.
+    pub fn apply(&self, window: &winit::window::Window) {
+        match self {
+            Cursor::Hidden => window.set_cursor_visible(false),
+            Cursor::System(icon) => {
+                window.set_cursor(*icon);
+                window.set_cursor_visible(true);
+            }
+            Cursor::Image(cursor) => {
+                window.set_cursor(cursor.clone());
+                window.set_cursor_visible(true);
+            }
+        }
+    }
+}
+
+/// The appearance of the mouse cursor, set with `Window.cursor` or, for a single frame onwards,
+/// with `Frame.cursor`.
+///
+/// Cursors are created with `Cursor.system`, `Cursor.image`, or `Cursor.hidden`. Creating a cursor
+/// from an image takes some time, so create image cursors once, before the trials, and switch
+/// between them as needed.
+#[cfg(feature = "python")]
+#[pyclass(name = "Cursor")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PyCursor(pub Cursor);
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyCursor {
+    /// A cursor shape of the platform.
+    ///
+    /// Parameters
+    /// ----------
+    /// name : str, optional
+    ///   The name of the shape as in CSS, e.g. "default" (an arrow), "crosshair", "pointer" (a
+    ///   hand), "text", "wait", "grab", "move", or "not-allowed". Defaults to "default".
+    ///
+    /// Returns
+    /// -------
+    /// Cursor
+    ///   The cursor.
+    #[staticmethod]
+    #[pyo3(name = "system")]
+    #[pyo3(signature = (name = "default"))]
+    fn py_system(name: &str) -> PyResult<Self> {
+        Ok(Self(Cursor::system(name)?))
+    }
+
+    /// A cursor that shows an image.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///   The image file. Transparent pixels of the image are transparent in the cursor. Most
+    ///   platforms limit the size of cursors (e.g., to 256x256 px on Windows) and some scale them.
+    /// hotspot : tuple[int, int], optional
+    ///   The pixel of the image (from the top left corner) that marks the position of the cursor.
+    ///   Defaults to the top left corner.
+    /// context : ExperimentContext, optional
+    ///   The experiment context. Defaults to the context of the experiment function.
+    ///
+    /// Returns
+    /// -------
+    /// Cursor
+    ///   The cursor.
+    #[staticmethod]
+    #[pyo3(name = "image")]
+    #[pyo3(signature = (path, hotspot = (0, 0), context = None))]
+    fn py_image(path: PathBuf, hotspot: (u16, u16), context: Option<ExperimentContext>, py: Python) -> PyResult<Self> {
+        let context = get_experiment_context(context, py)?;
+        let image = renderer::image::open(&path)
+            .map_err(PsydkError::ImageError)?
+            .into_rgba8();
+        Ok(py.allow_threads(|| Cursor::image(&context, &image, hotspot).map(Self))?)
+    }
+
+    /// A hidden cursor.
+    ///
+    /// Returns
+    /// -------
+    /// Cursor
+    ///   The cursor.
+    #[staticmethod]
+    #[pyo3(name = "hidden")]
+    fn py_hidden() -> Self {
+        Self(Cursor::Hidden)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.0 {
+            Cursor::Hidden => "Cursor.hidden()".to_string(),
+            Cursor::System(icon) => format!("Cursor.system(\"{}\")", icon.name()),
+            Cursor::Image(_) => "Cursor.image(...)".to_string(),
+        }
+    }
+}
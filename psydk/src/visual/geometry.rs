@@ -4,11 +4,14 @@
 
 use nalgebra::{Matrix3, Vector3};
 use num_traits::Float;
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
 use pyo3::{prelude::*, PyClass};
+use strum::EnumString;
 
 use super::window::{PhysicalScreen, PixelSize, Window};
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Clone, Debug)]
 pub struct BoxedSize(Box<Size>);
 
@@ -69,7 +72,7 @@ impl NumberOrSize {
 /// let unit = Size::ScreenHeight(0.1);
 /// ```
 #[derive(Clone, Debug)]
-#[pyclass(module = "psydk.visual")]
+#[cfg_attr(feature = "python", pyclass(module = "psydk.visual"))]
 pub enum Size {
     // Physical pixels
     Pixels(f32),
@@ -77,6 +80,10 @@ pub enum Size {
     ViewportHeight(f32),
     /// Fraction of the screen width.
     ViewportWidth(f32),
+    /// Normalized to half the screen width, so that -1 to 1 spans the screen horizontally.
+    NormalizedWidth(f32),
+    /// Normalized to half the screen height, so that -1 to 1 spans the screen vertically.
+    NormalizedHeight(f32),
     /// Degrees of visual angle.
     Degrees(f32),
     /// Millimeters.
@@ -105,6 +112,7 @@ impl From<IntoSize> for Size {
     }
 }
 
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for IntoSize {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         // try to extract a Size
@@ -119,7 +127,9 @@ impl<'py> FromPyObject<'py> for IntoSize {
         }
         // try to extract a string and use a regex to parse it
         else if let Ok(value) = ob.extract::<String>() {
-            Ok(IntoSize(Size::from_str(&value).unwrap()))
+            Size::from_str(&value)
+                .map(IntoSize)
+                .map_err(pyo3::exceptions::PyValueError::new_err)
         } else {
             return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("Size must be a float."));
         }
@@ -219,6 +229,26 @@ impl std::ops::Div<f32> for Size {
     }
 }
 
+impl Size {
+    /// The size multiplied by `factor`. Unlike `size * factor`, sizes with a single unit keep that
+    /// unit, e.g. `Size::Degrees(2.0).scaled(1.5)` is `Size::Degrees(3.0)`.
+    pub fn scaled(&self, factor: f32) -> Size {
+        match self {
+            Size::Pixels(value) => Size::Pixels(value * factor),
+            Size::ViewportHeight(value) => Size::ViewportHeight(value * factor),
+            Size::ViewportWidth(value) => Size::ViewportWidth(value * factor),
+            Size::NormalizedWidth(value) => Size::NormalizedWidth(value * factor),
+            Size::NormalizedHeight(value) => Size::NormalizedHeight(value * factor),
+            Size::Degrees(value) => Size::Degrees(value * factor),
+            Size::Millimeters(value) => Size::Millimeters(value * factor),
+            Size::Centimeters(value) => Size::Centimeters(value * factor),
+            Size::Inches(value) => Size::Inches(value * factor),
+            Size::Points(value) => Size::Points(value * factor),
+            _ => self.clone() * factor,
+        }
+    }
+}
+
 // implements the minus operator for a single size
 impl std::ops::Neg for Size {
     type Output = Size;
@@ -266,6 +296,8 @@ impl Size {
             Size::Pixels(pixels) => *pixels,
             Size::ViewportWidth(normalised) => *normalised * window_size.width as f32,
             Size::ViewportHeight(normalised) => *normalised * window_size.height as f32,
+            Size::NormalizedWidth(normalised) => *normalised * window_size.width as f32 / 2.0,
+            Size::NormalizedHeight(normalised) => *normalised * window_size.height as f32 / 2.0,
             Size::Degrees(degrees) => {
                 Size::angle_to_milimeter(*degrees, window_props.viewing_distance).eval(window_size, window_props)
             }
@@ -322,7 +354,9 @@ impl Size {
         match unit {
             "px" => Ok(Size::Pixels(if negative { -number } else { number })),
             "vw" => Ok(Size::ViewportWidth(if negative { -number } else { number })),
-            "vh" => Ok(Size::ViewportHeight(if negative { -number } else { number })),
+            "vh" | "height" => Ok(Size::ViewportHeight(if negative { -number } else { number })),
+            "nw" => Ok(Size::NormalizedWidth(if negative { -number } else { number })),
+            "nh" => Ok(Size::NormalizedHeight(if negative { -number } else { number })),
             "deg" => Ok(Size::Degrees(if negative { -number } else { number })),
             "mm" => Ok(Size::Millimeters(if negative { -number } else { number })),
             "cm" => Ok(Size::Centimeters(if negative { -number } else { number })),
@@ -333,13 +367,13 @@ impl Size {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Size {
     // constructors
     #[new]
     fn __new__(string: String) -> PyResult<Size> {
-        let size = Size::from_str(&string).unwrap();
-        Ok(size)
+        Size::from_str(&string).map_err(pyo3::exceptions::PyValueError::new_err)
     }
 
     // printing
@@ -369,6 +403,23 @@ impl Size {
         let window_state = window_state.as_ref().unwrap();
         self.eval(window_state.size, window_state.physical_screen)
     }
+
+    /// Convert the size to pixels for the given window, taking its size, the physical size of the
+    /// screen, and the viewing distance into account.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window to convert the size for.
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///   The size in pixels.
+    #[pyo3(name = "to_pixels")]
+    fn py_to_pixels(&self, window: &Window) -> f32 {
+        self.py_eval(window)
+    }
 }
 
 impl SizeVector2D {
@@ -393,7 +444,7 @@ impl SizeVector2D {
     }
 }
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Debug, Clone)]
 pub struct BoxedTransformation2D(Box<Transformation2D>);
 
@@ -423,7 +474,7 @@ impl std::ops::Deref for BoxedTransformation2D {
 /// the experiment, the transformation matrix of the object can only be known at
 /// the time of rendering.
 #[derive(Debug, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum Transformation2D {
     /// Identity transformation (no transformation).
     Identity(),
@@ -542,6 +593,7 @@ impl Transformation2D {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Transformation2D {
     /// Create a new identity transformation.
@@ -581,7 +633,7 @@ impl std::ops::Mul for Transformation2D {
 
 // basic 2d shapes
 #[derive(Debug, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum Shape {
     /// A rectangle.
     Rectangle {
@@ -612,6 +664,96 @@ pub enum Shape {
     Path { points: Vec<(Size, Size)> },
 }
 
+impl Shape {
+    /// The bounding box of the shape (in pixels, in the coordinate system of the window), given by
+    /// the corner with the smallest coordinates and the width and height.
+    pub fn bounds(&self, window_size: PixelSize, screen: PhysicalScreen) -> (f32, f32, f32, f32) {
+        let eval = |size: &Size| size.eval(window_size, screen);
+
+        let (x0, y0, x1, y1) = match self {
+            Shape::Rectangle { x, y, width, height } => {
+                let (x, y) = (eval(x), eval(y));
+                (x, y, x + eval(width), y + eval(height))
+            }
+            Shape::Circle { x, y, radius } => {
+                let (x, y, radius) = (eval(x), eval(y), eval(radius));
+                (x - radius, y - radius, x + radius, y + radius)
+            }
+            Shape::Ellipse {
+                x,
+                y,
+                radius_x,
+                radius_y,
+            } => {
+                let (x, y, radius_x, radius_y) = (eval(x), eval(y), eval(radius_x), eval(radius_y));
+                (x - radius_x, y - radius_y, x + radius_x, y + radius_y)
+            }
+            Shape::Line { x1, y1, x2, y2 } => (eval(x1), eval(y1), eval(x2), eval(y2)),
+            Shape::Polygon { points } | Shape::Path { points } => {
+                let points = points.iter().map(|(px, py)| (eval(px), eval(py))).collect::<Vec<_>>();
+                let min_x = points.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+                let min_y = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+                let max_x = points.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+                let max_y = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+                if points.is_empty() {
+                    (0.0, 0.0, 0.0, 0.0)
+                } else {
+                    (min_x, min_y, max_x, max_y)
+                }
+            }
+        };
+
+        (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+
+    /// Returns true if the point (in pixels, in the coordinate system of the window) lies inside the
+    /// shape. Lines have no area and never contain a point; paths are treated as closed polygons.
+    pub fn contains_point(&self, x: f32, y: f32, window_size: PixelSize, screen: PhysicalScreen) -> bool {
+        let eval = |size: &Size| size.eval(window_size, screen);
+
+        match self {
+            Shape::Rectangle {
+                x: rx,
+                y: ry,
+                width,
+                height,
+            } => {
+                let (x0, x1) = (eval(rx), eval(rx) + eval(width));
+                let (y0, y1) = (eval(ry), eval(ry) + eval(height));
+                x >= x0.min(x1) && x <= x0.max(x1) && y >= y0.min(y1) && y <= y0.max(y1)
+            }
+            Shape::Circle { x: cx, y: cy, radius } => {
+                let (dx, dy) = (x - eval(cx), y - eval(cy));
+                dx * dx + dy * dy <= eval(radius).powi(2)
+            }
+            Shape::Ellipse {
+                x: cx,
+                y: cy,
+                radius_x,
+                radius_y,
+            } => {
+                let dx = (x - eval(cx)) / eval(radius_x);
+                let dy = (y - eval(cy)) / eval(radius_y);
+                dx * dx + dy * dy <= 1.0
+            }
+            Shape::Line { .. } => false,
+            Shape::Polygon { points } | Shape::Path { points } => {
+                // even-odd rule: count the edges that a ray from the point to the right crosses
+                let points = points.iter().map(|(px, py)| (eval(px), eval(py))).collect::<Vec<_>>();
+                let mut inside = false;
+                for (i, &(xi, yi)) in points.iter().enumerate() {
+                    let (xj, yj) = points[(i + points.len() - 1) % points.len()];
+                    if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl Shape {
     #[staticmethod]
@@ -769,7 +911,7 @@ impl Anchor {
     }
 
     pub fn from_str(string: &str) -> Result<Anchor, String> {
-        match string {
+        match string.replace('_', "-").as_str() {
             "top-left" => Ok(Anchor::TopLeft),
             "top-center" => Ok(Anchor::TopCenter),
             "top-right" => Ok(Anchor::TopRight),
@@ -785,11 +927,12 @@ impl Anchor {
 }
 
 // implement FromPyObject for Anchor
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for Anchor {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         // try to extract a string from the object and then convert it to a TransitionFunction
         if let Ok(name) = ob.extract::<String>() {
-            Ok(Anchor::from_str(&name).unwrap())
+            Anchor::from_str(&name).map_err(pyo3::exceptions::PyValueError::new_err)
         } else {
             Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
                 "Anchor must be a string.",
@@ -798,44 +941,172 @@ impl<'py> FromPyObject<'py> for Anchor {
     }
 }
 
+/// Where the origin of the coordinate system of a window lies.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Origin {
+    /// The center of the window.
+    Center,
+    /// The top left corner of the window.
+    TopLeft,
+}
+
+/// The direction in which the y-axis of the coordinate system of a window points.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum YAxis {
+    Down,
+    Up,
+}
+
+/// The coordinate system that positions of stimuli and of the mouse are given in.
+///
+/// Stimuli are drawn onto a scene with the origin in the center of the window and the y-axis
+/// pointing down. Only positions are converted; sizes and the shape of stimuli are not flipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateSystem {
+    pub origin: Origin,
+    pub y_axis: YAxis,
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        Self {
+            origin: Origin::Center,
+            y_axis: YAxis::Down,
+        }
+    }
+}
+
+impl CoordinateSystem {
+    /// The position of the origin in the scene.
+    fn origin(&self, window_size: PixelSize) -> (f32, f32) {
+        match self.origin {
+            Origin::Center => (0.0, 0.0),
+            Origin::TopLeft => (-(window_size.width as f32) / 2.0, -(window_size.height as f32) / 2.0),
+        }
+    }
+
+    /// 1 if the y-axis points down (like in the scene), -1 if it points up.
+    pub fn y_sign(&self) -> f32 {
+        match self.y_axis {
+            YAxis::Down => 1.0,
+            YAxis::Up => -1.0,
+        }
+    }
+
+    /// Convert a point (in pixels) to the coordinates of the scene.
+    pub fn to_scene(&self, x: f32, y: f32, window_size: PixelSize) -> (f32, f32) {
+        let (origin_x, origin_y) = self.origin(window_size);
+        (origin_x + x, origin_y + self.y_sign() * y)
+    }
+
+    /// Convert a point in the coordinates of the scene to this coordinate system.
+    pub fn from_scene(&self, x: f32, y: f32, window_size: PixelSize) -> (f32, f32) {
+        let (origin_x, origin_y) = self.origin(window_size);
+        (x - origin_x, self.y_sign() * (y - origin_y))
+    }
+
+    /// Convert a rectangle given by a corner and a (possibly negative) width and height to the
+    /// coordinates of the scene. Returns the top left corner and the (positive) width and height.
+    pub fn rect_to_scene(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        window_size: PixelSize,
+    ) -> (f32, f32, f32, f32) {
+        let (x0, y0) = self.to_scene(x, y, window_size);
+        let (x1, y1) = self.to_scene(x + width, y + height, window_size);
+        (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+
+    /// Convert a rectangle in the coordinates of the scene to this coordinate system. Returns the
+    /// corner with the smallest coordinates and the (positive) width and height.
+    pub fn rect_from_scene(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        window_size: PixelSize,
+    ) -> (f32, f32, f32, f32) {
+        let (x0, y0) = self.from_scene(x, y, window_size);
+        let (x1, y1) = self.from_scene(x + width, y + height, window_size);
+        (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+}
+
 // convience function to create Size
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value in pixels.
 pub fn px(value: f32) -> Size {
     Size::Pixels(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value as a fraction of the viewport width.
 pub fn vw(value: f32) -> Size {
     Size::ViewportWidth(value)
 }
 
+#[cfg(feature = "python")]
+#[pyfunction]
+/// Create a new Size with the given value as a fraction of the viewport height, like PsychoPy's
+/// "height" units. The same as `vh`.
+pub fn height(value: f32) -> Size {
+    Size::ViewportHeight(value)
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+/// Create a new Size normalized to half the viewport width, like PsychoPy's "norm" units for the
+/// horizontal axis: -1 is the left edge and 1 the right edge of the window.
+pub fn nw(value: f32) -> Size {
+    Size::NormalizedWidth(value)
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+/// Create a new Size normalized to half the viewport height, like PsychoPy's "norm" units for the
+/// vertical axis: -1 and 1 are the edges of the window.
+pub fn nh(value: f32) -> Size {
+    Size::NormalizedHeight(value)
+}
+
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value as a fraction of the viewport height.
 pub fn vh(value: f32) -> Size {
     Size::ViewportHeight(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value in degrees of visual angle.
 pub fn deg(value: f32) -> Size {
     Size::Degrees(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value in millimeters.
 pub fn mm(value: f32) -> Size {
     Size::Millimeters(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value in centimeters.
 pub fn cm(value: f32) -> Size {
     Size::Centimeters(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(name = "in")]
 /// Create a new Size with the given value in inches.
@@ -843,6 +1114,7 @@ pub fn py_in(value: f32) -> Size {
     Size::Inches(value)
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new Size with the given value in points.
 pub fn pt(value: f32) -> Size {
@@ -851,6 +1123,7 @@ pub fn pt(value: f32) -> Size {
 
 // convience function to create Shape
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(signature = (
     width,
@@ -868,6 +1141,7 @@ pub fn rectangle(width: IntoSize, height: IntoSize, x: IntoSize, y: IntoSize) ->
     }
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 #[pyo3(signature = (
     radius,
@@ -883,6 +1157,7 @@ pub fn circle(radius: IntoSize, x: IntoSize, y: IntoSize) -> Shape {
     }
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new line.
 pub fn line(x1: IntoSize, y1: IntoSize, x2: IntoSize, y2: IntoSize) -> Shape {
@@ -894,6 +1169,7 @@ pub fn line(x1: IntoSize, y1: IntoSize, x2: IntoSize, y2: IntoSize) -> Shape {
     }
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new ellipse.
 pub fn ellipse(x: IntoSize, y: IntoSize, radius_x: IntoSize, radius_y: IntoSize) -> Shape {
@@ -905,6 +1181,7 @@ pub fn ellipse(x: IntoSize, y: IntoSize, radius_x: IntoSize, radius_y: IntoSize)
     }
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new polygon.
 pub fn polygon(points: Vec<(IntoSize, IntoSize)>) -> Shape {
@@ -913,6 +1190,7 @@ pub fn polygon(points: Vec<(IntoSize, IntoSize)>) -> Shape {
     }
 }
 
+#[cfg(feature = "python")]
 #[pyfunction]
 /// Create a new path.
 pub fn path(points: Vec<(IntoSize, IntoSize)>) -> Shape {
@@ -920,3 +1198,120 @@ pub fn path(points: Vec<(IntoSize, IntoSize)>) -> Shape {
         points: points.into_iter().map(|(x, y)| (x.into(), y.into())).collect(),
     }
 }
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (rows, cols, spacing, spacing_y = None))]
+/// Create the positions of a rectangular grid that is centered on the origin.
+///
+/// Parameters
+/// ----------
+/// rows : int
+///   The number of rows.
+/// cols : int
+///   The number of columns.
+/// spacing : Size
+///   The distance between neighbouring columns (and rows, unless `spacing_y` is given). The
+///   positions are given in the same unit.
+/// spacing_y : Size, optional
+///   The distance between neighbouring rows.
+///
+/// Returns
+/// -------
+/// list[tuple[Size, Size]]
+///   The positions, row by row along increasing y.
+pub fn grid(rows: usize, cols: usize, spacing: IntoSize, spacing_y: Option<IntoSize>) -> Vec<(Size, Size)> {
+    let spacing_x: Size = spacing.into();
+    let spacing_y: Size = spacing_y.map_or_else(|| spacing_x.clone(), Into::into);
+
+    let offset = |index: usize, count: usize| index as f32 - (count as f32 - 1.0) / 2.0;
+
+    let mut positions = Vec::with_capacity(rows * cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            positions.push((spacing_x.scaled(offset(col, cols)), spacing_y.scaled(offset(row, rows))));
+        }
+    }
+    positions
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (rows, cols, spacing))]
+/// Create the positions of a hexagonal lattice that is centered on the origin. Every other row is
+/// shifted by half the spacing, so that each position has the same distance to its six neighbours.
+///
+/// Parameters
+/// ----------
+/// rows : int
+///   The number of rows.
+/// cols : int
+///   The number of positions per row.
+/// spacing : Size
+///   The distance between neighbouring positions. The positions are given in the same unit.
+///
+/// Returns
+/// -------
+/// list[tuple[Size, Size]]
+///   The positions, row by row along increasing y.
+pub fn hex_grid(rows: usize, cols: usize, spacing: IntoSize) -> Vec<(Size, Size)> {
+    let spacing: Size = spacing.into();
+    let row_height = 3.0f32.sqrt() / 2.0;
+    // with more than one row, the shifted rows stick out by half the spacing
+    let shift = if rows > 1 { 0.25 } else { 0.0 };
+
+    let mut positions = Vec::with_capacity(rows * cols);
+    for row in 0..rows {
+        let row_shift = if row % 2 == 1 { 0.5 } else { 0.0 };
+        for col in 0..cols {
+            let x = col as f32 - (cols as f32 - 1.0) / 2.0 + row_shift - shift;
+            let y = (row as f32 - (rows as f32 - 1.0) / 2.0) * row_height;
+            positions.push((spacing.scaled(x), spacing.scaled(y)));
+        }
+    }
+    positions
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (rings, spacing))]
+/// Create the positions of a hexagonal lattice in concentric rings around the origin. The first
+/// position is the origin, followed by the 6 positions of the first ring, the 12 positions of the
+/// second ring, and so on.
+///
+/// Parameters
+/// ----------
+/// rings : int
+///   The number of rings around the origin.
+/// spacing : Size
+///   The distance between neighbouring positions. The positions are given in the same unit.
+///
+/// Returns
+/// -------
+/// list[tuple[Size, Size]]
+///   The positions, ring by ring.
+pub fn hex_rings(rings: usize, spacing: IntoSize) -> Vec<(Size, Size)> {
+    let spacing: Size = spacing.into();
+    // steps between neighbouring positions in axial coordinates
+    const DIRECTIONS: [(i64, i64); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+    let to_position = |q: i64, r: i64| {
+        let x = q as f32 + r as f32 / 2.0;
+        let y = r as f32 * 3.0f32.sqrt() / 2.0;
+        (spacing.scaled(x), spacing.scaled(y))
+    };
+
+    let mut positions = Vec::with_capacity(1 + 3 * rings * (rings + 1));
+    positions.push(to_position(0, 0));
+    for ring in 1..=rings as i64 {
+        let (mut q, mut r) = (-ring, ring);
+        for (dq, dr) in DIRECTIONS {
+            for _ in 0..ring {
+                positions.push(to_position(q, r));
+                q += dq;
+                r += dr;
+            }
+        }
+    }
+    positions
+}
@@ -0,0 +1,426 @@
+use std::{fs::OpenOptions, path::Path};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use rand::Rng;
+use send_wrapper::SendWrapper;
+
+use super::{
+    color::{IntoLinRgba, LinRgba},
+    geometry::{IntoSize, Shape, Size, Transformation2D},
+    stimuli::{
+        shape::{GradientType, ShapeParams, ShapeStimulus},
+        DynamicStimulus, StimulusParamValue, StrokeStyle,
+    },
+    window::Window,
+};
+use crate::errors::{PsydkError, PsydkResult};
+
+/// The columns of the files isoluminant points are stored in.
+const COLUMNS: [&str; 10] = [
+    "participant",
+    "reference_r",
+    "reference_g",
+    "reference_b",
+    "test_r",
+    "test_g",
+    "test_b",
+    "frequency",
+    "scale",
+    "settings",
+];
+
+fn scale_color(color: LinRgba, scale: f64) -> LinRgba {
+    let scale = scale as f32;
+    LinRgba::new(color.r * scale, color.g * scale, color.b * scale, color.a)
+}
+
+fn csv_error(error: csv::Error) -> PsydkError {
+    PsydkError::CustomError(error.to_string())
+}
+
+/// The intensity at which a test color is isoluminant with a reference color, as measured with
+/// heterochromatic flicker photometry.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct IsoluminancePoint {
+    /// The reference color.
+    pub reference: LinRgba,
+    /// The test color at its initial intensity.
+    pub test: LinRgba,
+    /// The flicker frequency in Hz.
+    pub frequency: f64,
+    /// The intensity of the test color (relative to `test`) of every setting of the participant.
+    pub settings: Vec<f64>,
+}
+
+impl IsoluminancePoint {
+    /// The isoluminant intensity of the test color relative to `test`, i.e. the geometric mean of
+    /// all settings.
+    pub fn scale(&self) -> f64 {
+        let log_sum = self.settings.iter().map(|setting| setting.ln()).sum::<f64>();
+        (log_sum / self.settings.len().max(1) as f64).exp()
+    }
+
+    /// The test color at the isoluminant intensity.
+    pub fn color(&self) -> LinRgba {
+        scale_color(self.test, self.scale())
+    }
+
+    /// Append the point to a CSV file, creating the file if it does not exist.
+    pub fn save(&self, path: &Path, participant: &str) -> PsydkResult<()> {
+        let is_empty = path.metadata().map(|metadata| metadata.len() == 0).unwrap_or(true);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = csv::Writer::from_writer(file);
+
+        if is_empty {
+            writer.write_record(COLUMNS).map_err(csv_error)?;
+        }
+
+        let (reference, test) = (self.reference, self.test);
+        let settings = self
+            .settings
+            .iter()
+            .map(|setting| setting.to_string())
+            .collect::<Vec<_>>()
+            .join(";");
+        writer
+            .write_record([
+                participant.to_string(),
+                reference.r.to_string(),
+                reference.g.to_string(),
+                reference.b.to_string(),
+                test.r.to_string(),
+                test.g.to_string(),
+                test.b.to_string(),
+                self.frequency.to_string(),
+                self.scale().to_string(),
+                settings,
+            ])
+            .map_err(csv_error)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Load the most recent point of a participant from a CSV file written by `save`. Returns
+    /// `None` if the file contains no point for the participant.
+    pub fn load(path: &Path, participant: &str) -> PsydkResult<Option<Self>> {
+        let invalid = || PsydkError::CustomError(format!("Invalid isoluminance file: {}", path.display()));
+
+        let mut reader = csv::Reader::from_path(path).map_err(csv_error)?;
+        let mut point = None;
+        for record in reader.records() {
+            let record = record.map_err(csv_error)?;
+            if record.get(0) != Some(participant) {
+                continue;
+            }
+
+            let value = |index: usize| -> PsydkResult<f64> {
+                record
+                    .get(index)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(invalid)
+            };
+            let color = |start: usize| -> PsydkResult<LinRgba> {
+                Ok(LinRgba::new(
+                    value(start)? as f32,
+                    value(start + 1)? as f32,
+                    value(start + 2)? as f32,
+                    1.0,
+                ))
+            };
+            let settings = record
+                .get(9)
+                .unwrap_or_default()
+                .split(';')
+                .filter(|setting| !setting.is_empty())
+                .map(|setting| setting.parse::<f64>().map_err(|_| invalid()))
+                .collect::<PsydkResult<Vec<_>>>()?;
+
+            point = Some(Self {
+                reference: color(1)?,
+                test: color(4)?,
+                frequency: value(7)?,
+                settings,
+            });
+        }
+
+        Ok(point)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl IsoluminancePoint {
+    /// The reference color.
+    #[getter(reference)]
+    fn py_reference(&self) -> LinRgba {
+        self.reference
+    }
+
+    /// The test color at its initial intensity.
+    #[getter(test)]
+    fn py_test(&self) -> LinRgba {
+        self.test
+    }
+
+    /// The flicker frequency in Hz that was used.
+    #[getter(frequency)]
+    fn py_frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// The intensity of the test color (relative to `test`) of every setting.
+    #[getter(settings)]
+    fn py_settings(&self) -> Vec<f64> {
+        self.settings.clone()
+    }
+
+    /// The isoluminant intensity of the test color relative to `test` (the geometric mean of all
+    /// settings).
+    #[getter(scale)]
+    fn py_scale(&self) -> f64 {
+        self.scale()
+    }
+
+    /// The test color at the isoluminant intensity.
+    #[getter(color)]
+    fn py_color(&self) -> LinRgba {
+        self.color()
+    }
+
+    /// Append the point to a CSV file, so that it can be loaded again in later sessions.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///   The CSV file. It is created if it does not exist.
+    /// participant : str
+    ///   The participant the point belongs to.
+    #[pyo3(name = "save")]
+    fn py_save(&self, path: &str, participant: &str) -> PyResult<()> {
+        Ok(self.save(Path::new(path), participant)?)
+    }
+
+    /// Load the most recent point of a participant from a CSV file written by `save`.
+    ///
+    /// Parameters
+    /// ----------
+    /// path : str
+    ///   The CSV file.
+    /// participant : str
+    ///   The participant.
+    ///
+    /// Returns
+    /// -------
+    /// IsoluminancePoint or None
+    ///   The point, or None if the file contains no point for the participant.
+    #[staticmethod]
+    #[pyo3(name = "load")]
+    fn py_load(path: &str, participant: &str) -> PyResult<Option<Self>> {
+        Ok(Self::load(Path::new(path), participant)?)
+    }
+
+    fn __repr__(&self) -> String {
+        let color = self.color();
+        format!(
+            "IsoluminancePoint(scale={:.4}, color=({:.4}, {:.4}, {:.4}), settings={})",
+            self.scale(),
+            color.r,
+            color.g,
+            color.b,
+            self.settings.len()
+        )
+    }
+}
+
+/// Heterochromatic flicker photometry: a disc alternates between a reference and a test color, and
+/// the participant adjusts the intensity of the test color until the flicker is minimal. At that
+/// point, both colors are isoluminant for the participant.
+///
+/// The intensity is changed with the up and down arrow keys (in smaller steps while shift is held)
+/// and a setting is confirmed with enter or space. Escape aborts the procedure.
+#[derive(Debug, Clone)]
+pub struct FlickerPhotometry {
+    pub reference: LinRgba,
+    pub test: LinRgba,
+    /// The flicker frequency in Hz. It is rounded so that each color is shown for a whole number of
+    /// refresh intervals.
+    pub frequency: f64,
+    /// The radius of the disc.
+    pub radius: Size,
+    /// The number of settings, each starting from a random intensity.
+    pub repetitions: u32,
+    /// The relative change of the intensity per key press.
+    pub step: f64,
+}
+
+impl FlickerPhotometry {
+    fn disc(&self, color: LinRgba) -> PsydkResult<DynamicStimulus> {
+        let params = ShapeParams {
+            shape: Shape::Circle {
+                x: Size::Pixels(0.0),
+                y: Size::Pixels(0.0),
+                radius: self.radius.clone(),
+            },
+            x: Size::Pixels(0.0),
+            y: Size::Pixels(0.0),
+            fill_color: color,
+            gradient_angle: 0.0,
+            stroke_style: StrokeStyle::None,
+            stroke_color: LinRgba::default(),
+            stroke_width: Size::Pixels(0.0),
+            alpha: None,
+        };
+        let disc = ShapeStimulus::new(
+            params,
+            None,
+            GradientType::Linear,
+            None,
+            None,
+            Transformation2D::Identity(),
+        )?;
+        Ok(DynamicStimulus::new(disc))
+    }
+
+    /// Run the procedure on `window` and return the isoluminant point.
+    pub fn run(&self, window: &Window) -> PsydkResult<IsoluminancePoint> {
+        if self.frequency <= 0.0 || self.step <= 0.0 || self.repetitions == 0 {
+            return Err(PsydkError::ParameterError(
+                "The frequency, the step, and the number of repetitions must be positive".into(),
+            ));
+        }
+
+        // the test color cannot be made brighter than the brightest channel allows
+        let brightest = self.test.r.max(self.test.g).max(self.test.b) as f64;
+        if brightest <= 0.0 {
+            return Err(PsydkError::ParameterError("The test color must not be black".into()));
+        }
+        let max_scale = 1.0 / brightest;
+
+        let refresh_rate = window.get_current_refresh_rate().ok_or_else(|| {
+            PsydkError::MonitorError("Failed to get the refresh rate of the monitor the window is on".into())
+        })?;
+        let half_period = ((refresh_rate / (2.0 * self.frequency)).round() as u32).max(1);
+
+        let reference = self.disc(self.reference)?;
+        let test = self.disc(self.test)?;
+        let mut reference_frame = window.get_frame()?;
+        reference_frame.add(&reference);
+        let mut test_frame = window.get_frame()?;
+        test_frame.add(&test);
+
+        let mut receiver = window.create_event_receiver();
+        let mut rng = rand::thread_rng();
+        let mut settings = Vec::with_capacity(self.repetitions as usize);
+        while settings.len() < self.repetitions as usize {
+            // start every setting at a random intensity between half and twice the test color, so
+            // that settings are independent of each other
+            let mut scale = (rng.gen_range(-1.0..1.0) * std::f64::consts::LN_2).exp().min(max_scale);
+            receiver.flush();
+
+            loop {
+                test.lock()
+                    .set_param("fill_color", StimulusParamValue::LinRgba(scale_color(self.test, scale)));
+                window.present_sequence(&[(&reference_frame, half_period), (&test_frame, half_period)], false)?;
+
+                let events = receiver.poll();
+                if events.key_pressed("Escape") {
+                    return Err(PsydkError::CustomError("Flicker photometry was aborted".into()));
+                }
+
+                let step = if window.keyboard.key("Shift").pressed {
+                    self.step / 5.0
+                } else {
+                    self.step
+                };
+                for event in events.iter() {
+                    if event.key_pressed("ArrowUp") {
+                        scale = (scale * (1.0 + step)).min(max_scale);
+                    } else if event.key_pressed("ArrowDown") {
+                        scale /= 1.0 + step;
+                    }
+                }
+
+                if events.key_pressed("Enter") || events.key_pressed("Space") {
+                    settings.push(scale);
+                    break;
+                }
+            }
+        }
+
+        Ok(IsoluminancePoint {
+            reference: self.reference,
+            test: self.test,
+            frequency: refresh_rate / (2.0 * half_period as f64),
+            settings,
+        })
+    }
+}
+
+/// Find the intensity at which a test color is isoluminant with a reference color using
+/// heterochromatic flicker photometry. This is typically needed before chromatic experiments, as
+/// isoluminance differs between participants.
+///
+/// A disc in the center of the window alternates between the reference and the test color. The
+/// participant adjusts the intensity of the test color with the up and down arrow keys (in smaller
+/// steps while shift is held) until the flicker is minimal and confirms with enter or space. Escape
+/// aborts. Each setting starts at a random intensity, and the result is the geometric mean of all
+/// settings.
+///
+/// Parameters
+/// ----------
+/// window : Window
+///   The window to run the procedure on.
+/// reference : LinRgba
+///   The reference color, e.g. a red.
+/// test : LinRgba
+///   The test color, e.g. a green. Only its intensity is adjusted.
+/// frequency : float, optional
+///   The flicker frequency in Hz, typically between 15 and 25 Hz. It is rounded so that each color
+///   is shown for a whole number of refresh intervals.
+/// radius : Size, num, or str, optional
+///   The radius of the disc. Defaults to 1 degree of visual angle.
+/// repetitions : int, optional
+///   The number of settings.
+/// step : float, optional
+///   The relative change of the intensity per key press.
+///
+/// Returns
+/// -------
+/// IsoluminancePoint
+///   The isoluminant point. Use `save` to store it for the participant.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "flicker_photometry")]
+#[pyo3(signature = (
+    window,
+    reference,
+    test,
+    frequency = 20.0,
+    radius = IntoSize(Size::Degrees(1.0)),
+    repetitions = 4,
+    step = 0.05,
+))]
+pub fn py_flicker_photometry(
+    py: Python,
+    window: Window,
+    reference: IntoLinRgba,
+    test: IntoLinRgba,
+    frequency: f64,
+    radius: IntoSize,
+    repetitions: u32,
+    step: f64,
+) -> PyResult<IsoluminancePoint> {
+    let photometry = FlickerPhotometry {
+        reference: reference.into(),
+        test: test.into(),
+        frequency,
+        radius: radius.into(),
+        repetitions,
+        step,
+    };
+    let window = SendWrapper::new(window);
+    Ok(py.allow_threads(move || photometry.run(&window))?)
+}
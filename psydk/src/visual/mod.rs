@@ -1,6 +1,20 @@
+pub mod aoi;
+pub mod breaks;
+pub mod calibration;
 pub mod color;
+mod compositor;
+pub mod contrast;
+pub mod cursor;
 mod fill;
 pub mod geometry;
+pub mod isoluminance;
+pub mod overlay;
+pub mod report;
+pub mod scheduler;
+#[cfg(feature = "python")]
+pub mod sequence;
 pub mod stimuli;
+pub mod test_pattern;
 pub mod utils;
+pub mod watchdog;
 pub mod window;
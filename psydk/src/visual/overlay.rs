@@ -0,0 +1,204 @@
+//! Overlays that are drawn on top of every frame: timing and input information, and the layout of
+//! the stimuli. Meant for development only, as they are drawn into the frames that participants see.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use renderer::{affine::Affine, brushes::Brush, colors::RGBA, shapes::Shape, styles::StrokeStyle, DynamicScene};
+
+use super::{
+    color::LinRgba,
+    geometry::{Anchor, Size, Transformation2D},
+    stimuli::{
+        text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+        DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue,
+    },
+    window::WindowState,
+};
+use crate::context::ExperimentContext;
+
+/// Number of frames that the frame rate is averaged over.
+const FPS_FRAMES: usize = 60;
+/// Font size of the overlay in pixels.
+const FONT_SIZE: f32 = 14.0;
+/// Distance of the overlay from the top left corner of the window in pixels.
+const MARGIN: f32 = 8.0;
+/// Padding between the text and the edge of the background in pixels.
+const PADDING: f32 = 6.0;
+/// Half the size of the cross that marks the anchor of a stimulus in pixels.
+const ANCHOR_SIZE: f32 = 6.0;
+
+/// Shows the frame rate, the duration of the last frame, the number of dropped frames, the mouse
+/// and gaze position, and the number of stimuli in the top left corner of the window.
+#[derive(Debug)]
+pub struct DebugOverlay {
+    text: TextStimulus,
+    /// Intervals between the most recent frame onsets.
+    intervals: VecDeque<Duration>,
+    last_onset: Option<Instant>,
+    render_time: Duration,
+}
+
+impl DebugOverlay {
+    pub fn new(context: &ExperimentContext) -> Self {
+        let text = TextStimulus::new(
+            Size::Pixels(0.0),
+            Size::Pixels(0.0),
+            "",
+            TextAlignment::Left,
+            TextDirection::Ltr,
+            TextOrientation::Horizontal,
+            Anchor::TopLeft,
+            Size::Pixels(FONT_SIZE),
+            &[],
+            FontWeight::Regular,
+            LinRgba::new(1.0, 1.0, 1.0, 1.0),
+            1.0,
+            0.0,
+            Transformation2D::Identity(),
+            context,
+        );
+
+        Self {
+            text,
+            intervals: VecDeque::with_capacity(FPS_FRAMES),
+            last_onset: None,
+            render_time: Duration::ZERO,
+        }
+    }
+
+    /// Record the onset of a frame. Called for every presented frame, including repeated frames.
+    pub fn frame_presented(&mut self, onset: Instant, render_time: Duration) {
+        if let Some(last_onset) = self.last_onset.replace(onset) {
+            if self.intervals.len() == FPS_FRAMES {
+                self.intervals.pop_front();
+            }
+            self.intervals.push_back(onset.saturating_duration_since(last_onset));
+        }
+        self.render_time = render_time;
+    }
+
+    fn text(&self, win_state: &WindowState, stimuli: &[&DynamicStimulus]) -> String {
+        let total: Duration = self.intervals.iter().sum();
+        let fps = match total.is_zero() {
+            true => "-".to_string(),
+            false => format!("{:.1}", self.intervals.len() as f64 / total.as_secs_f64()),
+        };
+        let frame_time = self.intervals.back().map_or("-".to_string(), |interval| {
+            format!("{:.2} ms", interval.as_secs_f64() * 1000.0)
+        });
+        let dropped = match win_state.watchdog.enabled {
+            true => win_state.watchdog.dropped_frames.len().to_string(),
+            false => "- (watchdog disabled)".to_string(),
+        };
+        let position =
+            |position: Option<(f32, f32)>| position.map_or("-".to_string(), |(x, y)| format!("({x:.0}, {y:.0})"));
+        let n_visible = stimuli.iter().filter(|stimulus| stimulus.lock().visible()).count();
+
+        format!(
+            "FPS: {fps}\nFrame: {frame_time} (CPU render {:.2} ms)\nDropped: {dropped}\nMouse: {}\nGaze: {}\nStimuli: {n_visible}",
+            self.render_time.as_secs_f64() * 1000.0,
+            position(win_state.mouse_position),
+            position(win_state.gaze_position),
+        )
+    }
+
+    /// Draw the overlay on top of the stimuli. `view` is the transform and zoom factor of the view
+    /// of the frame, if any. The overlay is moved and scaled so that it stays in the corner of the
+    /// window, but it is rotated with the view.
+    pub fn draw(
+        &mut self,
+        scene: &mut DynamicScene,
+        win_state: &WindowState,
+        view: Option<(Affine, f64)>,
+        stimuli: &[&DynamicStimulus],
+    ) {
+        let window_size = win_state.size;
+        let coordinate_system = win_state.coordinate_system;
+        let (inverse_view, scale) = match view {
+            Some((transform, scale)) => {
+                let inverse = transform
+                    .as_matrix()
+                    .try_inverse()
+                    .unwrap_or_else(nalgebra::Matrix3::identity);
+                (inverse, scale as f32)
+            }
+            None => (nalgebra::Matrix3::identity(), 1.0),
+        };
+
+        // the top left corner of the window in the coordinates of the scene
+        let corner = inverse_view
+            * nalgebra::Vector3::new(
+                -(window_size.width as f32) / 2.0 + MARGIN,
+                -(window_size.height as f32) / 2.0 + MARGIN,
+                1.0,
+            );
+        let (x, y) = (corner.x + PADDING / scale, corner.y + PADDING / scale);
+        let (text_x, text_y) = coordinate_system.from_scene(x, y, window_size);
+
+        let text = self.text(win_state, stimuli);
+        self.text.set_param("text", StimulusParamValue::String(text));
+        self.text.set_param("x", StimulusParamValue::Size(Size::Pixels(text_x)));
+        self.text.set_param("y", StimulusParamValue::Size(Size::Pixels(text_y)));
+        self.text
+            .set_param("font_size", StimulusParamValue::Size(Size::Pixels(FONT_SIZE / scale)));
+
+        let bounds = self
+            .text
+            .metrics(window_size, win_state.physical_screen, coordinate_system)
+            .bounds;
+        let background = Shape::rectangle(
+            (corner.x, corner.y),
+            (bounds.width + 2.0 * PADDING / scale) as f64,
+            (bounds.height + 2.0 * PADDING / scale) as f64,
+        );
+        scene.draw_shape_fill(
+            background,
+            Brush::Solid(RGBA::new_linear(0.0, 0.0, 0.0, 0.6)),
+            None,
+            None,
+        );
+        self.text.draw(scene, win_state);
+    }
+}
+
+/// Outline the bounding box of every visible stimulus (green), mark its anchor (yellow), and fill
+/// the region in which `contains` returns true (red). Stimuli without hit testing have no red
+/// region.
+pub fn draw_layout(scene: &mut DynamicScene, win_state: &WindowState, stimuli: &[&DynamicStimulus]) {
+    let layouts: Vec<StimulusLayout> = stimuli
+        .iter()
+        .filter(|stimulus| stimulus.lock().visible())
+        .flat_map(|stimulus| stimulus.lock().layout(win_state))
+        .collect();
+
+    let stroke = |scene: &mut DynamicScene, shape: Shape, color: RGBA| {
+        scene.draw_shape_stroke(shape, Brush::Solid(color), StrokeStyle::new(1.0), None, None);
+    };
+
+    for layout in layouts {
+        if let Some(region) = layout.hit_region.filter(|region| region.len() > 2) {
+            scene.draw_shape_fill(
+                Shape::polygon(region.clone()),
+                Brush::Solid(RGBA::new_linear(1.0, 0.0, 0.0, 0.25)),
+                None,
+                None,
+            );
+            stroke(scene, Shape::polygon(region), RGBA::new_linear(1.0, 0.0, 0.0, 1.0));
+        }
+        if layout.bounds.len() > 2 {
+            stroke(
+                scene,
+                Shape::polygon(layout.bounds),
+                RGBA::new_linear(0.0, 1.0, 0.0, 1.0),
+            );
+        }
+        if let Some((x, y)) = layout.anchor {
+            let color = RGBA::new_linear(1.0, 1.0, 0.0, 1.0);
+            stroke(scene, Shape::line((x - ANCHOR_SIZE, y), (x + ANCHOR_SIZE, y)), color);
+            stroke(scene, Shape::line((x, y - ANCHOR_SIZE), (x, y + ANCHOR_SIZE)), color);
+        }
+    }
+}
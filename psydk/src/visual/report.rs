@@ -0,0 +1,481 @@
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use numpy::{IntoPyArray, PyArray1};
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, Python};
+
+use crate::time::Timestamp;
+
+/// A frame counts as a missed deadline if it was shown this many refresh intervals (or more)
+/// after the previous frame.
+const MISSED_DEADLINE_THRESHOLD: f64 = 1.5;
+
+/// Timing of a single call to `Window.present`, describing what appeared on screen.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct PresentationReport {
+    /// Number of frames that were requested.
+    pub requested_frames: u32,
+    /// The time each frame was presented.
+    pub frame_onsets: Vec<Instant>,
+    /// For each frame, the refresh it was rendered for and the refresh at which it was shown,
+    /// counted since the window was opened.
+    pub refreshes: Vec<(u64, u64)>,
+    /// The refresh interval of the monitor.
+    pub refresh_interval: Duration,
+}
+
+impl PresentationReport {
+    pub fn new(
+        requested_frames: u32,
+        frame_onsets: Vec<Instant>,
+        refreshes: Vec<(u64, u64)>,
+        refresh_interval: Duration,
+    ) -> Self {
+        Self {
+            requested_frames,
+            frame_onsets,
+            refreshes,
+            refresh_interval,
+        }
+    }
+
+    /// The onset of the first frame.
+    pub fn onset(&self) -> Option<Instant> {
+        self.frame_onsets.first().copied()
+    }
+
+    /// The estimated time the last frame was replaced, i.e. one refresh interval after it was shown.
+    pub fn offset(&self) -> Option<Instant> {
+        self.frame_onsets.last().map(|onset| *onset + self.refresh_interval)
+    }
+
+    /// Number of refresh intervals the frame was actually on screen. Larger than the number of
+    /// requested frames if deadlines were missed.
+    pub fn actual_frames(&self) -> u32 {
+        match (self.onset(), self.offset()) {
+            (Some(onset), Some(offset)) => {
+                ((offset - onset).as_secs_f64() / self.refresh_interval.as_secs_f64()).round() as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// Number of frames that were shown late, i.e. after more than one refresh interval.
+    pub fn missed_deadlines(&self) -> u32 {
+        let interval = self.refresh_interval.as_secs_f64();
+        self.frame_onsets
+            .windows(2)
+            .filter(|pair| (pair[1] - pair[0]).as_secs_f64() >= MISSED_DEADLINE_THRESHOLD * interval)
+            .count() as u32
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PresentationReport {
+    /// Number of frames that were requested.
+    #[getter(requested_frames)]
+    fn py_requested_frames(&self) -> u32 {
+        self.requested_frames
+    }
+
+    /// Number of frames that were submitted to the display.
+    #[getter(presented_frames)]
+    fn py_presented_frames(&self) -> usize {
+        self.frame_onsets.len()
+    }
+
+    /// Number of refresh intervals the frame was actually on screen.
+    #[getter(actual_frames)]
+    fn py_actual_frames(&self) -> u32 {
+        self.actual_frames()
+    }
+
+    /// The onset of the first frame.
+    #[getter(onset)]
+    fn py_onset(&self) -> Option<Timestamp> {
+        self.onset().map(Into::into)
+    }
+
+    /// The estimated time at which the last frame was replaced.
+    #[getter(offset)]
+    fn py_offset(&self) -> Option<Timestamp> {
+        self.offset().map(Into::into)
+    }
+
+    /// The onsets of all presented frames.
+    #[getter(frame_onsets)]
+    fn py_frame_onsets(&self) -> Vec<Timestamp> {
+        self.frame_onsets.iter().map(|onset| (*onset).into()).collect()
+    }
+
+    /// Number of frames that were shown late.
+    #[getter(missed_deadlines)]
+    fn py_missed_deadlines(&self) -> u32 {
+        self.missed_deadlines()
+    }
+
+    /// The refresh at which each frame was shown, counted since the window was opened. Refreshes
+    /// without a new frame are counted as well, so the count advances by more than one between two
+    /// frames when a deadline was missed.
+    #[getter(refreshes)]
+    fn py_refreshes(&self) -> Vec<u64> {
+        self.refreshes.iter().map(|(_, shown)| *shown).collect()
+    }
+
+    /// The refresh that each frame was rendered for. Differs from `refreshes` for frames that were
+    /// shown late, and for repeated frames that were not rendered again (see `repeat_update`).
+    #[getter(planned_refreshes)]
+    fn py_planned_refreshes(&self) -> Vec<u64> {
+        self.refreshes.iter().map(|(planned, _)| *planned).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PresentationReport(requested_frames={}, presented_frames={}, actual_frames={}, missed_deadlines={})",
+            self.requested_frames,
+            self.frame_onsets.len(),
+            self.actual_frames(),
+            self.missed_deadlines()
+        )
+    }
+}
+
+/// Timing of a call to `Window.present_sequence`, with one `PresentationReport` per step.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct SequenceReport {
+    /// The reports of the individual steps, in the order they were presented.
+    pub steps: Vec<PresentationReport>,
+    /// The refresh interval of the monitor.
+    pub refresh_interval: Duration,
+}
+
+impl SequenceReport {
+    pub fn new(steps: Vec<PresentationReport>, refresh_interval: Duration) -> Self {
+        Self {
+            steps,
+            refresh_interval,
+        }
+    }
+
+    /// The whole sequence as a single report. Deadlines missed between two steps are included.
+    pub fn combined(&self) -> PresentationReport {
+        PresentationReport::new(
+            self.steps.iter().map(|step| step.requested_frames).sum(),
+            self.steps
+                .iter()
+                .flat_map(|step| step.frame_onsets.iter().copied())
+                .collect(),
+            self.steps
+                .iter()
+                .flat_map(|step| step.refreshes.iter().copied())
+                .collect(),
+            self.refresh_interval,
+        )
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SequenceReport {
+    /// The reports of the individual steps.
+    #[getter(steps)]
+    fn py_steps(&self) -> Vec<PresentationReport> {
+        self.steps.clone()
+    }
+
+    /// The onset of the first frame of each step.
+    #[getter(step_onsets)]
+    fn py_step_onsets(&self) -> Vec<Option<Timestamp>> {
+        self.steps.iter().map(|step| step.onset().map(Into::into)).collect()
+    }
+
+    /// Number of frames that were requested for the whole sequence.
+    #[getter(requested_frames)]
+    fn py_requested_frames(&self) -> u32 {
+        self.combined().requested_frames
+    }
+
+    /// Number of refresh intervals the whole sequence was on screen.
+    #[getter(actual_frames)]
+    fn py_actual_frames(&self) -> u32 {
+        self.combined().actual_frames()
+    }
+
+    /// The onset of the first frame of the sequence.
+    #[getter(onset)]
+    fn py_onset(&self) -> Option<Timestamp> {
+        self.combined().onset().map(Into::into)
+    }
+
+    /// The estimated time at which the last frame of the sequence was replaced.
+    #[getter(offset)]
+    fn py_offset(&self) -> Option<Timestamp> {
+        self.combined().offset().map(Into::into)
+    }
+
+    /// The onsets of all presented frames.
+    #[getter(frame_onsets)]
+    fn py_frame_onsets(&self) -> Vec<Timestamp> {
+        self.combined().py_frame_onsets()
+    }
+
+    /// The refresh at which each frame was shown (see `PresentationReport.refreshes`).
+    #[getter(refreshes)]
+    fn py_refreshes(&self) -> Vec<u64> {
+        self.combined().py_refreshes()
+    }
+
+    /// The refresh that each frame was rendered for (see `PresentationReport.planned_refreshes`).
+    #[getter(planned_refreshes)]
+    fn py_planned_refreshes(&self) -> Vec<u64> {
+        self.combined().py_planned_refreshes()
+    }
+
+    /// Number of frames that were shown late, including the first frames of steps.
+    #[getter(missed_deadlines)]
+    fn py_missed_deadlines(&self) -> u32 {
+        self.combined().missed_deadlines()
+    }
+
+    fn __len__(&self) -> usize {
+        self.steps.len()
+    }
+
+    fn __repr__(&self) -> String {
+        let combined = self.combined();
+        format!(
+            "SequenceReport(steps={}, requested_frames={}, actual_frames={}, missed_deadlines={})",
+            self.steps.len(),
+            combined.requested_frames,
+            combined.actual_frames(),
+            combined.missed_deadlines()
+        )
+    }
+}
+
+/// Photometric properties of a rendered frame, measured from the values that are sent to the
+/// display (i.e. after gamma encoding and the LUT).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct FrameMeasurement {
+    /// The mean relative luminance (from 0.0 to 1.0).
+    pub mean_luminance: f64,
+    /// The RMS contrast, i.e. the standard deviation of the relative luminance.
+    pub rms_contrast: f64,
+    /// Histograms of the red, green, and blue output values, with one bin per 8-bit value.
+    pub histogram: [Vec<u64>; 3],
+}
+
+impl FrameMeasurement {
+    /// Measure an image of the output values. The relative luminance of each pixel is computed by
+    /// decoding the values with the sRGB transfer function and weighting the channels with the
+    /// Rec. 709 coefficients, so it is only accurate for displays with an sRGB response.
+    pub fn from_output(output: &image::RgbaImage) -> Self {
+        let decode = |value: u8| {
+            let c = value as f64 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        let mut histogram = [vec![0; 256], vec![0; 256], vec![0; 256]];
+        let mut sum = 0.0;
+        let mut sum_of_squares = 0.0;
+        for pixel in output.pixels() {
+            for channel in 0..3 {
+                histogram[channel][pixel[channel] as usize] += 1;
+            }
+            let luminance = 0.2126 * decode(pixel[0]) + 0.7152 * decode(pixel[1]) + 0.0722 * decode(pixel[2]);
+            sum += luminance;
+            sum_of_squares += luminance * luminance;
+        }
+
+        let count = (output.width() as f64 * output.height() as f64).max(1.0);
+        let mean_luminance = sum / count;
+        let rms_contrast = (sum_of_squares / count - mean_luminance * mean_luminance)
+            .max(0.0)
+            .sqrt();
+
+        Self {
+            mean_luminance,
+            rms_contrast,
+            histogram,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl FrameMeasurement {
+    /// The mean relative luminance of the frame, from 0.0 to 1.0.
+    #[getter(mean_luminance)]
+    fn py_mean_luminance(&self) -> f64 {
+        self.mean_luminance
+    }
+
+    /// The RMS contrast of the frame, i.e. the standard deviation of the relative luminance.
+    #[getter(rms_contrast)]
+    fn py_rms_contrast(&self) -> f64 {
+        self.rms_contrast
+    }
+
+    /// Histograms of the final output values as a tuple of three arrays (red, green, and blue) with
+    /// 256 bins each, counting the pixels with each 8-bit value.
+    #[getter(histogram)]
+    fn py_histogram<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> (
+        Bound<'py, PyArray1<u64>>,
+        Bound<'py, PyArray1<u64>>,
+        Bound<'py, PyArray1<u64>>,
+    ) {
+        let [red, green, blue] = self.histogram.clone();
+        (red.into_pyarray(py), green.into_pyarray(py), blue.into_pyarray(py))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "FrameMeasurement(mean_luminance={:.4}, rms_contrast={:.4})",
+            self.mean_luminance, self.rms_contrast
+        )
+    }
+}
+
+/// The measured intervals between frames of a window, compared with the refresh rate reported by
+/// the monitor. See `Window.measure_refresh_rate`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct RefreshMeasurement {
+    /// The refresh rate reported by the monitor in Hz, if any.
+    pub reported_rate: Option<f64>,
+    /// The intervals between consecutive frames.
+    pub intervals: Vec<Duration>,
+}
+
+impl RefreshMeasurement {
+    pub fn new(frame_onsets: &[Instant], reported_rate: Option<f64>) -> Self {
+        Self {
+            reported_rate,
+            intervals: frame_onsets.windows(2).map(|pair| pair[1] - pair[0]).collect(),
+        }
+    }
+
+    /// The median interval between frames in seconds.
+    pub fn median_interval(&self) -> Option<f64> {
+        let mut intervals = self.intervals.clone();
+        intervals.sort();
+        intervals
+            .get(intervals.len() / 2)
+            .map(|interval| interval.as_secs_f64())
+    }
+
+    /// The measured refresh rate in Hz, from the median interval between frames.
+    pub fn measured_rate(&self) -> Option<f64> {
+        self.median_interval()
+            .filter(|interval| *interval > 0.0)
+            .map(|interval| 1.0 / interval)
+    }
+
+    /// The standard deviation of the intervals between frames in seconds.
+    pub fn jitter(&self) -> f64 {
+        let intervals = self.intervals.iter().map(|i| i.as_secs_f64()).collect::<Vec<_>>();
+        let count = intervals.len().max(1) as f64;
+        let mean = intervals.iter().sum::<f64>() / count;
+        (intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / count).sqrt()
+    }
+
+    /// Problems with the display mode that were detected from the intervals, e.g. frame doubling
+    /// or interference by a compositor.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let (Some(median), Some(measured)) = (self.median_interval(), self.measured_rate()) else {
+            return warnings;
+        };
+
+        if let Some(reported) = self.reported_rate {
+            let ratio = reported / measured;
+            if ratio > 1.5 {
+                warnings.push(format!(
+                    "Frames are shown at {measured:.1} Hz, about 1/{:.0} of the reported {reported:.1} Hz. The display or a compositor is likely showing every frame more than once.",
+                    ratio
+                ));
+            } else if (measured - reported).abs() / reported > 0.02 {
+                warnings.push(format!(
+                    "The measured refresh rate ({measured:.2} Hz) differs from the reported refresh rate ({reported:.2} Hz)."
+                ));
+            }
+        }
+
+        let jitter = self.jitter();
+        if jitter > 0.1 * median {
+            warnings.push(format!(
+                "The intervals between frames vary by {:.2} ms (SD). A compositor may be interfering with presentation.",
+                jitter * 1000.0
+            ));
+        }
+
+        let late = self
+            .intervals
+            .iter()
+            .filter(|interval| interval.as_secs_f64() >= MISSED_DEADLINE_THRESHOLD * median)
+            .count();
+        if late > 0 {
+            warnings.push(format!("{late} of {} frames were shown late.", self.intervals.len()));
+        }
+
+        warnings
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl RefreshMeasurement {
+    /// The refresh rate reported by the monitor in Hz, or None if it is unknown.
+    #[getter(reported_rate)]
+    fn py_reported_rate(&self) -> Option<f64> {
+        self.reported_rate
+    }
+
+    /// The measured refresh rate in Hz, from the median interval between frames.
+    #[getter(measured_rate)]
+    fn py_measured_rate(&self) -> Option<f64> {
+        self.measured_rate()
+    }
+
+    /// The intervals between consecutive frames in seconds.
+    #[getter(intervals)]
+    fn py_intervals(&self) -> Vec<f64> {
+        self.intervals.iter().map(|i| i.as_secs_f64()).collect()
+    }
+
+    /// The standard deviation of the intervals between frames in seconds.
+    #[getter(jitter)]
+    fn py_jitter(&self) -> f64 {
+        self.jitter()
+    }
+
+    /// Problems with the display mode that were detected, e.g. frame doubling or interference by a
+    /// compositor. Empty if the display behaves as expected.
+    #[getter(warnings)]
+    fn py_warnings(&self) -> Vec<String> {
+        self.warnings()
+    }
+
+    fn __repr__(&self) -> String {
+        let rate = |rate: Option<f64>| rate.map_or("None".to_string(), |rate| format!("{rate:.2}"));
+        format!(
+            "RefreshMeasurement(reported_rate={}, measured_rate={}, jitter={:.3}ms, warnings={})",
+            rate(self.reported_rate),
+            rate(self.measured_rate()),
+            self.jitter() * 1000.0,
+            self.warnings().len()
+        )
+    }
+}
@@ -0,0 +1,477 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyDictMethods},
+};
+use send_wrapper::SendWrapper;
+
+#[cfg(feature = "python")]
+use super::stimuli::PyStimulus;
+use super::{
+    report::SequenceReport,
+    stimuli::DynamicStimulus,
+    window::{Frame, Window},
+};
+#[cfg(feature = "python")]
+use crate::time::PyTimeline;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    input::{Event, EventKind},
+    time::{TimelineEvent, Timestamp},
+};
+
+/// A set of stimuli that is shown at a given time for a given duration.
+#[derive(Debug, Clone)]
+pub struct ScheduleItem {
+    pub stimuli: Vec<DynamicStimulus>,
+    /// Onset relative to the start of the schedule.
+    pub onset: Duration,
+    pub duration: Duration,
+    /// If set, the schedule waits after this item until a response arrives or until this long
+    /// after the onset of the item, whichever comes first, and a `Timeout` event is emitted at the
+    /// deadline if there was no response. Later items are then shifted so that they keep their
+    /// onsets relative to the deadline.
+    pub deadline: Option<Duration>,
+}
+
+/// A response that was collected while a schedule was running.
+#[derive(Debug, Clone)]
+pub struct ScheduledResponse {
+    pub event: Event,
+    /// The index of the item that was on screen (or last shown) when the response was given.
+    pub item: Option<usize>,
+    /// Time since the onset of that item.
+    pub rt: Option<Duration>,
+}
+
+/// Timing and responses of a schedule run.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct ScheduleReport {
+    /// The timing of all presented frames, including blank frames between items.
+    pub sequence: SequenceReport,
+    /// For every item, the index of its step in `sequence`.
+    item_steps: Vec<usize>,
+    /// All collected responses in the order they were given.
+    pub responses: Vec<ScheduledResponse>,
+}
+
+impl ScheduleReport {
+    /// The onset of the given item.
+    pub fn item_onset(&self, item: usize) -> Option<Instant> {
+        self.sequence.steps.get(self.item_steps[item])?.onset()
+    }
+
+    /// The estimated offset of the given item.
+    pub fn item_offset(&self, item: usize) -> Option<Instant> {
+        self.sequence.steps.get(self.item_steps[item])?.offset()
+    }
+}
+
+/// Runs a sequence of timed stimulus presentations, e.g. rapid serial visual presentation (RSVP),
+/// without returning to Python between items.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    pub items: Vec<ScheduleItem>,
+}
+
+impl Scheduler {
+    /// Present all items on `window` and collect events of the given kinds as responses.
+    ///
+    /// Onsets and durations are rounded to whole refresh intervals. The time between items shows
+    /// the window's background. Timeouts of items with a deadline are collected as responses.
+    pub fn run(
+        &self,
+        window: &Window,
+        response_kinds: &[EventKind],
+        repeat_update: bool,
+    ) -> PsydkResult<ScheduleReport> {
+        if self.items.is_empty() {
+            return Err(PsydkError::ParameterError("The schedule is empty".into()));
+        }
+
+        let refresh_rate = window.get_current_refresh_rate().ok_or_else(|| {
+            PsydkError::MonitorError("Failed to get the refresh rate of the monitor the window is on".into())
+        })?;
+
+        let mut items = self.items.iter().enumerate().collect::<Vec<_>>();
+        items.sort_by_key(|(_, item)| item.onset);
+
+        let has_deadlines = items.iter().any(|(_, item)| item.deadline.is_some());
+        if has_deadlines && response_kinds.is_empty() {
+            return Err(PsydkError::ParameterError(
+                "Items with a deadline need at least one kind of response".into(),
+            ));
+        }
+        for (index, item) in &items {
+            if item.deadline.is_some_and(|deadline| deadline < item.duration) {
+                return Err(PsydkError::ParameterError(format!(
+                    "The deadline of item {index} is shorter than its duration"
+                )));
+            }
+        }
+
+        // responses (and timeouts of deadlines) are collected by event handlers, which are called
+        // while frames are presented
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let mut handler_ids = Vec::new();
+        let timeout = has_deadlines.then_some(EventKind::Timeout);
+        for kind in response_kinds.iter().chain(timeout.iter()) {
+            let collected = collected.clone();
+            handler_ids.push(window.add_event_handler(*kind, move |event| {
+                collected.lock().unwrap().push(event);
+                false
+            })?);
+        }
+
+        let presented = Self::present_items(window, &items, response_kinds, repeat_update, &collected, refresh_rate);
+
+        for id in handler_ids {
+            window.remove_event_handler(id);
+        }
+        let (sequence, item_steps) = presented?;
+
+        let mut report = ScheduleReport {
+            sequence,
+            item_steps,
+            responses: Vec::new(),
+        };
+
+        let mut events = std::mem::take(&mut *collected.lock().unwrap());
+        events.sort_by_key(|event| event.timestamp().timestamp);
+        report.responses = events
+            .into_iter()
+            .map(|event| {
+                let time = event.timestamp().timestamp;
+                // the response belongs to the last item that started before it
+                let item = (0..report.item_steps.len())
+                    .filter_map(|item| Some((item, report.item_onset(item)?)))
+                    .filter(|(_, onset)| *onset <= time)
+                    .max_by_key(|(_, onset)| *onset);
+                ScheduledResponse {
+                    event,
+                    item: item.map(|(item, _)| item),
+                    rt: item.map(|(_, onset)| time - onset),
+                }
+            })
+            .collect();
+
+        Ok(report)
+    }
+}
+
+impl Scheduler {
+    /// Present the items (sorted by onset) in segments that end with an item that has a deadline,
+    /// and wait for a response or the deadline after each segment. Returns the timing of all
+    /// presented frames and the index of the step of every item.
+    fn present_items(
+        window: &Window,
+        items: &[(usize, &ScheduleItem)],
+        response_kinds: &[EventKind],
+        repeat_update: bool,
+        collected: &Mutex<Vec<Event>>,
+        refresh_rate: f64,
+    ) -> PsydkResult<(SequenceReport, Vec<usize>)> {
+        let to_frames = |duration: Duration| (duration.as_secs_f64() * refresh_rate).round() as u32;
+        let refresh_interval = Duration::from_secs_f64(1.0 / refresh_rate);
+
+        let mut steps = Vec::new();
+        let mut item_steps = vec![0; items.len()];
+        // the time, relative to the start of the schedule, that the current segment starts at
+        let mut base = Duration::ZERO;
+        let mut remaining = items;
+        while !remaining.is_empty() {
+            let end = remaining
+                .iter()
+                .position(|(_, item)| item.deadline.is_some())
+                .map_or(remaining.len(), |position| position + 1);
+            let (segment, rest) = remaining.split_at(end);
+            remaining = rest;
+
+            // build one frame per item, with blank frames filling the gaps between items
+            let mut frames: Vec<(Frame, u32)> = Vec::new();
+            let mut next_frame = 0;
+            for (index, item) in segment {
+                let onset = item.onset.checked_sub(base).ok_or_else(|| {
+                    PsydkError::ParameterError(format!(
+                        "Item {index} starts at {:.3} s, before the deadline of the previous item",
+                        item.onset.as_secs_f64()
+                    ))
+                })?;
+                let start = to_frames(onset);
+                if start < next_frame {
+                    return Err(PsydkError::ParameterError(format!(
+                        "Item {index} starts at {:.3} s, before the previous item has ended",
+                        item.onset.as_secs_f64()
+                    )));
+                }
+                if start > next_frame {
+                    frames.push((window.get_frame()?, start - next_frame));
+                }
+
+                let n_frames = to_frames(item.duration).max(1);
+                let mut frame = window.get_frame()?;
+                for stimulus in &item.stimuli {
+                    frame.add(stimulus);
+                }
+                item_steps[*index] = steps.len() + frames.len();
+                frames.push((frame, n_frames));
+                next_frame = start + n_frames;
+            }
+
+            let frames = frames.iter().map(|(frame, n)| (frame, *n)).collect::<Vec<_>>();
+            steps.extend(window.present_sequence(&frames, repeat_update)?.steps);
+
+            // after an item with a deadline, show the background until a response arrives or
+            // the deadline passes
+            let (index, item) = segment[segment.len() - 1];
+            let (Some(deadline), Some(onset)) = (item.deadline, steps[item_steps[index]].onset()) else {
+                continue;
+            };
+            let responded = || {
+                collected
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|event| event.timestamp().timestamp >= onset)
+            };
+            if !responded() {
+                let _deadline = window.set_response_deadline(response_kinds, onset + deadline)?;
+                let mut blank = window.get_frame()?;
+                while !responded() {
+                    steps.push(window.present(&mut blank, None, None, false, None)?);
+                }
+            }
+            base = item.onset + deadline;
+        }
+
+        Ok((SequenceReport::new(steps, refresh_interval), item_steps))
+    }
+}
+
+/// Runs a sequence of timed stimulus presentations without returning to Python in between.
+///
+/// This is intended for rapid serial visual presentation (RSVP), n-back tasks, and other designs
+/// where stimuli change at a high rate and a garbage collection pause in Python would delay the next
+/// stimulus. The whole schedule is presented from Rust, and responses are collected while it runs.
+///
+/// Parameters
+/// ----------
+/// items : list[tuple[Stimulus or list[Stimulus], float, float]], optional
+///     The items of the schedule as (stimuli, onset, duration), with onset and duration in seconds.
+///     Onsets are relative to the start of the schedule.
+#[cfg(feature = "python")]
+#[pyclass(name = "Scheduler")]
+#[derive(Debug, Clone, Default)]
+pub struct PyScheduler(pub Scheduler);
+
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+enum StimulusOrStimuli {
+    Stimulus(PyStimulus),
+    Stimuli(Vec<PyStimulus>),
+}
+
+#[cfg(feature = "python")]
+impl From<StimulusOrStimuli> for Vec<DynamicStimulus> {
+    fn from(stimuli: StimulusOrStimuli) -> Self {
+        match stimuli {
+            StimulusOrStimuli::Stimulus(stimulus) => vec![stimulus.as_super().clone()],
+            StimulusOrStimuli::Stimuli(stimuli) => stimuli.iter().map(|s| s.as_super().clone()).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+fn schedule_item(
+    stimuli: StimulusOrStimuli,
+    onset: f64,
+    duration: f64,
+    deadline: Option<f64>,
+) -> PyResult<ScheduleItem> {
+    let to_duration = |seconds: f64, name: &str| {
+        Duration::try_from_secs_f64(seconds).map_err(|_| {
+            PsydkError::ParameterError(format!(
+                "Invalid {name} {seconds}, must be a non-negative number of seconds"
+            ))
+        })
+    };
+    Ok(ScheduleItem {
+        stimuli: stimuli.into(),
+        onset: to_duration(onset, "onset")?,
+        duration: to_duration(duration, "duration")?,
+        deadline: deadline.map(|deadline| to_duration(deadline, "deadline")).transpose()?,
+    })
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyScheduler {
+    #[new]
+    #[pyo3(signature = (items = Vec::new()))]
+    fn __new__(items: Vec<(StimulusOrStimuli, f64, f64)>) -> PyResult<Self> {
+        let items = items
+            .into_iter()
+            .map(|(stimuli, onset, duration)| schedule_item(stimuli, onset, duration, None))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Self(Scheduler { items }))
+    }
+
+    /// Add an item to the schedule.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimuli : Stimulus or list[Stimulus]
+    ///     The stimuli to show.
+    /// onset : float
+    ///     The onset in seconds, relative to the start of the schedule.
+    /// duration : float
+    ///     How long the stimuli are shown in seconds.
+    /// deadline : float, optional
+    ///     A response deadline in seconds after the onset, at least as long as the duration. If
+    ///     set, the schedule waits after this item until a response arrives or the deadline passes,
+    ///     in which case a "timeout" event is emitted at the deadline and collected with the
+    ///     responses. Later items are shifted so that their onsets stay the same relative to the
+    ///     deadline, i.e. the schedule advances early after a response.
+    #[pyo3(name = "add")]
+    #[pyo3(signature = (stimuli, onset, duration, deadline = None))]
+    fn py_add(&mut self, stimuli: StimulusOrStimuli, onset: f64, duration: f64, deadline: Option<f64>) -> PyResult<()> {
+        self.0.items.push(schedule_item(stimuli, onset, duration, deadline)?);
+        Ok(())
+    }
+
+    /// Present the schedule on a window. Blocks until the last item has been shown.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///     The window to present the schedule on.
+    /// responses : list[EventKind], optional
+    ///     The kinds of events that are collected as responses. Defaults to key presses.
+    /// repeat_update : bool, optional
+    ///     Whether frames are re-rendered for every refresh interval, see `Window.present`.
+    ///
+    /// Returns
+    /// -------
+    /// ScheduleReport
+    ///     The onsets and offsets of all items and the collected responses.
+    #[pyo3(name = "run")]
+    #[pyo3(signature = (window, responses = vec![EventKind::KeyPress], repeat_update = true))]
+    fn py_run(
+        &self,
+        window: Window,
+        responses: Vec<EventKind>,
+        repeat_update: bool,
+        py: Python,
+    ) -> PyResult<ScheduleReport> {
+        let scheduler = SendWrapper::new(self.0.clone());
+        let window_wrapper = SendWrapper::new(window.clone());
+        let report = py
+            .allow_threads(move || scheduler.run(&window_wrapper, &responses, repeat_update))
+            .map_err(PyErr::from)?;
+
+        window.call_watchdog_callback(py)?;
+
+        Ok(report)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.items.len()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ScheduleReport {
+    /// The timing of all presented frames, including blank frames between items.
+    #[getter(sequence)]
+    fn py_sequence(&self) -> SequenceReport {
+        self.sequence.clone()
+    }
+
+    /// The onset of each item.
+    #[getter(onsets)]
+    fn py_onsets(&self) -> Vec<Option<Timestamp>> {
+        (0..self.item_steps.len())
+            .map(|item| self.item_onset(item).map(Into::into))
+            .collect()
+    }
+
+    /// The estimated offset of each item.
+    #[getter(offsets)]
+    fn py_offsets(&self) -> Vec<Option<Timestamp>> {
+        (0..self.item_steps.len())
+            .map(|item| self.item_offset(item).map(Into::into))
+            .collect()
+    }
+
+    /// The collected responses as dictionaries with the event, the index of the item that was shown
+    /// last before the response, and the response time in seconds relative to that item.
+    #[getter(responses)]
+    fn py_responses<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.responses
+            .iter()
+            .map(|response| {
+                let dict = PyDict::new(py);
+                dict.set_item("event", response.event.clone())?;
+                dict.set_item("item", response.item)?;
+                dict.set_item("rt", response.rt.map(|rt| rt.as_secs_f64()))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Number of frames that were shown late.
+    #[getter(missed_deadlines)]
+    fn py_missed_deadlines(&self) -> u32 {
+        self.sequence.combined().missed_deadlines()
+    }
+
+    /// Add the onsets and offsets of all items and all responses to a timeline. Onsets and offsets
+    /// are labelled with the index of the item.
+    ///
+    /// Parameters
+    /// ----------
+    /// timeline : Timeline
+    ///     The timeline to add the events to.
+    #[pyo3(name = "to_timeline")]
+    fn py_to_timeline(&self, timeline: &mut PyTimeline) {
+        for item in 0..self.item_steps.len() {
+            for (kind, time) in [("onset", self.item_onset(item)), ("offset", self.item_offset(item))] {
+                if let Some(time) = time {
+                    timeline.0.add(TimelineEvent {
+                        kind: kind.to_string(),
+                        time,
+                        label: Some(item.to_string()),
+                        data: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        for response in &self.responses {
+            let mut event = TimelineEvent::from(&response.event);
+            if let Some(item) = response.item {
+                event.data.push(("item".to_string(), item.to_string()));
+            }
+            if let Some(rt) = response.rt {
+                event.data.push(("rt".to_string(), rt.as_secs_f64().to_string()));
+            }
+            timeline.0.add(event);
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScheduleReport(items={}, responses={}, missed_deadlines={})",
+            self.item_steps.len(),
+            self.responses.len(),
+            self.sequence.combined().missed_deadlines()
+        )
+    }
+}
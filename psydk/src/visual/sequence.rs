@@ -0,0 +1,50 @@
+use pyo3::prelude::*;
+
+use super::window::Frame;
+
+/// A sequence of frames that are presented back-to-back, e.g. a prime followed by a mask.
+///
+/// Presenting a sequence with `Window.present_sequence` guarantees that there is no gap between
+/// the steps, which is not the case when calling `Window.present` repeatedly from Python.
+///
+/// Parameters
+/// ----------
+/// steps : list[tuple[Frame, int]], optional
+///     The frames and the number of refresh intervals each of them is shown for.
+#[pyclass]
+pub struct Sequence {
+    pub steps: Vec<(Py<Frame>, u32)>,
+}
+
+#[pymethods]
+impl Sequence {
+    #[new]
+    #[pyo3(signature = (steps = Vec::new()))]
+    fn __new__(steps: Vec<(Py<Frame>, u32)>) -> Self {
+        Self { steps }
+    }
+
+    /// Append a frame to the sequence.
+    ///
+    /// Parameters
+    /// ----------
+    /// frame : Frame
+    ///     The frame to show. The same frame can appear in the sequence more than once.
+    /// n_frames : int, optional
+    ///     The number of refresh intervals the frame is shown for. Defaults to 1.
+    #[pyo3(name = "add")]
+    #[pyo3(signature = (frame, n_frames = 1))]
+    fn py_add(&mut self, frame: Py<Frame>, n_frames: u32) {
+        self.steps.push((frame, n_frames));
+    }
+
+    /// The total number of refresh intervals of the sequence.
+    #[getter(n_frames)]
+    fn py_n_frames(&self) -> u32 {
+        self.steps.iter().map(|(_, n_frames)| n_frames).sum()
+    }
+
+    fn __len__(&self) -> usize {
+        self.steps.len()
+    }
+}
@@ -1,5 +1,6 @@
 use std::time::Instant;
 
+#[cfg(feature = "python")]
 use pyo3::{types::PyAnyMethods, Bound, FromPyObject, PyAny, PyResult};
 
 use super::{Stimulus, StimulusParamValue};
@@ -8,7 +9,8 @@ use crate::visual::{
     window::{Window, WindowState},
 };
 
-#[derive(FromPyObject, Debug, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", derive(FromPyObject))]
 pub enum Repeat {
     /// Play the animation the specified number of times.
     Loop(u32),
@@ -27,6 +29,7 @@ pub enum TransitionFunction {
 }
 
 // implement FromPyObject for TransitionFunction
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for TransitionFunction {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         // try to extract a string from the object and then convert it to a TransitionFunction
@@ -0,0 +1,378 @@
+//! Clocks.
+//!
+//! A clock shows the time that has passed since it was started, or the time that remains of a
+//! countdown, as "mm:ss.t". The time is taken from the frame that is being drawn, so the readout
+//! is updated on every frame without changing the text from Python, and it matches the time at
+//! which the frame is shown.
+
+use std::time::{Duration, Instant};
+
+use renderer::DynamicScene;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation,
+    helpers, impl_pystimulus_for_wrapper,
+    text::{FontFamilies, FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+    Stimulus, StimulusLayout, StimulusParamValue,
+};
+use crate::{
+    context::ExperimentContext,
+    time::Timestamp,
+    visual::{
+        color::IntoLinRgba,
+        geometry::{Anchor, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// Format a number of seconds as minutes, seconds, and tenths of a second ("mm:ss.t"). The tenths
+/// are rounded up if `round_up` is true (so that a countdown only shows zero once it is over) and
+/// down otherwise.
+pub fn format_clock(seconds: f64, round_up: bool) -> String {
+    let tenths = seconds.max(0.0) * 10.0;
+    // without the small tolerance, 1.0 - 0.9 would be shown as 0.2
+    let tenths = match round_up {
+        true => (tenths - 1e-6).ceil(),
+        false => (tenths + 1e-6).floor(),
+    }
+    .max(0.0) as u64;
+    format!("{:02}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+/// A readout of elapsed time or of a countdown.
+#[derive(Debug)]
+pub struct ClockStimulus {
+    id: Uuid,
+    text: TextStimulus,
+    /// The duration of the countdown, or None to show the elapsed time.
+    duration: Option<Duration>,
+    /// When the clock was (last) started or resumed, if it is running.
+    running_since: Option<Instant>,
+    /// The time that had elapsed when the clock was last paused.
+    elapsed_before: Duration,
+    /// Whether the clock starts with the first frame it is drawn in.
+    auto_start: bool,
+    /// The time of the last frame the clock was drawn in.
+    frame_time: Option<Instant>,
+}
+
+impl ClockStimulus {
+    pub fn new(text: TextStimulus, duration: Option<Duration>, auto_start: bool) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            text,
+            duration,
+            running_since: None,
+            elapsed_before: Duration::ZERO,
+            auto_start,
+            frame_time: None,
+        }
+    }
+
+    /// Start (or resume) the clock at `time`. Does nothing if the clock is running.
+    pub fn start(&mut self, time: Instant) {
+        self.auto_start = false;
+        self.running_since.get_or_insert(time);
+    }
+
+    /// Stop the clock at `time`, keeping the elapsed time.
+    pub fn pause(&mut self, time: Instant) {
+        self.auto_start = false;
+        if let Some(since) = self.running_since.take() {
+            self.elapsed_before += time.saturating_duration_since(since);
+        }
+    }
+
+    /// Stop the clock and set it back to zero (or to the full duration of a countdown). If
+    /// `auto_start` is true, it starts again with the next frame it is drawn in.
+    pub fn reset(&mut self, auto_start: bool) {
+        self.running_since = None;
+        self.elapsed_before = Duration::ZERO;
+        self.auto_start = auto_start;
+    }
+
+    pub fn running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    /// The time that has elapsed at `time`.
+    pub fn elapsed(&self, time: Instant) -> Duration {
+        let running = self
+            .running_since
+            .map_or(Duration::ZERO, |since| time.saturating_duration_since(since));
+        self.elapsed_before + running
+    }
+
+    /// The time that remains of the countdown at `time`, or None if the clock is not a countdown.
+    pub fn remaining(&self, time: Instant) -> Option<Duration> {
+        self.duration
+            .map(|duration| duration.saturating_sub(self.elapsed(time)))
+    }
+
+    /// Whether the countdown was over in the last frame the clock was drawn in.
+    pub fn finished(&self) -> bool {
+        let time = self.frame_time.unwrap_or_else(Instant::now);
+        self.remaining(time) == Some(Duration::ZERO)
+    }
+
+    /// The readout at `time`.
+    pub fn readout(&self, time: Instant) -> String {
+        match self.remaining(time) {
+            Some(remaining) => format_clock(remaining.as_secs_f64(), true),
+            None => format_clock(self.elapsed(time).as_secs_f64(), false),
+        }
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyClockStimulus, ClockStimulus);
+
+impl Stimulus for ClockStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.text.visible() {
+            return;
+        }
+        let time = self.frame_time.unwrap_or_else(Instant::now);
+        if self.auto_start {
+            self.start(time);
+        }
+        self.text
+            .set_param("text", StimulusParamValue::String(self.readout(time)));
+        self.text.draw(scene, window_state);
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        self.text.contains(x, y, window)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        self.text.layout(window_state)
+    }
+
+    fn update_animations(&mut self, time: Instant, window_state: &WindowState) {
+        // the readout is for the time the frame is shown
+        self.frame_time = Some(time);
+        self.text.update_animations(time, window_state);
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.text.set_visible(visible);
+    }
+
+    fn visible(&self) -> bool {
+        self.text.visible()
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        self.text.animations()
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.text.add_animation(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.text.set_transformation(transformation);
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.text.transformation()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.text.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.text.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.text.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.text.set_param(name, value)
+    }
+}
+
+/// A clock that shows the elapsed time or a countdown as "mm:ss.t", e.g. to show time pressure in
+/// timed blocks.
+///
+/// The readout is computed for each frame when it is drawn, for the time at which the frame is
+/// shown, so the clock does not need to be updated from Python. By default, the clock starts with
+/// the first frame it is drawn in; it can also be started, paused, and reset explicitly. A
+/// countdown stops at 00:00.0, and `finished` tells whether it is over.
+///
+/// The clock has the parameters of a `TextStimulus` (e.g. "font_size" and "fill_color"), except
+/// that its "text" is replaced on every frame.
+///
+/// Parameters
+/// ----------
+/// duration : float, optional
+///   The duration of a countdown in seconds. Defaults to None, which shows the elapsed time
+///   instead.
+/// font_size : Size, optional
+///   The font size.
+/// auto_start : bool, optional
+///   Whether the clock starts with the first frame it is drawn in. Defaults to True.
+/// font_family : str or list[str], optional
+///   The font family. Defaults to "Noto Sans".
+/// font_weight : str, optional
+///   The font weight. Defaults to "regular".
+/// alpha : float, optional
+///   The opacity of the clock.
+/// anchor : str, optional
+///   The point of the readout that the position refers to. Defaults to "center".
+/// x : Size, optional
+///   The x position of the clock.
+/// y : Size, optional
+///   The y position of the clock.
+/// fill_color : Color, optional
+///   The color of the readout. Defaults to black.
+/// transform : Transformation2D, optional
+///   The transformation of the clock.
+/// context : ExperimentContext, optional
+///   The experiment context. Defaults to the context of the experiment function.
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "ClockStimulus", extends=PyStimulus)]
+pub struct PyClockStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyClockStimulus {
+    #[new]
+    #[pyo3(signature = (
+        duration = None,
+        font_size = IntoSize(Size::Pixels(48.0)),
+        auto_start = true,
+        font_family = FontFamilies::Family("Noto Sans".to_string()),
+        font_weight = FontWeight::Regular,
+        alpha = 1.0,
+        anchor = Anchor::Center,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        fill_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        transform = Transformation2D::Identity(),
+        context = None,
+    ))]
+    fn __new__(
+        py: Python,
+        duration: Option<f64>,
+        font_size: IntoSize,
+        auto_start: bool,
+        font_family: FontFamilies,
+        font_weight: FontWeight,
+        alpha: f64,
+        anchor: Anchor,
+        x: IntoSize,
+        y: IntoSize,
+        fill_color: IntoLinRgba,
+        transform: Transformation2D,
+        context: Option<ExperimentContext>,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let context = helpers::get_experiment_context(context, py)?;
+        let duration = duration
+            .map(|duration| {
+                Duration::try_from_secs_f64(duration)
+                    .map_err(|_| PyValueError::new_err("The duration must be a non-negative number of seconds"))
+            })
+            .transpose()?;
+        let text = TextStimulus::new(
+            x.into(),
+            y.into(),
+            "",
+            TextAlignment::Center,
+            TextDirection::Ltr,
+            TextOrientation::Horizontal,
+            anchor,
+            font_size.into(),
+            &font_family.into_vec(),
+            font_weight,
+            fill_color.into(),
+            alpha,
+            0.0,
+            transform,
+            &context,
+        );
+        Ok((Self(), PyStimulus::new(ClockStimulus::new(text, duration, auto_start))))
+    }
+
+    /// Start (or resume) the clock now. Does nothing if the clock is running.
+    #[pyo3(name = "start")]
+    fn py_start(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, ClockStimulus).start(Instant::now());
+    }
+
+    /// Stop the clock now, keeping the elapsed time. `start` resumes it.
+    #[pyo3(name = "pause")]
+    fn py_pause(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, ClockStimulus).pause(Instant::now());
+    }
+
+    /// Stop the clock and set it back to zero (or to the full duration of a countdown).
+    ///
+    /// Parameters
+    /// ----------
+    /// auto_start : bool, optional
+    ///   Whether the clock starts again with the next frame it is drawn in. Defaults to True.
+    #[pyo3(name = "reset")]
+    #[pyo3(signature = (auto_start = true))]
+    fn py_reset(slf: PyRefMut<'_, Self>, auto_start: bool) {
+        downcast_py_stimulus_mut!(slf, ClockStimulus).reset(auto_start);
+    }
+
+    /// Whether the clock is running.
+    #[getter(running)]
+    fn py_running(slf: PyRef<'_, Self>) -> bool {
+        downcast_stimulus!(slf, ClockStimulus).running()
+    }
+
+    /// The time that has elapsed now, in seconds.
+    #[getter(elapsed)]
+    fn py_elapsed(slf: PyRef<'_, Self>) -> f64 {
+        downcast_stimulus!(slf, ClockStimulus)
+            .elapsed(Instant::now())
+            .as_secs_f64()
+    }
+
+    /// The time that remains of the countdown now, in seconds, or None if the clock shows the
+    /// elapsed time.
+    #[getter(remaining)]
+    fn py_remaining(slf: PyRef<'_, Self>) -> Option<f64> {
+        downcast_stimulus!(slf, ClockStimulus)
+            .remaining(Instant::now())
+            .map(|remaining| remaining.as_secs_f64())
+    }
+
+    /// Whether the countdown was over in the last frame the clock was drawn in.
+    #[getter(finished)]
+    fn py_finished(slf: PyRef<'_, Self>) -> bool {
+        downcast_stimulus!(slf, ClockStimulus).finished()
+    }
+
+    /// The readout for a point in time.
+    ///
+    /// Parameters
+    /// ----------
+    /// time : Timestamp, optional
+    ///   The point in time. Defaults to now.
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///   The readout, as "mm:ss.t".
+    #[pyo3(name = "readout")]
+    #[pyo3(signature = (time = None))]
+    fn py_readout(slf: PyRef<'_, Self>, time: Option<Timestamp>) -> String {
+        let time = time.map_or_else(Instant::now, |time| time.timestamp);
+        downcast_stimulus!(slf, ClockStimulus).readout(time)
+    }
+}
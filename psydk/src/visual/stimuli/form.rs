@@ -0,0 +1,1170 @@
+//! Questionnaires.
+//!
+//! A form shows a list of items, each a question with a response field (a text or number field,
+//! radio buttons, checkboxes, or a dropdown list), one below the other in a box, followed by a
+//! submit button. The box scrolls with the mouse wheel, by dragging, and with the arrow and page
+//! keys when the items don't fit. Submitting checks that all required items are answered and that
+//! numbers are valid, and marks the items that are not.
+
+use std::time::Instant;
+
+use psydk_proc::StimulusParams;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyList};
+use renderer::{shapes::Shape, styles::BlendMode, DynamicScene};
+use send_wrapper::SendWrapper;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation,
+    helpers, impl_pystimulus_for_wrapper,
+    text::FontWeight,
+    widgets::{
+        faded, fill, stroke, Attachment, Interactive, Label, Pointer, Rect, Viewport, DRAG_THRESHOLD, ERROR_COLOR,
+    },
+    DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
+};
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    input::{text::EditableText, Event},
+    time::Timestamp,
+    visual::{
+        color::{IntoLinRgba, LinRgba},
+        geometry::{Anchor, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// Shown below required items that are not answered.
+const REQUIRED_MESSAGE: &str = "Please answer this question.";
+/// Shown below number fields that don't contain a number.
+const NUMBER_MESSAGE: &str = "Please enter a number.";
+/// Shown in dropdown lists without a selection.
+const DROPDOWN_PLACEHOLDER: &str = "Select...";
+/// How far one line of mouse wheel movement scrolls, in font sizes.
+const WHEEL_STEP: f32 = 2.0;
+
+/// The kind of response to an item of a form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseType {
+    /// A line of free text.
+    Text { max_length: Option<usize> },
+    /// A number, optionally within bounds.
+    Number { min: Option<f64>, max: Option<f64> },
+    /// One of the options, shown as radio buttons.
+    Radio { options: Vec<String>, horizontal: bool },
+    /// Any number of the options, shown as checkboxes.
+    Checkbox { options: Vec<String>, horizontal: bool },
+    /// One of the options, chosen from a dropdown list.
+    Dropdown { options: Vec<String> },
+}
+
+impl ResponseType {
+    fn options(&self) -> &[String] {
+        match self {
+            ResponseType::Radio { options, .. }
+            | ResponseType::Checkbox { options, .. }
+            | ResponseType::Dropdown { options } => options,
+            ResponseType::Text { .. } | ResponseType::Number { .. } => &[],
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, ResponseType::Text { .. } | ResponseType::Number { .. })
+    }
+}
+
+/// An item of a form: a question and how it is answered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormItem {
+    /// The key of the answer.
+    pub name: String,
+    /// The question.
+    pub label: String,
+    pub response: ResponseType,
+    pub required: bool,
+}
+
+/// The answer to an item of a form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    Text(String),
+    Number(f64),
+    /// The selected option of radio buttons and dropdown lists.
+    Choice(String),
+    /// The selected options of checkboxes, in the order of the options.
+    Choices(Vec<String>),
+}
+
+/// The state of the response field of an item.
+#[derive(Debug, Clone, Default)]
+struct Field {
+    /// The text of text and number fields.
+    text: EditableText,
+    /// The text an input method is composing.
+    preedit: String,
+    /// Which options are selected.
+    selected: Vec<bool>,
+    /// When the answer was last changed.
+    changed: Option<Instant>,
+    /// Why the answer is not valid, shown below the item after a failed submission.
+    error: Option<String>,
+}
+
+/// A part of the form that can be clicked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    /// An option (radio button or checkbox) of an item.
+    Option(usize, usize),
+    /// The closed dropdown list of an item.
+    Dropdown(usize),
+    /// The text or number field of an item.
+    Field(usize),
+    Submit,
+}
+
+/// Where the parts of the form were drawn last. Rectangles of the content are in the coordinates of
+/// the scene as if the form was not scrolled.
+#[derive(Debug, Clone)]
+struct FormLayout {
+    viewport: Viewport,
+    bounds: Rect,
+    font_size: f32,
+    targets: Vec<(Rect, Target)>,
+    /// The options of the open dropdown list.
+    popup: Vec<(Rect, usize)>,
+    /// The top of each item.
+    item_tops: Vec<f32>,
+    max_scroll: f32,
+}
+
+/// A pointer that is pressed on the form.
+#[derive(Debug, Clone, Copy)]
+struct Press {
+    position: (f32, f32),
+    scroll: f32,
+    dragging: bool,
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct FormParams {
+    pub x: Size,
+    pub y: Size,
+    pub width: Size,
+    pub height: Size,
+    pub font_size: Size,
+    pub text_color: LinRgba,
+    /// The color of selections, the focused field, and the submit button.
+    pub accent_color: LinRgba,
+    pub background_color: LinRgba,
+}
+
+/// A questionnaire with text, number, radio button, checkbox, and dropdown items.
+#[derive(Debug)]
+pub struct FormStimulus {
+    id: Uuid,
+    params: FormParams,
+    items: Vec<FormItem>,
+    fields: Vec<Field>,
+    submit_label: String,
+    anchor: Anchor,
+    label: Label,
+    /// How far the content is scrolled up, in pixels.
+    scroll: f32,
+    /// The item whose text field receives typed text.
+    focus: Option<usize>,
+    /// The item whose dropdown list is open.
+    open: Option<usize>,
+    press: Option<Press>,
+    submitted: Option<Instant>,
+    layout: Option<FormLayout>,
+    attachment: Option<Attachment>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl FormStimulus {
+    pub fn new(
+        items: Vec<FormItem>,
+        submit_label: &str,
+        anchor: Anchor,
+        params: FormParams,
+        context: &ExperimentContext,
+    ) -> PsydkResult<Self> {
+        for (i, item) in items.iter().enumerate() {
+            if items[..i].iter().any(|other| other.name == item.name) {
+                return Err(PsydkError::ParameterError(format!(
+                    "The name \"{}\" is used by more than one item of the form",
+                    item.name
+                )));
+            }
+            if !item.response.is_text() && item.response.options().is_empty() {
+                return Err(PsydkError::ParameterError(format!(
+                    "The item \"{}\" has no options",
+                    item.name
+                )));
+            }
+        }
+
+        let fields = items
+            .iter()
+            .map(|item| Field {
+                selected: vec![false; item.response.options().len()],
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            params,
+            items,
+            fields,
+            submit_label: submit_label.to_string(),
+            anchor,
+            label: Label::new(FontWeight::Regular, context),
+            scroll: 0.0,
+            focus: None,
+            open: None,
+            press: None,
+            submitted: None,
+            layout: None,
+            attachment: None,
+            transformation: Transformation2D::Identity(),
+            animations: Vec::new(),
+            visible: true,
+        })
+    }
+
+    pub fn items(&self) -> &[FormItem] {
+        &self.items
+    }
+
+    /// The answer to item `i`, or an error message if the answer is not valid.
+    fn answer(&self, i: usize) -> Result<Option<Answer>, String> {
+        let field = &self.fields[i];
+        let options = self.items[i].response.options();
+        let selected = || {
+            options
+                .iter()
+                .zip(&field.selected)
+                .filter(|(_, selected)| **selected)
+                .map(|(option, _)| option.clone())
+        };
+        let text = field.text.text.trim();
+
+        match &self.items[i].response {
+            ResponseType::Text { .. } if text.is_empty() => Ok(None),
+            ResponseType::Text { .. } => Ok(Some(Answer::Text(field.text.text.clone()))),
+            ResponseType::Number { .. } if text.is_empty() => Ok(None),
+            ResponseType::Number { min, max } => {
+                let number: f64 = text.replace(',', ".").parse().map_err(|_| NUMBER_MESSAGE.to_string())?;
+                match (min, max) {
+                    (Some(min), Some(max)) if number < *min || number > *max => {
+                        Err(format!("Please enter a number between {min} and {max}."))
+                    }
+                    (Some(min), None) if number < *min => Err(format!("Please enter a number of at least {min}.")),
+                    (None, Some(max)) if number > *max => Err(format!("Please enter a number of at most {max}.")),
+                    _ => Ok(Some(Answer::Number(number))),
+                }
+            }
+            ResponseType::Radio { .. } | ResponseType::Dropdown { .. } => Ok(selected().next().map(Answer::Choice)),
+            ResponseType::Checkbox { .. } => {
+                let selected: Vec<String> = selected().collect();
+                Ok((!selected.is_empty()).then_some(Answer::Choices(selected)))
+            }
+        }
+    }
+
+    /// The answers by item name, in the order of the items. Unanswered items and invalid numbers
+    /// have no answer.
+    pub fn answers(&self) -> Vec<(String, Option<Answer>)> {
+        (0..self.items.len())
+            .map(|i| (self.items[i].name.clone(), self.answer(i).ok().flatten()))
+            .collect()
+    }
+
+    /// When each answer was last changed, by item name.
+    pub fn answer_times(&self) -> Vec<(String, Option<Instant>)> {
+        self.items
+            .iter()
+            .zip(&self.fields)
+            .map(|(item, field)| (item.name.clone(), field.changed))
+            .collect()
+    }
+
+    /// Check all answers and mark the items that are required but not answered or not valid.
+    /// Returns true if all answers are valid.
+    pub fn validate(&mut self) -> bool {
+        for i in 0..self.items.len() {
+            self.fields[i].error = match self.answer(i) {
+                Err(message) => Some(message),
+                Ok(None) if self.items[i].required => Some(REQUIRED_MESSAGE.to_string()),
+                Ok(_) => None,
+            };
+        }
+        self.fields.iter().all(|field| field.error.is_none())
+    }
+
+    /// When the form was submitted with valid answers, if it was.
+    pub fn submitted(&self) -> Option<Instant> {
+        self.submitted
+    }
+
+    /// Discard all answers and scroll back to the top.
+    pub fn reset(&mut self) {
+        for field in &mut self.fields {
+            *field = Field {
+                selected: vec![false; field.selected.len()],
+                ..Default::default()
+            };
+        }
+        self.scroll = 0.0;
+        self.focus = None;
+        self.open = None;
+        self.press = None;
+        self.submitted = None;
+    }
+
+    /// Pass the input events of `window` to `form` as they arrive, until `detach` is called.
+    pub fn attach(form: &DynamicStimulus, window: &Window) -> PsydkResult<()> {
+        window.set_ime_allowed(true)?;
+        // created before the form is locked, as it needs the state of the window
+        let attachment = Attachment::new::<Self>(window, form)?;
+        if let Some(form) = form.lock().downcast_mut::<Self>() {
+            form.attachment = Some(attachment);
+        }
+        Ok(())
+    }
+
+    /// Stop passing input events to the form.
+    pub fn detach(&mut self) {
+        self.attachment = None;
+    }
+
+    fn scroll_to(&mut self, scroll: f32) {
+        let max_scroll = self.layout.as_ref().map_or(0.0, |layout| layout.max_scroll);
+        self.scroll = scroll.clamp(0.0, max_scroll);
+    }
+
+    fn submit(&mut self, time: Instant) {
+        self.focus = None;
+        if self.validate() {
+            self.submitted = Some(time);
+            return;
+        }
+        // show the first item that needs attention
+        let first = self.fields.iter().position(|field| field.error.is_some());
+        if let (Some(i), Some(layout)) = (first, &self.layout) {
+            let scroll = layout.item_tops[i] - layout.bounds.y - layout.font_size;
+            self.scroll_to(scroll);
+        }
+    }
+
+    fn changed(&mut self, i: usize, time: Instant) {
+        self.fields[i].changed = Some(time);
+        self.fields[i].error = None;
+    }
+
+    fn click(&mut self, position: (f32, f32), time: Instant) -> bool {
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        let content = (position.0, position.1 + self.scroll);
+
+        // an open dropdown list takes the click, wherever it is
+        if let Some(i) = self.open.take() {
+            if let Some((_, option)) = layout.popup.iter().find(|(rect, _)| rect.contains(content)) {
+                let option = *option;
+                self.fields[i]
+                    .selected
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(j, s)| *s = j == option);
+                self.changed(i, time);
+            }
+            return true;
+        }
+
+        if !layout.bounds.contains(position) {
+            self.focus = None;
+            return false;
+        }
+        let target = layout
+            .targets
+            .iter()
+            .find(|(rect, _)| rect.contains(content))
+            .map(|(_, target)| *target);
+        self.focus = None;
+        match target {
+            Some(Target::Option(i, option)) => {
+                let selected = &mut self.fields[i].selected;
+                match self.items[i].response {
+                    ResponseType::Checkbox { .. } => selected[option] = !selected[option],
+                    _ => selected.iter_mut().enumerate().for_each(|(j, s)| *s = j == option),
+                }
+                self.changed(i, time);
+            }
+            Some(Target::Dropdown(i)) => self.open = Some(i),
+            Some(Target::Field(i)) => {
+                self.focus = Some(i);
+                let text = &mut self.fields[i].text;
+                text.caret = text.text.len();
+            }
+            Some(Target::Submit) => self.submit(time),
+            None => {}
+        }
+        true
+    }
+
+    fn pointer(&mut self, pointer: Pointer, position: (f32, f32), time: Instant) -> bool {
+        match pointer {
+            Pointer::Down => {
+                let inside = self
+                    .layout
+                    .as_ref()
+                    .is_some_and(|layout| layout.bounds.contains(position));
+                if inside || self.open.is_some() {
+                    self.press = Some(Press {
+                        position,
+                        scroll: self.scroll,
+                        dragging: false,
+                    });
+                }
+                self.press.is_some()
+            }
+            Pointer::Move => {
+                let Some(press) = self.press.as_mut() else {
+                    return false;
+                };
+                let dy = position.1 - press.position.1;
+                press.dragging |= dy.abs() > DRAG_THRESHOLD && self.open.is_none();
+                if !press.dragging {
+                    return false;
+                }
+                let scroll = press.scroll - dy;
+                self.scroll_to(scroll);
+                true
+            }
+            Pointer::Up => match self.press.take() {
+                Some(press) if press.dragging => true,
+                Some(press) => self.click(press.position, time),
+                None => false,
+            },
+        }
+    }
+
+    fn key(&mut self, key: &str, time: Instant) -> bool {
+        if let Some(i) = self.focus {
+            // while an input method is composing, it handles the keys itself
+            if !self.fields[i].preedit.is_empty() {
+                return false;
+            }
+            if key == "Tab" || key == "Enter" {
+                // move on to the next text field
+                self.focus = (i + 1..self.items.len()).find(|&j| self.items[j].response.is_text());
+                return true;
+            }
+            if self.fields[i].text.key(key) {
+                if key == "Backspace" || key == "Delete" {
+                    self.changed(i, time);
+                }
+                return true;
+            }
+        }
+
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        let scroll = match key {
+            "ArrowDown" => self.scroll + WHEEL_STEP * layout.font_size,
+            "ArrowUp" => self.scroll - WHEEL_STEP * layout.font_size,
+            "PageDown" => self.scroll + 0.9 * layout.bounds.height,
+            "PageUp" => self.scroll - 0.9 * layout.bounds.height,
+            "Home" => 0.0,
+            "End" => layout.max_scroll,
+            _ => return false,
+        };
+        self.scroll_to(scroll);
+        true
+    }
+
+    fn text(&mut self, text: &str, time: Instant) -> bool {
+        let Some(i) = self.focus else {
+            return false;
+        };
+        let text = match self.items[i].response {
+            // only what can be part of a number
+            ResponseType::Number { .. } => text
+                .chars()
+                .filter(|c| c.is_ascii_digit() || "+-.,eE".contains(*c))
+                .collect(),
+            _ => text.to_string(),
+        };
+        let max_length = match self.items[i].response {
+            ResponseType::Text { max_length } => max_length,
+            _ => None,
+        };
+        self.fields[i].text.insert(&text, max_length);
+        self.changed(i, time);
+        true
+    }
+
+    /// The rectangle of the form in the coordinates of the scene.
+    fn bounds(&self, window_state: &WindowState) -> Rect {
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let (width, height) = (eval(&self.params.width), eval(&self.params.height));
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (eval(&self.params.x), eval(&self.params.y)),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+        Rect::new(x, y, width, height)
+    }
+}
+
+impl Interactive for FormStimulus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if !self.visible || self.submitted.is_some() {
+            return false;
+        }
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        let time = event.timestamp().timestamp;
+
+        if let Some((pointer, position)) = layout.viewport.pointer(event) {
+            return self.pointer(pointer, position, time);
+        }
+        match event {
+            Event::MouseWheel { vertical, .. } => {
+                let scroll = self.scroll - vertical * WHEEL_STEP * layout.font_size;
+                self.scroll_to(scroll);
+                true
+            }
+            Event::KeyPress { key, .. } => self.key(key, time),
+            Event::Text { text, .. } => self.text(text, time),
+            Event::ImePreedit { text, .. } => match self.focus {
+                Some(i) => {
+                    self.fields[i].preedit = text.clone();
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Show `form` on `window` until it is submitted with valid answers. Returns when it was submitted.
+pub fn run(form: &DynamicStimulus, window: &Window) -> PsydkResult<Instant> {
+    let ime_allowed = window.ime_allowed()?;
+    window.set_ime_allowed(true)?;
+
+    let mut receiver = window.create_event_receiver();
+    let mut frame = window.get_frame()?;
+    frame.add(form);
+    let submitted = loop {
+        window.present(&mut frame, None, None, false, None)?;
+        let mut form = form.lock();
+        let form = form.downcast_mut::<FormStimulus>().expect("the stimulus is a form");
+        for event in receiver.poll().iter() {
+            form.handle_event(event);
+        }
+        if let Some(submitted) = form.submitted() {
+            break submitted;
+        }
+    };
+
+    window.set_ime_allowed(ime_allowed)?;
+    Ok(submitted)
+}
+
+impl Stimulus for FormStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let bounds = self.bounds(window_state);
+        let font_size = self
+            .params
+            .font_size
+            .eval(window_state.size, window_state.physical_screen);
+        let (text_color, accent_color) = (self.params.text_color, self.params.accent_color);
+        let background_color = self.params.background_color;
+        let muted_color = faded(text_color, 0.5);
+
+        let padding = font_size;
+        let row_height = 1.6 * font_size;
+        let mark_size = font_size;
+        let left = bounds.x + padding;
+        let content_width = (bounds.width - 2.0 * padding).max(0.0);
+        let field_width = content_width.min(20.0 * font_size);
+        let scroll = self.scroll;
+        // moves rectangles of the content to where they are drawn
+        let shown = |rect: Rect| Rect::new(rect.x, rect.y - scroll, rect.width, rect.height);
+
+        fill(scene, bounds.shape(), background_color);
+        scene.start_layer(BlendMode::SourceOver, bounds.shape(), None, None, 1.0);
+
+        let Self {
+            items,
+            fields,
+            label,
+            focus,
+            open,
+            submit_label,
+            ..
+        } = self;
+        let text = |label: &mut Label, scene: &mut DynamicScene, text: &str, (x, y): (f32, f32), color: LinRgba| {
+            label.draw(scene, window_state, text, (x, y - scroll), font_size, color)
+        };
+
+        let mut targets = Vec::new();
+        let mut item_tops = Vec::new();
+        let mut popup = None;
+        let mut y = bounds.y + padding;
+        for (i, (item, field)) in items.iter().zip(fields.iter()).enumerate() {
+            item_tops.push(y);
+            let question = match item.required {
+                true => format!("{} *", item.label),
+                false => item.label.clone(),
+            };
+            y += text(label, scene, &question, (left, y), text_color).1 + 0.5 * font_size;
+
+            match &item.response {
+                ResponseType::Text { .. } | ResponseType::Number { .. } => {
+                    let rect = Rect::new(left, y, field_width, row_height);
+                    let focused = *focus == Some(i);
+                    let content = match focused {
+                        true => field.text.with_caret(&format!("{}|", field.preedit)),
+                        false => field.text.text.clone(),
+                    };
+                    stroke(
+                        scene,
+                        shown(rect).rounded(0.2 * font_size),
+                        if focused { accent_color } else { muted_color },
+                        if focused { 2.0 } else { 1.0 },
+                    );
+                    text(
+                        label,
+                        scene,
+                        &content,
+                        (left + 0.4 * font_size, y + 0.3 * font_size),
+                        text_color,
+                    );
+                    targets.push((rect, Target::Field(i)));
+                    y += row_height;
+                }
+                ResponseType::Radio { options, horizontal } | ResponseType::Checkbox { options, horizontal } => {
+                    let radio = matches!(item.response, ResponseType::Radio { .. });
+                    let mut x = left;
+                    for (j, option) in options.iter().enumerate() {
+                        let width = 1.5 * mark_size + label.measure(option, font_size, window_state).0;
+                        if *horizontal && x > left && x + width > left + content_width {
+                            x = left;
+                            y += row_height;
+                        }
+                        let mark = Rect::new(x, y + (row_height - mark_size) / 2.0, mark_size, mark_size);
+                        let shape = |rect: Rect| match radio {
+                            true => Shape::circle(rect.center(), (rect.width / 2.0) as f64),
+                            false => rect.rounded(0.15 * rect.width),
+                        };
+                        stroke(scene, shape(shown(mark)), muted_color, 1.5);
+                        if field.selected[j] {
+                            let inset = 0.25 * mark_size;
+                            let dot = Rect::new(
+                                mark.x + inset,
+                                mark.y + inset,
+                                mark_size - 2.0 * inset,
+                                mark_size - 2.0 * inset,
+                            );
+                            fill(scene, shape(shown(dot)), accent_color);
+                        }
+                        text(
+                            label,
+                            scene,
+                            option,
+                            (x + 1.5 * mark_size, y + (row_height - font_size) / 2.0),
+                            text_color,
+                        );
+                        targets.push((Rect::new(x, y, width, row_height), Target::Option(i, j)));
+                        match horizontal {
+                            true => x += width + 1.5 * font_size,
+                            false => y += row_height,
+                        }
+                    }
+                    if *horizontal {
+                        y += row_height;
+                    }
+                }
+                ResponseType::Dropdown { options } => {
+                    let rect = Rect::new(left, y, field_width, row_height);
+                    let selected = field.selected.iter().position(|selected| *selected);
+                    stroke(
+                        scene,
+                        shown(rect).rounded(0.2 * font_size),
+                        if *open == Some(i) { accent_color } else { muted_color },
+                        1.0,
+                    );
+                    let (value, color) = match selected {
+                        Some(j) => (options[j].as_str(), text_color),
+                        None => (DROPDOWN_PLACEHOLDER, muted_color),
+                    };
+                    text(
+                        label,
+                        scene,
+                        value,
+                        (left + 0.4 * font_size, y + 0.3 * font_size),
+                        color,
+                    );
+                    // a small triangle pointing down at the right
+                    let (cx, cy) = (rect.x + rect.width - font_size, rect.y + rect.height / 2.0 - scroll);
+                    let half = 0.3 * font_size;
+                    fill(
+                        scene,
+                        Shape::polygon(vec![
+                            (cx - half, cy - half / 2.0),
+                            (cx + half, cy - half / 2.0),
+                            (cx, cy + half / 2.0),
+                        ]),
+                        muted_color,
+                    );
+                    targets.push((rect, Target::Dropdown(i)));
+                    if *open == Some(i) {
+                        popup = Some((rect, options.clone(), selected));
+                    }
+                    y += row_height;
+                }
+            }
+
+            if let Some(error) = &field.error {
+                y += 0.25 * font_size;
+                y += text(label, scene, error, (left, y), ERROR_COLOR).1;
+            }
+            y += 1.5 * font_size;
+        }
+
+        // the submit button
+        let (label_width, label_height) = label.measure(submit_label, font_size, window_state);
+        let button = Rect::new(left, y, label_width + 2.0 * font_size, label_height + font_size);
+        fill(scene, shown(button).rounded(0.2 * font_size), accent_color);
+        label.draw(
+            scene,
+            window_state,
+            submit_label,
+            (button.x + font_size, button.y + 0.5 * font_size - scroll),
+            font_size,
+            background_color,
+        );
+        targets.push((button, Target::Submit));
+        y += button.height + padding;
+
+        scene.end_layer();
+
+        let max_scroll = (y - bounds.y - bounds.height).max(0.0);
+        if max_scroll > 0.0 {
+            let content_height = y - bounds.y;
+            let thumb_height = bounds.height * bounds.height / content_height;
+            let thumb = Rect::new(
+                bounds.x + bounds.width - 0.4 * font_size,
+                bounds.y + (bounds.height - thumb_height) * scroll.min(max_scroll) / max_scroll,
+                0.3 * font_size,
+                thumb_height,
+            );
+            fill(scene, thumb.rounded(0.15 * font_size), muted_color);
+        }
+
+        // the open dropdown list is drawn on top and may extend beyond the form
+        let mut popup_targets = Vec::new();
+        if let Some((rect, options, selected)) = popup {
+            let list = Rect::new(
+                rect.x,
+                rect.y + rect.height,
+                rect.width,
+                row_height * options.len() as f32,
+            );
+            fill(scene, shown(list).shape(), background_color);
+            stroke(scene, shown(list).shape(), accent_color, 1.0);
+            for (j, option) in options.iter().enumerate() {
+                let row = Rect::new(list.x, list.y + j as f32 * row_height, list.width, row_height);
+                if selected == Some(j) {
+                    fill(scene, shown(row).shape(), faded(accent_color, 0.3));
+                }
+                label.draw(
+                    scene,
+                    window_state,
+                    option,
+                    (row.x + 0.4 * font_size, row.y + 0.3 * font_size - scroll),
+                    font_size,
+                    text_color,
+                );
+                popup_targets.push((row, j));
+            }
+        }
+
+        self.layout = Some(FormLayout {
+            viewport: Viewport::new(window_state),
+            bounds,
+            font_size,
+            targets,
+            popup: popup_targets,
+            item_tops,
+            max_scroll,
+        });
+        self.scroll = self.scroll.clamp(0.0, max_scroll);
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        let window_state = window.state.lock().unwrap();
+        let window_state = window_state.as_ref().unwrap();
+        let x = x.eval(window_state.size, window_state.physical_screen);
+        let y = y.eval(window_state.size, window_state.physical_screen);
+        let point = window_state.coordinate_system.to_scene(x, y, window_state.size);
+        self.bounds(window_state).contains(point)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let bounds = self.bounds(window_state);
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let anchor = window_state
+            .coordinate_system
+            .to_scene(eval(&self.params.x), eval(&self.params.y), window_size);
+        let identity = nalgebra::Matrix3::<f32>::identity();
+        vec![helpers::rect_layout(
+            (bounds.x, bounds.y, bounds.width, bounds.height),
+            anchor,
+            &identity,
+            &identity,
+        )]
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for FormItem {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let item = ob
+            .downcast::<PyDict>()
+            .map_err(|_| PsydkError::ParameterError("Items of a form must be dictionaries".into()))?;
+        let get = |key: &str| item.get_item(key).map(|value| value.filter(|value| !value.is_none()));
+
+        let name: String = get("name")?
+            .ok_or_else(|| PsydkError::ParameterError("Every item of a form needs a \"name\"".into()))?
+            .extract()?;
+        let label = match get("label")? {
+            Some(label) => label.extract()?,
+            None => name.clone(),
+        };
+        let options =
+            || -> PyResult<Vec<String>> { get("options")?.map_or(Ok(Vec::new()), |options| options.extract()) };
+        let horizontal = get("horizontal")?.map_or(Ok(false), |horizontal| horizontal.extract())?;
+        let kind: String = get("type")?.map_or(Ok("text".to_string()), |kind| kind.extract())?;
+
+        let response = match kind.as_str() {
+            "text" => ResponseType::Text {
+                max_length: get("max_length")?.map(|value| value.extract()).transpose()?,
+            },
+            "number" => ResponseType::Number {
+                min: get("min")?.map(|value| value.extract()).transpose()?,
+                max: get("max")?.map(|value| value.extract()).transpose()?,
+            },
+            "radio" => ResponseType::Radio {
+                options: options()?,
+                horizontal,
+            },
+            "checkbox" => ResponseType::Checkbox {
+                options: options()?,
+                horizontal,
+            },
+            "dropdown" => ResponseType::Dropdown { options: options()? },
+            other => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Unknown type \"{other}\" of the item \"{name}\", must be \"text\", \"number\", \"radio\", \"checkbox\", or \"dropdown\""
+                ))
+                .into())
+            }
+        };
+
+        Ok(Self {
+            name,
+            label,
+            response,
+            required: get("required")?.map_or(Ok(false), |required| required.extract())?,
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+fn answer_to_py(py: Python<'_>, answer: Option<Answer>) -> PyResult<PyObject> {
+    Ok(match answer {
+        Some(Answer::Text(text)) | Some(Answer::Choice(text)) => text.into_pyobject(py)?.into_any().unbind(),
+        Some(Answer::Number(number)) => number.into_pyobject(py)?.into_any().unbind(),
+        Some(Answer::Choices(choices)) => PyList::new(py, choices)?.into_any().unbind(),
+        None => py.None(),
+    })
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "FormStimulus", extends=PyStimulus)]
+/// A questionnaire, e.g. for demographics or rating scales such as the PANAS.
+///
+/// The items are laid out one below the other in a box, followed by a submit button. The box
+/// scrolls (with the mouse wheel, by dragging, or with the arrow and page keys) when the items
+/// don't fit. Clicking or tapping a text or number field focuses it for typing; Tab and Enter move
+/// on to the next field. Submitting checks the answers: required items must be answered (for
+/// checkboxes, at least one option must be selected) and numbers must be valid and within their
+/// bounds. Items that are not are marked with a message, and the form scrolls to the first of
+/// them.
+///
+/// Either show the form with `run`, which returns the answers once the form has been submitted,
+/// or add it to your own frames and pass it the input events with `handle_event` or `attach`.
+///
+/// Parameters
+/// ----------
+/// items : list[dict]
+///   The items. Each item is a dictionary with a unique "name" (the key of the answer), the
+///   "label" (the question, defaults to the name), the "type" ("text" (default), "number",
+///   "radio", "checkbox", or "dropdown"), and whether it is "required" (defaults to False). Radio
+///   buttons, checkboxes, and dropdown lists need "options" (a list of strings); radio buttons and
+///   checkboxes are shown in a row if "horizontal" is True. Text items can have a "max_length",
+///   and number items a "min" and "max".
+/// x : Size, optional
+///   The x position of the form.
+/// y : Size, optional
+///   The y position of the form.
+/// width : Size, optional
+///   The width of the form.
+/// height : Size, optional
+///   The height of the form. Content that does not fit is scrolled.
+/// anchor : str, optional
+///   The point of the form that the position refers to. Defaults to "center".
+/// font_size : Size, optional
+///   The font size. All other parts of the form are sized relative to it.
+/// text_color : Color, optional
+///   The color of the text and the outlines. Defaults to black.
+/// accent_color : Color, optional
+///   The color of selections, the focused field, and the submit button.
+/// background_color : Color, optional
+///   The color of the box and of the text on the submit button. Defaults to white.
+/// submit_label : str, optional
+///   The text on the submit button.
+/// context : ExperimentContext, optional
+///   The experiment context. Defaults to the context of the experiment function.
+pub struct PyFormStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyFormStimulus {
+    #[new]
+    #[pyo3(signature = (
+        items,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        width = IntoSize(Size::Pixels(800.0)),
+        height = IntoSize(Size::Pixels(600.0)),
+        anchor = Anchor::Center,
+        font_size = IntoSize(Size::Pixels(24.0)),
+        text_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        accent_color = IntoLinRgba::new(0.05, 0.25, 0.8, 1.0),
+        background_color = IntoLinRgba::new(1.0, 1.0, 1.0, 1.0),
+        submit_label = "Continue",
+        context = None,
+    ))]
+    fn __new__(
+        py: Python,
+        items: Vec<FormItem>,
+        x: IntoSize,
+        y: IntoSize,
+        width: IntoSize,
+        height: IntoSize,
+        anchor: Anchor,
+        font_size: IntoSize,
+        text_color: IntoLinRgba,
+        accent_color: IntoLinRgba,
+        background_color: IntoLinRgba,
+        submit_label: &str,
+        context: Option<ExperimentContext>,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let context = helpers::get_experiment_context(context, py)?;
+        let params = FormParams {
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
+            font_size: font_size.into(),
+            text_color: text_color.into(),
+            accent_color: accent_color.into(),
+            background_color: background_color.into(),
+        };
+        let form = FormStimulus::new(items, submit_label, anchor, params, &context)?;
+        Ok((Self(), PyStimulus::new(form)))
+    }
+
+    /// Show the form until it is submitted with valid answers.
+    ///
+    /// The form is shown on its own, and input methods are allowed while it is shown. Don't
+    /// attach the form to the window at the same time, as events would be handled twice.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window to show the form on.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   The answers (see `answers`).
+    #[pyo3(name = "run")]
+    fn py_run<'py>(slf: PyRef<'py, Self>, py: Python<'py>, window: Window) -> PyResult<Bound<'py, PyDict>> {
+        let form = SendWrapper::new(slf.as_super().0.clone());
+        let window_wrapper = SendWrapper::new(window.clone());
+        py.allow_threads(move || run(&form, &window_wrapper))?;
+        window.call_watchdog_callback(py)?;
+        Self::py_answers(slf, py)
+    }
+
+    /// The answers, by item name and in the order of the items: a string for text items, radio
+    /// buttons, and dropdown lists, a float for number items, and a list of the selected options
+    /// for checkboxes. Unanswered items (and numbers that are not valid) are None.
+    #[getter(answers)]
+    fn py_answers<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let answers = downcast_stimulus!(slf, FormStimulus).answers();
+        let dict = PyDict::new(py);
+        for (name, answer) in answers {
+            dict.set_item(name, answer_to_py(py, answer)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// When each answer was last changed, by item name, or None for items that have not been
+    /// answered.
+    #[getter(answer_times)]
+    fn py_answer_times<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (name, time) in downcast_stimulus!(slf, FormStimulus).answer_times() {
+            dict.set_item(name, time.map(Timestamp::from))?;
+        }
+        Ok(dict)
+    }
+
+    /// When the form was submitted with valid answers, or None if it has not been.
+    #[getter(submitted)]
+    fn py_submitted(slf: PyRef<'_, Self>) -> Option<Timestamp> {
+        downcast_stimulus!(slf, FormStimulus).submitted().map(Timestamp::from)
+    }
+
+    /// Check the answers and mark the items that are required but not answered, or not valid.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   Whether all answers are valid.
+    #[pyo3(name = "validate")]
+    fn py_validate(slf: PyRefMut<'_, Self>) -> bool {
+        downcast_py_stimulus_mut!(slf, FormStimulus).validate()
+    }
+
+    /// Pass an input event to the form, e.g. from an event receiver, if the form is shown in your
+    /// own frames.
+    ///
+    /// Parameters
+    /// ----------
+    /// event : Event
+    ///   The event.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   Whether the form used the event.
+    #[pyo3(name = "handle_event")]
+    fn py_handle_event(slf: PyRefMut<'_, Self>, event: Event) -> bool {
+        downcast_py_stimulus_mut!(slf, FormStimulus).handle_event(&event)
+    }
+
+    /// Pass the input events of a window to the form as they arrive, until `detach` is called.
+    /// Input methods are allowed for the window.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window that the form is shown on.
+    #[pyo3(name = "attach")]
+    fn py_attach(slf: PyRef<'_, Self>, window: Window) -> PyResult<()> {
+        Ok(FormStimulus::attach(&slf.as_super().0, &window)?)
+    }
+
+    /// Stop passing the input events of the window to the form.
+    #[pyo3(name = "detach")]
+    fn py_detach(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, FormStimulus).detach();
+    }
+
+    /// Discard all answers, e.g. to show the form again.
+    #[pyo3(name = "reset")]
+    fn py_reset(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, FormStimulus).reset();
+    }
+
+    fn __len__(slf: PyRef<'_, Self>) -> usize {
+        downcast_stimulus!(slf, FormStimulus).items().len()
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyFormStimulus, FormStimulus);
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use psydk_proc::{FromPyStr, StimulusParams};
+#[cfg(feature = "python")]
 use pyo3::{pyclass, pymethods};
 use renderer::{
     affine::Affine,
@@ -13,9 +14,11 @@ use renderer::{
 use strum::EnumString;
 use uuid::Uuid;
 
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
-    animations::Animation, impl_pystimulus_for_wrapper, PyStimulus, Stimulus, StimulusParamValue, StimulusParams,
-    StrokeStyle,
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, Stimulus, StimulusLayout, StimulusParamValue,
+    StimulusParams, StrokeStyle,
 };
 use crate::visual::{
     color::LinRgba,
@@ -147,6 +150,7 @@ impl GaborStimulus {
 }
 
 #[derive(Debug, Clone)]
+#[cfg(feature = "python")]
 #[pyclass(name = "GaborStimulus", extends=PyStimulus, module = "psydk.visual.stimuli")]
 /// A 1D grating multiplied with a Gaussian envelope.
 ///
@@ -182,6 +186,7 @@ impl GaborStimulus {
 ///   The alpha value of the stimulus.
 pub struct PyGaborStimulus();
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyGaborStimulus {
     #[new]
@@ -259,13 +264,20 @@ impl Stimulus for GaborStimulus {
         let radius = self.params.radius.eval(window_size, screen_props) as f64;
         let sigma = self.params.sigma.eval(window_size, screen_props);
         let cycle_length = self.params.cycle_length.eval(window_size, screen_props) as f64;
-        let pos_x = self.params.cx.eval(window_size, screen_props) as f64;
-        let pos_y = self.params.cy.eval(window_size, screen_props) as f64;
 
-        // apply the anchor
-        let bb_width = radius * 2.0;
-        let bb_height = radius * 2.0;
-        let (pos_x, pos_y) = self.anchor.to_center(pos_x, pos_y, bb_width, bb_height);
+        // apply the anchor to the bounding box of the patch
+        let (left, top) = helpers::anchored_top_left(
+            self.anchor,
+            (
+                self.params.cx.eval(window_size, screen_props),
+                self.params.cy.eval(window_size, screen_props),
+            ),
+            radius as f32 * 2.0,
+            radius as f32 * 2.0,
+            window_size,
+            window_state.coordinate_system,
+        );
+        let (pos_x, pos_y) = (left as f64 + radius, top as f64 + radius);
 
         let trans_mat = self.transformation.eval(window_size, screen_props);
 
@@ -390,6 +402,41 @@ impl Stimulus for GaborStimulus {
         false
     }
 
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let radius = self.params.radius.eval(window_size, screen_props);
+        let (cx, cy) = (
+            self.params.cx.eval(window_size, screen_props),
+            self.params.cy.eval(window_size, screen_props),
+        );
+        let (left, top) = helpers::anchored_top_left(
+            self.anchor,
+            (cx, cy),
+            radius * 2.0,
+            radius * 2.0,
+            window_size,
+            window_state.coordinate_system,
+        );
+
+        let transform = self.transformation.eval(window_size, screen_props);
+        let anchor = window_state.coordinate_system.to_scene(cx, cy, window_size);
+        vec![StimulusLayout {
+            bounds: helpers::transform_rect(&transform, (left, top, radius * 2.0, radius * 2.0)),
+            anchor: Some(helpers::transform_point(&transform, anchor)),
+            hit_region: None,
+        }]
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
     fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
         self.params.get_param(name)
     }
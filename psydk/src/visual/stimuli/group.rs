@@ -0,0 +1,252 @@
+use std::time::Instant;
+
+use psydk_proc::StimulusParams;
+use renderer::{styles::BlendMode, DynamicScene};
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, DynamicStimulus, Stimulus, StimulusLayout,
+    StimulusParamValue, StimulusParams,
+};
+use crate::visual::{
+    geometry::{Size, Transformation2D},
+    window::WindowState,
+};
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct StimulusGroupParams {
+    pub alpha: f64,
+}
+
+/// A group of stimuli that are drawn together and share a transformation, visibility, and opacity.
+#[derive(Debug)]
+pub struct StimulusGroup {
+    id: Uuid,
+    params: StimulusGroupParams,
+    children: Vec<DynamicStimulus>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl StimulusGroup {
+    pub fn new(children: Vec<DynamicStimulus>, alpha: f64, transformation: Transformation2D) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            params: StimulusGroupParams { alpha },
+            children,
+            transformation,
+            animations: Vec::new(),
+            visible: true,
+        }
+    }
+
+    /// Add a child to the group. The child is drawn after (i.e., on top of) the existing children.
+    pub fn add(&mut self, child: DynamicStimulus) {
+        self.children.push(child);
+    }
+
+    /// Remove a child from the group. Returns false if the stimulus is not part of the group.
+    pub fn remove(&mut self, child: &DynamicStimulus) -> bool {
+        let len = self.children.len();
+        self.children.retain(|c| !c.ptr_eq(child));
+        self.children.len() != len
+    }
+
+    /// Run `f` on a child with the group's transformation applied on top of the child's own
+    /// transformation.
+    fn with_group_transformation<R>(&self, child: &DynamicStimulus, f: impl FnOnce(&DynamicStimulus) -> R) -> R {
+        let own_transformation = child.lock().transformation();
+        child
+            .lock()
+            .set_transformation(self.transformation.clone() * own_transformation.clone());
+        let result = f(child);
+        child.lock().set_transformation(own_transformation);
+        result
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "StimulusGroup", extends=PyStimulus)]
+/// A group of stimuli that can be moved, rotated, hidden, and faded as one unit.
+///
+/// Children are drawn in the order they were added. A child should not be added to a frame
+/// separately, as it would then be drawn twice.
+///
+/// Parameters
+/// ----------
+/// children : list[Stimulus], optional
+///     The stimuli in the group.
+/// alpha : float, optional
+///     The opacity of the group, applied on top of the children's own opacity.
+/// transform : Transformation2D, optional
+///     The transformation of the group, applied on top of the children's own transformations.
+pub struct PyStimulusGroup();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyStimulusGroup {
+    #[new]
+    #[pyo3(signature = (
+        children = Vec::new(),
+        alpha = 1.0,
+        transform = Transformation2D::Identity(),
+    ))]
+    fn __new__(children: Vec<PyStimulus>, alpha: f64, transform: Transformation2D) -> (Self, PyStimulus) {
+        let children = children.iter().map(|c| c.as_super().clone()).collect();
+        (Self(), PyStimulus::new(StimulusGroup::new(children, alpha, transform)))
+    }
+
+    /// Add a stimulus to the group.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimulus : Stimulus
+    ///     The stimulus to add. It is drawn on top of the existing children.
+    #[pyo3(name = "add")]
+    fn py_add(slf: PyRefMut<'_, Self>, stimulus: PyStimulus) -> PyResult<()> {
+        let child = stimulus.as_super();
+        // the group's lock is held while drawing the children, so a group cannot contain itself,
+        // not even through one of its children
+        if child.is_or_contains(&slf.as_super().0) {
+            return Err(PyValueError::new_err("a stimulus group cannot contain itself"));
+        }
+        downcast_py_stimulus_mut!(slf, StimulusGroup).add(child.clone());
+        Ok(())
+    }
+
+    /// Remove a stimulus from the group.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimulus : Stimulus
+    ///     The stimulus to remove.
+    #[pyo3(name = "remove")]
+    fn py_remove(slf: PyRefMut<'_, Self>, stimulus: PyStimulus) -> PyResult<()> {
+        if !downcast_py_stimulus_mut!(slf, StimulusGroup).remove(stimulus.as_super()) {
+            return Err(PyValueError::new_err("the stimulus is not part of the group"));
+        }
+        Ok(())
+    }
+
+    /// The stimuli in the group.
+    #[getter(children)]
+    fn py_children(slf: PyRef<'_, Self>) -> Vec<PyStimulus> {
+        downcast_stimulus!(slf, StimulusGroup)
+            .children()
+            .iter()
+            .map(|c| PyStimulus(c.clone()))
+            .collect()
+    }
+
+    fn __len__(slf: PyRef<'_, Self>) -> usize {
+        downcast_stimulus!(slf, StimulusGroup).children().len()
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyStimulusGroup, StimulusGroup);
+
+impl Stimulus for StimulusGroup {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        // the opacity is applied to the group as a whole, so that overlapping children do not show
+        // through each other
+        scene.start_layer(
+            BlendMode::SourceOver,
+            helpers::window_clip(window_state),
+            None,
+            None,
+            self.params.alpha as f32,
+        );
+        for child in &self.children {
+            self.with_group_transformation(child, |child| child.draw(scene, window_state));
+        }
+        scene.end_layer();
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        self.children
+            .iter()
+            .any(|child| self.with_group_transformation(child, |child| child.contains(x.clone(), y.clone(), window)))
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        if !self.visible {
+            return Vec::new();
+        }
+        self.children
+            .iter()
+            .filter(|child| child.lock().visible())
+            .flat_map(|child| self.with_group_transformation(child, |child| child.lock().layout(window_state)))
+            .collect()
+    }
+
+    fn update_animations(&mut self, time: Instant, window_state: &WindowState) {
+        let mut params_to_set = Vec::new();
+        self.animations.retain_mut(|animation| {
+            params_to_set.push((animation.parameter().to_string(), animation.value(time, window_state)));
+            !animation.finished(time)
+        });
+        for (param, value) in params_to_set {
+            self.set_param(&param, value);
+        }
+
+        for child in &self.children {
+            child.update_animations(time, window_state);
+        }
+    }
+
+    fn children(&self) -> &[DynamicStimulus] {
+        &self.children
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
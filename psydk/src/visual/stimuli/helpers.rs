@@ -1,23 +1,174 @@
 use std::{borrow::Cow, sync::Arc};
 
 use psydk_proc::StimulusParams;
+#[cfg(feature = "python")]
 use pyo3::{exceptions::PyValueError, ffi::c_str, prelude::*};
 use renderer::{
     affine::Affine,
     brushes::{Brush, Gradient},
     colors::RGBA,
+    shapes::Shape,
 };
+use rustfft::{num_complex::Complex, FftDirection, FftPlanner};
 use uuid::Uuid;
 
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
-    animations::Animation, impl_pystimulus_for_wrapper, pattern::FillPattern, LinRgba, PyStimulus, Stimulus,
+    animations::Animation, impl_pystimulus_for_wrapper, pattern::FillPattern, LinRgba, Stimulus, StimulusLayout,
     StimulusParamValue, StimulusParams, StrokeStyle,
 };
 use crate::{
     context::{ExperimentContext, PyRendererFactory},
-    visual::{geometry::Size, window::Window},
+    visual::{
+        geometry::{self, Anchor, CoordinateSystem, Size},
+        window::{PixelSize, Window, WindowState},
+    },
 };
 
+/// The top left corner (in the coordinates of the scene) of a `width` x `height` box whose `anchor`
+/// lies at `position` (in pixels, in the coordinate system of the window). All stimuli that support
+/// an anchor use this, so that alignment is the same regardless of the stimulus type.
+pub(crate) fn anchored_top_left(
+    anchor: Anchor,
+    position: (f32, f32),
+    width: f32,
+    height: f32,
+    window_size: PixelSize,
+    coordinate_system: CoordinateSystem,
+) -> (f32, f32) {
+    let (x, y) = coordinate_system.to_scene(position.0, position.1, window_size);
+    anchor.to_top_left(x, y, width, height)
+}
+
+/// Apply a (homogeneous) transformation matrix to a point.
+pub(crate) fn transform_point(matrix: &nalgebra::Matrix3<f32>, (x, y): (f32, f32)) -> (f32, f32) {
+    let p = matrix * nalgebra::Vector3::new(x, y, 1.0);
+    (p.x, p.y)
+}
+
+/// The corners of the rectangle `(x, y, width, height)` after applying `matrix`.
+pub(crate) fn transform_rect(
+    matrix: &nalgebra::Matrix3<f32>,
+    (x, y, width, height): (f32, f32, f32, f32),
+) -> Vec<(f32, f32)> {
+    [(x, y), (x + width, y), (x + width, y + height), (x, y + height)]
+        .into_iter()
+        .map(|corner| transform_point(matrix, corner))
+        .collect()
+}
+
+/// The layout of a rectangular stimulus (e.g. an image) that is drawn at `rect` (in the coordinates
+/// of the scene) with `transform` applied. Its `contains` is true where the point, transformed by
+/// `hit_transform`, lies inside the untransformed rectangle.
+pub(crate) fn rect_layout(
+    rect: (f32, f32, f32, f32),
+    anchor: (f32, f32),
+    transform: &nalgebra::Matrix3<f32>,
+    hit_transform: &nalgebra::Matrix3<f32>,
+) -> StimulusLayout {
+    // points in the hit region are those that `hit_transform` maps into the rectangle
+    let hit_region = hit_transform
+        .try_inverse()
+        .map(|inverse| transform_rect(&inverse, rect));
+    StimulusLayout {
+        bounds: transform_rect(transform, rect),
+        anchor: Some(transform_point(transform, anchor)),
+        hit_region,
+    }
+}
+
+/// Place a shape at `position` (in pixels, in the coordinate system of the window). Without an
+/// anchor, the coordinates of the shape are relative to `position`. Otherwise, the shape is moved so
+/// that the anchor of its bounding box lies at `position`. Returns a function that converts a point
+/// of the shape (in pixels) to the coordinates of the scene.
+pub(crate) fn place_shape(
+    shape: &geometry::Shape,
+    position: (f32, f32),
+    anchor: Option<Anchor>,
+    window_state: &WindowState,
+) -> impl Fn(f32, f32) -> (f64, f64) {
+    let window_size = window_state.size;
+    let coordinate_system = window_state.coordinate_system;
+
+    let (offset, shift) = match anchor {
+        None => (position, (0.0, 0.0)),
+        Some(anchor) => {
+            let (bx, by, bw, bh) = shape.bounds(window_size, window_state.physical_screen);
+            let (bx, by, bw, bh) = coordinate_system.rect_to_scene(bx, by, bw, bh, window_size);
+            let (left, top) = anchored_top_left(anchor, position, bw, bh, window_size, coordinate_system);
+            ((0.0, 0.0), (left - bx, top - by))
+        }
+    };
+
+    move |x, y| {
+        let (x, y) = coordinate_system.to_scene(x + offset.0, y + offset.1, window_size);
+        ((x + shift.0) as f64, (y + shift.1) as f64)
+    }
+}
+
+/// Convert a shape to a shape of the renderer, using `place` (see `place_shape`) to convert its
+/// points to the coordinates of the scene.
+pub(crate) fn scene_shape(
+    shape: &geometry::Shape,
+    window_state: &WindowState,
+    place: impl Fn(f32, f32) -> (f64, f64),
+) -> Shape {
+    let eval = |size: &Size| size.eval(window_state.size, window_state.physical_screen);
+    let place_all = |points: &[(Size, Size)]| points.iter().map(|(x, y)| place(eval(x), eval(y))).collect::<Vec<_>>();
+
+    match shape {
+        geometry::Shape::Rectangle { x, y, width, height } => {
+            let (x, y) = (eval(x), eval(y));
+            let (x0, y0) = place(x, y);
+            let (x1, y1) = place(x + eval(width), y + eval(height));
+            Shape::rectangle((x0.min(x1), y0.min(y1)), (x1 - x0).abs(), (y1 - y0).abs())
+        }
+        geometry::Shape::Circle { x, y, radius } => Shape::circle(place(eval(x), eval(y)), eval(radius) as f64),
+        geometry::Shape::Ellipse {
+            x,
+            y,
+            radius_x,
+            radius_y,
+        } => Shape::ellipse(
+            place(eval(x), eval(y)),
+            eval(radius_x) as f64,
+            eval(radius_y) as f64,
+            0.0,
+        ),
+        geometry::Shape::Line { x1, y1, x2, y2 } => Shape::line(place(eval(x1), eval(y1)), place(eval(x2), eval(y2))),
+        geometry::Shape::Polygon { points } => Shape::polygon(place_all(points)),
+        geometry::Shape::Path { points } => Shape::path(place_all(points)),
+    }
+}
+
+/// In-place 2D FFT of a row-major `width` x `height` grid: the rows are transformed first, then the
+/// columns. Like rustfft, the result is not normalized.
+pub(crate) fn fft_2d(data: &mut [Complex<f64>], width: usize, height: usize, direction: FftDirection) {
+    let mut planner = FftPlanner::new();
+    let row_fft = planner.plan_fft(width, direction);
+    data.chunks_exact_mut(width).for_each(|row| row_fft.process(row));
+
+    let column_fft = planner.plan_fft(height, direction);
+    let mut column = vec![Complex::new(0.0, 0.0); height];
+    for col in 0..width {
+        for row in 0..height {
+            column[row] = data[row * width + col];
+        }
+        column_fft.process(&mut column);
+        for row in 0..height {
+            data[row * width + col] = column[row];
+        }
+    }
+}
+
+/// A rectangle that covers the whole window, used as the clip of layers that apply to entire stimuli.
+pub(crate) fn window_clip(window_state: &WindowState) -> Shape {
+    let width = window_state.size.width as f64;
+    let height = window_state.size.height as f64;
+    Shape::rectangle((-width, -height), 2.0 * width, 2.0 * height)
+}
+
 pub(crate) fn create_fill_brush_uniform<'a>(fill_color: &LinRgba) -> Brush<'a> {
     Brush::Solid((*fill_color).into())
 }
@@ -88,6 +239,7 @@ pub(crate) fn create_fill_brush2<'a>(
 //     Ok(renderer_factory)
 // }
 
+#[cfg(feature = "python")]
 pub(crate) fn get_experiment_context(em: Option<ExperimentContext>, py: Python) -> PyResult<ExperimentContext> {
     // if we already have an experiment context, return it
     if let Some(em) = em {
@@ -3,23 +3,32 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use psydk_proc::StimulusParams;
+use psydk_proc::{FromPyStr, StimulusParams};
+#[cfg(feature = "python")]
 use pyo3::ffi::c_str;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use renderer::{
     brushes::{Brush, Extend, ImageSampling},
+    image::{imageops, RgbaImage},
+    renderer::ColorSpace,
     shapes::Shape,
     styles::ImageFitMode,
     DynamicBitmap, DynamicScene,
 };
+use rustfft::{num_complex::Complex, FftDirection};
+use strum::EnumString;
 use uuid::Uuid;
 
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
     animations::Animation,
     helpers::{self, get_experiment_context},
-    impl_pystimulus_for_wrapper, PyStimulus, Stimulus, StimulusParamValue, StimulusParams,
+    impl_pystimulus_for_wrapper, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
 };
 use crate::{
     context::{ExperimentContext, PyRendererFactory},
+    errors::{PsydkError, PsydkResult},
     visual::{
         geometry::{Anchor, Size, Transformation2D},
         window::{Frame, WindowState},
@@ -87,10 +96,216 @@ impl ImageStimulus {
     }
 }
 
+/// How an image is scrambled when it is loaded.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ScrambleMethod {
+    /// Randomize the Fourier phase while keeping the amplitude spectrum.
+    Phase,
+    /// Shuffle square blocks of the image.
+    Block,
+}
+
+fn scramble_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Randomizes the Fourier phase of an image while keeping its amplitude spectrum (and therefore its
+/// mean luminance and contrast energy at every spatial frequency).
+///
+/// The same random phase is added to every color channel, so that colors are not decorrelated. The
+/// random phase is taken from the spectrum of white noise, which makes it symmetric and keeps the
+/// result real-valued. An `amount` of 0.0 returns the original image and 1.0 fully scrambles it. The
+/// alpha channel is left untouched.
+pub fn phase_scramble(image: &RgbaImage, amount: f64, seed: Option<u64>) -> RgbaImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut scrambled = image.clone();
+    if width == 0 || height == 0 {
+        return scrambled;
+    }
+
+    let mut rng = scramble_rng(seed);
+    let mut noise = (0..width * height)
+        .map(|_| Complex::new(rng.gen::<f64>(), 0.0))
+        .collect::<Vec<_>>();
+    helpers::fft_2d(&mut noise, width, height, FftDirection::Forward);
+    // keep the mean of the image
+    noise[0] = Complex::new(0.0, 0.0);
+    let rotation = noise
+        .iter()
+        .map(|c| Complex::from_polar(1.0, amount * c.arg()))
+        .collect::<Vec<_>>();
+
+    let count = (width * height) as f64;
+    for channel in 0..3 {
+        let mut spectrum = image
+            .pixels()
+            .map(|pixel| Complex::new(pixel[channel] as f64, 0.0))
+            .collect::<Vec<_>>();
+        helpers::fft_2d(&mut spectrum, width, height, FftDirection::Forward);
+        spectrum.iter_mut().zip(&rotation).for_each(|(c, r)| *c *= *r);
+        helpers::fft_2d(&mut spectrum, width, height, FftDirection::Inverse);
+
+        for (pixel, value) in scrambled.pixels_mut().zip(&spectrum) {
+            pixel[channel] = (value.re / count).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    scrambled
+}
+
+/// Cuts an image into square blocks of `block_size` pixels and shuffles them.
+///
+/// Only whole blocks are shuffled; if the size of the image is not a multiple of `block_size`, the
+/// remaining pixels at the right and bottom edges stay in place.
+pub fn block_scramble(image: &RgbaImage, block_size: u32, seed: Option<u64>) -> PsydkResult<RgbaImage> {
+    if block_size == 0 {
+        return Err(PsydkError::ParameterError(
+            "the block size must be at least one pixel".into(),
+        ));
+    }
+
+    let cols = image.width() / block_size;
+    let rows = image.height() / block_size;
+    let mut order = (0..cols * rows).collect::<Vec<_>>();
+    order.shuffle(&mut scramble_rng(seed));
+
+    let mut scrambled = image.clone();
+    for (target, source) in order.into_iter().enumerate() {
+        let (target_x, target_y) = (target as u32 % cols * block_size, target as u32 / cols * block_size);
+        let (source_x, source_y) = (source % cols * block_size, source / cols * block_size);
+        for dy in 0..block_size {
+            for dx in 0..block_size {
+                let pixel = *image.get_pixel(source_x + dx, source_y + dy);
+                scrambled.put_pixel(target_x + dx, target_y + dy, pixel);
+            }
+        }
+    }
+
+    Ok(scrambled)
+}
+
+/// The filter used to resample an image when it is resized.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ResizeFilter {
+    Nearest,
+    Linear,
+    Cubic,
+    Gaussian,
+    Lanczos,
+}
+
+impl From<ResizeFilter> for imageops::FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => imageops::FilterType::Nearest,
+            ResizeFilter::Linear => imageops::FilterType::Triangle,
+            ResizeFilter::Cubic => imageops::FilterType::CatmullRom,
+            ResizeFilter::Gaussian => imageops::FilterType::Gaussian,
+            ResizeFilter::Lanczos => imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Operations that are applied to an image when it is loaded, in the order of the fields.
+///
+/// Luminance is computed from the encoded pixel values (scaled to 0.0-1.0) with the Rec. 709
+/// weights. Fully transparent pixels are ignored when measuring the mean luminance and RMS contrast,
+/// so that the background of cut-out objects does not count towards the statistics.
+#[derive(Debug, Clone, Default)]
+pub struct ImagePreprocessing {
+    /// Crop the image to `(x, y, width, height)`, in image pixels.
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Resize the image to `(width, height)`, in image pixels.
+    pub resize: Option<(u32, u32)>,
+    /// The filter used for resizing.
+    pub filter: Option<ResizeFilter>,
+    /// Convert the image to grayscale.
+    pub grayscale: bool,
+    /// Set the mean luminance of the image, from 0.0 to 1.0.
+    pub mean_luminance: Option<f64>,
+    /// Set the RMS contrast (the standard deviation of the luminance) of the image.
+    pub rms_contrast: Option<f64>,
+}
+
+fn luminance(pixel: &renderer::image::Rgba<u8>) -> f64 {
+    (0.2126 * pixel[0] as f64 + 0.7152 * pixel[1] as f64 + 0.0722 * pixel[2] as f64) / 255.0
+}
+
+/// The mean luminance and RMS contrast of the (not fully transparent) pixels of an image.
+pub fn luminance_stats(image: &RgbaImage) -> (f64, f64) {
+    let values = image
+        .pixels()
+        .filter(|pixel| pixel[3] > 0)
+        .map(luminance)
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+impl ImagePreprocessing {
+    pub fn apply(&self, mut image: RgbaImage) -> PsydkResult<RgbaImage> {
+        if let Some((x, y, width, height)) = self.crop {
+            if x.saturating_add(width) > image.width() || y.saturating_add(height) > image.height() {
+                return Err(PsydkError::ParameterError(format!(
+                    "the crop rectangle ({x}, {y}, {width}, {height}) exceeds the image size ({}, {})",
+                    image.width(),
+                    image.height()
+                )));
+            }
+            image = imageops::crop_imm(&image, x, y, width, height).to_image();
+        }
+
+        if let Some((width, height)) = self.resize {
+            let filter = self.filter.unwrap_or(ResizeFilter::Lanczos);
+            image = imageops::resize(&image, width, height, filter.into());
+        }
+
+        if self.grayscale {
+            for pixel in image.pixels_mut() {
+                let value = (luminance(pixel) * 255.0).round() as u8;
+                pixel[0] = value;
+                pixel[1] = value;
+                pixel[2] = value;
+            }
+        }
+
+        if self.mean_luminance.is_some() || self.rms_contrast.is_some() {
+            // an affine transformation of all channels changes the luminance in the same way
+            let (mean, contrast) = luminance_stats(&image);
+            let target_mean = self.mean_luminance.unwrap_or(mean);
+            let gain = match self.rms_contrast {
+                Some(target) if contrast > 0.0 => target / contrast,
+                _ => 1.0,
+            };
+            for pixel in image.pixels_mut() {
+                for channel in 0..3 {
+                    let value = pixel[channel] as f64 / 255.0;
+                    let value = target_mean + (value - mean) * gain;
+                    pixel[channel] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg(feature = "python")]
 #[pyclass(name = "ImageStimulus", extends=PyStimulus)]
 pub struct PyImageStimulus();
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyImageStimulus {
     #[new]
@@ -105,6 +320,16 @@ impl PyImageStimulus {
         anchor = Anchor::Center,
         transform = None,
         srgb = true,
+        crop = None,
+        resize = None,
+        resize_filter = None,
+        grayscale = false,
+        mean_luminance = None,
+        rms_contrast = None,
+        scramble = None,
+        scramble_amount = 1.0,
+        block_size = 16,
+        seed = None,
         context = None,
     ))]
     /// Creates a new `ImageStimulus` from a file path.
@@ -120,9 +345,41 @@ impl PyImageStimulus {
     /// width : Size, num, or str
     ///     The width of the stimulus.
     /// height : Size, num, or str
-    /// The height of the stimulus.
+    ///     The height of the stimulus.
     /// rotation : float, optional
-    ///
+    ///     The rotation of the stimulus in degrees.
+    /// opacity : float, optional
+    ///     The opacity of the stimulus, from 0.0 (transparent) to 1.0 (opaque).
+    /// anchor : str, optional
+    ///     The anchor point of the stimulus.
+    /// transform : Transformation2D, optional
+    ///     A transformation applied to the stimulus.
+    /// srgb : bool, optional
+    ///     Whether the image file is encoded in sRGB (the default) or in linear RGB.
+    /// crop : tuple[int, int, int, int], optional
+    ///     Crop the image to (x, y, width, height), in image pixels.
+    /// resize : tuple[int, int], optional
+    ///     Resize the image to (width, height), in image pixels.
+    /// resize_filter : str, optional
+    ///     The filter used for resizing: "nearest", "linear", "cubic", "gaussian", or "lanczos" (the
+    ///     default).
+    /// grayscale : bool, optional
+    ///     Convert the image to grayscale.
+    /// mean_luminance : float, optional
+    ///     Set the mean luminance of the image (from 0.0 to 1.0, computed from the pixel values).
+    ///     Useful to equate a set of stimuli for low-level properties.
+    /// rms_contrast : float, optional
+    ///     Set the RMS contrast (the standard deviation of the luminance) of the image.
+    /// scramble : str, optional
+    ///     Scramble the image when it is loaded, e.g. to create control stimuli. "phase" randomizes
+    ///     the Fourier phase while keeping the amplitude spectrum, "block" shuffles square blocks.
+    /// scramble_amount : float, optional
+    ///     How much of the phase is randomized when phase scrambling, from 0.0 (none) to 1.0 (fully
+    ///     scrambled).
+    /// block_size : int, optional
+    ///     The size of the blocks in image pixels when block scrambling.
+    /// seed : int, optional
+    ///     The seed for scrambling. The same seed always produces the same scrambled image.
     fn __new__(
         py: Python,
         src: String,
@@ -135,11 +392,38 @@ impl PyImageStimulus {
         anchor: Anchor,
         transform: Option<Transformation2D>,
         srgb: bool,
+        crop: Option<(u32, u32, u32, u32)>,
+        resize: Option<(u32, u32)>,
+        resize_filter: Option<ResizeFilter>,
+        grayscale: bool,
+        mean_luminance: Option<f64>,
+        rms_contrast: Option<f64>,
+        scramble: Option<ScrambleMethod>,
+        scramble_amount: f64,
+        block_size: u32,
+        seed: Option<u64>,
         context: Option<ExperimentContext>,
     ) -> PyResult<(Self, PyStimulus)> {
         let ctx = get_experiment_context(context, py)?;
 
-        let bitmap = ctx.renderer_factory().create_bitmap_from_path(&src);
+        let image = renderer::image::open(&src).map_err(PsydkError::from)?.to_rgba8();
+        let image = ImagePreprocessing {
+            crop,
+            resize,
+            filter: resize_filter,
+            grayscale,
+            mean_luminance,
+            rms_contrast,
+        }
+        .apply(image)?;
+        let image = match scramble {
+            Some(ScrambleMethod::Phase) => phase_scramble(&image, scramble_amount, seed),
+            Some(ScrambleMethod::Block) => block_scramble(&image, block_size, seed)?,
+            None => image,
+        };
+
+        let color_space = if srgb { ColorSpace::Srgb } else { ColorSpace::LinearSrgb };
+        let bitmap = ctx.renderer_factory().create_bitmap_u8(image, color_space);
 
         Ok((
             Self(),
@@ -184,7 +468,15 @@ impl Stimulus for ImageStimulus {
         let width = self.params.width.eval(window_size, screen_props);
         let height = self.params.height.eval(window_size, screen_props);
 
-        let (x, y) = self.anchor.to_top_left(x, y, width, height);
+        let (anchor_x, anchor_y) = window_state.coordinate_system.to_scene(x, y, window_size);
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (x, y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
 
         let image_offset_x = self.params.image_x.eval(window_size, screen_props);
         let image_offset_y = self.params.image_y.eval(window_size, screen_props);
@@ -193,8 +485,8 @@ impl Stimulus for ImageStimulus {
         let trans_mat = self.transformation.clone()
             * Transformation2D::RotationPoint(
                 self.params.rotation as f32,
-                self.params.x.clone(),
-                self.params.y.clone(),
+                Size::Pixels(anchor_x),
+                Size::Pixels(anchor_y),
             );
 
         let trans_mat = trans_mat.eval(window_size, screen_props);
@@ -258,10 +550,14 @@ impl Stimulus for ImageStimulus {
         let width = self.params.width.eval(window_size, screen_props);
         let height = self.params.height.eval(window_size, screen_props);
 
+        let coordinate_system = window_state.coordinate_system;
+        let (ix, iy) = helpers::anchored_top_left(self.anchor, (ix, iy), width, height, window_size, coordinate_system);
+
         let trans_mat = self.transformation.eval(window_size, screen_props);
 
         let x = x.eval(window_size, screen_props);
         let y = y.eval(window_size, screen_props);
+        let (x, y) = coordinate_system.to_scene(x, y, window_size);
 
         // apply transformation by multiplying the point with the transformation matrix
         let p = nalgebra::Vector3::new(x, y, 1.0);
@@ -271,6 +567,51 @@ impl Stimulus for ImageStimulus {
         p_new[0] >= ix && p_new[0] <= ix + width && p_new[1] >= iy && p_new[1] <= iy + height
     }
 
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let x = self.params.x.eval(window_size, screen_props);
+        let y = self.params.y.eval(window_size, screen_props);
+        let width = self.params.width.eval(window_size, screen_props);
+        let height = self.params.height.eval(window_size, screen_props);
+
+        let (anchor_x, anchor_y) = window_state.coordinate_system.to_scene(x, y, window_size);
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (x, y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+
+        // the same transformations as in `draw` and `contains`
+        let transform = (self.transformation.clone()
+            * Transformation2D::RotationPoint(
+                self.params.rotation as f32,
+                Size::Pixels(anchor_x),
+                Size::Pixels(anchor_y),
+            ))
+        .eval(window_size, screen_props);
+        let hit_transform = self.transformation.eval(window_size, screen_props);
+
+        vec![helpers::rect_layout(
+            (x, y, width, height),
+            (anchor_x, anchor_y),
+            &transform,
+            &hit_transform,
+        )]
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
     fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
         self.params.get_param(name)
     }
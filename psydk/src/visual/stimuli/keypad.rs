@@ -0,0 +1,605 @@
+//! On-screen keyboards.
+//!
+//! A keypad shows rows of keys that are pressed with the mouse or a finger, e.g. on touch screens
+//! without a physical keyboard. Pressing a key delivers the same events to the window as the key on
+//! a physical keyboard (a `key_press`, a `text` event for keys that enter text, and a `key_release`
+//! when the key is let go), so code that waits for key presses or collects text with a `TextInput`
+//! works with either.
+
+use std::time::Instant;
+
+use psydk_proc::StimulusParams;
+use renderer::DynamicScene;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation,
+    helpers, impl_pystimulus_for_wrapper,
+    text::FontWeight,
+    widgets::{fill, Attachment, Interactive, Label, Pointer, Rect, Viewport},
+    DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
+};
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    input::Event,
+    visual::{
+        color::{IntoLinRgba, LinRgba},
+        geometry::{Anchor, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// The rows of the numeric keypad.
+const NUMERIC: [&[&str]; 4] = [
+    &["7", "8", "9"],
+    &["4", "5", "6"],
+    &["1", "2", "3"],
+    &["Backspace", "0", "Enter"],
+];
+
+/// The rows of the QWERTY keyboard.
+const QWERTY: [&[&str]; 5] = [
+    &["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"],
+    &["q", "w", "e", "r", "t", "y", "u", "i", "o", "p"],
+    &["a", "s", "d", "f", "g", "h", "j", "k", "l"],
+    &["Shift", "z", "x", "c", "v", "b", "n", "m", "Backspace"],
+    &["Space", "Enter"],
+];
+
+/// The gap between keys, as a fraction of the height of a row.
+const GAP: f32 = 0.15;
+
+/// A key of a keypad.
+#[derive(Debug, Clone, PartialEq)]
+struct Key {
+    /// The name of the key in key events, e.g. "a", "7", or "Backspace".
+    name: String,
+    label: String,
+    /// The text that the key enters, if any.
+    text: Option<String>,
+    /// The width of the key, relative to a key with a single character.
+    width: f32,
+}
+
+impl Key {
+    /// A key from its name: a single character, or "Backspace", "Enter", "Space", "Shift", or
+    /// "Tab".
+    fn parse(name: &str) -> PsydkResult<Self> {
+        let (label, text, width) = match name {
+            "Backspace" => ("Delete", None, 1.5),
+            "Enter" => ("Enter", None, 1.5),
+            "Shift" => ("Shift", None, 1.5),
+            "Tab" => ("Tab", None, 1.5),
+            "Space" => ("", Some(" "), 5.0),
+            name if name.chars().count() == 1 => (name, Some(name), 1.0),
+            name => {
+                return Err(PsydkError::ParameterError(format!(
+                    "Unknown key \"{name}\" (keys are single characters, \"Backspace\", \"Enter\", \"Space\", \"Shift\", or \"Tab\")"
+                )))
+            }
+        };
+        Ok(Self {
+            name: name.to_string(),
+            label: label.to_string(),
+            text: text.map(str::to_string),
+            width,
+        })
+    }
+
+    /// The key as typed with Shift held down.
+    fn shifted(&self) -> Self {
+        match &self.text {
+            Some(text) if self.name.chars().count() == 1 => Self {
+                name: text.to_uppercase(),
+                label: self.label.to_uppercase(),
+                text: Some(text.to_uppercase()),
+                width: self.width,
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+/// The key that is held down.
+#[derive(Debug, Clone)]
+struct Press {
+    row: usize,
+    column: usize,
+    /// The key as it was pressed (i.e., with Shift applied), for the release event.
+    key: Key,
+}
+
+/// Where the keypad was drawn last.
+#[derive(Debug, Clone)]
+struct KeypadLayout {
+    viewport: Viewport,
+    bounds: Rect,
+    keys: Vec<(Rect, (usize, usize))>,
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct KeypadParams {
+    pub x: Size,
+    pub y: Size,
+    pub width: Size,
+    pub height: Size,
+    pub font_size: Size,
+    pub key_color: LinRgba,
+    /// The color of the key that is held down and of Shift while it is active.
+    pub pressed_color: LinRgba,
+    pub text_color: LinRgba,
+}
+
+/// An on-screen keypad or keyboard that delivers key events to a window.
+#[derive(Debug)]
+pub struct KeypadStimulus {
+    id: Uuid,
+    params: KeypadParams,
+    rows: Vec<Vec<Key>>,
+    anchor: Anchor,
+    label: Label,
+    /// Whether the next character is typed with Shift.
+    shift: bool,
+    press: Option<Press>,
+    /// The events for the window, taken by `take_events`.
+    events: Vec<Event>,
+    layout: Option<KeypadLayout>,
+    attachment: Option<Attachment>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl KeypadStimulus {
+    /// A keypad with rows of keys by their names (see `Key::parse`).
+    pub fn new(
+        rows: &[Vec<String>],
+        anchor: Anchor,
+        params: KeypadParams,
+        context: &ExperimentContext,
+    ) -> PsydkResult<Self> {
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|name| Key::parse(name)).collect::<PsydkResult<Vec<_>>>())
+            .collect::<PsydkResult<Vec<_>>>()?;
+        if rows.iter().all(Vec::is_empty) {
+            return Err(PsydkError::ParameterError("The keypad has no keys".to_string()));
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            params,
+            rows,
+            anchor,
+            label: Label::new(FontWeight::Regular, context),
+            shift: false,
+            press: None,
+            events: Vec::new(),
+            layout: None,
+            attachment: None,
+            transformation: Transformation2D::Identity(),
+            animations: Vec::new(),
+            visible: true,
+        })
+    }
+
+    /// Pass the input events of `window` to `keypad` as they arrive, and deliver the key events of
+    /// the keypad to the window, until `detach` is called.
+    pub fn attach(keypad: &DynamicStimulus, window: &Window) -> PsydkResult<()> {
+        // created before the keypad is locked, as it needs the state of the window
+        let attachment = Attachment::new::<Self>(window, keypad)?;
+        if let Some(keypad) = keypad.lock().downcast_mut::<Self>() {
+            keypad.attachment = Some(attachment);
+        }
+        Ok(())
+    }
+
+    /// Stop passing input events to the keypad.
+    pub fn detach(&mut self) {
+        self.attachment = None;
+    }
+
+    /// The rectangle of the keypad in the coordinates of the scene.
+    fn bounds(&self, window_state: &WindowState) -> Rect {
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let (width, height) = (eval(&self.params.width), eval(&self.params.height));
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (eval(&self.params.x), eval(&self.params.y)),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+        Rect::new(x, y, width, height)
+    }
+
+    /// The rectangles of the keys, with the row and column of each. All keys have the same height,
+    /// and keys with the same relative width have the same width, so that the widest row fills the
+    /// keypad. The other rows are centered.
+    fn key_rects(&self, bounds: Rect) -> Vec<(Rect, (usize, usize))> {
+        let rows = self.rows.len() as f32;
+        let row_height = bounds.height / (rows + (rows + 1.0) * GAP);
+        let gap = GAP * row_height;
+        // the width of a key with a single character, so that every row fits
+        let unit = self
+            .rows
+            .iter()
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                let units: f32 = row.iter().map(|key| key.width).sum();
+                (bounds.width - (row.len() as f32 + 1.0) * gap) / units
+            })
+            .fold(f32::INFINITY, f32::min)
+            .max(0.0);
+
+        let mut rects = Vec::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            let row_width = row.iter().map(|key| key.width * unit).sum::<f32>() + (row.len() as f32 - 1.0) * gap;
+            let mut x = bounds.x + (bounds.width - row_width) / 2.0;
+            let y = bounds.y + gap + i as f32 * (row_height + gap);
+            for (j, key) in row.iter().enumerate() {
+                rects.push((Rect::new(x, y, key.width * unit, row_height), (i, j)));
+                x += key.width * unit + gap;
+            }
+        }
+        rects
+    }
+
+    /// Press the key at `row` and `column`.
+    fn press(&mut self, row: usize, column: usize, time: Instant) {
+        let key = &self.rows[row][column];
+        let key = match self.shift {
+            true => key.shifted(),
+            false => key.clone(),
+        };
+        self.events.push(Event::KeyPress {
+            timestamp: time.into(),
+            key: key.name.clone(),
+            // there is no physical key
+            code: 0,
+        });
+        if let Some(text) = &key.text {
+            self.events.push(Event::Text {
+                timestamp: time.into(),
+                text: text.clone(),
+            });
+        }
+        // Shift applies to the next key
+        self.shift = match key.name.as_str() {
+            "Shift" => !self.shift,
+            _ => false,
+        };
+        self.press = Some(Press { row, column, key });
+    }
+
+    /// Release the key that is held down, if any.
+    fn release(&mut self, time: Instant) -> bool {
+        let Some(press) = self.press.take() else {
+            return false;
+        };
+        self.events.push(Event::KeyRelease {
+            timestamp: time.into(),
+            key: press.key.name,
+            code: 0,
+        });
+        true
+    }
+}
+
+impl Interactive for KeypadStimulus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        // key events (including those of the keypad itself) are not used
+        let Some((pointer, position)) = layout.viewport.pointer(event) else {
+            return false;
+        };
+        let time = event.timestamp().timestamp;
+
+        match pointer {
+            Pointer::Down if self.visible => {
+                let Some((row, column)) = layout
+                    .keys
+                    .iter()
+                    .find(|(rect, _)| rect.contains(position))
+                    .map(|(_, key)| *key)
+                else {
+                    return layout.bounds.contains(position);
+                };
+                // a second finger releases the key of the first
+                self.release(time);
+                self.press(row, column, time);
+                true
+            }
+            // keys are released even if the keypad was hidden in the meantime
+            Pointer::Up => self.release(time),
+            _ => false,
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyKeypadStimulus, KeypadStimulus);
+
+impl Stimulus for KeypadStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let bounds = self.bounds(window_state);
+        let keys = self.key_rects(bounds);
+        let font_size = self
+            .params
+            .font_size
+            .eval(window_state.size, window_state.physical_screen);
+
+        for (rect, (row, column)) in &keys {
+            let key = &self.rows[*row][*column];
+            let pressed = self
+                .press
+                .as_ref()
+                .is_some_and(|press| (press.row, press.column) == (*row, *column))
+                || (key.name == "Shift" && self.shift);
+            let color = match pressed {
+                true => self.params.pressed_color,
+                false => self.params.key_color,
+            };
+            fill(scene, rect.rounded(0.1 * rect.height.min(rect.width)), color);
+
+            let label = match self.shift {
+                true => key.shifted().label,
+                false => key.label.clone(),
+            };
+            let (width, height) = self.label.measure(&label, font_size, window_state);
+            let (x, y) = rect.center();
+            self.label.draw(
+                scene,
+                window_state,
+                &label,
+                (x - width / 2.0, y - height / 2.0),
+                font_size,
+                self.params.text_color,
+            );
+        }
+
+        self.layout = Some(KeypadLayout {
+            viewport: Viewport::new(window_state),
+            bounds,
+            keys,
+        });
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        let window_state = window.state.lock().unwrap();
+        let window_state = window_state.as_ref().unwrap();
+        let x = x.eval(window_state.size, window_state.physical_screen);
+        let y = y.eval(window_state.size, window_state.physical_screen);
+        let point = window_state.coordinate_system.to_scene(x, y, window_state.size);
+        self.bounds(window_state).contains(point)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let bounds = self.bounds(window_state);
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let anchor = window_state
+            .coordinate_system
+            .to_scene(eval(&self.params.x), eval(&self.params.y), window_size);
+        let identity = nalgebra::Matrix3::<f32>::identity();
+        vec![helpers::rect_layout(
+            (bounds.x, bounds.y, bounds.width, bounds.height),
+            anchor,
+            &identity,
+            &identity,
+        )]
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
+
+/// The keys of a keypad: the name of a layout, or rows of key names.
+#[derive(Debug, Clone)]
+pub struct KeypadRows(pub Vec<Vec<String>>);
+
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for KeypadRows {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let preset = |rows: &[&[&str]]| {
+            rows.iter()
+                .map(|row| row.iter().map(|key| key.to_string()).collect())
+                .collect()
+        };
+        if let Ok(name) = ob.extract::<String>() {
+            return match name.as_str() {
+                "numeric" => Ok(Self(preset(&NUMERIC))),
+                "qwerty" => Ok(Self(preset(&QWERTY))),
+                name => Err(PyValueError::new_err(format!(
+                    "Unknown keypad layout \"{name}\" (use \"numeric\", \"qwerty\", or a list of rows)"
+                ))),
+            };
+        }
+        Ok(Self(ob.extract()?))
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "KeypadStimulus", extends=PyStimulus)]
+/// An on-screen keypad or keyboard for touch screens without a physical keyboard.
+///
+/// Pressing a key with the mouse or a finger delivers the same events to the window as pressing the
+/// key on a physical keyboard: a `key_press` event with the name of the key, a `text` event for keys
+/// that enter text (characters and Space), and a `key_release` event when the key is let go. So
+/// responses can be collected with event receivers, `TextInput`, or the keyboard state of the
+/// window without changes. The key events have the time of the touch and a `code` of 0. Shift makes
+/// the next letter upper case.
+///
+/// Events are only delivered while the keypad is attached to a window with `attach`.
+///
+/// Parameters
+/// ----------
+/// layout : str or list[list[str]], optional
+///   "numeric" (the digits, Backspace, and Enter, the default), "qwerty" (the digits, the letters,
+///   Shift, Backspace, Space, and Enter), or rows of keys. A key is a single character,
+///   "Backspace", "Enter", "Space", "Shift", or "Tab".
+/// x : Size, optional
+///   The x position of the keypad.
+/// y : Size, optional
+///   The y position of the keypad.
+/// width : Size, optional
+///   The width of the keypad.
+/// height : Size, optional
+///   The height of the keypad.
+/// anchor : str, optional
+///   The point of the keypad that the position refers to. Defaults to "center".
+/// font_size : Size, optional
+///   The font size of the key labels.
+/// key_color : Color, optional
+///   The color of the keys.
+/// pressed_color : Color, optional
+///   The color of the key that is held down.
+/// text_color : Color, optional
+///   The color of the key labels.
+/// context : ExperimentContext, optional
+///   The experiment context. Defaults to the context of the experiment function.
+pub struct PyKeypadStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyKeypadStimulus {
+    #[new]
+    #[pyo3(signature = (
+        layout = KeypadRows(NUMERIC.iter().map(|row| row.iter().map(|key| key.to_string()).collect()).collect()),
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        width = IntoSize(Size::Pixels(360.0)),
+        height = IntoSize(Size::Pixels(400.0)),
+        anchor = Anchor::Center,
+        font_size = IntoSize(Size::Pixels(32.0)),
+        key_color = IntoLinRgba::new(0.85, 0.85, 0.85, 1.0),
+        pressed_color = IntoLinRgba::new(0.55, 0.55, 0.55, 1.0),
+        text_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        context = None,
+    ))]
+    fn __new__(
+        py: Python,
+        layout: KeypadRows,
+        x: IntoSize,
+        y: IntoSize,
+        width: IntoSize,
+        height: IntoSize,
+        anchor: Anchor,
+        font_size: IntoSize,
+        key_color: IntoLinRgba,
+        pressed_color: IntoLinRgba,
+        text_color: IntoLinRgba,
+        context: Option<ExperimentContext>,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let context = helpers::get_experiment_context(context, py)?;
+        let params = KeypadParams {
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
+            font_size: font_size.into(),
+            key_color: key_color.into(),
+            pressed_color: pressed_color.into(),
+            text_color: text_color.into(),
+        };
+        let keypad = KeypadStimulus::new(&layout.0, anchor, params, &context)?;
+        Ok((Self(), PyStimulus::new(keypad)))
+    }
+
+    /// Pass an input event to the keypad, e.g. from an event receiver.
+    ///
+    /// Parameters
+    /// ----------
+    /// event : Event
+    ///   The event.
+    ///
+    /// Returns
+    /// -------
+    /// list[Event]
+    ///   The key and text events of the keys that were pressed or released. They are not
+    ///   delivered to the window.
+    #[pyo3(name = "handle_event")]
+    fn py_handle_event(slf: PyRefMut<'_, Self>, event: Event) -> Vec<Event> {
+        let mut keypad = slf.as_super().0.lock();
+        let keypad = keypad.downcast_mut::<KeypadStimulus>().expect("downcast failed");
+        keypad.handle_event(&event);
+        keypad.take_events()
+    }
+
+    /// Pass the input events of a window to the keypad as they arrive, and deliver the key events
+    /// of the keypad to the window, until `detach` is called.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window that the keypad is shown on.
+    #[pyo3(name = "attach")]
+    fn py_attach(slf: PyRef<'_, Self>, window: Window) -> PyResult<()> {
+        Ok(KeypadStimulus::attach(&slf.as_super().0, &window)?)
+    }
+
+    /// Stop passing the input events of the window to the keypad.
+    #[pyo3(name = "detach")]
+    fn py_detach(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, KeypadStimulus).detach();
+    }
+}
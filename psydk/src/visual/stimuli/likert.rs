@@ -0,0 +1,690 @@
+//! Matrix questions.
+//!
+//! A Likert grid shows items as rows and the options of a rating scale as columns, with a radio
+//! button in each cell, as in many questionnaires (e.g., the Big Five Inventory). One option is
+//! chosen per row, with the mouse or a finger, or with the keyboard: the arrow keys move between
+//! rows and options, and the number keys choose an option of the current row and move on to the
+//! next.
+
+use std::time::Instant;
+
+use psydk_proc::StimulusParams;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+use renderer::{shapes::Shape, DynamicScene};
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation,
+    helpers, impl_pystimulus_for_wrapper,
+    text::FontWeight,
+    widgets::{faded, fill, stroke, Attachment, Interactive, Label, Pointer, Rect, Viewport, ERROR_COLOR},
+    DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
+};
+use crate::{
+    context::ExperimentContext,
+    errors::{PsydkError, PsydkResult},
+    input::Event,
+    time::Timestamp,
+    visual::{
+        color::{IntoLinRgba, LinRgba},
+        geometry::{Anchor, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// The share of the width that the item labels take up.
+const LABEL_WIDTH: f32 = 0.4;
+/// The height of the header with the options, in rows.
+const HEADER_HEIGHT: f32 = 1.5;
+
+/// The response to a row of a Likert grid.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowResponse {
+    /// The index of the chosen option.
+    pub choice: Option<usize>,
+    /// When the option was last chosen.
+    pub time: Option<Instant>,
+    /// How often the response was changed after the first choice.
+    pub changes: usize,
+}
+
+/// Where the grid was drawn last.
+#[derive(Debug, Clone)]
+struct LikertLayout {
+    viewport: Viewport,
+    /// The rectangle of each row, below the header.
+    rows: Vec<Rect>,
+    /// The left edge of the first column and the width of a column.
+    columns: (f32, f32),
+}
+
+impl LikertLayout {
+    /// The row and column (if any) at `position`.
+    fn cell(&self, position: (f32, f32)) -> Option<(usize, Option<usize>)> {
+        let row = self.rows.iter().position(|rect| rect.contains(position))?;
+        let (left, width) = self.columns;
+        let column = (position.0 >= left && width > 0.0).then(|| ((position.0 - left) / width) as usize);
+        Some((row, column))
+    }
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct LikertParams {
+    pub x: Size,
+    pub y: Size,
+    pub width: Size,
+    pub height: Size,
+    pub font_size: Size,
+    pub text_color: LinRgba,
+    /// The color of the chosen options and the current row.
+    pub accent_color: LinRgba,
+    pub background_color: LinRgba,
+}
+
+/// A grid of items (rows) that are rated on the same scale (columns).
+#[derive(Debug)]
+pub struct LikertStimulus {
+    id: Uuid,
+    params: LikertParams,
+    items: Vec<String>,
+    options: Vec<String>,
+    anchor: Anchor,
+    label: Label,
+    responses: Vec<RowResponse>,
+    /// The row that the keyboard chooses options for.
+    focus: usize,
+    /// Whether rows without a response are marked, after a failed `validate`.
+    mark_missing: bool,
+    /// When the grid was first drawn, which reaction times are relative to.
+    shown: Option<Instant>,
+    /// The row and column where a pointer was pressed.
+    press: Option<(usize, Option<usize>)>,
+    layout: Option<LikertLayout>,
+    attachment: Option<Attachment>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl LikertStimulus {
+    pub fn new(
+        items: Vec<String>,
+        options: Vec<String>,
+        anchor: Anchor,
+        params: LikertParams,
+        context: &ExperimentContext,
+    ) -> PsydkResult<Self> {
+        if items.is_empty() || options.is_empty() {
+            return Err(PsydkError::ParameterError(
+                "A Likert grid needs at least one item and one option".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            params,
+            responses: vec![RowResponse::default(); items.len()],
+            items,
+            options,
+            anchor,
+            label: Label::new(FontWeight::Regular, context),
+            focus: 0,
+            mark_missing: false,
+            shown: None,
+            press: None,
+            layout: None,
+            attachment: None,
+            transformation: Transformation2D::Identity(),
+            animations: Vec::new(),
+            visible: true,
+        })
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    /// The response to each row, in the order of the items.
+    pub fn responses(&self) -> &[RowResponse] {
+        &self.responses
+    }
+
+    /// Whether each row has a response.
+    pub fn completed(&self) -> Vec<bool> {
+        self.responses
+            .iter()
+            .map(|response| response.choice.is_some())
+            .collect()
+    }
+
+    /// Whether all rows have a response.
+    pub fn complete(&self) -> bool {
+        self.responses.iter().all(|response| response.choice.is_some())
+    }
+
+    /// When the grid was first drawn.
+    pub fn shown(&self) -> Option<Instant> {
+        self.shown
+    }
+
+    /// Mark the rows without a response, until they are answered. Returns true if all rows have a
+    /// response.
+    pub fn validate(&mut self) -> bool {
+        self.mark_missing = !self.complete();
+        // the keyboard continues with the first row that is missing
+        if let Some(row) = self.responses.iter().position(|response| response.choice.is_none()) {
+            self.focus = row;
+        }
+        !self.mark_missing
+    }
+
+    /// Discard all responses. Reaction times are measured from when the grid is drawn next.
+    pub fn reset(&mut self) {
+        self.responses = vec![RowResponse::default(); self.items.len()];
+        self.focus = 0;
+        self.mark_missing = false;
+        self.shown = None;
+        self.press = None;
+    }
+
+    /// Choose `option` for `row`.
+    pub fn choose(&mut self, row: usize, option: usize, time: Instant) {
+        let response = &mut self.responses[row];
+        if response.choice == Some(option) {
+            return;
+        }
+        if response.choice.is_some() {
+            response.changes += 1;
+        }
+        response.choice = Some(option);
+        response.time = Some(time);
+        self.focus = row;
+    }
+
+    /// Pass the input events of `window` to `grid` as they arrive, until `detach` is called.
+    pub fn attach(grid: &DynamicStimulus, window: &Window) -> PsydkResult<()> {
+        // created before the grid is locked, as it needs the state of the window
+        let attachment = Attachment::new::<Self>(window, grid)?;
+        if let Some(grid) = grid.lock().downcast_mut::<Self>() {
+            grid.attachment = Some(attachment);
+        }
+        Ok(())
+    }
+
+    /// Stop passing input events to the grid.
+    pub fn detach(&mut self) {
+        self.attachment = None;
+    }
+
+    /// The rectangle of the grid in the coordinates of the scene.
+    fn bounds(&self, window_state: &WindowState) -> Rect {
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let (width, height) = (eval(&self.params.width), eval(&self.params.height));
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (eval(&self.params.x), eval(&self.params.y)),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+        Rect::new(x, y, width, height)
+    }
+
+    fn pointer(&mut self, pointer: Pointer, position: (f32, f32), time: Instant) -> bool {
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        match pointer {
+            Pointer::Down => {
+                self.press = layout.cell(position);
+                self.press.is_some()
+            }
+            Pointer::Move => false,
+            // the option is chosen if the pointer is released on the cell it was pressed on
+            Pointer::Up => match (self.press.take(), layout.cell(position)) {
+                (Some(press), Some(cell)) if press == cell => {
+                    match cell {
+                        (row, Some(column)) if column < self.options.len() => self.choose(row, column, time),
+                        (row, _) => self.focus = row,
+                    }
+                    true
+                }
+                (press, _) => press.is_some(),
+            },
+        }
+    }
+
+    fn key(&mut self, key: &str, time: Instant) -> bool {
+        let (rows, options) = (self.items.len(), self.options.len());
+        let row = self.focus;
+        match key {
+            "ArrowUp" => self.focus = row.saturating_sub(1),
+            "ArrowDown" | "Enter" | "Tab" => self.focus = (row + 1).min(rows - 1),
+            "ArrowLeft" | "ArrowRight" => {
+                let option = match (self.responses[row].choice, key) {
+                    (Some(option), "ArrowLeft") => option.saturating_sub(1),
+                    (Some(option), _) => (option + 1).min(options - 1),
+                    (None, "ArrowLeft") => options - 1,
+                    (None, _) => 0,
+                };
+                self.choose(row, option, time);
+            }
+            key => {
+                // the number keys choose the option with that number (starting at 1)
+                let Some(option) = key.parse::<usize>().ok().filter(|n| (1..=options).contains(n)) else {
+                    return false;
+                };
+                self.choose(row, option - 1, time);
+                self.focus = (row + 1).min(rows - 1);
+            }
+        }
+        true
+    }
+}
+
+impl Interactive for LikertStimulus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if !self.visible {
+            return false;
+        }
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        let time = event.timestamp().timestamp;
+
+        if let Some((pointer, position)) = layout.viewport.pointer(event) {
+            return self.pointer(pointer, position, time);
+        }
+        match event {
+            Event::KeyPress { key, .. } => self.key(key, time),
+            _ => false,
+        }
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyLikertStimulus, LikertStimulus);
+
+impl Stimulus for LikertStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+        self.shown.get_or_insert_with(Instant::now);
+
+        let bounds = self.bounds(window_state);
+        let font_size = self
+            .params
+            .font_size
+            .eval(window_state.size, window_state.physical_screen);
+        let (text_color, accent_color) = (self.params.text_color, self.params.accent_color);
+        let muted_color = faded(text_color, 0.5);
+
+        let padding = 0.5 * font_size;
+        let row_height = bounds.height / (self.items.len() as f32 + HEADER_HEIGHT);
+        let header_height = HEADER_HEIGHT * row_height;
+        let label_width = LABEL_WIDTH * bounds.width;
+        let column_left = bounds.x + label_width;
+        let column_width = (bounds.width - label_width) / self.options.len() as f32;
+        let mark_size = font_size.min(0.6 * row_height);
+        let line_height = 1.2 * font_size;
+
+        fill(scene, bounds.shape(), self.params.background_color);
+
+        let Self {
+            items,
+            options,
+            label,
+            responses,
+            focus,
+            mark_missing,
+            ..
+        } = self;
+        // draws lines of text centered vertically in `rect`, and horizontally if `centered`
+        let mut text = |scene: &mut DynamicScene, text: &str, rect: Rect, centered: bool, color: LinRgba| {
+            let lines = label.wrap(text, rect.width - 2.0 * padding, font_size, window_state);
+            let mut y = rect.y + (rect.height - lines.len() as f32 * line_height) / 2.0;
+            for line in &lines {
+                let x = match centered {
+                    true => rect.center().0 - label.measure(line, font_size, window_state).0 / 2.0,
+                    false => rect.x + padding,
+                };
+                label.draw(scene, window_state, line, (x, y), font_size, color);
+                y += line_height;
+            }
+        };
+
+        for (j, option) in options.iter().enumerate() {
+            let rect = Rect::new(
+                column_left + j as f32 * column_width,
+                bounds.y,
+                column_width,
+                header_height,
+            );
+            text(scene, option, rect, true, text_color);
+        }
+
+        let mut rows = Vec::new();
+        for (i, (item, response)) in items.iter().zip(responses.iter()).enumerate() {
+            let rect = Rect::new(
+                bounds.x,
+                bounds.y + header_height + i as f32 * row_height,
+                bounds.width,
+                row_height,
+            );
+            let missing = *mark_missing && response.choice.is_none();
+            // every other row is shaded, so that rows are easy to follow
+            if i == *focus {
+                fill(scene, rect.shape(), faded(accent_color, 0.15));
+            } else if i % 2 == 0 {
+                fill(scene, rect.shape(), faded(text_color, 0.05));
+            }
+            if missing {
+                stroke(scene, rect.shape(), ERROR_COLOR, 2.0);
+            }
+
+            let item_rect = Rect::new(rect.x, rect.y, label_width, rect.height);
+            text(
+                scene,
+                item,
+                item_rect,
+                false,
+                if missing { ERROR_COLOR } else { text_color },
+            );
+
+            for j in 0..options.len() {
+                let center = (column_left + (j as f32 + 0.5) * column_width, rect.center().1);
+                let circle = Shape::circle(center, (mark_size / 2.0) as f64);
+                stroke(scene, circle, muted_color, 1.5);
+                if response.choice == Some(j) {
+                    fill(scene, Shape::circle(center, (mark_size / 4.0) as f64), accent_color);
+                }
+            }
+            rows.push(rect);
+        }
+
+        self.layout = Some(LikertLayout {
+            viewport: Viewport::new(window_state),
+            rows,
+            columns: (column_left, column_width),
+        });
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        let window_state = window.state.lock().unwrap();
+        let window_state = window_state.as_ref().unwrap();
+        let x = x.eval(window_state.size, window_state.physical_screen);
+        let y = y.eval(window_state.size, window_state.physical_screen);
+        let point = window_state.coordinate_system.to_scene(x, y, window_state.size);
+        self.bounds(window_state).contains(point)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let bounds = self.bounds(window_state);
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let anchor = window_state
+            .coordinate_system
+            .to_scene(eval(&self.params.x), eval(&self.params.y), window_size);
+        let identity = nalgebra::Matrix3::<f32>::identity();
+        vec![helpers::rect_layout(
+            (bounds.x, bounds.y, bounds.width, bounds.height),
+            anchor,
+            &identity,
+            &identity,
+        )]
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "LikertStimulus", extends=PyStimulus)]
+/// A matrix question: items (rows) that are rated on the same scale (columns), with one option
+/// chosen per row.
+///
+/// Options are chosen by clicking or tapping a cell, or with the keyboard: the up and down arrow
+/// keys (and Enter and Tab) move between rows, the left and right arrow keys change the option of
+/// the current row, and the number keys choose the option with that number (starting at 1) and
+/// move on to the next row. The current row is highlighted.
+///
+/// The grid records when each row was answered and how often the answer was changed, and
+/// `validate` marks the rows that are not answered. The rows share the height of the grid, and
+/// long item labels and options are wrapped.
+///
+/// Parameters
+/// ----------
+/// items : list[str]
+///   The items, one per row.
+/// options : list[str]
+///   The options of the scale, one per column, e.g. from "strongly disagree" to "strongly agree".
+/// x : Size, optional
+///   The x position of the grid.
+/// y : Size, optional
+///   The y position of the grid.
+/// width : Size, optional
+///   The width of the grid.
+/// height : Size, optional
+///   The height of the grid.
+/// anchor : str, optional
+///   The point of the grid that the position refers to. Defaults to "center".
+/// font_size : Size, optional
+///   The font size of the items and options.
+/// text_color : Color, optional
+///   The color of the text and the radio buttons. Defaults to black.
+/// accent_color : Color, optional
+///   The color of the chosen options and the current row.
+/// background_color : Color, optional
+///   The color of the background. Defaults to white.
+/// context : ExperimentContext, optional
+///   The experiment context. Defaults to the context of the experiment function.
+pub struct PyLikertStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyLikertStimulus {
+    #[new]
+    #[pyo3(signature = (
+        items,
+        options,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        width = IntoSize(Size::Pixels(1000.0)),
+        height = IntoSize(Size::Pixels(600.0)),
+        anchor = Anchor::Center,
+        font_size = IntoSize(Size::Pixels(22.0)),
+        text_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        accent_color = IntoLinRgba::new(0.05, 0.25, 0.8, 1.0),
+        background_color = IntoLinRgba::new(1.0, 1.0, 1.0, 1.0),
+        context = None,
+    ))]
+    fn __new__(
+        py: Python,
+        items: Vec<String>,
+        options: Vec<String>,
+        x: IntoSize,
+        y: IntoSize,
+        width: IntoSize,
+        height: IntoSize,
+        anchor: Anchor,
+        font_size: IntoSize,
+        text_color: IntoLinRgba,
+        accent_color: IntoLinRgba,
+        background_color: IntoLinRgba,
+        context: Option<ExperimentContext>,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let context = helpers::get_experiment_context(context, py)?;
+        let params = LikertParams {
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
+            font_size: font_size.into(),
+            text_color: text_color.into(),
+            accent_color: accent_color.into(),
+            background_color: background_color.into(),
+        };
+        let grid = LikertStimulus::new(items, options, anchor, params, &context)?;
+        Ok((Self(), PyStimulus::new(grid)))
+    }
+
+    /// The index of the chosen option of each row (starting at 0), or None for rows that are not
+    /// answered.
+    #[getter(choices)]
+    fn py_choices(slf: PyRef<'_, Self>) -> Vec<Option<usize>> {
+        downcast_stimulus!(slf, LikertStimulus)
+            .responses()
+            .iter()
+            .map(|response| response.choice)
+            .collect()
+    }
+
+    /// Whether each row is answered.
+    #[getter(completed)]
+    fn py_completed(slf: PyRef<'_, Self>) -> Vec<bool> {
+        downcast_stimulus!(slf, LikertStimulus).completed()
+    }
+
+    /// Whether all rows are answered.
+    #[getter(complete)]
+    fn py_complete(slf: PyRef<'_, Self>) -> bool {
+        downcast_stimulus!(slf, LikertStimulus).complete()
+    }
+
+    /// The responses, as one dictionary per row with the "item", the "choice" (the index of the
+    /// chosen option, starting at 0) and the "option", the "time" it was last chosen, the reaction
+    /// time "rt" (in seconds since the grid was first drawn), and how often the answer was
+    /// changed ("changes"). Rows that are not answered have None as choice, option, time, and rt.
+    #[pyo3(name = "export")]
+    fn py_export<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let grid = slf.as_super().0.lock();
+        let grid = grid.downcast_ref::<LikertStimulus>().expect("downcast failed");
+        grid.items()
+            .iter()
+            .zip(grid.responses())
+            .map(|(item, response)| {
+                let dict = PyDict::new(py);
+                dict.set_item("item", item)?;
+                dict.set_item("choice", response.choice)?;
+                dict.set_item("option", response.choice.map(|choice| &grid.options()[choice]))?;
+                dict.set_item("time", response.time.map(Timestamp::from))?;
+                let rt = match (response.time, grid.shown()) {
+                    (Some(time), Some(shown)) => Some(time.saturating_duration_since(shown).as_secs_f64()),
+                    _ => None,
+                };
+                dict.set_item("rt", rt)?;
+                dict.set_item("changes", response.changes)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Mark the rows that are not answered, until they are.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   Whether all rows are answered.
+    #[pyo3(name = "validate")]
+    fn py_validate(slf: PyRefMut<'_, Self>) -> bool {
+        downcast_py_stimulus_mut!(slf, LikertStimulus).validate()
+    }
+
+    /// Pass an input event to the grid, e.g. from an event receiver.
+    ///
+    /// Parameters
+    /// ----------
+    /// event : Event
+    ///   The event.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   Whether the grid used the event.
+    #[pyo3(name = "handle_event")]
+    fn py_handle_event(slf: PyRefMut<'_, Self>, event: Event) -> bool {
+        downcast_py_stimulus_mut!(slf, LikertStimulus).handle_event(&event)
+    }
+
+    /// Pass the input events of a window to the grid as they arrive, until `detach` is called.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window that the grid is shown on.
+    #[pyo3(name = "attach")]
+    fn py_attach(slf: PyRef<'_, Self>, window: Window) -> PyResult<()> {
+        Ok(LikertStimulus::attach(&slf.as_super().0, &window)?)
+    }
+
+    /// Stop passing the input events of the window to the grid.
+    #[pyo3(name = "detach")]
+    fn py_detach(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, LikertStimulus).detach();
+    }
+
+    /// Discard all responses, e.g. to show the grid again. Reaction times are measured from when
+    /// the grid is drawn next.
+    #[pyo3(name = "reset")]
+    fn py_reset(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, LikertStimulus).reset();
+    }
+
+    fn __len__(slf: PyRef<'_, Self>) -> usize {
+        downcast_stimulus!(slf, LikertStimulus).items().len()
+    }
+}
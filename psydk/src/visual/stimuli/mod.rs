@@ -1,32 +1,53 @@
 use std::{
-    sync::{Arc, Mutex, MutexGuard},
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
     time::Instant,
 };
 
 use animations::{Animation, Repeat, TransitionFunction};
+#[cfg(feature = "python")]
 use numpy::PyUntypedArrayMethods;
 #[macro_use]
 use uuid::Uuid;
 
 use dyn_clone::DynClone;
+#[cfg(feature = "python")]
 use pyo3::{exceptions::PyValueError, prelude::*, types::PyString};
-use renderer::{image::GenericImageView, DynamicScene};
+use renderer::{image::GenericImageView, styles::BlendMode, DynamicScene};
 use strum_macros::{Display, EnumString};
 
 use super::{
     geometry::{IntoSize, Size, Transformation2D},
     window::{Frame, Window, WindowState},
 };
-use crate::visual::color::LinRgba;
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    input::{Event, EventHandler, EventHandlerId, EventKind},
+    visual::color::LinRgba,
+};
 
 pub mod animations;
-mod helpers;
+pub(crate) mod helpers;
+pub(crate) mod widgets;
 
+pub mod clock;
+pub mod form;
 pub mod gabor;
+pub mod group;
 // pub mod grid;
 pub mod image;
+pub mod keypad;
+pub mod likert;
+pub mod noise;
 pub mod pattern;
+pub mod rich_text;
+pub mod scroll_view;
+pub mod shape;
 // pub mod sprite;
+pub mod tagged;
 pub mod text;
 // pub mod vector;
 pub mod video;
@@ -56,6 +77,7 @@ pub enum StrokeStyle {
 }
 
 // implement IntoPy for StrokeStyle (by converting it to a string)
+#[cfg(feature = "python")]
 impl<'py> IntoPyObject<'py> for StrokeStyle {
     type Target = PyString;
     type Output = Bound<'py, Self::Target>;
@@ -68,6 +90,7 @@ impl<'py> IntoPyObject<'py> for StrokeStyle {
 }
 
 // implement FromPyObject for StrokeStyle (by parsing it from a string)
+#[cfg(feature = "python")]
 impl<'p> FromPyObject<'p> for StrokeStyle {
     fn extract_bound(ob: &Bound<'p, PyAny>) -> PyResult<Self> {
         let s = ob.extract::<String>()?;
@@ -91,6 +114,49 @@ impl StimulusParamValue {
             _ => false,
         }
     }
+
+    /// Convert a Python value to a parameter of the given type (the name of the variant, as
+    /// returned by `StimulusParams::param_type`). Sizes can be given in pixels or as strings with
+    /// a unit, e.g. "2deg".
+    #[cfg(feature = "python")]
+    pub fn extract(kind: &str, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        match kind {
+            "Size" => Ok(StimulusParamValue::Size(value.extract::<IntoSize>()?.into())),
+            "f64" => Ok(StimulusParamValue::f64(value.extract()?)),
+            "String" => Ok(StimulusParamValue::String(value.extract()?)),
+            "bool" => Ok(StimulusParamValue::bool(value.extract()?)),
+            "i64" => Ok(StimulusParamValue::i64(value.extract()?)),
+            "LinRgba" => Ok(StimulusParamValue::LinRgba(
+                value.extract::<crate::visual::color::IntoLinRgba>()?.into(),
+            )),
+            "Shape" => Ok(StimulusParamValue::Shape(value.extract()?)),
+            "StrokeStyle" => Ok(StimulusParamValue::StrokeStyle(value.extract()?)),
+            _ => Err(PyValueError::new_err(format!(
+                "parameters of type {} cannot be set from Python",
+                kind
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'py> IntoPyObject<'py> for StimulusParamValue {
+    type Target = PyAny;
+    type Output = Bound<'py, Self::Target>;
+    type Error = pyo3::PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(match self {
+            StimulusParamValue::Size(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::f64(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::String(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::bool(val) => pyo3::types::PyBool::new(py, val).to_owned().into_any(),
+            StimulusParamValue::i64(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::LinRgba(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::Shape(val) => val.into_pyobject(py)?.into_any(),
+            StimulusParamValue::StrokeStyle(val) => val.into_pyobject(py)?.into_any(),
+        })
+    }
 }
 
 pub struct IntoStimulusParamValue(pub StimulusParamValue);
@@ -101,6 +167,7 @@ impl From<IntoStimulusParamValue> for StimulusParamValue {
     }
 }
 
+#[cfg(feature = "python")]
 impl<'py> FromPyObject<'py> for IntoStimulusParamValue {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
         if let Ok(value) = ob.extract::<f64>() {
@@ -134,10 +201,28 @@ impl<'py> FromPyObject<'py> for IntoStimulusParamValue {
 }
 
 pub trait StimulusParams {
+    /// The names of all parameters.
+    fn param_names(&self) -> &'static [&'static str];
+    /// The name of the `StimulusParamValue` variant of a parameter, or None if the parameter
+    /// does not exist.
+    fn param_type(&self, name: &str) -> Option<&'static str>;
     fn get_param(&self, name: &str) -> Option<StimulusParamValue>;
     fn set_param(&mut self, name: &str, value: StimulusParamValue);
 }
 
+/// Where a stimulus is drawn and where `contains` reports hits, in the coordinates of the scene.
+/// Used by the layout debug mode of the window.
+#[derive(Debug, Clone, Default)]
+pub struct StimulusLayout {
+    /// The corners of the bounding box, after transformations.
+    pub bounds: Vec<(f32, f32)>,
+    /// The point that the position (and anchor) of the stimulus refers to.
+    pub anchor: Option<(f32, f32)>,
+    /// The corners of the region in which `contains` returns true, or None if the stimulus does
+    /// not support hit testing.
+    pub hit_region: Option<Vec<(f32, f32)>>,
+}
+
 /// The stimulus trait.
 pub trait Stimulus: downcast_rs::Downcast + std::fmt::Debug + Send {
     /// Draw the stimulus onto the scene.
@@ -151,6 +236,12 @@ pub trait Stimulus: downcast_rs::Downcast + std::fmt::Debug + Send {
         false
     }
 
+    /// The layout of the stimulus (and of its children, if any) for the layout debug mode. By
+    /// default, stimuli report no layout and are not outlined.
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        Vec::new()
+    }
+
     /// Return the UUID that identifies the stimulus.
     fn uuid(&self) -> Uuid;
 
@@ -159,6 +250,11 @@ pub trait Stimulus: downcast_rs::Downcast + std::fmt::Debug + Send {
         self.uuid() == other.uuid()
     }
 
+    /// Returns the stimuli that are part of this stimulus (e.g., the children of a group).
+    fn children(&self) -> &[DynamicStimulus] {
+        &[]
+    }
+
     /// Returns true if the stimulus is currently visible.
     fn visible(&self) -> bool {
         true
@@ -275,6 +371,17 @@ pub trait Stimulus: downcast_rs::Downcast + std::fmt::Debug + Send {
         self.set_transformation(Transformation2D::ShearPoint(x, y, x0, y0));
     }
 
+    /// The names of all parameters of the stimulus.
+    fn param_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The type of a parameter (the name of the `StimulusParamValue` variant), or None if the
+    /// stimulus has no such parameter.
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        None
+    }
+
     /// Get a parameter of the stimulus.
     fn get_param(&self, name: &str) -> Option<StimulusParamValue>;
 
@@ -285,23 +392,280 @@ pub trait Stimulus: downcast_rs::Downcast + std::fmt::Debug + Send {
 downcast_rs::impl_downcast!(Stimulus);
 
 #[derive(Debug, Clone)]
-pub struct DynamicStimulus(Arc<Mutex<dyn Stimulus>>);
+pub struct DynamicStimulus(Arc<Mutex<dyn Stimulus>>, Arc<StimulusShared>);
+
+/// Properties that are managed outside of the stimulus itself. Shared between all clones of the
+/// stimulus.
+pub struct StimulusShared {
+    /// The onset and offset handlers of the stimulus.
+    event_handlers: Mutex<HashMap<EventHandlerId, (EventKind, EventHandler)>>,
+    /// The depth of the stimulus. Stimuli with a higher z are drawn on top.
+    z: AtomicI32,
+    /// The opacity of the stimulus (stored as the bits of an `f64`), applied on top of any opacity
+    /// parameter of the stimulus.
+    opacity: AtomicU64,
+    /// The fade that is currently running, if any.
+    fade: Mutex<Option<Fade>>,
+    /// Parameter changes that are applied together when the stimulus is next presented.
+    staged_params: Mutex<Vec<(String, StimulusParamValue)>>,
+}
+
+impl Default for StimulusShared {
+    fn default() -> Self {
+        Self {
+            event_handlers: Mutex::new(HashMap::new()),
+            z: AtomicI32::new(0),
+            opacity: AtomicU64::new(1.0f64.to_bits()),
+            fade: Mutex::new(None),
+            staged_params: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// An animation of the opacity of a stimulus.
+#[derive(Debug)]
+struct Fade {
+    animation: Animation,
+    /// Hide the stimulus (and reset the opacity) once the fade has finished.
+    hide_when_done: bool,
+}
+
+impl std::fmt::Debug for StimulusShared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StimulusShared")
+            .field("event_handlers", &self.event_handlers.lock().unwrap().len())
+            .field("z", &self.z.load(Ordering::Relaxed))
+            .field("opacity", &f64::from_bits(self.opacity.load(Ordering::Relaxed)))
+            .field("staged_params", &self.staged_params.lock().unwrap())
+            .finish()
+    }
+}
 
 /// Wraps a Stimulus. This class is used either as a base class for other
 /// stimulus classes or as a standalone class, when no specific runtume type
 /// information is available.
+#[cfg(feature = "python")]
 #[pyclass(name = "Stimulus", subclass, module = "psydk.visual.stimuli")]
 #[derive(Debug, Clone)]
 pub struct PyStimulus(DynamicStimulus);
 
+/// Check that the duration of a fade is finite and not negative, as the fade would never finish
+/// otherwise.
+fn check_fade_duration(duration: f64) -> PsydkResult<()> {
+    if duration.is_finite() && duration >= 0.0 {
+        Ok(())
+    } else {
+        Err(PsydkError::ParameterError(format!(
+            "Invalid fade duration {duration}, must be finite and non-negative"
+        )))
+    }
+}
+
 impl DynamicStimulus {
     pub fn new(stimulus: impl Stimulus + 'static) -> Self {
-        Self(Arc::new(Mutex::new(stimulus)))
+        Self(Arc::new(Mutex::new(stimulus)), Arc::default())
     }
 
     pub fn lock(&self) -> MutexGuard<dyn Stimulus> {
         self.0.lock().unwrap()
     }
+
+    /// Returns true if both refer to the same stimulus.
+    pub fn ptr_eq(&self, other: &DynamicStimulus) -> bool {
+        Arc::as_ptr(&self.0) as *const () == Arc::as_ptr(&other.0) as *const ()
+    }
+
+    /// Returns true if `other` is this stimulus or one of its descendants (e.g., a child of a group).
+    pub fn is_or_contains(&self, other: &DynamicStimulus) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        let children = self.lock().children().to_vec();
+        children.iter().any(|child| child.is_or_contains(other))
+    }
+
+    /// Add a handler that is called with the frame onset when the stimulus first appears on the
+    /// screen (`EventKind::Onset`) or when it stops being drawn (`EventKind::Offset`).
+    pub fn add_event_handler<F>(&self, kind: EventKind, handler: F) -> PsydkResult<EventHandlerId>
+    where
+        F: Fn(Event) -> bool + 'static + Send + Sync,
+    {
+        if kind != EventKind::Onset && kind != EventKind::Offset {
+            return Err(PsydkError::ParameterError(format!(
+                "Stimuli only support onset and offset event handlers, not {}",
+                kind
+            )));
+        }
+
+        let mut event_handlers = self.1.event_handlers.lock().unwrap();
+
+        // find a free id
+        let id = loop {
+            let id = rand::random::<EventHandlerId>();
+            if !event_handlers.contains_key(&id) {
+                break id;
+            }
+        };
+
+        event_handlers.insert(id, (kind, Arc::new(handler)));
+
+        Ok(id)
+    }
+
+    /// Remove an onset or offset handler.
+    pub fn remove_event_handler(&self, id: EventHandlerId) {
+        self.1.event_handlers.lock().unwrap().remove(&id);
+    }
+
+    /// Return all handlers for the given event kind.
+    pub fn event_handlers(&self, kind: EventKind) -> Vec<EventHandler> {
+        self.1
+            .event_handlers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, handler)| handler.clone())
+            .collect()
+    }
+
+    /// The depth of the stimulus. Within a frame, stimuli with a higher z are drawn on top of
+    /// stimuli with a lower z. Stimuli with the same z are drawn in the order they were added.
+    pub fn z(&self) -> i32 {
+        self.1.z.load(Ordering::Relaxed)
+    }
+
+    /// Set the depth of the stimulus.
+    pub fn set_z(&self, z: i32) {
+        self.1.z.store(z, Ordering::Relaxed);
+    }
+
+    /// The opacity of the stimulus between 0 and 1. This is applied to the stimulus as a whole,
+    /// on top of any opacity or alpha parameter of the stimulus.
+    pub fn opacity(&self) -> f64 {
+        f64::from_bits(self.1.opacity.load(Ordering::Relaxed))
+    }
+
+    /// Set the opacity of the stimulus. This stops a running fade.
+    pub fn set_opacity(&self, opacity: f64) {
+        *self.1.fade.lock().unwrap() = None;
+        self.store_opacity(opacity);
+    }
+
+    fn store_opacity(&self, opacity: f64) {
+        self.1
+            .opacity
+            .store(opacity.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Show the stimulus and fade its opacity from 0 to 1 over `duration` seconds. A duration of 0
+    /// shows the stimulus at full opacity immediately.
+    pub fn fade_in(&self, duration: f64) -> PsydkResult<()> {
+        check_fade_duration(duration)?;
+        self.lock().show();
+        if duration == 0.0 {
+            self.set_opacity(1.0);
+        } else {
+            self.store_opacity(0.0);
+            self.start_fade(0.0, 1.0, duration, false);
+        }
+        Ok(())
+    }
+
+    /// Fade the opacity of the stimulus to 0 over `duration` seconds, then hide the stimulus and
+    /// reset its opacity. A duration of 0 hides the stimulus immediately.
+    pub fn fade_out(&self, duration: f64) -> PsydkResult<()> {
+        check_fade_duration(duration)?;
+        if duration == 0.0 {
+            self.lock().hide();
+            self.set_opacity(1.0);
+        } else {
+            self.start_fade(self.opacity(), 0.0, duration, true);
+        }
+        Ok(())
+    }
+
+    fn start_fade(&self, from: f64, to: f64, duration: f64, hide_when_done: bool) {
+        let animation = Animation::new(
+            "opacity",
+            StimulusParamValue::f64(from),
+            StimulusParamValue::f64(to),
+            duration,
+            Instant::now(),
+            Repeat::Loop(1),
+            TransitionFunction::None,
+        );
+        *self.1.fade.lock().unwrap() = Some(Fade {
+            animation,
+            hide_when_done,
+        });
+    }
+
+    /// Stage parameter changes. They are applied together (while the stimulus is locked) when the
+    /// stimulus is next presented, so that a frame never shows only some of the changes.
+    pub fn stage_params(&self, params: impl IntoIterator<Item = (String, StimulusParamValue)>) {
+        self.1.staged_params.lock().unwrap().extend(params);
+    }
+
+    /// Apply all staged parameter changes, including those of child stimuli.
+    pub fn apply_staged_params(&self) {
+        let staged = std::mem::take(&mut *self.1.staged_params.lock().unwrap());
+
+        let children = {
+            let mut stimulus = self.lock();
+            for (name, value) in staged {
+                stimulus.set_param(&name, value);
+            }
+            stimulus.children().to_vec()
+        };
+
+        for child in children {
+            child.apply_staged_params();
+        }
+    }
+
+    /// Update the running fade and the animations of the stimulus.
+    pub fn update_animations(&self, time: Instant, window_state: &WindowState) {
+        let mut stimulus = self.lock();
+
+        let mut fade = self.1.fade.lock().unwrap();
+        if let Some(running) = fade.as_ref() {
+            if let StimulusParamValue::f64(opacity) = running.animation.value(time, window_state) {
+                self.store_opacity(opacity);
+            }
+            if running.animation.finished(time) {
+                if running.hide_when_done {
+                    stimulus.hide();
+                    self.store_opacity(1.0);
+                }
+                *fade = None;
+            }
+        }
+        drop(fade);
+
+        stimulus.update_animations(time, window_state);
+    }
+
+    /// Draw the stimulus onto the scene. If the opacity of the stimulus is below 1, it is drawn
+    /// into a layer with that opacity.
+    pub fn draw(&self, scene: &mut DynamicScene, window_state: &WindowState) {
+        let opacity = self.opacity();
+        let mut stimulus = self.lock();
+
+        if opacity >= 1.0 {
+            stimulus.draw(scene, window_state);
+        } else if opacity > 0.0 {
+            scene.start_layer(
+                BlendMode::SourceOver,
+                helpers::window_clip(window_state),
+                None,
+                None,
+                opacity as f32,
+            );
+            stimulus.draw(scene, window_state);
+            scene.end_layer();
+        }
+    }
 }
 
 // #[pymethods]
@@ -323,6 +687,7 @@ impl DynamicStimulus {
 
 // }
 
+#[cfg(feature = "python")]
 impl PyStimulus {
     pub fn new(stimulus: impl Stimulus + 'static) -> Self {
         Self(DynamicStimulus::new(stimulus))
@@ -333,6 +698,13 @@ impl PyStimulus {
     }
 }
 
+#[cfg(feature = "python")]
+impl From<DynamicStimulus> for PyStimulus {
+    fn from(stimulus: DynamicStimulus) -> Self {
+        Self(stimulus)
+    }
+}
+
 macro_rules! downcast_stimulus {
     ($slf:ident, $name:ident) => {
         $slf.as_super()
@@ -364,7 +736,9 @@ macro_rules! impl_pystimulus_for_wrapper {
     ($wrapper:ident, $name:ident) => {
         use std::mem;
 
-        use pyo3::types::{PyAny, PyBool};
+        #[cfg(feature = "python")]
+        use pyo3::types::PyAny;
+        #[cfg(feature = "python")]
         use pyo3::{exceptions::PyValueError, prelude::*};
 
         use crate::visual::{
@@ -376,132 +750,86 @@ macro_rules! impl_pystimulus_for_wrapper {
             window::Window,
         };
 
+        #[cfg(feature = "python")]
         #[pymethods]
         impl $wrapper {
+            /// Get a parameter of the stimulus, e.g. `stimulus["x"]`. Optional parameters that are not
+            /// set are returned as None.
             fn __getitem__(slf: PyRef<'_, Self>, name: &str) -> PyResult<Py<PyAny>> {
                 let py = slf.py();
-
-                let param = downcast_stimulus!(slf, $name).get_param(name);
-
-                // extract the value from the StimulusParam
-                match param {
-                    Some(StimulusParamValue::Size(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::f64(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::String(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::bool(val)) => Ok(PyBool::new(py, val)
-                        .to_owned()
-                        .into_pyobject(py)?
-                        .unbind()
-                        .into_any()),
-                    Some(StimulusParamValue::i64(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::LinRgba(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::Shape(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::StrokeStyle(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    Some(StimulusParamValue::Shape(val)) => Ok(val.into_pyobject(py)?.unbind().into_any()),
-                    None => Err(PyValueError::new_err("parameter not found")),
-                    _ => Err(PyValueError::new_err("parameter not supported")),
+                let stimulus = slf.as_super().0.lock();
+                if stimulus.param_type(name).is_none() {
+                    return Err(pyo3::exceptions::PyKeyError::new_err(format!(
+                        "unknown parameter {}",
+                        name
+                    )));
                 }
+                Ok(stimulus.get_param(name).into_pyobject(py)?.unbind())
             }
 
-            fn __setitem__(slf: Bound<Self>, name: &str, value: Py<PyAny>) -> PyResult<()> {
+            /// Set a parameter of the stimulus, e.g. `stimulus["width"] = "2deg"`. Sizes can be given
+            /// in pixels or as strings with a unit ("px", "vw", "vh", "height", "nw", "nh", "deg", "mm",
+            /// "cm", "in", "pt").
+            fn __setitem__(slf: PyRef<'_, Self>, name: &str, value: Bound<'_, PyAny>) -> PyResult<()> {
                 let py = slf.py();
+                let dynamic_stimulus = slf.as_super().0.clone();
 
-                // get DynamicStimulus from the wrapper
-                let dynamic_stimulus = slf.as_super().borrow().0.clone();
-
-                let current_val = py.allow_threads(move || dynamic_stimulus.0.lock().unwrap().get_param(name).unwrap());
-
-                let dynamic_stimulus = slf.as_super().borrow().0.clone();
+                let kind = dynamic_stimulus
+                    .lock()
+                    .param_type(name)
+                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(format!("unknown parameter {}", name)))?;
+                let value = StimulusParamValue::extract(kind, &value)?;
 
-                match current_val {
-                    StimulusParamValue::String(_) => {
-                        let value = value.extract::<String>(py)?;
-                        let value = StimulusParamValue::String(value);
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
-
-                        return Ok(());
-                    }
-                    StimulusParamValue::Size(_) => {
-                        let value = value.extract::<IntoSize>(py)?;
-                        let value = StimulusParamValue::Size(value.into());
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
-
-                        return Ok(());
-                    }
-                    StimulusParamValue::f64(_) => {
-                        let value = value.extract::<f64>(py)?;
-                        let value = StimulusParamValue::f64(value);
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
-
-                        return Ok(());
-                    }
-                    StimulusParamValue::bool(_) => {
-                        let value = value.extract::<bool>(py)?;
-                        let value = StimulusParamValue::bool(value);
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
-
-                        return Ok(());
-                    }
-                    StimulusParamValue::i64(_) => {
-                        let value = value.extract::<i64>(py)?;
-                        let value = StimulusParamValue::i64(value);
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
-
-                        return Ok(());
-                    }
-                    StimulusParamValue::LinRgba(_) => {
-                        let value = value.extract::<crate::visual::color::IntoLinRgba>(py)?;
-                        let value = StimulusParamValue::LinRgba(value.into());
-
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
+                py.allow_threads(move || dynamic_stimulus.lock().set_param(name, value));
+                Ok(())
+            }
 
-                        return Ok(());
+            /// Stage parameter changes that are applied together when the stimulus is next presented.
+            /// Unlike setting parameters one by one, this guarantees that no frame shows only some of
+            /// the changes, even if a presentation is in progress.
+            ///
+            /// Parameters
+            /// ----------
+            /// **params
+            ///   The parameters to change, e.g. `stimulus.stage(x="2deg", y="-1deg")`.
+            #[pyo3(signature = (**params))]
+            fn stage(slf: PyRef<'_, Self>, params: Option<&Bound<'_, pyo3::types::PyDict>>) -> PyResult<()> {
+                let Some(params) = params else {
+                    return Ok(());
+                };
+                let dynamic_stimulus = slf.as_super().0.clone();
+
+                let mut staged = Vec::with_capacity(params.len());
+                {
+                    let stimulus = dynamic_stimulus.lock();
+                    for (name, value) in params.iter() {
+                        let name: String = name.extract()?;
+                        let kind = stimulus.param_type(&name).ok_or_else(|| {
+                            pyo3::exceptions::PyKeyError::new_err(format!("unknown parameter {}", name))
+                        })?;
+                        staged.push((name, StimulusParamValue::extract(kind, &value)?));
                     }
-                    StimulusParamValue::Shape(_) => {
-                        let value = value.extract::<super::super::geometry::Shape>(py)?;
-                        let value = StimulusParamValue::Shape(value);
+                }
 
-                        py.allow_threads(move || {
-                            let mut ds = dynamic_stimulus.0.lock().unwrap();
-                            let ds = ds.downcast_mut::<$name>().expect("downcast failed");
-                            ds.set_param(name, value);
-                        });
+                dynamic_stimulus.stage_params(staged);
+                Ok(())
+            }
 
-                        return Ok(());
-                    }
-                    _ => {}
+            /// Return all parameters of the stimulus as a dictionary, e.g. to log the state of the
+            /// stimulus in each trial.
+            ///
+            /// Returns
+            /// -------
+            /// dict
+            ///   The parameters by name. Optional parameters that are not set are None.
+            fn params<'py>(slf: PyRef<'py, Self>) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+                let py = slf.py();
+                let dict = pyo3::types::PyDict::new(py);
+                let stimulus = slf.as_super().0.lock();
+                for name in stimulus.param_names() {
+                    dict.set_item(*name, stimulus.get_param(name))?;
                 }
-
-                return Err(PyValueError::new_err("parameter not found"));
+                Ok(dict)
             }
 
             /// Rotate the stimulus at a given point.
@@ -549,6 +877,89 @@ macro_rules! impl_pystimulus_for_wrapper {
                 downcast_stimulus!(slf, $name).contains(x.into(), y.into(), window)
             }
 
+            /// Add a handler that is called when the stimulus first appears on the screen or when it
+            /// stops being drawn. The handler receives an event with the onset timestamp of the frame.
+            /// It is called on a separate thread right after that frame has been presented, so it can
+            /// send a trigger while the frame is still being repeated. `present` returns once the
+            /// handler has returned.
+            ///
+            /// Parameters
+            /// ----------
+            /// kind : str
+            ///   Either "onset" or "offset".
+            /// callback : callable
+            ///   The function to call. It receives the event as its only argument.
+            ///
+            /// Returns
+            /// -------
+            /// int
+            ///   The id of the handler, which can be used to remove it again.
+            fn add_event_handler(
+                slf: PyRef<'_, Self>,
+                kind: crate::input::EventKind,
+                callback: Py<PyAny>,
+            ) -> PyResult<crate::input::EventHandlerId> {
+                let handler = move |event: crate::input::Event| -> bool {
+                    Python::with_gil(|py| crate::visual::window::call_event_callback(py, &callback, event));
+                    false
+                };
+                Ok(slf.as_super().0.add_event_handler(kind, handler)?)
+            }
+
+            /// Remove an onset or offset handler.
+            ///
+            /// Parameters
+            /// ----------
+            /// id : int
+            ///   The id returned by `add_event_handler`.
+            fn remove_event_handler(slf: PyRef<'_, Self>, id: crate::input::EventHandlerId) {
+                slf.as_super().0.remove_event_handler(id);
+            }
+
+            /// The depth of the stimulus. Within a frame, stimuli with a higher z are drawn on top of
+            /// stimuli with a lower z. Stimuli with the same z are drawn in the order they were added.
+            #[getter(z)]
+            fn py_get_z(slf: PyRef<'_, Self>) -> i32 {
+                slf.as_super().0.z()
+            }
+
+            #[setter(z)]
+            fn py_set_z(slf: PyRef<'_, Self>, z: i32) {
+                slf.as_super().0.set_z(z);
+            }
+
+            /// The opacity of the stimulus between 0 and 1, applied on top of any opacity or alpha
+            /// parameter of the stimulus. Setting the opacity stops a running fade.
+            #[getter(opacity)]
+            fn py_get_opacity(slf: PyRef<'_, Self>) -> f64 {
+                slf.as_super().0.opacity()
+            }
+
+            #[setter(opacity)]
+            fn py_set_opacity(slf: PyRef<'_, Self>, opacity: f64) {
+                slf.as_super().0.set_opacity(opacity);
+            }
+
+            /// Show the stimulus and fade it in.
+            ///
+            /// Parameters
+            /// ----------
+            /// duration : float
+            ///   The duration of the fade in seconds.
+            fn fade_in(slf: PyRef<'_, Self>, duration: f64) -> PyResult<()> {
+                Ok(slf.as_super().0.fade_in(duration)?)
+            }
+
+            /// Fade the stimulus out and hide it once the fade has finished.
+            ///
+            /// Parameters
+            /// ----------
+            /// duration : float
+            ///   The duration of the fade in seconds.
+            fn fade_out(slf: PyRef<'_, Self>, duration: f64) -> PyResult<()> {
+                Ok(slf.as_super().0.fade_out(duration)?)
+            }
+
             /// Animate a parameter of the stimulus.
             /// The parameter must be a valid parameter of the stimulus.
             ///
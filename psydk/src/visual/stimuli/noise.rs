@@ -0,0 +1,468 @@
+use std::time::{Duration, Instant};
+
+use psydk_proc::{FromPyStr, StimulusParams};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use renderer::{
+    affine::Affine,
+    brushes::{Brush, Extend, ImageSampling},
+    image::{ImageBuffer, Rgba},
+    shapes::Shape,
+    styles::ImageFitMode,
+    DynamicBitmap, DynamicScene,
+};
+use rustfft::{num_complex::Complex, FftDirection};
+use strum::EnumString;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, Stimulus, StimulusParamValue, StimulusParams,
+};
+use crate::visual::{
+    color::{IntoLinRgba, LinRgba},
+    geometry::{Anchor, Size, Transformation2D},
+    window::WindowState,
+};
+
+/// The distribution of the noise.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum NoiseType {
+    /// Each element is either the low or the high color.
+    Binary,
+    /// Uniformly distributed values.
+    White,
+    /// Normally distributed values.
+    Gaussian,
+    /// Noise with a 1/f amplitude spectrum.
+    Pink,
+    /// Smooth gradient noise.
+    Perlin,
+}
+
+/// When the noise is regenerated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseUpdate {
+    /// The noise is only generated once (and again when its size changes).
+    Never,
+    /// The noise is regenerated whenever the stimulus is drawn.
+    EveryFrame,
+    /// The noise is regenerated at a fixed rate, given as the interval between updates.
+    Rate(Duration),
+}
+
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for NoiseUpdate {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if ob.is_none() {
+            Ok(NoiseUpdate::Never)
+        } else if let Ok(rate) = ob.extract::<f64>() {
+            // the interval is negative or infinite for rates that are not positive, and also
+            // infinite for tiny rates
+            Duration::try_from_secs_f64(1.0 / rate)
+                .map(NoiseUpdate::Rate)
+                .map_err(|_| PyValueError::new_err(format!("Invalid update rate {rate}, must be positive")))
+        } else if ob.extract::<String>().is_ok_and(|update| update == "frame") {
+            Ok(NoiseUpdate::EveryFrame)
+        } else {
+            Err(PyValueError::new_err(
+                "update_rate must be None, \"frame\", or a rate in Hz",
+            ))
+        }
+    }
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct NoiseParams {
+    pub x: Size,
+    pub y: Size,
+    pub width: Size,
+    pub height: Size,
+    /// The size of a single noise element.
+    pub element_size: Size,
+    /// The size of the features of Perlin noise.
+    pub feature_size: Size,
+    pub color_low: LinRgba,
+    pub color_high: LinRgba,
+    pub alpha: f64,
+}
+
+#[derive(Debug)]
+pub struct NoiseStimulus {
+    id: uuid::Uuid,
+    params: NoiseParams,
+    noise_type: NoiseType,
+    update: NoiseUpdate,
+    rng: StdRng,
+    /// The current noise texture, its size in elements, and when it was generated.
+    texture: Option<(DynamicBitmap, (u32, u32), Instant)>,
+    /// Forces the noise to be regenerated the next time it is drawn.
+    stale: bool,
+    anchor: Anchor,
+    transform: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+unsafe impl Send for NoiseStimulus {}
+
+impl NoiseStimulus {
+    pub fn new(
+        params: NoiseParams,
+        noise_type: NoiseType,
+        update: NoiseUpdate,
+        seed: Option<u64>,
+        anchor: Anchor,
+        transform: Transformation2D,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            params,
+            noise_type,
+            update,
+            rng: seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+            texture: None,
+            stale: true,
+            anchor,
+            transform,
+            animations: Vec::new(),
+            visible: true,
+        }
+    }
+
+    /// Generate a new sample of the noise the next time the stimulus is drawn.
+    pub fn regenerate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Whether the texture needs to be generated for a noise of `size` elements.
+    fn needs_update(&self, size: (u32, u32), now: Instant) -> bool {
+        let Some((_, texture_size, generated)) = &self.texture else {
+            return true;
+        };
+
+        self.stale
+            || *texture_size != size
+            || match self.update {
+                NoiseUpdate::Never => false,
+                NoiseUpdate::EveryFrame => true,
+                NoiseUpdate::Rate(interval) => now.duration_since(*generated) >= interval,
+            }
+    }
+}
+
+/// Generate `width` x `height` noise values between 0 and 1.
+fn generate_noise(noise_type: NoiseType, width: u32, height: u32, feature_size: f64, rng: &mut StdRng) -> Vec<f32> {
+    let n = (width * height) as usize;
+    match noise_type {
+        NoiseType::Binary => (0..n).map(|_| if rng.gen::<bool>() { 1.0 } else { 0.0 }).collect(),
+        NoiseType::White => (0..n).map(|_| rng.gen::<f32>()).collect(),
+        // values within 3 standard deviations of the mean span the range between the two colors
+        NoiseType::Gaussian => (0..n)
+            .map(|_| (0.5 + gaussian(rng) / 6.0).clamp(0.0, 1.0) as f32)
+            .collect(),
+        NoiseType::Pink => pink_noise(width as usize, height as usize, rng),
+        NoiseType::Perlin => perlin_noise(width as usize, height as usize, feature_size.max(1.0), rng),
+    }
+}
+
+/// A standard normally distributed value (Box-Muller transform).
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Noise with a 1/f amplitude spectrum and random phases, created in the Fourier domain. Like
+/// Gaussian noise, values within 3 standard deviations span the range from 0 to 1.
+fn pink_noise(width: usize, height: usize, rng: &mut StdRng) -> Vec<f32> {
+    let frequency = |index: usize, count: usize| index.min(count - index) as f64 / count as f64;
+
+    let mut spectrum = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for col in 0..width {
+            let f = frequency(col, width).hypot(frequency(row, height));
+            let amplitude = if f > 0.0 { 1.0 / f } else { 0.0 };
+            spectrum.push(Complex::new(gaussian(rng), gaussian(rng)) * amplitude);
+        }
+    }
+
+    helpers::fft_2d(&mut spectrum, width, height, FftDirection::Inverse);
+
+    let values = spectrum.iter().map(|c| c.re).collect::<Vec<_>>();
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let sd = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+    values
+        .iter()
+        .map(|v| {
+            let z = if sd > 0.0 { (v - mean) / sd } else { 0.0 };
+            (0.5 + z / 6.0).clamp(0.0, 1.0) as f32
+        })
+        .collect()
+}
+
+/// Perlin noise with random gradients on a lattice whose cells are `feature_size` elements wide.
+fn perlin_noise(width: usize, height: usize, feature_size: f64, rng: &mut StdRng) -> Vec<f32> {
+    let cols = (width as f64 / feature_size).ceil() as usize + 1;
+    let rows = (height as f64 / feature_size).ceil() as usize + 1;
+    let gradients = (0..cols * rows)
+        .map(|_| {
+            let angle = rng.gen::<f64>() * 2.0 * std::f64::consts::PI;
+            (angle.cos(), angle.sin())
+        })
+        .collect::<Vec<_>>();
+
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+    let mut values = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for col in 0..width {
+            let x = (col as f64 + 0.5) / feature_size;
+            let y = (row as f64 + 0.5) / feature_size;
+            let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+            let (dx, dy) = (x - x0 as f64, y - y0 as f64);
+
+            let dot = |cx: usize, cy: usize| {
+                let (gx, gy) = gradients[cy * cols + cx];
+                gx * (x - cx as f64) + gy * (y - cy as f64)
+            };
+
+            let top = lerp(dot(x0, y0), dot(x0 + 1, y0), fade(dx));
+            let bottom = lerp(dot(x0, y0 + 1), dot(x0 + 1, y0 + 1), fade(dx));
+            let value = lerp(top, bottom, fade(dy));
+
+            // 2D Perlin noise lies within ±sqrt(1/2)
+            values.push((0.5 + value / 2f64.sqrt()).clamp(0.0, 1.0) as f32);
+        }
+    }
+    values
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "NoiseStimulus", extends=PyStimulus)]
+/// A rectangle filled with a random noise texture.
+///
+/// Parameters
+/// ----------
+/// width : Size
+///     The width of the stimulus.
+/// height : Size
+///     The height of the stimulus.
+/// x : Size, optional
+///     The x position of the stimulus.
+/// y : Size, optional
+///     The y position of the stimulus.
+/// noise_type : str, optional
+///     One of "binary", "white" (uniform), "gaussian", "pink" (1/f), or "perlin".
+/// element_size : Size, optional
+///     The size of a single noise element.
+/// feature_size : Size, optional
+///     The size of the features of Perlin noise.
+/// color_low : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
+///     The color of the lowest noise value.
+/// color_high : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
+///     The color of the highest noise value.
+/// alpha : float, optional
+///     The opacity of the stimulus.
+/// update_rate : Union[float, str], optional
+///     How often the noise is regenerated: None to keep it, "frame" to regenerate it whenever the
+///     stimulus is drawn, or a rate in Hz.
+/// seed : int, optional
+///     Seed for the random number generator, to show the same sequence of noise again.
+/// anchor : str, optional
+///     The point of the stimulus that lies at (x, y).
+/// transform : Transformation2D, optional
+///     The transformation of the stimulus.
+pub struct PyNoiseStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyNoiseStimulus {
+    #[new]
+    #[pyo3(signature = (
+        width,
+        height,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        noise_type = NoiseType::Binary,
+        element_size = IntoSize(Size::Pixels(1.0)),
+        feature_size = IntoSize(Size::Pixels(32.0)),
+        color_low = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        color_high = IntoLinRgba::new(1.0, 1.0, 1.0, 1.0),
+        alpha = 1.0,
+        update_rate = NoiseUpdate::Never,
+        seed = None,
+        anchor = Anchor::Center,
+        transform = Transformation2D::Identity(),
+    ))]
+    fn __new__(
+        width: IntoSize,
+        height: IntoSize,
+        x: IntoSize,
+        y: IntoSize,
+        noise_type: NoiseType,
+        element_size: IntoSize,
+        feature_size: IntoSize,
+        color_low: IntoLinRgba,
+        color_high: IntoLinRgba,
+        alpha: f64,
+        update_rate: NoiseUpdate,
+        seed: Option<u64>,
+        anchor: Anchor,
+        transform: Transformation2D,
+    ) -> (Self, PyStimulus) {
+        let params = NoiseParams {
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
+            element_size: element_size.into(),
+            feature_size: feature_size.into(),
+            color_low: color_low.into(),
+            color_high: color_high.into(),
+            alpha,
+        };
+
+        (
+            Self(),
+            PyStimulus::new(NoiseStimulus::new(
+                params,
+                noise_type,
+                update_rate,
+                seed,
+                anchor,
+                transform,
+            )),
+        )
+    }
+
+    /// Generate a new sample of the noise the next time the stimulus is drawn.
+    #[pyo3(name = "regenerate")]
+    fn py_regenerate(mut slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, NoiseStimulus).regenerate();
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyNoiseStimulus, NoiseStimulus);
+
+impl Stimulus for NoiseStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let x = self.params.x.eval(window_size, screen_props);
+        let y = self.params.y.eval(window_size, screen_props);
+        let width = self.params.width.eval(window_size, screen_props);
+        let height = self.params.height.eval(window_size, screen_props);
+        let element_size = self.params.element_size.eval(window_size, screen_props).max(1.0);
+
+        // one texel per noise element
+        let size = (
+            (width / element_size).ceil().max(1.0) as u32,
+            (height / element_size).ceil().max(1.0) as u32,
+        );
+
+        let now = Instant::now();
+        if self.needs_update(size, now) {
+            let feature_size = (self.params.feature_size.eval(window_size, screen_props) / element_size) as f64;
+            let values = generate_noise(self.noise_type, size.0, size.1, feature_size, &mut self.rng);
+
+            let (low, high) = (self.params.color_low, self.params.color_high);
+            let image = ImageBuffer::from_fn(size.0, size.1, |col, row| {
+                let t = values[(row * size.0 + col) as usize];
+                Rgba([
+                    low.r + (high.r - low.r) * t,
+                    low.g + (high.g - low.g) * t,
+                    low.b + (high.b - low.b) * t,
+                    low.a + (high.a - low.a) * t,
+                ])
+            });
+            let bitmap = window_state
+                .shared_renderer_state
+                .create_bitmap_f32(image, renderer::renderer::ColorSpace::LinearSrgb);
+            self.texture = Some((bitmap, size, now));
+            self.stale = false;
+        }
+
+        let (left, top) = helpers::anchored_top_left(
+            self.anchor,
+            (x, y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+        let transform: Affine = self.transform.eval(window_size, screen_props).into();
+        let (bitmap, size, _) = self.texture.as_ref().unwrap();
+
+        scene.draw_shape_fill(
+            Shape::rectangle((left, top), width as f64, height as f64),
+            Brush::Image {
+                image: bitmap,
+                start: (left, top).into(),
+                // elements keep their size, so the last row and column may be cut off
+                fit_mode: ImageFitMode::Exact {
+                    width: size.0 as f32 * element_size,
+                    height: size.1 as f32 * element_size,
+                },
+                sampling: ImageSampling::Nearest,
+                edge_mode: (Extend::Pad, Extend::Pad),
+                transform: None,
+                alpha: Some(self.params.alpha as f32),
+            },
+            Some(transform),
+            None,
+        );
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transform = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transform.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
@@ -14,15 +14,17 @@ use uuid::Uuid;
 
 unsafe impl Send for PatternStimulus {}
 
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
-    animations::Animation, helpers, impl_pystimulus_for_wrapper, PyStimulus, Stimulus, StimulusParamValue,
-    StimulusParams, StrokeStyle,
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, Stimulus, StimulusParamValue, StimulusParams,
+    StrokeStyle,
 };
 use crate::{
     context::ExperimentContext,
     visual::{
         color::{IntoLinRgba, LinRgba},
-        geometry::{Shape, Size, Transformation2D},
+        geometry::{Anchor, Shape, Size, Transformation2D},
         window::{Frame, WindowState},
     },
 };
@@ -61,6 +63,7 @@ pub struct PatternStimulus {
 
     gradient_colors: Option<Vec<LinRgba>>,
     pattern_image: Option<DynamicBitmap>,
+    anchor: Option<Anchor>,
     transform: Transformation2D,
     animations: Vec<Animation>,
     visible: bool,
@@ -82,6 +85,7 @@ impl PatternStimulus {
         stroke_color: LinRgba,
         stroke_width: Size,
         alpha: Option<f64>,
+        anchor: Option<Anchor>,
         transform: Transformation2D,
         context: &ExperimentContext,
     ) -> Self {
@@ -105,6 +109,7 @@ impl PatternStimulus {
             fill_pattern: pattern,
             gradient_colors: None,
             pattern_image: None,
+            anchor,
             transform,
             animations: Vec::new(),
             visible: true,
@@ -160,6 +165,7 @@ impl PatternStimulus {
 }
 
 #[derive(Debug, Clone)]
+#[cfg(feature = "python")]
 #[pyclass(name = "PatternStimulus", extends=PyStimulus)]
 /// A stimulus that displays a shape.
 ///
@@ -168,9 +174,9 @@ impl PatternStimulus {
 /// shape : Shape
 ///     The shape to display.
 /// x : Size, optional
-///     The x-coordinate of the center of the shape.
+///     The x-coordinate the shape is positioned relative to.
 /// y : Size, optional
-///     The y-coordinate of the center of the shape.
+///     The y-coordinate the shape is positioned relative to.
 /// fill_color : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
 ///    The fill color of the shape.
 /// stroke_style : StrokeStyle, optional
@@ -181,10 +187,14 @@ impl PatternStimulus {
 ///  The stroke width of the shape.
 /// alpha : float, optional
 ///  The alpha channel of the shape.
+/// anchor : str, optional
+///  If given, the shape is moved so that this point of its bounding box (e.g. "center" or
+///  "top-left") lies at (x, y). Otherwise, the coordinates of the shape are relative to (x, y).
 /// transform : Transformation2D, optional
 /// The transformation of the shape.
 pub struct PyPatternStimulus();
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyPatternStimulus {
     #[new]
@@ -203,6 +213,7 @@ impl PyPatternStimulus {
         stroke_color = IntoLinRgba(LinRgba::default()),
         stroke_width = IntoSize(Size::Pixels(0.0)),
         alpha = None,
+        anchor = None,
         transform = Transformation2D::Identity(),
         context = None,
     ))]
@@ -213,9 +224,9 @@ impl PyPatternStimulus {
     /// shape : Shape
     ///     The shape to display.
     /// x : Size, optional
-    ///     The x-coordinate of the center of the shape.
+    ///     The x-coordinate the shape is positioned relative to.
     /// y : Size, optional
-    ///     The y-coordinate of the center of the shape.
+    ///     The y-coordinate the shape is positioned relative to.
     /// fill_color : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
     ///    The fill color of the shape.
     /// stroke_style : StrokeStyle, optional
@@ -226,6 +237,9 @@ impl PyPatternStimulus {
     ///    The stroke width of the shape.
     /// alpha : float, optional
     ///    The alpha channel of the shape.
+    /// anchor : str, optional
+    ///    If given, the shape is moved so that this point of its bounding box (e.g. "center" or
+    ///    "top-left") lies at (x, y). Otherwise, the coordinates of the shape are relative to (x, y).
     /// transform : Transformation2D, optional
     ///    The transformation of the shape.
     fn __new__(
@@ -244,6 +258,7 @@ impl PyPatternStimulus {
         stroke_color: IntoLinRgba,
         stroke_width: IntoSize,
         alpha: Option<f64>,
+        anchor: Option<Anchor>,
         transform: Transformation2D,
         context: Option<ExperimentContext>,
     ) -> (Self, PyStimulus) {
@@ -265,6 +280,7 @@ impl PyPatternStimulus {
                 stroke_color.into(),
                 stroke_width.into(),
                 alpha,
+                anchor,
                 transform,
                 &context,
             )),
@@ -297,8 +313,12 @@ impl Stimulus for PatternStimulus {
 
         let renderer_factory = &window_state.shared_renderer_state;
 
-        let x_origin = self.params.x.eval(windows_size, screen_props) as f64;
-        let y_origin = self.params.y.eval(windows_size, screen_props) as f64;
+        let x_origin = self.params.x.eval(windows_size, screen_props);
+        let y_origin = self.params.y.eval(windows_size, screen_props);
+
+        // positions are given in the coordinate system of the window
+        let place = helpers::place_shape(&self.params.shape, (x_origin, y_origin), self.anchor, window_state);
+        let (scene_x_origin, scene_y_origin) = place(0.0, 0.0);
 
         let pattern_size = self.params.pattern_size.eval(windows_size, screen_props);
 
@@ -312,7 +332,7 @@ impl Stimulus for PatternStimulus {
             FillPattern::Sinosoidal => todo!(),
             FillPattern::Checkerboard | FillPattern::Stripes => Brush::Image {
                 image: &self.pattern_image.as_ref().unwrap(),
-                start: (x_origin + shift_x, y_origin + shift_y).into(),
+                start: (scene_x_origin + shift_x, scene_y_origin + shift_y).into(),
                 fit_mode: ImageFitMode::Exact {
                     width: pattern_size,
                     height: pattern_size,
@@ -332,99 +352,14 @@ impl Stimulus for PatternStimulus {
 
         let stroke_options = renderer::styles::StrokeStyle::new(stroke_width);
 
-        match &self.params.shape {
-            Shape::Circle { x, y, radius } => {
-                let x = x.eval(windows_size, screen_props) as f64;
-                let y = y.eval(windows_size, screen_props) as f64;
-                let radius = radius.eval(windows_size, screen_props) as f64;
-
-                // move by x_origin and y_origin
-                let x = x + x_origin;
-                let y = y + y_origin;
-
-                let shape = renderer::shapes::Shape::circle((x, y), radius);
-
-                scene.draw_shape_fill(shape.clone(), fill_brush.clone(), None, None);
-
-                scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
-            }
-            Shape::Rectangle { x, y, width, height } => {
-                let x = x.eval(windows_size, screen_props) as f64;
-                let y = y.eval(windows_size, screen_props) as f64;
-                let width = width.eval(windows_size, screen_props) as f64;
-                let height = height.eval(windows_size, screen_props) as f64;
-
-                // move by x_origin and y_origin
-                let x = x + x_origin;
-                let y = y + y_origin;
-
-                let shape = renderer::shapes::Shape::rectangle((x, y), width, height);
-
-                scene.draw_shape_fill(shape.clone(), fill_brush.clone(), None, None);
-
-                scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
-            }
-            Shape::Ellipse {
-                x,
-                y,
-                radius_x,
-                radius_y,
-            } => {
-                todo!("Render ellipse")
-            }
-            Shape::Line { x1, y1, x2, y2 } => {
-                let x1 = x1.eval(windows_size, screen_props) as f64;
-                let y1 = y1.eval(windows_size, screen_props) as f64;
-                let x2 = x2.eval(windows_size, screen_props) as f64;
-                let y2 = y2.eval(windows_size, screen_props) as f64;
-
-                // move by x_origin and y_origin
-                let x1 = x1 + x_origin;
-                let y1 = y1 + y_origin;
-                let x2 = x2 + x_origin;
-                let y2 = y2 + y_origin;
-
-                let shape = renderer::shapes::Shape::line((x1, y1), (x2, y2));
+        let shape = helpers::scene_shape(&self.params.shape, window_state, &place);
 
-                scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
-            }
-            Shape::Polygon { points } => {
-                let points = points
-                    .iter()
-                    .map(|p| {
-                        let x = p.0.eval(windows_size, screen_props) as f64;
-                        let y = p.1.eval(windows_size, screen_props) as f64;
-
-                        // move by x_origin and y_origin
-                        (x + x_origin, y + y_origin).into()
-                    })
-                    .collect::<Vec<(f64, f64)>>();
-
-                let shape = renderer::shapes::Shape::polygon(points);
-
-                scene.draw_shape_fill(shape.clone(), fill_brush.clone(), None, None);
-
-                scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
-            }
-            Shape::Path { points } => {
-                let points = points
-                    .iter()
-                    .map(|p| {
-                        let x = p.0.eval(windows_size, screen_props) as f64;
-                        let y = p.1.eval(windows_size, screen_props) as f64;
-
-                        // move by x_origin and y_origin
-                        (x + x_origin, y + y_origin).into()
-                    })
-                    .collect::<Vec<(f64, f64)>>();
-
-                let shape = renderer::shapes::Shape::path(points);
-
-                scene.draw_shape_fill(shape.clone(), fill_brush.clone(), None, None);
+        // lines have no area to fill
+        if !matches!(self.params.shape, Shape::Line { .. }) {
+            scene.draw_shape_fill(shape.clone(), fill_brush, None, None);
+        }
 
-                scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
-            }
-        };
+        scene.draw_shape_stroke(shape, stroke_brush, stroke_options, None, None);
     }
     fn set_visible(&mut self, visible: bool) {
         self.visible = visible;
@@ -442,6 +377,14 @@ impl Stimulus for PatternStimulus {
         self.transform.clone()
     }
 
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
     fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
         self.params.get_param(name)
     }
@@ -0,0 +1,565 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use cosmic_text::Attrs as ComsicAttrs;
+use cosmic_text::Buffer as CosmicBuffer;
+use cosmic_text::Family as CosmicFamily;
+use cosmic_text::FontSystem as CosmicFontSystem;
+use cosmic_text::Metrics as CosmicMetrics;
+use cosmic_text::Style as CosmicStyle;
+use cosmic_text::Weight as CosmicWeight;
+use psydk_proc::StimulusParams;
+use renderer::brushes::{Brush, Extend, ImageSampling};
+use renderer::colors::RGBA;
+use renderer::shapes::Shape;
+use renderer::styles::ImageFitMode;
+use renderer::{DynamicBitmap, DynamicScene};
+use uuid::Uuid;
+
+use super::text::fallback_face;
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, Stimulus, StimulusParamValue, StimulusParams,
+};
+use crate::context::ExperimentContext;
+use crate::errors::PsydkResult;
+use crate::visual::color::{IntoLinRgba, LinRgba};
+use crate::visual::geometry::{Anchor, Size, Transformation2D};
+use crate::visual::window::{Frame, WindowState};
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct RichTextParams {
+    pub x: Size,
+    pub y: Size,
+    /// The width at which lines are wrapped.
+    pub width: Size,
+    /// The font size of paragraphs. Headings are larger.
+    pub font_size: Size,
+    pub fill_color: LinRgba,
+    pub alpha: f64,
+}
+
+/// The emphasis of a run of text.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct SpanStyle {
+    bold: bool,
+    italic: bool,
+}
+
+type Spans = Vec<(String, SpanStyle)>;
+
+/// A block of a Markdown document.
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading {
+        level: usize,
+        spans: Spans,
+    },
+    Paragraph(Spans),
+    /// A list item, with its bullet or number.
+    ListItem {
+        marker: String,
+        spans: Spans,
+    },
+    Image {
+        src: String,
+    },
+}
+
+/// Parse the Markdown subset supported by `RichTextStimulus`: headings (`#` to `######`),
+/// paragraphs, bulleted (`-`, `*`, `+`) and numbered (`1.`) lists, `**bold**`, `*italic*`, and
+/// images (`![alt](src)`), which are placed on their own line.
+fn parse_markdown(markdown: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+
+    fn flush(paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>) {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(parse_inline(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+    }
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+            continue;
+        }
+
+        // headings
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading {
+                level,
+                spans: parse_inline(trimmed[level..].trim()),
+            });
+            continue;
+        }
+
+        // list items
+        let bullet = ["- ", "* ", "+ "]
+            .iter()
+            .find(|bullet| trimmed.starts_with(*bullet))
+            .map(|bullet| ("\u{2022}".to_string(), &trimmed[bullet.len()..]));
+        let number = trimmed.split_once(". ").and_then(|(number, rest)| {
+            (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then(|| (format!("{}.", number), rest))
+        });
+        if let Some((marker, rest)) = bullet.or(number) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::ListItem {
+                marker,
+                spans: parse_inline(rest.trim()),
+            });
+            continue;
+        }
+
+        // images are split out of the line
+        let mut rest = trimmed;
+        while let Some((before, src, after)) = find_image(rest) {
+            if !before.trim().is_empty() {
+                paragraph.push(before.trim());
+            }
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Image { src: src.to_string() });
+            rest = after;
+        }
+        if !rest.trim().is_empty() {
+            paragraph.push(rest.trim());
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+
+    blocks
+}
+
+/// Find the first image (`![alt](src)`) in `text`. Returns the text before it, its source, and the
+/// text after it.
+fn find_image(text: &str) -> Option<(&str, &str, &str)> {
+    let start = text.find("![")?;
+    let alt_end = start + text[start..].find("](")?;
+    let src_end = alt_end + text[alt_end..].find(')')?;
+    Some((&text[..start], &text[alt_end + 2..src_end], &text[src_end + 1..]))
+}
+
+/// Split `text` into runs of equal emphasis. `**` and `__` toggle bold, `*` and `_` toggle italic
+/// (`_` only at the start or end of a word), and a backslash escapes the next character.
+fn parse_inline(text: &str) -> Spans {
+    let mut spans: Spans = Vec::new();
+    let mut style = SpanStyle::default();
+    let mut current = String::new();
+    let chars = text.chars().collect::<Vec<_>>();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let previous = i.checked_sub(1).map(|j| chars[j]);
+
+        let toggle = match c {
+            '\\' if next.is_some() => {
+                current.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '*' | '_' if next == Some(c) => Some((true, 2)),
+            '*' => Some((false, 1)),
+            '_' if !previous.map_or(false, char::is_alphanumeric) || !next.map_or(false, char::is_alphanumeric) => {
+                Some((false, 1))
+            }
+            _ => None,
+        };
+
+        match toggle {
+            Some((bold, len)) => {
+                if !current.is_empty() {
+                    spans.push((std::mem::take(&mut current), style));
+                }
+                if bold {
+                    style.bold = !style.bold;
+                } else {
+                    style.italic = !style.italic;
+                }
+                i += len;
+            }
+            None => {
+                current.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !current.is_empty() {
+        spans.push((current, style));
+    }
+
+    spans
+}
+
+#[derive(Debug)]
+pub struct RichTextStimulus {
+    id: uuid::Uuid,
+    params: RichTextParams,
+    blocks: Vec<Block>,
+    font_family: String,
+    /// The images of the document, with their size in pixels.
+    images: HashMap<String, (DynamicBitmap, u32, u32)>,
+    anchor: Anchor,
+    faces: HashMap<cosmic_text::fontdb::ID, renderer::font::DynamicFontFace>,
+    font_manager: Arc<Mutex<CosmicFontSystem>>,
+    transform: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+unsafe impl Send for RichTextStimulus {}
+
+impl RichTextStimulus {
+    /// Create a new rich text stimulus. Relative image paths are resolved against `base_path`.
+    pub fn new(
+        x: Size,
+        y: Size,
+        width: Size,
+        markdown: &str,
+        font_size: Size,
+        font_family: &str,
+        fill_color: LinRgba,
+        alpha: f64,
+        anchor: Anchor,
+        base_path: Option<&Path>,
+        transform: Transformation2D,
+        context: &ExperimentContext,
+    ) -> PsydkResult<Self> {
+        let blocks = parse_markdown(markdown);
+
+        let mut images = HashMap::new();
+        for block in &blocks {
+            if let Block::Image { src } = block {
+                if images.contains_key(src) {
+                    continue;
+                }
+                let path = match base_path {
+                    Some(base_path) => base_path.join(src),
+                    None => Path::new(src).to_path_buf(),
+                };
+                let image = image::open(path)?.to_rgba8();
+                let (width, height) = image.dimensions();
+                let bitmap = context
+                    .renderer_factory()
+                    .create_bitmap_u8(image, renderer::renderer::ColorSpace::Srgb);
+                images.insert(src.clone(), (bitmap, width, height));
+            }
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            params: RichTextParams {
+                x,
+                y,
+                width,
+                font_size,
+                fill_color,
+                alpha,
+            },
+            blocks,
+            font_family: font_family.to_string(),
+            images,
+            anchor,
+            faces: HashMap::new(),
+            font_manager: context.font_manager().clone(),
+            transform,
+            animations: Vec::new(),
+            visible: true,
+        })
+    }
+}
+
+/// A stimulus that renders a subset of Markdown, e.g. for instruction screens.
+///
+/// Supported are headings (`#` to `######`), paragraphs (separated by empty lines), bulleted
+/// (`-`, `*`, or `+`) and numbered (`1.`) lists, `**bold**` and `*italic*` text, and images
+/// (`![description](path/to/image.png)`), which are shown on their own line at their original size
+/// (or scaled down to the width of the stimulus). Bold and italic text need bold and italic faces
+/// of the font family to be loaded.
+///
+/// Parameters
+/// ----------
+/// markdown : str
+///   The text to display.
+/// width : Size
+///   The width of the stimulus. Lines are wrapped at this width.
+/// font_size : Size
+///   The font size of paragraphs and lists. Headings are up to twice as large.
+/// font_family : str, optional
+///   The font family. Defaults to "Noto Sans".
+/// base_path : str, optional
+///   The directory that relative image paths are resolved against. Defaults to the current
+///   working directory.
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "RichTextStimulus", extends=PyStimulus)]
+pub struct PyRichTextStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyRichTextStimulus {
+    #[new]
+    #[pyo3(signature = (
+        markdown,
+        width,
+        font_size,
+        font_family = "Noto Sans".to_string(),
+        base_path = None,
+        alpha = 1.0,
+        anchor = Anchor::Center,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        fill_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        transform = Transformation2D::Identity(),
+        context = None,
+    ))]
+    fn __new__(
+        py: Python,
+        markdown: &str,
+        width: IntoSize,
+        font_size: IntoSize,
+        font_family: String,
+        base_path: Option<std::path::PathBuf>,
+        alpha: f64,
+        anchor: Anchor,
+        x: IntoSize,
+        y: IntoSize,
+        fill_color: IntoLinRgba,
+        transform: Transformation2D,
+        context: Option<ExperimentContext>,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let context = helpers::get_experiment_context(context, py)?;
+        Ok((
+            Self(),
+            PyStimulus::new(RichTextStimulus::new(
+                x.into(),
+                y.into(),
+                width.into(),
+                markdown,
+                font_size.into(),
+                &font_family,
+                fill_color.into(),
+                alpha,
+                anchor,
+                base_path.as_deref(),
+                transform,
+                &context,
+            )?),
+        ))
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyRichTextStimulus, RichTextStimulus);
+
+/// An image of the document, positioned relative to the top left corner of the stimulus.
+struct PlacedImage<'a> {
+    bitmap: &'a DynamicBitmap,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Stimulus for RichTextStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let width = self.params.width.eval(window_size, screen_props);
+        let font_size = self.params.font_size.eval(window_size, screen_props);
+        let trans_mat = self.transform.eval(window_size, screen_props);
+        let fill_color: RGBA = self.params.fill_color.into();
+
+        let font_manager = self.font_manager.clone();
+        let mut font_manager = font_manager.lock().unwrap();
+
+        // glyphs are drawn per font and font size, relative to the top left corner of the stimulus
+        let mut glyphs: Vec<((cosmic_text::fontdb::ID, u32), Vec<renderer::font::Glyph>)> = vec![];
+        let mut images: Vec<PlacedImage> = vec![];
+        let spacing = 0.6 * font_size;
+        let mut top = 0.0;
+
+        let mut shape =
+            |font_manager: &mut CosmicFontSystem, spans: &Spans, size: f32, heading: bool, x: f32, top: f32| {
+                let mut buffer = CosmicBuffer::new(font_manager, CosmicMetrics::new(size, size * 1.3));
+                buffer.set_size(font_manager, Some((width - x).max(size)), None);
+
+                let attrs = ComsicAttrs::new().family(CosmicFamily::Name(&self.font_family));
+                let span_attrs = spans.iter().map(|(text, style)| {
+                    let weight = if style.bold || heading {
+                        CosmicWeight::BOLD
+                    } else {
+                        CosmicWeight::NORMAL
+                    };
+                    let style = if style.italic {
+                        CosmicStyle::Italic
+                    } else {
+                        CosmicStyle::Normal
+                    };
+                    (text.as_str(), attrs.weight(weight).style(style))
+                });
+                buffer.set_rich_text(font_manager, span_attrs, attrs, cosmic_text::Shaping::Advanced);
+                buffer.shape_until_scroll(font_manager, true);
+
+                let mut height: f32 = 0.0;
+                for run in buffer.layout_runs() {
+                    for glyph in run.glyphs {
+                        let glyph_out = renderer::font::Glyph {
+                            id: glyph.glyph_id,
+                            position: (
+                                x + glyph.x + glyph.x_offset * glyph.font_size,
+                                top + run.line_y + glyph.y - glyph.y_offset * glyph.font_size,
+                            )
+                                .into(),
+                            rotation: 0.0,
+                        };
+                        let key = (glyph.font_id, glyph.font_size.to_bits());
+                        match glyphs.iter_mut().find(|(k, _)| *k == key) {
+                            Some((_, font_glyphs)) => font_glyphs.push(glyph_out),
+                            None => glyphs.push((key, vec![glyph_out])),
+                        }
+                    }
+                    height = height.max(run.line_top + run.line_height);
+                }
+                height
+            };
+
+        for block in &self.blocks {
+            let height = match block {
+                Block::Heading { level, spans } => {
+                    let scale = [2.0, 1.5, 1.25, 1.0, 1.0, 1.0][level - 1];
+                    shape(&mut font_manager, spans, font_size * scale, true, 0.0, top)
+                }
+                Block::Paragraph(spans) => shape(&mut font_manager, spans, font_size, false, 0.0, top),
+                Block::ListItem { marker, spans } => {
+                    let marker = vec![(marker.clone(), SpanStyle::default())];
+                    shape(&mut font_manager, &marker, font_size, false, 0.5 * font_size, top);
+                    shape(&mut font_manager, spans, font_size, false, 2.0 * font_size, top)
+                }
+                Block::Image { src } => {
+                    let (bitmap, image_width, image_height) = &self.images[src];
+                    let scale = (width / *image_width as f32).min(1.0);
+                    let (image_width, image_height) = (*image_width as f32 * scale, *image_height as f32 * scale);
+                    images.push(PlacedImage {
+                        bitmap,
+                        x: (width - image_width) / 2.0,
+                        y: top,
+                        width: image_width,
+                        height: image_height,
+                    });
+                    image_height
+                }
+            };
+            top += height + spacing;
+        }
+        let height = (top - spacing).max(0.0);
+
+        let pos_x = self.params.x.eval(window_size, screen_props);
+        let pos_y = self.params.y.eval(window_size, screen_props);
+        let (origin_x, origin_y) = helpers::anchored_top_left(
+            self.anchor,
+            (pos_x, pos_y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+
+        for image in images {
+            let (x, y) = (origin_x + image.x, origin_y + image.y);
+            scene.draw_shape_fill(
+                Shape::Rectangle {
+                    a: (x, y).into(),
+                    w: image.width as f64,
+                    h: image.height as f64,
+                },
+                Brush::Image {
+                    image: image.bitmap,
+                    start: (x, y).into(),
+                    fit_mode: ImageFitMode::Exact {
+                        width: image.width,
+                        height: image.height,
+                    },
+                    sampling: ImageSampling::Linear,
+                    edge_mode: (Extend::Pad, Extend::Pad),
+                    transform: None,
+                    alpha: Some(self.params.alpha as f32),
+                },
+                Some(trans_mat.into()),
+                None,
+            );
+        }
+
+        for ((font_id, size), font_glyphs) in glyphs {
+            let Some(font) = fallback_face(&mut self.faces, &mut font_manager, font_id, window_state) else {
+                continue;
+            };
+            scene.draw_glyphs(
+                (origin_x, origin_y).into(),
+                &font_glyphs,
+                font,
+                f32::from_bits(size),
+                Brush::Solid(fill_color),
+                Some(self.params.alpha as f32),
+                Some(trans_mat.into()),
+                None,
+            );
+        }
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: crate::visual::geometry::Transformation2D) {
+        self.transform = transformation;
+    }
+
+    fn transformation(&self) -> crate::visual::geometry::Transformation2D {
+        self.transform.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
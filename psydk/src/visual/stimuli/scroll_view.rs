@@ -0,0 +1,782 @@
+//! Scrollable viewports.
+//!
+//! A scroll view draws its children clipped to a rectangle and moved by the scroll offset. The
+//! offset is limited to the extent of the children, so that the content can be scrolled from its
+//! first to its last line but not beyond. Dragging can continue with inertia after the pointer is
+//! released, slowing down exponentially, like on touch screens.
+
+use std::time::Instant;
+
+use psydk_proc::{FromPyStr, StimulusParams};
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+use renderer::{styles::BlendMode, DynamicScene};
+use strum::EnumString;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation,
+    helpers, impl_pystimulus_for_wrapper,
+    widgets::{fill, Attachment, Interactive, Pointer, Rect, Viewport, DRAG_THRESHOLD},
+    DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
+};
+use crate::{
+    errors::PsydkResult,
+    input::Event,
+    time::Timestamp,
+    visual::{
+        color::{IntoLinRgba, LinRgba},
+        geometry::{Anchor, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// Inertial scrolling stops below this speed, in pixels per second.
+const MIN_SPEED: f64 = 5.0;
+/// Pointer movements that are older than this (in seconds) when the pointer is released don't
+/// count towards the speed of inertial scrolling.
+const VELOCITY_WINDOW: f64 = 0.1;
+/// The width of the scroll bars in pixels.
+const SCROLLBAR_WIDTH: f32 = 6.0;
+
+/// The directions in which a scroll view can be scrolled.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ScrollDirection {
+    Vertical,
+    Horizontal,
+    Both,
+}
+
+impl ScrollDirection {
+    fn horizontal(&self) -> bool {
+        matches!(self, ScrollDirection::Horizontal | ScrollDirection::Both)
+    }
+
+    fn vertical(&self) -> bool {
+        matches!(self, ScrollDirection::Vertical | ScrollDirection::Both)
+    }
+}
+
+/// A change of the scroll offset.
+#[derive(Debug, Clone, Copy)]
+pub struct ScrollRecord {
+    pub time: Instant,
+    pub scroll_x: f64,
+    pub scroll_y: f64,
+}
+
+/// A pointer that is pressed on the view.
+#[derive(Debug, Clone)]
+struct Drag {
+    start: (f32, f32),
+    start_scroll: (f64, f64),
+    dragging: bool,
+    /// Recent pointer positions, for the speed at release.
+    samples: Vec<(Instant, (f32, f32))>,
+}
+
+/// Where the view was drawn last.
+#[derive(Debug, Clone, Copy)]
+struct ScrollLayout {
+    viewport: Viewport,
+    bounds: Rect,
+    /// The smallest and largest scroll offsets.
+    range_x: (f64, f64),
+    range_y: (f64, f64),
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct ScrollViewParams {
+    pub x: Size,
+    pub y: Size,
+    pub width: Size,
+    pub height: Size,
+    /// How far the content is scrolled to the right, in pixels.
+    pub scroll_x: f64,
+    /// How far the content is scrolled down, in pixels.
+    pub scroll_y: f64,
+    /// The color of the scroll bars, or transparent to hide them.
+    pub scrollbar_color: LinRgba,
+}
+
+/// Shows its children clipped to a rectangle that can be scrolled.
+#[derive(Debug)]
+pub struct ScrollViewStimulus {
+    id: Uuid,
+    params: ScrollViewParams,
+    children: Vec<DynamicStimulus>,
+    anchor: Anchor,
+    direction: ScrollDirection,
+    /// The time constant (in seconds) with which inertial scrolling slows down, or None for no
+    /// inertia.
+    inertia: Option<f64>,
+    /// How far one line of mouse wheel movement scrolls, in pixels.
+    wheel_step: f64,
+    /// Whether the arrow keys, Page Up, Page Down, Home, and End scroll.
+    keys: bool,
+    drag: Option<Drag>,
+    /// The speed of inertial scrolling in pixels per second, and when it was last applied.
+    velocity: Option<((f64, f64), Instant)>,
+    reached_end: bool,
+    log: Vec<ScrollRecord>,
+    layout: Option<ScrollLayout>,
+    attachment: Option<Attachment>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl ScrollViewStimulus {
+    pub fn new(
+        children: Vec<DynamicStimulus>,
+        anchor: Anchor,
+        direction: ScrollDirection,
+        inertia: Option<f64>,
+        wheel_step: f64,
+        keys: bool,
+        params: ScrollViewParams,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            params,
+            children,
+            anchor,
+            direction,
+            inertia: inertia.filter(|inertia| *inertia > 0.0),
+            wheel_step,
+            keys,
+            drag: None,
+            velocity: None,
+            reached_end: false,
+            log: Vec::new(),
+            layout: None,
+            attachment: None,
+            transformation: Transformation2D::Identity(),
+            animations: Vec::new(),
+            visible: true,
+        }
+    }
+
+    /// Add a child. The child is drawn after (i.e., on top of) the existing children.
+    pub fn add(&mut self, child: DynamicStimulus) {
+        self.children.push(child);
+    }
+
+    /// Remove a child. Returns false if the stimulus is not part of the view.
+    pub fn remove(&mut self, child: &DynamicStimulus) -> bool {
+        let len = self.children.len();
+        self.children.retain(|c| !c.ptr_eq(child));
+        self.children.len() != len
+    }
+
+    /// The scroll offset in pixels (to the right and down).
+    pub fn scroll(&self) -> (f64, f64) {
+        (self.params.scroll_x, self.params.scroll_y)
+    }
+
+    /// Scroll to an offset, limited to the extent of the content. Stops inertial scrolling.
+    pub fn scroll_to(&mut self, x: f64, y: f64, time: Instant) {
+        self.velocity = None;
+        self.set_scroll(x, y, time);
+    }
+
+    fn set_scroll(&mut self, x: f64, y: f64, time: Instant) {
+        let (x, y) = match &self.layout {
+            Some(layout) => (
+                x.clamp(layout.range_x.0, layout.range_x.1),
+                y.clamp(layout.range_y.0, layout.range_y.1),
+            ),
+            None => (x, y),
+        };
+        let (x, y) = (
+            if self.direction.horizontal() { x } else { 0.0 },
+            if self.direction.vertical() { y } else { 0.0 },
+        );
+        if (x, y) == self.scroll() {
+            return;
+        }
+        self.params.scroll_x = x;
+        self.params.scroll_y = y;
+        self.log.push(ScrollRecord {
+            time,
+            scroll_x: x,
+            scroll_y: y,
+        });
+        self.check_end();
+    }
+
+    fn check_end(&mut self) {
+        if let Some(layout) = &self.layout {
+            self.reached_end |= self.params.scroll_x >= layout.range_x.1 && self.params.scroll_y >= layout.range_y.1;
+        }
+    }
+
+    /// Whether the content has been scrolled to its end (or fits into the view), e.g. to only let
+    /// participants continue once they have seen all instructions.
+    pub fn reached_end(&self) -> bool {
+        self.reached_end
+    }
+
+    /// Every change of the scroll offset.
+    pub fn log(&self) -> &[ScrollRecord] {
+        &self.log
+    }
+
+    /// Pass the input events of `window` to `view` as they arrive, until `detach` is called.
+    pub fn attach(view: &DynamicStimulus, window: &Window) -> PsydkResult<()> {
+        // created before the view is locked, as it needs the state of the window
+        let attachment = Attachment::new::<Self>(window, view)?;
+        if let Some(view) = view.lock().downcast_mut::<Self>() {
+            view.attachment = Some(attachment);
+        }
+        Ok(())
+    }
+
+    /// Stop passing input events to the view.
+    pub fn detach(&mut self) {
+        self.attachment = None;
+    }
+
+    /// The rectangle of the view in the coordinates of the scene.
+    fn bounds(&self, window_state: &WindowState) -> Rect {
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let (width, height) = (eval(&self.params.width), eval(&self.params.height));
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (eval(&self.params.x), eval(&self.params.y)),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+        Rect::new(x, y, width, height)
+    }
+
+    /// Run `f` on a child that is moved by the scroll offset (on top of its own transformation).
+    fn with_scroll_transformation<R>(&self, child: &DynamicStimulus, f: impl FnOnce(&DynamicStimulus) -> R) -> R {
+        let own_transformation = child.lock().transformation();
+        let scroll = Transformation2D::Translation(
+            Size::Pixels(-self.params.scroll_x as f32),
+            Size::Pixels(-self.params.scroll_y as f32),
+        );
+        child
+            .lock()
+            .set_transformation(self.transformation.clone() * scroll * own_transformation.clone());
+        let result = f(child);
+        child.lock().set_transformation(own_transformation);
+        result
+    }
+
+    /// The extent of the children (without scrolling) in the coordinates of the scene, as the
+    /// smallest and largest x and y.
+    fn content_extent(&self, window_state: &WindowState) -> Option<((f32, f32), (f32, f32))> {
+        let points: Vec<(f32, f32)> = self
+            .children
+            .iter()
+            .filter(|child| child.lock().visible())
+            .flat_map(|child| child.lock().layout(window_state))
+            .flat_map(|layout| layout.bounds)
+            .collect();
+        let (first, rest) = points.split_first()?;
+        Some(rest.iter().fold(
+            ((first.0, first.0), (first.1, first.1)),
+            |((x0, x1), (y0, y1)), (x, y)| ((x0.min(*x), x1.max(*x)), (y0.min(*y), y1.max(*y))),
+        ))
+    }
+
+    fn pointer(&mut self, pointer: Pointer, position: (f32, f32), time: Instant) -> bool {
+        match pointer {
+            Pointer::Down => {
+                let inside = self.layout.is_some_and(|layout| layout.bounds.contains(position));
+                if !inside {
+                    return false;
+                }
+                // grabbing the content stops it
+                self.velocity = None;
+                self.drag = Some(Drag {
+                    start: position,
+                    start_scroll: self.scroll(),
+                    dragging: false,
+                    samples: vec![(time, position)],
+                });
+                true
+            }
+            Pointer::Move => {
+                let Some(drag) = self.drag.as_mut() else {
+                    return false;
+                };
+                let (dx, dy) = (position.0 - drag.start.0, position.1 - drag.start.1);
+                drag.dragging |= dx.abs().max(dy.abs()) > DRAG_THRESHOLD;
+                drag.samples.push((time, position));
+                drag.samples
+                    .retain(|(sample_time, _)| time.duration_since(*sample_time).as_secs_f64() <= VELOCITY_WINDOW);
+                if !drag.dragging {
+                    return false;
+                }
+                let (x, y) = (drag.start_scroll.0 - dx as f64, drag.start_scroll.1 - dy as f64);
+                self.set_scroll(x, y, time);
+                true
+            }
+            Pointer::Up => {
+                let Some(drag) = self.drag.take() else {
+                    return false;
+                };
+                if !drag.dragging {
+                    return false;
+                }
+                if let (Some(_), Some((first_time, first))) = (self.inertia, drag.samples.first()) {
+                    let duration = time.duration_since(*first_time).as_secs_f64();
+                    if duration > 0.0 {
+                        // the content keeps moving with the pointer, so the offset moves against it
+                        let velocity = (
+                            -(position.0 - first.0) as f64 / duration,
+                            -(position.1 - first.1) as f64 / duration,
+                        );
+                        self.velocity = Some((velocity, time));
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    fn key(&mut self, key: &str, time: Instant) -> bool {
+        let Some(layout) = self.layout else {
+            return false;
+        };
+        let (x, y) = self.scroll();
+        let page = 0.9 * layout.bounds.height as f64;
+        let (x, y) = match key {
+            "ArrowDown" => (x, y + self.wheel_step),
+            "ArrowUp" => (x, y - self.wheel_step),
+            "ArrowRight" => (x + self.wheel_step, y),
+            "ArrowLeft" => (x - self.wheel_step, y),
+            "PageDown" => (x, y + page),
+            "PageUp" => (x, y - page),
+            "Home" => (layout.range_x.0, layout.range_y.0),
+            "End" => (layout.range_x.1, layout.range_y.1),
+            _ => return false,
+        };
+        self.scroll_to(x, y, time);
+        true
+    }
+}
+
+impl Interactive for ScrollViewStimulus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if !self.visible {
+            return false;
+        }
+        let Some(layout) = self.layout else {
+            return false;
+        };
+        let time = event.timestamp().timestamp;
+
+        if let Some((pointer, position)) = layout.viewport.pointer(event) {
+            return self.pointer(pointer, position, time);
+        }
+        match event {
+            Event::MouseWheel {
+                horizontal, vertical, ..
+            } => {
+                let (x, y) = self.scroll();
+                // with only a vertical wheel, it scrolls horizontal views
+                let (dx, dy) = match self.direction {
+                    ScrollDirection::Horizontal if *horizontal == 0.0 => (*vertical, 0.0),
+                    _ => (*horizontal, *vertical),
+                };
+                let (x, y) = (x - dx as f64 * self.wheel_step, y - dy as f64 * self.wheel_step);
+                self.scroll_to(x, y, time);
+                true
+            }
+            Event::KeyPress { key, .. } if self.keys => self.key(key, time),
+            _ => false,
+        }
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyScrollViewStimulus, ScrollViewStimulus);
+
+impl Stimulus for ScrollViewStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let bounds = self.bounds(window_state);
+        let (range_x, range_y) = match self.content_extent(window_state) {
+            Some(((x0, x1), (y0, y1))) => (
+                (
+                    (x0 - bounds.x).min(0.0) as f64,
+                    (x1 - bounds.x - bounds.width).max(0.0) as f64,
+                ),
+                (
+                    (y0 - bounds.y).min(0.0) as f64,
+                    (y1 - bounds.y - bounds.height).max(0.0) as f64,
+                ),
+            ),
+            None => ((0.0, 0.0), (0.0, 0.0)),
+        };
+        self.layout = Some(ScrollLayout {
+            viewport: Viewport::new(window_state),
+            bounds,
+            range_x,
+            range_y,
+        });
+        // the content may have changed, so the offset is limited again
+        let (x, y) = self.scroll();
+        self.params.scroll_x = x.clamp(range_x.0, range_x.1);
+        self.params.scroll_y = y.clamp(range_y.0, range_y.1);
+        self.check_end();
+
+        scene.start_layer(BlendMode::SourceOver, bounds.shape(), None, None, 1.0);
+        for child in &self.children {
+            self.with_scroll_transformation(child, |child| child.draw(scene, window_state));
+        }
+        scene.end_layer();
+
+        // the scroll bars show which part of the content is visible
+        let color = self.params.scrollbar_color;
+        let thumb = |length: f32, range: (f64, f64), scroll: f64| {
+            let content = length + (range.1 - range.0) as f32;
+            let size = length * length / content;
+            let offset = (length - size) * ((scroll - range.0) / (range.1 - range.0)) as f32;
+            (offset, size)
+        };
+        if range_y.1 > range_y.0 {
+            let (offset, size) = thumb(bounds.height, range_y, self.params.scroll_y);
+            let bar = Rect::new(
+                bounds.x + bounds.width - 1.5 * SCROLLBAR_WIDTH,
+                bounds.y + offset,
+                SCROLLBAR_WIDTH,
+                size,
+            );
+            fill(scene, bar.rounded(SCROLLBAR_WIDTH / 2.0), color);
+        }
+        if range_x.1 > range_x.0 {
+            let (offset, size) = thumb(bounds.width, range_x, self.params.scroll_x);
+            let bar = Rect::new(
+                bounds.x + offset,
+                bounds.y + bounds.height - 1.5 * SCROLLBAR_WIDTH,
+                size,
+                SCROLLBAR_WIDTH,
+            );
+            fill(scene, bar.rounded(SCROLLBAR_WIDTH / 2.0), color);
+        }
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        let window_state = window.state.lock().unwrap();
+        let window_state = window_state.as_ref().unwrap();
+        let x = x.eval(window_state.size, window_state.physical_screen);
+        let y = y.eval(window_state.size, window_state.physical_screen);
+        let point = window_state.coordinate_system.to_scene(x, y, window_state.size);
+        self.bounds(window_state).contains(point)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let bounds = self.bounds(window_state);
+        let window_size = window_state.size;
+        let eval = |size: &Size| size.eval(window_size, window_state.physical_screen);
+        let anchor = window_state
+            .coordinate_system
+            .to_scene(eval(&self.params.x), eval(&self.params.y), window_size);
+        let identity = nalgebra::Matrix3::<f32>::identity();
+        vec![helpers::rect_layout(
+            (bounds.x, bounds.y, bounds.width, bounds.height),
+            anchor,
+            &identity,
+            &identity,
+        )]
+    }
+
+    fn update_animations(&mut self, time: Instant, window_state: &WindowState) {
+        let mut params_to_set = Vec::new();
+        self.animations.retain_mut(|animation| {
+            params_to_set.push((animation.parameter().to_string(), animation.value(time, window_state)));
+            !animation.finished(time)
+        });
+        for (param, value) in params_to_set {
+            self.set_param(&param, value);
+        }
+
+        // inertial scrolling slows down exponentially
+        if let (Some(((vx, vy), last)), Some(inertia)) = (self.velocity, self.inertia) {
+            let dt = time.saturating_duration_since(last).as_secs_f64();
+            let decay = (-dt / inertia).exp();
+            let distance = inertia * (1.0 - decay);
+            let (x, y) = self.scroll();
+            self.set_scroll(x + vx * distance, y + vy * distance, time);
+            let (vx, vy) = (vx * decay, vy * decay);
+            self.velocity = (vx.hypot(vy) >= MIN_SPEED).then_some(((vx, vy), time));
+        }
+
+        for child in &self.children {
+            child.update_animations(time, window_state);
+        }
+    }
+
+    fn children(&self) -> &[DynamicStimulus] {
+        &self.children
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "ScrollViewStimulus", extends=PyStimulus)]
+/// A rectangular viewport that shows its children clipped to the rectangle and can be scrolled,
+/// e.g. for long instructions, questionnaires, or texts in reading studies.
+///
+/// The content can be scrolled as far as the children extend beyond the view, but not further.
+/// It is scrolled with the mouse wheel, by dragging with the mouse or a finger (which continues
+/// with inertia after release, if enabled), and with the arrow keys, Page Up, Page Down, Home, and
+/// End. The view handles input events that are passed to `handle_event` or, after `attach`, all
+/// events of the window. The scroll offset is also available as the parameters "scroll_x" and
+/// "scroll_y", which can be set or animated like other parameters.
+///
+/// Children are positioned as usual and moved by the scroll offset when they are drawn. A child
+/// should not be added to a frame separately, as it would then be drawn twice.
+///
+/// Parameters
+/// ----------
+/// children : list[Stimulus], optional
+///   The content of the view.
+/// x : Size, optional
+///   The x position of the view.
+/// y : Size, optional
+///   The y position of the view.
+/// width : Size, optional
+///   The width of the view.
+/// height : Size, optional
+///   The height of the view.
+/// anchor : str, optional
+///   The point of the view that the position refers to. Defaults to "center".
+/// direction : str, optional
+///   "vertical" (default), "horizontal", or "both".
+/// inertia : float, optional
+///   The time constant (in seconds) with which the content slows down after a drag, or None to
+///   stop it when the pointer is released. Defaults to 0.3.
+/// wheel_step : float, optional
+///   How far (in pixels) one step of the mouse wheel or an arrow key scrolls. Defaults to 40.
+/// keys : bool, optional
+///   Whether the arrow keys, Page Up, Page Down, Home, and End scroll. Defaults to True.
+/// scrollbar_color : Color, optional
+///   The color of the scroll bars. Pass a transparent color to hide them.
+pub struct PyScrollViewStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyScrollViewStimulus {
+    #[new]
+    #[pyo3(signature = (
+        children = Vec::new(),
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        width = IntoSize(Size::Pixels(800.0)),
+        height = IntoSize(Size::Pixels(600.0)),
+        anchor = Anchor::Center,
+        direction = ScrollDirection::Vertical,
+        inertia = Some(0.3),
+        wheel_step = 40.0,
+        keys = true,
+        scrollbar_color = IntoLinRgba::new(0.5, 0.5, 0.5, 0.8),
+    ))]
+    fn __new__(
+        children: Vec<PyStimulus>,
+        x: IntoSize,
+        y: IntoSize,
+        width: IntoSize,
+        height: IntoSize,
+        anchor: Anchor,
+        direction: ScrollDirection,
+        inertia: Option<f64>,
+        wheel_step: f64,
+        keys: bool,
+        scrollbar_color: IntoLinRgba,
+    ) -> (Self, PyStimulus) {
+        let children = children.iter().map(|c| c.as_super().clone()).collect();
+        let params = ScrollViewParams {
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
+            scroll_x: 0.0,
+            scroll_y: 0.0,
+            scrollbar_color: scrollbar_color.into(),
+        };
+        let view = ScrollViewStimulus::new(children, anchor, direction, inertia, wheel_step, keys, params);
+        (Self(), PyStimulus::new(view))
+    }
+
+    /// Add a stimulus to the content.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimulus : Stimulus
+    ///   The stimulus to add. It is drawn on top of the existing children.
+    #[pyo3(name = "add")]
+    fn py_add(slf: PyRefMut<'_, Self>, stimulus: PyStimulus) -> PyResult<()> {
+        let child = stimulus.as_super();
+        // the view's lock is held while drawing the children, so a view cannot contain itself
+        if slf.as_super().0.ptr_eq(child) {
+            return Err(PyValueError::new_err("a scroll view cannot contain itself"));
+        }
+        downcast_py_stimulus_mut!(slf, ScrollViewStimulus).add(child.clone());
+        Ok(())
+    }
+
+    /// Remove a stimulus from the content.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimulus : Stimulus
+    ///   The stimulus to remove.
+    #[pyo3(name = "remove")]
+    fn py_remove(slf: PyRefMut<'_, Self>, stimulus: PyStimulus) -> PyResult<()> {
+        if !downcast_py_stimulus_mut!(slf, ScrollViewStimulus).remove(stimulus.as_super()) {
+            return Err(PyValueError::new_err("the stimulus is not part of the scroll view"));
+        }
+        Ok(())
+    }
+
+    /// The stimuli in the view.
+    #[getter(children)]
+    fn py_children(slf: PyRef<'_, Self>) -> Vec<PyStimulus> {
+        downcast_stimulus!(slf, ScrollViewStimulus)
+            .children()
+            .iter()
+            .map(|c| PyStimulus(c.clone()))
+            .collect()
+    }
+
+    /// Scroll to an offset. The offset is limited to the extent of the content once the view has
+    /// been drawn.
+    ///
+    /// Parameters
+    /// ----------
+    /// x : float, optional
+    ///   How far to scroll to the right in pixels. Defaults to the current offset.
+    /// y : float, optional
+    ///   How far to scroll down in pixels. Defaults to the current offset.
+    #[pyo3(name = "scroll_to")]
+    #[pyo3(signature = (x = None, y = None))]
+    fn py_scroll_to(slf: PyRefMut<'_, Self>, x: Option<f64>, y: Option<f64>) {
+        let mut view = slf.as_super().0.lock();
+        let view = view.downcast_mut::<ScrollViewStimulus>().expect("downcast failed");
+        let (current_x, current_y) = view.scroll();
+        view.scroll_to(x.unwrap_or(current_x), y.unwrap_or(current_y), Instant::now());
+    }
+
+    /// Whether the content has been scrolled to its end (or fits into the view) at least once,
+    /// e.g. to only let participants continue once they have seen all instructions.
+    #[getter(reached_end)]
+    fn py_reached_end(slf: PyRef<'_, Self>) -> bool {
+        downcast_stimulus!(slf, ScrollViewStimulus).reached_end()
+    }
+
+    /// Every change of the scroll offset, as dictionaries with the "time" and the offset
+    /// ("scroll_x" and "scroll_y" in pixels), e.g. to reconstruct what was visible when in a
+    /// reading study.
+    #[getter(log)]
+    fn py_log<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        downcast_stimulus!(slf, ScrollViewStimulus)
+            .log()
+            .iter()
+            .map(|record| {
+                let dict = PyDict::new(py);
+                dict.set_item("time", Timestamp::from(record.time))?;
+                dict.set_item("scroll_x", record.scroll_x)?;
+                dict.set_item("scroll_y", record.scroll_y)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Pass an input event to the view, e.g. from an event receiver.
+    ///
+    /// Parameters
+    /// ----------
+    /// event : Event
+    ///   The event.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///   Whether the view used the event.
+    #[pyo3(name = "handle_event")]
+    fn py_handle_event(slf: PyRefMut<'_, Self>, event: Event) -> bool {
+        downcast_py_stimulus_mut!(slf, ScrollViewStimulus).handle_event(&event)
+    }
+
+    /// Pass the input events of a window to the view as they arrive, until `detach` is called.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window that the view is shown on.
+    #[pyo3(name = "attach")]
+    fn py_attach(slf: PyRef<'_, Self>, window: Window) -> PyResult<()> {
+        Ok(ScrollViewStimulus::attach(&slf.as_super().0, &window)?)
+    }
+
+    /// Stop passing the input events of the window to the view.
+    #[pyo3(name = "detach")]
+    fn py_detach(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, ScrollViewStimulus).detach();
+    }
+
+    fn __len__(slf: PyRef<'_, Self>) -> usize {
+        downcast_stimulus!(slf, ScrollViewStimulus).children().len()
+    }
+}
@@ -0,0 +1,489 @@
+use psydk_proc::{FromPyStr, StimulusParams};
+use renderer::{
+    affine::Affine,
+    brushes::{Brush, Extend, Gradient, GradientKind, ImageSampling},
+    colors::RGBA,
+    image::{ImageBuffer, Rgba},
+    styles::{BlendMode, ImageFitMode},
+    DynamicBitmap, DynamicScene,
+};
+use strum::EnumString;
+use uuid::Uuid;
+
+unsafe impl Send for ShapeStimulus {}
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation, helpers, impl_pystimulus_for_wrapper, Stimulus, StimulusLayout, StimulusParamValue,
+    StimulusParams, StrokeStyle,
+};
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    visual::{
+        color::{IntoLinRgba, LinRgba},
+        geometry::{Anchor, Shape, Size, Transformation2D},
+        window::WindowState,
+    },
+};
+
+/// How the colors of a gradient fill are laid out.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum GradientType {
+    /// The colors change along a line through the shape.
+    Linear,
+    /// The colors radiate from the center of the shape.
+    Radial,
+}
+
+#[derive(StimulusParams, Clone, Debug)]
+pub struct ShapeParams {
+    pub shape: Shape,
+    pub x: Size,
+    pub y: Size,
+    pub fill_color: LinRgba,
+    pub gradient_angle: f64,
+    pub stroke_style: StrokeStyle,
+    pub stroke_color: LinRgba,
+    pub stroke_width: Size,
+    pub alpha: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct ShapeStimulus {
+    id: uuid::Uuid,
+    params: ShapeParams,
+
+    /// The colors of a gradient fill. Takes precedence over the fill color.
+    gradient: Option<Vec<LinRgba>>,
+    gradient_type: GradientType,
+    /// One color per point of a polygon, interpolated across the polygon. Takes precedence over the
+    /// fill color and gradient.
+    vertex_colors: Option<Vec<LinRgba>>,
+    /// The rasterized vertex colors, together with the points (in the coordinates of the scene) they
+    /// have been rasterized for.
+    vertex_bitmap: Option<(Vec<(f64, f64)>, DynamicBitmap)>,
+    anchor: Option<Anchor>,
+    transform: Transformation2D,
+    animations: Vec<Animation>,
+    visible: bool,
+}
+
+impl ShapeStimulus {
+    pub fn new(
+        params: ShapeParams,
+        gradient: Option<Vec<LinRgba>>,
+        gradient_type: GradientType,
+        vertex_colors: Option<Vec<LinRgba>>,
+        anchor: Option<Anchor>,
+        transform: Transformation2D,
+    ) -> PsydkResult<Self> {
+        if gradient.as_ref().is_some_and(|colors| colors.len() < 2) {
+            return Err(PsydkError::ParameterError(
+                "a gradient needs at least two colors".into(),
+            ));
+        }
+
+        if let Some(colors) = &vertex_colors {
+            match &params.shape {
+                Shape::Polygon { points } if points.len() == colors.len() => {}
+                Shape::Polygon { points } => {
+                    return Err(PsydkError::ParameterError(format!(
+                        "expected one vertex color for each of the {} points of the polygon, got {}",
+                        points.len(),
+                        colors.len()
+                    )))
+                }
+                _ => {
+                    return Err(PsydkError::ParameterError(
+                        "vertex colors are only supported for polygons".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            params,
+            gradient,
+            gradient_type,
+            vertex_colors,
+            vertex_bitmap: None,
+            anchor,
+            transform,
+            animations: Vec::new(),
+            visible: true,
+        })
+    }
+
+    /// The vertex colors rasterized over the bounding box of `points` (in the coordinates of the
+    /// scene). Returns the bitmap and the top left corner and size of the bounding box.
+    fn vertex_color_bitmap(
+        &mut self,
+        points: Vec<(f64, f64)>,
+        window_state: &WindowState,
+    ) -> (&DynamicBitmap, (f64, f64), (f32, f32)) {
+        let min_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min).floor();
+        let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min).floor();
+        let max_x = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max).ceil();
+        let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max).ceil();
+        let width = (max_x - min_x).max(1.0) as u32;
+        let height = (max_y - min_y).max(1.0) as u32;
+
+        // only rasterize again if the polygon has moved or changed
+        let outdated = self
+            .vertex_bitmap
+            .as_ref()
+            .map_or(true, |(cached, _)| *cached != points);
+
+        if outdated {
+            let colors = self.vertex_colors.as_deref().unwrap_or_default();
+            let image = rasterize_vertex_colors(&points, colors, (min_x, min_y), width, height);
+            let bitmap = window_state
+                .shared_renderer_state
+                .create_bitmap_f32(image, renderer::renderer::ColorSpace::LinearSrgb);
+            self.vertex_bitmap = Some((points, bitmap));
+        }
+
+        let (_, bitmap) = self.vertex_bitmap.as_ref().unwrap();
+        (bitmap, (min_x, min_y), (width as f32, height as f32))
+    }
+}
+
+/// Interpolate the colors of the points of a polygon over a `width` x `height` image whose top left
+/// corner lies at `origin`. The polygon is split into triangles that share its centroid, so the
+/// result is exact for triangles and smooth for convex polygons.
+fn rasterize_vertex_colors(
+    points: &[(f64, f64)],
+    colors: &[LinRgba],
+    origin: (f64, f64),
+    width: u32,
+    height: u32,
+) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+    let n = points.len().max(1) as f64;
+    let centroid = (
+        points.iter().map(|p| p.0).sum::<f64>() / n,
+        points.iter().map(|p| p.1).sum::<f64>() / n,
+    );
+    let mean = |channel: fn(&LinRgba) -> f32| colors.iter().map(channel).sum::<f32>() / n as f32;
+    let centroid_color = LinRgba::new(mean(|c| c.r), mean(|c| c.g), mean(|c| c.b), mean(|c| c.a));
+
+    ImageBuffer::from_fn(width, height, |px, py| {
+        let p = (origin.0 + px as f64 + 0.5, origin.1 + py as f64 + 0.5);
+
+        // find the triangle that contains the pixel. Pixels outside of the polygon (which are only
+        // sampled at the anti-aliased edges) use the closest triangle
+        let mut best: Option<(f64, [f64; 3], usize)> = None;
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            let det = (a.1 - b.1) * (centroid.0 - b.0) + (b.0 - a.0) * (centroid.1 - b.1);
+            if det.abs() < f64::EPSILON {
+                continue;
+            }
+            let l0 = ((a.1 - b.1) * (p.0 - b.0) + (b.0 - a.0) * (p.1 - b.1)) / det;
+            let l1 = ((b.1 - centroid.1) * (p.0 - b.0) + (centroid.0 - b.0) * (p.1 - b.1)) / det;
+            let weights = [l0, l1, 1.0 - l0 - l1];
+            let inside = weights.iter().cloned().fold(f64::INFINITY, f64::min);
+            if best.map_or(true, |(best_inside, _, _)| inside > best_inside) {
+                best = Some((inside, weights, i));
+            }
+        }
+
+        let color = match best {
+            Some((_, weights, i)) => {
+                let weights = weights.map(|w| w.max(0.0));
+                let sum = weights.iter().sum::<f64>().max(f64::EPSILON);
+                let [w0, w1, w2] = weights.map(|w| (w / sum) as f32);
+                let (c1, c2) = (colors[i], colors[(i + 1) % colors.len()]);
+                LinRgba::new(
+                    w0 * centroid_color.r + w1 * c1.r + w2 * c2.r,
+                    w0 * centroid_color.g + w1 * c1.g + w2 * c2.g,
+                    w0 * centroid_color.b + w1 * c1.b + w2 * c2.b,
+                    w0 * centroid_color.a + w1 * c1.a + w2 * c2.a,
+                )
+            }
+            None => centroid_color,
+        };
+
+        Rgba([color.r, color.g, color.b, color.a])
+    })
+}
+
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "ShapeStimulus", extends=PyStimulus)]
+/// A stimulus that displays a shape with a solid or gradient fill and an outline.
+///
+/// Parameters
+/// ----------
+/// shape : Shape
+///     The shape to display.
+/// x : Size, optional
+///     The x-coordinate the shape is positioned relative to.
+/// y : Size, optional
+///     The y-coordinate the shape is positioned relative to.
+/// fill_color : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
+///     The fill color of the shape.
+/// gradient : list[Union[LinRgba, (float, float, float), (float, float, float, float), str]], optional
+///     Fill the shape with a gradient between these colors instead of a solid color.
+/// gradient_type : str, optional
+///     Either "linear" or "radial".
+/// gradient_angle : float, optional
+///     The direction of a linear gradient in degrees, from the x-axis towards the y-axis.
+/// vertex_colors : list[Union[LinRgba, (float, float, float), (float, float, float, float), str]], optional
+///     One color for each point of a polygon. The colors are interpolated across the polygon.
+/// stroke_style : StrokeStyle, optional
+///     The stroke style of the outline. "None" disables the outline.
+/// stroke_color : Union[LinRgba, (float, float, float), (float, float, float, float), str], optional
+///     The color of the outline.
+/// stroke_width : Union[Size, float], optional
+///     The width of the outline.
+/// alpha : float, optional
+///     The opacity of the whole shape.
+/// anchor : str, optional
+///     If given, the shape is moved so that this point of its bounding box (e.g. "center" or
+///     "top-left") lies at (x, y). Otherwise, the coordinates of the shape are relative to (x, y).
+/// transform : Transformation2D, optional
+///     The transformation of the shape.
+pub struct PyShapeStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyShapeStimulus {
+    #[new]
+    #[pyo3(signature = (
+        shape,
+        x = IntoSize(Size::Pixels(0.0)),
+        y = IntoSize(Size::Pixels(0.0)),
+        fill_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        gradient = None,
+        gradient_type = GradientType::Linear,
+        gradient_angle = 0.0,
+        vertex_colors = None,
+        stroke_style = StrokeStyle::Solid,
+        stroke_color = IntoLinRgba::new(0.0, 0.0, 0.0, 1.0),
+        stroke_width = IntoSize(Size::Pixels(0.0)),
+        alpha = None,
+        anchor = None,
+        transform = Transformation2D::Identity(),
+    ))]
+    fn __new__(
+        shape: Shape,
+        x: IntoSize,
+        y: IntoSize,
+        fill_color: IntoLinRgba,
+        gradient: Option<Vec<IntoLinRgba>>,
+        gradient_type: GradientType,
+        gradient_angle: f64,
+        vertex_colors: Option<Vec<IntoLinRgba>>,
+        stroke_style: StrokeStyle,
+        stroke_color: IntoLinRgba,
+        stroke_width: IntoSize,
+        alpha: Option<f64>,
+        anchor: Option<Anchor>,
+        transform: Transformation2D,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let params = ShapeParams {
+            shape,
+            x: x.into(),
+            y: y.into(),
+            fill_color: fill_color.into(),
+            gradient_angle,
+            stroke_style,
+            stroke_color: stroke_color.into(),
+            stroke_width: stroke_width.into(),
+            alpha,
+        };
+        let into_colors = |colors: Vec<IntoLinRgba>| colors.into_iter().map(|c| c.into()).collect();
+
+        Ok((
+            Self(),
+            PyStimulus::new(ShapeStimulus::new(
+                params,
+                gradient.map(into_colors),
+                gradient_type,
+                vertex_colors.map(into_colors),
+                anchor,
+                transform,
+            )?),
+        ))
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyShapeStimulus, ShapeStimulus);
+
+impl Stimulus for ShapeStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.visible {
+            return;
+        }
+
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let x = self.params.x.eval(window_size, screen_props);
+        let y = self.params.y.eval(window_size, screen_props);
+
+        // positions are given in the coordinate system of the window
+        let place = helpers::place_shape(&self.params.shape, (x, y), self.anchor, window_state);
+        let shape = helpers::scene_shape(&self.params.shape, window_state, &place);
+
+        let transform: Affine = self.transform.eval(window_size, screen_props).into();
+
+        // the opacity applies to the shape as a whole, so that fill and outline do not show through
+        // each other
+        let alpha = self.params.alpha.unwrap_or(1.0);
+        if alpha < 1.0 {
+            scene.start_layer(
+                BlendMode::SourceOver,
+                helpers::window_clip(window_state),
+                None,
+                None,
+                alpha as f32,
+            );
+        }
+
+        // lines have no area to fill
+        if !matches!(self.params.shape, Shape::Line { .. }) {
+            let (bx, by, bw, bh) = self.params.shape.bounds(window_size, screen_props);
+            let (x0, y0) = place(bx, by);
+            let (x1, y1) = place(bx + bw, by + bh);
+            let (bx, by, bw, bh) = (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs());
+            let (cx, cy) = (bx + bw / 2.0, by + bh / 2.0);
+
+            // vertex colors only apply to polygons with one color per point
+            let vertex_points = match (&self.params.shape, &self.vertex_colors) {
+                (Shape::Polygon { points }, Some(colors)) if points.len() == colors.len() => Some(
+                    points
+                        .iter()
+                        .map(|(x, y)| place(x.eval(window_size, screen_props), y.eval(window_size, screen_props)))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => None,
+            };
+
+            let fill_brush = if let Some(points) = vertex_points {
+                let (image, start, (width, height)) = self.vertex_color_bitmap(points, window_state);
+                Brush::Image {
+                    image,
+                    start: start.into(),
+                    fit_mode: ImageFitMode::Exact { width, height },
+                    sampling: ImageSampling::Linear,
+                    edge_mode: (Extend::Pad, Extend::Pad),
+                    transform: None,
+                    alpha: None,
+                }
+            } else if let Some(colors) = &self.gradient {
+                let kind = match self.gradient_type {
+                    GradientType::Linear => {
+                        let (sin, cos) = self.params.gradient_angle.to_radians().sin_cos();
+                        let sin = sin * window_state.coordinate_system.y_sign() as f64;
+                        // the gradient spans the extent of the bounding box along its direction
+                        let half_length = (bw * cos.abs() + bh * sin.abs()) / 2.0;
+                        GradientKind::Linear {
+                            start: (cx - cos * half_length, cy - sin * half_length).into(),
+                            end: (cx + cos * half_length, cy + sin * half_length).into(),
+                        }
+                    }
+                    GradientType::Radial => GradientKind::Radial {
+                        center: (cx, cy).into(),
+                        radius: (bw.max(bh) / 2.0) as f32,
+                    },
+                };
+                let colors = colors.iter().map(|&c| c.into()).collect::<Vec<RGBA>>();
+                Brush::Gradient(Gradient::new_equidistant(Extend::Pad, kind, &colors))
+            } else {
+                Brush::Solid(self.params.fill_color.into())
+            };
+
+            scene.draw_shape_fill(shape.clone(), fill_brush, Some(transform), None);
+        }
+
+        let stroke_width = self.params.stroke_width.eval(window_size, screen_props) as f64;
+        if !matches!(self.params.stroke_style, StrokeStyle::None) && stroke_width > 0.0 {
+            scene.draw_shape_stroke(
+                shape,
+                Brush::Solid(self.params.stroke_color.into()),
+                renderer::styles::StrokeStyle::new(stroke_width),
+                Some(transform),
+                None,
+            );
+        }
+
+        if alpha < 1.0 {
+            scene.end_layer();
+        }
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let x = self.params.x.eval(window_size, screen_props);
+        let y = self.params.y.eval(window_size, screen_props);
+        let place = helpers::place_shape(&self.params.shape, (x, y), self.anchor, window_state);
+
+        let (bx, by, bw, bh) = self.params.shape.bounds(window_size, screen_props);
+        let (x0, y0) = place(bx, by);
+        let (x1, y1) = place(bx + bw, by + bh);
+        let bounds = (
+            x0.min(x1) as f32,
+            y0.min(y1) as f32,
+            (x1 - x0).abs() as f32,
+            (y1 - y0).abs() as f32,
+        );
+
+        let transform = self.transform.eval(window_size, screen_props);
+        let anchor = window_state.coordinate_system.to_scene(x, y, window_size);
+        vec![StimulusLayout {
+            bounds: helpers::transform_rect(&transform, bounds),
+            anchor: Some(helpers::transform_point(&transform, anchor)),
+            hit_region: None,
+        }]
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transform = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transform.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        self.params.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        self.params.set_param(name, value)
+    }
+}
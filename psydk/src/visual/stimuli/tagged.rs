@@ -0,0 +1,431 @@
+//! Frequency tagging.
+//!
+//! A tagged stimulus modulates a parameter of another stimulus (e.g., its opacity) at a fixed
+//! frequency, as in steady-state visual evoked potential (SSVEP) experiments. The modulation is
+//! locked to the refresh count of the display rather than to the clock: the value for a frame is
+//! computed from the number of refreshes since the modulation started, so every frame gets the
+//! value of the refresh it is shown at, and frequencies that are not a divisor of the refresh rate
+//! are approximated as well as the refresh rate allows. The value drawn for each refresh is
+//! recorded, so the modulation that was actually shown can be reconstructed for spectral analysis.
+
+use std::f64::consts::TAU;
+
+#[cfg(feature = "python")]
+use numpy::IntoPyArray;
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+use renderer::DynamicScene;
+use strum::EnumString;
+use uuid::Uuid;
+
+#[cfg(feature = "python")]
+use super::PyStimulus;
+use super::{
+    animations::Animation, impl_pystimulus_for_wrapper, DynamicStimulus, Stimulus, StimulusLayout, StimulusParamValue,
+};
+use crate::{
+    errors::{PsydkError, PsydkResult},
+    visual::{
+        geometry::{Size, Transformation2D},
+        report::PresentationReport,
+        window::WindowState,
+    },
+};
+
+/// The shape of a modulation.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Waveform {
+    /// A sinusoid from the low to the high value.
+    Sine,
+    /// The high value for the first half of each cycle and the low value for the second half.
+    Square,
+}
+
+/// Modulates a parameter of a stimulus at a fixed frequency, locked to the refreshes of the
+/// display.
+#[derive(Debug)]
+pub struct TaggedStimulus {
+    id: Uuid,
+    child: DynamicStimulus,
+    /// The parameter of the child that is modulated.
+    parameter: String,
+    frequency: f64,
+    waveform: Waveform,
+    /// The phase at the first refresh, in radians.
+    phase: f64,
+    low: f64,
+    high: f64,
+    /// The refresh at which the modulation started, i.e. the first refresh it was drawn for.
+    start: Option<u64>,
+    /// The refresh rate the modulation is computed for.
+    refresh_rate: Option<f64>,
+    /// The refresh that each drawn frame was rendered for, and the value it was drawn with.
+    samples: Vec<(u64, f64)>,
+    transformation: Transformation2D,
+    animations: Vec<Animation>,
+}
+
+impl TaggedStimulus {
+    pub fn new(
+        child: DynamicStimulus,
+        parameter: &str,
+        frequency: f64,
+        waveform: Waveform,
+        phase: f64,
+        low: f64,
+        high: f64,
+    ) -> PsydkResult<Self> {
+        if child.lock().param_type(parameter) != Some("f64") {
+            return Err(PsydkError::ParameterError(format!(
+                "The stimulus has no numeric parameter \"{parameter}\" that can be modulated"
+            )));
+        }
+        if !(frequency.is_finite() && frequency > 0.0) {
+            return Err(PsydkError::ParameterError(format!(
+                "The frequency must be positive, not {frequency}"
+            )));
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            child,
+            parameter: parameter.to_string(),
+            frequency,
+            waveform,
+            phase,
+            low,
+            high,
+            start: None,
+            refresh_rate: None,
+            samples: Vec::new(),
+            transformation: Transformation2D::Identity(),
+            animations: Vec::new(),
+        })
+    }
+
+    /// The value of the modulation `refreshes` refreshes after it started, at `refresh_rate`.
+    pub fn value(&self, refreshes: u64, refresh_rate: f64) -> f64 {
+        let angle = TAU * self.frequency * refreshes as f64 / refresh_rate + self.phase;
+        let level = match self.waveform {
+            Waveform::Sine => (1.0 + angle.sin()) / 2.0,
+            Waveform::Square => match angle.rem_euclid(TAU) < TAU / 2.0 {
+                true => 1.0,
+                false => 0.0,
+            },
+        };
+        self.low + (self.high - self.low) * level
+    }
+
+    /// Start the modulation again at the next refresh it is drawn for, and discard the recorded
+    /// values.
+    pub fn restart(&mut self) {
+        self.start = None;
+        self.samples.clear();
+    }
+
+    /// The refresh at which the modulation started, if it has been drawn.
+    pub fn start(&self) -> Option<u64> {
+        self.start
+    }
+
+    /// The refresh that each drawn frame was rendered for, and the value it was drawn with.
+    pub fn samples(&self) -> &[(u64, f64)] {
+        &self.samples
+    }
+
+    /// The value that was on screen at each refresh of a presentation, from the first frame of
+    /// `report` to its last, together with the value intended for that refresh. Refreshes at which
+    /// the frame on screen was not drawn with the modulation (e.g., as it was not part of the
+    /// frame) have no value.
+    pub fn realized(&self, report: &PresentationReport) -> Vec<(u64, Option<f64>, Option<f64>)> {
+        let (Some(&(_, first)), Some(&(_, last))) = (report.refreshes.first(), report.refreshes.last()) else {
+            return Vec::new();
+        };
+        let drawn = |refresh: u64| {
+            self.samples
+                .iter()
+                .rev()
+                .find(|(sample, _)| *sample == refresh)
+                .map(|(_, value)| *value)
+        };
+        let intended = |refresh: u64| match (self.start, self.refresh_rate) {
+            (Some(start), Some(rate)) if refresh >= start => Some(self.value(refresh - start, rate)),
+            _ => None,
+        };
+
+        let mut frames = report.refreshes.iter().peekable();
+        let mut shown = None;
+        (first..=last)
+            .map(|refresh| {
+                // a frame stays on screen until the next one is shown
+                while let Some((planned, _)) = frames.next_if(|(_, at)| *at <= refresh) {
+                    shown = drawn(*planned);
+                }
+                (refresh, shown, intended(refresh))
+            })
+            .collect()
+    }
+}
+
+impl_pystimulus_for_wrapper!(PyTaggedStimulus, TaggedStimulus);
+
+impl Stimulus for TaggedStimulus {
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    fn draw(&mut self, scene: &mut DynamicScene, window_state: &WindowState) {
+        if !self.child.lock().visible() {
+            return;
+        }
+        // the refreshes are only counted once a frame has been presented, before that (e.g., when a
+        // frame is measured) the stimulus is drawn as it is
+        let interval = window_state.refresh.interval;
+        if !interval.is_zero() {
+            let refresh = window_state.refresh.planned;
+            let start = *self.start.get_or_insert(refresh);
+            let refresh_rate = *self.refresh_rate.get_or_insert(1.0 / interval.as_secs_f64());
+            let value = self.value(refresh.saturating_sub(start), refresh_rate);
+            self.samples.push((refresh, value));
+            self.child
+                .lock()
+                .set_param(&self.parameter, StimulusParamValue::f64(value));
+        }
+
+        let own_transformation = self.child.lock().transformation();
+        // the transformation of the tag applies on top of the child's own, as for a group
+        self.child
+            .lock()
+            .set_transformation(self.transformation.clone() * own_transformation.clone());
+        self.child.draw(scene, window_state);
+        self.child.lock().set_transformation(own_transformation);
+    }
+
+    fn contains(&self, x: Size, y: Size, window: &Window) -> bool {
+        self.child.lock().contains(x, y, window)
+    }
+
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        self.child.lock().layout(window_state)
+    }
+
+    fn update_animations(&mut self, time: std::time::Instant, window_state: &WindowState) {
+        let mut params_to_set = Vec::new();
+        self.animations.retain_mut(|animation| {
+            params_to_set.push((animation.parameter().to_string(), animation.value(time, window_state)));
+            !animation.finished(time)
+        });
+        for (param, value) in params_to_set {
+            self.set_param(&param, value);
+        }
+        self.child.update_animations(time, window_state);
+    }
+
+    fn children(&self) -> &[DynamicStimulus] {
+        std::slice::from_ref(&self.child)
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.child.lock().set_visible(visible);
+    }
+
+    fn visible(&self) -> bool {
+        self.child.lock().visible()
+    }
+
+    fn animations(&mut self) -> &mut Vec<Animation> {
+        &mut self.animations
+    }
+
+    fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    fn set_transformation(&mut self, transformation: Transformation2D) {
+        self.transformation = transformation;
+    }
+
+    fn transformation(&self) -> Transformation2D {
+        self.transformation.clone()
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        &["frequency", "phase", "low", "high"]
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.param_names().contains(&name).then_some("f64")
+    }
+
+    fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
+        let value = match name {
+            "frequency" => self.frequency,
+            "phase" => self.phase,
+            "low" => self.low,
+            "high" => self.high,
+            _ => return None,
+        };
+        Some(StimulusParamValue::f64(value))
+    }
+
+    fn set_param(&mut self, name: &str, value: StimulusParamValue) {
+        let StimulusParamValue::f64(value) = value else {
+            panic!("Invalid type for field {name}");
+        };
+        match name {
+            "frequency" => self.frequency = value,
+            "phase" => self.phase = value,
+            "low" => self.low = value,
+            "high" => self.high = value,
+            _ => panic!("Invalid field name {name}"),
+        }
+    }
+}
+
+/// Modulates a parameter of a stimulus at a fixed frequency, for steady-state visual evoked
+/// potential (SSVEP) and frequency-tagging experiments.
+///
+/// The modulation is locked to the refreshes of the display: the value for each frame is computed
+/// from the number of refreshes since the modulation started (the first frame it was drawn in),
+/// at the refresh the frame is expected to be shown at. So the modulation keeps its phase over
+/// several calls to `Window.present`, and frames that are shown late are skipped rather than
+/// delaying the modulation. Present the frames with `repeat_update=True` (the default), so that
+/// every refresh gets its own value, and add the tagged stimulus to the frames instead of the
+/// stimulus itself.
+///
+/// With the default parameter "alpha", the opacity of the stimulus is modulated, which modulates
+/// its luminance against the background (e.g., a white patch on a black background) or its
+/// contrast (e.g., a grating on a background of its mean luminance). Any other numeric parameter
+/// of the stimulus can be modulated instead.
+///
+/// Parameters
+/// ----------
+/// stimulus : Stimulus
+///   The stimulus to modulate.
+/// frequency : float
+///   The frequency of the modulation in Hz.
+/// waveform : str, optional
+///   "sine" (default) or "square".
+/// phase : float, optional
+///   The phase of the modulation at its first refresh, in radians, e.g. for joint frequency and
+///   phase modulation. A sine starts at the middle of its range with a phase of 0, and at `high`
+///   with a phase of pi/2. Defaults to 0.
+/// parameter : str, optional
+///   The numeric parameter of the stimulus that is modulated. Defaults to "alpha".
+/// low : float, optional
+///   The lowest value of the parameter. Defaults to 0.
+/// high : float, optional
+///   The highest value of the parameter. Defaults to 1.
+#[derive(Debug, Clone)]
+#[cfg(feature = "python")]
+#[pyclass(name = "TaggedStimulus", extends=PyStimulus)]
+pub struct PyTaggedStimulus();
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyTaggedStimulus {
+    #[new]
+    #[pyo3(signature = (
+        stimulus,
+        frequency,
+        waveform = Waveform::Sine,
+        phase = 0.0,
+        parameter = "alpha",
+        low = 0.0,
+        high = 1.0,
+    ))]
+    fn __new__(
+        stimulus: PyStimulus,
+        frequency: f64,
+        waveform: Waveform,
+        phase: f64,
+        parameter: &str,
+        low: f64,
+        high: f64,
+    ) -> PyResult<(Self, PyStimulus)> {
+        let tagged = TaggedStimulus::new(
+            stimulus.as_super().clone(),
+            parameter,
+            frequency,
+            waveform,
+            phase,
+            low,
+            high,
+        )?;
+        Ok((Self(), PyStimulus::new(tagged)))
+    }
+
+    /// The modulated stimulus.
+    #[getter(stimulus)]
+    fn py_stimulus(slf: PyRef<'_, Self>) -> PyStimulus {
+        PyStimulus(downcast_stimulus!(slf, TaggedStimulus).children()[0].clone())
+    }
+
+    /// The refresh (counted since the window was opened) at which the modulation started, or None
+    /// if it has not been drawn yet.
+    #[getter(start)]
+    fn py_start(slf: PyRef<'_, Self>) -> Option<u64> {
+        downcast_stimulus!(slf, TaggedStimulus).start()
+    }
+
+    /// Start the modulation again (at the phase given when it was created) with the next frame it
+    /// is drawn in, and discard the recorded values.
+    #[pyo3(name = "restart")]
+    fn py_restart(slf: PyRefMut<'_, Self>) {
+        downcast_py_stimulus_mut!(slf, TaggedStimulus).restart();
+    }
+
+    /// The values the stimulus was drawn with, as a dictionary with the "refresh" each frame was
+    /// rendered for (counted since the window was opened) and the "value" of the parameter, as
+    /// arrays.
+    #[getter(samples)]
+    fn py_samples<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (refreshes, values): (Vec<u64>, Vec<f64>) = downcast_stimulus!(slf, TaggedStimulus)
+            .samples()
+            .iter()
+            .copied()
+            .unzip();
+        let dict = PyDict::new(py);
+        dict.set_item("refresh", refreshes.into_pyarray(py))?;
+        dict.set_item("value", values.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// The modulation that was shown during a presentation, refresh by refresh, e.g. to compute
+    /// its spectrum. A frame stays on screen until the next one is shown, so the value of a frame
+    /// that was shown late is repeated.
+    ///
+    /// Parameters
+    /// ----------
+    /// report : PresentationReport
+    ///   The report of the presentation, as returned by `Window.present` with `report=True`.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   The "refresh" (counted since the window was opened), the "value" of the parameter on
+    ///   screen (NaN while the frame on screen was not drawn with the modulation), and the
+    ///   "intended" value for the refresh, as arrays with one element per refresh from the first
+    ///   to the last frame of the presentation.
+    #[pyo3(name = "realized")]
+    fn py_realized<'py>(
+        slf: PyRef<'py, Self>,
+        py: Python<'py>,
+        report: PresentationReport,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let realized = downcast_stimulus!(slf, TaggedStimulus).realized(&report);
+        let refreshes: Vec<u64> = realized.iter().map(|(refresh, _, _)| *refresh).collect();
+        let values: Vec<f64> = realized.iter().map(|(_, value, _)| value.unwrap_or(f64::NAN)).collect();
+        let intended: Vec<f64> = realized
+            .iter()
+            .map(|(_, _, intended)| intended.unwrap_or(f64::NAN))
+            .collect();
+        let dict = PyDict::new(py);
+        dict.set_item("refresh", refreshes.into_pyarray(py))?;
+        dict.set_item("value", values.into_pyarray(py))?;
+        dict.set_item("intended", intended.into_pyarray(py))?;
+        Ok(dict)
+    }
+}
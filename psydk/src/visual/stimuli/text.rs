@@ -1,12 +1,15 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use super::helpers;
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
-    animations::Animation, impl_pystimulus_for_wrapper, PyStimulus, Stimulus, StimulusParamValue, StimulusParams,
+    animations::Animation, impl_pystimulus_for_wrapper, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
 };
 use crate::context::ExperimentContext;
 use crate::visual::geometry::Transformation2D;
-use crate::visual::geometry::{Anchor, Size};
+use crate::visual::geometry::{Anchor, CoordinateSystem, Shape, Size};
 use cosmic_text::Buffer as CosmicBuffer;
 use cosmic_text::Family as CosmicFamily;
 use cosmic_text::FontSystem as CosmicFontSystem;
@@ -17,13 +20,15 @@ use cosmic_text::Weight as CosmicWeight;
 use cosmic_text::{Attrs as ComsicAttrs, CacheKeyFlags};
 
 use psydk_proc::{FromPyStr, StimulusParams};
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
 use renderer::DynamicScene;
 use strum::EnumString;
 use uuid::Uuid;
 
 use crate::visual::color::IntoLinRgba;
 use crate::visual::color::LinRgba;
-use crate::visual::window::{Frame, WindowState};
+use crate::visual::window::{Frame, PhysicalScreen, PixelSize, Window, WindowState};
 use renderer::affine::Affine;
 use renderer::brushes::Brush;
 use renderer::colors::RGBA;
@@ -51,6 +56,63 @@ pub enum TextAlignment {
     Justify,
 }
 
+impl From<TextAlignment> for cosmic_text::Align {
+    fn from(alignment: TextAlignment) -> Self {
+        match alignment {
+            TextAlignment::Left => cosmic_text::Align::Left,
+            TextAlignment::Center => cosmic_text::Align::Center,
+            TextAlignment::Right => cosmic_text::Align::Right,
+            TextAlignment::Justify => cosmic_text::Align::Justified,
+        }
+    }
+}
+
+/// The base direction of each paragraph of a text.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TextDirection {
+    /// Detected from the first strong character (e.g., a Hebrew or Latin letter) of each paragraph.
+    Auto,
+    /// Left-to-right.
+    Ltr,
+    /// Right-to-left.
+    Rtl,
+}
+
+impl TextDirection {
+    /// Force the direction by starting each paragraph with a (zero-width) directional mark, which
+    /// the bidirectional algorithm picks up as the first strong character.
+    fn apply<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        let mark = match self {
+            TextDirection::Auto => return text.into(),
+            TextDirection::Ltr => '\u{200E}',
+            TextDirection::Rtl => '\u{200F}',
+        };
+        text.split('\n')
+            .map(|line| format!("{mark}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into()
+    }
+}
+
+/// A font family, or a list of families that are tried in order for each character.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+pub enum FontFamilies {
+    Family(String),
+    Families(Vec<String>),
+}
+
+impl FontFamilies {
+    pub(crate) fn into_vec(self) -> Vec<String> {
+        match self {
+            FontFamilies::Family(family) => vec![family],
+            FontFamilies::Families(families) => families,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct OwnedCosmicAttrs {
     family: String,
@@ -67,6 +129,100 @@ pub struct TextParams {
     pub font_size: Size,
     pub fill_color: LinRgba,
     pub alpha: f64,
+    /// Clockwise rotation of each glyph around its center, in degrees.
+    pub glyph_rotation: f64,
+}
+
+/// Whether lines of text run horizontally or vertically.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TextOrientation {
+    Horizontal,
+    /// Characters are stacked top to bottom, and lines become columns from left to right.
+    Vertical,
+}
+
+/// A glyph after layout, relative to the baseline of the first line.
+#[derive(Debug, Clone, Copy)]
+struct PlacedGlyph {
+    font_id: cosmic_text::fontdb::ID,
+    id: u16,
+    x: f32,
+    y: f32,
+    /// The advance width of the glyph.
+    w: f32,
+    /// The line of the text the glyph belongs to.
+    line: usize,
+    /// The byte range of the glyph's cluster in the (shaped) line.
+    start: usize,
+    end: usize,
+    /// The baseline, top, and height of the line (or, for vertical text, the cell) of the glyph.
+    baseline: f32,
+    top: f32,
+    height: f32,
+}
+
+/// The position of a character or word of a text, in pixels in the coordinate system of the window
+/// (like the mouse position). `x` and `y` are the corner with the smallest coordinates.
+#[derive(Debug, Clone)]
+pub struct TextBox {
+    pub text: String,
+    /// The line of the text.
+    pub line: usize,
+    /// The index of the first character in the line.
+    pub index: usize,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl TextBox {
+    fn union(&mut self, other: &TextBox) {
+        let (x1, y1) = (
+            (self.x + self.width).max(other.x + other.width),
+            (self.y + self.height).max(other.y + other.height),
+        );
+        self.x = self.x.min(other.x);
+        self.y = self.y.min(other.y);
+        self.width = x1 - self.x;
+        self.height = y1 - self.y;
+    }
+
+    #[cfg(feature = "python")]
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("text", &self.text)?;
+        dict.set_item("line", self.line)?;
+        dict.set_item("index", self.index)?;
+        dict.set_item("x", self.x)?;
+        dict.set_item("y", self.y)?;
+        dict.set_item("width", self.width)?;
+        dict.set_item("height", self.height)?;
+        dict.set_item(
+            "shape",
+            Shape::Rectangle {
+                x: Size::Pixels(self.x),
+                y: Size::Pixels(self.y),
+                width: Size::Pixels(self.width),
+                height: Size::Pixels(self.height),
+            },
+        )?;
+        Ok(dict)
+    }
+}
+
+/// The shaped layout of a text stimulus.
+#[derive(Debug, Clone)]
+pub struct TextMetrics {
+    /// The bounding box of all characters.
+    pub bounds: TextBox,
+    /// The baseline of each line (for vertical text, of the first character of each column).
+    pub baselines: Vec<f32>,
+    /// All characters (grapheme clusters) in logical order.
+    pub characters: Vec<TextBox>,
+    /// All words (separated by whitespace) in logical order.
+    pub words: Vec<TextBox>,
 }
 
 #[derive(Debug)]
@@ -76,8 +232,16 @@ pub struct TextStimulus {
     buffer: CosmicBuffer,
     attrs: OwnedCosmicAttrs,
     alignment: TextAlignment,
+    direction: TextDirection,
+    orientation: TextOrientation,
     anchor: Anchor,
     font: renderer::font::DynamicFontFace,
+    /// Families that are tried (in order) for characters the primary font does not cover, before
+    /// falling back to any loaded font that covers them.
+    fallback_families: Vec<String>,
+    /// Font faces of fallback fonts, created when they are first used.
+    fallback_faces: HashMap<cosmic_text::fontdb::ID, renderer::font::DynamicFontFace>,
+    font_id: cosmic_text::fontdb::ID,
     font_manager: Arc<Mutex<CosmicFontSystem>>,
     transform: Transformation2D,
     animations: Vec<Animation>,
@@ -90,15 +254,23 @@ impl TextStimulus {
         y: Size,
         text: &str,
         alignment: TextAlignment,
+        direction: TextDirection,
+        orientation: TextOrientation,
         anchor: Anchor,
         font_size: Size,
-        font_family: &str,
+        font_families: &[String],
         font_weight: FontWeight,
         fill_color: LinRgba,
         alpha: f64,
+        glyph_rotation: f64,
         transform: Transformation2D,
         context: &ExperimentContext,
     ) -> Self {
+        let (font_family, fallback_families) = match font_families.split_first() {
+            Some((family, fallback)) => (family.as_str(), fallback.to_vec()),
+            None => ("Noto Sans", Vec::new()),
+        };
+
         // Attributes indicate what font to choose
         let attrs = ComsicAttrs::new();
         let attrs = attrs.family(CosmicFamily::Name(font_family));
@@ -143,11 +315,17 @@ impl TextStimulus {
                 font_size,
                 fill_color,
                 alpha,
+                glyph_rotation,
             },
             buffer: cosmic_buffer,
             attrs: owned_attrs,
             font,
+            fallback_families,
+            fallback_faces: HashMap::new(),
+            font_id: cosmic_font_id,
             alignment,
+            direction,
+            orientation,
             anchor,
             font_manager: font_manager_clone,
             transform,
@@ -155,21 +333,289 @@ impl TextStimulus {
             visible: true,
         }
     }
+
+    /// Shape the text for the window. Returns the font size in pixels.
+    fn shape(&mut self, window_size: PixelSize, screen_props: PhysicalScreen) -> f32 {
+        let font_size = self.params.font_size.eval(window_size, screen_props);
+        let font_manager = self.font_manager.clone();
+        let mut font_manager = font_manager.lock().unwrap();
+
+        // Set a size for the text buffer, in pixels
+        self.buffer.set_size(&mut font_manager, None, None);
+
+        self.buffer
+            .set_metrics(&mut font_manager, CosmicMetrics::new(font_size, font_size));
+
+        let attrs: ComsicAttrs = (&self.attrs).into();
+
+        let text = self.direction.apply(&self.params.text);
+
+        // Add some text! Advanced shaping is needed for font fallback, complex scripts, and
+        // bidirectional text
+        if self.fallback_families.is_empty() {
+            self.buffer
+                .set_text(&mut font_manager, &text, attrs, cosmic_text::Shaping::Advanced);
+        } else {
+            let spans = fallback_spans(&mut font_manager, &text, &self.attrs, &self.fallback_families);
+            let spans = spans
+                .iter()
+                .map(|(range, family)| (&text[range.clone()], attrs.family(CosmicFamily::Name(family))));
+            self.buffer
+                .set_rich_text(&mut font_manager, spans, attrs, cosmic_text::Shaping::Advanced);
+        }
+
+        for line in self.buffer.lines.iter_mut() {
+            line.set_align(Some(self.alignment.into()));
+        }
+
+        // Perform shaping
+        self.buffer.shape_until_scroll(&mut font_manager, true);
+
+        font_size
+    }
+
+    /// Lay out the shaped glyphs according to the orientation. Returns the glyphs and the width
+    /// and height of the text.
+    fn place_glyphs(&self, font_size: f32) -> (Vec<PlacedGlyph>, f32, f32) {
+        let first_line_y = self.buffer.layout_runs().next().map_or(0.0, |run| run.line_y);
+        let place =
+            |run: &cosmic_text::LayoutRun, glyph: &cosmic_text::LayoutGlyph, x: f32, baseline: f32| PlacedGlyph {
+                font_id: glyph.font_id,
+                id: glyph.glyph_id,
+                x: x + glyph.x_offset * glyph.font_size,
+                y: baseline + glyph.y - glyph.y_offset * glyph.font_size,
+                w: glyph.w,
+                line: run.line_i,
+                start: glyph.start,
+                end: glyph.end,
+                baseline,
+                top: baseline - (run.line_y - run.line_top),
+                height: run.line_height,
+            };
+
+        match self.orientation {
+            TextOrientation::Horizontal => {
+                let glyphs = self
+                    .buffer
+                    .layout_runs()
+                    .flat_map(|run| {
+                        run.glyphs
+                            .iter()
+                            .map(|glyph| place(&run, glyph, glyph.x, run.line_y - first_line_y))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                let (width, height) = measure(&self.buffer);
+                (glyphs, width, height)
+            }
+            TextOrientation::Vertical => {
+                // every character takes up one line height, centered horizontally in its column
+                let mut glyphs = Vec::new();
+                let (mut columns, mut rows) = (0, 0);
+                for (column, run) in self.buffer.layout_runs().enumerate() {
+                    let mut run_glyphs = run.glyphs.iter().collect::<Vec<_>>();
+                    run_glyphs.sort_by_key(|glyph| glyph.start);
+                    for (row, glyph) in run_glyphs.iter().enumerate() {
+                        let x = (column as f32 + 0.5) * font_size - glyph.w / 2.0;
+                        let mut placed = place(&run, glyph, x, row as f32 * font_size);
+                        placed.height = font_size;
+                        glyphs.push(placed);
+                    }
+                    columns = column + 1;
+                    rows = rows.max(run_glyphs.len());
+                }
+                (glyphs, columns as f32 * font_size, rows as f32 * font_size)
+            }
+        }
+    }
+
+    /// The position in the scene where the baseline of the first line starts.
+    fn origin(
+        &self,
+        width: f32,
+        height: f32,
+        window_size: PixelSize,
+        screen_props: PhysicalScreen,
+        coordinate_system: CoordinateSystem,
+    ) -> (f32, f32) {
+        let pos_x = self.params.x.eval(window_size, screen_props);
+        let pos_y = self.params.y.eval(window_size, screen_props);
+
+        // depending on the achoring, we need to adjust the position
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (pos_x, pos_y),
+            width,
+            height,
+            window_size,
+            coordinate_system,
+        );
+
+        // the baseline is below the top of the first line
+        let ascent = self
+            .buffer
+            .layout_runs()
+            .next()
+            .map_or(0.0, |run| run.line_y - run.line_top);
+        (x, y + ascent)
+    }
+
+    /// The shaped layout of the text in the window, e.g. to define areas of interest for each
+    /// word. Transformations of the stimulus are not taken into account.
+    pub fn metrics(
+        &mut self,
+        window_size: PixelSize,
+        screen_props: PhysicalScreen,
+        coordinate_system: CoordinateSystem,
+    ) -> TextMetrics {
+        let font_size = self.shape(window_size, screen_props);
+        let (mut placed, width, height) = self.place_glyphs(font_size);
+        let (origin_x, origin_y) = self.origin(width, height, window_size, screen_props, coordinate_system);
+        placed.sort_by_key(|glyph| (glyph.line, glyph.start));
+
+        let lines = self
+            .buffer
+            .layout_runs()
+            .map(|run| (run.line_i, run.text))
+            .collect::<HashMap<_, _>>();
+        let is_mark = |c: char| c == '\u{200E}' || c == '\u{200F}';
+
+        let mut characters: Vec<TextBox> = Vec::new();
+        let mut baselines: Vec<(usize, f32)> = Vec::new();
+        for glyph in &placed {
+            let line_text = lines[&glyph.line];
+            let text = line_text[glyph.start..glyph.end].replace(is_mark, "");
+            if text.is_empty() {
+                continue;
+            }
+            let character = TextBox {
+                text,
+                line: glyph.line,
+                index: line_text[..glyph.start].chars().filter(|c| !is_mark(*c)).count(),
+                x: origin_x + glyph.x,
+                y: origin_y + glyph.top,
+                width: glyph.w,
+                height: glyph.height,
+            };
+            // glyphs of the same cluster (e.g. a base letter and a combining mark) form one character
+            match characters.last_mut() {
+                Some(last) if last.line == character.line && last.index == character.index => last.union(&character),
+                _ => characters.push(character),
+            }
+            match baselines.last_mut() {
+                Some((line, baseline)) if *line == glyph.line => *baseline = baseline.min(origin_y + glyph.baseline),
+                _ => baselines.push((glyph.line, origin_y + glyph.baseline)),
+            }
+        }
+
+        let mut words: Vec<TextBox> = Vec::new();
+        let mut previous: Option<&TextBox> = None;
+        for character in &characters {
+            let is_space = character.text.chars().all(char::is_whitespace);
+            let continues_word = previous.map_or(false, |previous| {
+                previous.line == character.line && !previous.text.chars().all(char::is_whitespace)
+            });
+            if !is_space {
+                match words.last_mut() {
+                    Some(word) if continues_word => {
+                        word.text.push_str(&character.text);
+                        word.union(character);
+                    }
+                    _ => words.push(character.clone()),
+                }
+            }
+            previous = Some(character);
+        }
+
+        let mut bounds = TextBox {
+            text: self.params.text.clone(),
+            line: 0,
+            index: 0,
+            x: origin_x,
+            y: origin_y,
+            width: 0.0,
+            height: 0.0,
+        };
+        if let Some((first, rest)) = characters.split_first() {
+            bounds.x = first.x;
+            bounds.y = first.y;
+            bounds.width = first.width;
+            bounds.height = first.height;
+            for character in rest {
+                bounds.union(character);
+            }
+        }
+
+        // convert from the scene to the coordinate system of the window
+        let to_window = |mut text_box: TextBox| {
+            let (x, y, width, height) =
+                coordinate_system.rect_from_scene(text_box.x, text_box.y, text_box.width, text_box.height, window_size);
+            (text_box.x, text_box.y, text_box.width, text_box.height) = (x, y, width, height);
+            text_box
+        };
+
+        TextMetrics {
+            bounds: to_window(bounds),
+            baselines: baselines
+                .into_iter()
+                .map(|(_, baseline)| coordinate_system.from_scene(0.0, baseline, window_size).1)
+                .collect(),
+            characters: characters.into_iter().map(to_window).collect(),
+            words: words.into_iter().map(to_window).collect(),
+        }
+    }
 }
 
+/// A stimulus that displays text.
+///
+/// Text is shaped with font fallback: characters that the font does not cover, e.g. emoji or
+/// characters from other scripts, are taken from the families in `font_family` (if it is a list)
+/// and then from any other loaded font, so load fonts that cover them (e.g. with
+/// `ExperimentContext.load_system_fonts`). Color emoji fonts (COLR and CBDT) are rendered in color
+/// with the Skia renderer.
+///
+/// Parameters
+/// ----------
+/// text : str
+///   The text to display.
+/// font_size : Size
+///   The font size.
+/// font_family : str or list[str], optional
+///   The font family, or a list of families that are tried in order for each character.
+///   Defaults to "Noto Sans".
+/// alignment : str, optional
+///   How lines are aligned relative to each other: "left", "center" (default), "right", or
+///   "justify".
+/// direction : str, optional
+///   The base direction of each paragraph: "auto" (default) detects it from the first strong
+///   character, "ltr" and "rtl" force left-to-right or right-to-left, e.g. for paragraphs that
+///   start with a number or a Latin word but should be laid out right-to-left. Mixed runs (e.g.
+///   Latin words in Hebrew or Arabic text) are always ordered with the Unicode bidirectional
+///   algorithm.
+/// orientation : str, optional
+///   "horizontal" (default) or "vertical". Vertical text stacks the characters of each line from
+///   top to bottom, and lines become columns from left to right.
+/// glyph_rotation : float, optional
+///   Rotates each character around its center by this many degrees (clockwise), e.g. 90 for
+///   sideways letters in a vertical marquee. Defaults to 0.
 #[derive(Debug, Clone)]
+#[cfg(feature = "python")]
 #[pyclass(name = "TextStimulus", extends=PyStimulus)]
 pub struct PyTextStimulus();
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyTextStimulus {
     #[new]
     #[pyo3(signature = (
         text,
         font_size,
-        font_family = "Noto Sans",
+        font_family = FontFamilies::Family("Noto Sans".to_string()),
         font_weight = FontWeight::Regular,
         alignment = TextAlignment::Center,
+        direction = TextDirection::Auto,
+        orientation = TextOrientation::Horizontal,
+        glyph_rotation = 0.0,
         alpha = 1.0,
         anchor = Anchor::Center,
         x = IntoSize(Size::Pixels(0.0)),
@@ -182,9 +628,12 @@ impl PyTextStimulus {
         py: Python,
         text: &str,
         font_size: IntoSize,
-        font_family: &str,
+        font_family: FontFamilies,
         font_weight: FontWeight,
         alignment: TextAlignment,
+        direction: TextDirection,
+        orientation: TextOrientation,
+        glyph_rotation: f64,
         alpha: f64,
         anchor: Anchor,
         x: IntoSize,
@@ -201,17 +650,76 @@ impl PyTextStimulus {
                 y.into(),
                 text,
                 alignment,
+                direction,
+                orientation,
                 anchor,
                 font_size.into(),
-                font_family,
+                &font_family.into_vec(),
                 font_weight,
                 fill_color.into(),
                 alpha,
+                glyph_rotation,
                 transform,
                 &context,
             )),
         )
     }
+
+    /// The shaped layout of the text, e.g. to define an area of interest for each word in a
+    /// reading experiment. All positions are in pixels in the coordinate system of the window (like
+    /// the mouse position), and "x" and "y" are the corner of a rectangle with the smallest
+    /// coordinates. Transformations of the stimulus are not taken into account.
+    ///
+    /// Parameters
+    /// ----------
+    /// window : Window
+    ///   The window the text is shown in. Needed to convert the units of the position and the font
+    ///   size.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///   A dictionary with the bounding box of the text ("x", "y", "width", "height", and "shape"),
+    ///   the "baseline" of each line, and lists of "characters" and "words". Each character and
+    ///   word is a dictionary with its "text", the "line" it is on, the "index" of its first
+    ///   character in the line, its rectangle ("x", "y", "width", "height"), and the rectangle as a
+    ///   `Shape` that can be passed to `AreaOfInterest`.
+    #[pyo3(name = "metrics")]
+    fn py_metrics<'py>(slf: PyRefMut<'py, Self>, window: &Window, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (window_size, screen_props, coordinate_system) = {
+            let window_state = window.state.lock().unwrap();
+            let window_state = window_state.as_ref().unwrap();
+            (
+                window_state.size,
+                window_state.physical_screen,
+                window_state.coordinate_system,
+            )
+        };
+        let metrics =
+            downcast_py_stimulus_mut!(slf, TextStimulus).metrics(window_size, screen_props, coordinate_system);
+
+        let dict = metrics.bounds.to_dict(py)?;
+        dict.del_item("line")?;
+        dict.del_item("index")?;
+        dict.set_item("baseline", metrics.baselines)?;
+        dict.set_item(
+            "characters",
+            metrics
+                .characters
+                .iter()
+                .map(|character| character.to_dict(py))
+                .collect::<PyResult<Vec<_>>>()?,
+        )?;
+        dict.set_item(
+            "words",
+            metrics
+                .words
+                .iter()
+                .map(|word| word.to_dict(py))
+                .collect::<PyResult<Vec<_>>>()?,
+        )?;
+        Ok(dict)
+    }
 }
 
 impl_pystimulus_for_wrapper!(PyTextStimulus, TextStimulus);
@@ -228,67 +736,69 @@ impl Stimulus for TextStimulus {
 
         let window_size = window_state.size;
         let screen_props = window_state.physical_screen;
-        let mut font_manager = self.font_manager.lock().unwrap();
-
-        // convert physical units to pixels
-        let pos_x = self.params.x.eval(window_size, screen_props) as f64;
-        let pos_y = self.params.y.eval(window_size, screen_props) as f64;
-        let font_size = self.params.font_size.eval(window_size, screen_props) as f64;
 
         let trans_mat = self.transform.eval(window_size, screen_props);
 
         let fill_color: RGBA = self.params.fill_color.into();
 
-        // Set a size for the text buffer, in pixels
-        self.buffer.set_size(&mut font_manager, None, None);
+        let font_size = self.shape(window_size, screen_props);
 
-        self.buffer.set_metrics(
-            &mut font_manager,
-            CosmicMetrics::new(font_size as f32, font_size as f32),
+        // get the positions of the glyphs and the size of the text
+        let (placed, bb_width, bb_height) = self.place_glyphs(font_size);
+        let (origin_x, origin_y) = self.origin(
+            bb_width,
+            bb_height,
+            window_size,
+            screen_props,
+            window_state.coordinate_system,
         );
 
-        let attrs = (&self.attrs).into();
-
-        // Add some text!
-        self.buffer
-            .set_text(&mut font_manager, &self.params.text, attrs, cosmic_text::Shaping::Basic);
-
-        // Perform shaping
-        self.buffer.shape_until_scroll(&mut font_manager, true);
-
-        // get the width and height of the text
-        let (bb_width, bb_height) = measure(&self.buffer);
-        // let (bb_width, bb_height) = (bb_width as f64, bb_height as f64);
-
-        // depending on the achoring, we need to adjust the position
-        let (new_x, new_y) = self
-            .anchor
-            .to_top_left(pos_x as f32, pos_y as f32, bb_width, bb_height / 2.0);
-
-        let mut glyphs = vec![];
+        let mut font_manager = self.font_manager.lock().unwrap();
 
-        for run in self.buffer.layout_runs() {
-            for glyph in run.glyphs {
-                let glyph = renderer::font::Glyph {
-                    id: glyph.glyph_id,
-                    position: (glyph.x as f32, glyph.y as f32).into(),
-                };
-                glyphs.push(glyph);
+        // glyphs can come from different fonts because of fallback, so they are drawn per font
+        let mut glyphs: Vec<(cosmic_text::fontdb::ID, Vec<renderer::font::Glyph>)> = vec![];
+        let rotation = (self.params.glyph_rotation as f32).to_radians();
+        let (sin, cos) = rotation.sin_cos();
+
+        for glyph in placed {
+            // rotate around the center of the glyph rather than its origin on the baseline
+            let (cx, cy) = (glyph.x + glyph.w / 2.0, glyph.y - 0.35 * font_size);
+            let (dx, dy) = (glyph.x - cx, glyph.y - cy);
+            let position = (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos);
+
+            let glyph_out = renderer::font::Glyph {
+                id: glyph.id,
+                position: position.into(),
+                rotation,
+            };
+            match glyphs.iter_mut().find(|(id, _)| *id == glyph.font_id) {
+                Some((_, font_glyphs)) => font_glyphs.push(glyph_out),
+                None => glyphs.push((glyph.font_id, vec![glyph_out])),
             }
         }
 
-        let brush = Brush::Solid(fill_color);
-
-        scene.draw_glyphs(
-            (new_x, -new_y).into(),
-            &glyphs,
-            &self.font,
-            font_size as f32,
-            brush,
-            Some(self.params.alpha as f32),
-            None,
-            None,
-        );
+        for (font_id, font_glyphs) in glyphs {
+            let font = if font_id == self.font_id {
+                &self.font
+            } else {
+                let Some(font) = fallback_face(&mut self.fallback_faces, &mut font_manager, font_id, window_state)
+                else {
+                    continue;
+                };
+                font
+            };
+
+            scene.draw_glyphs(
+                (origin_x, origin_y).into(),
+                &font_glyphs,
+                font,
+                font_size,
+                Brush::Solid(fill_color),
+                Some(self.params.alpha as f32),
+                Some(trans_mat.into()),
+                None,
+            );
+        }
     }
 
     fn set_visible(&mut self, visible: bool) {
@@ -299,6 +809,27 @@ impl Stimulus for TextStimulus {
         self.visible
     }
 
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+        let coordinate_system = window_state.coordinate_system;
+
+        let bounds = self.metrics(window_size, screen_props, coordinate_system).bounds;
+        let bounds = coordinate_system.rect_to_scene(bounds.x, bounds.y, bounds.width, bounds.height, window_size);
+        let anchor = coordinate_system.to_scene(
+            self.params.x.eval(window_size, screen_props),
+            self.params.y.eval(window_size, screen_props),
+            window_size,
+        );
+
+        let trans_mat = self.transform.eval(window_size, screen_props);
+        vec![StimulusLayout {
+            bounds: helpers::transform_rect(&trans_mat, bounds),
+            anchor: Some(helpers::transform_point(&trans_mat, anchor)),
+            hit_region: None,
+        }]
+    }
+
     fn animations(&mut self) -> &mut Vec<Animation> {
         &mut self.animations
     }
@@ -315,6 +846,14 @@ impl Stimulus for TextStimulus {
         self.transform.clone()
     }
 
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
     fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
         self.params.get_param(name)
     }
@@ -380,3 +919,58 @@ fn measure(buffer: &CosmicBuffer) -> (f32, f32) {
         (size.0.max(run.line_w), size.1 + run.line_height)
     })
 }
+
+/// Split `text` into runs that use the first family (the primary one, then the fallback families)
+/// that covers each character. Characters that no family covers are left to the font system's
+/// fallback.
+fn fallback_spans(
+    font_system: &mut CosmicFontSystem,
+    text: &str,
+    attrs: &OwnedCosmicAttrs,
+    fallback_families: &[String],
+) -> Vec<(std::ops::Range<usize>, String)> {
+    let fonts = std::iter::once(&attrs.family)
+        .chain(fallback_families)
+        .filter_map(|family| {
+            let query = cosmic_text::fontdb::Query {
+                families: &[CosmicFamily::Name(family)],
+                weight: attrs.weight,
+                stretch: attrs.stretch,
+                style: attrs.style,
+            };
+            let id = font_system.db().query(&query)?;
+            Some((family.clone(), font_system.get_font(id)?))
+        })
+        .collect::<Vec<_>>();
+
+    let mut spans: Vec<(std::ops::Range<usize>, String)> = Vec::new();
+    for (index, c) in text.char_indices() {
+        let family = fonts
+            .iter()
+            .find(|(_, font)| font.unicode_codepoints().binary_search(&(c as u32)).is_ok())
+            .map_or(&attrs.family, |(family, _)| family);
+        let end = index + c.len_utf8();
+        match spans.last_mut() {
+            Some((range, last)) if last == family => range.end = end,
+            _ => spans.push((index..end, family.clone())),
+        }
+    }
+    spans
+}
+
+/// Get the renderer's font face for a font of the font system, creating it the first time it is
+/// used.
+pub(super) fn fallback_face<'a>(
+    faces: &'a mut HashMap<cosmic_text::fontdb::ID, renderer::font::DynamicFontFace>,
+    font_system: &mut CosmicFontSystem,
+    font_id: cosmic_text::fontdb::ID,
+    window_state: &WindowState,
+) -> Option<&'a renderer::font::DynamicFontFace> {
+    if !faces.contains_key(&font_id) {
+        let font = font_system.get_font(font_id)?;
+        let index = font_system.db().face(font_id)?.index;
+        let face = window_state.shared_renderer_state.create_font_face(font.data(), index);
+        faces.insert(font_id, face);
+    }
+    faces.get(&font_id)
+}
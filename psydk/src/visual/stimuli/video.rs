@@ -9,6 +9,7 @@ use crate::{app::GPUState, errors::PsydkError};
 use byte_slice_cast::*;
 use gstreamer::{element_error, element_warning, prelude::*};
 use psydk_proc::StimulusParams;
+#[cfg(feature = "python")]
 use pyo3::ffi::c_str;
 use renderer::{
     brushes::{Brush, Extend, ImageSampling},
@@ -19,10 +20,12 @@ use renderer::{
 };
 use uuid::Uuid;
 
+#[cfg(feature = "python")]
+use super::PyStimulus;
 use super::{
     animations::Animation,
     helpers::{self, get_experiment_context},
-    impl_pystimulus_for_wrapper, PyStimulus, Stimulus, StimulusParamValue, StimulusParams,
+    impl_pystimulus_for_wrapper, Stimulus, StimulusLayout, StimulusParamValue, StimulusParams,
 };
 use crate::{
     context::{ExperimentContext, PyRendererFactory},
@@ -602,9 +605,11 @@ impl VideoStimulus {
 }
 
 #[derive(Debug, Clone)]
+#[cfg(feature = "python")]
 #[pyclass(name = "VideoStimulus", extends=PyStimulus)]
 pub struct PyVideoStimulus();
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyVideoStimulus {
     #[new]
@@ -820,7 +825,15 @@ impl Stimulus for VideoStimulus {
         let width = self.params.width.eval(window_size, screen_props);
         let height = self.params.height.eval(window_size, screen_props);
 
-        let (x, y) = self.anchor.to_top_left(x, y, width, height);
+        let (anchor_x, anchor_y) = window_state.coordinate_system.to_scene(x, y, window_size);
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (x, y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
 
         let image_offset_x = self.params.image_x.eval(window_size, screen_props);
         let image_offset_y = self.params.image_y.eval(window_size, screen_props);
@@ -829,8 +842,8 @@ impl Stimulus for VideoStimulus {
         let trans_mat = self.transformation.clone()
             * Transformation2D::RotationPoint(
                 self.params.rotation as f32,
-                self.params.x.clone(),
-                self.params.y.clone(),
+                Size::Pixels(anchor_x),
+                Size::Pixels(anchor_y),
             );
 
         let trans_mat = trans_mat.eval(window_size, screen_props);
@@ -894,10 +907,14 @@ impl Stimulus for VideoStimulus {
         let width = self.params.width.eval(window_size, screen_props);
         let height = self.params.height.eval(window_size, screen_props);
 
+        let coordinate_system = window_state.coordinate_system;
+        let (ix, iy) = helpers::anchored_top_left(self.anchor, (ix, iy), width, height, window_size, coordinate_system);
+
         let trans_mat = self.transformation.eval(window_size, screen_props);
 
         let x = x.eval(window_size, screen_props);
         let y = y.eval(window_size, screen_props);
+        let (x, y) = coordinate_system.to_scene(x, y, window_size);
 
         // Apply transformation by multiplying the point with the transformation matrix
         let p = nalgebra::Vector3::new(x, y, 1.0);
@@ -907,6 +924,51 @@ impl Stimulus for VideoStimulus {
         p_new[0] >= ix && p_new[0] <= ix + width && p_new[1] >= iy && p_new[1] <= iy + height
     }
 
+    fn layout(&mut self, window_state: &WindowState) -> Vec<StimulusLayout> {
+        let window_size = window_state.size;
+        let screen_props = window_state.physical_screen;
+
+        let x = self.params.x.eval(window_size, screen_props);
+        let y = self.params.y.eval(window_size, screen_props);
+        let width = self.params.width.eval(window_size, screen_props);
+        let height = self.params.height.eval(window_size, screen_props);
+
+        let (anchor_x, anchor_y) = window_state.coordinate_system.to_scene(x, y, window_size);
+        let (x, y) = helpers::anchored_top_left(
+            self.anchor,
+            (x, y),
+            width,
+            height,
+            window_size,
+            window_state.coordinate_system,
+        );
+
+        // the same transformations as in `draw` and `contains`
+        let transform = (self.transformation.clone()
+            * Transformation2D::RotationPoint(
+                self.params.rotation as f32,
+                Size::Pixels(anchor_x),
+                Size::Pixels(anchor_y),
+            ))
+        .eval(window_size, screen_props);
+        let hit_transform = self.transformation.eval(window_size, screen_props);
+
+        vec![helpers::rect_layout(
+            (x, y, width, height),
+            (anchor_x, anchor_y),
+            &transform,
+            &hit_transform,
+        )]
+    }
+
+    fn param_names(&self) -> &'static [&'static str] {
+        self.params.param_names()
+    }
+
+    fn param_type(&self, name: &str) -> Option<&'static str> {
+        self.params.param_type(name)
+    }
+
     fn get_param(&self, name: &str) -> Option<StimulusParamValue> {
         self.params.get_param(name)
     }
@@ -0,0 +1,303 @@
+//! Building blocks of interactive stimuli, such as forms.
+//!
+//! Interactive stimuli lay out their parts in the coordinates of the scene when they are drawn and
+//! remember where the parts ended up, so that pointer events are hit tested against what was on the
+//! screen. They are driven by the input events of a window, either by passing the events to
+//! `handle_event` or by attaching the stimulus to the window, which does so as the events arrive.
+
+use renderer::{brushes::Brush, colors::RGBA, shapes::Shape, DynamicScene};
+
+use super::{
+    text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+    DynamicStimulus, Stimulus, StimulusParamValue,
+};
+use crate::{
+    context::ExperimentContext,
+    errors::PsydkResult,
+    input::{Event, EventHandlerId, EventKind, MouseButton},
+    visual::{
+        color::LinRgba,
+        geometry::{Anchor, CoordinateSystem, Size, Transformation2D},
+        window::{PixelSize, Window, WindowState},
+    },
+};
+
+/// How far (in pixels) a pointer has to move while pressed before it drags instead of clicks.
+pub(crate) const DRAG_THRESHOLD: f32 = 8.0;
+
+/// The color of error messages and of answers that are missing.
+pub(crate) const ERROR_COLOR: LinRgba = LinRgba {
+    r: 0.8,
+    g: 0.05,
+    b: 0.05,
+    a: 1.0,
+};
+
+/// A rectangle in the coordinates of the scene.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn shape(&self) -> Shape {
+        Shape::rectangle((self.x, self.y), self.width as f64, self.height as f64)
+    }
+
+    pub fn rounded(&self, radius: f32) -> Shape {
+        Shape::rounded_rectangle((self.x, self.y), self.width as f64, self.height as f64, radius as f64)
+    }
+}
+
+/// Fill a shape with a solid color.
+pub(crate) fn fill(scene: &mut DynamicScene, shape: Shape, color: LinRgba) {
+    scene.draw_shape_fill(shape, Brush::Solid(RGBA::from(color)), None, None);
+}
+
+/// Outline a shape with a solid color.
+pub(crate) fn stroke(scene: &mut DynamicScene, shape: Shape, color: LinRgba, width: f32) {
+    scene.draw_shape_stroke(
+        shape,
+        Brush::Solid(RGBA::from(color)),
+        renderer::styles::StrokeStyle::new(width as f64),
+        None,
+        None,
+    );
+}
+
+/// `color` with its alpha multiplied by `alpha`.
+pub(crate) fn faded(color: LinRgba, alpha: f32) -> LinRgba {
+    LinRgba::new(color.r, color.g, color.b, color.a * alpha)
+}
+
+/// What a pointer (the mouse or a finger) did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Pointer {
+    Down,
+    Move,
+    Up,
+}
+
+/// The window an interactive stimulus was drawn in last, to convert the positions of pointer events
+/// to the coordinates of the scene that its parts were laid out in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Viewport {
+    size: PixelSize,
+    coordinate_system: CoordinateSystem,
+}
+
+impl Viewport {
+    pub fn new(window_state: &WindowState) -> Self {
+        Self {
+            size: window_state.size,
+            coordinate_system: window_state.coordinate_system,
+        }
+    }
+
+    /// The pointer action of an event (only the left mouse button counts) and its position in the
+    /// coordinates of the scene.
+    pub fn pointer(&self, event: &Event) -> Option<(Pointer, (f32, f32))> {
+        let to_scene = |(x, y): (f32, f32)| self.coordinate_system.to_scene(x, y, self.size);
+        let (pointer, position) = match event {
+            Event::MouseButtonPress {
+                button: MouseButton::Left(),
+                position,
+                ..
+            }
+            | Event::TouchStart { position, .. } => (Pointer::Down, to_scene(*position)),
+            Event::MouseButtonRelease {
+                button: MouseButton::Left(),
+                position,
+                ..
+            }
+            | Event::TouchEnd { position, .. } => (Pointer::Up, to_scene(*position)),
+            Event::TouchMove { position, .. } => (Pointer::Move, to_scene(*position)),
+            // cursor movements are already in the coordinates of the scene
+            Event::CursorMoved { position, .. } => (Pointer::Move, *position),
+            _ => return None,
+        };
+        Some((pointer, position))
+    }
+}
+
+/// A line of text that is drawn with its top left corner at a point in the coordinates of the
+/// scene. One label can draw several texts per frame.
+#[derive(Debug)]
+pub(crate) struct Label(TextStimulus);
+
+impl Label {
+    pub fn new(weight: FontWeight, context: &ExperimentContext) -> Self {
+        Self(TextStimulus::new(
+            Size::Pixels(0.0),
+            Size::Pixels(0.0),
+            "",
+            TextAlignment::Left,
+            TextDirection::Ltr,
+            TextOrientation::Horizontal,
+            Anchor::TopLeft,
+            Size::Pixels(16.0),
+            &[],
+            weight,
+            LinRgba::new(1.0, 1.0, 1.0, 1.0),
+            1.0,
+            0.0,
+            Transformation2D::Identity(),
+            context,
+        ))
+    }
+
+    fn set(&mut self, text: &str, font_size: f32, window_state: &WindowState) -> (f32, f32) {
+        self.0.set_param("text", StimulusParamValue::String(text.to_string()));
+        self.0
+            .set_param("font_size", StimulusParamValue::Size(Size::Pixels(font_size)));
+        let bounds = self
+            .0
+            .metrics(
+                window_state.size,
+                window_state.physical_screen,
+                window_state.coordinate_system,
+            )
+            .bounds;
+        // empty text has no bounds, but still takes up a line
+        (bounds.width, bounds.height.max(font_size))
+    }
+
+    /// The width and height of `text`.
+    pub fn measure(&mut self, text: &str, font_size: f32, window_state: &WindowState) -> (f32, f32) {
+        self.set(text, font_size, window_state)
+    }
+
+    /// Break `text` into lines at spaces so that each line is at most `width` wide, where possible.
+    /// Words that are wider than `width` get a line of their own.
+    pub fn wrap(&mut self, text: &str, width: f32, font_size: f32, window_state: &WindowState) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for word in text.split_whitespace() {
+            let fits = match lines.last() {
+                Some(line) => {
+                    let candidate = format!("{line} {word}");
+                    self.measure(&candidate, font_size, window_state).0 <= width
+                }
+                None => false,
+            };
+            match (fits, lines.last_mut()) {
+                (true, Some(line)) => {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_string()),
+            }
+        }
+        lines
+    }
+
+    /// Draw `text` with its top left corner at `position`. Returns its width and height.
+    pub fn draw(
+        &mut self,
+        scene: &mut DynamicScene,
+        window_state: &WindowState,
+        text: &str,
+        position: (f32, f32),
+        font_size: f32,
+        color: LinRgba,
+    ) -> (f32, f32) {
+        let size = self.set(text, font_size, window_state);
+        let (x, y) = window_state
+            .coordinate_system
+            .from_scene(position.0, position.1, window_state.size);
+        self.0.set_param("x", StimulusParamValue::Size(Size::Pixels(x)));
+        self.0.set_param("y", StimulusParamValue::Size(Size::Pixels(y)));
+        self.0.set_param("fill_color", StimulusParamValue::LinRgba(color));
+        self.0.draw(scene, window_state);
+        size
+    }
+}
+
+/// A stimulus that responds to input events.
+pub(crate) trait Interactive: Stimulus {
+    /// Handle an input event. Returns true if the event was used.
+    fn handle_event(&mut self, event: &Event) -> bool;
+
+    /// Events that the stimulus wants delivered to the window, taken after each call to
+    /// `handle_event` when the stimulus is attached to a window.
+    fn take_events(&mut self) -> Vec<Event> {
+        Vec::new()
+    }
+}
+
+/// The kinds of events that are passed to attached stimuli.
+const ATTACHED_EVENTS: [EventKind; 10] = [
+    EventKind::MouseButtonPress,
+    EventKind::MouseButtonRelease,
+    EventKind::CursorMoved,
+    EventKind::MouseWheel,
+    EventKind::TouchStart,
+    EventKind::TouchMove,
+    EventKind::TouchEnd,
+    EventKind::KeyPress,
+    EventKind::Text,
+    EventKind::ImePreedit,
+];
+
+/// Passes the input events of a window to an interactive stimulus as they arrive. The events are
+/// handled on the event loop, while the stimulus is locked.
+#[derive(Debug)]
+pub(crate) struct Attachment {
+    window: Window,
+    handlers: Vec<EventHandlerId>,
+}
+
+impl Attachment {
+    pub fn new<T: Interactive>(window: &Window, stimulus: &DynamicStimulus) -> PsydkResult<Self> {
+        let mut attachment = Self {
+            window: window.clone(),
+            handlers: Vec::new(),
+        };
+        for kind in ATTACHED_EVENTS {
+            let (stimulus, window) = (stimulus.clone(), window.clone());
+            let id = window.clone().add_event_handler(kind, move |event| {
+                let (used, events) = {
+                    let mut stimulus = stimulus.lock();
+                    let Some(stimulus) = stimulus.downcast_mut::<T>() else {
+                        return false;
+                    };
+                    (stimulus.handle_event(&event), stimulus.take_events())
+                };
+                // delivered once the stimulus is unlocked, as the window may pass them back to it
+                for event in events {
+                    window.inject_event(event);
+                }
+                used
+            })?;
+            attachment.handlers.push(id);
+        }
+        Ok(attachment)
+    }
+
+    /// Stop passing events to the stimulus.
+    pub fn detach(&mut self) {
+        for id in self.handlers.drain(..) {
+            self.window.remove_event_handler(id);
+        }
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
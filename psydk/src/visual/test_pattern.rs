@@ -0,0 +1,371 @@
+//! Test patterns for checking a display before a session, e.g. that the window is shown at the
+//! native resolution, that gray levels are distinguishable, and that the black level is set up
+//! correctly.
+
+use psydk_proc::FromPyStr;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use renderer::{
+    image::{Rgba, RgbaImage},
+    renderer::ColorSpace,
+};
+use send_wrapper::SendWrapper;
+use strum::{Display, EnumString};
+
+use super::{
+    color::LinRgba,
+    geometry::{Anchor, Shape, Size, Transformation2D},
+    stimuli::{
+        image::{ImageParams, ImageStimulus},
+        shape::{GradientType, ShapeParams, ShapeStimulus},
+        text::{FontWeight, TextAlignment, TextDirection, TextOrientation, TextStimulus},
+        DynamicStimulus, StimulusParamValue, StrokeStyle,
+    },
+    window::Window,
+};
+use crate::{context::ExperimentContext, errors::PsydkResult};
+
+/// The size of the squares of the checkerboard in pixels.
+const CHECKER_SIZE: u32 = 32;
+/// The colors of the top bars of the SMPTE color bars (75% intensity).
+const SMPTE_TOP: [[u8; 3]; 7] = [
+    [191, 191, 191],
+    [191, 191, 0],
+    [0, 191, 191],
+    [0, 191, 0],
+    [191, 0, 191],
+    [191, 0, 0],
+    [0, 0, 191],
+];
+/// The colors of the narrow middle bars of the SMPTE color bars.
+const SMPTE_MIDDLE: [[u8; 3]; 7] = [
+    [0, 0, 191],
+    [19, 19, 19],
+    [191, 0, 191],
+    [19, 19, 19],
+    [0, 191, 191],
+    [19, 19, 19],
+    [191, 191, 191],
+];
+
+/// A test pattern that fills the window.
+#[derive(EnumString, Display, Debug, Clone, Copy, PartialEq, FromPyStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TestPattern {
+    /// A continuous gray ramp from black to white (top half) above a ramp of 16 steps (bottom
+    /// half). Banding in the continuous ramp or steps that cannot be told apart show problems
+    /// with the bit depth, gamma, or black and white levels.
+    GrayRamp,
+    /// SMPTE color bars with 7.5% black setup. The three bars of the PLUGE (below, at, and above
+    /// black) in the bottom row are for setting the black level: only the right one should be
+    /// visible.
+    SmpteBars,
+    /// A black and white checkerboard with squares of 32 pixels.
+    Checkerboard,
+    /// One pixel wide lines every 10 pixels (gray) and every 100 pixels (white). Moiré or blurry
+    /// lines show that the window is not displayed at the native resolution.
+    PixelGrid,
+}
+
+impl TestPattern {
+    const ALL: [TestPattern; 4] = [
+        TestPattern::GrayRamp,
+        TestPattern::SmpteBars,
+        TestPattern::Checkerboard,
+        TestPattern::PixelGrid,
+    ];
+
+    /// The pattern `step` positions after this one, wrapping around.
+    fn cycle(self, step: isize) -> Self {
+        let n = Self::ALL.len() as isize;
+        let index = Self::ALL.iter().position(|pattern| *pattern == self).unwrap_or(0) as isize;
+        Self::ALL[(index + step).rem_euclid(n) as usize]
+    }
+
+    /// Render the pattern at the given size in pixels. Values are sRGB encoded.
+    pub fn render(&self, width: u32, height: u32) -> RgbaImage {
+        let gray = |v: u8| Rgba([v, v, v, 255]);
+        match self {
+            TestPattern::GrayRamp => RgbaImage::from_fn(width, height, |x, y| {
+                let t = x as f64 / width.saturating_sub(1).max(1) as f64;
+                if y < height / 2 {
+                    gray((t * 255.0).round() as u8)
+                } else {
+                    gray(((t * 16.0).floor().min(15.0) * 17.0) as u8)
+                }
+            }),
+            TestPattern::SmpteBars => RgbaImage::from_fn(width, height, |x, y| {
+                let [r, g, b] = smpte_bars(x as f64 / width as f64, y as f64 / height as f64);
+                Rgba([r, g, b, 255])
+            }),
+            TestPattern::Checkerboard => RgbaImage::from_fn(width, height, |x, y| {
+                gray(if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 {
+                    255
+                } else {
+                    0
+                })
+            }),
+            TestPattern::PixelGrid => RgbaImage::from_fn(width, height, |x, y| {
+                if x % 100 == 0 || y % 100 == 0 {
+                    gray(255)
+                } else if x % 10 == 0 || y % 10 == 0 {
+                    gray(128)
+                } else {
+                    gray(0)
+                }
+            }),
+        }
+    }
+}
+
+/// The color of the SMPTE color bars at the relative position `(u, v)`.
+fn smpte_bars(u: f64, v: f64) -> [u8; 3] {
+    let bar = u * 7.0;
+    if v < 2.0 / 3.0 {
+        return SMPTE_TOP[(bar as usize).min(6)];
+    }
+    if v < 0.75 {
+        return SMPTE_MIDDLE[(bar as usize).min(6)];
+    }
+
+    // -I, white, +Q, black, and the PLUGE (below, at, and above black)
+    match bar {
+        b if b < 1.25 => [0, 33, 76],
+        b if b < 2.5 => [255, 255, 255],
+        b if b < 3.75 => [50, 0, 106],
+        b if b < 5.0 => [19, 19, 19],
+        b if b < 5.0 + 1.0 / 3.0 => [9, 9, 9],
+        b if b < 5.0 + 2.0 / 3.0 => [19, 19, 19],
+        b if b < 6.0 => [29, 29, 29],
+        _ => [19, 19, 19],
+    }
+}
+
+fn shape(shape: Shape, fill_color: LinRgba, stroke: bool, anchor: Option<Anchor>) -> PsydkResult<DynamicStimulus> {
+    let params = ShapeParams {
+        shape,
+        x: Size::Pixels(0.0),
+        y: Size::Pixels(0.0),
+        fill_color,
+        gradient_angle: 0.0,
+        stroke_style: if stroke { StrokeStyle::Solid } else { StrokeStyle::None },
+        stroke_color: LinRgba::new(1.0, 0.0, 0.0, 1.0),
+        stroke_width: Size::Pixels(1.0),
+        alpha: None,
+    };
+    let stimulus = ShapeStimulus::new(
+        params,
+        None,
+        GradientType::Linear,
+        None,
+        anchor,
+        Transformation2D::Identity(),
+    )?;
+    Ok(DynamicStimulus::new(stimulus))
+}
+
+/// Show test patterns on `window` until escape, enter, or space is pressed. The left and right
+/// arrow keys switch between patterns. With `probe`, a crosshair follows the mouse and the value of
+/// the pattern under the cursor is shown in the bottom left corner.
+pub fn show_test_pattern(
+    context: &ExperimentContext,
+    window: &Window,
+    pattern: TestPattern,
+    probe: bool,
+) -> PsydkResult<()> {
+    let (size, coordinate_system) = window.with_state(|state| (state.size, state.coordinate_system))?;
+    let (width, height) = (size.width as f32, size.height as f32);
+    let corner = |x: f32, y: f32| coordinate_system.from_scene(x, y, size);
+
+    // the crosshair spans the whole window wherever the mouse is
+    let horizontal = shape(
+        Shape::Line {
+            x1: Size::Pixels(-width),
+            y1: Size::Pixels(0.0),
+            x2: Size::Pixels(width),
+            y2: Size::Pixels(0.0),
+        },
+        LinRgba::default(),
+        true,
+        None,
+    )?;
+    let vertical = shape(
+        Shape::Line {
+            x1: Size::Pixels(0.0),
+            y1: Size::Pixels(-height),
+            x2: Size::Pixels(0.0),
+            y2: Size::Pixels(height),
+        },
+        LinRgba::default(),
+        true,
+        None,
+    )?;
+
+    let (panel_x, panel_y) = corner(-width / 2.0 + 10.0, height / 2.0 - 10.0);
+    let panel = shape(
+        Shape::Rectangle {
+            x: Size::Pixels(0.0),
+            y: Size::Pixels(0.0),
+            width: Size::Pixels(560.0),
+            height: Size::Pixels(60.0),
+        },
+        LinRgba::new(0.0, 0.0, 0.0, 0.75),
+        false,
+        Some(Anchor::BottomLeft),
+    )?;
+    {
+        let mut panel = panel.lock();
+        panel.set_param("x", StimulusParamValue::Size(Size::Pixels(panel_x)));
+        panel.set_param("y", StimulusParamValue::Size(Size::Pixels(panel_y)));
+    }
+
+    let (text_x, text_y) = corner(-width / 2.0 + 20.0, height / 2.0 - 20.0);
+    let readout = DynamicStimulus::new(TextStimulus::new(
+        Size::Pixels(text_x),
+        Size::Pixels(text_y),
+        "",
+        TextAlignment::Left,
+        TextDirection::Ltr,
+        TextOrientation::Horizontal,
+        Anchor::BottomLeft,
+        Size::Pixels(16.0),
+        &[],
+        FontWeight::Regular,
+        LinRgba::new(1.0, 1.0, 1.0, 1.0),
+        1.0,
+        0.0,
+        Transformation2D::Identity(),
+        context,
+    ));
+
+    let (image_x, image_y) = corner(-width / 2.0, -height / 2.0);
+    let mut receiver = window.create_event_receiver();
+    let mut pattern = pattern;
+    loop {
+        let image = pattern.render(size.width, size.height);
+        let bitmap = context
+            .renderer_factory()
+            .create_bitmap_u8(image.clone(), ColorSpace::Srgb);
+        let params = ImageParams {
+            x: Size::Pixels(image_x),
+            y: Size::Pixels(image_y),
+            width: Size::Pixels(width),
+            height: Size::Pixels(height),
+            rotation: 0.0,
+            opacity: 1.0,
+            image_x: Size::Pixels(0.0),
+            image_y: Size::Pixels(0.0),
+        };
+        let background = DynamicStimulus::new(ImageStimulus::from_image(bitmap, params, None, Anchor::TopLeft));
+
+        let mut frame = window.get_frame()?;
+        frame.add(&background);
+        if probe {
+            frame.add(&horizontal);
+            frame.add(&vertical);
+            frame.add(&panel);
+            frame.add(&readout);
+        }
+
+        let step = loop {
+            if probe {
+                let mouse_position = window.with_state(|state| state.mouse_position)?;
+                let pixel = mouse_position.and_then(|(x, y)| {
+                    let (sx, sy) = coordinate_system.to_scene(x, y, size);
+                    let (px, py) = (sx + width / 2.0, sy + height / 2.0);
+                    let inside = px >= 0.0 && py >= 0.0 && px < width && py < height;
+                    inside.then(|| (x, y, px as u32, py as u32))
+                });
+
+                let text = match pixel {
+                    Some((x, y, px, py)) => {
+                        let Rgba([r, g, b, _]) = *image.get_pixel(px, py);
+                        format!("{pattern}: pixel ({px}, {py}), position ({x:.0}, {y:.0}), RGB ({r}, {g}, {b})")
+                    }
+                    None => format!("{pattern}"),
+                };
+                readout.lock().set_param(
+                    "text",
+                    StimulusParamValue::String(format!("{text}\nLeft/right: change pattern, escape: close")),
+                );
+
+                for line in [&horizontal, &vertical] {
+                    let mut line = line.lock();
+                    match mouse_position {
+                        Some((x, y)) => {
+                            line.set_param("x", StimulusParamValue::Size(Size::Pixels(x)));
+                            line.set_param("y", StimulusParamValue::Size(Size::Pixels(y)));
+                            line.show();
+                        }
+                        None => line.hide(),
+                    }
+                }
+            }
+
+            let result = window.present(&mut frame, None, None, true, None);
+            // the window was closed by the user
+            if window.with_state(|_| ()).is_err() {
+                return Ok(());
+            }
+            result?;
+
+            let events = receiver.poll();
+            if events.key_pressed("Escape") || events.key_pressed("Enter") || events.key_pressed("Space") {
+                return Ok(());
+            }
+            if events.key_pressed("ArrowRight") {
+                break 1;
+            }
+            if events.key_pressed("ArrowLeft") {
+                break -1;
+            }
+        };
+        pattern = pattern.cycle(step);
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ExperimentContext {
+    /// Show a test pattern to check the display before a session. The left and right arrow keys
+    /// switch between patterns, and escape, enter, or space closes the pattern.
+    ///
+    /// Available patterns are "gray_ramp" (a continuous and a stepped gray ramp), "smpte_bars"
+    /// (SMPTE color bars with a PLUGE for setting the black level), "checkerboard", and
+    /// "pixel_grid" (one pixel wide lines to check for scaling). Patterns are drawn pixel by pixel
+    /// at the size of the window.
+    ///
+    /// Parameters
+    /// ----------
+    /// pattern : str, optional
+    ///   The pattern that is shown first. Defaults to "gray_ramp".
+    /// window : Window, optional
+    ///   The window to show the pattern on. By default, a fullscreen window is opened on the
+    ///   default monitor and closed afterwards.
+    /// probe : bool, optional
+    ///   Show a crosshair at the mouse cursor and the value (sRGB, 0-255) of the pattern under the
+    ///   cursor. Defaults to True.
+    #[pyo3(name = "show_test_pattern")]
+    #[pyo3(signature = (pattern = TestPattern::GrayRamp, window = None, probe = true))]
+    fn py_show_test_pattern(
+        &self,
+        py: Python,
+        pattern: TestPattern,
+        window: Option<Window>,
+        probe: bool,
+    ) -> PyResult<()> {
+        let context = SendWrapper::new(self.clone());
+        let window = SendWrapper::new(window);
+        Ok(py.allow_threads(move || -> PsydkResult<()> {
+            match &*window {
+                Some(window) => show_test_pattern(&context, window, pattern, probe),
+                None => {
+                    let window = context.create_default_window(true, None, None, false)?;
+                    let result = show_test_pattern(&context, &window, pattern, probe);
+                    window.close();
+                    result
+                }
+            }
+        })?)
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyDictMethods};
+#[cfg(feature = "python")]
+use pyo3::{Bound, Py, PyAny, PyResult, Python};
+
+use crate::time::Timestamp;
+
+/// A frame that was shown later than expected.
+#[derive(Debug, Clone)]
+pub struct DroppedFrame {
+    /// When the late frame was presented.
+    pub timestamp: Instant,
+    /// The expected time between frames in seconds (one refresh interval).
+    pub expected_interval: f64,
+    /// The actual time since the previous frame in seconds.
+    pub actual_interval: f64,
+    /// Number of refresh intervals that were missed.
+    pub missed_frames: u32,
+    /// Number of stimuli in the frame.
+    pub n_stimuli: usize,
+    /// Time the CPU spent rendering the frame and submitting it to the GPU in seconds. The time the
+    /// GPU takes to execute the work is not included.
+    pub cpu_render_time: f64,
+    /// User-provided context, e.g. the trial number.
+    pub context: HashMap<String, String>,
+}
+
+/// Detects dropped frames by comparing successive frame onsets to the refresh interval.
+#[derive(Debug)]
+pub struct FrameWatchdog {
+    pub enabled: bool,
+    /// A frame is late if it arrives more than `tolerance` refresh intervals after the expected
+    /// time.
+    pub tolerance: f64,
+    /// Context that is attached to every dropped frame, e.g. the trial number.
+    pub context: HashMap<String, String>,
+    /// Python callback that is called with every dropped frame once `present` has returned, i.e.
+    /// after the frame was shown late.
+    #[cfg(feature = "python")]
+    pub callback: Option<Py<PyAny>>,
+    /// All dropped frames so far.
+    pub dropped_frames: Vec<DroppedFrame>,
+    /// Dropped frames that have not been passed to the callback yet.
+    pub pending: Vec<DroppedFrame>,
+    last_onset: Option<Instant>,
+    last_present_end: Option<Instant>,
+}
+
+impl Default for FrameWatchdog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tolerance: 0.5,
+            context: HashMap::new(),
+            #[cfg(feature = "python")]
+            callback: None,
+            dropped_frames: Vec::new(),
+            pending: Vec::new(),
+            last_onset: None,
+            last_present_end: None,
+        }
+    }
+}
+
+impl FrameWatchdog {
+    /// Call at the start of `present`. If the experiment did not present frames back-to-back (i.e.
+    /// more than one refresh interval passed since the previous `present` returned), the next frame
+    /// is not compared to the previous one.
+    pub fn begin_present(&mut self, refresh_interval: Duration) {
+        let idle = self
+            .last_present_end
+            .map_or(true, |end| end.elapsed() > refresh_interval);
+        if idle {
+            self.last_onset = None;
+        }
+    }
+
+    /// Call at the end of `present`.
+    pub fn end_present(&mut self) {
+        self.last_present_end = Some(Instant::now());
+    }
+
+    /// Record the onset of a frame and check whether it was late.
+    pub fn frame_presented(
+        &mut self,
+        onset: Instant,
+        refresh_interval: Duration,
+        n_stimuli: usize,
+        cpu_render_time: Duration,
+    ) {
+        let previous = self.last_onset.replace(onset);
+        if !self.enabled {
+            return;
+        }
+        let Some(previous) = previous else {
+            return;
+        };
+
+        let expected = refresh_interval.as_secs_f64();
+        let actual = onset.saturating_duration_since(previous).as_secs_f64();
+        if actual <= expected * (1.0 + self.tolerance) {
+            return;
+        }
+
+        let dropped = DroppedFrame {
+            timestamp: onset,
+            expected_interval: expected,
+            actual_interval: actual,
+            missed_frames: ((actual / expected).round() as u32).saturating_sub(1).max(1),
+            n_stimuli,
+            cpu_render_time: cpu_render_time.as_secs_f64(),
+            context: self.context.clone(),
+        };
+
+        log::warn!(
+            "Dropped frame: {:.2} ms since the previous frame (expected {:.2} ms, {} missed), {} stimuli, CPU render time {:.2} ms, context {:?}",
+            actual * 1000.0,
+            expected * 1000.0,
+            dropped.missed_frames,
+            n_stimuli,
+            dropped.cpu_render_time * 1000.0,
+            dropped.context
+        );
+
+        self.dropped_frames.push(dropped.clone());
+        #[cfg(feature = "python")]
+        if self.callback.is_some() {
+            self.pending.push(dropped);
+        }
+    }
+}
+
+impl DroppedFrame {
+    /// Convert to a dictionary for Python.
+    #[cfg(feature = "python")]
+    pub fn to_py_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("timestamp", Timestamp::from(self.timestamp))?;
+        dict.set_item("expected_interval", self.expected_interval)?;
+        dict.set_item("actual_interval", self.actual_interval)?;
+        dict.set_item("missed_frames", self.missed_frames)?;
+        dict.set_item("n_stimuli", self.n_stimuli)?;
+        dict.set_item("cpu_render_time", self.cpu_render_time)?;
+        dict.set_item("context", self.context.clone())?;
+        Ok(dict)
+    }
+}
@@ -5,9 +5,10 @@ use std::{
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex, MutexGuard, RwLock,
+        mpsc, Arc, Mutex, MutexGuard, RwLock,
     },
-    time::Instant,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use async_channel::{bounded, Receiver, Sender};
@@ -15,7 +16,10 @@ use derive_debug::Dbg;
 use futures_lite::{future::block_on, Future};
 use nalgebra;
 use palette::IntoColor;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
 use renderer::{
     renderer::{DynamicRenderResources, SharedRendererState},
     wgpu_renderer::WgpuRenderer,
@@ -28,14 +32,25 @@ use winit::{dpi::PhysicalSize, window::WindowId};
 
 use super::{
     color::LinRgba,
-    geometry::Size,
+    cursor::Cursor,
+    geometry::{CoordinateSystem, IntoSize, Origin, Size, YAxis},
+    overlay::{self, DebugOverlay},
+    report::{FrameMeasurement, PresentationReport, RefreshMeasurement, SequenceReport},
     stimuli::{DynamicStimulus, Stimulus},
+    watchdog::FrameWatchdog,
 };
+#[cfg(feature = "python")]
+use super::{cursor::PyCursor, sequence::Sequence};
+#[cfg(feature = "python")]
+use crate::input::simulation::PySimulatedParticipant;
 use crate::{
     app::GPUState,
     context::Monitor,
     errors::{PsydkError, PsydkResult},
-    input::{Event, EventHandler, EventHandlerId, EventHandlingExt, EventKind, EventReceiver},
+    input::{
+        gaze::GazeEventDetector, keyboard::KeyboardState, simulation::SimulatedParticipant, Event, EventHandler,
+        EventHandlerId, EventHandlingExt, EventKind, EventReceiver,
+    },
     time::Timestamp,
     RenderThreadChannelPayload,
 };
@@ -44,7 +59,7 @@ use crate::{
 pub struct PhysicalScreen {
     /// Pixel/mm of the screen.
     pub pixel_density: f32,
-    /// Viewing distance in meters.
+    /// Viewing distance in millimeters.
     pub viewing_distance: f32,
 }
 
@@ -79,6 +94,19 @@ impl PhysicalScreen {
     pub fn set_pixel_density(&mut self, width_px: u32, width_mm: f32) {
         self.pixel_density = width_px as f32 / width_mm;
     }
+
+    /// Returns the angle in degrees between two points on the screen (in pixels relative to the
+    /// center) as seen from the viewing position in front of the center.
+    pub fn visual_angle(&self, a: (f32, f32), b: (f32, f32)) -> f64 {
+        let to_mm = |(x, y): (f32, f32)| {
+            nalgebra::Vector3::new(
+                (x / self.pixel_density) as f64,
+                (y / self.pixel_density) as f64,
+                self.viewing_distance as f64,
+            )
+        };
+        to_mm(a).angle(&to_mm(b)).to_degrees()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -110,6 +138,91 @@ impl From<PixelSize> for (u32, u32) {
 
 pub type FrameId = u64;
 
+/// Counts the refreshes of the display since the window was opened, so that stimuli can be locked
+/// to them (e.g., for frequency tagging). Refreshes without a new frame (between calls to
+/// `present` or when a deadline is missed) are counted as well, assuming that the display keeps
+/// refreshing at the nominal interval.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshCounter {
+    /// The refresh at which the frame that is being rendered is expected to be shown.
+    pub planned: u64,
+    /// The refresh interval of the display.
+    pub interval: Duration,
+    /// The refresh at which the last frame was shown, and when.
+    last: Option<(u64, Instant)>,
+}
+
+impl RefreshCounter {
+    /// The number of refreshes from `from` to `to`, rounded to the nearest refresh.
+    fn refreshes_between(&self, from: Instant, to: Instant) -> u64 {
+        (to.saturating_duration_since(from).as_secs_f64() / self.interval.as_secs_f64()).round() as u64
+    }
+
+    /// Plan the frame that is rendered for `time` (its expected onset, or now if it is not known).
+    /// Returns the refresh at which it is expected to be shown.
+    pub fn plan(&mut self, time: Instant, interval: Duration) -> u64 {
+        self.interval = interval;
+        self.planned = match self.last {
+            // a frame is shown at the first refresh after it has been rendered. Expected onsets fall
+            // on a refresh, which the tolerance of a tenth of an interval keeps from being skipped
+            Some((refresh, onset)) => {
+                let intervals = time.saturating_duration_since(onset).as_secs_f64() / interval.as_secs_f64();
+                refresh + ((intervals - 0.1).ceil() as u64).max(1)
+            }
+            None => 0,
+        };
+        self.planned
+    }
+
+    /// Count a frame that was shown at `onset`. Returns the refresh at which it was shown.
+    pub fn presented(&mut self, onset: Instant) -> u64 {
+        let refresh = match self.last {
+            Some((refresh, last_onset)) => refresh + self.refreshes_between(last_onset, onset).max(1),
+            None => self.planned,
+        };
+        self.last = Some((refresh, onset));
+        refresh
+    }
+}
+
+/// Calls the onset and offset handlers of stimuli on a separate thread, so that they run as soon as
+/// a frame has been presented and not only after the whole presentation. Handlers that use the
+/// window wait until the presentation has released it.
+#[derive(Default)]
+struct StimulusHandlerDispatch {
+    sender: Option<mpsc::Sender<Vec<(EventHandler, Event)>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StimulusHandlerDispatch {
+    /// Call the handlers with their events, in order and after those dispatched before.
+    fn dispatch(&mut self, events: Vec<(EventHandler, Event)>) {
+        if events.is_empty() {
+            return;
+        }
+        let sender = self.sender.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<Vec<(EventHandler, Event)>>();
+            self.thread = Some(std::thread::spawn(move || {
+                for (handler, event) in receiver.into_iter().flatten() {
+                    handler(event);
+                }
+            }));
+            sender
+        });
+        let _ = sender.send(events);
+    }
+
+    /// Wait until all dispatched handlers have returned.
+    fn finish(mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("A stimulus onset or offset handler panicked");
+            }
+        }
+    }
+}
+
 /// Internal window state. This is used to store the winit window, the wgpu
 /// device, the wgpu queue, etc.
 #[derive(Dbg)]
@@ -130,15 +243,35 @@ pub struct WindowState {
     pub shared_renderer_state: Arc<dyn SharedRendererState>,
     // The current mouse position. None if the mouse has left the window.
     pub mouse_position: Option<(f32, f32)>,
+    /// The most recent gaze sample, if any, in the coordinate system of the window.
+    pub gaze_position: Option<(f32, f32)>,
+    /// The number of `wait_for_fixation` calls in a row that timed out.
+    pub fixation_failures: u32,
+    /// Detects saccades and blinks in the gaze samples, if enabled.
+    pub gaze_events: Option<GazeEventDetector>,
     /// Stores if the mouse cursor is currently visible.
     pub mouse_cursor_visible: bool,
+    /// The appearance of the mouse cursor while it is visible.
+    pub cursor: Cursor,
+    /// Whether mouse input passes through the window to the applications below it.
+    pub click_through: bool,
+    /// Whether input methods are allowed for the window.
+    pub ime_allowed: bool,
+    /// Whether an input method is enabled, in which case typed text is committed by the input
+    /// method instead of being reported with key presses.
+    pub ime_active: bool,
     /// The size of the window in pixels.
     pub size: PixelSize,
     /// Physical properties of the screen.
     pub physical_screen: PhysicalScreen,
-    /// Event handlers for the window.
+    /// The viewing distance at the onset of every presented frame, if it is logged (e.g. while a
+    /// head tracker updates it).
+    pub viewing_distance_log: Option<Vec<(Instant, f32)>>,
+    /// An audio stream that emits a trigger pulse at the onset of every presented frame.
     #[dbg(placeholder = "...")]
-    pub event_handlers: HashMap<EventHandlerId, (EventKind, EventHandler)>,
+    pub audio_trigger: Option<timed_audio::Stream>,
+    /// The coordinate system that positions of stimuli and of the mouse are given in.
+    pub coordinate_system: CoordinateSystem,
     /// Background color of the window.
     pub bg_color: LinRgba,
     /// The frame callbacks that maps the frame number to the callback.
@@ -148,6 +281,21 @@ pub struct WindowState {
     #[dbg(placeholder = "...")]
     pub frame_queue: Vec<FrameId>,
     pub last_frame_id: FrameId,
+    /// Detects frames that were presented late.
+    pub watchdog: FrameWatchdog,
+    /// The most recent measurement of the refresh rate, if any.
+    pub refresh_measurement: Option<RefreshMeasurement>,
+    /// The visible stimuli of the frame that is currently on screen.
+    #[dbg(placeholder = "...")]
+    pub displayed_stimuli: Vec<(Uuid, DynamicStimulus)>,
+    /// The debug overlay that is drawn on top of every frame, if enabled.
+    #[dbg(placeholder = "...")]
+    pub debug_overlay: Option<DebugOverlay>,
+    /// Whether the bounding boxes, anchors, and hit-test regions of the stimuli are drawn on top of
+    /// every frame.
+    pub debug_layout: bool,
+    /// Counts the refreshes of the display.
+    pub refresh: RefreshCounter,
 }
 
 unsafe impl Send for WindowState {}
@@ -164,6 +312,120 @@ impl WindowState {
         self.wgpu_renderer
             .resize(size.width, size.height, &self.surface, &gpu_state.device);
     }
+
+    /// Set the appearance of the mouse cursor.
+    pub fn set_cursor(&mut self, cursor: Cursor) {
+        cursor.apply(&self.winit_window);
+        self.mouse_cursor_visible = cursor.is_visible();
+        // a hidden cursor keeps the shape it has when it is shown again
+        if cursor.is_visible() {
+            self.cursor = cursor;
+        }
+    }
+
+    /// Replace the stimuli that are on screen with the visible stimuli of a newly presented frame.
+    /// Returns the onset handlers of stimuli that were not on screen before and the offset handlers
+    /// of stimuli that are no longer drawn, together with the events to call them with.
+    pub fn update_displayed_stimuli(
+        &mut self,
+        stimuli: &[DynamicStimulus],
+        onset: Instant,
+    ) -> Vec<(EventHandler, Event)> {
+        let mut displayed: Vec<(Uuid, DynamicStimulus)> = Vec::new();
+        for stimulus in stimuli {
+            let (uuid, visible) = {
+                let stimulus = stimulus.lock();
+                (stimulus.uuid(), stimulus.visible())
+            };
+            if visible && !displayed.iter().any(|(id, _)| *id == uuid) {
+                displayed.push((uuid, stimulus.clone()));
+            }
+        }
+
+        let was_displayed = |uuid: &Uuid| self.displayed_stimuli.iter().any(|(id, _)| id == uuid);
+        let is_displayed = |uuid: &Uuid| displayed.iter().any(|(id, _)| id == uuid);
+
+        let onset_handlers = displayed
+            .iter()
+            .filter(|(uuid, _)| !was_displayed(uuid))
+            .flat_map(|(_, stimulus)| stimulus.event_handlers(EventKind::Onset))
+            .map(|handler| {
+                let event = Event::Onset {
+                    timestamp: onset.into(),
+                };
+                (handler, event)
+            });
+        let offset_handlers = self
+            .displayed_stimuli
+            .iter()
+            .filter(|(uuid, _)| !is_displayed(uuid))
+            .flat_map(|(_, stimulus)| stimulus.event_handlers(EventKind::Offset))
+            .map(|handler| {
+                let event = Event::Offset {
+                    timestamp: onset.into(),
+                };
+                (handler, event)
+            });
+        let events = onset_handlers.chain(offset_handlers).collect();
+
+        self.displayed_stimuli = displayed;
+        events
+    }
+
+    /// Acquire the next texture from the surface. If the surface was lost or is outdated (e.g., after
+    /// the computer went to sleep or the display was unplugged), the surface is reconfigured and
+    /// acquiring the texture is retried a few times before giving up.
+    pub fn acquire_surface_texture(&mut self, gpu_state: &GPUState) -> PsydkResult<wgpu::SurfaceTexture> {
+        const MAX_ATTEMPTS: u32 = 3;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            if let Some(reason) = gpu_state.device_lost.lock().unwrap().as_ref() {
+                return Err(PsydkError::DisplayLost(format!(
+                    "The graphics device was lost ({})",
+                    reason
+                )));
+            }
+
+            match self.surface.get_current_texture() {
+                Ok(texture) => return Ok(texture),
+                Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                    log::warn!(
+                        "Surface error ({}), reconfiguring (attempt {} of {})",
+                        e,
+                        attempt,
+                        MAX_ATTEMPTS
+                    );
+                    self.surface.configure(&gpu_state.device, &self.config);
+                }
+                Err(wgpu::SurfaceError::Timeout) => {
+                    log::warn!(
+                        "Timed out acquiring surface texture (attempt {} of {})",
+                        attempt,
+                        MAX_ATTEMPTS
+                    );
+                }
+                Err(e) => return Err(PsydkError::DisplayLost(e.to_string())),
+            }
+        }
+
+        Err(PsydkError::DisplayLost(format!(
+            "Failed to acquire surface texture after {} attempts",
+            MAX_ATTEMPTS
+        )))
+    }
+}
+
+/// Call a Python event handler. Event handlers run on the event loop, so errors can't be propagated
+/// to the caller. Instead, the Python traceback is printed and the error is logged.
+#[cfg(feature = "python")]
+pub(crate) fn call_event_callback(py: Python, callback: &Py<PyAny>, event: Event) {
+    if let Err(e) = callback.call1(py, (event,)) {
+        log::error!(
+            "Error calling callback in event handler. Make sure the callback takes a single argument of type Event. Error: {}",
+            e
+        );
+        e.print(py);
+    }
 }
 
 /// How to block when presenting a frame.
@@ -171,7 +433,7 @@ impl WindowState {
 /// to submit them to the screen for rendering. Each window has a render task
 /// that is responsible for rendering stimuli to the screen.
 #[derive(Dbg, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct Window {
     /// Window ID
     pub winit_id: WindowId,
@@ -185,6 +447,14 @@ pub struct Window {
     pub event_broadcast_sender: async_broadcast::Sender<Event>,
     /// Broadcast receiver for keyboard events.
     pub event_broadcast_receiver: async_broadcast::InactiveReceiver<Event>,
+    /// Event handlers for the window. Kept outside of the window state, so that events can be
+    /// dispatched while a frame is being presented.
+    #[dbg(placeholder = "...")]
+    pub event_handlers: Arc<Mutex<HashMap<EventHandlerId, (EventKind, EventHandler)>>>,
+    /// The state of the keyboard, updated by the event loop.
+    pub keyboard: KeyboardState,
+    /// A simulated participant that responds to frames that expect a response.
+    pub simulated_participant: Arc<Mutex<Option<SimulatedParticipant>>>,
 }
 
 impl Window {
@@ -196,14 +466,46 @@ impl Window {
         }
     }
 
+    /// Run `f` with the window state. Returns an error if the window has already been closed.
+    pub fn with_state<R>(&self, f: impl FnOnce(&mut WindowState) -> R) -> PsydkResult<R> {
+        let mut win_state = self.state.lock().unwrap();
+        win_state.as_mut().map(f).ok_or(PsydkError::WindowClosed)
+    }
+
     /// Resizes the window's surface to the given size.
-    pub fn resize(&self, size: impl Into<PixelSize>) {
+    pub fn resize(&self, size: impl Into<PixelSize>) -> PsydkResult<()> {
         let size = size.into();
         let mut gpu_state = self.gpu_state.lock().unwrap();
-        let mut win_state = self.state.lock().unwrap();
-        let mut win_state = win_state.as_mut().unwrap();
+        self.with_state(|win_state| win_state.resize(size, &mut gpu_state))
+    }
+
+    /// Present `n_frames` blank frames and measure the intervals between them, e.g. at the start of
+    /// an experiment to validate the display mode. The result is logged (with a warning for every
+    /// detected problem, such as frame doubling) and kept with the window.
+    pub fn measure_refresh_rate(&self, n_frames: u32) -> PsydkResult<RefreshMeasurement> {
+        if n_frames < 2 {
+            return Err(PsydkError::ParameterError(
+                "At least two frames are needed to measure the refresh rate".into(),
+            ));
+        }
+
+        let mut frame = self.get_frame()?;
+        let report = self.present(&mut frame, Some(n_frames), None, false, Some(false))?;
+        let measurement = RefreshMeasurement::new(&report.frame_onsets, self.get_current_refresh_rate());
+
+        log::info!(
+            "Measured refresh rate: {:.3} Hz over {} frames (reported: {:?} Hz, jitter: {:.3} ms)",
+            measurement.measured_rate().unwrap_or(f64::NAN),
+            n_frames,
+            measurement.reported_rate,
+            measurement.jitter() * 1000.0
+        );
+        for warning in measurement.warnings() {
+            log::warn!("{}", warning);
+        }
 
-        win_state.resize(size, &mut gpu_state);
+        self.with_state(|win_state| win_state.refresh_measurement = Some(measurement.clone()))?;
+        Ok(measurement)
     }
 
     /// Present a frame on the window.
@@ -214,7 +516,33 @@ impl Window {
         repeat_time: Option<f64>,
         repeat_update: bool,
         pedantic: Option<bool>,
-    ) -> PsydkResult<Option<Instant>> {
+    ) -> PsydkResult<PresentationReport> {
+        let mut handlers = StimulusHandlerDispatch::default();
+        let report = self.present_frames(
+            frame,
+            repeat_frames,
+            repeat_time,
+            repeat_update,
+            pedantic,
+            &mut handlers,
+        )?;
+
+        // wait for the handlers, which can only use the window once the presentation has released it
+        handlers.finish();
+        self.simulate_response(frame, report.onset());
+
+        Ok(report)
+    }
+
+    fn present_frames(
+        &self,
+        frame: &mut Frame,
+        repeat_frames: Option<u32>,
+        repeat_time: Option<f64>,
+        repeat_update: bool,
+        pedantic: Option<bool>,
+        handlers: &mut StimulusHandlerDispatch,
+    ) -> PsydkResult<PresentationReport> {
         // make sure that only one of repeat_frames or repeat_time is set (or none)
         if repeat_frames.is_some() && repeat_time.is_some() {
             return Err(PsydkError::ParameterError(
@@ -222,15 +550,15 @@ impl Window {
             ));
         }
 
-        let mut onset_time = Arc::new(Mutex::new(None));
-
         // get the refresh rate of the  monitor
-        let refresh_rate = self.get_current_refresh_rate().expect("Failed to get refresh rate");
+        let refresh_rate = self.get_current_refresh_rate().ok_or_else(|| {
+            PsydkError::MonitorError("Failed to get the refresh rate of the monitor the window is on".into())
+        })?;
 
         // lock the gpu state and window state
         let gpu_state = &mut self.gpu_state.lock().unwrap();
         let mut win_state = &mut self.state.lock().unwrap();
-        let mut win_state = win_state.as_mut().unwrap();
+        let mut win_state = win_state.as_mut().ok_or(PsydkError::WindowClosed)?;
 
         let pedantic = pedantic.unwrap_or(self.config.lock().unwrap().pedantic);
 
@@ -253,6 +581,110 @@ impl Window {
         // convert the repeat frames to an integer
         let repeat_frames = f_repeat_frames.round() as u32;
 
+        let refresh_interval = Duration::from_secs_f64(1.0 / refresh_rate);
+        win_state.watchdog.begin_present(refresh_interval);
+        let report = Self::present_locked(
+            gpu_state,
+            win_state,
+            frame,
+            repeat_frames,
+            repeat_update,
+            refresh_interval,
+            handlers,
+        )?;
+        win_state.watchdog.end_present();
+
+        // TODO wait for the frame to be presented
+        // TODO on Windows, we will run the callback here
+        // TODO on MacOS we will let Metal run the callback
+
+        Ok(report)
+    }
+
+    /// Present a sequence of frames back-to-back, each for the given number of refresh intervals.
+    ///
+    /// The window is locked for the whole sequence, so no other work (or Python code) can run between
+    /// two frames and the first frame of each step is shown at the next refresh after the last frame
+    /// of the previous step.
+    pub fn present_sequence(&self, steps: &[(&Frame, u32)], repeat_update: bool) -> PsydkResult<SequenceReport> {
+        let mut handlers = StimulusHandlerDispatch::default();
+        let report = self.present_sequence_frames(steps, repeat_update, &mut handlers)?;
+
+        handlers.finish();
+        for ((frame, _), step) in steps.iter().zip(&report.steps) {
+            self.simulate_response(frame, step.onset());
+        }
+
+        Ok(report)
+    }
+
+    fn present_sequence_frames(
+        &self,
+        steps: &[(&Frame, u32)],
+        repeat_update: bool,
+        handlers: &mut StimulusHandlerDispatch,
+    ) -> PsydkResult<SequenceReport> {
+        if steps.is_empty() {
+            return Err(PsydkError::ParameterError("A sequence needs at least one frame".into()));
+        }
+        if steps.iter().any(|(_, n_frames)| *n_frames == 0) {
+            return Err(PsydkError::ParameterError(
+                "Every frame in a sequence needs to be presented for at least one refresh interval".into(),
+            ));
+        }
+
+        let refresh_rate = self.get_current_refresh_rate().ok_or_else(|| {
+            PsydkError::MonitorError("Failed to get the refresh rate of the monitor the window is on".into())
+        })?;
+        let refresh_interval = Duration::from_secs_f64(1.0 / refresh_rate);
+
+        let gpu_state = &mut self.gpu_state.lock().unwrap();
+        let mut win_state = self.state.lock().unwrap();
+        let win_state = win_state.as_mut().ok_or(PsydkError::WindowClosed)?;
+
+        win_state.watchdog.begin_present(refresh_interval);
+        let mut reports = Vec::with_capacity(steps.len());
+        for (frame, n_frames) in steps {
+            reports.push(Self::present_locked(
+                gpu_state,
+                win_state,
+                frame,
+                *n_frames,
+                repeat_update,
+                refresh_interval,
+                handlers,
+            )?);
+        }
+        win_state.watchdog.end_present();
+
+        Ok(SequenceReport::new(reports, refresh_interval))
+    }
+
+    /// Render and present `frame` for `repeat_frames` refresh intervals. The caller holds the locks of
+    /// the GPU and window state. The onset and offset handlers of stimuli that appear or disappear
+    /// are passed to `handlers` as soon as the first frame has been presented.
+    fn present_locked(
+        gpu_state: &GPUState,
+        win_state: &mut WindowState,
+        frame: &Frame,
+        repeat_frames: u32,
+        repeat_update: bool,
+        refresh_interval: Duration,
+        handlers: &mut StimulusHandlerDispatch,
+    ) -> PsydkResult<PresentationReport> {
+        let mut frame_onsets = Vec::with_capacity(repeat_frames as usize);
+        let mut refreshes = Vec::with_capacity(repeat_frames as usize);
+
+        // stimuli are drawn in order of their depth. The sort is stable, so stimuli with the same z are
+        // drawn in the order they were added to the frame
+        let mut stimuli: Vec<&DynamicStimulus> = frame.stimuli.iter().collect();
+        stimuli.sort_by_key(|stimulus| stimulus.z());
+
+        // apply staged parameter changes before anything is drawn
+        for stimulus in &stimuli {
+            stimulus.apply_staged_params();
+        }
+
         let device = &gpu_state.device;
         let queue = &gpu_state.queue;
         let width = win_state.size.width;
@@ -289,29 +721,27 @@ impl Window {
             .insert(new_frame_id, Box::new(onset_callback_fn));
 
         for i in 0..repeat_frames {
-            let suface_texture = win_state
-                .surface
-                .get_current_texture()
-                .expect("Failed to acquire next swap chain texture");
+            let suface_texture = win_state.acquire_surface_texture(gpu_state)?;
+            let render_start = Instant::now();
 
             let width = suface_texture.texture.size().width;
             let height = suface_texture.texture.size().height;
 
-            let texture = win_state.wgpu_renderer.texture();
-
-            let mut scene = win_state.renderer.create_scene(width, height);
-
-            for stimulus in &frame.stimuli {
-                let now = Instant::now();
-                let mut stimulus = (&stimulus).lock();
-                stimulus.update_animations(now, &win_state);
-                stimulus.draw(&mut scene, &win_state);
+            // unless `repeat_update` is false, the scene is rebuilt for every repeated frame so that
+            // animations advance with every refresh. Otherwise, the texture rendered for the first
+            // frame is presented again.
+            if i == 0 || repeat_update {
+                // evaluate animations at the expected onset of this frame, so that they advance by
+                // exactly one refresh interval per repeated frame
+                let frame_time = frame_onsets
+                    .last()
+                    .map(|onset: &Instant| *onset + refresh_interval)
+                    .unwrap_or_else(Instant::now);
+                win_state.refresh.plan(frame_time, refresh_interval);
+
+                Self::render_locked(gpu_state, win_state, frame, &stimuli, frame_time, width, height, true);
             }
 
-            win_state
-                .renderer
-                .render_to_texture(device, queue, texture, width, height, &mut scene);
-
             let surface_texture_view = suface_texture.texture.create_view(&wgpu::TextureViewDescriptor {
                 format: Some(config.format),
                 ..wgpu::TextureViewDescriptor::default()
@@ -321,6 +751,7 @@ impl Window {
             win_state
                 .wgpu_renderer
                 .render_to_texture(device, queue, &surface_texture_view);
+            let render_time = render_start.elapsed();
 
             // on metal, we will don't need to use the frame queue as we can tell metal to run the callback
             // #[cfg(all(target_os = "macos", feature = "metal"))]
@@ -345,51 +776,193 @@ impl Window {
             // then wait for the frame to be presented
             #[cfg(all(feature = "dx12", target_os = "windows"))]
             {
-                let swap_chain = unsafe {
-                    win_state
-                        .surface
-                        .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.unwrap().swap_chain().unwrap())
-                };
-
+                // the surface is not a DX12 surface if we are running on a different backend (e.g., GL)
                 let waitable_handle = unsafe {
                     win_state
                         .surface
-                        .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.unwrap().waitable_handle().unwrap())
+                        .as_hal::<wgpu::hal::api::Dx12, _, _>(|surface| surface.and_then(|s| s.waitable_handle()))
                 };
 
                 // let frame_id = unsafe { swap_chain.GetLastPresentCount() }.expect("Failed to get frame id");
                 // win_state.frame_queue.push(frame_id.into());
                 // this is waiting for the frame latency waitable object to be signaled
-                unsafe { windows::Win32::System::Threading::WaitForSingleObject(waitable_handle, 10000) };
+                if let Some(waitable_handle) = waitable_handle {
+                    unsafe { windows::Win32::System::Threading::WaitForSingleObject(waitable_handle, 10000) };
+                }
 
                 if i == 0 {
-                    // timestamp frame presentation
-                    let timestamp = Instant::now();
-                    onset_time.lock().unwrap().replace(timestamp);
                     // get the frame id that was presented from the frame queue
                     let frame_id = win_state.frame_queue.remove(0);
                     // get the callback for the frame id
-                    let callback = win_state
-                        .frame_callbacks
-                        .remove(&frame_id)
-                        .expect("Failed to get callback for frame id");
-                    // // call the callback
-                    callback();
+                    // call the callback
+                    if let Some(callback) = win_state.frame_callbacks.remove(&frame_id) {
+                        callback();
+                    }
+                }
+            }
+
+            // timestamp frame presentation
+            let onset = Instant::now();
+            frame_onsets.push(onset);
+            // a repeated frame that is not rendered again shows what was rendered for the first one
+            let planned = win_state.refresh.planned;
+            refreshes.push((planned, win_state.refresh.presented(onset)));
+            if i == 0 {
+                handlers.dispatch(win_state.update_displayed_stimuli(&frame.stimuli, onset));
+            }
+            win_state
+                .watchdog
+                .frame_presented(onset, refresh_interval, frame.stimuli.len(), render_time);
+            if let Some(overlay) = &mut win_state.debug_overlay {
+                overlay.frame_presented(onset, render_time);
+            }
+            if let Some(log) = &mut win_state.viewing_distance_log {
+                log.push((onset, win_state.physical_screen.viewing_distance));
+            }
+            if i == 0 {
+                if let Some(cursor) = &frame.cursor {
+                    win_state.set_cursor(cursor.clone());
+                }
+                if let Some(stream) = &win_state.audio_trigger {
+                    if let Err(e) = stream.pulse_now() {
+                        log::warn!("Failed to emit the audio trigger pulse: {e}");
+                    }
                 }
             }
         }
 
-        // TODO wait for the frame to be presented
-        // TODO on Windows, we will run the callback here
-        // TODO on MacOS we will let Metal run the callback
+        Ok(PresentationReport::new(
+            repeat_frames,
+            frame_onsets,
+            refreshes,
+            refresh_interval,
+        ))
+    }
+
+    /// Render `frame` into the offscreen texture of the window, with animations evaluated at
+    /// `frame_time`. The debug overlays are drawn on top if they are enabled and `overlay` is true.
+    /// The caller holds the locks of the GPU and window state.
+    fn render_locked(
+        gpu_state: &GPUState,
+        win_state: &mut WindowState,
+        frame: &Frame,
+        stimuli: &[&DynamicStimulus],
+        frame_time: Instant,
+        width: u32,
+        height: u32,
+        overlay: bool,
+    ) {
+        let texture = win_state.wgpu_renderer.texture();
+
+        let mut scene = win_state.renderer.create_scene(width, height);
+        if let Some(view) = &frame.view {
+            scene.set_view_transform(view.transform(win_state));
+        }
+        scene.set_bg_color(frame.bg_color.into());
+
+        for stimulus in stimuli {
+            stimulus.update_animations(frame_time, &win_state);
+            stimulus.draw(&mut scene, &win_state);
+        }
+
+        if overlay && win_state.debug_layout {
+            overlay::draw_layout(&mut scene, win_state, stimuli);
+        }
+
+        // the overlay is taken out of the window state while it is drawn, as drawing needs the state
+        let debug_overlay = match overlay {
+            true => win_state.debug_overlay.take(),
+            false => None,
+        };
+        if let Some(mut debug_overlay) = debug_overlay {
+            let view = frame.view.as_ref().map(|view| (view.transform(win_state), view.scale));
+            debug_overlay.draw(&mut scene, win_state, view, stimuli);
+            win_state.debug_overlay = Some(debug_overlay);
+        }
 
-        let mut onset_time = onset_time.lock().unwrap();
-        // if the onset time is None, set it to the current time
-        if onset_time.is_none() {
-            let now = Instant::now();
-            *onset_time = Some(now);
+        win_state
+            .renderer
+            .render_to_texture(&gpu_state.device, &gpu_state.queue, texture, width, height, &mut scene);
+    }
+
+    /// Render `frame` offscreen, without presenting it, and measure the mean luminance, RMS contrast,
+    /// and histogram of the values that would be sent to the display.
+    pub fn measure(&self, frame: &Frame) -> PsydkResult<FrameMeasurement> {
+        let gpu_state = self.gpu_state.lock().unwrap();
+        let mut win_state = self.state.lock().unwrap();
+        let win_state = win_state.as_mut().ok_or(PsydkError::WindowClosed)?;
+
+        let mut stimuli: Vec<&DynamicStimulus> = frame.stimuli.iter().collect();
+        stimuli.sort_by_key(|stimulus| stimulus.z());
+        for stimulus in &stimuli {
+            stimulus.apply_staged_params();
+        }
+
+        let (width, height) = (win_state.size.width, win_state.size.height);
+        Self::render_locked(
+            &gpu_state,
+            win_state,
+            frame,
+            &stimuli,
+            Instant::now(),
+            width,
+            height,
+            false,
+        );
+
+        let output = win_state
+            .wgpu_renderer
+            .read_output(&gpu_state.device, &gpu_state.queue)
+            .ok_or_else(|| {
+                PsydkError::PresentationError("The output format of the window cannot be read back".into())
+            })?;
+
+        Ok(FrameMeasurement::from_output(&output))
+    }
+
+    /// Let the simulated participant, if any, respond to a frame that expects a response.
+    fn simulate_response(&self, frame: &Frame, onset: Option<Instant>) {
+        let (Some(expected), Some(onset)) = (&frame.expected_response, onset) else {
+            return;
+        };
+        if let Some(participant) = self.simulated_participant.lock().unwrap().as_ref() {
+            participant.respond(self, onset, expected.as_deref());
         }
-        Ok(*onset_time)
+    }
+
+    /// Deliver an input event to the keyboard state, event receivers, and event handlers of the
+    /// window, as if it had come from the event loop.
+    pub fn inject_event(&self, event: Event) {
+        self.keyboard.update(&event);
+
+        // broadcast the event
+        let _ = self.event_broadcast_sender.try_broadcast(event.clone());
+
+        // send the event to the window
+        self.dispatch_event(event);
+    }
+
+    /// Record a gaze sample (in the coordinate system of the window, `None` if the tracker lost
+    /// the eye). Updates the gaze position of the window and, if gaze event detection is enabled,
+    /// delivers the detected saccades and blinks like other input events.
+    pub fn add_gaze_sample(&self, time: Instant, position: Option<(f32, f32)>) -> PsydkResult<()> {
+        let events = self.with_state(|win_state| {
+            if position.is_some() {
+                win_state.gaze_position = position;
+            }
+            let mut detector = win_state.gaze_events.take();
+            let events = detector
+                .as_mut()
+                .map(|detector| detector.add_sample(time, position, win_state))
+                .unwrap_or_default();
+            win_state.gaze_events = detector;
+            events
+        })?;
+
+        for event in events {
+            self.inject_event(event);
+        }
+        Ok(())
     }
 
     pub fn close(&self) {
@@ -400,11 +973,7 @@ impl Window {
     }
 
     pub fn get_current_refresh_rate(&self) -> Option<f64> {
-        let winit_window = {
-            let win_state = self.state.lock().unwrap();
-            let win_state = win_state.as_ref().unwrap();
-            win_state.winit_window.clone()
-        };
+        let winit_window = self.with_state(|win_state| win_state.winit_window.clone()).ok()?;
 
         let monitor = winit_window.current_monitor();
 
@@ -416,11 +985,7 @@ impl Window {
     }
 
     pub fn get_current_monitor(&self) -> Option<Monitor> {
-        let winit_window = {
-            let win_state = self.state.lock().unwrap();
-            let win_state = win_state.as_ref().unwrap();
-            win_state.winit_window.clone()
-        };
+        let winit_window = self.with_state(|win_state| win_state.winit_window.clone()).ok()?;
         let monitor = winit_window.current_monitor();
 
         if let Some(monitor) = monitor {
@@ -435,26 +1000,95 @@ impl Window {
     }
 
     /// Set the visibility of the mouse cursor.
-    pub fn set_cursor_visible(&self, visible: bool) {
-        let mut win_state = self.state.lock().unwrap();
-        let mut win_state = win_state.as_mut().unwrap();
-        win_state.mouse_cursor_visible = visible;
-        win_state.winit_window.set_cursor_visible(false);
+    pub fn set_cursor_visible(&self, visible: bool) -> PsydkResult<()> {
+        self.with_state(|win_state| {
+            win_state.mouse_cursor_visible = visible;
+            win_state.winit_window.set_cursor_visible(visible);
+        })
+    }
+
+    /// Set the appearance of the mouse cursor. A hidden cursor hides the cursor, and any other cursor
+    /// makes it visible.
+    pub fn set_cursor(&self, cursor: Cursor) -> PsydkResult<()> {
+        self.with_state(|win_state| win_state.set_cursor(cursor))
+    }
+
+    /// Returns the appearance of the mouse cursor.
+    pub fn cursor(&self) -> PsydkResult<Cursor> {
+        self.with_state(|win_state| match win_state.mouse_cursor_visible {
+            true => win_state.cursor.clone(),
+            false => Cursor::Hidden,
+        })
     }
 
     /// Returns true if the mouse cursor is currently visible.
-    pub fn cursor_visible(&self) -> bool {
-        let win_state = self.state.lock().unwrap();
-        let win_state = win_state.as_ref().unwrap();
+    pub fn cursor_visible(&self) -> PsydkResult<bool> {
+        self.with_state(|win_state| win_state.mouse_cursor_visible)
+    }
+
+    /// Let mouse input pass through the window to the applications below it (or stop doing so).
+    pub fn set_click_through(&self, click_through: bool) -> PsydkResult<()> {
+        self.with_state(|win_state| {
+            win_state
+                .winit_window
+                .set_cursor_hittest(!click_through)
+                .map_err(|e| PsydkError::CustomError(format!("Failed to change whether clicks pass through: {e}")))?;
+            win_state.click_through = click_through;
+            Ok(())
+        })?
+    }
+
+    /// Returns true if mouse input passes through the window.
+    pub fn click_through(&self) -> PsydkResult<bool> {
+        self.with_state(|win_state| win_state.click_through)
+    }
+
+    /// Allow input methods (e.g., for Chinese or Japanese) to compose text in the window. While
+    /// allowed, the window receives `ImePreedit` and `ImeCommit` events.
+    pub fn set_ime_allowed(&self, allowed: bool) -> PsydkResult<()> {
+        self.with_state(|win_state| {
+            win_state.ime_allowed = allowed;
+            win_state.winit_window.set_ime_allowed(allowed);
+            if !allowed {
+                win_state.ime_active = false;
+            }
+        })
+    }
+
+    /// Returns true if input methods are allowed for the window.
+    pub fn ime_allowed(&self) -> PsydkResult<bool> {
+        self.with_state(|win_state| win_state.ime_allowed)
+    }
+
+    /// Place the candidate window of the input method next to the given position (in the
+    /// coordinate system of the window), e.g. the text field that is being typed in.
+    pub fn set_ime_position(&self, x: f32, y: f32) -> PsydkResult<()> {
+        self.with_state(|win_state| {
+            let size = win_state.size;
+            let (x, y) = win_state.coordinate_system.to_scene(x, y, size);
+            let position = winit::dpi::PhysicalPosition::new(
+                x as f64 + size.width as f64 / 2.0,
+                y as f64 + size.height as f64 / 2.0,
+            );
+            win_state
+                .winit_window
+                .set_ime_cursor_area(position, winit::dpi::PhysicalSize::new(1, 1));
+        })
+    }
+
+    /// Set the coordinate system that positions of stimuli and of the mouse are given in.
+    pub fn set_coordinate_system(&self, coordinate_system: CoordinateSystem) -> PsydkResult<()> {
+        self.with_state(|win_state| win_state.coordinate_system = coordinate_system)
+    }
 
-        win_state.mouse_cursor_visible
+    /// Returns the coordinate system of the window.
+    pub fn coordinate_system(&self) -> PsydkResult<CoordinateSystem> {
+        self.with_state(|win_state| win_state.coordinate_system)
     }
 
-    /// Returns the mouse position. None if cursor not in window.
+    /// Returns the mouse position. None if cursor not in window or the window has been closed.
     pub fn mouse_position(&self) -> Option<(f32, f32)> {
-        let win_state = self.state.lock().unwrap();
-        let win_state = win_state.as_ref().unwrap();
-        win_state.mouse_position.clone()
+        self.with_state(|win_state| win_state.mouse_position).ok().flatten()
     }
 
     /// Returns the 4x4 matrix than when applied to pixel coordinates will transform
@@ -472,52 +1106,37 @@ impl Window {
     }
 
     /// Returns the size of the window in pixels.
-    pub fn size(&self) -> PixelSize {
-        let win_state = self.state.lock().unwrap();
-        let win_state = win_state.as_ref().unwrap();
-        win_state.size
+    pub fn size(&self) -> PsydkResult<PixelSize> {
+        self.with_state(|win_state| win_state.size)
     }
 
     /// Return a new frame for the window.
-    pub fn get_frame(&self) -> Frame {
-        let win_state = self.state.lock().unwrap();
-        let win_state = win_state.as_ref().unwrap();
+    pub fn get_frame(&self) -> PsydkResult<Frame> {
+        let bg_color = self.with_state(|win_state| win_state.bg_color)?;
         // let scene = win_state
         //     .renderer
         //     .create_scene(win_state.size.width, win_state.size.height);
-        let mut frame = Frame {
+        let frame = Frame {
             stimuli: Vec::new(),
             window: self.clone(),
             event_handlers: HashMap::new(),
+            bg_color,
+            expected_response: None,
+            view: None,
+            cursor: None,
         };
 
-        frame.set_bg_color(win_state.bg_color);
-
-        frame
+        Ok(frame)
     }
-    fn remove_event_handler(&self, id: EventHandlerId) {
-        let mut state = self.state.lock().unwrap();
-        let state = state.as_mut().unwrap();
-        state.event_handlers.remove(&id);
+    pub(crate) fn remove_event_handler(&self, id: EventHandlerId) {
+        self.event_handlers.lock().unwrap().remove(&id);
     }
 
     pub fn dispatch_event(&self, event: Event) -> bool {
         let mut handled = false;
 
-        let event_handlers = {
-            let state = self.state.lock().unwrap();
-            let state = state.as_ref().unwrap();
-
-            // clone the event handlers
-            let event_handlers = &state.event_handlers;
-
-            let mut new_event_handlers: HashMap<EventHandlerId, (EventKind, EventHandler)> = HashMap::new();
-            for (id, (kind, handler)) in event_handlers.iter() {
-                new_event_handlers.insert(*id, (*kind, handler.clone()));
-            }
-
-            new_event_handlers
-        };
+        // clone the event handlers, so that handlers can add or remove handlers
+        let event_handlers = self.event_handlers.lock().unwrap().clone();
 
         for (id, (kind, handler)) in event_handlers.iter() {
             // println!("Checking handler with id: {} for event kind: {:?}", id, kind);
@@ -531,13 +1150,14 @@ impl Window {
         handled
     }
 
-    fn add_event_handler<F>(&self, kind: EventKind, handler: F) -> EventHandlerId
+    pub(crate) fn add_event_handler<F>(&self, kind: EventKind, handler: F) -> PsydkResult<EventHandlerId>
     where
         F: Fn(Event) -> bool + 'static + Send + Sync,
     {
-        let mut state = self.state.lock().unwrap();
-        let mut state = state.as_mut().unwrap();
-        let mut event_handlers = &mut state.event_handlers;
+        if self.state.lock().unwrap().is_none() {
+            return Err(PsydkError::WindowClosed);
+        }
+        let mut event_handlers = self.event_handlers.lock().unwrap();
 
         // find a free id
         let id = loop {
@@ -550,17 +1170,43 @@ impl Window {
         // add handler
         event_handlers.insert(id, (kind, Arc::new(handler)));
 
-        id
+        Ok(id)
     }
 }
 
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+enum SequenceOrSteps {
+    Sequence(Py<Sequence>),
+    Steps(Vec<(Py<Frame>, u32)>),
+}
+
+#[cfg(feature = "python")]
+impl Window {
+    /// Pass the frames that the watchdog detected as dropped to its callback. Called after the window
+    /// state has been released, so that the callback can use the window.
+    pub(crate) fn call_watchdog_callback(&self, py: Python) -> PyResult<()> {
+        let (dropped_frames, callback) = self.with_state(|win_state| {
+            let pending = std::mem::take(&mut win_state.watchdog.pending);
+            (pending, win_state.watchdog.callback.as_ref().map(|c| c.clone_ref(py)))
+        })?;
+        if let Some(callback) = callback {
+            for dropped_frame in dropped_frames {
+                callback.call1(py, (dropped_frame.to_py_dict(py)?,))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl Window {
     #[pyo3(name = "get_frame")]
-    fn py_get_frame(&self, py: Python) -> Frame {
+    fn py_get_frame(&self, py: Python) -> PyResult<Frame> {
         let self_wrapper = SendWrapper::new(self.clone());
         let d = py.allow_threads(move || SendWrapper::new(self_wrapper.get_frame()));
-        d.take()
+        Ok(d.take()?)
     }
 
     #[pyo3(name = "get_frames")]
@@ -569,13 +1215,20 @@ impl Window {
     }
 
     #[pyo3(name = "present")]
-    #[pyo3(signature = (frame, repeat_frames=None, repeat_time=None, repeat_update=true, pedantic=None))]
+    #[pyo3(signature = (frame, repeat_frames=None, repeat_time=None, repeat_update=true, pedantic=None, report=false))]
     /// Present a frame on the window. By default, the frame will be presented once.
     /// Alternatively, you can specify the number of times to present the frame or the
     /// time to present the frame. Please note that if you're using a fixed frame rate monitor
     /// with the `repeat_time` parameter, `repeat_time` need to be a multiple of the
     /// monitor's frame time. Otherwise, the this function will error.
     ///
+    /// If `repeat_update` is True (the default), the frame is re-rendered for every repeated frame,
+    /// so animations advance with every refresh. This costs one full render per refresh. If False,
+    /// the frame is rendered once and the same image is presented repeatedly.
+    ///
+    /// By default, the onset timestamp of the first frame is returned. If `report` is True, a
+    /// `PresentationReport` with the onsets of all frames, the offset, and the number of missed
+    /// deadlines is returned instead.
     fn py_present(
         &self,
         frame: &mut Frame,
@@ -583,32 +1236,390 @@ impl Window {
         repeat_time: Option<f64>,
         repeat_update: bool,
         pedantic: Option<bool>,
+        report: bool,
         py: Python,
-    ) -> PyResult<Option<Timestamp>> {
+    ) -> PyResult<PyObject> {
         let self_wrapper = SendWrapper::new(self.clone());
         let frame_wrapper = SendWrapper::new(frame);
-        py.allow_threads(move || {
-            self_wrapper
-                .present(
+        let presentation_report = py
+            .allow_threads(move || {
+                self_wrapper.present(
                     frame_wrapper.take(),
                     repeat_frames,
                     repeat_time,
                     repeat_update,
                     pedantic,
                 )
-                .map(|x| x.map(|x| Timestamp { timestamp: x }))
-        })
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+            })
+            .map_err(PyErr::from)?;
+
+        self.call_watchdog_callback(py)?;
+
+        if report {
+            Ok(presentation_report.into_pyobject(py)?.into_any().unbind())
+        } else {
+            let onset = presentation_report.onset().unwrap_or_else(Instant::now);
+            Ok(Timestamp::from(onset).into_pyobject(py)?.into_any().unbind())
+        }
+    }
+
+    /// Present a sequence of frames back-to-back, e.g. a prime followed by a mask. Each frame is
+    /// shown for its number of refresh intervals and the next frame follows at the very next
+    /// refresh, without returning to Python in between.
+    ///
+    /// Parameters
+    /// ----------
+    /// sequence : Sequence or list[tuple[Frame, int]]
+    ///   The frames and the number of refresh intervals each of them is shown for.
+    /// repeat_update : bool, optional
+    ///   Whether frames are re-rendered for every refresh interval, see `present`. Defaults to True.
+    ///
+    /// Returns
+    /// -------
+    /// SequenceReport
+    ///   The timing of the whole sequence and of each step.
+    #[pyo3(name = "present_sequence")]
+    #[pyo3(signature = (sequence, repeat_update = true))]
+    fn py_present_sequence(
+        &self,
+        sequence: SequenceOrSteps,
+        repeat_update: bool,
+        py: Python,
+    ) -> PyResult<SequenceReport> {
+        let steps = match sequence {
+            SequenceOrSteps::Sequence(sequence) => sequence
+                .borrow(py)
+                .steps
+                .iter()
+                .map(|(frame, n_frames)| (frame.clone_ref(py), *n_frames))
+                .collect(),
+            SequenceOrSteps::Steps(steps) => steps,
+        };
+        let frames = steps
+            .iter()
+            .map(|(frame, _)| frame.try_borrow(py))
+            .collect::<Result<Vec<_>, _>>()?;
+        let steps = frames
+            .iter()
+            .zip(steps.iter())
+            .map(|(frame, (_, n_frames))| (&**frame, *n_frames))
+            .collect::<Vec<_>>();
+
+        let self_wrapper = SendWrapper::new(self.clone());
+        let steps_wrapper = SendWrapper::new(steps);
+        let report = py
+            .allow_threads(move || self_wrapper.present_sequence(&steps_wrapper, repeat_update))
+            .map_err(PyErr::from)?;
+
+        self.call_watchdog_callback(py)?;
+
+        Ok(report)
+    }
+
+    /// Enable or disable the dropped frame watchdog. When enabled, the time between successive
+    /// frames is compared to the refresh interval of the monitor, and late frames are logged.
+    ///
+    /// Parameters
+    /// ----------
+    /// enabled : bool, optional
+    ///   Whether the watchdog is enabled. Defaults to True.
+    /// tolerance : float, optional
+    ///   How late (as a fraction of the refresh interval) a frame can be before it counts as
+    ///   dropped. Defaults to 0.5.
+    /// callback : callable, optional
+    ///   Called with a dictionary describing each dropped frame once `present` has returned (for
+    ///   all frames of the presentation), e.g. to flag the current trial as invalid. The frame has
+    ///   already been shown late at that point, so the callback cannot prevent the drop. The
+    ///   dictionary contains the "cpu_render_time", the time the CPU spent rendering and submitting
+    ///   the frame (not the time the GPU took to execute it).
+    #[pyo3(name = "set_frame_watchdog")]
+    #[pyo3(signature = (enabled = true, tolerance = 0.5, callback = None))]
+    fn py_set_frame_watchdog(&self, enabled: bool, tolerance: f64, callback: Option<Py<PyAny>>) -> PyResult<()> {
+        Ok(self.with_state(|win_state| {
+            win_state.watchdog.enabled = enabled;
+            win_state.watchdog.tolerance = tolerance;
+            win_state.watchdog.callback = callback;
+        })?)
+    }
+
+    /// Set context that is attached to every dropped frame, e.g. `trial=12`. Replaces the previous
+    /// context.
+    #[pyo3(name = "set_watchdog_context")]
+    #[pyo3(signature = (**context))]
+    fn py_set_watchdog_context(&self, context: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        let context = match context {
+            Some(context) => context
+                .iter()
+                .map(|(key, value)| Ok((key.extract::<String>()?, value.str()?.to_string())))
+                .collect::<PyResult<HashMap<_, _>>>()?,
+            None => HashMap::new(),
+        };
+        Ok(self.with_state(|win_state| win_state.watchdog.context = context)?)
+    }
+
+    /// Set the coordinate system that positions of stimuli, mouse positions, and `contains` hit
+    /// tests use. By default, the origin is in the center of the window and the y-axis points down.
+    /// Only positions are affected: stimuli are never drawn upside down, and sizes stay positive.
+    /// Mouse positions that were recorded before the change are not converted.
+    ///
+    /// Parameters
+    /// ----------
+    /// origin : str, optional
+    ///   Either "center" (default) or "top_left".
+    /// y_axis : str, optional
+    ///   Either "down" (default) or "up".
+    #[pyo3(name = "set_coordinate_system")]
+    #[pyo3(signature = (origin = Origin::Center, y_axis = YAxis::Down))]
+    fn py_set_coordinate_system(&self, origin: Origin, y_axis: YAxis) -> PyResult<()> {
+        Ok(self.set_coordinate_system(CoordinateSystem { origin, y_axis })?)
+    }
+
+    /// Measure the refresh rate by presenting blank frames, e.g. at the start of an experiment. The
+    /// measured rate is compared with the rate reported by the monitor, and warnings are logged
+    /// when frame doubling or interference by a compositor is detected.
+    ///
+    /// Parameters
+    /// ----------
+    /// n_frames : int, optional
+    ///   The number of frames to present.
+    ///
+    /// Returns
+    /// -------
+    /// RefreshMeasurement
+    ///   The measured frame intervals and any detected problems. The most recent measurement is
+    ///   also available as `refresh_measurement`.
+    #[pyo3(name = "measure_refresh_rate")]
+    #[pyo3(signature = (n_frames = 120))]
+    fn py_measure_refresh_rate(&self, n_frames: u32, py: Python) -> PyResult<RefreshMeasurement> {
+        let self_wrapper = SendWrapper::new(self.clone());
+        Ok(py.allow_threads(move || self_wrapper.measure_refresh_rate(n_frames))?)
+    }
+
+    /// The most recent result of `measure_refresh_rate`, or None if the refresh rate has not been
+    /// measured.
+    #[getter(refresh_measurement)]
+    fn py_refresh_measurement(&self) -> PyResult<Option<RefreshMeasurement>> {
+        Ok(self.with_state(|win_state| win_state.refresh_measurement.clone())?)
+    }
+
+    /// All frames that the watchdog detected as dropped, as a list of dictionaries.
+    #[getter(dropped_frames)]
+    fn py_dropped_frames<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let dropped_frames = self.with_state(|win_state| win_state.watchdog.dropped_frames.clone())?;
+        dropped_frames.iter().map(|d| d.to_py_dict(py)).collect()
+    }
+
+    /// Whether the debug overlay is shown. The overlay shows the frame rate, the duration of the
+    /// last frame, the number of dropped frames (if the frame watchdog is enabled), the mouse and
+    /// gaze position, and the number of visible stimuli in the top left corner of the window.
+    ///
+    /// The overlay is drawn into every presented frame, so it should only be enabled during
+    /// development. It is not included in frames that are measured with `measure`.
+    #[getter(debug_overlay)]
+    fn py_debug_overlay(&self) -> PyResult<bool> {
+        Ok(self.with_state(|win_state| win_state.debug_overlay.is_some())?)
+    }
+
+    #[setter(debug_overlay)]
+    fn py_set_debug_overlay(&self, enabled: bool, py: Python) -> PyResult<()> {
+        if enabled == self.py_debug_overlay()? {
+            return Ok(());
+        }
+        let overlay = match enabled {
+            true => Some(DebugOverlay::new(&super::stimuli::helpers::get_experiment_context(
+                None, py,
+            )?)),
+            false => None,
+        };
+        Ok(self.with_state(|win_state| win_state.debug_overlay = overlay)?)
+    }
+
+    /// Whether the layout of the stimuli is shown: the bounding box of every visible stimulus is
+    /// outlined in green, its anchor (the point its position refers to) is marked with a yellow
+    /// cross, and the region in which `contains` returns true is filled in red. Stimuli that do not
+    /// support hit testing have no red region.
+    ///
+    /// Like the debug overlay, this is drawn into every presented frame and is meant for development
+    /// only.
+    #[getter(debug_layout)]
+    fn py_debug_layout(&self) -> PyResult<bool> {
+        Ok(self.with_state(|win_state| win_state.debug_layout)?)
+    }
+
+    #[setter(debug_layout)]
+    fn py_set_debug_layout(&self, enabled: bool) -> PyResult<()> {
+        Ok(self.with_state(|win_state| win_state.debug_layout = enabled)?)
+    }
+
+    /// The most recent gaze sample (in the coordinate system of the window), shown by the debug
+    /// overlay. Set automatically by areas of interest with the "gaze" source, or set it directly
+    /// from the samples of an eye tracker.
+    #[getter(gaze_position)]
+    fn py_gaze_position(&self) -> PyResult<Option<(f32, f32)>> {
+        Ok(self.with_state(|win_state| win_state.gaze_position)?)
+    }
+
+    #[setter(gaze_position)]
+    fn py_set_gaze_position(&self, position: Option<(f32, f32)>) -> PyResult<()> {
+        Ok(self.with_state(|win_state| win_state.gaze_position = position)?)
+    }
+
+    /// Record a gaze sample from an eye tracker. Updates `gaze_position` and, if enabled with
+    /// `detect_gaze_events`, detects saccades and blinks. Trackers built into psydk and areas of
+    /// interest with the "gaze" source do this automatically.
+    ///
+    /// Parameters
+    /// ----------
+    /// position : tuple[float, float] or None
+    ///   The gaze position in the coordinate system of the window, or None if the tracker lost the
+    ///   eye (e.g. during a blink).
+    /// timestamp : Timestamp, optional
+    ///   The time of the sample. Defaults to now.
+    #[pyo3(name = "add_gaze_sample")]
+    #[pyo3(signature = (position, timestamp = None))]
+    fn py_add_gaze_sample(
+        &self,
+        py: Python,
+        position: Option<(f32, f32)>,
+        timestamp: Option<Timestamp>,
+    ) -> PyResult<()> {
+        let time = timestamp.map_or_else(Instant::now, |timestamp| timestamp.timestamp);
+        let window = SendWrapper::new(self.clone());
+        Ok(py.allow_threads(move || window.add_gaze_sample(time, position))?)
+    }
+
+    /// Detect saccades and blinks in the gaze samples of the window. Detected events are delivered
+    /// like other input events, as "saccade" events (at the onset of a saccade, with the gaze
+    /// `position` and `velocity`) and "blink" events (when valid samples resume after a blink,
+    /// with the `duration` of the blink), so gaze-contingent displays can react to them with event
+    /// handlers or event receivers.
+    ///
+    /// Saccades are detected when the angular velocity between two samples exceeds the threshold,
+    /// which requires the physical size of the screen and the viewing distance to be set. Blinks
+    /// are gaps in valid samples of a plausible duration.
+    ///
+    /// Parameters
+    /// ----------
+    /// enabled : bool, optional
+    ///   Whether to detect events. Pass False to stop detecting.
+    /// velocity_threshold : float, optional
+    ///   The velocity above which a saccade is detected, in degrees per second.
+    /// min_blink : float, optional
+    ///   The shortest gap in valid samples that counts as a blink, in seconds.
+    /// max_blink : float, optional
+    ///   The longest gap in valid samples that counts as a blink, in seconds.
+    #[pyo3(name = "detect_gaze_events")]
+    #[pyo3(signature = (enabled = true, velocity_threshold = 30.0, min_blink = 0.05, max_blink = 0.5))]
+    fn py_detect_gaze_events(
+        &self,
+        enabled: bool,
+        velocity_threshold: f32,
+        min_blink: f64,
+        max_blink: f64,
+    ) -> PyResult<()> {
+        let to_duration = |seconds: f64, name: &str| {
+            Duration::try_from_secs_f64(seconds).map_err(|_| {
+                PsydkError::ParameterError(format!("Invalid {name} {seconds}, must be finite and non-negative"))
+            })
+        };
+        let min_blink = to_duration(min_blink, "min_blink")?;
+        let max_blink = to_duration(max_blink, "max_blink")?;
+        if min_blink > max_blink {
+            return Err(PsydkError::ParameterError(format!(
+                "min_blink ({}) must not be greater than max_blink ({})",
+                min_blink.as_secs_f64(),
+                max_blink.as_secs_f64()
+            ))
+            .into());
+        }
+
+        let detector = enabled.then(|| GazeEventDetector::new(velocity_threshold, min_blink, max_blink));
+        Ok(self.with_state(|win_state| win_state.gaze_events = detector)?)
+    }
+
+    /// Attach a simulated participant that responds to frames that expect a response, or detach
+    /// it by passing None.
+    ///
+    /// Parameters
+    /// ----------
+    /// participant : SimulatedParticipant, optional
+    ///   The simulated participant.
+    #[pyo3(name = "simulate")]
+    fn py_simulate(&self, participant: Option<PySimulatedParticipant>) {
+        *self.simulated_participant.lock().unwrap() = participant.map(|p| p.0);
+    }
+
+    /// The state of the keyboard, for polling whether keys are pressed (e.g., for hold-to-respond
+    /// tasks) instead of handling key events.
+    #[getter(keyboard)]
+    fn py_keyboard(&self) -> KeyboardState {
+        self.keyboard.clone()
     }
 
     #[getter(cursor_visible)]
-    fn py_cursor_visible(&self) -> bool {
-        self.cursor_visible()
+    fn py_cursor_visible(&self) -> PyResult<bool> {
+        Ok(self.cursor_visible()?)
     }
 
     #[setter(cursor_visible)]
-    fn py_set_cursor_visible(&self, visible: bool) {
-        self.set_cursor_visible(visible);
+    fn py_set_cursor_visible(&self, visible: bool) -> PyResult<()> {
+        Ok(self.set_cursor_visible(visible)?)
+    }
+
+    /// The appearance of the mouse cursor (see `Cursor`). Setting a hidden cursor hides the cursor
+    /// and setting any other cursor shows it. To change the cursor exactly when a frame is shown
+    /// (e.g., at the start of the response phase of a trial), set `Frame.cursor` instead.
+    #[getter(cursor)]
+    fn py_cursor(&self) -> PyResult<PyCursor> {
+        Ok(PyCursor(self.cursor()?))
+    }
+
+    #[setter(cursor)]
+    fn py_set_cursor(&self, cursor: PyCursor) -> PyResult<()> {
+        Ok(self.set_cursor(cursor.0)?)
+    }
+
+    /// Whether mouse input passes through the window to the applications below it, e.g. for an
+    /// overlay created with `ExperimentContext.create_overlay_window`. While clicks pass through,
+    /// the window receives no mouse events. Not supported on all platforms (see
+    /// `create_overlay_window`).
+    #[getter(click_through)]
+    fn py_click_through(&self) -> PyResult<bool> {
+        Ok(self.click_through()?)
+    }
+
+    #[setter(click_through)]
+    fn py_set_click_through(&self, click_through: bool) -> PyResult<()> {
+        Ok(self.set_click_through(click_through)?)
+    }
+
+    /// Whether input methods (e.g., for Chinese, Japanese, or Korean) may compose text in the
+    /// window. While allowed, typed text may be committed by the input method, which is reported
+    /// with `ime_commit` events, and the text that is being composed is reported with
+    /// `ime_preedit` events. In both cases, `text` events report the text that was entered. Text
+    /// inputs (see `TextInput`) allow input methods automatically. Defaults to False.
+    #[getter(ime_allowed)]
+    fn py_ime_allowed(&self) -> PyResult<bool> {
+        Ok(self.ime_allowed()?)
+    }
+
+    #[setter(ime_allowed)]
+    fn py_set_ime_allowed(&self, allowed: bool) -> PyResult<()> {
+        Ok(self.set_ime_allowed(allowed)?)
+    }
+
+    /// Place the candidate window of the input method next to a position, e.g. the text that is
+    /// being typed.
+    ///
+    /// Parameters
+    /// ----------
+    /// x : float
+    ///   The horizontal position in pixels, in the coordinate system of the window.
+    /// y : float
+    ///   The vertical position in pixels, in the coordinate system of the window.
+    #[pyo3(name = "set_ime_position")]
+    fn py_set_ime_position(&self, x: f32, y: f32) -> PyResult<()> {
+        Ok(self.set_ime_position(x, y)?)
     }
 
     #[pyo3(name = "get_current_monitor")]
@@ -618,30 +1629,22 @@ impl Window {
     }
 
     #[pyo3(name = "get_size")]
-    fn py_get_size(&self, py: Python) -> (u32, u32) {
-        self.size().into()
+    fn py_get_size(&self, py: Python) -> PyResult<(u32, u32)> {
+        Ok(self.size()?.into())
     }
 
     #[pyo3(name = "bg_color")]
     #[getter]
-    fn py_get_bg_color(&self, py: Python) -> LinRgba {
+    fn py_get_bg_color(&self, py: Python) -> PyResult<LinRgba> {
         let self_wrapper = SendWrapper::new(self);
-        py.allow_threads(move || {
-            let state = self_wrapper.state.lock().unwrap();
-            let state = state.as_ref().unwrap();
-            state.bg_color
-        })
+        Ok(py.allow_threads(move || self_wrapper.with_state(|state| state.bg_color))?)
     }
 
     #[pyo3(name = "bg_color")]
     #[setter]
-    fn py_set_bg_color(&self, py: Python, bg_color: LinRgba) {
+    fn py_set_bg_color(&self, py: Python, bg_color: LinRgba) -> PyResult<()> {
         let self_wrapper = SendWrapper::new(self);
-        py.allow_threads(move || {
-            let mut state = self_wrapper.state.lock().unwrap();
-            let mut state = state.as_mut().unwrap();
-            state.bg_color = bg_color
-        })
+        Ok(py.allow_threads(move || self_wrapper.with_state(|state| state.bg_color = bg_color))?)
     }
 
     /// Add an event handler to the window. The event handler will be called
@@ -654,24 +1657,19 @@ impl Window {
     /// callback : callable
     ///  The callback that will be called when the event occurs. The callback should take a single argument, an instance of `Event`.
     #[pyo3(name = "add_event_handler")]
-    fn py_add_event_handler(&self, kind: EventKind, callback: Py<PyAny>, py: Python<'_>) -> EventHandlerId {
+    fn py_add_event_handler(&self, kind: EventKind, callback: Py<PyAny>, py: Python<'_>) -> PyResult<EventHandlerId> {
         // let kind = EventKind::from_str(&kind).expect("Invalid event kind");
 
         let rust_callback_fn = move |event: Event| -> bool {
-            Python::with_gil(|py| -> PyResult<()> {
-
-                    callback.call1(py, (event,))
-                            .expect("Error calling callback in event handler. Make sure the callback takes a single argument of type Event. Error");
-                    Ok(())
-                }).unwrap();
+            Python::with_gil(|py| call_event_callback(py, &callback, event));
             false
         };
 
         let self_wrapper = SendWrapper::new(self);
 
-        let id = py.allow_threads(move || self_wrapper.add_event_handler(kind, rust_callback_fn));
+        let id = py.allow_threads(move || self_wrapper.add_event_handler(kind, rust_callback_fn))?;
 
-        id
+        Ok(id)
     }
 
     /// Remove an event handler from the window.
@@ -706,12 +1704,13 @@ impl Window {
 
 /// FrameIterator is an iterator that yields frames.
 #[derive(Debug, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct FrameIterator {
     /// The window that the frames are associated with.
     window: Window,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl FrameIterator {
     fn __iter__(slf: PyRef<Self>) -> PyResult<Py<FrameIterator>> {
@@ -719,13 +1718,13 @@ impl FrameIterator {
     }
 
     fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<Frame>> {
-        let frame = slf.window.get_frame();
+        let frame = slf.window.get_frame()?;
         Ok(Some(frame))
     }
 }
 
 #[derive(Dbg)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct Frame {
     #[dbg(placeholder = "...")]
     /// The vector of stimuli that will be drawn upon presentation.
@@ -735,12 +1734,78 @@ pub struct Frame {
     /// An optional callback that will be called when the frame is presented.
     #[dbg(placeholder = "...")]
     pub event_handlers: HashMap<EventHandlerId, (EventKind, EventHandler)>,
+    /// The background color of the frame (in linear RGB). Defaults to the window's background color.
+    bg_color: LinRgba,
+    /// Whether a simulated participant should respond to the frame, and the correct key, if any.
+    expected_response: Option<Option<String>>,
+    /// The view the frame is rendered with, if it has been changed.
+    view: Option<FrameView>,
+    /// The cursor that is shown from the onset of the frame, if it changes.
+    cursor: Option<Cursor>,
+}
+
+/// A global transform applied to all stimuli of a frame.
+#[derive(Debug, Clone)]
+pub struct FrameView {
+    /// The point of the scene that is shown at the origin of the window.
+    pub center: (Size, Size),
+    /// The zoom factor.
+    pub scale: f64,
+    /// The rotation in degrees.
+    pub rotation: f64,
+}
+
+impl FrameView {
+    /// The transform from scene coordinates to view coordinates (relative to the center of the window).
+    fn transform(&self, win_state: &WindowState) -> renderer::affine::Affine {
+        let window_size = win_state.size;
+        let screen_props = win_state.physical_screen;
+        let coordinate_system = win_state.coordinate_system;
+
+        // the origin of the coordinate system is shown at the origin of the window when the view is reset
+        let (ox, oy) = coordinate_system.to_scene(0.0, 0.0, window_size);
+        let (cx, cy) = coordinate_system.to_scene(
+            self.center.0.eval(window_size, screen_props),
+            self.center.1.eval(window_size, screen_props),
+            window_size,
+        );
+
+        renderer::affine::Affine::translate(ox as f64, oy as f64)
+            * renderer::affine::Affine::rotate(self.rotation)
+            * renderer::affine::Affine::scale(self.scale)
+            * renderer::affine::Affine::translate(-cx as f64, -cy as f64)
+    }
 }
 
 impl Frame {
     /// Set the background color of the frame.
     pub fn set_bg_color(&mut self, bg_color: LinRgba) {
-        // TODO
+        self.bg_color = bg_color;
+    }
+
+    /// The background color of the frame.
+    pub fn bg_color(&self) -> LinRgba {
+        self.bg_color
+    }
+
+    /// Pan, zoom, and rotate the whole frame. The point `center` is shown where the origin of the
+    /// window's coordinate system would be, scaled by `scale` and rotated by `rotation` degrees.
+    pub fn set_view(&mut self, center: (Size, Size), scale: f64, rotation: f64) {
+        self.view = Some(FrameView {
+            center,
+            scale,
+            rotation,
+        });
+    }
+
+    /// Reset the view, so that stimuli are drawn where they are positioned.
+    pub fn reset_view(&mut self) {
+        self.view = None;
+    }
+
+    /// The view of the frame, if it has been changed.
+    pub fn view(&self) -> Option<&FrameView> {
+        self.view.as_ref()
     }
 
     /// Draw onto the frame.
@@ -757,6 +1822,26 @@ impl Frame {
         // stimulus.draw(self);
     }
 
+    /// Draw a stimulus on top of all other stimuli in the frame. The stimulus is moved to the end of
+    /// the frame and, if necessary, its z is raised to the highest z in the frame.
+    pub fn bring_to_front(&mut self, stimulus: &DynamicStimulus) -> PsydkResult<()> {
+        let index = self
+            .stimuli
+            .iter()
+            .position(|s| s.ptr_eq(stimulus))
+            .ok_or_else(|| PsydkError::ParameterError("The stimulus is not part of the frame".into()))?;
+        let stimulus = self.stimuli.remove(index);
+
+        if let Some(max_z) = self.stimuli.iter().map(|s| s.z()).max() {
+            if stimulus.z() < max_z {
+                stimulus.set_z(max_z);
+            }
+        }
+        self.stimuli.push(stimulus);
+
+        Ok(())
+    }
+
     fn add_event_handler<F>(&mut self, kind: EventKind, handler: F) -> EventHandlerId
     where
         F: Fn(Event) -> bool + 'static + Send + Sync,
@@ -780,8 +1865,20 @@ impl Frame {
     pub fn window(&self) -> Window {
         self.window.clone()
     }
+
+    /// Render the frame offscreen and measure the values that would be sent to the display.
+    pub fn measure(&self) -> PsydkResult<FrameMeasurement> {
+        self.window.measure(self)
+    }
+
+    /// Mark the frame as expecting a response. When a simulated participant is attached to the
+    /// window, it responds to the frame after it has been presented.
+    pub fn expect_response(&mut self, correct: Option<String>) {
+        self.expected_response = Some(correct);
+    }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Frame {
     #[pyo3(name = "add")]
@@ -791,19 +1888,99 @@ impl Frame {
         py.allow_threads(move || self_wrapper.add(stimulus_wrapper.as_super()));
     }
 
+    /// Draw a stimulus on top of all other stimuli in the frame.
+    ///
+    /// Parameters
+    /// ----------
+    /// stimulus : Stimulus
+    ///   A stimulus that has been added to the frame.
+    #[pyo3(name = "bring_to_front")]
+    fn py_bring_to_front(&mut self, stimulus: crate::visual::stimuli::PyStimulus) -> PyResult<()> {
+        Ok(self.bring_to_front(stimulus.as_super())?)
+    }
+
+    /// Mark the frame as expecting a response. Has no effect unless a simulated participant is
+    /// attached to the window (see `Window.simulate`), which then responds after the frame has
+    /// been presented.
+    ///
+    /// Parameters
+    /// ----------
+    /// correct : str, optional
+    ///   The correct key, used by the "correct" policy.
+    #[pyo3(name = "expect_response")]
+    #[pyo3(signature = (correct = None))]
+    fn py_expect_response(&mut self, correct: Option<String>) {
+        self.expect_response(correct);
+    }
+
+    #[getter(bg_color)]
+    fn py_get_bg_color(&self) -> LinRgba {
+        self.bg_color()
+    }
+
     #[setter(bg_color)]
     fn py_set_bg_color(&mut self, bg_color: super::color::LinRgba) {
         self.set_bg_color(bg_color);
     }
 
+    /// The cursor (see `Cursor`) that is set when the frame is presented, and kept until it is
+    /// changed again. Defaults to None, which leaves the cursor as it is.
+    #[getter(cursor)]
+    fn py_get_cursor(&self) -> Option<PyCursor> {
+        self.cursor.clone().map(PyCursor)
+    }
+
+    #[setter(cursor)]
+    fn py_set_cursor(&mut self, cursor: Option<PyCursor>) {
+        self.cursor = cursor.map(|cursor| cursor.0);
+    }
+
+    /// Pan, zoom, and rotate everything that is drawn in the frame, without changing the stimuli.
+    /// The transform is applied when the frame is rendered, so it can be changed from frame to
+    /// frame for smooth zooming, or used to scale an entire layout to a different screen size.
+    ///
+    /// Parameters
+    /// ----------
+    /// center : tuple[Size, Size], optional
+    ///   The point (in the coordinate system of the window) that is shown at the origin of the
+    ///   window. Defaults to the origin, i.e. no panning.
+    /// scale : float, optional
+    ///   The zoom factor. Values larger than 1 zoom in.
+    /// rotation : float, optional
+    ///   The rotation of the view in degrees.
+    #[pyo3(name = "set_view")]
+    #[pyo3(signature = (center = None, scale = 1.0, rotation = 0.0))]
+    fn py_set_view(&mut self, center: Option<(IntoSize, IntoSize)>, scale: f64, rotation: f64) {
+        let center = center
+            .map(|(x, y)| (x.into(), y.into()))
+            .unwrap_or((Size::Pixels(0.0), Size::Pixels(0.0)));
+        self.set_view(center, scale, rotation);
+    }
+
+    /// Reset the view of the frame (see `set_view`).
+    #[pyo3(name = "reset_view")]
+    fn py_reset_view(&mut self) {
+        self.reset_view();
+    }
+
+    /// Render the frame offscreen, without presenting it, and measure the final pixel values (after
+    /// gamma encoding and the LUT). Use this to verify that a display matches the intended
+    /// photometric properties.
+    ///
+    /// Returns
+    /// -------
+    /// FrameMeasurement
+    ///   The mean luminance, RMS contrast, and histogram of the frame.
+    #[pyo3(name = "measure")]
+    fn py_measure(&self, py: Python) -> PyResult<FrameMeasurement> {
+        let self_wrapper = SendWrapper::new(self);
+        Ok(py.allow_threads(move || self_wrapper.measure())?)
+    }
+
     #[pyo3(name = "add_event_handler")]
     fn py_add_event_handler(&mut self, kind: EventKind, callback: Py<PyAny>, py: Python<'_>) -> EventHandlerId {
         let rust_callback_fn = move |event: Event| -> bool {
-            Python::with_gil(|py| -> PyResult<()> {
-                callback.call1(py, (event,))
-                    .expect("Error calling callback in event handler. Make sure the callback takes a single argument of type Event. Error");
-                Ok(())
-            }).unwrap();
+            Python::with_gil(|py| call_event_callback(py, &callback, event));
             false
         };
 
@@ -39,6 +39,8 @@ pub trait Typeface: Any {
 pub struct Glyph {
     pub id: u16,
     pub position: Point,
+    /// Clockwise rotation of the glyph around its position, in radians.
+    pub rotation: f32,
 }
 
 #[derive(Debug, Clone)]
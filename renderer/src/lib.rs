@@ -9,10 +9,14 @@ pub mod prerenderd_scene;
 pub mod renderer;
 pub mod scenes;
 pub mod shapes;
+#[cfg(feature = "skia")]
 pub mod skia_backend;
 pub mod styles;
+#[cfg(feature = "tiny-skia")]
+pub mod tiny_skia_backend;
 mod utils;
-// pub mod vello_backend;
+#[cfg(feature = "vello")]
+pub mod vello_backend;
 pub mod wgpu_renderer;
 
 pub use cosmic_text;
@@ -26,9 +30,24 @@ pub use scenes::DynamicScene;
 // re-export wgpu crate
 pub use wgpu;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Backend {
     Vello,
+    #[default]
     Skia,
+    /// CPU rasterizer, used as a fallback when no suitable GPU is available.
+    TinySkia,
+}
+
+impl Backend {
+    /// Whether the backend was compiled into this build.
+    pub const fn is_available(&self) -> bool {
+        match self {
+            Backend::Vello => cfg!(feature = "vello"),
+            Backend::Skia => cfg!(feature = "skia"),
+            Backend::TinySkia => cfg!(feature = "tiny-skia"),
+        }
+    }
 }
 
 // pub mod prelude {
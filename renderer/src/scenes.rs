@@ -30,6 +30,10 @@ impl DynamicScene {
         self.inner().set_bg_color(color);
     }
 
+    pub fn set_view_transform(&mut self, transform: Affine) {
+        self.inner().set_view_transform(transform);
+    }
+
     pub fn set_width(&mut self, width: u32) {
         self.inner().set_width(width);
     }
@@ -171,4 +175,7 @@ pub trait Scene: Any {
     );
     fn set_bg_color(&mut self, color: RGBA);
     fn bg_color(&self) -> RGBA;
+    /// Set a transform that is applied to the whole scene (relative to its center) when it is
+    /// rendered, e.g. to pan or zoom the view. Must be set before drawing into the scene.
+    fn set_view_transform(&mut self, transform: Affine);
 }
@@ -4,9 +4,13 @@ use cosmic_text::fontdb::FaceInfo;
 use foreign_types_shared::ForeignType;
 
 #[cfg(target_os = "windows")]
-use skia_safe::gpu::{d3d, d3d::BackendContext, Protected};
+use skia_safe::gpu::d3d;
+#[cfg(feature = "gl")]
+use skia_safe::gpu::gl;
 #[cfg(any(target_os = "macos", target_os = "ios"))]
-use skia_safe::gpu::{mtl, mtl::BackendContext};
+use skia_safe::gpu::mtl;
+#[cfg(any(target_os = "windows", feature = "gl"))]
+use skia_safe::gpu::Protected;
 #[cfg(target_os = "windows")]
 use windows::Win32::Graphics::Dxgi::Common::{
     DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC, DXGI_STANDARD_MULTISAMPLE_QUALITY_PATTERN,
@@ -44,6 +48,8 @@ pub struct SkiaScene {
     pub width: u32,
     pub height: u32,
     pub bg_color: RGBA,
+    /// Transform applied to the whole picture when it is rendered.
+    pub view: Affine,
 }
 
 pub struct SkiaRenderer {
@@ -93,6 +99,7 @@ impl SkiaScene {
             width,
             height,
             bg_color: RGBA::WHITE,
+            view: Affine::identity(),
         }
     }
 
@@ -326,6 +333,26 @@ impl Scene for SkiaScene {
 
         // draw the glyphs
         let canvas = self.picture_recorder.recording_canvas().unwrap();
+
+        // rotated glyphs are drawn one by one, each in its own rotated coordinate system
+        if glyphs.iter().any(|glyph| glyph.rotation != 0.0) {
+            for glyph in glyphs {
+                let glyph_origin: skia_safe::Point = glyph.position.into();
+                canvas.save();
+                canvas.translate(origin + glyph_origin);
+                canvas.rotate(glyph.rotation.to_degrees(), None);
+                canvas.draw_glyphs_at(
+                    &[glyph.id],
+                    skia_safe::canvas::GlyphPositions::Points(&[skia_safe::Point::default()]),
+                    skia_safe::Point::default(),
+                    &skia_font,
+                    &paint,
+                );
+                canvas.restore();
+            }
+            return;
+        }
+
         let glyph_ids = glyphs.iter().map(|glyph| glyph.id).collect::<Vec<u16>>();
         let glyph_positions: Vec<skia_safe::Point> = glyphs.into_iter().map(|glyph| glyph.position.into()).collect();
         let glyph_positions = skia_safe::canvas::GlyphPositions::Points(&glyph_positions);
@@ -336,13 +363,22 @@ impl Scene for SkiaScene {
 
     fn set_bg_color(&mut self, color: RGBA) {
         self.bg_color = color;
-        let bg_color: skia_safe::Color4f = color.into();
-        self.picture_recorder.recording_canvas().unwrap().clear(bg_color);
+        // `clear` interprets the color as sRGB, so fill with a paint that carries the color's encoding
+        // instead (otherwise linear colors would be gamma-encoded twice on the linear surface)
+        let mut paint = skia_safe::Paint::default();
+        let color_space: skia_safe::ColorSpace = color.color_encoding().into();
+        paint.set_color4f(skia_safe::Color4f::from(color), &color_space);
+        paint.set_blend_mode(skia_safe::BlendMode::Src);
+        self.picture_recorder.recording_canvas().unwrap().draw_paint(&paint);
     }
 
     fn bg_color(&self) -> RGBA {
         self.bg_color
     }
+
+    fn set_view_transform(&mut self, transform: Affine) {
+        self.view = transform;
+    }
 }
 
 impl Renderer for SkiaRenderer {
@@ -355,48 +391,8 @@ impl Renderer for SkiaRenderer {
         height: u32,
         scene: &mut dyn Scene,
     ) {
-        let mut skia_context = self
-            .shared_state
-            .context
-            .try_borrow_mut()
-            .expect("Failed to borrow skia context");
-
-        // create a new surface
-        #[cfg(target_os = "windows")]
-        let mut surface = Self::create_surface_dx12(
-            device,
-            width,
-            height,
-            texture,
-            &self.shared_state.backend.borrow(),
-            &mut skia_context,
-        );
-
-        #[cfg(any(target_os = "macos", target_os = "ios"))]
-        let mut surface = Self::create_surface_metal(
-            device,
-            width,
-            height,
-            texture,
-            &self.shared_state.backend.borrow(),
-            &mut skia_context,
-        );
-
-        let canvas = surface.canvas();
-
-        // move origin to the center
-        canvas.translate((width as scalar / 2.0, height as scalar / 2.0));
-
-        // try to downcast the scene to a SkiaScene
-        let skia_scene = scene.as_any_mut().downcast_mut::<SkiaScene>().unwrap();
-
-        let picture = skia_scene.picture_recorder.finish_recording_as_picture(None).unwrap();
-
-        // draw the picture to the canvas
-        canvas.draw_picture(&picture, None, None);
-
-        // flush the surface
-        skia_context.flush_and_submit();
+        // the GL context needs to be current while Skia is issuing commands
+        with_gl_context(device, || self.render_scene(device, texture, width, height, scene))
     }
 
     fn create_scene(&self, width: u32, heigth: u32) -> Box<dyn Scene> {
@@ -436,6 +432,47 @@ impl Renderer for SkiaRenderer {
 }
 
 impl SkiaRenderer {
+    fn render_scene(&self, device: &Device, texture: &Texture, width: u32, height: u32, scene: &mut dyn Scene) {
+        let mut skia_context = self
+            .shared_state
+            .context
+            .try_borrow_mut()
+            .expect("Failed to borrow skia context");
+
+        // create a new surface
+        let mut surface = match &*self.shared_state.backend.borrow() {
+            #[cfg(target_os = "windows")]
+            BackendContext::Dx12(backend) => {
+                Self::create_surface_dx12(device, width, height, texture, backend, &mut skia_context)
+            }
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            BackendContext::Metal(backend) => {
+                Self::create_surface_metal(device, width, height, texture, backend, &mut skia_context)
+            }
+            #[cfg(feature = "gl")]
+            BackendContext::Gl(_) => Self::create_surface_gl(width, height, texture, &mut skia_context),
+        };
+
+        let canvas = surface.canvas();
+
+        // move origin to the center
+        canvas.translate((width as scalar / 2.0, height as scalar / 2.0));
+
+        // try to downcast the scene to a SkiaScene
+        let skia_scene = scene.as_any_mut().downcast_mut::<SkiaScene>().unwrap();
+
+        // apply the view transform
+        canvas.concat(&skia_scene.view.into());
+
+        let picture = skia_scene.picture_recorder.finish_recording_as_picture(None).unwrap();
+
+        // draw the picture to the canvas
+        canvas.draw_picture(&picture, None, None);
+
+        // flush the surface
+        skia_context.flush_and_submit();
+    }
+
     // #[cfg(any(target_os = "macos", target_os = "ios"))]
     // fn try_create_backend_metal(device: &Device, queue: &Queue) -> Option<(mtl::BackendContext, gpu::DirectContext)> {
     //     let backend = create_backend_context(device, queue);
@@ -451,7 +488,7 @@ impl SkiaRenderer {
         width: u32,
         height: u32,
         texture: &Texture,
-        backend: &BackendContext,
+        backend: &mtl::BackendContext,
         context: &mut gpu::DirectContext,
     ) -> skia_safe::Surface {
         let raw_texture_ptr = unsafe {
@@ -559,6 +596,37 @@ impl SkiaRenderer {
         )
         .expect("Failed to create Skia surface from DX12 texture")
     }
+
+    #[cfg(feature = "gl")]
+    fn create_surface_gl(
+        width: u32,
+        height: u32,
+        texture: &Texture,
+        context: &mut gpu::DirectContext,
+    ) -> skia_safe::Surface {
+        // the surface is created with the format of the texture, so that Skia renders into it as it is
+        let (texture_info, color_type) = gl_texture_info(texture).expect("Failed to get raw texture from WGPU texture");
+
+        let backend_texture = unsafe {
+            gpu::backend_textures::make_gl(
+                (width as i32, height as i32),
+                gpu::Mipmapped::No,
+                &texture_info,
+                "default",
+            )
+        };
+
+        gpu::surfaces::wrap_backend_texture(
+            &mut *context,
+            &backend_texture,
+            SurfaceOrigin::TopLeft,
+            None,
+            color_type,
+            ColorSpace::new_srgb_linear(),
+            None,
+        )
+        .expect("Failed to create Skia surface from GL texture")
+    }
 }
 
 impl Bitmap for SkiaBitmap {
@@ -861,18 +929,23 @@ unsafe impl Send for SkiaSharedRendererState {}
 unsafe impl Sync for SkiaSharedRendererState {}
 
 impl SkiaSharedRendererState {
-    pub fn new(adapter: &Adapter, device: &Device, queue: &Queue) -> Self {
-        let backend_context = create_backend_context(adapter, device, queue);
-        let skia_context = create_context(&backend_context);
+    /// Create the shared state for the given device. Fails if Skia cannot render with the graphics
+    /// backend of the device, so that the caller can fall back to another renderer.
+    pub fn new(adapter: &Adapter, device: &Device, queue: &Queue) -> Result<Self, String> {
+        let (backend_context, skia_context) = with_gl_context(device, || {
+            let backend_context = create_backend_context(adapter, device, queue)?;
+            let skia_context = create_context(&backend_context)?;
+            Ok::<_, String>((backend_context, skia_context))
+        })?;
 
         // create a font manager
         let font_manager = skia_safe::FontMgr::new();
 
-        Self {
+        Ok(Self {
             context: RefCell::new(skia_context),
             backend: Arc::new(RefCell::new(backend_context)),
             font_manager,
-        }
+        })
     }
 }
 
@@ -1015,8 +1088,22 @@ impl From<ColorEncoding> for skia_safe::ColorSpace {
 
 // Helper functions
 
-/// Create a Skia backend texture from a WGPU texture. Currently only supports Windows with Direct3D 12 and Metal on macOS/iOS.
+/// Create a Skia backend texture from a WGPU texture. Currently only supports Windows with Direct3D 12, Metal on macOS/iOS,
+/// and OpenGL (if the `gl` feature is enabled).
 fn create_backend_texture(texture: &wgpu::Texture) -> skia_safe::gpu::BackendTexture {
+    // opengl implementation (selected at runtime, as GL is available on all platforms)
+    #[cfg(feature = "gl")]
+    if let Some((texture_info, _)) = gl_texture_info(texture) {
+        return unsafe {
+            skia_safe::gpu::backend_textures::make_gl(
+                (texture.width() as i32, texture.height() as i32),
+                skia_safe::gpu::Mipmapped::No,
+                &texture_info,
+                "default",
+            )
+        };
+    }
+
     // windows/dx12 implementation
     #[cfg(target_os = "windows")]
     {
@@ -1115,7 +1202,25 @@ fn create_bitmap_from_wgpu_texture(
     DynamicBitmap(Box::new(skia_texture))
 }
 
-fn create_backend_context(adapter: &Adapter, device: &Device, queue: &Queue) -> BackendContext {
+/// The native graphics API context that Skia is running on. This always matches the backend of the WGPU device.
+#[derive(Debug)]
+enum BackendContext {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    Metal(mtl::BackendContext),
+    #[cfg(target_os = "windows")]
+    Dx12(d3d::BackendContext),
+    #[cfg(feature = "gl")]
+    Gl(gl::Interface),
+}
+
+fn create_backend_context(adapter: &Adapter, device: &Device, queue: &Queue) -> Result<BackendContext, String> {
+    #[cfg(feature = "gl")]
+    if adapter.get_info().backend == wgpu::Backend::Gl {
+        // the GL context is made current by the caller, so Skia can simply pick up the native interface
+        let interface = gl::Interface::new_native().ok_or("Failed to create the Skia GL interface")?;
+        return Ok(BackendContext::Gl(interface));
+    }
+
     #[cfg(any(target_os = "macos", target_os = "ios"))]
     {
         let command_queue_ptr =
@@ -1131,9 +1236,9 @@ fn create_backend_context(adapter: &Adapter, device: &Device, queue: &Queue) ->
             let backend =
                 unsafe { mtl::BackendContext::new(raw_device_ptr.unwrap(), command_queue_ptr as mtl::Handle) };
 
-            backend
+            Ok(BackendContext::Metal(backend))
         } else {
-            panic!("Failed to create Skia backend context: command queue pointer is None");
+            Err("Failed to create Skia backend context: command queue pointer is None".to_string())
         }
     }
     #[cfg(target_os = "windows")]
@@ -1151,26 +1256,102 @@ fn create_backend_context(adapter: &Adapter, device: &Device, queue: &Queue) ->
                 unsafe { device.as_hal::<wgpu::hal::api::Dx12, _, _>(|device| device.map(|s| s.raw_device().clone())) }
                     .unwrap();
 
-            d3d::BackendContext {
+            Ok(BackendContext::Dx12(d3d::BackendContext {
                 adapter: raw_adapter.into(),
                 device: raw_device,
                 queue: command_queue.clone(),
                 memory_allocator: None,
                 protected_context: Protected::No,
-            }
+            }))
         } else {
-            panic!("Failed to create Skia backend context: command queue is None");
+            Err("Failed to create Skia backend context: command queue is None".to_string())
         }
     }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "ios")))]
+    {
+        let _ = (adapter, device, queue);
+        Err(
+            "On this platform, Skia can only render with the OpenGL graphics backend, which requires the `gl` \
+             feature"
+                .to_string(),
+        )
+    }
 }
 
-fn create_context(backend: &BackendContext) -> gpu::DirectContext {
-    #[cfg(any(target_os = "macos", target_os = "ios"))]
+fn create_context(backend: &BackendContext) -> Result<gpu::DirectContext, String> {
+    let context = match backend {
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        BackendContext::Metal(backend) => gpu::direct_contexts::make_metal(backend, None),
+        #[cfg(target_os = "windows")]
+        BackendContext::Dx12(backend) => unsafe { gpu::DirectContext::new_d3d(backend, None) },
+        #[cfg(feature = "gl")]
+        BackendContext::Gl(interface) => gpu::direct_contexts::make_gl(interface.clone(), None),
+    };
+    context.ok_or_else(|| "Failed to create Skia DirectContext".to_string())
+}
+
+/// Run `f` with the GL context used by WGPU made current on this thread. If the device is not using the GL backend,
+/// `f` is simply called.
+fn with_gl_context<R>(device: &Device, f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "gl")]
     {
-        gpu::direct_contexts::make_metal(backend, None).expect("Failed to create Skia DirectContext")
+        let result = unsafe {
+            device.as_hal::<wgpu::hal::api::Gles, _, _>(|device| match device {
+                Some(device) => {
+                    let _lock = device.context().lock();
+                    Ok(f())
+                }
+                None => Err(f),
+            })
+        };
+
+        match result {
+            Ok(result) => result,
+            Err(f) => f(),
+        }
     }
-    #[cfg(target_os = "windows")]
+    #[cfg(not(feature = "gl"))]
     {
-        unsafe { gpu::DirectContext::new_d3d(backend, None).expect("Failed to create Skia DirectContext") }
+        let _ = device;
+        f()
     }
 }
+
+#[cfg(feature = "gl")]
+const GL_TEXTURE_2D: gl::Enum = 0x0DE1;
+
+/// The GL format and the Skia color type that match a WGPU texture format, if Skia supports it.
+#[cfg(feature = "gl")]
+fn gl_format(format: wgpu::TextureFormat) -> Option<(gl::Format, ColorType)> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => Some((gl::Format::RGBA8, ColorType::RGBA8888)),
+        wgpu::TextureFormat::Rgba8UnormSrgb => Some((gl::Format::SRGB8_ALPHA8, ColorType::SRGBA8888)),
+        wgpu::TextureFormat::Rgb10a2Unorm => Some((gl::Format::RGB10_A2, ColorType::RGBA1010102)),
+        wgpu::TextureFormat::Rgba16Unorm => Some((gl::Format::RGBA16, ColorType::R16G16B16A16UNorm)),
+        wgpu::TextureFormat::Rgba16Float => Some((gl::Format::RGBA16F, ColorType::RGBAF16)),
+        _ => None,
+    }
+}
+
+/// Get the GL texture info for a WGPU texture, with the Skia color type of its format. Returns `None`
+/// if the texture is not a GL texture or its format is not supported.
+#[cfg(feature = "gl")]
+fn gl_texture_info(texture: &Texture) -> Option<(gl::TextureInfo, ColorType)> {
+    let (format, color_type) = gl_format(texture.format())?;
+    let raw_texture = unsafe {
+        texture.as_hal::<wgpu::hal::api::Gles, _, _>(|texture| {
+            texture.and_then(|t| match &t.inner {
+                wgpu::hal::gles::TextureInner::Texture { raw, .. } => Some(raw.0.get()),
+                _ => None,
+            })
+        })
+    }?;
+
+    let texture_info = gl::TextureInfo {
+        target: GL_TEXTURE_2D,
+        id: raw_texture,
+        format: format.into(),
+        protected: Protected::No,
+    };
+    Some((texture_info, color_type))
+}
@@ -0,0 +1,946 @@
+use std::{any::Any, cell::RefCell, sync::Arc};
+
+use cosmic_text::fontdb::FaceInfo;
+use skrifa::{
+    instance::{LocationRef, Size as FontSize},
+    outline::{DrawSettings, OutlinePen},
+    MetadataProvider,
+};
+use tiny_skia::{
+    FillRule, FilterQuality, GradientStop, LinearGradient, Mask, Paint, PathBuilder, Pattern, Pixmap, PixmapPaint,
+    RadialGradient, SpreadMode, Transform,
+};
+use wgpu::{Device, Queue, Texture};
+
+use crate::{
+    affine::Affine,
+    bitmaps::{Bitmap, DynamicBitmap},
+    brushes::{Brush, Extend, Gradient, GradientKind, ImageSampling},
+    color_formats::ColorEncoding,
+    colors::RGBA,
+    font::{DynamicFontFace, Glyph, Typeface},
+    renderer::{ColorSpace, Renderer, SharedRendererState},
+    scenes::Scene,
+    shapes::{Point, Shape},
+    styles::{BlendMode, ImageFitMode, StrokeStyle},
+};
+
+/// The CPU-rendered pixmap is uploaded into an intermediate texture of this format and then blitted
+/// into the target texture.
+const STAGING_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// A layer started by `start_layer`. Layers are rendered into their own pixmap and composited onto the
+/// parent layer when they end.
+struct TinySkiaLayer {
+    pixmap: Pixmap,
+    blend_mode: tiny_skia::BlendMode,
+    alpha: f32,
+    clip: Option<Mask>,
+}
+
+pub struct TinySkiaScene {
+    /// The stack of layers. The first layer is the root layer and is never popped.
+    layers: Vec<TinySkiaLayer>,
+    /// Transform moving the origin to the center of the scene.
+    origin: Transform,
+    /// The width of the scene in pixels.
+    pub width: u32,
+    /// The height of the scene in pixels.
+    pub height: u32,
+    /// The background color of the scene.
+    pub bg_color: RGBA,
+}
+
+pub struct TinySkiaRenderer {
+    shared_state: TinySkiaSharedRendererState,
+    /// The texture the pixmap is uploaded to, re-created when the size changes.
+    staging_texture: RefCell<Option<wgpu::Texture>>,
+    /// Blitter used to copy the staging texture into the target texture.
+    blitter: RefCell<Option<(wgpu::TextureFormat, wgpu::util::TextureBlitter)>>,
+}
+
+unsafe impl Send for TinySkiaRenderer {}
+
+#[derive(Debug)]
+/// A Bitmap that is backed by a pixmap in CPU memory or by a WGPU texture that is read back whenever
+/// it is drawn.
+pub enum TinySkiaBitmap {
+    Pixmap(Pixmap),
+    Texture {
+        texture: wgpu::Texture,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct TinySkiaFont {
+    data: Arc<Vec<u8>>,
+    index: u32,
+}
+
+impl Typeface for TinySkiaFont {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn cloned(&self) -> Box<dyn Typeface> {
+        Box::new(self.clone())
+    }
+}
+
+impl TinySkiaScene {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut pixmap = Pixmap::new(width.max(1), height.max(1)).expect("Failed to create pixmap");
+        pixmap.fill(tiny_skia::Color::WHITE);
+
+        Self {
+            layers: vec![TinySkiaLayer {
+                pixmap,
+                blend_mode: tiny_skia::BlendMode::SourceOver,
+                alpha: 1.0,
+                clip: None,
+            }],
+            origin: Transform::from_translate(width as f32 / 2.0, height as f32 / 2.0),
+            width,
+            height,
+            bg_color: RGBA::WHITE,
+        }
+    }
+
+    fn current_pixmap(&mut self) -> &mut Pixmap {
+        &mut self.layers.last_mut().unwrap().pixmap
+    }
+
+    fn transform(&self, transform: Option<Affine>) -> Transform {
+        match transform {
+            Some(transform) => self.origin.pre_concat(transform.into()),
+            None => self.origin,
+        }
+    }
+
+    fn fill_path(&mut self, path: &tiny_skia::Path, paint: &Paint, transform: Transform) {
+        self.current_pixmap()
+            .fill_path(path, paint, FillRule::Winding, transform, None);
+    }
+}
+
+/// Converts a brush into a tiny-skia paint and passes it to `f`. Image brushes borrow their pixmap,
+/// which might only live for the duration of the call (e.g. when reading back a texture).
+fn with_paint<R>(brush: &Brush, f: impl FnOnce(&Paint) -> R) -> R {
+    let mut paint = Paint::default();
+    paint.anti_alias = false;
+
+    match brush {
+        Brush::Solid(color) => {
+            paint.set_color(to_tiny_skia_color(color));
+            f(&paint)
+        }
+        Brush::Gradient(gradient) => {
+            paint.shader = gradient_shader(gradient);
+            f(&paint)
+        }
+        Brush::Image {
+            image,
+            start,
+            fit_mode,
+            sampling,
+            edge_mode,
+            transform,
+            alpha,
+        } => {
+            let bitmap = image
+                .try_as::<TinySkiaBitmap>()
+                .expect("You're trying to use a non-tiny-skia image with a tiny-skia renderer");
+
+            let read_back;
+            let pixmap = match bitmap {
+                TinySkiaBitmap::Pixmap(pixmap) => pixmap,
+                TinySkiaBitmap::Texture { texture, device, queue } => {
+                    read_back = read_texture(device, queue, texture);
+                    &read_back
+                }
+            };
+
+            // mirror the Skia backend: translate to the start point, then scale to fit
+            let mut pattern_transform = match fit_mode {
+                ImageFitMode::Original => Transform::from_translate(start.x as f32, start.y as f32),
+                ImageFitMode::Exact { width, height } => Transform::from_translate(start.x as f32, start.y as f32)
+                    .pre_scale(width / pixmap.width() as f32, height / pixmap.height() as f32),
+            };
+
+            if let Some(transform) = transform {
+                pattern_transform = pattern_transform.post_concat((*transform).into());
+            }
+
+            // tiny-skia only supports a single spread mode for both directions
+            let spread_mode = edge_mode.0.into();
+
+            let quality = match sampling {
+                ImageSampling::Nearest => FilterQuality::Nearest,
+                ImageSampling::Linear => FilterQuality::Bilinear,
+            };
+
+            paint.shader = Pattern::new(
+                pixmap.as_ref(),
+                spread_mode,
+                quality,
+                alpha.unwrap_or(1.0),
+                pattern_transform,
+            );
+            f(&paint)
+        }
+    }
+}
+
+fn gradient_shader(gradient: &Gradient) -> tiny_skia::Shader<'static> {
+    let stops: Vec<GradientStop> = gradient
+        .stops
+        .iter()
+        .map(|stop| GradientStop::new(stop.offset, to_tiny_skia_color(&stop.color)))
+        .collect();
+
+    let first_color = gradient
+        .stops
+        .first()
+        .map(|stop| to_tiny_skia_color(&stop.color))
+        .unwrap_or(tiny_skia::Color::TRANSPARENT);
+
+    let shader = match &gradient.kind {
+        GradientKind::Linear { start, end } => LinearGradient::new(
+            (*start).into(),
+            (*end).into(),
+            stops,
+            gradient.extend.into(),
+            Transform::identity(),
+        ),
+        GradientKind::Radial { center, radius } => RadialGradient::new(
+            (*center).into(),
+            (*center).into(),
+            *radius,
+            stops,
+            gradient.extend.into(),
+            Transform::identity(),
+        ),
+        // tiny-skia does not support sweep gradients
+        GradientKind::Sweep { .. } => None,
+    };
+
+    shader.unwrap_or(tiny_skia::Shader::SolidColor(first_color))
+}
+
+impl Scene for TinySkiaScene {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn set_width(&mut self, width: u32) {
+        self.width = width;
+    }
+
+    fn set_height(&mut self, height: u32) {
+        self.height = height;
+    }
+
+    fn background_color(&self) -> RGBA {
+        self.bg_color
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn start_layer(
+        &mut self,
+        composite_mode: BlendMode,
+        clip: Shape,
+        clip_transform: Option<Affine>,
+        _layer_transform: Option<Affine>,
+        alpha: f32,
+    ) {
+        let clip_transform = self.transform(clip_transform);
+        let clip = shape_to_path(&clip).and_then(|path| {
+            let mut mask = Mask::new(self.width.max(1), self.height.max(1))?;
+            mask.fill_path(&path, FillRule::Winding, false, clip_transform);
+            Some(mask)
+        });
+
+        self.layers.push(TinySkiaLayer {
+            pixmap: Pixmap::new(self.width.max(1), self.height.max(1)).expect("Failed to create pixmap"),
+            blend_mode: composite_mode.into(),
+            alpha,
+            clip,
+        });
+    }
+
+    fn end_layer(&mut self) {
+        if self.layers.len() < 2 {
+            return;
+        }
+
+        let layer = self.layers.pop().unwrap();
+        let paint = PixmapPaint {
+            opacity: layer.alpha,
+            blend_mode: layer.blend_mode,
+            quality: FilterQuality::Nearest,
+        };
+
+        self.current_pixmap().draw_pixmap(
+            0,
+            0,
+            layer.pixmap.as_ref(),
+            &paint,
+            Transform::identity(),
+            layer.clip.as_ref(),
+        );
+    }
+
+    fn draw_shape_fill(
+        &mut self,
+        shape: Shape,
+        brush: Brush,
+        transform: Option<Affine>,
+        blend_mode: Option<BlendMode>,
+    ) {
+        let Some(path) = shape_to_path(&shape) else {
+            return;
+        };
+        let transform = self.transform(transform);
+
+        with_paint(&brush, |paint| {
+            let mut paint = paint.clone();
+            if let Some(blend_mode) = blend_mode {
+                paint.blend_mode = blend_mode.into();
+            }
+            self.fill_path(&path, &paint, transform);
+        });
+    }
+
+    fn draw_shape_stroke(
+        &mut self,
+        shape: Shape,
+        brush: Brush,
+        style: StrokeStyle,
+        transform: Option<Affine>,
+        blend_mode: Option<BlendMode>,
+    ) {
+        let Some(path) = shape_to_path(&shape) else {
+            return;
+        };
+        let transform = self.transform(transform);
+        let stroke: tiny_skia::Stroke = style.into();
+
+        with_paint(&brush, |paint| {
+            let mut paint = paint.clone();
+            if let Some(blend_mode) = blend_mode {
+                paint.blend_mode = blend_mode.into();
+            }
+            self.current_pixmap()
+                .stroke_path(&path, &paint, &stroke, transform, None);
+        });
+    }
+
+    fn draw_glyphs(
+        &mut self,
+        position: Point,
+        glyphs: &[Glyph],
+        font_face: &DynamicFontFace,
+        font_size: f32,
+        brush: Brush,
+        alpha: Option<f32>,
+        transform: Option<Affine>,
+        blend_mode: Option<BlendMode>,
+    ) {
+        let font = font_face
+            .try_as::<TinySkiaFont>()
+            .expect("You're trying to use a non-tiny-skia font with a tiny-skia renderer");
+
+        let Ok(font_ref) = skrifa::FontRef::from_index(&font.data, font.index) else {
+            return;
+        };
+        let outlines = font_ref.outline_glyphs();
+
+        // build a single path containing all glyphs
+        let mut pen = GlyphPen::default();
+        for glyph in glyphs {
+            let Some(outline) = outlines.get(skrifa::GlyphId::from(glyph.id)) else {
+                continue;
+            };
+
+            pen.offset = (
+                (position.x + glyph.position.x) as f32,
+                (position.y + glyph.position.y) as f32,
+            );
+            pen.rotation = glyph.rotation.sin_cos();
+            let settings = DrawSettings::unhinted(FontSize::new(font_size), LocationRef::default());
+            let _ = outline.draw(settings, &mut pen);
+        }
+
+        let Some(path) = pen.builder.finish() else {
+            return;
+        };
+        let transform = self.transform(transform);
+
+        with_paint(&brush, |paint| {
+            let mut paint = paint.clone();
+            paint.anti_alias = true;
+            if let Some(blend_mode) = blend_mode {
+                paint.blend_mode = blend_mode.into();
+            }
+            if let (Some(alpha), tiny_skia::Shader::SolidColor(color)) = (alpha, &mut paint.shader) {
+                color.apply_opacity(alpha);
+            }
+            self.fill_path(&path, &paint, transform);
+        });
+    }
+
+    fn set_bg_color(&mut self, color: RGBA) {
+        self.bg_color = color;
+        let bg_color = to_tiny_skia_color(&color);
+        self.current_pixmap().fill(bg_color);
+    }
+
+    fn bg_color(&self) -> RGBA {
+        self.bg_color
+    }
+
+    fn set_view_transform(&mut self, transform: Affine) {
+        self.origin =
+            Transform::from_translate(self.width as f32 / 2.0, self.height as f32 / 2.0).pre_concat(transform.into());
+    }
+}
+
+/// Collects glyph outlines into a tiny-skia path. Font outlines are y-up, so the y axis is flipped.
+struct GlyphPen {
+    builder: PathBuilder,
+    offset: (f32, f32),
+    /// Sine and cosine of the clockwise rotation of the current glyph.
+    rotation: (f32, f32),
+}
+
+impl Default for GlyphPen {
+    fn default() -> Self {
+        Self {
+            builder: PathBuilder::default(),
+            offset: (0.0, 0.0),
+            rotation: (0.0, 1.0),
+        }
+    }
+}
+
+impl GlyphPen {
+    /// Convert a point of the (y-up) outline to the (y-down) canvas, rotating it around the glyph
+    /// origin.
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (sin, cos) = self.rotation;
+        let (x, y) = (x, -y);
+        (self.offset.0 + x * cos - y * sin, self.offset.1 + x * sin + y * cos)
+    }
+}
+
+impl OutlinePen for GlyphPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        let (cx0, cy0) = self.point(cx0, cy0);
+        let (x, y) = self.point(x, y);
+        self.builder.quad_to(cx0, cy0, x, y);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        let (cx0, cy0) = self.point(cx0, cy0);
+        let (cx1, cy1) = self.point(cx1, cy1);
+        let (x, y) = self.point(x, y);
+        self.builder.cubic_to(cx0, cy0, cx1, cy1, x, y);
+    }
+
+    fn close(&mut self) {
+        self.builder.close();
+    }
+}
+
+impl TinySkiaRenderer {
+    fn staging_texture(&self, device: &Device, width: u32, height: u32) -> wgpu::Texture {
+        let mut texture = self.staging_texture.borrow_mut();
+
+        let needs_new_texture = match texture.as_ref() {
+            Some(t) => t.width() != width || t.height() != height,
+            None => true,
+        };
+
+        if needs_new_texture {
+            *texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Software Renderer Staging Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: STAGING_TEXTURE_FORMAT,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }));
+        }
+
+        texture.as_ref().unwrap().clone()
+    }
+}
+
+impl Renderer for TinySkiaRenderer {
+    fn render_to_texture(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        texture: &Texture,
+        _width: u32,
+        _height: u32,
+        scene: &mut dyn Scene,
+    ) {
+        let scene = scene
+            .as_any_mut()
+            .downcast_mut::<TinySkiaScene>()
+            .expect("Incorrect scene type. You can only use TinySkiaScene with TinySkiaRenderer");
+
+        // close any layers that were not ended explicitly
+        while scene.layers.len() > 1 {
+            scene.end_layer();
+        }
+
+        let pixmap = &scene.layers[0].pixmap;
+
+        // upload the pixmap into the staging texture
+        let staging_texture = self.staging_texture(device, pixmap.width(), pixmap.height());
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &staging_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixmap.data(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * pixmap.width()),
+                rows_per_image: Some(pixmap.height()),
+            },
+            wgpu::Extent3d {
+                width: pixmap.width(),
+                height: pixmap.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // blit the staging texture into the target texture
+        let mut blitter = self.blitter.borrow_mut();
+        if blitter.as_ref().map(|(format, _)| *format) != Some(texture.format()) {
+            *blitter = Some((
+                texture.format(),
+                wgpu::util::TextureBlitter::new(device, texture.format()),
+            ));
+        }
+
+        let staging_view = staging_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let target_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Software Renderer Blit Encoder"),
+        });
+        blitter
+            .as_ref()
+            .unwrap()
+            .1
+            .copy(device, &mut encoder, &staging_view, &target_view);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    fn create_scene(&self, width: u32, heigth: u32) -> Box<dyn Scene> {
+        Box::new(TinySkiaScene::new(width, heigth))
+    }
+
+    fn load_font_face(&mut self, _face_info: &FaceInfo, font_data: &[u8], index: usize) -> DynamicFontFace {
+        self.shared_state.create_font_face(font_data, index as u32)
+    }
+
+    fn create_bitmap_u8(&self, data: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+        tiny_skia_create_bitmap_u8(data, color_space)
+    }
+
+    fn create_bitmap_f32(
+        &self,
+        data: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+        color_space: ColorSpace,
+    ) -> DynamicBitmap {
+        tiny_skia_create_bitmap_f32(data, color_space)
+    }
+
+    fn create_bitmap_from_wgpu_texture(&self, texture: wgpu::Texture, color_space: ColorSpace) -> DynamicBitmap {
+        self.shared_state.create_bitmap_from_wgpu_texture(texture, color_space)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TinySkiaSharedRendererState {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+unsafe impl Send for TinySkiaSharedRendererState {}
+unsafe impl Sync for TinySkiaSharedRendererState {}
+
+impl TinySkiaSharedRendererState {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        Self {
+            device: device.clone(),
+            queue: queue.clone(),
+        }
+    }
+}
+
+impl SharedRendererState for TinySkiaSharedRendererState {
+    fn create_renderer(
+        &self,
+        _surface_format: wgpu::TextureFormat,
+        _width: u32,
+        _height: u32,
+    ) -> crate::DynamicRenderer {
+        let renderer = TinySkiaRenderer {
+            shared_state: self.clone(),
+            staging_texture: RefCell::new(None),
+            blitter: RefCell::new(None),
+        };
+
+        crate::DynamicRenderer::new(Box::new(renderer) as Box<dyn Renderer>)
+    }
+
+    fn cloned(&self) -> Box<dyn SharedRendererState> {
+        Box::new(self.clone())
+    }
+
+    fn create_font_face(&self, font_data: &[u8], index: u32) -> DynamicFontFace {
+        DynamicFontFace(Box::new(TinySkiaFont {
+            data: Arc::new(font_data.to_vec()),
+            index,
+        }))
+    }
+
+    fn create_bitmap_u8(&self, data: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+        tiny_skia_create_bitmap_u8(data, color_space)
+    }
+
+    fn create_bitmap_f32(
+        &self,
+        data: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+        color_space: ColorSpace,
+    ) -> DynamicBitmap {
+        tiny_skia_create_bitmap_f32(data, color_space)
+    }
+
+    fn create_bitmap_from_wgpu_texture(&self, texture: wgpu::Texture, _color_space: ColorSpace) -> DynamicBitmap {
+        DynamicBitmap(Box::new(TinySkiaBitmap::Texture {
+            texture,
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+        }))
+    }
+
+    fn render_resources(&self) -> Option<crate::renderer::DynamicRenderResources> {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Bitmap for TinySkiaBitmap {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Create a premultiplied pixmap from unpremultiplied, linear RGBA values in the range 0-1.
+fn pixmap_from_linear_rgba(width: u32, height: u32, rgba: impl Iterator<Item = [f32; 4]>) -> Pixmap {
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1)).expect("Failed to create pixmap");
+
+    for (dst, [r, g, b, a]) in pixmap.pixels_mut().iter_mut().zip(rgba) {
+        let color = tiny_skia::Color::from_rgba(
+            r.clamp(0.0, 1.0),
+            g.clamp(0.0, 1.0),
+            b.clamp(0.0, 1.0),
+            a.clamp(0.0, 1.0),
+        )
+        .unwrap_or(tiny_skia::Color::TRANSPARENT);
+        *dst = color.premultiply().to_color_u8();
+    }
+
+    pixmap
+}
+
+/// The software renderer does not color-manage images, so sRGB images are converted to linear values
+/// on creation (the GPU backends render into a linear surface as well).
+fn tiny_skia_create_bitmap_u8(rgba: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+    let (width, height) = rgba.dimensions();
+    let pixels = rgba.pixels().map(|p| {
+        let convert = |c: u8| match color_space {
+            ColorSpace::Srgb => srgb2lin(c as f32 / 255.0),
+            ColorSpace::LinearSrgb => c as f32 / 255.0,
+        };
+        [convert(p[0]), convert(p[1]), convert(p[2]), p[3] as f32 / 255.0]
+    });
+
+    DynamicBitmap(Box::new(TinySkiaBitmap::Pixmap(pixmap_from_linear_rgba(
+        width, height, pixels,
+    ))))
+}
+
+fn tiny_skia_create_bitmap_f32(
+    rgba: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+    color_space: ColorSpace,
+) -> DynamicBitmap {
+    let (width, height) = rgba.dimensions();
+    let pixels = rgba.pixels().map(|p| {
+        let convert = |c: f32| match color_space {
+            ColorSpace::Srgb => srgb2lin(c),
+            ColorSpace::LinearSrgb => c,
+        };
+        [convert(p[0]), convert(p[1]), convert(p[2]), p[3]]
+    });
+
+    DynamicBitmap(Box::new(TinySkiaBitmap::Pixmap(pixmap_from_linear_rgba(
+        width, height, pixels,
+    ))))
+}
+
+/// Read an `Rgba8Unorm` texture back into a pixmap.
+fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Pixmap {
+    let (width, height) = (texture.width(), texture.height());
+    let unpadded_bytes_per_row = 4 * width;
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Software Renderer Readback Buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Software Renderer Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    let _ = device.poll(wgpu::PollType::Wait);
+
+    let data = slice.get_mapped_range();
+    let rows = data
+        .chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| row[..unpadded_bytes_per_row as usize].chunks_exact(4))
+        .map(|p| {
+            [
+                p[0] as f32 / 255.0,
+                p[1] as f32 / 255.0,
+                p[2] as f32 / 255.0,
+                p[3] as f32 / 255.0,
+            ]
+        });
+
+    // video frames are sRGB encoded
+    let pixmap = pixmap_from_linear_rgba(
+        width,
+        height,
+        rows.map(|[r, g, b, a]| [srgb2lin(r), srgb2lin(g), srgb2lin(b), a]),
+    );
+
+    drop(data);
+    buffer.unmap();
+
+    pixmap
+}
+
+fn srgb2lin(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a color into a tiny-skia color with linear values.
+fn to_tiny_skia_color(color: &RGBA) -> tiny_skia::Color {
+    let (r, g, b) = match color.color_encoding() {
+        ColorEncoding::Linear => (color.r, color.g, color.b),
+        ColorEncoding::Srgb => (srgb2lin(color.r), srgb2lin(color.g), srgb2lin(color.b)),
+    };
+
+    tiny_skia::Color::from_rgba(
+        r.clamp(0.0, 1.0),
+        g.clamp(0.0, 1.0),
+        b.clamp(0.0, 1.0),
+        color.a.clamp(0.0, 1.0),
+    )
+    .unwrap_or(tiny_skia::Color::TRANSPARENT)
+}
+
+/// Convert a shape to a tiny-skia path. Returns `None` if the shape is empty.
+fn shape_to_path(shape: &Shape) -> Option<tiny_skia::Path> {
+    match shape {
+        Shape::Rectangle { a, w, h } => {
+            let rect = tiny_skia::Rect::from_xywh(a.x as f32, a.y as f32, *w as f32, *h as f32)?;
+            Some(PathBuilder::from_rect(rect))
+        }
+        Shape::RoundedRectangle { a, b, radius } => {
+            let (x0, y0, x1, y1) = (a.x as f32, a.y as f32, b.x as f32, b.y as f32);
+            let r = (*radius as f32).min((x1 - x0).abs() / 2.0).min((y1 - y0).abs() / 2.0);
+            // approximate the corners with quadratic curves
+            let mut pb = PathBuilder::new();
+            pb.move_to(x0 + r, y0);
+            pb.line_to(x1 - r, y0);
+            pb.quad_to(x1, y0, x1, y0 + r);
+            pb.line_to(x1, y1 - r);
+            pb.quad_to(x1, y1, x1 - r, y1);
+            pb.line_to(x0 + r, y1);
+            pb.quad_to(x0, y1, x0, y1 - r);
+            pb.line_to(x0, y0 + r);
+            pb.quad_to(x0, y0, x0 + r, y0);
+            pb.close();
+            pb.finish()
+        }
+        Shape::Circle { center, radius } => PathBuilder::from_circle(center.x as f32, center.y as f32, *radius as f32),
+        Shape::Line { start, end } => {
+            let mut pb = PathBuilder::new();
+            pb.move_to(start.x as f32, start.y as f32);
+            pb.line_to(end.x as f32, end.y as f32);
+            pb.finish()
+        }
+        Shape::Ellipse {
+            center,
+            radius_x,
+            radius_y,
+            rotation,
+        } => {
+            let rect = tiny_skia::Rect::from_xywh(
+                (center.x - radius_x) as f32,
+                (center.y - radius_y) as f32,
+                (*radius_x * 2.0) as f32,
+                (*radius_y * 2.0) as f32,
+            )?;
+            let rotation = Transform::from_rotate_at(*rotation as f32, center.x as f32, center.y as f32);
+            PathBuilder::from_oval(rect)?.transform(rotation)
+        }
+        Shape::Polygon { points } | Shape::Path { points } => {
+            let (first, rest) = points.split_first()?;
+            let mut pb = PathBuilder::new();
+            pb.move_to(first.x as f32, first.y as f32);
+            for point in rest {
+                pb.line_to(point.x as f32, point.y as f32);
+            }
+            if matches!(shape, Shape::Polygon { .. }) {
+                pb.close();
+            }
+            pb.finish()
+        }
+    }
+}
+
+// Point
+impl From<Point> for tiny_skia::Point {
+    fn from(point: Point) -> Self {
+        tiny_skia::Point::from_xy(point.x as f32, point.y as f32)
+    }
+}
+
+// Affine
+impl From<Affine> for Transform {
+    fn from(affine: Affine) -> Self {
+        let m = affine.as_matrix();
+        Transform::from_row(m[(0, 0)], m[(1, 0)], m[(0, 1)], m[(1, 1)], m[(0, 2)], m[(1, 2)])
+    }
+}
+
+// StrokeStyle
+impl From<StrokeStyle> for tiny_skia::Stroke {
+    fn from(style: StrokeStyle) -> Self {
+        tiny_skia::Stroke {
+            width: style.width as f32,
+            miter_limit: style.miter_limit as f32,
+            ..Default::default()
+        }
+    }
+}
+
+// Extend
+impl From<Extend> for SpreadMode {
+    fn from(extend: Extend) -> Self {
+        match extend {
+            Extend::Pad => SpreadMode::Pad,
+            Extend::Repeat => SpreadMode::Repeat,
+            Extend::Reflect => SpreadMode::Reflect,
+        }
+    }
+}
+
+// BlendMode
+impl From<BlendMode> for tiny_skia::BlendMode {
+    fn from(mode: BlendMode) -> Self {
+        match mode {
+            BlendMode::SourceOver => tiny_skia::BlendMode::SourceOver,
+            BlendMode::DestinationOver => tiny_skia::BlendMode::DestinationOver,
+            BlendMode::SourceIn => tiny_skia::BlendMode::SourceIn,
+            BlendMode::DestinationIn => tiny_skia::BlendMode::DestinationIn,
+            BlendMode::SourceOut => tiny_skia::BlendMode::SourceOut,
+            BlendMode::DestinationOut => tiny_skia::BlendMode::DestinationOut,
+            BlendMode::SourceAtop => tiny_skia::BlendMode::SourceAtop,
+            BlendMode::DestinationAtop => tiny_skia::BlendMode::DestinationAtop,
+            BlendMode::Lighter => tiny_skia::BlendMode::Lighten,
+            BlendMode::Copy => tiny_skia::BlendMode::Source,
+            BlendMode::Xor => tiny_skia::BlendMode::Xor,
+            BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+            BlendMode::Modulate => tiny_skia::BlendMode::Modulate,
+        }
+    }
+}
@@ -1,252 +1,158 @@
-use crate::brushes::{Extend, ImageColor};
+use std::{any::Any, cell::RefCell, sync::Arc};
+
 use cosmic_text::fontdb::FaceInfo;
-use image::{DynamicImage, GenericImageView};
-use skrifa::raw::FileRef;
-use std::any::Any;
-use std::sync::Arc;
-use vello::kurbo::PathEl;
-use vello::peniko::color::{AlphaColor, ColorSpaceTag};
-use vello::peniko::BlendMode as VelloBlendMode;
-use vello::peniko::Compose as VelloCompose;
-use vello::peniko::Mix as VelloMix;
-use vello::RendererOptions;
-use wgpu::util::DeviceExt;
-
-use super::brushes::{Gradient, GradientKind};
-use super::text::{Alignment, FormatedText, VerticalAlignment};
-use super::{
-    brushes::{Brush, ColorStop},
+use vello::{
+    kurbo::{self, Shape as KurboShape},
+    peniko::{self, color::ColorSpaceTag, BlendMode as VelloBlendMode, Compose as VelloCompose, Mix as VelloMix},
+    AaConfig, AaSupport, RenderParams, RendererOptions,
+};
+use wgpu::{Device, Queue, Texture};
+
+use crate::{
+    affine::Affine,
+    bitmaps::{Bitmap, DynamicBitmap},
+    brushes::{Brush, ColorStop, Extend, Gradient, GradientKind, ImageSampling},
+    color_formats::ColorEncoding,
     colors::RGBA,
+    font::{DynamicFontFace, Glyph, Typeface},
+    renderer::{ColorSpace, Renderer, SharedRendererState},
+    scenes::Scene,
+    shapes::{Point, Shape},
+    styles::{BlendMode, ImageFitMode, StrokeStyle},
 };
-use crate::bitmaps::DynamicBitmap;
-use crate::prelude::DynamicFontFace;
-use crate::renderer::Renderer;
-use crate::shapes::{Point, Shape};
-use crate::styles::{BlendMode, FillStyle, StrokeStyle};
-use crate::{affine::Affine, scenes::Scene};
+
+/// Tolerance used when flattening curves into paths.
+const PATH_TOLERANCE: f64 = 0.1;
+
+/// Vello can only render into `Rgba8Unorm` storage textures, so we render into an intermediate
+/// texture of that format and blit the result into the target texture.
+const VELLO_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
 
 pub struct VelloScene {
     /// The Vello scene.
     pub vello_scene: vello::Scene,
-    /// The global transform.
-    pub global_transform: Affine,
-    /// array of
-    pub gpu_images: Vec<(vello::peniko::Image, wgpu::TexelCopyTextureInfoBase<wgpu::Texture>)>,
+    /// Images that are backed by a GPU texture and need to be overridden before rendering.
+    pub gpu_images: Vec<(peniko::Image, wgpu::Texture)>,
     /// The width of the scene in pixels.
     pub width: u32,
     /// The height of the scene in pixels.
     pub height: u32,
+    /// The background color of the scene.
+    pub bg_color: RGBA,
+    /// Transform applied to the whole scene when it is rendered.
+    pub view: Affine,
 }
 
 pub struct VelloRenderer {
-    /// The vello renderer struct
-    pub renderer: vello::Renderer,
-    // /// An optional wgpu render pipeline used for rendering the texture that vello produces
-    // pub render_pipeline: wgpu::RenderPipeline,
-    // /// The texture that vello will render to
-    // pub texture: wgpu::Texture,
-    // /// Uniform buffer for gamma correction
-    // pub gamma_buffer: wgpu::Buffer,
-    // /// The bind group
-    // pub bind_group: wgpu::BindGroup,
+    shared_state: VelloSharedRendererState,
+    /// The vello renderer.
+    renderer: RefCell<vello::Renderer>,
+    /// The intermediate texture vello renders to, re-created when the size changes.
+    texture: RefCell<Option<wgpu::Texture>>,
+    /// Blitter used to copy the intermediate texture into the target texture.
+    blitter: RefCell<Option<(wgpu::TextureFormat, wgpu::util::TextureBlitter)>>,
 }
 
-impl VelloRenderer {
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
-        let renderer = vello::Renderer::new(
-            &device,
-            RendererOptions {
-                surface_format: Some(surface_format),
-                use_cpu: false,
-                antialiasing_support: vello::AaSupport::all(),
-                num_init_threads: std::num::NonZeroUsize::new(1),
-            },
-        )
-        .unwrap();
-
-        // // create a render pipeline
-        // let render_pipeline = Self::create_render_pipelie(device, surface_format);
-        // let texture = Self::create_texture(device, width, height);
-        // let gamma_buffer = Self::create_uniform_buffer(device);
-        // let bind_group = Self::create_bind_group(device, &texture);
+unsafe impl Send for VelloRenderer {}
 
-        Self {
-            renderer,
-            // render_pipeline,
-            // texture,
-            // gamma_buffer,
-            // bind_group,
-        }
-    }
+#[derive(Debug, Clone)]
+/// A Bitmap that is backed by a peniko image and, optionally, a WGPU texture.
+pub struct VelloBitmap {
+    image: peniko::Image,
+    texture: Option<wgpu::Texture>,
 }
 
-impl Renderer for vello::Renderer {
-    /// Render the scene to a WGPU surface.
-    // fn render_to_surface(
-    //     &mut self,
-    //     device: &wgpu::Device,
-    //     queue: &wgpu::Queue,
-    //     surface: &wgpu::SurfaceTexture,
-    //     scene: &impl Scene,
-    // ) {
-    //     // try to downcast the scene to a VelloScene
-    //     let scene = scene
-    //         .as_any()
-    //         .downcast_ref::<VelloScene>()
-    //         .expect("Failed to downcast scene to VelloScene");
-    //     let vello_scene = &scene.backend.vello_scene;
-    //     let render_params = vello::RenderParams {
-    //         base_color: scene.background_color.into(),
-    //         width: surface.texture.width(),
-    //         height: surface.texture.height(),
-    //         antialiasing_method: vello::AaConfig::Msaa16,
-    //     };
-    //     // (interim) replace the images with GPU textures.
-    //     for (image, wgpu_texture) in &scene.backend.gpu_images {
-    //         self.renderer.override_image(image, Some(wgpu_texture.clone()));
-    //     }
-    //     self.renderer
-    //         .render_to_surface(device, queue, vello_scene, surface, &render_params)
-    //         .expect("Failed to render to surface");
-    // }
-
-    /// Render the scene to a WGPU texture.
-    fn render_to_texture(
-        &self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        texture: &wgpu::Texture,
-        width: u32,
-        height: u32,
-        scene: &mut dyn Scene,
-    ) {
-        // try to downcast the scene to a VelloScene
-        let vello_scene = &scene
-            .as_any()
-            .downcast_ref::<VelloScene>()
-            .expect("Incorrect scene type. You can only use VelloScene with VelloRenderer");
-
-        let render_params = vello::RenderParams {
-            base_color: AlphaColor::from_rgba8(0, 0, 0, 0),
-            width: width,
-            height: height,
-            antialiasing_method: vello::AaConfig::Msaa16,
-        };
-
-        // (interim) replace the images with GPU textures.
-        // for (image, wgpu_texture) in &vello_scene.gpu_images {
-        //     self.override_image(image, Some(wgpu_texture.clone()));
-        // }
+#[derive(Debug, Clone)]
+pub struct VelloFont(peniko::Font);
 
-        // self.render_to_texture(
-        //     device,
-        //     queue,
-        //     &vello_scene.vello_scene,
-        //     &texture.create_view(&Default::default()),
-        //     &render_params,
-        // )
-        // .expect("Failed to render to texture");
+impl Typeface for VelloFont {
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 
-    fn create_scene(&self, width: u32, heigth: u32) -> Box<dyn Scene> {
-        Box::new(VelloScene::new(width, heigth))
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 
-    fn create_bitmap(&self, data: DynamicImage) -> DynamicBitmap {
-        todo!()
+    fn cloned(&self) -> Box<dyn Typeface> {
+        Box::new(self.clone())
     }
+}
 
-    fn load_font_face(
-        &mut self,
-        face_info: &cosmic_text::fontdb::FaceInfo,
-        font_data: &[u8],
-        index: usize,
-    ) -> DynamicFontFace {
-        todo!()
+impl VelloFont {
+    pub fn from_bytes(bytes: &[u8], index: u32) -> Self {
+        let blob = peniko::Blob::new(Arc::new(bytes.to_vec()));
+        Self(peniko::Font::new(blob, index))
     }
 }
 
-// /// Render the scene to a WGPU surface but sets up its own render pass.
-// pub fn render_to_surface2(
-//     &mut self,
-//     device: &wgpu::Device,
-//     queue: &wgpu::Queue,
-//     surface: &wgpu::SurfaceTexture,
-//     scene: &Scene<VelloBackend>,
-// ) {
-//     // create texture view
-//     let texture_view = self.texture.create_view(&wgpu::TextureViewDescriptor::default());
-//
-//     // render the scene
-//     self.render_to_texture(
-//         device,
-//         queue,
-//         &texture_view,
-//         surface.texture.width(),
-//         surface.texture.height(),
-//         scene,
-//     );
-//
-//     // create a new render pass
-//     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-//         label: Some("Render Encoder"),
-//     });
-//
-//     let surface_texture_view = surface.texture.create_view(&wgpu::TextureViewDescriptor::default());
-//
-//     {
-//         // bind the render pass
-//         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-//             label: Some("Render Pass"),
-//             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-//                 view: &surface_texture_view,
-//                 resolve_target: None,
-//                 ops: wgpu::Operations {
-//                     load: wgpu::LoadOp::Clear(wgpu::Color::RED),
-//                     store: wgpu::StoreOp::Store,
-//                 },
-//             })],
-//             depth_stencil_attachment: None,
-//             timestamp_writes: None,
-//             occlusion_query_set: None,
-//         });
-//
-//         // bind the render pipeline
-//         render_pass.set_pipeline(&self.render_pipeline);
-//         // bind the bind group
-//         render_pass.set_bind_group(0, &self.bind_group, &[]);
-//         // draw the quad
-//         render_pass.draw(0..6, 0..1);
-//     }
-//
-//     // submit the render pass
-//     queue.submit(Some(encoder.finish()));
-// }
-//
-//
-// }
-
 impl VelloScene {
-    /// Create a new Vello backend.
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             vello_scene: vello::Scene::new(),
-            global_transform: Affine::translate(width as f64 / 2.0, height as f64 / 2.0),
             gpu_images: Vec::new(),
             width,
             height,
+            bg_color: RGBA::WHITE,
+            view: Affine::identity(),
         }
     }
-}
 
-macro_rules! dispatch_to_fun {
-    ($value:expr, $fun:expr, $enum_name:ident { $($variant:ident),* }) => {
-        match $value {
-            $(
-                $enum_name::$variant(ref inner) => $fun(inner),
-            )*
+    /// Convert a brush into a peniko brush and an optional brush transform. GPU-backed images
+    /// are recorded so that the renderer can override them before rendering.
+    fn convert_brush(&mut self, brush: &Brush) -> (peniko::Brush, Option<kurbo::Affine>) {
+        match brush {
+            Brush::Solid(color) => (peniko::Brush::Solid(to_vello_color(color)), None),
+            Brush::Gradient(gradient) => (peniko::Brush::Gradient(gradient.clone().into()), None),
+            Brush::Image {
+                image,
+                start,
+                fit_mode,
+                sampling,
+                edge_mode,
+                transform,
+                alpha,
+            } => {
+                let bitmap = image
+                    .try_as::<VelloBitmap>()
+                    .expect("You're trying to use a non-vello image with a vello renderer");
+
+                if let Some(texture) = &bitmap.texture {
+                    self.gpu_images.push((bitmap.image.clone(), texture.clone()));
+                }
+
+                let quality = match sampling {
+                    ImageSampling::Nearest => peniko::ImageQuality::Low,
+                    ImageSampling::Linear => peniko::ImageQuality::Medium,
+                };
+
+                let vello_image = bitmap
+                    .image
+                    .clone()
+                    .with_x_extend(edge_mode.0.into())
+                    .with_y_extend(edge_mode.1.into())
+                    .with_quality(quality)
+                    .with_alpha(alpha.unwrap_or(1.0));
+
+                // mirror the Skia backend: translate to the start point, then scale to fit
+                let mut brush_transform = match fit_mode {
+                    ImageFitMode::Original => kurbo::Affine::translate((start.x, start.y)),
+                    ImageFitMode::Exact { width, height } => {
+                        let scale_x = *width as f64 / bitmap.image.width as f64;
+                        let scale_y = *height as f64 / bitmap.image.height as f64;
+                        kurbo::Affine::translate((start.x, start.y))
+                            * kurbo::Affine::scale_non_uniform(scale_x, scale_y)
+                    }
+                };
+
+                if let Some(transform) = transform {
+                    brush_transform = kurbo::Affine::from(*transform) * brush_transform;
+                }
+
+                (peniko::Brush::Image(vello_image), Some(brush_transform))
+            }
         }
-    };
+    }
 }
 
 impl Scene for VelloScene {
@@ -258,20 +164,16 @@ impl Scene for VelloScene {
         self
     }
 
-    fn set_background_color(&mut self, color: RGBA) {
-        todo!()
-    }
-
     fn set_width(&mut self, width: u32) {
-        todo!()
+        self.width = width;
     }
 
     fn set_height(&mut self, height: u32) {
-        todo!()
+        self.height = height;
     }
 
     fn background_color(&self) -> RGBA {
-        todo!()
+        self.bg_color
     }
 
     fn width(&self) -> u32 {
@@ -287,35 +189,14 @@ impl Scene for VelloScene {
         composite_mode: BlendMode,
         clip: Shape,
         clip_transform: Option<Affine>,
-        layer_transform: Option<Affine>,
+        _layer_transform: Option<Affine>,
         alpha: f32,
     ) {
-        // error if a layer transform is provided
-        if layer_transform.is_some() {
-            todo!();
-        }
-        let clip_shape: VelloShape = clip.into();
-        let global_transform = self.global_transform;
-        let clip_transform = if let Some(clip_transform) = clip_transform {
-            (global_transform * clip_transform)
-        } else {
-            global_transform
-        };
+        let clip_path: kurbo::BezPath = (&clip).into();
+        let clip_transform = clip_transform.map(Into::into).unwrap_or(kurbo::Affine::IDENTITY);
 
-        dispatch_to_fun!(
-            clip_shape,
-            |shape| {
-                self.vello_scene
-                    .push_layer(composite_mode, alpha, clip_transform.into(), shape);
-            },
-            VelloShape {
-                Rectangle,
-                RoundedRect,
-                Circle,
-                Ellipse,
-                Line
-            }
-        );
+        self.vello_scene
+            .push_layer(VelloBlendMode::from(composite_mode), alpha, clip_transform, &clip_path);
     }
 
     fn end_layer(&mut self) {
@@ -329,26 +210,22 @@ impl Scene for VelloScene {
         transform: Option<Affine>,
         blend_mode: Option<BlendMode>,
     ) {
-        let vello_shape = shape.into();
-        dispatch_to_fun!(
-            vello_shape,
-            |shape| {
-                self.vello_scene.fill(
-                    FillStyle::NonZero.into(),
-                    transform.unwrap_or(Affine::identity()).into(),
-                    &brush.as_brush_or_brushref(),
-                    None,
-                    &shape,
-                );
-            },
-            VelloShape {
-                Rectangle,
-                RoundedRect,
-                Circle,
-                Ellipse,
-                Line
-            }
-        );
+        let path: kurbo::BezPath = (&shape).into();
+        let transform = transform.map(Into::into).unwrap_or(kurbo::Affine::IDENTITY);
+        let (brush, brush_transform) = self.convert_brush(&brush);
+
+        // vello only supports blend modes on layers
+        if let Some(blend_mode) = blend_mode {
+            self.vello_scene
+                .push_layer(VelloBlendMode::from(blend_mode), 1.0, transform, &path);
+        }
+
+        self.vello_scene
+            .fill(peniko::Fill::NonZero, transform, &brush, brush_transform, &path);
+
+        if blend_mode.is_some() {
+            self.vello_scene.pop_layer();
+        }
     }
 
     fn draw_shape_stroke(
@@ -359,252 +236,457 @@ impl Scene for VelloScene {
         transform: Option<Affine>,
         blend_mode: Option<BlendMode>,
     ) {
-        // TODO: if a blend mode is provided, we need to push a new layer
-
-        let vello_shape = shape.into();
-        dispatch_to_fun!(
-            vello_shape,
-            |shape| {
-                self.vello_scene.stroke(
-                    &style.into(),
-                    transform.unwrap_or(Affine::identity()).into(),
-                    &brush.as_brush_or_brushref(),
-                    None,
-                    &shape,
-                );
-            },
-            VelloShape {
-                Rectangle,
-                RoundedRect,
-                Circle,
-                Ellipse,
-                Line
-            }
-        );
+        let path: kurbo::BezPath = (&shape).into();
+        let transform = transform.map(Into::into).unwrap_or(kurbo::Affine::IDENTITY);
+        let (brush, brush_transform) = self.convert_brush(&brush);
+        let stroke: kurbo::Stroke = style.into();
+
+        if let Some(blend_mode) = blend_mode {
+            // the clip needs to include the stroke, so we use the bounding box grown by the stroke width
+            let clip = path.bounding_box().inflate(stroke.width, stroke.width);
+            self.vello_scene
+                .push_layer(VelloBlendMode::from(blend_mode), 1.0, transform, &clip);
+        }
+
+        self.vello_scene
+            .stroke(&stroke, transform, &brush, brush_transform, &path);
+
+        if blend_mode.is_some() {
+            self.vello_scene.pop_layer();
+        }
     }
 
     fn draw_glyphs(
         &mut self,
         position: Point,
-        glyphs: &[crate::prelude::Glyph],
+        glyphs: &[Glyph],
         font_face: &DynamicFontFace,
         font_size: f32,
         brush: Brush,
+        alpha: Option<f32>,
         transform: Option<Affine>,
-        blend_mode: Option<BlendMode>,
+        _blend_mode: Option<BlendMode>,
     ) {
-        todo!()
+        let font = font_face
+            .try_as::<VelloFont>()
+            .expect("You're trying to use a non-vello font with a vello renderer");
+
+        let transform = transform.map(Into::into).unwrap_or(kurbo::Affine::IDENTITY)
+            * kurbo::Affine::translate((position.x, position.y));
+
+        let (brush, _) = self.convert_brush(&brush);
+
+        // the glyph transform applies to every glyph of a run, so consecutive glyphs with the same
+        // rotation are drawn together
+        let mut start = 0;
+        while start < glyphs.len() {
+            let end = glyphs[start..]
+                .iter()
+                .position(|glyph| glyph.rotation != glyphs[start].rotation)
+                .map_or(glyphs.len(), |n| start + n);
+            let run = &glyphs[start..end];
+            start = end;
+
+            let glyph_transform = (run[0].rotation != 0.0).then(|| kurbo::Affine::rotate(run[0].rotation as f64));
+            self.vello_scene
+                .draw_glyphs(&font.0)
+                .font_size(font_size)
+                .transform(transform)
+                .glyph_transform(glyph_transform)
+                .brush(&brush)
+                .brush_alpha(alpha.unwrap_or(1.0))
+                .hint(false)
+                .draw(
+                    peniko::Fill::NonZero,
+                    run.iter().map(|glyph| vello::Glyph {
+                        id: glyph.id as u32,
+                        x: glyph.position.x as f32,
+                        y: glyph.position.y as f32,
+                    }),
+                );
+        }
     }
-}
-//
-// // Textures
-// impl Image {
-//     // /// Create a new texture from an image::DynamicImage.
-//     // pub fn new(image: &image::DynamicImage, color_space: ImageColor) -> Self {
-//     //     let data = Arc::new(image.clone().into_rgba8().into_vec());
-//     //
-//     //     return Self {
-//     //         gpu_texture: None,
-//     //         data,
-//     //         width: image.width(),
-//     //         height: image.height(),
-//     //         color_space,
-//     //     };
-//     // }
-//
-//     /// Move the texture to the GPU.
-//     // pub fn to_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
-//     //     if self.gpu_texture.is_some() {
-//     //         return;
-//     //     }
-//     //     println!("Creating texture with color space: {:?}", self.color_space);
-//     //     let data = &self.data;
-//     //     // create a new wgpu texture
-//     //     let wgpu_tetxure = device.create_texture(&wgpu::TextureDescriptor {
-//     //         size: wgpu::Extent3d {
-//     //             width: self.width,
-//     //             height: self.height,
-//     //             depth_or_array_layers: 1,
-//     //         },
-//     //         mip_level_count: 1,
-//     //         sample_count: 1,
-//     //         dimension: wgpu::TextureDimension::D2,
-//     //         format: if self.color_space == ImageColor::LinearRGB {
-//     //             wgpu::TextureFormat::Rgba8Unorm
-//     //         } else {
-//     //             wgpu::TextureFormat::Rgba8UnormSrgb
-//     //         },
-//     //         usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
-//     //         label: None,
-//     //         view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-//     //     });
-//     //
-//     //     // write the image to the texture
-//     //     queue.write_texture(
-//     //         wgpu::ImageCopyTexture {
-//     //             texture: &wgpu_tetxure,
-//     //             mip_level: 0,
-//     //             origin: wgpu::Origin3d::ZERO,
-//     //             aspect: wgpu::TextureAspect::All,
-//     //         },
-//     //         data.as_ref(),
-//     //         wgpu::ImageDataLayout {
-//     //             offset: 0,
-//     //             bytes_per_row: Some(4 * self.width),
-//     //             rows_per_image: Some(self.height),
-//     //         },
-//     //         wgpu::Extent3d {
-//     //             width: self.width,
-//     //             height: self.height,
-//     //             depth_or_array_layers: 1,
-//     //         },
-//     //     );
-//     //
-//     //     self.gpu_texture = Some(Arc::new(wgpu_tetxure));
-//     // }
-// }
 
-#[derive(Debug, Clone)]
-pub enum VelloShape {
-    Rectangle(vello::kurbo::Rect),
-    RoundedRect(vello::kurbo::RoundedRect),
-    Circle(vello::kurbo::Circle),
-    Ellipse(vello::kurbo::Ellipse),
-    Line(vello::kurbo::Line),
+    fn set_bg_color(&mut self, color: RGBA) {
+        // setting the background color clears the scene (like `clear` on a Skia canvas)
+        self.bg_color = color;
+        self.vello_scene.reset();
+        self.gpu_images.clear();
+    }
+
+    fn bg_color(&self) -> RGBA {
+        self.bg_color
+    }
+
+    fn set_view_transform(&mut self, transform: Affine) {
+        self.view = transform;
+    }
 }
 
-// allow converting Shape enum to VelloShape
-impl Into<VelloShape> for Shape {
-    fn into(self) -> VelloShape {
-        match self {
-            Shape::Rectangle { a, b } => VelloShape::Rectangle(vello::kurbo::Rect::new(a.x, a.y, b.x, b.y)),
-            Shape::RoundedRectangle { a, b, radius } => {
-                VelloShape::RoundedRect(vello::kurbo::RoundedRect::new(a.x, a.y, b.x, b.y, radius))
-            }
-            Shape::Circle { center, radius } => VelloShape::Circle(vello::kurbo::Circle::new(center, radius)),
-            Shape::Line { start, end } => VelloShape::Line(vello::kurbo::Line::new(start, end)),
-            Shape::Ellipse {
-                center,
-                radius_x,
-                radius_y,
-                rotation,
-            } => VelloShape::Ellipse(vello::kurbo::Ellipse::new(
-                center,
-                (radius_x, radius_y),
-                rotation.to_radians(),
-            )),
+impl VelloRenderer {
+    fn intermediate_texture(&self, device: &Device, width: u32, height: u32) -> wgpu::Texture {
+        let mut texture = self.texture.borrow_mut();
+
+        let needs_new_texture = match texture.as_ref() {
+            Some(t) => t.width() != width || t.height() != height,
+            None => true,
+        };
+
+        if needs_new_texture {
+            *texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Vello Intermediate Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: VELLO_TEXTURE_FORMAT,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }));
         }
+
+        texture.as_ref().unwrap().clone()
     }
 }
 
-// Point2D
-impl From<Point> for vello::kurbo::Point {
-    fn from(point: Point) -> Self {
-        vello::kurbo::Point::new(point.x, point.y)
+impl Renderer for VelloRenderer {
+    fn render_to_texture(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        texture: &Texture,
+        width: u32,
+        height: u32,
+        scene: &mut dyn Scene,
+    ) {
+        let vello_scene = scene
+            .as_any_mut()
+            .downcast_mut::<VelloScene>()
+            .expect("Incorrect scene type. You can only use VelloScene with VelloRenderer");
+
+        let mut renderer = self.renderer.borrow_mut();
+
+        // replace images that are backed by GPU textures
+        for (image, gpu_texture) in vello_scene.gpu_images.drain(..) {
+            renderer.override_image(
+                &image,
+                Some(wgpu::TexelCopyTextureInfoBase {
+                    texture: gpu_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                }),
+            );
+        }
+
+        // move origin to the center and apply the view transform
+        let mut root_scene = vello::Scene::new();
+        let view: kurbo::Affine = vello_scene.view.into();
+        root_scene.append(
+            &vello_scene.vello_scene,
+            Some(kurbo::Affine::translate((width as f64 / 2.0, height as f64 / 2.0)) * view),
+        );
+
+        let render_params = RenderParams {
+            base_color: to_vello_color(&vello_scene.bg_color),
+            width,
+            height,
+            antialiasing_method: AaConfig::Area,
+        };
+
+        let intermediate_texture = self.intermediate_texture(device, width, height);
+        let intermediate_view = intermediate_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        renderer
+            .render_to_texture(device, queue, &root_scene, &intermediate_view, &render_params)
+            .expect("Failed to render vello scene to texture");
+
+        // blit the intermediate texture into the target texture
+        let mut blitter = self.blitter.borrow_mut();
+        if blitter.as_ref().map(|(format, _)| *format) != Some(texture.format()) {
+            *blitter = Some((
+                texture.format(),
+                wgpu::util::TextureBlitter::new(device, texture.format()),
+            ));
+        }
+
+        let target_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Vello Blit Encoder"),
+        });
+        blitter
+            .as_ref()
+            .unwrap()
+            .1
+            .copy(device, &mut encoder, &intermediate_view, &target_view);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    fn create_scene(&self, width: u32, heigth: u32) -> Box<dyn Scene> {
+        Box::new(VelloScene::new(width, heigth))
+    }
+
+    fn load_font_face(&mut self, _face_info: &FaceInfo, font_data: &[u8], index: usize) -> DynamicFontFace {
+        DynamicFontFace(Box::new(VelloFont::from_bytes(font_data, index as u32)))
+    }
+
+    fn create_bitmap_u8(&self, data: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+        vello_create_bitmap_u8(data, color_space)
+    }
+
+    fn create_bitmap_f32(
+        &self,
+        data: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+        color_space: ColorSpace,
+    ) -> DynamicBitmap {
+        vello_create_bitmap_f32(data, color_space)
+    }
+
+    fn create_bitmap_from_wgpu_texture(&self, texture: wgpu::Texture, color_space: ColorSpace) -> DynamicBitmap {
+        self.shared_state.create_bitmap_from_wgpu_texture(texture, color_space)
     }
 }
 
-// Affine
-impl From<Affine> for vello::kurbo::Affine {
-    fn from(affine: Affine) -> Self {
-        vello::kurbo::Affine::new(affine.0)
+#[derive(Clone, Debug)]
+pub struct VelloSharedRendererState {
+    device: wgpu::Device,
+}
+
+unsafe impl Send for VelloSharedRendererState {}
+unsafe impl Sync for VelloSharedRendererState {}
+
+impl VelloSharedRendererState {
+    pub fn new(device: &Device) -> Self {
+        Self { device: device.clone() }
     }
 }
 
-// FillStyle
-impl From<FillStyle> for vello::peniko::Fill {
-    fn from(style: FillStyle) -> Self {
-        match style {
-            FillStyle::NonZero => vello::peniko::Fill::NonZero,
+impl SharedRendererState for VelloSharedRendererState {
+    fn create_renderer(
+        &self,
+        _surface_format: wgpu::TextureFormat,
+        _width: u32,
+        _height: u32,
+    ) -> crate::DynamicRenderer {
+        let renderer = vello::Renderer::new(
+            &self.device,
+            RendererOptions {
+                use_cpu: false,
+                antialiasing_support: AaSupport::area_only(),
+                num_init_threads: std::num::NonZeroUsize::new(1),
+                pipeline_cache: None,
+            },
+        )
+        .expect("Failed to create vello renderer");
+
+        let renderer = VelloRenderer {
+            shared_state: self.clone(),
+            renderer: RefCell::new(renderer),
+            texture: RefCell::new(None),
+            blitter: RefCell::new(None),
+        };
 
-            FillStyle::EvenOdd => vello::peniko::Fill::EvenOdd,
-        }
+        crate::DynamicRenderer::new(Box::new(renderer) as Box<dyn Renderer>)
+    }
+
+    fn cloned(&self) -> Box<dyn SharedRendererState> {
+        Box::new(self.clone())
+    }
+
+    fn create_font_face(&self, font_data: &[u8], index: u32) -> DynamicFontFace {
+        DynamicFontFace(Box::new(VelloFont::from_bytes(font_data, index)))
+    }
+
+    fn create_bitmap_u8(&self, data: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+        vello_create_bitmap_u8(data, color_space)
+    }
+
+    fn create_bitmap_f32(
+        &self,
+        data: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+        color_space: ColorSpace,
+    ) -> DynamicBitmap {
+        vello_create_bitmap_f32(data, color_space)
+    }
+
+    fn create_bitmap_from_wgpu_texture(&self, texture: wgpu::Texture, _color_space: ColorSpace) -> DynamicBitmap {
+        // the image data is never used since the renderer overrides it with the texture
+        let (width, height) = (texture.width(), texture.height());
+        let placeholder = vec![0u8; (width * height * 4) as usize];
+        let image = peniko::Image::new(
+            peniko::Blob::new(Arc::new(placeholder)),
+            peniko::ImageFormat::Rgba8,
+            width,
+            height,
+        );
+
+        DynamicBitmap(Box::new(VelloBitmap {
+            image,
+            texture: Some(texture),
+        }))
+    }
+
+    fn render_resources(&self) -> Option<crate::renderer::DynamicRenderResources> {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
-// StrokeStyle
-impl From<StrokeStyle> for vello::kurbo::Stroke {
-    fn from(style: StrokeStyle) -> Self {
-        vello::kurbo::Stroke {
-            width: style.width,
-            ..Default::default()
-        }
+impl Bitmap for VelloBitmap {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
-// BrushRef (this needs to be refactored)
-impl<'a> Brush<'_> {
-    fn as_brush_or_brushref(&'a self) -> VelloBrushOrBrushRef<'a> {
-        match self {
-            Brush::Image { .. } => {
-                todo!()
-            }
-            Brush::Solid(rgba) => VelloBrushOrBrushRef::Brush(vello::peniko::Brush::Solid(rgba.clone().into())),
-            Brush::Gradient(gradient) => {
-                VelloBrushOrBrushRef::Brush(vello::peniko::Brush::Gradient(gradient.clone().into()))
+/// Vello does not color-manage images, so sRGB images are converted to linear values on creation
+/// (the Skia backend renders into a linear surface as well).
+fn vello_create_bitmap_u8(rgba: image::RgbaImage, color_space: ColorSpace) -> DynamicBitmap {
+    let (width, height) = rgba.dimensions();
+    let mut buffer = rgba.into_raw();
+
+    if color_space == ColorSpace::Srgb {
+        for pixel in buffer.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = (srgb2lin(*c as f32 / 255.0) * 255.0).round() as u8;
             }
-            _ => todo!(),
         }
     }
+
+    let image = peniko::Image::new(
+        peniko::Blob::new(Arc::new(buffer)),
+        peniko::ImageFormat::Rgba8,
+        width,
+        height,
+    );
+
+    DynamicBitmap(Box::new(VelloBitmap { image, texture: None }))
 }
 
-/// A brush or a brush reference
-pub enum VelloBrushOrBrushRef<'a> {
-    /// A brush brush
-    Brush(vello::peniko::Brush),
-    /// A brush reference
-    BrushRef(vello::peniko::BrushRef<'a>),
+fn vello_create_bitmap_f32(
+    rgba: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+    color_space: ColorSpace,
+) -> DynamicBitmap {
+    let (width, height) = rgba.dimensions();
+
+    let buffer = rgba
+        .into_raw()
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let convert = |c: f32| match color_space {
+                ColorSpace::Srgb => srgb2lin(c),
+                ColorSpace::LinearSrgb => c,
+            };
+            [convert(pixel[0]), convert(pixel[1]), convert(pixel[2]), pixel[3]]
+        })
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect::<Vec<u8>>();
+
+    let image = peniko::Image::new(
+        peniko::Blob::new(Arc::new(buffer)),
+        peniko::ImageFormat::Rgba8,
+        width,
+        height,
+    );
+
+    DynamicBitmap(Box::new(VelloBitmap { image, texture: None }))
 }
 
-// allow VelloBrushOrBrushRef to BrushRef
-impl<'a> From<&'a VelloBrushOrBrushRef<'a>> for vello::peniko::BrushRef<'a> {
-    fn from(brush: &'a VelloBrushOrBrushRef<'a>) -> Self {
-        match brush {
-            VelloBrushOrBrushRef::BrushRef(brush_ref) => brush_ref.clone(),
-            VelloBrushOrBrushRef::Brush(brush) => brush.into(),
-        }
+fn srgb2lin(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
     }
 }
 
-// allow to get peniko::Image from VelloBrushOrBrushRef
-impl<'a> TryFrom<&'a VelloBrushOrBrushRef<'a>> for vello::peniko::Image {
-    type Error = &'static str;
+/// Convert a color into a vello color with linear values.
+fn to_vello_color(color: &RGBA) -> peniko::Color {
+    match color.color_encoding() {
+        ColorEncoding::Linear => peniko::Color::new([color.r, color.g, color.b, color.a]),
+        ColorEncoding::Srgb => peniko::Color::new([srgb2lin(color.r), srgb2lin(color.g), srgb2lin(color.b), color.a]),
+    }
+}
 
-    fn try_from(brush: &'a VelloBrushOrBrushRef<'a>) -> Result<Self, Self::Error> {
-        match brush {
-            VelloBrushOrBrushRef::Brush(vello::peniko::Brush::Image(image)) => Ok(image.clone()),
-            _ => Err("Not an image brush"),
+// convert Shape to a kurbo path
+impl From<&Shape> for kurbo::BezPath {
+    fn from(shape: &Shape) -> Self {
+        match shape {
+            Shape::Rectangle { a, w, h } => kurbo::Rect::new(a.x, a.y, a.x + w, a.y + h).to_path(PATH_TOLERANCE),
+            Shape::RoundedRectangle { a, b, radius } => {
+                kurbo::RoundedRect::new(a.x, a.y, b.x, b.y, *radius).to_path(PATH_TOLERANCE)
+            }
+            Shape::Circle { center, radius } => kurbo::Circle::new(*center, *radius).to_path(PATH_TOLERANCE),
+            Shape::Line { start, end } => kurbo::Line::new(*start, *end).to_path(PATH_TOLERANCE),
+            Shape::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+                rotation,
+            } => kurbo::Ellipse::new(*center, (*radius_x, *radius_y), rotation.to_radians()).to_path(PATH_TOLERANCE),
+            Shape::Polygon { points } | Shape::Path { points } => {
+                let mut path = kurbo::BezPath::new();
+                if let Some((first, rest)) = points.split_first() {
+                    path.move_to(*first);
+                    for point in rest {
+                        path.line_to(*point);
+                    }
+                    if matches!(shape, Shape::Polygon { .. }) {
+                        path.close_path();
+                    }
+                }
+                path
+            }
         }
     }
 }
 
-// Colors
-impl From<RGBA> for vello::peniko::Color {
-    fn from(color: RGBA) -> Self {
-        vello::peniko::Color::from_rgba8(
-            (color.r * 255.0) as u8,
-            (color.g * 255.0) as u8,
-            (color.b * 255.0) as u8,
-            (color.a * 255.0) as u8,
-        )
+// Point
+impl From<Point> for kurbo::Point {
+    fn from(point: Point) -> Self {
+        kurbo::Point::new(point.x, point.y)
     }
 }
 
-impl From<RGBA> for vello::peniko::color::DynamicColor {
-    fn from(color: RGBA) -> Self {
-        let ac = vello::peniko::Color::from_rgba8(
-            (color.r * 255.0) as u8,
-            (color.g * 255.0) as u8,
-            (color.b * 255.0) as u8,
-            (color.a * 255.0) as u8,
-        );
+// Affine
+impl From<Affine> for kurbo::Affine {
+    fn from(affine: Affine) -> Self {
+        let m = affine.as_matrix();
+        // kurbo expects the coefficients in column major order
+        kurbo::Affine::new([
+            m[(0, 0)] as f64,
+            m[(1, 0)] as f64,
+            m[(0, 1)] as f64,
+            m[(1, 1)] as f64,
+            m[(0, 2)] as f64,
+            m[(1, 2)] as f64,
+        ])
+    }
+}
 
-        vello::peniko::color::DynamicColor::from_alpha_color(ac)
+// StrokeStyle
+impl From<StrokeStyle> for kurbo::Stroke {
+    fn from(style: StrokeStyle) -> Self {
+        kurbo::Stroke::new(style.width).with_miter_limit(style.miter_limit)
     }
 }
 
-// CompositeMode
-impl From<BlendMode> for vello::peniko::BlendMode {
+// BlendMode
+impl From<BlendMode> for VelloBlendMode {
     fn from(mode: BlendMode) -> Self {
         match mode {
             BlendMode::SourceIn => VelloCompose::SrcIn.into(),
@@ -625,45 +707,35 @@ impl From<BlendMode> for vello::peniko::BlendMode {
 }
 
 // ColorStop
-impl From<ColorStop> for vello::peniko::ColorStop {
+impl From<ColorStop> for peniko::ColorStop {
     fn from(color_stop: ColorStop) -> Self {
-        vello::peniko::ColorStop {
+        peniko::ColorStop {
             offset: color_stop.offset,
-            color: color_stop.color.into(),
+            color: to_vello_color(&color_stop.color).into(),
         }
     }
 }
 
 // Extend
-impl From<Extend> for vello::peniko::Extend {
+impl From<Extend> for peniko::Extend {
     fn from(extend: Extend) -> Self {
         match extend {
-            Extend::Pad => vello::peniko::Extend::Pad,
-            Extend::Repeat => vello::peniko::Extend::Repeat,
-            Extend::Reflect => vello::peniko::Extend::Reflect,
-        }
-    }
-}
-
-impl From<&Extend> for vello::peniko::Extend {
-    fn from(extend: &Extend) -> Self {
-        match extend {
-            Extend::Pad => vello::peniko::Extend::Pad,
-            Extend::Repeat => vello::peniko::Extend::Repeat,
-            Extend::Reflect => vello::peniko::Extend::Reflect,
+            Extend::Pad => peniko::Extend::Pad,
+            Extend::Repeat => peniko::Extend::Repeat,
+            Extend::Reflect => peniko::Extend::Reflect,
         }
     }
 }
 
 // GradientKind
-impl From<GradientKind> for vello::peniko::GradientKind {
+impl From<GradientKind> for peniko::GradientKind {
     fn from(kind: GradientKind) -> Self {
         match kind {
-            GradientKind::Linear { start, end } => vello::peniko::GradientKind::Linear {
+            GradientKind::Linear { start, end } => peniko::GradientKind::Linear {
                 start: start.into(),
                 end: end.into(),
             },
-            GradientKind::Radial { center, radius } => vello::peniko::GradientKind::Radial {
+            GradientKind::Radial { center, radius } => peniko::GradientKind::Radial {
                 start_center: center.into(),
                 start_radius: 0.0,
                 end_center: center.into(),
@@ -673,7 +745,7 @@ impl From<GradientKind> for vello::peniko::GradientKind {
                 center,
                 start_angle,
                 end_angle,
-            } => vello::peniko::GradientKind::Sweep {
+            } => peniko::GradientKind::Sweep {
                 center: center.into(),
                 start_angle,
                 end_angle,
@@ -683,125 +755,16 @@ impl From<GradientKind> for vello::peniko::GradientKind {
 }
 
 // Gradient
-impl From<Gradient> for vello::peniko::Gradient {
+impl From<Gradient> for peniko::Gradient {
     fn from(gradient: Gradient) -> Self {
-        let stops: Vec<vello::peniko::ColorStop> = gradient.stops.into_iter().map(|stop| stop.into()).collect();
-        let stops = vello::peniko::ColorStops::from(stops.as_slice());
+        let stops: Vec<peniko::ColorStop> = gradient.stops.into_iter().map(|stop| stop.into()).collect();
 
-        vello::peniko::Gradient {
+        peniko::Gradient {
             kind: gradient.kind.into(),
-            stops: stops,
+            stops: peniko::ColorStops::from(stops.as_slice()),
             extend: gradient.extend.into(),
             interpolation_cs: ColorSpaceTag::LinearSrgb,
             hue_direction: Default::default(),
         }
     }
 }
-
-// Text
-#[derive(Debug, Clone)]
-pub struct VelloFont(vello::peniko::Font);
-
-impl VelloFont {
-    pub fn from_bytes(bytes: &[u8]) -> Self {
-        let blob = vello::peniko::Blob::new(Arc::new(bytes.to_vec()));
-        let font = vello::peniko::Font::new(blob, 0);
-
-        Self(font)
-    }
-}
-//
-// impl Drawable<VelloBackend> for FormatedText<VelloFont> {
-//     fn draw(&mut self, scene: &mut Scene<VelloBackend>) {
-//         let transform: vello::kurbo::Affine = (self.transform * scene.backend.global_transform).into();
-//
-//         let font = &self.font.0;
-//         let font_size = skrifa::instance::Size::new(self.size);
-//         let text = &self.text;
-//
-//         let font_ref = vello_font_to_font_ref(font).expect("Failed to load font");
-//         let axes = skrifa::MetadataProvider::axes(&font_ref);
-//         let variations = [("wght", 100.0), ("wdth", 500.0)];
-//         let var_loc = axes.location(variations.iter().copied());
-//
-//         let charmap = skrifa::MetadataProvider::charmap(&font_ref);
-//         let metrics = skrifa::MetadataProvider::metrics(&font_ref, font_size, &var_loc);
-//         let line_height = metrics.ascent - metrics.descent + metrics.leading;
-//         let glyph_metrics = skrifa::MetadataProvider::glyph_metrics(&font_ref, font_size, &var_loc);
-//
-//         let mut pen_x = (self.x * 2.0) as f32;
-//         let mut pen_y = (self.y * 2.0) as f32;
-//
-//         let brush_color: vello::peniko::Color = self.color.into();
-//
-//         let glyphs = text
-//             .chars()
-//             .filter_map(|ch| {
-//                 if ch == '\n' {
-//                     pen_y += line_height;
-//                     pen_x = 0.0;
-//                     return None;
-//                 }
-//                 let gid = charmap.map(ch).unwrap_or_default();
-//                 let advance = glyph_metrics.advance_width(gid).unwrap_or_default();
-//                 let x = pen_x;
-//                 pen_x += advance;
-//                 Some(vello::Glyph {
-//                     id: gid.to_u32(),
-//                     x,
-//                     y: pen_y,
-//                 })
-//             })
-//             .collect::<Vec<_>>();
-//
-//         let text_width = pen_x as f64;
-//         let text_height = pen_y as f64 + line_height as f64;
-//
-//         let transform_x = match self.alignment {
-//             Alignment::Left => 0.0,
-//             Alignment::Center => -text_width / 2.0,
-//             Alignment::Right => -text_width,
-//         };
-//
-//         let transform_y = match self.vertical_alignment {
-//             VerticalAlignment::Top => 0.0,
-//             VerticalAlignment::Middle => text_height / 2.0,
-//             VerticalAlignment::Bottom => text_height,
-//         };
-//
-//         let transform = transform.pre_translate(vello::kurbo::Vec2::new(transform_x, transform_y));
-//
-//         scene
-//             .backend
-//             .vello_scene
-//             .draw_glyphs(font)
-//             .font_size(self.size)
-//             .transform(transform)
-//             .glyph_transform(self.glyph_transform.map(|t| t.into()))
-//             .normalized_coords(bytemuck::cast_slice(var_loc.coords()))
-//             .brush(brush_color)
-//             .hint(false)
-//             .draw(vello::peniko::Fill::NonZero, glyphs.into_iter());
-//     }
-// }
-
-fn vello_font_to_font_ref(font: &vello::peniko::Font) -> Option<skrifa::FontRef<'_>> {
-    use skrifa::raw::FileRef;
-    let file_ref = FileRef::new(font.data.as_ref()).ok()?;
-    match file_ref {
-        FileRef::Font(font) => Some(font),
-        FileRef::Collection(collection) => collection.get(font.index).ok(),
-    }
-}
-//
-// impl Drawable<VelloBackend> for &PrerenderedScene {
-//     fn draw(&mut self, scene: &mut Scene<VelloBackend>) {
-//         let global_transform = scene.backend.global_transform;
-//         let transform = self.transform * global_transform;
-//
-//         scene
-//             .backend
-//             .vello_scene
-//             .append(&mut &self.scene, Some(transform.into()));
-//     }
-// }
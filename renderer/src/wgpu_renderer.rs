@@ -24,6 +24,7 @@ pub struct WgpuRenderer {
     gamma_buffer: Buffer,
     bind_group: BindGroup,
     size: PhysicalSize<u32>,
+    alpha_mode: wgpu::CompositeAlphaMode,
 }
 
 impl WgpuRenderer {
@@ -116,6 +117,7 @@ impl WgpuRenderer {
             gamma_buffer,
             bind_group,
             size,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
         }
     }
 
@@ -139,13 +141,19 @@ impl WgpuRenderer {
         self.surface_format
     }
 
+    /// Set how the surface is composited with whatever is behind the window. Takes effect the next
+    /// time the surface is configured.
+    pub fn set_alpha_mode(&mut self, alpha_mode: wgpu::CompositeAlphaMode) {
+        self.alpha_mode = alpha_mode;
+    }
+
     pub fn configure_surface(&self, surface: &Surface, device: &Device) {
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: self.surface_format,
             // Request compatibility with the sRGB-format texture view we‘re going to create later.
             view_formats: vec![self.surface_format],
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            alpha_mode: self.alpha_mode,
             width: self.size.width,
             height: self.size.height,
             desired_maximum_frame_latency: 1,
@@ -382,6 +390,88 @@ impl WgpuRenderer {
         surface_texture.present();
     }
 
+    /// Render the texture (including gamma encoding and the LUT) offscreen and read back the values
+    /// that would be sent to the display. Returns `None` if the surface format is not an 8-bit
+    /// RGBA or BGRA format.
+    pub fn read_output(&mut self, device: &Device, queue: &Queue) -> Option<image::RgbaImage> {
+        let bgra = match self.surface_format {
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            _ => return None,
+        };
+
+        let (width, height) = (self.size.width, self.size.height);
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Output Readback Texture"),
+            view_formats: &[self.surface_format],
+        });
+        self.render_to_texture(
+            device,
+            queue,
+            &output.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row =
+            unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Output Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Output Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            output.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            output.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        let _ = device.poll(wgpu::PollType::Wait);
+
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks_exact(padded_bytes_per_row as usize)
+            .flat_map(|row| row[..unpadded_bytes_per_row as usize].chunks_exact(4))
+            .flat_map(|p| {
+                if bgra {
+                    [p[2], p[1], p[0], p[3]]
+                } else {
+                    [p[0], p[1], p[2], p[3]]
+                }
+            })
+            .collect::<Vec<_>>();
+
+        drop(data);
+        buffer.unmap();
+
+        image::RgbaImage::from_raw(width, height, pixels)
+    }
+
     pub fn render_to_texture(&mut self, device: &Device, queue: &Queue, texture_view: &wgpu::TextureView) {
         // create a new render pass
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
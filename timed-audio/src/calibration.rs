@@ -0,0 +1,133 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use ndarray::{Array, IxDyn};
+
+use crate::{AudioObject, Stream, build_input_stream};
+
+/// Duration of the click used for calibration.
+const CLICK_DURATION: Duration = Duration::from_millis(2);
+/// Time between the start of recording and the click, so the noise floor can be estimated.
+const CLICK_DELAY: Duration = Duration::from_millis(300);
+/// How long to record after each click.
+const RECORD_DURATION: Duration = Duration::from_millis(800);
+
+/// Recorded input: the time each callback was called and the (mono) samples it received.
+type Recording = Vec<(Instant, Vec<f32>)>;
+
+impl Stream {
+    /// Estimate the end-to-end output latency by playing clicks and recording them through
+    /// `input_device` (using a loopback cable or a microphone placed next to the speaker).
+    ///
+    /// The median latency over `n_clicks` is stored and automatically used to compensate
+    /// `play_at` scheduling.
+    pub fn calibrate_latency(&self, input_device: &cpal::Device, n_clicks: usize) -> anyhow::Result<Duration> {
+        if n_clicks == 0 {
+            return Err(anyhow::anyhow!("At least one click is required for calibration"));
+        }
+
+        let mut latencies = Vec::with_capacity(n_clicks);
+
+        for i in 0..n_clicks {
+            match self.measure_click(input_device)? {
+                Some(latency) => latencies.push(latency),
+                None => log::warn!("Calibration click {} was not detected", i + 1),
+            }
+        }
+
+        if latencies.is_empty() {
+            return Err(anyhow::anyhow!(
+                "None of the calibration clicks were detected. Check the loopback cable or microphone and the input volume."
+            ));
+        }
+
+        latencies.sort_unstable();
+        let latency = latencies[latencies.len() / 2];
+
+        self.set_latency_compensation(Some(latency));
+        Ok(latency)
+    }
+
+    /// Play a single click and return the time between the scheduled and the recorded onset.
+    fn measure_click(&self, input_device: &cpal::Device) -> anyhow::Result<Option<Duration>> {
+        let supported_config = input_device.default_input_config()?;
+        let sample_format = supported_config.sample_format();
+        let input_config: cpal::StreamConfig = supported_config.into();
+        let input_channels = input_config.channels as usize;
+        let input_sample_rate = input_config.sample_rate.0;
+
+        let recording: Arc<Mutex<Recording>> = Arc::new(Mutex::new(Vec::new()));
+        let _recording = recording.clone();
+
+        let input_stream = build_input_stream(
+            input_device,
+            &input_config,
+            sample_format,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let now = Instant::now();
+                let mono = data.chunks(input_channels).map(|frame| frame[0]).collect();
+                _recording.lock().unwrap().push((now, mono));
+            },
+            |err| log::error!("an error occurred on the calibration input stream: {}", err),
+        )?;
+        input_stream.play()?;
+
+        let onset = Instant::now() + CLICK_DELAY;
+        self.play_at_uncompensated(self.click(), onset);
+
+        std::thread::sleep(CLICK_DELAY + RECORD_DURATION);
+        drop(input_stream);
+
+        let recording = recording.lock().unwrap();
+        Ok(detect_onset(&recording, input_sample_rate, onset)
+            .and_then(|detected| detected.checked_duration_since(onset)))
+    }
+
+    /// A short full-scale click on all channels except the trigger channel.
+    fn click(&self) -> AudioObject {
+        let channels = self.content_channels();
+        let n_frames = (CLICK_DURATION.as_secs_f64() * self.sample_rate() as f64).ceil() as usize;
+        let data = Array::from_elem(IxDyn(&[n_frames, channels]), 1.0f32);
+        AudioObject::from_samples(data, self.sample_rate())
+    }
+}
+
+/// Find the time of the first sample after `onset` that clearly exceeds the noise floor recorded
+/// before `onset`. The callback time is taken as the time of the last sample in each buffer.
+fn detect_onset(recording: &Recording, sample_rate: u32, onset: Instant) -> Option<Instant> {
+    let sample_duration = Duration::from_secs_f64(1.0 / sample_rate as f64);
+
+    let sample_times = recording.iter().flat_map(|(time, samples)| {
+        let n = samples.len() as u32;
+        samples
+            .iter()
+            .enumerate()
+            .map(move |(i, sample)| (*time - sample_duration * (n - i as u32), sample.abs()))
+    });
+
+    let noise_floor = sample_times
+        .clone()
+        .filter(|(time, _)| *time < onset)
+        .map(|(_, sample)| sample)
+        .fold(0.0f32, f32::max);
+
+    let peak = sample_times
+        .clone()
+        .filter(|(time, _)| *time >= onset)
+        .map(|(_, sample)| sample)
+        .fold(0.0f32, f32::max);
+
+    // the click needs to stand out clearly from the noise
+    if peak < 0.05 || peak < 4.0 * noise_floor {
+        return None;
+    }
+
+    let threshold = noise_floor.max(0.5 * peak);
+    sample_times
+        .filter(|(time, _)| *time >= onset)
+        .find(|(_, sample)| *sample > threshold)
+        .map(|(time, _)| time)
+}
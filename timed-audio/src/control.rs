@@ -0,0 +1,146 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+/// State shared between a `PlaybackHandle` and the audio callback. All fields are atomics so that
+/// the callback never has to block.
+#[derive(Debug)]
+pub struct PlaybackControl {
+    stopped: AtomicBool,
+    finished: AtomicBool,
+    // f32 values stored as bits
+    volume: AtomicU32,
+    pan: AtomicU32,
+    // requested fade-out length in frames, 0 if no fade-out has been requested
+    fade_out: AtomicU32,
+}
+
+impl Default for PlaybackControl {
+    fn default() -> Self {
+        Self {
+            stopped: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            pan: AtomicU32::new(0.0f32.to_bits()),
+            fade_out: AtomicU32::new(0),
+        }
+    }
+}
+
+impl PlaybackControl {
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_finished(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fade_out_frames(&self) -> u32 {
+        self.fade_out.load(Ordering::Relaxed)
+    }
+
+    /// Snapshot of the current gains, taken once per callback.
+    pub(crate) fn gains(&self, channels: usize, fade: Option<(u32, u32)>) -> Gains {
+        let volume = f32::from_bits(self.volume.load(Ordering::Relaxed));
+        let pan = f32::from_bits(self.pan.load(Ordering::Relaxed));
+
+        Gains {
+            volume,
+            // balance law: the centre position plays both channels at full volume
+            left: (1.0 - pan).min(1.0),
+            right: (1.0 + pan).min(1.0),
+            stereo: channels == 2,
+            fade,
+        }
+    }
+}
+
+/// Per-callback gains applied by the `AudioObjectDataWriter`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Gains {
+    volume: f32,
+    left: f32,
+    right: f32,
+    stereo: bool,
+    // (remaining, total) frames of an active fade-out
+    fade: Option<(u32, u32)>,
+}
+
+impl Gains {
+    /// The gain of the given channel, `frame` frames into the current buffer.
+    pub(crate) fn get(&self, frame: usize, channel: usize) -> f32 {
+        let fade = match self.fade {
+            Some((remaining, total)) => (remaining as f32 - frame as f32).max(0.0) / total as f32,
+            None => 1.0,
+        };
+        let pan = match (self.stereo, channel) {
+            (true, 0) => self.left,
+            (true, 1) => self.right,
+            _ => 1.0,
+        };
+        self.volume * fade * pan
+    }
+}
+
+/// A handle to a sound that has been passed to `Stream::play_now` or `Stream::play_at`. It can be
+/// used to stop the sound, fade it out, or change its volume and pan while it plays.
+#[derive(Debug, Clone)]
+pub struct PlaybackHandle {
+    control: Arc<PlaybackControl>,
+    sample_rate: u32,
+}
+
+impl PlaybackHandle {
+    pub(crate) fn new(control: Arc<PlaybackControl>, sample_rate: u32) -> Self {
+        Self { control, sample_rate }
+    }
+
+    /// Stop the sound immediately. If the sound has not started yet, it will not be played.
+    pub fn stop(&self) {
+        self.control.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Fade the sound out linearly over `duration` and stop it afterwards.
+    pub fn fade_out(&self, duration: Duration) {
+        let frames = (duration.as_secs_f64() * self.sample_rate as f64).round() as u32;
+        if frames == 0 {
+            self.stop();
+        } else {
+            self.control.fade_out.store(frames, Ordering::Relaxed);
+        }
+    }
+
+    /// The volume as a linear gain factor.
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.control.volume.load(Ordering::Relaxed))
+    }
+
+    /// Set the volume as a linear gain factor, where 1.0 is the original volume.
+    pub fn set_volume(&self, volume: f32) {
+        self.control.volume.store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// The stereo pan, from -1.0 (left) to 1.0 (right).
+    pub fn pan(&self) -> f32 {
+        f32::from_bits(self.control.pan.load(Ordering::Relaxed))
+    }
+
+    /// Set the stereo pan, from -1.0 (left) to 1.0 (right). Has no effect on streams that do not
+    /// have exactly two channels.
+    pub fn set_pan(&self, pan: f32) {
+        self.control
+            .pan
+            .store(pan.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Whether the sound has finished playing, either because it reached its end or because it
+    /// was stopped.
+    pub fn is_finished(&self) -> bool {
+        self.control.finished.load(Ordering::Relaxed)
+    }
+}
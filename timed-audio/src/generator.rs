@@ -0,0 +1,140 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// The shape of a parameter ramp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RampCurve {
+    /// Change the value linearly.
+    Linear,
+    /// Change the value exponentially, i.e. by a constant ratio per unit of time. This is the
+    /// natural choice for frequency sweeps. Falls back to linear if either end is not positive.
+    Exponential,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+    curve: RampCurve,
+}
+
+impl Ramp {
+    /// The value of the ramp `elapsed` seconds after its start.
+    fn value(&self, elapsed: f64) -> f32 {
+        // a ramp without a duration steps to its end value at its start
+        if self.duration.is_zero() {
+            return if elapsed < 0.0 { self.from } else { self.to };
+        }
+        let t = (elapsed / self.duration.as_secs_f64()).clamp(0.0, 1.0) as f32;
+        match self.curve {
+            RampCurve::Exponential if self.from > 0.0 && self.to > 0.0 => self.from * (self.to / self.from).powf(t),
+            _ => self.from + (self.to - self.from) * t,
+        }
+    }
+}
+
+/// A generator parameter that can be changed while the generator is playing, either immediately
+/// or along a ramp that is evaluated per sample in the audio callback.
+#[derive(Debug)]
+pub struct ModulatedParam {
+    // the target value (f32 bits), i.e. the end value of the ramp if there is one
+    value: AtomicU32,
+    ramp: Mutex<Option<Ramp>>,
+}
+
+impl ModulatedParam {
+    pub fn new(value: f32) -> Self {
+        Self {
+            value: AtomicU32::new(value.to_bits()),
+            ramp: Mutex::new(None),
+        }
+    }
+
+    /// The current value, taking an active ramp into account.
+    pub fn get(&self) -> f32 {
+        self.snapshot(Instant::now()).value_at(0.0)
+    }
+
+    /// Set the value immediately, cancelling any active ramp.
+    pub fn set(&self, value: f32) {
+        let mut ramp = self.ramp.lock().unwrap();
+        *ramp = None;
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Change the value from its value at `start` to `to` over `duration`.
+    pub fn ramp_to(&self, to: f32, duration: Duration, start: Instant, curve: RampCurve) {
+        let from = self.snapshot(start).value_at(0.0);
+        let mut ramp = self.ramp.lock().unwrap();
+        *ramp = Some(Ramp {
+            from,
+            to,
+            start,
+            duration,
+            curve,
+        });
+        self.value.store(to.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Capture the state of the parameter at `now`. Never blocks: if the ramp is being changed
+    /// concurrently, the ramp is ignored and the target value is used for this buffer.
+    pub(crate) fn snapshot(&self, now: Instant) -> ParamSnapshot {
+        let value = f32::from_bits(self.value.load(Ordering::Relaxed));
+        let ramp = self.ramp.try_lock().ok().and_then(|ramp| *ramp);
+
+        ParamSnapshot {
+            value,
+            ramp: ramp.map(|ramp| {
+                let offset = match now.checked_duration_since(ramp.start) {
+                    Some(elapsed) => elapsed.as_secs_f64(),
+                    None => -ramp.start.duration_since(now).as_secs_f64(),
+                };
+                (ramp, offset)
+            }),
+        }
+    }
+}
+
+/// The state of a `ModulatedParam` at the start of an audio buffer.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ParamSnapshot {
+    value: f32,
+    // the ramp and the time of the snapshot relative to the start of the ramp in seconds
+    ramp: Option<(Ramp, f64)>,
+}
+
+impl ParamSnapshot {
+    /// The value `t` seconds after the snapshot was taken.
+    pub(crate) fn value_at(&self, t: f64) -> f32 {
+        match self.ramp {
+            Some((ramp, offset)) => ramp.value(offset + t),
+            None => self.value,
+        }
+    }
+}
+
+/// Parameters of a continuous generator, shared between all copies of the audio object and the
+/// audio callback.
+#[derive(Debug)]
+pub struct GeneratorParams {
+    /// The frequency in Hz. Ignored by noise generators.
+    pub frequency: ModulatedParam,
+    /// The amplitude as a linear factor.
+    pub amplitude: ModulatedParam,
+}
+
+impl GeneratorParams {
+    pub fn new(frequency: f32, amplitude: f32) -> Self {
+        Self {
+            frequency: ModulatedParam::new(frequency),
+            amplitude: ModulatedParam::new(amplitude),
+        }
+    }
+}
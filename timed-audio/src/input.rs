@@ -0,0 +1,226 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+
+/// How often `wait_for_silence` checks the level.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// How often `wait_for_silence_checked` calls its check while waiting.
+const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// RMS and peak level of the most recent input buffer, updated from the audio callback.
+#[derive(Debug)]
+struct LevelMeter {
+    // f32 values stored as bits
+    rms: AtomicU32,
+    peak: AtomicU32,
+}
+
+impl LevelMeter {
+    fn update(&self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        let sum_squares: f32 = samples.iter().map(|s| s * s).sum();
+        let rms = (sum_squares / samples.len() as f32).sqrt();
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+        self.rms.store(rms.to_bits(), Ordering::Relaxed);
+        self.peak.store(peak.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A buffer of captured audio, as passed to subscribers of an `InputStream`.
+#[derive(Debug, Clone)]
+pub struct InputChunk {
+    /// Index of the first frame of the chunk since the stream was opened.
+    pub first_frame: u64,
+    /// The time at which the first frame was captured by the device.
+    pub time: Instant,
+    /// Interleaved samples.
+    pub data: Vec<f32>,
+}
+
+/// A stream that captures audio from an input device (e.g., a microphone).
+pub struct InputStream {
+    level: Arc<LevelMeter>,
+    sample_rate: u32,
+    channels: u16,
+    close_sender: mpsc::Sender<()>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<InputChunk>>>>,
+}
+
+impl InputStream {
+    /// Open the default input configuration of `device` and start capturing.
+    pub fn new(device: &cpal::Device) -> anyhow::Result<Self> {
+        let supported_config = device.default_input_config()?;
+        let sample_format = supported_config.sample_format();
+        let config: cpal::StreamConfig = supported_config.into();
+        let level = Arc::new(LevelMeter {
+            rms: AtomicU32::new(0.0f32.to_bits()),
+            peak: AtomicU32::new(0.0f32.to_bits()),
+        });
+
+        let (close_sender, close_receiver) = mpsc::channel();
+        let (ready_sender, ready_receiver) = mpsc::channel();
+
+        let _device = device.clone();
+        let _config = config.clone();
+        let _level = level.clone();
+        let subscribers: Arc<Mutex<Vec<mpsc::Sender<InputChunk>>>> = Arc::new(Mutex::new(Vec::new()));
+        let _subscribers = subscribers.clone();
+        let channels = config.channels as usize;
+        let mut frames_captured = 0u64;
+        let mut promoted = false;
+
+        // the cpal stream is not `Send` on all platforms, so it lives on its own thread
+        std::thread::spawn(move || {
+            let stream = crate::build_input_stream(
+                &_device,
+                &_config,
+                sample_format,
+                move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                    crate::realtime::promote_audio_thread_once(&mut promoted, &crate::realtime::INPUT_CALLBACK);
+                    _level.update(data);
+
+                    let first_frame = frames_captured;
+                    frames_captured += (data.len() / channels) as u64;
+
+                    let mut subscribers = _subscribers.lock().unwrap();
+                    if subscribers.is_empty() {
+                        return;
+                    }
+
+                    // the callback is called some time after the data was captured
+                    let timestamp = info.timestamp();
+                    let delay = timestamp
+                        .callback
+                        .duration_since(&timestamp.capture)
+                        .unwrap_or_default();
+                    let chunk = InputChunk {
+                        first_frame,
+                        time: Instant::now() - delay,
+                        data: data.to_vec(),
+                    };
+
+                    // drop subscribers that have gone away
+                    subscribers.retain(|subscriber| subscriber.send(chunk.clone()).is_ok());
+                },
+                |err| log::error!("an error occurred on the input stream: {}", err),
+            );
+
+            let stream = match stream.and_then(|stream| {
+                stream.play()?;
+                Ok(stream)
+            }) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = ready_sender.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready_sender.send(Ok(()));
+
+            // keep the stream alive until the input stream is closed or dropped
+            let _ = close_receiver.recv();
+            drop(stream);
+        });
+
+        ready_receiver
+            .recv()
+            .map_err(|_| anyhow::anyhow!("The input stream thread exited unexpectedly"))??;
+
+        Ok(Self {
+            level,
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            close_sender,
+            subscribers,
+        })
+    }
+
+    /// Receive all audio captured from now on. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> mpsc::Receiver<InputChunk> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// The RMS level (linear, 0.0 to 1.0) of the most recent input buffer across all channels.
+    pub fn rms(&self) -> f32 {
+        f32::from_bits(self.level.rms.load(Ordering::Relaxed))
+    }
+
+    /// The peak level (linear, 0.0 to 1.0) of the most recent input buffer across all channels.
+    pub fn peak(&self) -> f32 {
+        f32::from_bits(self.level.peak.load(Ordering::Relaxed))
+    }
+
+    /// Block until the RMS level has stayed below `threshold` for `duration`. Returns false if
+    /// `timeout` expired first.
+    pub fn wait_for_silence(&self, threshold: f32, duration: Duration, timeout: Option<Duration>) -> bool {
+        let Ok(silent) = self.wait_for_silence_checked(threshold, duration, timeout, || Ok::<_, Infallible>(()));
+        silent
+    }
+
+    /// Like `wait_for_silence`, but calls `check` every 50 ms while waiting and stops with its error
+    /// if it fails, e.g. to handle interrupts while waiting without a timeout.
+    pub fn wait_for_silence_checked<E>(
+        &self,
+        threshold: f32,
+        duration: Duration,
+        timeout: Option<Duration>,
+        mut check: impl FnMut() -> Result<(), E>,
+    ) -> Result<bool, E> {
+        let start = Instant::now();
+        let mut silent_since = None;
+        let mut last_check = start;
+
+        loop {
+            let now = Instant::now();
+            if self.rms() < threshold {
+                let since = *silent_since.get_or_insert(now);
+                if now.duration_since(since) >= duration {
+                    return Ok(true);
+                }
+            } else {
+                silent_since = None;
+            }
+
+            if timeout.is_some_and(|timeout| now.duration_since(start) >= timeout) {
+                return Ok(false);
+            }
+            if now.duration_since(last_check) >= CHECK_INTERVAL {
+                check()?;
+                last_check = now;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Stop capturing. Also happens when the stream is dropped.
+    pub fn close(&self) {
+        let _ = self.close_sender.send(());
+    }
+}
+
+impl Drop for InputStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -19,6 +19,20 @@ use rand_distr::Distribution;
 use symphonia::core::{io::MediaSourceStream, probe::Hint};
 use thread_priority::ThreadPriorityValue;
 
+mod calibration;
+mod control;
+mod generator;
+mod input;
+pub mod realtime;
+mod trigger;
+
+pub use control::PlaybackHandle;
+use control::{Gains, PlaybackControl};
+pub use generator::{GeneratorParams, ModulatedParam, RampCurve};
+pub use input::{InputChunk, InputStream};
+pub use trigger::TriggerChannel;
+use trigger::TriggerState;
+
 #[derive(Debug, Clone)]
 pub enum AudioObject {
     Buffer {
@@ -38,6 +52,15 @@ pub enum AudioObject {
     Silence {
         duration: Duration,
     },
+    /// A sine tone that plays until it is stopped. Its parameters can be modulated while it plays.
+    Tone {
+        params: Arc<GeneratorParams>,
+    },
+    /// White noise that plays until it is stopped. Its amplitude can be modulated while it plays.
+    Noise {
+        params: Arc<GeneratorParams>,
+        seed: Option<u64>,
+    },
 }
 
 impl AudioObject {
@@ -88,6 +111,27 @@ impl AudioObject {
         Self::Silence { duration }
     }
 
+    pub fn tone(frequency: f32, amplitude: f32) -> Self {
+        Self::Tone {
+            params: Arc::new(GeneratorParams::new(frequency, amplitude)),
+        }
+    }
+
+    pub fn noise(amplitude: f32, seed: Option<u64>) -> Self {
+        Self::Noise {
+            params: Arc::new(GeneratorParams::new(0.0, amplitude)),
+            seed,
+        }
+    }
+
+    /// The modulatable parameters of continuous generators (`Tone` and `Noise`).
+    pub fn generator_params(&self) -> Option<&Arc<GeneratorParams>> {
+        match self {
+            AudioObject::Tone { params } | AudioObject::Noise { params, .. } => Some(params),
+            _ => None,
+        }
+    }
+
     pub fn from_samples(samples: Array<f32, ndarray::IxDyn>, sample_rate: u32) -> Self {
         Self::Buffer {
             data: samples,
@@ -105,6 +149,7 @@ impl AudioObject {
             AudioObject::SineWave { duration, .. } => *duration,
             AudioObject::WhiteNoise { duration, .. } => *duration,
             AudioObject::Silence { duration, .. } => *duration,
+            AudioObject::Tone { .. } | AudioObject::Noise { .. } => Duration::MAX,
         }
     }
 
@@ -114,12 +159,13 @@ impl AudioObject {
             AudioObject::SineWave { .. } => None,
             AudioObject::WhiteNoise { .. } => None,
             AudioObject::Silence { .. } => None,
+            AudioObject::Tone { .. } | AudioObject::Noise { .. } => None,
         }
     }
 
     pub fn into_writer(self, stream_sample_rate: u32, stream_channels: usize) -> AudioObjectDataWriter {
         let rng = match self {
-            AudioObject::WhiteNoise { seed, .. } => {
+            AudioObject::WhiteNoise { seed, .. } | AudioObject::Noise { seed, .. } => {
                 if let Some(seed) = seed {
                     Some(rand::rngs::SmallRng::seed_from_u64(seed))
                 } else {
@@ -135,6 +181,9 @@ impl AudioObject {
             target_sample_rate: stream_sample_rate,
             target_channels: stream_channels,
             rng,
+            control: Arc::new(PlaybackControl::default()),
+            fade: None,
+            phase: 0.0,
         }
     }
 }
@@ -146,6 +195,11 @@ pub struct AudioObjectDataWriter {
     target_sample_rate: u32,
     target_channels: usize,
     rng: Option<rand::rngs::SmallRng>,
+    control: Arc<PlaybackControl>,
+    // (remaining, total) frames of an active fade-out
+    fade: Option<(u32, u32)>,
+    // phase of continuous tones in radians, so that frequency changes don't cause discontinuities
+    phase: f64,
 }
 
 impl AudioObjectDataWriter {
@@ -153,7 +207,44 @@ impl AudioObjectDataWriter {
         self.current_idx += n_samples;
     }
 
+    fn with_control(mut self, control: Arc<PlaybackControl>) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Write the next chunk of data to the output buffer. Returns true if the audio object has
+    /// ended, either because all data has been written or because it was stopped.
     pub fn write_data<T>(&mut self, output: &mut [T]) -> Result<bool, anyhow::Error>
+    where
+        T: Sample + FromSample<f32>,
+    {
+        if self.control.is_stopped() || self.fade.is_some_and(|(remaining, _)| remaining == 0) {
+            self.control.set_finished();
+            return Ok(true);
+        }
+
+        if self.fade.is_none() {
+            let frames = self.control.fade_out_frames();
+            if frames > 0 {
+                self.fade = Some((frames, frames));
+            }
+        }
+
+        let gains = self.control.gains(self.target_channels, self.fade);
+        let n_frames = self.write_frames(output, gains)?;
+
+        if let Some((remaining, total)) = self.fade {
+            self.fade = Some((remaining.saturating_sub(n_frames as u32), total));
+        }
+
+        if n_frames == 0 {
+            self.control.set_finished();
+        }
+        Ok(n_frames == 0)
+    }
+
+    /// Write up to one buffer of frames, scaled by `gains`, and return the number of frames written.
+    fn write_frames<T>(&mut self, output: &mut [T], gains: Gains) -> Result<usize, anyhow::Error>
     where
         T: Sample + FromSample<f32>,
     {
@@ -183,14 +274,13 @@ impl AudioObjectDataWriter {
                 // copy the data
                 for (i, frame) in output.chunks_mut(self.target_channels).enumerate().take(n_frames) {
                     for (j, sample) in frame.iter_mut().enumerate() {
-                        *sample = T::from_sample(data[[self.current_idx + i, j]]);
+                        *sample = T::from_sample(data[[self.current_idx + i, j]] * gains.get(i, j));
                     }
                 }
 
                 self.current_idx += n_frames;
 
-                // return true if the end of the audio object has been reached
-                Ok(n_frames == 0)
+                Ok(n_frames)
             }
             AudioObject::SineWave {
                 frequency,
@@ -205,15 +295,14 @@ impl AudioObjectDataWriter {
                 for (i, frame) in output.chunks_mut(self.target_channels).enumerate().take(n_frames) {
                     let t = t + i as f32 / sample_rate;
                     let value = amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin();
-                    for sample in frame.iter_mut() {
-                        *sample = T::from_sample(value);
+                    for (j, sample) in frame.iter_mut().enumerate() {
+                        *sample = T::from_sample(value * gains.get(i, j));
                     }
                 }
 
                 self.current_idx += n_frames;
 
-                // return true if the end of the audio object has been reached
-                Ok(n_frames == 0)
+                Ok(n_frames)
             }
             AudioObject::WhiteNoise {
                 amplitude,
@@ -228,17 +317,57 @@ impl AudioObjectDataWriter {
                 let normal = rand_distr::Normal::new(0.0, 1.0).unwrap();
                 let mut rng = self.rng.as_mut().unwrap();
 
-                for (_, frame) in output.chunks_mut(self.target_channels).enumerate().take(n_frames) {
-                    for sample in frame.iter_mut() {
+                for (i, frame) in output.chunks_mut(self.target_channels).enumerate().take(n_frames) {
+                    for (j, sample) in frame.iter_mut().enumerate() {
                         let random_f: f32 = normal.sample(&mut rng);
-                        *sample = T::from_sample(amplitude * (2.0 * random_f - 1.0));
+                        *sample = T::from_sample(amplitude * (2.0 * random_f - 1.0) * gains.get(i, j));
+                    }
+                }
+
+                self.current_idx += n_frames;
+
+                Ok(n_frames)
+            }
+            AudioObject::Tone { params } => {
+                let n_frames = output.len() / self.target_channels;
+                let sample_rate = self.target_sample_rate as f64;
+                let now = Instant::now();
+                let frequency = params.frequency.snapshot(now);
+                let amplitude = params.amplitude.snapshot(now);
+
+                for (i, frame) in output.chunks_mut(self.target_channels).enumerate() {
+                    let t = i as f64 / sample_rate;
+                    let value = amplitude.value_at(t) * self.phase.sin() as f32;
+                    for (j, sample) in frame.iter_mut().enumerate() {
+                        *sample = T::from_sample(value * gains.get(i, j));
                     }
+                    self.phase = (self.phase + std::f64::consts::TAU * frequency.value_at(t) as f64 / sample_rate)
+                        % std::f64::consts::TAU;
                 }
 
                 self.current_idx += n_frames;
 
-                // return true if the end of the audio object has been reached
-                Ok(n_frames == 0)
+                Ok(n_frames)
+            }
+            AudioObject::Noise { params, .. } => {
+                let n_frames = output.len() / self.target_channels;
+                let sample_rate = self.target_sample_rate as f64;
+                let amplitude = params.amplitude.snapshot(Instant::now());
+
+                let normal = rand_distr::Normal::new(0.0, 1.0).unwrap();
+                let mut rng = self.rng.as_mut().unwrap();
+
+                for (i, frame) in output.chunks_mut(self.target_channels).enumerate() {
+                    let amplitude = amplitude.value_at(i as f64 / sample_rate);
+                    for (j, sample) in frame.iter_mut().enumerate() {
+                        let random_f: f32 = normal.sample(&mut rng);
+                        *sample = T::from_sample(amplitude * (2.0 * random_f - 1.0) * gains.get(i, j));
+                    }
+                }
+
+                self.current_idx += n_frames;
+
+                Ok(n_frames)
             }
             _ => todo!(),
         }
@@ -247,10 +376,13 @@ impl AudioObjectDataWriter {
 
 #[derive(Debug, Clone)]
 pub enum StreamCommand {
-    PlayNow(AudioObject, u32),
-    PlayAt(AudioObject, Instant, u32),
+    PlayNow(AudioObject, u32, Arc<PlaybackControl>),
+    PlayAt(AudioObject, Instant, u32, Arc<PlaybackControl>),
     GetStatus(std::sync::mpsc::Sender<Status>),
     GetLatency(std::sync::mpsc::Sender<Option<u32>>),
+    SetTrigger(Option<TriggerChannel>),
+    PulseNow,
+    PulseAt(Instant),
     Stop,
     Close,
 }
@@ -264,11 +396,22 @@ pub enum Status {
 #[derive(Debug)]
 pub enum CallbackCommand {
     /// Set the audio object to play with the given delay in samples
-    SetAudioObject(AudioObject, u32),
+    SetAudioObject(AudioObject, u32, Arc<PlaybackControl>),
     /// Remove the audio object
     RemoveAudioObject,
     /// Timestamp the current chunk of data
     Timestamp(oneshot::Sender<Instant>),
+    /// Set (or remove) the trigger channel
+    SetTrigger(Option<TriggerChannel>),
+    /// Start a trigger pulse with the next chunk of data
+    Pulse,
+}
+
+/// Something the dispatcher thread sends to the callback at a scheduled time.
+#[derive(Debug, Clone)]
+enum Scheduled {
+    Sound(AudioObject, Arc<PlaybackControl>),
+    Pulse,
 }
 
 #[derive(Clone)]
@@ -279,6 +422,9 @@ pub struct Stream {
     // channels for communication with the stream thread
     command_sender: std::sync::mpsc::Sender<StreamCommand>,
     sample_rate: u32,
+    // measured end-to-end latency, subtracted from the onset time in `play_at`
+    latency_compensation: Arc<Mutex<Option<Duration>>>,
+    trigger: Arc<Mutex<Option<TriggerChannel>>>,
 }
 
 impl Stream {
@@ -313,46 +459,93 @@ impl Stream {
         // spawn a thread to handle the stream
         std::thread::spawn(move || {
             // create a cpal stream
-            let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
+            let err_fn = |err| log::error!("an error occurred on stream: {}", err);
 
             let _channels = _config.channels as usize;
 
-            let mut ao_writer = None;
+            let mut ao_writer: Option<AudioObjectDataWriter> = None;
+            let mut trigger: Option<TriggerState> = None;
+            // sounds are written here first when they are routed around the trigger channel
+            let mut content: Vec<T> = Vec::new();
 
             // create a channel to communicate with the callback using CallbackCommand
             let (callback_sender, callback_receiver) = std::sync::mpsc::channel();
 
             let mut _current_sample = 0;
+            let mut promoted = false;
 
             let stream = _device
                 .build_output_stream(
                     &_config,
                     move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-                        // check if there is a new command
-                        match callback_receiver.try_recv() {
-                            Ok(CallbackCommand::SetAudioObject(audio_object, delay)) => {
-                                ao_writer = Some(audio_object.into_writer(_config.sample_rate.0, _channels));
-                                ao_writer.as_mut().unwrap().move_by(delay as usize);
-                                _current_sample = 0;
-                            }
-                            Ok(CallbackCommand::Timestamp(sender)) => {
-                                sender.send(Instant::now()).unwrap();
-                            }
-                            Ok(CallbackCommand::RemoveAudioObject) => {
-                                ao_writer = None;
+                        realtime::promote_audio_thread_once(&mut promoted, &realtime::OUTPUT_CALLBACK);
+
+                        // handle all new commands
+                        while let Ok(command) = callback_receiver.try_recv() {
+                            match command {
+                                CallbackCommand::SetAudioObject(audio_object, delay, control) => {
+                                    // the new audio object replaces the one that is currently playing
+                                    if let Some(previous) = ao_writer.as_ref() {
+                                        previous.control.set_finished();
+                                    }
+                                    let content_channels = match trigger {
+                                        Some(_) => _channels - 1,
+                                        None => _channels,
+                                    };
+                                    ao_writer = Some(
+                                        audio_object
+                                            .into_writer(_config.sample_rate.0, content_channels)
+                                            .with_control(control),
+                                    );
+                                    ao_writer.as_mut().unwrap().move_by(delay as usize);
+                                    _current_sample = 0;
+                                    // the pulse starts on the same sample as the sound
+                                    if let Some(trigger) = trigger.as_mut().filter(|t| t.config.pulse_on_play) {
+                                        trigger.pulse();
+                                    }
+                                }
+                                CallbackCommand::Timestamp(sender) => {
+                                    sender.send(Instant::now()).unwrap();
+                                }
+                                CallbackCommand::RemoveAudioObject => {
+                                    if let Some(previous) = ao_writer.take() {
+                                        previous.control.set_finished();
+                                    }
+                                }
+                                CallbackCommand::SetTrigger(config) => {
+                                    trigger = config.map(|config| TriggerState::new(config, _config.sample_rate.0));
+                                }
+                                CallbackCommand::Pulse => {
+                                    if let Some(trigger) = trigger.as_mut() {
+                                        trigger.pulse();
+                                    }
+                                }
                             }
-                            _ => {}
+                        }
+
+                        for sample in data.iter_mut() {
+                            *sample = T::from_sample(0.0);
                         }
                         if let Some(_ao_writer) = ao_writer.as_mut() {
-                            // write the audio object data
-                            let out = _ao_writer.write_data(data).unwrap();
+                            // write the audio object data, around the trigger channel if the sound
+                            // was started while a trigger channel was set
+                            let out = match trigger.as_ref() {
+                                Some(trigger) if _ao_writer.target_channels < _channels => {
+                                    let n_frames = data.len() / _channels;
+                                    content.clear();
+                                    content.resize(n_frames * _ao_writer.target_channels, T::from_sample(0.0));
+                                    let out = _ao_writer.write_data(&mut content).unwrap();
+                                    trigger::route(&content, data, _channels, trigger.config.channel);
+                                    out
+                                }
+                                _ => _ao_writer.write_data(data).unwrap(),
+                            };
                             if out {
                                 ao_writer = None;
                             }
-                        } else {
-                            for sample in data.iter_mut() {
-                                *sample = T::from_sample(0.0);
-                            }
+                        }
+                        if let Some(trigger) = trigger.as_mut() {
+                            trigger.write(data, _channels);
                         }
                     },
                     err_fn,
@@ -361,7 +554,7 @@ impl Stream {
                 .unwrap();
             stream.play().unwrap();
 
-            let scheudled_aos: Arc<Mutex<Vec<(AudioObject, Instant)>>> = Arc::new(Mutex::new(Vec::new()));
+            let scheudled_aos: Arc<Mutex<Vec<(Scheduled, Instant)>>> = Arc::new(Mutex::new(Vec::new()));
 
             // create another thread who's job is dispatching the audio objects at the right time
             // for this, it will iterate over the scheduled audio objects and check if they should be played
@@ -405,17 +598,25 @@ impl Stream {
                             // get the audio objects that should be played now
                             let now = Instant::now();
 
-                            scheudled_aos.retain(|(ao, t)| {
-                                if *t <= now {
+                            scheudled_aos.retain(|(item, t)| match item {
+                                Scheduled::Sound(_, control) if control.is_stopped() => {
+                                    // stopped before it started, drop it
+                                    control.set_finished();
+                                    false
+                                }
+                                Scheduled::Sound(ao, control) if *t <= now => {
                                     let safe_diff = now.checked_duration_since(*t).unwrap_or(Duration::MAX);
                                     println!("Playing audio object with latency of {:?}", safe_diff);
                                     _callback_sender
-                                        .send(CallbackCommand::SetAudioObject(ao.clone(), 0))
+                                        .send(CallbackCommand::SetAudioObject(ao.clone(), 0, control.clone()))
                                         .unwrap();
                                     false
-                                } else {
-                                    true
                                 }
+                                Scheduled::Pulse if *t <= now => {
+                                    _callback_sender.send(CallbackCommand::Pulse).unwrap();
+                                    false
+                                }
+                                _ => true,
                             });
                         }
                     }
@@ -425,19 +626,28 @@ impl Stream {
             // now start waiting for commands
             for command in command_receiver {
                 match command {
-                    StreamCommand::PlayNow(audio_object, _) => {
+                    StreamCommand::PlayNow(audio_object, _, control) => {
                         callback_sender
-                            .send(CallbackCommand::SetAudioObject(audio_object, 0))
+                            .send(CallbackCommand::SetAudioObject(audio_object, 0, control))
                             .unwrap();
                     }
-                    StreamCommand::PlayAt(audio_object, at, _) => {
+                    StreamCommand::PlayAt(audio_object, at, _, control) => {
                         println!(
                             "Scheduling audio object to be played at {:?} (now: {:?})",
                             at,
                             Instant::now()
                         );
                         let mut scheudled_aos = scheudled_aos.lock().unwrap();
-                        scheudled_aos.push((audio_object, at));
+                        scheudled_aos.push((Scheduled::Sound(audio_object, control), at));
+                    }
+                    StreamCommand::SetTrigger(config) => {
+                        callback_sender.send(CallbackCommand::SetTrigger(config)).unwrap();
+                    }
+                    StreamCommand::PulseNow => {
+                        callback_sender.send(CallbackCommand::Pulse).unwrap();
+                    }
+                    StreamCommand::PulseAt(at) => {
+                        scheudled_aos.lock().unwrap().push((Scheduled::Pulse, at));
                     }
                     StreamCommand::Stop => {
                         callback_sender.send(CallbackCommand::RemoveAudioObject).unwrap();
@@ -462,19 +672,108 @@ impl Stream {
             closed: false,
             command_sender,
             sample_rate: config.sample_rate.0,
+            latency_compensation: Arc::new(Mutex::new(None)),
+            trigger: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub fn play_now(&self, audio_object: AudioObject) {
+    pub fn play_now(&self, audio_object: AudioObject) -> PlaybackHandle {
+        let control = Arc::new(PlaybackControl::default());
         self.command_sender
-            .send(StreamCommand::PlayNow(audio_object, 0))
+            .send(StreamCommand::PlayNow(audio_object, 0, control.clone()))
             .unwrap();
+        PlaybackHandle::new(control, self.sample_rate)
+    }
+
+    /// Play the audio object so that it reaches the output at `at`. If a latency has been
+    /// measured (see `calibrate_latency`), the audio object is scheduled earlier to compensate.
+    pub fn play_at(&self, audio_object: AudioObject, at: Instant) -> PlaybackHandle {
+        let at = match self.measured_latency() {
+            Some(latency) => at.checked_sub(latency).unwrap_or(at),
+            None => at,
+        };
+        self.play_at_uncompensated(audio_object, at)
     }
 
-    pub fn play_at(&self, audio_object: AudioObject, at: Instant) {
+    fn play_at_uncompensated(&self, audio_object: AudioObject, at: Instant) -> PlaybackHandle {
+        let control = Arc::new(PlaybackControl::default());
         self.command_sender
-            .send(StreamCommand::PlayAt(audio_object, at, 0))
+            .send(StreamCommand::PlayAt(audio_object, at, 0, control.clone()))
             .unwrap();
+        PlaybackHandle::new(control, self.sample_rate)
+    }
+
+    /// The measured end-to-end latency that is used to compensate `play_at` scheduling, if any.
+    pub fn measured_latency(&self) -> Option<Duration> {
+        *self.latency_compensation.lock().unwrap()
+    }
+
+    /// Set (or clear) the latency that is used to compensate `play_at` scheduling.
+    pub fn set_latency_compensation(&self, latency: Option<Duration>) {
+        *self.latency_compensation.lock().unwrap() = latency;
+    }
+
+    /// Use (or stop using) an output channel for trigger pulses. While a trigger channel is set,
+    /// sounds that are started need one channel less than the stream (see `content_channels`)
+    /// and are played on the other channels.
+    pub fn set_trigger_channel(&self, trigger: Option<TriggerChannel>) -> anyhow::Result<()> {
+        if let Some(trigger) = &trigger {
+            let channels = self.cpal_config.channels as usize;
+            if channels < 2 {
+                return Err(anyhow::anyhow!(
+                    "A trigger channel requires an output device with at least two channels"
+                ));
+            }
+            if trigger.channel >= channels {
+                return Err(anyhow::anyhow!(
+                    "Trigger channel {} does not exist, the stream has {} channels",
+                    trigger.channel,
+                    channels
+                ));
+            }
+        }
+        *self.trigger.lock().unwrap() = trigger;
+        self.command_sender.send(StreamCommand::SetTrigger(trigger)).unwrap();
+        Ok(())
+    }
+
+    pub fn trigger_channel(&self) -> Option<TriggerChannel> {
+        *self.trigger.lock().unwrap()
+    }
+
+    /// The number of channels that sounds need to have, which excludes the trigger channel.
+    pub fn content_channels(&self) -> usize {
+        let channels = self.cpal_config.channels as usize;
+        match self.trigger_channel() {
+            Some(_) => channels - 1,
+            None => channels,
+        }
+    }
+
+    /// Emit a trigger pulse as soon as possible.
+    pub fn pulse_now(&self) -> anyhow::Result<()> {
+        self.require_trigger()?;
+        self.command_sender.send(StreamCommand::PulseNow).unwrap();
+        Ok(())
+    }
+
+    /// Emit a trigger pulse that reaches the output at `at`, compensated for the measured latency
+    /// like `play_at`. A pulse and a sound scheduled for the same time start on the same sample.
+    pub fn pulse_at(&self, at: Instant) -> anyhow::Result<()> {
+        self.require_trigger()?;
+        let at = match self.measured_latency() {
+            Some(latency) => at.checked_sub(latency).unwrap_or(at),
+            None => at,
+        };
+        self.command_sender.send(StreamCommand::PulseAt(at)).unwrap();
+        Ok(())
+    }
+
+    fn require_trigger(&self) -> anyhow::Result<()> {
+        match self.trigger_channel() {
+            Some(_) => Ok(()),
+            None => Err(anyhow::anyhow!("No trigger channel has been set")),
+        }
     }
 
     pub fn latency_samples(&self) -> Option<u32> {
@@ -494,3 +793,57 @@ impl Stream {
         self.cpal_config.sample_rate.0
     }
 }
+
+/// Build an input stream in the sample format of the device. The captured samples are passed to
+/// `data_callback` converted to `f32`.
+pub(crate) fn build_input_stream<D, E>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    data_callback: D,
+    error_callback: E,
+) -> anyhow::Result<cpal::Stream>
+where
+    D: FnMut(&[f32], &cpal::InputCallbackInfo) + Send + 'static,
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    fn build_typed<T, D, E>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+        D: FnMut(&[f32], &cpal::InputCallbackInfo) + Send + 'static,
+        E: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        // reused between callbacks, so that nothing is allocated once it has grown to the buffer size
+        let mut samples = Vec::new();
+        device.build_input_stream(
+            config,
+            move |data: &[T], info: &cpal::InputCallbackInfo| {
+                samples.clear();
+                samples.extend(data.iter().map(|sample| f32::from_sample(*sample)));
+                data_callback(&samples, info);
+            },
+            error_callback,
+            None,
+        )
+    }
+
+    let stream = match sample_format {
+        cpal::SampleFormat::I16 => build_typed::<i16, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::I32 => build_typed::<i32, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::I64 => build_typed::<i64, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::U8 => build_typed::<u8, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::U16 => build_typed::<u16, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::U32 => build_typed::<u32, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::U64 => build_typed::<u64, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::F32 => build_typed::<f32, _, _>(device, config, data_callback, error_callback),
+        cpal::SampleFormat::F64 => build_typed::<f64, _, _>(device, config, data_callback, error_callback),
+        sample_format => return Err(anyhow::anyhow!("Unsupported input sample format '{sample_format}'")),
+    }?;
+    Ok(stream)
+}
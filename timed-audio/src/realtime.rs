@@ -0,0 +1,405 @@
+use std::{
+    marker::PhantomData,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+};
+
+/// Whether audio callbacks should raise their thread priority when they first run.
+static AUDIO_REALTIME: AtomicBool = AtomicBool::new(false);
+/// Reports of all attempts to raise the priority of threads other than audio callbacks.
+static REPORTS: Mutex<Vec<PriorityReport>> = Mutex::new(Vec::new());
+
+/// The last attempts to raise the priority of the audio callback threads.
+pub(crate) static OUTPUT_CALLBACK: CallbackPromotion = CallbackPromotion::new("audio output callback");
+pub(crate) static INPUT_CALLBACK: CallbackPromotion = CallbackPromotion::new("audio input callback");
+
+/// The outcome of an attempt to raise the priority of a thread.
+#[derive(Debug, Clone)]
+pub struct PriorityReport {
+    /// The role of the thread, e.g. "audio output callback".
+    pub thread: String,
+    /// The mechanism that was used, e.g. "MMCSS (Pro Audio)" or "SCHED_FIFO".
+    pub method: &'static str,
+    /// Whether the priority was raised.
+    pub achieved: bool,
+    /// Additional information, e.g. the error if the priority could not be raised.
+    pub detail: String,
+}
+
+impl PriorityReport {
+    fn new(thread: &str, audio: bool, result: Result<(), i32>) -> Self {
+        Self {
+            thread: thread.to_string(),
+            method: method(audio),
+            achieved: result.is_ok(),
+            detail: match result {
+                Ok(()) => "ok".to_string(),
+                Err(code) => describe_error(code),
+            },
+        }
+    }
+}
+
+/// Opt in (or out) of raising the priority of audio callback threads.
+pub fn set_audio_realtime(enabled: bool) {
+    AUDIO_REALTIME.store(enabled, Ordering::Relaxed);
+}
+
+pub fn audio_realtime() -> bool {
+    AUDIO_REALTIME.load(Ordering::Relaxed)
+}
+
+/// Opts in (or out) of raising the priority of audio callback threads while it is alive, and
+/// restores the previous setting when it is dropped.
+#[derive(Debug)]
+pub struct AudioRealtimeGuard {
+    previous: bool,
+}
+
+impl AudioRealtimeGuard {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            previous: AUDIO_REALTIME.swap(enabled, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for AudioRealtimeGuard {
+    fn drop(&mut self) {
+        set_audio_realtime(self.previous);
+    }
+}
+
+/// All attempts to raise thread priorities so far.
+pub fn reports() -> Vec<PriorityReport> {
+    let mut reports = REPORTS.lock().unwrap().clone();
+    reports.extend(
+        [&OUTPUT_CALLBACK, &INPUT_CALLBACK]
+            .into_iter()
+            .filter_map(|callback| callback.report()),
+    );
+    reports
+}
+
+/// Keeps the priority of a thread raised while it is alive, and restores the previous priority
+/// when it is dropped. It has to be dropped on the thread it was created on.
+pub struct PriorityGuard {
+    previous: Option<Previous>,
+    // the priority belongs to the thread, so the guard must not be sent to another one
+    _thread: PhantomData<*const ()>,
+}
+
+impl PriorityGuard {
+    /// The outcome of the attempt to raise the priority.
+    pub fn achieved(&self) -> bool {
+        self.previous.is_some()
+    }
+}
+
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            if let Err(code) = restore(previous) {
+                log::warn!("Failed to restore the priority of a thread: {}", describe_error(code));
+            }
+        }
+    }
+}
+
+/// Raise the priority of the calling thread as far as the OS allows and record the outcome. The
+/// previous priority is restored when the returned guard is dropped.
+///
+/// Uses MMCSS on Windows ("Pro Audio" for audio threads, "Games" otherwise), the time-constraint
+/// policy (as used for audio threads) on macOS, and the real-time `SCHED_FIFO` policy on Linux,
+/// which may require elevated privileges (e.g. an `rtprio` limit). Only promote threads that block
+/// regularly (e.g. on vsync or on the audio device), as a real-time thread that keeps running can
+/// starve the rest of the system.
+pub fn promote_current_thread(thread: &str, audio: bool) -> PriorityGuard {
+    let previous = promote(audio);
+    let report = PriorityReport::new(thread, audio, previous.as_ref().map(|_| ()).map_err(|code| *code));
+
+    if !report.achieved {
+        log::warn!(
+            "Failed to raise the priority of the {} thread using {}: {}",
+            report.thread,
+            report.method,
+            report.detail
+        );
+    }
+    REPORTS.lock().unwrap().push(report);
+    PriorityGuard {
+        previous: previous.ok(),
+        _thread: PhantomData,
+    }
+}
+
+/// The outcome of raising the priority of an audio callback thread. It is recorded without locking,
+/// allocating, or printing, as that happens on the audio thread.
+pub(crate) struct CallbackPromotion {
+    thread: &'static str,
+    attempted: AtomicBool,
+    achieved: AtomicBool,
+    error: AtomicI32,
+}
+
+impl CallbackPromotion {
+    const fn new(thread: &'static str) -> Self {
+        Self {
+            thread,
+            attempted: AtomicBool::new(false),
+            achieved: AtomicBool::new(false),
+            error: AtomicI32::new(0),
+        }
+    }
+
+    fn record(&self, result: Result<(), i32>) {
+        self.achieved.store(result.is_ok(), Ordering::Relaxed);
+        self.error.store(result.err().unwrap_or(0), Ordering::Relaxed);
+        self.attempted.store(true, Ordering::Release);
+    }
+
+    fn report(&self) -> Option<PriorityReport> {
+        if !self.attempted.load(Ordering::Acquire) {
+            return None;
+        }
+        let result = match self.achieved.load(Ordering::Relaxed) {
+            true => Ok(()),
+            false => Err(self.error.load(Ordering::Relaxed)),
+        };
+        Some(PriorityReport::new(self.thread, true, result))
+    }
+}
+
+/// Promote the calling audio callback thread once if audio real-time priority has been requested.
+/// The priority is kept for the lifetime of the thread, which belongs to the audio stream.
+pub(crate) fn promote_audio_thread_once(promoted: &mut bool, callback: &CallbackPromotion) {
+    if !*promoted {
+        *promoted = true;
+        if audio_realtime() {
+            callback.record(promote(true).map(|_| ()));
+        }
+    }
+}
+
+/// How a thread was scheduled before it was promoted.
+#[cfg(target_os = "windows")]
+struct Previous(windows::Win32::Foundation::HANDLE);
+
+#[cfg(target_os = "windows")]
+fn method(audio: bool) -> &'static str {
+    match audio {
+        true => "MMCSS (Pro Audio)",
+        false => "MMCSS (Games)",
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn promote(audio: bool) -> Result<Previous, i32> {
+    use windows::Win32::System::Threading::AvSetMmThreadCharacteristicsW;
+    use windows::core::w;
+
+    let mut task_index = 0u32;
+    let task = match audio {
+        true => w!("Pro Audio"),
+        false => w!("Games"),
+    };
+    let result = unsafe { AvSetMmThreadCharacteristicsW(task, &mut task_index) };
+    result.map(Previous).map_err(|e| e.code().0)
+}
+
+#[cfg(target_os = "windows")]
+fn restore(previous: Previous) -> Result<(), i32> {
+    use windows::Win32::System::Threading::AvRevertMmThreadCharacteristics;
+
+    unsafe { AvRevertMmThreadCharacteristics(previous.0) }.map_err(|e| e.code().0)
+}
+
+#[cfg(target_os = "windows")]
+fn describe_error(code: i32) -> String {
+    windows::core::Error::from(windows::core::HRESULT(code)).to_string()
+}
+
+/// How a thread was scheduled before it was promoted: its time constraints, or `None` if it used
+/// the standard policy.
+#[cfg(target_os = "macos")]
+struct Previous(Option<libc::thread_time_constraint_policy>);
+
+#[cfg(target_os = "macos")]
+fn method(_audio: bool) -> &'static str {
+    "THREAD_TIME_CONSTRAINT_POLICY"
+}
+
+#[cfg(target_os = "macos")]
+fn promote(audio: bool) -> Result<Previous, i32> {
+    // the thread expects to run for `computation` out of every `period`, and to finish within
+    // `constraint`. Audio threads run for each buffer, the others (e.g. the present loop) for
+    // each frame
+    let (period_ms, computation_ms) = match audio {
+        true => (10, 5),
+        false => (16, 8),
+    };
+    let mut policy = libc::thread_time_constraint_policy {
+        period: ms_to_abs_time(period_ms),
+        computation: ms_to_abs_time(computation_ms),
+        constraint: ms_to_abs_time(period_ms),
+        preemptible: 1,
+    };
+
+    let thread = unsafe { libc::pthread_mach_thread_np(libc::pthread_self()) };
+    let previous = time_constraint_policy(thread)?;
+    let result = unsafe {
+        libc::thread_policy_set(
+            thread,
+            libc::THREAD_TIME_CONSTRAINT_POLICY as libc::thread_policy_flavor_t,
+            &mut policy as *mut _ as libc::thread_policy_t,
+            libc::THREAD_TIME_CONSTRAINT_POLICY_COUNT,
+        )
+    };
+    match result {
+        libc::KERN_SUCCESS => Ok(Previous(previous)),
+        code => Err(code),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn restore(previous: Previous) -> Result<(), i32> {
+    let thread = unsafe { libc::pthread_mach_thread_np(libc::pthread_self()) };
+    let result = match previous.0 {
+        Some(mut policy) => unsafe {
+            libc::thread_policy_set(
+                thread,
+                libc::THREAD_TIME_CONSTRAINT_POLICY as libc::thread_policy_flavor_t,
+                &mut policy as *mut _ as libc::thread_policy_t,
+                libc::THREAD_TIME_CONSTRAINT_POLICY_COUNT,
+            )
+        },
+        None => {
+            let mut policy = libc::thread_standard_policy { no_data: 0 };
+            unsafe {
+                libc::thread_policy_set(
+                    thread,
+                    libc::THREAD_STANDARD_POLICY as libc::thread_policy_flavor_t,
+                    &mut policy as *mut _ as libc::thread_policy_t,
+                    libc::THREAD_STANDARD_POLICY_COUNT as libc::mach_msg_type_number_t,
+                )
+            }
+        }
+    };
+    match result {
+        libc::KERN_SUCCESS => Ok(()),
+        code => Err(code),
+    }
+}
+
+/// The time constraints of a thread, or `None` if it uses the standard policy.
+#[cfg(target_os = "macos")]
+fn time_constraint_policy(thread: libc::thread_t) -> Result<Option<libc::thread_time_constraint_policy>, i32> {
+    let mut policy = libc::thread_time_constraint_policy {
+        period: 0,
+        computation: 0,
+        constraint: 0,
+        preemptible: 0,
+    };
+    let mut count = libc::THREAD_TIME_CONSTRAINT_POLICY_COUNT;
+    let mut get_default = 0;
+    let result = unsafe {
+        libc::thread_policy_get(
+            thread,
+            libc::THREAD_TIME_CONSTRAINT_POLICY as libc::thread_policy_flavor_t,
+            &mut policy as *mut _ as libc::thread_policy_t,
+            &mut count,
+            &mut get_default,
+        )
+    };
+    match (result, get_default) {
+        (libc::KERN_SUCCESS, 0) => Ok(Some(policy)),
+        (libc::KERN_SUCCESS, _) => Ok(None),
+        (code, _) => Err(code),
+    }
+}
+
+/// Convert milliseconds to Mach absolute time units.
+#[cfg(target_os = "macos")]
+#[allow(deprecated)]
+fn ms_to_abs_time(ms: u64) -> u32 {
+    let mut timebase = libc::mach_timebase_info { numer: 0, denom: 0 };
+    unsafe { libc::mach_timebase_info(&mut timebase) };
+    (ms * 1_000_000 * timebase.denom as u64 / timebase.numer.max(1) as u64) as u32
+}
+
+#[cfg(target_os = "macos")]
+fn describe_error(code: i32) -> String {
+    format!("kern_return_t {}", code)
+}
+
+/// How a thread was scheduled before it was promoted: its policy and parameters.
+#[cfg(all(unix, not(target_os = "macos")))]
+struct Previous(libc::c_int, libc::sched_param);
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn method(_audio: bool) -> &'static str {
+    "SCHED_FIFO"
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn promote(audio: bool) -> Result<Previous, i32> {
+    // audio threads preempt the present loop, which in turn preempts the rest of the system
+    let priority = match audio {
+        true => 70,
+        false => 60,
+    };
+
+    let thread = unsafe { libc::pthread_self() };
+    let mut policy = 0;
+    let mut param: libc::sched_param = unsafe { std::mem::zeroed() };
+    match unsafe { libc::pthread_getschedparam(thread, &mut policy, &mut param) } {
+        0 => {}
+        code => return Err(code),
+    }
+
+    let mut fifo: libc::sched_param = unsafe { std::mem::zeroed() };
+    fifo.sched_priority = priority.min(unsafe { libc::sched_get_priority_max(libc::SCHED_FIFO) });
+    match unsafe { libc::pthread_setschedparam(thread, libc::SCHED_FIFO, &fifo) } {
+        0 => Ok(Previous(policy, param)),
+        code => Err(code),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn restore(previous: Previous) -> Result<(), i32> {
+    let Previous(policy, param) = previous;
+    match unsafe { libc::pthread_setschedparam(libc::pthread_self(), policy, &param) } {
+        0 => Ok(()),
+        code => Err(code),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn describe_error(code: i32) -> String {
+    std::io::Error::from_raw_os_error(code).to_string()
+}
+
+#[cfg(not(any(target_os = "windows", unix)))]
+struct Previous;
+
+#[cfg(not(any(target_os = "windows", unix)))]
+fn method(_audio: bool) -> &'static str {
+    "none"
+}
+
+#[cfg(not(any(target_os = "windows", unix)))]
+fn promote(_audio: bool) -> Result<Previous, i32> {
+    Err(0)
+}
+
+#[cfg(not(any(target_os = "windows", unix)))]
+fn restore(_previous: Previous) -> Result<(), i32> {
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", unix)))]
+fn describe_error(_code: i32) -> String {
+    "not supported on this platform".to_string()
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use cpal::{FromSample, Sample};
+
+/// A dedicated output channel that carries square-wave trigger pulses instead of audio, e.g. to
+/// feed the trigger input of an EEG amplifier when no parallel port is available. Sounds are
+/// routed to the remaining channels, and pulses are mixed in sample-accurately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerChannel {
+    /// The index of the output channel.
+    pub channel: usize,
+    /// The level of the pulse, as a linear sample value.
+    pub amplitude: f32,
+    /// The length of each pulse.
+    pub width: Duration,
+    /// Whether a pulse is emitted on the first sample of every sound that is played.
+    pub pulse_on_play: bool,
+}
+
+/// The state of the trigger channel inside the audio callback.
+#[derive(Debug)]
+pub(crate) struct TriggerState {
+    pub(crate) config: TriggerChannel,
+    width_frames: u32,
+    // frames left of the current pulse
+    remaining: u32,
+}
+
+impl TriggerState {
+    pub(crate) fn new(config: TriggerChannel, sample_rate: u32) -> Self {
+        let width_frames = (config.width.as_secs_f64() * sample_rate as f64).round().max(1.0) as u32;
+        Self {
+            config,
+            width_frames,
+            remaining: 0,
+        }
+    }
+
+    /// Start a pulse on the first frame of the next buffer. A pulse that is still running is
+    /// restarted.
+    pub(crate) fn pulse(&mut self) {
+        self.remaining = self.width_frames;
+    }
+
+    /// Write the trigger channel of `output`, which has `channels` interleaved channels.
+    pub(crate) fn write<T>(&mut self, output: &mut [T], channels: usize)
+    where
+        T: Sample + FromSample<f32>,
+    {
+        for frame in output.chunks_mut(channels) {
+            let value = if self.remaining > 0 {
+                self.remaining -= 1;
+                self.config.amplitude
+            } else {
+                0.0
+            };
+            if let Some(sample) = frame.get_mut(self.config.channel) {
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+}
+
+/// Copy `content` (with one channel less than `output`) into all channels of `output` except
+/// `skip`.
+pub(crate) fn route<T: Copy>(content: &[T], output: &mut [T], channels: usize, skip: usize) {
+    for (source, target) in content.chunks(channels - 1).zip(output.chunks_mut(channels)) {
+        let mut source = source.iter();
+        for (j, sample) in target.iter_mut().enumerate() {
+            if j != skip {
+                if let Some(value) = source.next() {
+                    *sample = *value;
+                }
+            }
+        }
+    }
+}